@@ -5,7 +5,7 @@ use iris_core::conntrack::{
     TrackedActions,
 };
 use iris_core::filter::{
-    ast::{BinOp, FieldName, ProtocolName, Value},
+    ast::{ArithOp, BinOp, Expr, ExprFn, FieldName, ProtocolName, Value},
     subscription::{CallbackSpec, DataActions},
 };
 use proc_macro2::{Ident, Span};
@@ -41,6 +41,8 @@ pub(crate) fn cb_to_tokens(
         false => cb_name,
     };
     let cb_name_ident = Ident::new(&cb_name, Span::call_site());
+    let sample = sub.sample_rate(cb_group.map_or(cb_name, |grp| grp.as_str()));
+    let budget_id = sub.budget_id(cb_group.map_or(cb_name, |grp| grp.as_str()));
 
     let invoke = match cb_group {
         Some(grp) => {
@@ -85,9 +87,20 @@ pub(crate) fn cb_to_tokens(
         }
     };
 
+    // Budgeted CBs are skipped once they exceed their rate or
+    // if the connection was shed
+    let invoke = match budget_id {
+        Some(id) => quote! {
+            if iris_core::subscription::budget::admit_callback(#id, &mut conn.admitted) {
+                #invoke
+            }
+        },
+        None => invoke,
+    };
+
     // Invoking this CB requires checking that it
     // hasn't already been invoked
-    match invoke_once {
+    let invoke = match invoke_once {
         true => {
             let cb_wrapper_ident = Ident::new(&cb_wrapper_str, Span::call_site());
             quote! {
@@ -98,6 +111,16 @@ pub(crate) fn cb_to_tokens(
             }
         }
         false => quote! { #invoke },
+    };
+
+    // Sampled subscriptions are only invoked on their sample of connections
+    match sample {
+        Some(rate) if rate > 1 => quote! {
+            if conn.sample.is_sampled(#rate) {
+                #invoke
+            }
+        },
+        _ => invoke,
     }
 }
 
@@ -324,6 +347,7 @@ pub(crate) fn builtin_to_tokens(name: &String) -> proc_macro2::TokenStream {
         "Session" => quote! { conn.layers[0].last_session() },
         "SessionProto" => quote! { &conn.layers[0].last_protocol() },
         "CoreId" => quote! { &conn.tracked.core_id },
+        "ConnSample" => quote! { &conn.sample },
         _ => panic!("Unknown builtin datatype: {}", name),
     }
 }
@@ -482,6 +506,38 @@ pub(crate) fn parsers_to_tokens(sub: &SubscriptionDecoder) -> proc_macro2::Token
     }
 }
 
+/// Budgets of subscriptions that limit their load, in the order of their IDs.
+pub(crate) fn budgets_to_tokens(sub: &SubscriptionDecoder) -> proc_macro2::TokenStream {
+    let budgets = sub.budgets().into_iter().map(|(name, budget)| {
+        let name = LitStr::new(name, Span::call_site());
+        let max_rate = match budget.max_rate {
+            Some(rate) => quote! { Some(#rate) },
+            None => quote! { None },
+        };
+        let max_conns = match budget.max_conns {
+            Some(conns) => quote! { Some(#conns) },
+            None => quote! { None },
+        };
+        let priority = Ident::new(
+            &budget
+                .priority
+                .as_deref()
+                .unwrap_or("normal")
+                .to_camel_case(),
+            Span::call_site(),
+        );
+        quote! {
+            iris_core::subscription::budget::Budget {
+                subscription: #name,
+                max_rate: #max_rate,
+                max_conns: #max_conns,
+                priority: iris_core::subscription::budget::Priority::#priority,
+            }
+        }
+    });
+    quote! { &[ #( #budgets ),* ] }
+}
+
 /// Generates tokens for streaming updates to all tracked datatypes, callbacks,
 /// and filters that need to be invoked within each streaming state.
 /// This invokes the `update` for each.
@@ -542,9 +598,9 @@ pub(crate) fn fil_callback_to_tokens(
     // "try set active" will set the CB as "matched" unless it has
     // already unsubscribed
     if let Some(grp) = group {
-        let wrapper = Ident::new(&grp.to_lowercase(), Span::call_site());
+        let set_active = set_active_to_tokens(sub, grp);
         invoke = quote! {
-            conn.tracked.#wrapper.try_set_active();
+            #set_active
             #invoke
         };
     }
@@ -552,13 +608,28 @@ pub(crate) fn fil_callback_to_tokens(
     invoke
 }
 
-pub(crate) fn cb_set_active_to_tokens(spec: &CallbackSpec) -> proc_macro2::TokenStream {
+pub(crate) fn cb_set_active_to_tokens(
+    sub: &SubscriptionDecoder,
+    spec: &CallbackSpec,
+) -> proc_macro2::TokenStream {
     assert!(
         spec.is_streaming() || &spec.subscription_id != &spec.as_str,
         "Setting CB as matched should only happen for streaming or multi-function CBs"
     );
-    let wrapper = Ident::new(&spec.subscription_id.to_lowercase(), Span::call_site());
-    quote! { conn.tracked.#wrapper.try_set_active(); }
+    set_active_to_tokens(sub, &spec.subscription_id)
+}
+
+// Connections only become active for a budgeted CB if they are admitted
+fn set_active_to_tokens(sub: &SubscriptionDecoder, grp: &String) -> proc_macro2::TokenStream {
+    let wrapper = Ident::new(&grp.to_lowercase(), Span::call_site());
+    match sub.budget_id(grp) {
+        Some(id) => quote! {
+            if iris_core::subscription::budget::admit_conn(#id, &mut conn.admitted) {
+                conn.tracked.#wrapper.try_set_active();
+            }
+        },
+        None => quote! { conn.tracked.#wrapper.try_set_active(); },
+    }
 }
 
 /// Custom streaming (stateful or stateless) predicate to tokens
@@ -817,6 +888,99 @@ pub(crate) fn binary_to_tokens(
     }
 }
 
+// Comparisons between expressions over protocol fields
+pub(crate) fn compare_to_tokens(
+    lhs: &Expr,
+    op: &BinOp,
+    rhs: &Expr,
+    statics: &mut HashMap<String, (String, proc_macro2::TokenStream)>,
+) -> proc_macro2::TokenStream {
+    // Fields compared to integer expressions are widened to u64
+    let as_int = lhs.is_int() || rhs.is_int();
+    let lhs_tokens = expr_to_tokens(lhs, as_int);
+
+    if let Expr::Text(text) = rhs {
+        let val_lit = syn::LitStr::new(text, Span::call_site());
+        match *op {
+            BinOp::Re => {
+                if Regex::new(text).is_err() {
+                    panic!("Invalid Regex string: {}", text);
+                }
+                let kind = quote! { regex::Regex };
+                let re_ident = static_ident_re(statics, text, val_lit, kind);
+                return quote! { #re_ident.is_match(&#lhs_tokens[..]) };
+            }
+            BinOp::Contains => {
+                let finder_ident =
+                    static_ident_memchr(statics, text, quote! { #val_lit.as_bytes() });
+                return quote! { #finder_ident.find(#lhs_tokens.as_bytes()).is_some() };
+            }
+            BinOp::NotContains => {
+                let finder_ident =
+                    static_ident_memchr(statics, text, quote! { #val_lit.as_bytes() });
+                return quote! { #finder_ident.find(#lhs_tokens.as_bytes()).is_none() };
+            }
+            _ => {}
+        }
+    }
+
+    let rhs_tokens = expr_to_tokens(rhs, as_int);
+    match *op {
+        BinOp::Eq => quote! { #lhs_tokens == #rhs_tokens },
+        BinOp::Ne => quote! { #lhs_tokens != #rhs_tokens },
+        BinOp::Ge => quote! { #lhs_tokens >= #rhs_tokens },
+        BinOp::Le => quote! { #lhs_tokens <= #rhs_tokens },
+        BinOp::Gt => quote! { #lhs_tokens > #rhs_tokens },
+        BinOp::Lt => quote! { #lhs_tokens < #rhs_tokens },
+        _ => panic!(
+            "Invalid binary operation `{}` for comparison: `{} {} {}`.",
+            op, lhs, op, rhs
+        ),
+    }
+}
+
+// Integer arithmetic saturates; division or remainder by zero evaluates to 0.
+// See `compare` in core/src/filter/grammar.pest.
+fn expr_to_tokens(expr: &Expr, as_int: bool) -> proc_macro2::TokenStream {
+    match expr {
+        Expr::Field { protocol, field } => {
+            let proto = Ident::new(protocol.name(), Span::call_site());
+            let field = Ident::new(field.name(), Span::call_site());
+            match as_int {
+                true => quote! { (#proto.#field() as u64) },
+                false => quote! { #proto.#field() },
+            }
+        }
+        Expr::Int(val) => {
+            let val_lit = syn::LitInt::new(&format!("{}u64", val), Span::call_site());
+            quote! { #val_lit }
+        }
+        Expr::Text(text) => {
+            let val_lit = syn::LitStr::new(text, Span::call_site());
+            quote! { #val_lit }
+        }
+        Expr::Call { func, arg } => {
+            let arg = expr_to_tokens(arg, false);
+            match func {
+                ExprFn::Len => quote! { (#arg.len() as u64) },
+                ExprFn::Lower => quote! { #arg.to_lowercase() },
+                ExprFn::Count => quote! { (#arg.iter().count() as u64) },
+            }
+        }
+        Expr::Arith { lhs, op, rhs } => {
+            let lhs = expr_to_tokens(lhs, true);
+            let rhs = expr_to_tokens(rhs, true);
+            match op {
+                ArithOp::Add => quote! { #lhs.saturating_add(#rhs) },
+                ArithOp::Sub => quote! { #lhs.saturating_sub(#rhs) },
+                ArithOp::Mul => quote! { #lhs.saturating_mul(#rhs) },
+                ArithOp::Div => quote! { #lhs.checked_div(#rhs).unwrap_or(0) },
+                ArithOp::Rem => quote! { #lhs.checked_rem(#rhs).unwrap_or(0) },
+            }
+        }
+    }
+}
+
 fn static_ident_re(
     statics: &mut HashMap<String, (String, proc_macro2::TokenStream)>,
     text: &String,
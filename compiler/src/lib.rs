@@ -101,6 +101,16 @@ pub fn filter_group(args: TokenStream, input: TokenStream) -> TokenStream {
     .into()
 }
 
+#[proc_macro_attribute]
+pub fn filter_def(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut def = parse_macro_input!(args as FilterDefSpec);
+    def.validate().unwrap();
+    let spec = ParsedInput::FilterDef(def);
+    println!("Parsed named filter: {}", spec.name());
+    cache::push_input(spec);
+    input
+}
+
 #[proc_macro_attribute]
 pub fn cache_file(args: TokenStream, input: TokenStream) -> TokenStream {
     let fp = parse_macro_input!(args as syn::LitStr);
@@ -141,10 +151,12 @@ pub fn iris_main(_args: TokenStream, input: TokenStream) -> TokenStream {
     let tracked_new = codegen::tracked_new_to_tokens(&decoder);
     let tracked_update = codegen::tracked_update_to_tokens(&decoder);
     let parsers = codegen::parsers_to_tokens(&decoder);
+    let budgets = codegen::budgets_to_tokens(&decoder);
 
     let packet_tree = decoder.get_packet_filter_tree();
     let packet_filter = packet_filter::gen_packet_filter(&packet_tree);
     let filter_str = packet_tree.to_filter_string();
+    let filter_marks = packet_filter::gen_packet_marks(&packet_tree);
 
     let mut statics: HashMap<String, (String, proc_macro2::TokenStream)> = HashMap::new();
     let (state_tx_main, state_fns) = state_filters::gen_state_filters(&decoder, &mut statics);
@@ -245,6 +257,8 @@ pub fn iris_main(_args: TokenStream, input: TokenStream) -> TokenStream {
                 state_tx,
                 update
             )
+            .with_marks(#filter_marks)
+            .with_budgets(#budgets)
         }
 
         #input
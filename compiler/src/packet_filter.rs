@@ -3,7 +3,7 @@ use std::collections::HashMap;
 /// Generate code for the filter applied to every packet that hits an RX core.
 /// This returns `true` if a packet should continue to the connection tracker
 /// and `false` otherwise.
-use crate::codegen::binary_to_tokens;
+use crate::codegen::{binary_to_tokens, compare_to_tokens};
 use heck::CamelCase;
 use iris_core::filter::ast::*;
 use iris_core::filter::pred_ptree::{PredPNode, PredPTree};
@@ -36,7 +36,10 @@ pub(crate) fn gen_packet_filter(ptree: &PredPTree) -> proc_macro2::TokenStream {
     let outer = Ident::new("ethernet", Span::call_site());
     let outer_type = Ident::new(&outer.to_string().to_camel_case(), Span::call_site());
 
+    let mark_dispatch = gen_mark_dispatch(ptree);
+
     quote! {
+        #mark_dispatch
         if let Ok(#outer) = &iris_core::protocols::packet::Packet::parse_to::<iris_core::protocols::packet::#outer::#outer_type>(mbuf) {
             #( #body )*
         }
@@ -44,6 +47,101 @@ pub(crate) fn gen_packet_filter(ptree: &PredPTree) -> proc_macro2::TokenStream {
     }
 }
 
+/// Generate the (node ID, filter string) pairs that hardware rules may
+/// MARK packets with. Each ID corresponds to an arm of the mark dispatch.
+pub(crate) fn gen_packet_marks(ptree: &PredPTree) -> proc_macro2::TokenStream {
+    if !ptree.deliver.is_empty() {
+        return quote! { &[] };
+    }
+    let marks = ptree.to_path_strings().into_iter().map(|(id, filter_str)| {
+        let id = id as u32;
+        quote! { (#id, #filter_str) }
+    });
+    quote! { &[ #( #marks ),* ] }
+}
+
+/// Generate code that resumes the packet filter from the node a hardware
+/// rule MARKed the packet with. A marked packet already satisfies every
+/// predicate on the path to that node, so terminal nodes match immediately
+/// and other nodes only evaluate their subtree. If the subtree does not
+/// match, the full filter still runs, as the packet may match another branch.
+fn gen_mark_dispatch(ptree: &PredPTree) -> proc_macro2::TokenStream {
+    if !ptree.deliver.is_empty() || ptree.root.children.is_empty() {
+        return quote! {};
+    }
+    let mut terminal = vec![];
+    let mut arms = vec![];
+    gen_mark_arms(&ptree.root, &mut vec![], ptree, &mut terminal, &mut arms);
+
+    let terminal_arm = match terminal.is_empty() {
+        true => quote! {},
+        false => quote! { #( #terminal )|* => return true, },
+    };
+    quote! {
+        if let Some(mark) = mbuf.flow_mark() {
+            match mark {
+                #terminal_arm
+                #( #arms )*
+                _ => {}
+            }
+        }
+    }
+}
+
+fn gen_mark_arms(
+    node: &PredPNode,
+    protocols: &mut Vec<ProtocolName>,
+    tree: &PredPTree,
+    terminal: &mut Vec<u32>,
+    arms: &mut Vec<proc_macro2::TokenStream>,
+) {
+    for child in node.children.iter() {
+        let id = child.id as u32;
+        let is_unary = matches!(child.pred, Predicate::Unary { .. });
+        if is_unary {
+            protocols.push(child.pred.get_protocol().clone());
+        }
+        if child.is_terminal {
+            terminal.push(id);
+        } else {
+            let mut body: Vec<proc_macro2::TokenStream> = vec![];
+            gen_packet_filter_util(&mut body, child, tree);
+            let body = parse_path(protocols, quote! { #( #body )* });
+            arms.push(quote! { #id => { #body } });
+        }
+        gen_mark_arms(child, protocols, tree, terminal, arms);
+        if is_unary {
+            protocols.pop();
+        }
+    }
+}
+
+// Wraps `body` in the header parsing for each protocol on a path
+fn parse_path(
+    protocols: &[ProtocolName],
+    body: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut code = body;
+    for (i, protocol) in protocols.iter().enumerate().rev() {
+        let outer = match i {
+            0 => Ident::new("ethernet", Span::call_site()),
+            _ => Ident::new(protocols[i - 1].name(), Span::call_site()),
+        };
+        let ident = Ident::new(protocol.name(), Span::call_site());
+        let ident_type = Ident::new(&ident.to_string().to_camel_case(), Span::call_site());
+        code = quote! {
+            if let Ok(#ident) = &iris_core::protocols::packet::Packet::parse_to::<iris_core::protocols::packet::#ident::#ident_type>(#outer) {
+                #code
+            }
+        };
+    }
+    quote! {
+        if let Ok(ethernet) = &iris_core::protocols::packet::Packet::parse_to::<iris_core::protocols::packet::ethernet::Ethernet>(mbuf) {
+            #code
+        }
+    }
+}
+
 fn gen_packet_filter_util(
     code: &mut Vec<proc_macro2::TokenStream>,
     node: &PredPNode,
@@ -69,7 +167,16 @@ fn gen_packet_filter_util(
                 op,
                 value,
             } => {
-                add_binary_pred(code, child, protocol, field, op, value, tree);
+                let mut statics = HashMap::new();
+                let pred_tokenstream = binary_to_tokens(protocol, field, op, value, &mut statics);
+                assert!(statics.is_empty());
+                add_binary_pred(code, child, pred_tokenstream, tree);
+            }
+            Predicate::Compare { lhs, op, rhs, .. } => {
+                let mut statics = HashMap::new();
+                let pred_tokenstream = compare_to_tokens(lhs, op, rhs, &mut statics);
+                assert!(statics.is_empty());
+                add_binary_pred(code, child, pred_tokenstream, tree);
             }
             _ => panic!("Unexpected predicate in packet filter: {:?}", child.pred),
         }
@@ -125,18 +232,12 @@ fn add_unary_pred(
 fn add_binary_pred(
     code: &mut Vec<proc_macro2::TokenStream>,
     node: &PredPNode,
-    protocol: &ProtocolName,
-    field: &FieldName,
-    op: &BinOp,
-    value: &Value,
+    pred_tokenstream: proc_macro2::TokenStream,
     tree: &PredPTree,
 ) {
     let mut body: Vec<proc_macro2::TokenStream> = vec![];
     gen_packet_filter_util(&mut body, node, tree);
     update_body(&mut body, node, tree);
-    let mut statics = HashMap::new();
-    let pred_tokenstream = binary_to_tokens(protocol, field, op, value, &mut statics);
-    assert!(statics.is_empty());
     code.push(quote! {
         if #pred_tokenstream {
             #( #body )*
@@ -1,13 +1,15 @@
 use anyhow::{bail, Result};
 use iris_core::conntrack::DataLevel;
+use iris_core::filter::ast::ProtocolName;
 use iris_core::protocols::stream::IMPLEMENTED_PROTOCOLS;
+use iris_core::subscription::budget::Priority;
 use quote::ToTokens;
 use serde::{Deserialize, Serialize};
 use std::io::BufRead;
 use std::str::FromStr;
 use syn::{
     parse::{Parse, ParseStream},
-    Item, ItemFn, LitStr, TypePath,
+    Ident, Item, ItemFn, LitStr, Token, TypePath,
 };
 use thiserror::Error;
 
@@ -37,6 +39,20 @@ pub(crate) struct FnSpec {
     pub(crate) returns: FnReturn,
 }
 
+/// Load limits declared by a callback or callback group.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub(crate) struct BudgetSpec {
+    pub(crate) max_rate: Option<u32>,
+    pub(crate) max_conns: Option<usize>,
+    pub(crate) priority: Option<String>,
+}
+
+impl BudgetSpec {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 /// Spec generated by #[callback] tag on a function.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub(crate) struct CallbackFnSpec {
@@ -44,6 +60,10 @@ pub(crate) struct CallbackFnSpec {
     pub(crate) level: Vec<DataLevel>,
     pub(crate) func: FnSpec,
     pub(crate) expl_parsers: Vec<String>,
+    #[serde(default)]
+    pub(crate) sample: Option<u32>,
+    #[serde(default)]
+    pub(crate) budget: BudgetSpec,
 }
 
 /// Spec generated by #[callback] tag on a struct.
@@ -53,6 +73,10 @@ pub(crate) struct CallbackGroupSpec {
     pub(crate) level: Option<DataLevel>,
     pub(crate) name: String,
     pub(crate) expl_parsers: Vec<String>,
+    #[serde(default)]
+    pub(crate) sample: Option<u32>,
+    #[serde(default)]
+    pub(crate) budget: BudgetSpec,
 }
 
 /// Spec generated by #[callback_group]
@@ -87,6 +111,13 @@ pub(crate) struct FilterGroupFnSpec {
     pub(crate) func: FnSpec,
 }
 
+/// Spec generated by #[filter_def]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub(crate) struct FilterDefSpec {
+    pub(crate) name: String,
+    pub(crate) filter: String,
+}
+
 /// Spec generated by #[datatype]
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub(crate) struct DatatypeSpec {
@@ -113,6 +144,7 @@ pub(crate) enum ParsedInput {
     Filter(FilterFnSpec),
     FilterGroup(FilterGroupSpec),
     FilterGroupFn(FilterGroupFnSpec),
+    FilterDef(FilterDefSpec),
     Datatype(DatatypeSpec),
     DatatypeFn(DatatypeFnSpec),
 }
@@ -201,7 +233,8 @@ impl ParsedInput {
                     // Requires filter. Can specify levels.
                     // Streaming CBs return bool (TODO loosen this requirement in future)
                     Self::Callback(func) => {
-                        let (mut fil, level, expl_parsers) = InputKeys::callback(args, &spec.name)?;
+                        let (mut fil, level, expl_parsers, sample, budget) =
+                            InputKeys::callback(args, &spec.name)?;
                         if !matches!(spec.returns, FnReturn::Bool | FnReturn::None) {
                             bail!(ParserError::InvalidReturn(spec.name));
                         }
@@ -217,6 +250,8 @@ impl ParsedInput {
                         func.level = level;
                         func.func = spec;
                         func.expl_parsers = expl_parsers;
+                        func.sample = sample;
+                        func.budget = budget;
                     }
                     // Expecting: callback group func annotated with #[callback_group] macro
                     // Requires named callback group and (optional) levels.
@@ -333,7 +368,7 @@ impl ParsedInput {
                 let name = st.ident.to_string();
                 match self {
                     Self::Callback(_) => {
-                        let (mut filter, mut level, expl_parsers) =
+                        let (mut filter, mut level, expl_parsers, sample, budget) =
                             InputKeys::callback(args, &name)?;
                         let level = match level.len() {
                             0 => None,
@@ -351,10 +386,12 @@ impl ParsedInput {
                             level,
                             name,
                             expl_parsers,
+                            sample,
+                            budget,
                         });
                     }
                     Self::CallbackGroup(cb) => {
-                        let (mut filter, mut level, expl_parsers) =
+                        let (mut filter, mut level, expl_parsers, sample, budget) =
                             InputKeys::callback(args, &name)?;
                         cb.level = match level.len() {
                             0 => None,
@@ -370,6 +407,8 @@ impl ParsedInput {
                         cb.filter = filter;
                         cb.name = name;
                         cb.expl_parsers = expl_parsers;
+                        cb.sample = sample;
+                        cb.budget = budget;
                     }
                     Self::Filter(_) => {
                         let struct_def = InputKeys::struct_def(args, &name)?;
@@ -422,6 +461,7 @@ impl ParsedInput {
             Self::Filter(i) => &i.func.name,
             Self::FilterGroup(i) => &i.name,
             Self::FilterGroupFn(i) => &i.func.name,
+            Self::FilterDef(i) => &i.name,
             Self::Datatype(i) => &i.name,
             Self::DatatypeFn(i) => &i.func.name,
         }
@@ -441,6 +481,7 @@ impl ParsedInput {
                 None => vec![],
             },
             Self::FilterGroupFn(i) => i.level.clone(),
+            Self::FilterDef(_) => vec![],
             Self::Datatype(i) => match i.level {
                 Some(l) => vec![l],
                 None => vec![],
@@ -457,6 +498,28 @@ impl ParsedInput {
         )
     }
 
+    /// Per-subscription sampling rate (one in `rate` connections), if any.
+    pub(crate) fn sample(&self) -> Option<u32> {
+        match self {
+            Self::Callback(i) => i.sample,
+            Self::CallbackGroup(i) => i.sample,
+            _ => None,
+        }
+    }
+
+    /// Load limits declared by a callback or callback group, if any.
+    pub(crate) fn budget(&self) -> Option<&BudgetSpec> {
+        let budget = match self {
+            Self::Callback(i) => &i.budget,
+            Self::CallbackGroup(i) => &i.budget,
+            _ => return None,
+        };
+        match budget.is_empty() {
+            true => None,
+            false => Some(budget),
+        }
+    }
+
     pub(crate) fn expl_parsers(&self) -> Vec<String> {
         match self {
             Self::Callback(i) => i.expl_parsers.clone(),
@@ -465,6 +528,7 @@ impl ParsedInput {
             Self::Filter(i) => i.expl_parsers.clone(),
             Self::FilterGroup(i) => i.expl_parsers.clone(),
             Self::FilterGroupFn(_) => vec![],
+            Self::FilterDef(_) => vec![],
             Self::Datatype(i) => i.expl_parsers.clone(),
             Self::DatatypeFn(_) => vec![],
         }
@@ -479,17 +543,91 @@ impl ParsedInput {
             Self::Filter(_) => None,
             Self::FilterGroup(i) => Some(i.name.clone()),
             Self::FilterGroupFn(i) => Some(i.group_name.clone()),
+            Self::FilterDef(_) => None,
             Self::Datatype(i) => Some(i.name.clone()),
             Self::DatatypeFn(i) => Some(i.group_name.clone()),
         }
     }
 }
 
+/// Words that cannot be used as the name of a filter definition.
+const RESERVED_WORDS: &[&str] = &[
+    "or", "OR", "and", "AND", "not", "NOT", "ne", "ge", "le", "gt", "lt", "in", "matches", "eq",
+    "contains", "len", "lower", "count",
+];
+
+impl FilterDefSpec {
+    /// Checks that the name can be referenced from other filters without shadowing a protocol or
+    /// operator, and expands `file=` in the filter.
+    pub(crate) fn validate(&mut self) -> Result<()> {
+        let is_ident = self.name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_ident
+            || RESERVED_WORDS.contains(&self.name.as_str())
+            || ProtocolName(self.name.clone()).is_supported()
+        {
+            bail!(ParserError::InvalidValue("name".into(), self.name.clone()));
+        }
+        if self.filter.contains("file=") {
+            self.filter = InputKeys::parse_filters_from_file(&self.filter)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the arguments to #[filter_def], e.g., `name = "web", "tls or http or quic"`. The
+/// filter may also be labeled (`filter = "..."`).
+impl Parse for FilterDefSpec {
+    fn parse(input: ParseStream) -> syn::parse::Result<Self> {
+        let mut spec = FilterDefSpec::default();
+        while !input.is_empty() {
+            if input.peek(LitStr) {
+                spec.filter = input.parse::<LitStr>()?.value();
+            } else {
+                let key: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+                let value = input.parse::<LitStr>()?.value();
+                match key.to_string().as_str() {
+                    "name" => spec.name = value,
+                    "filter" => spec.filter = value,
+                    k => {
+                        return Err(syn::Error::new(
+                            key.span(),
+                            ParserError::InvalidKey(k.to_string()),
+                        ))
+                    }
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        if spec.name.is_empty() {
+            return Err(syn::Error::new(
+                input.span(),
+                ParserError::MissingParam("name".into(), "filter_def".into()),
+            ));
+        }
+        if spec.filter.is_empty() {
+            return Err(syn::Error::new(
+                input.span(),
+                ParserError::MissingParam("filter".into(), spec.name.clone()),
+            ));
+        }
+        Ok(spec)
+    }
+}
+
 #[derive(Default, Clone)]
 pub(crate) struct InputKeys {
     first: Option<String>,
     levels: Vec<DataLevel>,
     parsers: Vec<String>,
+    sample: Option<u32>,
+    budget: BudgetSpec,
 }
 
 impl InputKeys {
@@ -562,6 +700,26 @@ impl InputKeys {
                     })
                     .collect::<Vec<_>>();
                 ret.parsers.extend(v.iter().map(|s| s.trim().to_string()));
+            } else if k.contains("sample") {
+                match u32::from_str(&v) {
+                    Ok(rate) if rate > 0 => ret.sample = Some(rate),
+                    _ => bail!(ParserError::InvalidValue(k, v)),
+                }
+            } else if k.contains("max_rate") {
+                match u32::from_str(&v) {
+                    Ok(rate) => ret.budget.max_rate = Some(rate),
+                    Err(_) => bail!(ParserError::InvalidValue(k, v)),
+                }
+            } else if k.contains("max_conns") {
+                match usize::from_str(&v) {
+                    Ok(conns) => ret.budget.max_conns = Some(conns),
+                    Err(_) => bail!(ParserError::InvalidValue(k, v)),
+                }
+            } else if k.contains("priority") {
+                if Priority::from_str(&v).is_err() {
+                    bail!(ParserError::InvalidValue(k, v));
+                }
+                ret.budget.priority = Some(v.to_lowercase());
             } else {
                 bail!(ParserError::InvalidKey(k));
             }
@@ -594,6 +752,10 @@ impl InputKeys {
             || filter.contains("reassembled=")
             || filter.contains("name=")
             || filter.contains("parsers=")
+            || filter.contains("sample=")
+            || filter.contains("max_rate=")
+            || filter.contains("max_conns=")
+            || filter.contains("priority=")
     }
 
     fn parse_filters_from_file(filter: &String) -> Result<String> {
@@ -650,6 +812,11 @@ impl InputKeys {
                     "Parsers should be specified in group def, not functions: {}",
                     name
                 );
+                assert!(
+                    keys.sample.is_none() && keys.budget.is_empty(),
+                    "Sampling and budgets should be specified in group def, not functions: {}",
+                    name
+                );
             }
             None => bail!(ParserError::MissingParam("group".into(), name.clone())),
         }
@@ -659,8 +826,14 @@ impl InputKeys {
     fn callback(
         args: Option<String>,
         name: &String,
-    ) -> Result<(String, Vec<DataLevel>, Vec<String>)> {
-        let mut ret = (String::new(), Vec::new(), Vec::new());
+    ) -> Result<(String, Vec<DataLevel>, Vec<String>, Option<u32>, BudgetSpec)> {
+        let mut ret = (
+            String::new(),
+            Vec::new(),
+            Vec::new(),
+            None,
+            BudgetSpec::default(),
+        );
         match args {
             Some(args) => {
                 let keys = InputKeys::from_string(args)?;
@@ -670,6 +843,8 @@ impl InputKeys {
                 }
                 ret.1 = keys.levels;
                 ret.2 = keys.parsers;
+                ret.3 = keys.sample;
+                ret.4 = keys.budget;
             }
             None => bail!(ParserError::MissingParam("filter".into(), name.clone())),
         }
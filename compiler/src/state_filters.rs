@@ -164,6 +164,21 @@ fn gen_state_filter_util(
                     panic!("Unknown binary predicate: {}", child.pred);
                 }
             }
+            Predicate::Compare { lhs, op, rhs, .. } => {
+                if !child.pred.on_packet() && !child.pred.on_session() {
+                    panic!("Unknown comparison predicate: {}", child.pred);
+                }
+                let pred_tokenstream = compare_to_tokens(lhs, op, rhs, statics);
+                add_pred(
+                    code,
+                    child,
+                    tree,
+                    pred_tokenstream,
+                    statics,
+                    sub,
+                    extract_sessions,
+                );
+            }
             Predicate::LayerState { layer, state, op } => {
                 let extract_sessions_ = extract_sessions
                     || (layer == &SupportedLayer::L7 && state >= &LayerState::Headers);
@@ -361,7 +376,7 @@ fn update_body(body: &mut Vec<proc_macro2::TokenStream>, node: &PNode, sub: &Sub
         body.push(quote! { #cb });
     }
     for matched in &node.matched {
-        let cb = cb_set_active_to_tokens(matched);
+        let cb = cb_set_active_to_tokens(sub, matched);
         body.push(quote! { #cb });
     }
     // TODO datatypes
@@ -42,6 +42,11 @@ lazy_static! {
             name: "CoreId".into(),
             level: Some(DataLevel::Packet),
             expl_parsers: vec![],
+        }),
+        ParsedInput::Datatype(DatatypeSpec {
+            name: "ConnSample".into(),
+            level: Some(DataLevel::Packet),
+            expl_parsers: vec![],
         })
     ];
 }
@@ -112,6 +117,8 @@ pub(crate) struct SubscriptionDecoder {
     pub(crate) datatypes_raw: HashMap<String, Vec<ParsedInput>>,
     /// Map cb group (or name) -> Parsed Input(s)
     pub(crate) cbs_raw: HashMap<String, Vec<ParsedInput>>,
+    /// Named filter definition -> filter
+    pub(crate) filter_defs: HashMap<String, String>,
 
     /// Required stream protocol parsers
     pub(crate) parsers: HashSet<String>,
@@ -137,6 +144,7 @@ impl SubscriptionDecoder {
             filters_raw: HashMap::new(),
             datatypes_raw: HashMap::new(),
             cbs_raw: HashMap::new(),
+            filter_defs: HashMap::new(),
             parsers: HashSet::new(),
             custom_preds: Vec::new(),
             datatypes: HashMap::new(),
@@ -206,8 +214,113 @@ impl SubscriptionDecoder {
                     let v = self.cbs_raw.entry(group_name).or_insert(vec![]);
                     v.push(inp.clone());
                 }
+                ParsedInput::FilterDef(def) => {
+                    assert!(
+                        !self.filter_defs.contains_key(&name),
+                        "Filter definition {} defined twice",
+                        name
+                    );
+                    self.filter_defs.insert(name, def.filter.clone());
+                }
             }
         }
+        // A reference to a filter definition is expanded before custom filters are resolved, so
+        // a definition would silently shadow a filter of the same name
+        for name in self.filter_defs.keys() {
+            assert!(
+                !self.filters_raw.contains_key(name),
+                "Filter definition {} has the same name as a filter",
+                name
+            );
+        }
+    }
+
+    /// Replaces references to named filter definitions in `filter` with their (recursively
+    /// expanded) definitions. Redundant patterns introduced by overlapping definitions are
+    /// deduplicated when the filter is parsed.
+    pub(crate) fn expand_filter_defs(&self, filter: &str) -> String {
+        self.expand_filter(filter, &mut vec![])
+    }
+
+    /// Returns the sampling rate (one in `rate` connections) of the callback or callback group
+    /// `name`, if it is sampled.
+    pub(crate) fn sample_rate(&self, name: &str) -> Option<u32> {
+        self.cbs_raw.get(name)?.iter().find_map(|i| i.sample())
+    }
+
+    /// Returns the callbacks and callback groups that declare a budget, ordered by name.
+    /// A subscription's position in this list is its ID in the generated code.
+    pub(crate) fn budgets(&self) -> Vec<(&String, &BudgetSpec)> {
+        let mut budgets: Vec<_> = self
+            .cbs_raw
+            .iter()
+            .filter_map(|(name, inps)| inps.iter().find_map(|i| i.budget()).map(|b| (name, b)))
+            .collect();
+        budgets.sort_by(|a, b| a.0.cmp(b.0));
+        assert!(
+            budgets.len() <= iris_core::subscription::budget::MAX_BUDGETS,
+            "Too many subscriptions declare a budget"
+        );
+        budgets
+    }
+
+    /// Returns the ID of the budget declared by the callback or callback group `name`, if any.
+    pub(crate) fn budget_id(&self, name: &str) -> Option<usize> {
+        self.budgets().iter().position(|(n, _)| n.as_str() == name)
+    }
+
+    fn expand_filter(&self, filter: &str, stack: &mut Vec<String>) -> String {
+        let mut expanded = String::with_capacity(filter.len());
+        // Delimiter of the string or byte literal being copied, if any
+        let mut literal = None;
+        let mut chars = filter.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if let Some(delim) = literal {
+                if c == delim {
+                    literal = None;
+                }
+                expanded.push(c);
+                continue;
+            }
+            if c == '\'' || c == '|' {
+                literal = Some(c);
+            }
+            if !c.is_ascii_alphabetic() {
+                expanded.push(c);
+                continue;
+            }
+            let mut end = start + 1;
+            while let Some((i, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || *c == '_') {
+                    break;
+                }
+                end = i + 1;
+                chars.next();
+            }
+            let ident = &filter[start..end];
+            // Fields, and segments of IPv6 addresses, are not references
+            let prev = filter[..start].chars().last();
+            let next = filter[end..].chars().next();
+            let is_ref = !matches!(prev, Some(c) if c.is_ascii_alphanumeric() || c == '.' || c == ':' || c == '_')
+                && !matches!(next, Some('.' | ':'));
+            match self.filter_defs.get(ident) {
+                Some(def) if is_ref => {
+                    assert!(
+                        !stack.iter().any(|name| name == ident),
+                        "Cyclic filter definition: {} -> {}",
+                        stack.join(" -> "),
+                        ident
+                    );
+                    stack.push(ident.to_string());
+                    expanded.push('(');
+                    expanded.push_str(&self.expand_filter(def, stack));
+                    expanded.push(')');
+                    stack.pop();
+                }
+                _ => expanded.push_str(ident),
+            }
+        }
+        expanded
     }
 
     fn decode_datatypes(&mut self) {
@@ -280,8 +393,8 @@ impl SubscriptionDecoder {
                 .find(|i| matches!(i, ParsedInput::Callback(_) | ParsedInput::CallbackGroup(_)))
                 .expect(&format!("{} missing callback definition", cb_name));
             let filter = match inp_group {
-                ParsedInput::Callback(cb) => cb.filter.clone(),
-                ParsedInput::CallbackGroup(cb) => cb.filter.clone(),
+                ParsedInput::Callback(cb) => self.expand_filter_defs(&cb.filter),
+                ParsedInput::CallbackGroup(cb) => self.expand_filter_defs(&cb.filter),
                 _ => unreachable!(),
             };
             let mut callbacks = vec![];
@@ -773,6 +886,8 @@ mod tests {
                     returns: FnReturn::None,
                 },
                 expl_parsers: vec![],
+                sample: None,
+                budget: BudgetSpec::default(),
             }),
         ];
         let decoder = SubscriptionDecoder::new(&inputs);
@@ -837,4 +952,164 @@ mod tests {
         ptree.collapse();
         assert!(!ptree.deliver.is_empty());
     }
+
+    #[test]
+    fn test_filter_defs() {
+        let def = |name: &str, filter: &str| {
+            ParsedInput::FilterDef(FilterDefSpec {
+                name: name.into(),
+                filter: filter.into(),
+            })
+        };
+        let inputs = vec![
+            def("web", "tls or http or quic"),
+            def("secure", "ipv4 and web and tls.sni ~ 'web|secure'"),
+            ParsedInput::Callback(CallbackFnSpec {
+                filter: "secure or (dns and web)".into(),
+                level: vec![],
+                func: FnSpec {
+                    name: "my_cb".into(),
+                    datatypes: vec!["Session".into()],
+                    returns: FnReturn::None,
+                },
+                expl_parsers: vec![],
+                sample: None,
+                budget: BudgetSpec::default(),
+            }),
+        ];
+        let decoder = SubscriptionDecoder::new(&inputs);
+        assert_eq!(
+            decoder.subscriptions[0].filter,
+            "(ipv4 and (tls or http or quic) and tls.sni ~ 'web|secure') or (dns and (tls or http or quic))"
+        );
+        assert!(Filter::new(&decoder.subscriptions[0].filter, &decoder.custom_preds).is_ok());
+    }
+
+    #[test]
+    fn test_packet_marks() {
+        let inputs = vec![ParsedInput::Callback(CallbackFnSpec {
+            filter: "tcp.port = 80 or ipv4.src_addr = 1.2.3.4".into(),
+            level: vec![],
+            func: FnSpec {
+                name: "my_cb".into(),
+                datatypes: vec!["Session".into()],
+                returns: FnReturn::None,
+            },
+            expl_parsers: vec![],
+            sample: None,
+            budget: BudgetSpec::default(),
+        })];
+        let decoder = SubscriptionDecoder::new(&inputs);
+        let ptree = decoder.get_packet_filter_tree();
+        let marks = ptree
+            .to_path_strings()
+            .into_iter()
+            .map(|(id, filter_str)| (id as u32, filter_str))
+            .collect::<Vec<_>>();
+        assert_eq!(marks.len(), ptree.size - 1);
+
+        // Each pattern installed from the filter string ends at a terminal node
+        let filter = Filter::new(&ptree.to_filter_string(), &vec![])
+            .unwrap()
+            .with_marks(&marks)
+            .unwrap();
+        for pattern in filter.get_patterns_flat() {
+            let mark = filter.get_mark(&pattern).expect("Pattern not marked");
+            assert!(ptree.get_subtree(mark as usize).unwrap().is_terminal);
+        }
+    }
+
+    #[test]
+    fn test_budgets() {
+        let cb = |name: &str, budget: BudgetSpec| {
+            ParsedInput::Callback(CallbackFnSpec {
+                filter: "tls".into(),
+                level: vec![],
+                func: FnSpec {
+                    name: name.into(),
+                    datatypes: vec!["Session".into()],
+                    returns: FnReturn::None,
+                },
+                expl_parsers: vec![],
+                sample: None,
+                budget,
+            })
+        };
+        let inputs = vec![
+            cb(
+                "logger",
+                BudgetSpec {
+                    max_rate: Some(100),
+                    max_conns: None,
+                    priority: Some("low".into()),
+                },
+            ),
+            cb("unlimited", BudgetSpec::default()),
+            cb(
+                "alerts",
+                BudgetSpec {
+                    max_rate: None,
+                    max_conns: Some(1000),
+                    priority: Some("high".into()),
+                },
+            ),
+        ];
+        let decoder = SubscriptionDecoder::new(&inputs);
+        let budgets = decoder.budgets();
+        assert_eq!(budgets.len(), 2);
+        assert_eq!(decoder.budget_id("alerts"), Some(0));
+        assert_eq!(decoder.budget_id("logger"), Some(1));
+        assert_eq!(decoder.budget_id("unlimited"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Filter definition my_filter has the same name as a filter")]
+    fn test_filter_defs_shadow_filter() {
+        let inputs = vec![
+            ParsedInput::FilterDef(FilterDefSpec {
+                name: "my_filter".into(),
+                filter: "tls".into(),
+            }),
+            ParsedInput::Filter(FilterFnSpec {
+                level: vec![DataLevel::L4InPayload(false)],
+                func: FnSpec {
+                    name: "my_filter".into(),
+                    datatypes: vec!["L4Pdu".into()],
+                    returns: FnReturn::FilterResult,
+                },
+                expl_parsers: vec![],
+            }),
+        ];
+        SubscriptionDecoder::new(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "Filter definition MyGroup has the same name as a filter")]
+    fn test_filter_defs_shadow_filter_group() {
+        let inputs = vec![
+            ParsedInput::FilterGroupFn(FilterGroupFnSpec {
+                level: vec![DataLevel::L4InPayload(false)],
+                group_name: "MyGroup".into(),
+                func: FnSpec {
+                    name: "update".into(),
+                    datatypes: vec!["L4Pdu".into()],
+                    returns: FnReturn::FilterResult,
+                },
+            }),
+            ParsedInput::FilterDef(FilterDefSpec {
+                name: "MyGroup".into(),
+                filter: "tls".into(),
+            }),
+        ];
+        SubscriptionDecoder::new(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cyclic filter definition")]
+    fn test_filter_defs_cycle() {
+        let mut decoder = SubscriptionDecoder::new(&vec![]);
+        decoder.filter_defs.insert("a".into(), "tcp and b".into());
+        decoder.filter_defs.insert("b".into(), "udp or a".into());
+        decoder.expand_filter_defs("a");
+    }
 }
@@ -19,6 +19,12 @@ fn main() {
     println!("cargo:rustc-check-cfg=cfg(dpdk_ge_2111)");
     println!("cargo:rustc-check-cfg=cfg(dpdk_ge_2311)");
     println!("cargo:rustc-check-cfg=cfg(dpdk_ge_2411)");
+
+    // The heap-backed packet buffer backend does not use DPDK.
+    if env::var("CARGO_FEATURE_HEAP_MBUF").is_ok() {
+        return;
+    }
+
     let dpdk_version = env::var("DPDK_VERSION").expect("Set DPDK_VERSION env variable");
 
     if !["20.11", "21.08", "23.11", "24.11"].contains(&dpdk_version.as_str()) {
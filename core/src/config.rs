@@ -9,7 +9,9 @@
 //! "offline" mode (reading packets from a capture file). See
 //! [configs](https://github.com/stanford-esrg/iris/tree/main/configs) for examples.
 
-use crate::lcore::{CoreId, SocketId};
+use crate::lcore::CoreId;
+#[cfg(not(feature = "heap_mbuf"))]
+use crate::lcore::SocketId;
 
 use std::fs;
 #[cfg(feature = "prometheus")]
@@ -31,6 +33,9 @@ pub fn load_config<P: AsRef<Path>>(path: P) -> RuntimeConfig {
         );
         panic!();
     }
+    if let Some(online) = &config.online {
+        online.check_sinks();
+    }
     config
 }
 
@@ -90,6 +95,11 @@ pub struct RuntimeConfig {
     /// Connection tracking settings.
     pub conntrack: ConnTrackConfig,
 
+    /// Output settings for records emitted by callbacks. Defaults to `None` (records emitted with
+    /// [output::emit](crate::output::emit) are discarded).
+    #[serde(default = "default_output")]
+    pub output: Option<OutputConfig>,
+
     #[doc(hidden)]
     /// Runtime filter for testing purposes.
     #[serde(default = "default_filter")]
@@ -128,6 +138,7 @@ impl RuntimeConfig {
     }
 
     /// Returns a list of socket IDs in use.
+    #[cfg(not(feature = "heap_mbuf"))]
     pub(crate) fn get_all_socket_ids(&self) -> Vec<SocketId> {
         let mut sockets = vec![];
         for core_id in self.get_all_core_ids() {
@@ -139,6 +150,7 @@ impl RuntimeConfig {
     }
 
     /// Returns DPDK EAL parameters.
+    #[cfg(not(feature = "heap_mbuf"))]
     #[allow(clippy::vec_init_then_push)]
     pub(crate) fn get_eal_params(&self) -> Vec<String> {
         let mut eal_params = vec![];
@@ -158,9 +170,18 @@ impl RuntimeConfig {
             for supl_arg in online.dpdk_supl_args.iter() {
                 eal_params.push(supl_arg.to_string())
             }
-            for port in online.ports.iter() {
-                eal_params.push("-a".to_owned());
-                eal_params.push(port.device.to_string());
+            if online.backend.driver().is_some() {
+                // Kernel interfaces are attached as virtual devices, so no PCI devices are probed.
+                eal_params.push("--no-pci".to_owned());
+                for (index, port) in online.ports.iter().enumerate() {
+                    eal_params.push("--vdev".to_owned());
+                    eal_params.push(online.backend.vdev_args(index, port));
+                }
+            } else {
+                for port in online.ports.iter() {
+                    eal_params.push("-a".to_owned());
+                    eal_params.push(port.device.to_string());
+                }
             }
         }
 
@@ -196,6 +217,10 @@ fn default_filter() -> Option<String> {
     None
 }
 
+fn default_output() -> Option<OutputConfig> {
+    None
+}
+
 impl Default for RuntimeConfig {
     fn default() -> Self {
         RuntimeConfig {
@@ -219,11 +244,16 @@ impl Default for RuntimeConfig {
                 udp_inactivity_timeout: 60_000,
                 tcp_inactivity_timeout: 300_000,
                 tcp_establish_timeout: 5000,
+                expected_timeout: 60_000,
                 init_synack: false,
                 init_fin: false,
                 init_rst: false,
                 init_data: false,
+                shunt: None,
+                sampling: None,
+                shedding: None,
             },
+            output: None,
             filter: None,
         }
     }
@@ -281,6 +311,7 @@ fn default_cache_size() -> usize {
 ///     mtu = 1500
 ///     hardware_assist = true
 ///     dpdk_supl_args = []
+///     backend = "dpdk"
 ///
 /// [online.monitor.display]
 ///     throughput = true
@@ -324,15 +355,28 @@ pub struct OnlineConfig {
 
     /// If set, will attempt to offload parts of the filter to the NIC, depending on its hardware
     /// filtering support. Defaults to `true`.
+    ///
+    /// Where the device supports the MARK action, offloaded rules also tag matching packets so that
+    /// the software packet filter only evaluates predicates the NIC could not check.
     #[serde(default = "default_hardware_assist")]
     pub hardware_assist: bool,
 
+    /// If set with `hardware_assist`, hardware filter rules are only validated against each port
+    /// and reported, not installed. All traffic is then filtered in software. Useful for tuning a
+    /// filter for offload. Defaults to `false`.
+    #[serde(default = "default_hardware_dry_run")]
+    pub hardware_dry_run: bool,
+
     /// If set, will pass supplementary arguments to DPDK EAL (see DPDK
     /// configuration). For instance `--no-huge`.
     /// Defaults to empty string.
     #[serde(default = "default_dpdk_supl_args")]
     pub dpdk_supl_args: Vec<String>,
 
+    /// Packet capture backend used to read from `ports`. Defaults to `"dpdk"`.
+    #[serde(default = "default_backend")]
+    pub backend: CaptureBackend,
+
     /// Live performance monitoring. Defaults to `None`.
     #[serde(default = "default_monitor")]
     pub monitor: Option<MonitorConfig>,
@@ -346,6 +390,23 @@ pub struct OnlineConfig {
     pub ports: Vec<PortMap>,
 }
 
+impl OnlineConfig {
+    /// Panics if a port has a sink core with a backend other than `dpdk`.
+    pub(crate) fn check_sinks(&self) {
+        if self.backend == CaptureBackend::Dpdk {
+            return;
+        }
+        if let Some(port) = self.ports.iter().find(|port| port.sink.is_some()) {
+            log::error!(
+                "Sink core on {} requires the dpdk backend, not {:?}.",
+                port.device,
+                self.backend
+            );
+            panic!();
+        }
+    }
+}
+
 fn default_duration() -> Option<u64> {
     None
 }
@@ -354,10 +415,18 @@ fn default_hardware_assist() -> bool {
     true
 }
 
+fn default_hardware_dry_run() -> bool {
+    false
+}
+
 fn default_dpdk_supl_args() -> Vec<String> {
     Vec::new()
 }
 
+fn default_backend() -> CaptureBackend {
+    CaptureBackend::Dpdk
+}
+
 fn default_promiscuous() -> bool {
     true
 }
@@ -381,6 +450,76 @@ fn default_prometheus() -> Option<PrometheusConfig> {
 
 /* --------------------------------------------------------------------------------- */
 
+/// Packet capture backends for online mode.
+///
+/// ## Remarks
+/// The `af_packet` and `af_xdp` backends read from Linux network interfaces that remain bound to
+/// their kernel drivers (including virtio NICs and veth pairs), using the corresponding DPDK
+/// virtual device drivers. Packets are spread across the cores polling an interface by
+/// `PACKET_FANOUT_HASH` (`af_packet`) or by the NIC's receive queues (`af_xdp`), and are processed
+/// by the same RX cores as with `dpdk`. The RSS redirection table, and therefore hardware assist,
+/// is not available with these backends, and ports with a sink core are rejected: the sink queue
+/// would join the fanout group and silently drop its share of the traffic.
+///
+/// ## Example
+/// ```toml
+/// [online]
+///     backend = "af_packet"
+///
+///     [[online.ports]]
+///         device = "veth0"
+///         cores = [1,2]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureBackend {
+    /// DPDK poll-mode driver for a PCI device bound to a DPDK-compatible driver.
+    Dpdk,
+    /// Linux `AF_PACKET` sockets with hash fanout across RX cores.
+    AfPacket,
+    /// Linux `AF_XDP` sockets, one per NIC receive queue. The interface should be configured with
+    /// at least as many combined channels as RX cores (e.g., `ethtool -L <iface> combined <n>`).
+    AfXdp,
+}
+
+#[cfg(not(feature = "heap_mbuf"))]
+impl CaptureBackend {
+    /// Returns the name of the DPDK virtual device driver, or `None` for PCI devices.
+    pub(crate) fn driver(&self) -> Option<&'static str> {
+        match self {
+            CaptureBackend::Dpdk => None,
+            CaptureBackend::AfPacket => Some("net_af_packet"),
+            CaptureBackend::AfXdp => Some("net_af_xdp"),
+        }
+    }
+
+    /// Returns the DPDK device name of the `index`-th configured port.
+    pub(crate) fn device_name(&self, index: usize, port: &PortMap) -> String {
+        match self.driver() {
+            Some(driver) => format!("{}{}", driver, index),
+            None => port.device.clone(),
+        }
+    }
+
+    /// Returns the `--vdev` EAL argument attaching the `index`-th configured port.
+    pub(crate) fn vdev_args(&self, index: usize, port: &PortMap) -> String {
+        let name = self.device_name(index, port);
+        let nb_queues = port.nb_queues();
+        match self {
+            CaptureBackend::Dpdk => name,
+            CaptureBackend::AfPacket => {
+                format!("{},iface={},qpairs={}", name, port.device, nb_queues)
+            }
+            CaptureBackend::AfXdp => format!(
+                "{},iface={},start_queue=0,queue_count={}",
+                name, port.device, nb_queues
+            ),
+        }
+    }
+}
+
+/* --------------------------------------------------------------------------------- */
+
 /// Sink core options.
 ///
 /// A "sink" core is a utility core whose sole purpose is to drop received traffic. This is useful
@@ -432,7 +571,8 @@ fn default_nb_buckets() -> usize {
 /// ```
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PortMap {
-    /// PCI address of interface.
+    /// PCI address of interface, or the Linux interface name (e.g., `"eth0"`) with the
+    /// `af_packet` and `af_xdp` [backends](CaptureBackend).
     pub device: String,
 
     /// List of packet processing cores used to poll the interface.
@@ -447,6 +587,17 @@ pub struct PortMap {
     pub sink: Option<SinkConfig>,
 }
 
+#[cfg(not(feature = "heap_mbuf"))]
+impl PortMap {
+    /// Returns the number of receive queues polled by RX cores on the interface.
+    pub(crate) fn nb_queues(&self) -> usize {
+        let mut cores = self.cores.clone();
+        cores.sort_unstable();
+        cores.dedup();
+        cores.len()
+    }
+}
+
 fn default_sink() -> Option<SinkConfig> {
     None
 }
@@ -680,6 +831,12 @@ pub struct ConnTrackConfig {
     #[serde(default = "default_tcp_establish_timeout")]
     pub tcp_establish_timeout: usize,
 
+    /// Time to wait for a connection announced by a signalling session (e.g., an RTP stream
+    /// negotiated by SIP) before the announcement expires (in milliseconds). Defaults to `60_000`
+    /// (1 minute).
+    #[serde(default = "default_expected_timeout")]
+    pub expected_timeout: usize,
+
     #[doc(hidden)]
     /// Whether to track TCP connections where the first observed packet is a SYN/ACK. Defaults to
     /// `false`.
@@ -703,6 +860,20 @@ pub struct ConnTrackConfig {
     /// `false`.
     #[serde(default = "default_init_data")]
     pub init_data: bool,
+
+    /// Offloading of connections that no longer require processing. Defaults to `None` (no
+    /// shunting).
+    #[serde(default = "default_shunt")]
+    pub shunt: Option<ShuntConfig>,
+
+    /// Deterministic sampling of connections. Defaults to `None` (all connections are tracked).
+    #[serde(default = "default_sampling")]
+    pub sampling: Option<SamplingConfig>,
+
+    /// Load shedding by subscription priority as the connection table or RX queues fill up.
+    /// Defaults to `None` (subscriptions are only limited by their own budgets).
+    #[serde(default = "default_shedding")]
+    pub shedding: Option<SheddingConfig>,
 }
 
 fn default_max_connections() -> usize {
@@ -729,6 +900,10 @@ fn default_tcp_establish_timeout() -> usize {
     5000
 }
 
+fn default_expected_timeout() -> usize {
+    60_000
+}
+
 fn default_init_synack() -> bool {
     false
 }
@@ -744,3 +919,378 @@ fn default_init_rst() -> bool {
 fn default_init_data() -> bool {
     false
 }
+
+fn default_shunt() -> Option<ShuntConfig> {
+    None
+}
+
+fn default_sampling() -> Option<SamplingConfig> {
+    None
+}
+
+fn default_shedding() -> Option<SheddingConfig> {
+    None
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// Dynamic connection shunting options.
+///
+/// Once no subscription requires further data from a connection (e.g., its TLS handshake has been
+/// parsed and delivered), both directions of the connection are dropped in hardware with
+/// per-five-tuple flow rules, so the rest of the connection never reaches the CPU. Rules are
+/// removed once they stop matching traffic.
+///
+/// ## Remarks
+/// Installing flow rules from the packet processing cores is expensive, so installations are
+/// rate limited per core and the total number of rules is bounded. Connections that are discarded
+/// on their first packet are not shunted. Shunting requires a NIC that supports `DROP` and `COUNT`
+/// flow actions; in offline mode and with `emulate = true`, rules are emulated in software by
+/// discarding matching packets before connection tracking.
+///
+/// ## Example
+/// ```toml
+/// [conntrack.shunt]
+///     max_rules = 8192
+///     max_rate = 1000
+///     idle_timeout = 30_000
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ShuntConfig {
+    /// Maximum number of installed rules across all cores and ports. Each shunted connection uses
+    /// two rules (one per direction). Defaults to `8192`.
+    #[serde(default = "default_shunt_max_rules")]
+    pub max_rules: usize,
+
+    /// Maximum number of connections shunted per second on each core. Defaults to `1000`.
+    #[serde(default = "default_shunt_max_rate")]
+    pub max_rate: usize,
+
+    /// A rule that matches no packets for this amount of time (in milliseconds) is removed.
+    /// Defaults to `30_000` (30 seconds).
+    #[serde(default = "default_shunt_idle_timeout")]
+    pub idle_timeout: usize,
+
+    /// If set, rules are emulated in software instead of installed on the NIC. Defaults to
+    /// `false`.
+    #[serde(default = "default_shunt_emulate")]
+    pub emulate: bool,
+}
+
+fn default_shunt_max_rules() -> usize {
+    8192
+}
+
+fn default_shunt_max_rate() -> usize {
+    1000
+}
+
+fn default_shunt_idle_timeout() -> usize {
+    30_000
+}
+
+fn default_shunt_emulate() -> bool {
+    false
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// Connection sampling options.
+///
+/// Connections are sampled on their first packet by a keyed hash of their five-tuple, so that
+/// both directions of a connection share a sampling decision and the same connections are
+/// sampled on every core, run, and trace for a given key. Packets of connections that are not
+/// sampled are discarded before connection tracking.
+///
+/// ## Remarks
+/// Individual subscriptions can further sample connections with the `sample` callback attribute
+/// (e.g., `#[callback("tls,sample=16")]`). Samples are nested: a connection sampled at a rate of
+/// one in 64 is also sampled at one in 16. Callbacks can request the `ConnSample` datatype to
+/// re-weight their results.
+///
+/// ## Example
+/// ```toml
+/// [conntrack.sampling]
+///     rate = 16
+///     key = 0x1234
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SamplingConfig {
+    /// Tracks one in `rate` connections. Defaults to `1` (all connections).
+    #[serde(default = "default_sampling_rate")]
+    pub rate: u32,
+
+    /// Key of the connection hash. Changing the key selects a different sample of connections.
+    /// Defaults to `0`.
+    #[serde(default = "default_sampling_key")]
+    pub key: u64,
+}
+
+fn default_sampling_rate() -> u32 {
+    1
+}
+
+fn default_sampling_key() -> u64 {
+    0
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// Load shedding options.
+///
+/// Subscriptions are assigned a priority class with the `priority` callback attribute (`low`,
+/// `normal`, or `high`; defaults to `normal`). When the connection table of a core, or one of the
+/// RX queues it polls, fills past a class's threshold, subscriptions of that class stop admitting
+/// new connections on that core. Connections that were already admitted continue to be processed,
+/// and `high` priority subscriptions are never shed.
+///
+/// RX queue occupancy is the fraction of the queue's `nb_rxd` descriptors holding packets that
+/// the core has not yet polled, so it rises as callbacks slow the core down, before the NIC drops
+/// packets (`rx_missed`). It is sampled every 1024 polls, and is only available with drivers that
+/// report it; the kernel capture backends (`af_packet`, `af_xdp`) shed on table occupancy only.
+///
+/// ## Remarks
+/// Subscriptions can also bound their own load with the `max_rate` (callbacks per second) and
+/// `max_conns` (tracked connections) callback attributes, which apply on each core regardless of
+/// these options. Shed connections and callbacks are counted per core and subscription in the
+/// `iris_shed_connections` and `iris_shed_callbacks` statistics.
+///
+/// ## Example
+/// ```toml
+/// [conntrack.shedding]
+///     low = 0.5
+///     normal = 0.8
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SheddingConfig {
+    /// Fraction of `max_connections`, or of the RX descriptors, past which `low` priority
+    /// subscriptions are shed. Defaults to `0.5`.
+    #[serde(default = "default_shedding_low")]
+    pub low: f64,
+
+    /// Fraction of `max_connections`, or of the RX descriptors, past which `normal` priority
+    /// subscriptions are shed. Defaults to `0.75`.
+    #[serde(default = "default_shedding_normal")]
+    pub normal: f64,
+}
+
+fn default_shedding_low() -> f64 {
+    0.5
+}
+
+fn default_shedding_normal() -> f64 {
+    0.75
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// Output options for records emitted by callbacks.
+///
+/// Each core batches the records it emits and writes them to its own sink, so callbacks never
+/// contend on output. File sinks write one file per core, named `{path}_{core}.{ext}`, and start a
+/// new file (`{path}_{core}.{n}.{ext}`) once a file exceeds `rotate_bytes`. Batches are flushed
+/// and files are merged into `{path}.{ext}` when the runtime shuts down.
+///
+/// ## Remarks
+/// Parquet output requires building with the `parquet` feature. Its schema is inferred from the
+/// first record each core emits, and Parquet files are neither rotated nor merged. The Kafka sink
+/// produces each record as a JSON message to a single broker.
+///
+/// ## Example
+/// ```toml
+/// [output]
+///     format = "jsonl"
+///     path = "./results/tls"
+///     batch_size = 1024
+///     rotate_bytes = 1_000_000_000
+///     merge = true
+/// ```
+/// ```toml
+/// [output]
+///     format = "kafka"
+///     [output.kafka]
+///         broker = "127.0.0.1:9092"
+///         topic = "iris"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OutputConfig {
+    /// Output format, one of `jsonl`, `csv`, `parquet`, or `kafka`. Defaults to `jsonl`.
+    #[serde(default = "default_output_format")]
+    pub format: OutputFormat,
+
+    /// Path prefix of output files. Defaults to `./iris_output`.
+    #[serde(default = "default_output_path")]
+    pub path: String,
+
+    /// Number of records each core buffers before writing them to its sink. Defaults to `1024`.
+    #[serde(default = "default_output_batch_size")]
+    pub batch_size: usize,
+
+    /// Size (in bytes) after which a new output file is started. Defaults to `None` (no
+    /// rotation).
+    #[serde(default = "default_output_rotate_bytes")]
+    pub rotate_bytes: Option<u64>,
+
+    /// Whether to merge per-core JSONL and CSV files on shutdown. Defaults to `true`.
+    #[serde(default = "default_output_merge")]
+    pub merge: bool,
+
+    /// Kafka producer settings. Required if `format = "kafka"`.
+    #[serde(default = "default_output_kafka")]
+    pub kafka: Option<KafkaConfig>,
+}
+
+/// Format of records written by output sinks.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jsonl,
+    Csv,
+    Parquet,
+    Kafka,
+}
+
+fn default_output_format() -> OutputFormat {
+    OutputFormat::Jsonl
+}
+
+fn default_output_path() -> String {
+    "./iris_output".to_string()
+}
+
+fn default_output_batch_size() -> usize {
+    1024
+}
+
+fn default_output_rotate_bytes() -> Option<u64> {
+    None
+}
+
+fn default_output_merge() -> bool {
+    true
+}
+
+fn default_output_kafka() -> Option<KafkaConfig> {
+    None
+}
+
+/// Kafka producer options.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct KafkaConfig {
+    /// Address of the broker that leads `partition` of `topic`.
+    pub broker: String,
+
+    /// Topic to produce records to.
+    pub topic: String,
+
+    /// Partition to produce records to. Defaults to `0`.
+    #[serde(default = "default_kafka_partition")]
+    pub partition: i32,
+
+    /// Client ID sent with each request. Defaults to `iris`.
+    #[serde(default = "default_kafka_client_id")]
+    pub client_id: String,
+
+    /// Time to wait for the broker to acknowledge a batch (in milliseconds). Defaults to `5000`
+    /// (5 seconds).
+    #[serde(default = "default_kafka_timeout")]
+    pub timeout: u64,
+}
+
+fn default_kafka_partition() -> i32 {
+    0
+}
+
+fn default_kafka_client_id() -> String {
+    "iris".to_string()
+}
+
+fn default_kafka_timeout() -> u64 {
+    5000
+}
+
+#[cfg(all(test, not(feature = "heap_mbuf")))]
+mod tests {
+    use super::*;
+
+    fn online_config(backend: &str, sink: bool) -> RuntimeConfig {
+        let sink = match sink {
+            true => "[online.ports.sink]\n    core = 9\n",
+            false => "",
+        };
+        let config_str = format!(
+            r#"
+            main_core = 0
+            [mempool]
+            [conntrack]
+            [online]
+                backend = "{}"
+                [[online.ports]]
+                    device = "veth0"
+                    cores = [1, 2, 2]
+                    {}
+                [[online.ports]]
+                    device = "veth1"
+                    cores = [3]
+            "#,
+            backend, sink
+        );
+        toml::from_str(&config_str).unwrap()
+    }
+
+    #[test]
+    fn core_config_vdev_args() {
+        let config = online_config("af_packet", false);
+        let online = config.online.as_ref().unwrap();
+        assert_eq!(
+            online.backend.vdev_args(0, &online.ports[0]),
+            "net_af_packet0,iface=veth0,qpairs=2"
+        );
+        assert_eq!(
+            CaptureBackend::AfXdp.vdev_args(1, &online.ports[1]),
+            "net_af_xdp1,iface=veth1,start_queue=0,queue_count=1"
+        );
+        assert_eq!(CaptureBackend::Dpdk.vdev_args(1, &online.ports[1]), "veth1");
+    }
+
+    #[test]
+    fn core_config_eal_params() {
+        let config = online_config("af_xdp", false);
+        let params = config.get_eal_params();
+        let start = params.iter().position(|p| p == "--no-pci").unwrap();
+        assert_eq!(
+            params[start..start + 5],
+            [
+                "--no-pci",
+                "--vdev",
+                "net_af_xdp0,iface=veth0,start_queue=0,queue_count=2",
+                "--vdev",
+                "net_af_xdp1,iface=veth1,start_queue=0,queue_count=1",
+            ]
+        );
+        assert!(!params.iter().any(|p| p == "-a"));
+        assert_eq!(params[..4], ["--main-lcore", "0", "-l", "0,1,2,3"]);
+
+        let config = online_config("dpdk", true);
+        let params = config.get_eal_params();
+        assert!(!params.iter().any(|p| p == "--no-pci" || p == "--vdev"));
+        assert_eq!(params[..4], ["--main-lcore", "0", "-l", "0,1,2,3,9"]);
+        let start = params.iter().position(|p| p == "-a").unwrap();
+        assert_eq!(params[start..start + 4], ["-a", "veth0", "-a", "veth1"]);
+    }
+
+    #[test]
+    fn core_config_sink_backend() {
+        online_config("dpdk", true).online.unwrap().check_sinks();
+        online_config("af_xdp", false).online.unwrap().check_sinks();
+    }
+
+    #[test]
+    #[should_panic]
+    fn core_config_sink_af_packet() {
+        online_config("af_packet", true)
+            .online
+            .unwrap()
+            .check_sinks();
+    }
+}
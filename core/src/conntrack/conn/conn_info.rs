@@ -5,9 +5,12 @@
 // Probe, parse, etc.
 
 use super::conn_actions::TrackedActions;
+use crate::conntrack::expected::ExpectedConn;
+use crate::conntrack::sample::ConnSample;
 use crate::lcore::CoreId;
 use crate::protocols::packet::tcp::TCP_PROTOCOL;
-use crate::protocols::stream::{ConnData, ParserRegistry};
+use crate::protocols::stream::{ConnData, ConnParser, ParserRegistry};
+use crate::subscription::budget::Admitted;
 use crate::subscription::{Subscription, Trackable};
 use crate::FiveTuple;
 use crate::L4Pdu;
@@ -36,13 +39,17 @@ where
     pub layers: [Layer; NUM_LAYERS],
     /// Subscription data (for delivering)
     pub tracked: T,
+    /// Sampling metadata, used to gate sampled subscriptions.
+    pub sample: ConnSample,
+    /// Budgeted subscriptions that the connection was admitted to or shed from.
+    pub admitted: Admitted,
 }
 
 impl<T> ConnInfo<T>
 where
     T: Trackable,
 {
-    pub(super) fn new(pdu: &L4Pdu, core_id: CoreId) -> Self {
+    pub(super) fn new(pdu: &L4Pdu, core_id: CoreId, sample: ConnSample) -> Self {
         let five_tuple = FiveTuple::from_ctxt(pdu.ctxt);
         ConnInfo {
             linfo: LayerInfo {
@@ -56,6 +63,8 @@ where
             cdata: ConnData::new(five_tuple),
             layers: [Layer::L7(L7Session::new())],
             tracked: T::new(pdu, core_id),
+            sample,
+            admitted: Admitted::default(),
         }
     }
 
@@ -176,8 +185,21 @@ where
         }
     }
 
+    /// Parses the connection as an expected connection announced by another session, using
+    /// `parser`.
+    pub(crate) fn set_expected(&mut self, parser: ConnParser) {
+        self.layers[0].set_expected(parser);
+    }
+
+    /// Removes and returns connections announced by the sessions parsed in this connection.
+    pub(crate) fn drain_expected(&mut self) -> Vec<ExpectedConn> {
+        self.layers[0].drain_expected()
+    }
+
     pub(crate) fn clear(&mut self) {
         self.tracked.clear();
+        // Release budgets of subscriptions that no longer need the connection
+        self.admitted = Admitted::default();
     }
 
     pub(crate) fn needs_reassembly(&self) -> bool {
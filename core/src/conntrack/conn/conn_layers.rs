@@ -2,10 +2,11 @@
 
 use super::conn_actions::TrackedActions;
 use super::conn_state::{LayerState, StateTransition};
+use crate::conntrack::expected::ExpectedConn;
 use crate::conntrack::Actions;
 use crate::protocols::stream::{
-    ConnParser, ParseResult, ParserRegistry, ParsingState, ProbeRegistryResult, SessionData,
-    SessionProto,
+    ConnParser, ParseResult, ParserRegistry, ParsingState, ProbeRegistryResult, ProbeResult,
+    SessionData, SessionProto,
 };
 use crate::protocols::Session;
 use crate::L4Pdu;
@@ -128,6 +129,20 @@ impl Layer {
             Layer::L7(session) => session.get_protocol(),
         }
     }
+
+    /// Sets the parser of an expected connection, so that its protocol is known without probing.
+    pub(crate) fn set_expected(&mut self, parser: ConnParser) {
+        match self {
+            Layer::L7(session) => session.parser = parser,
+        }
+    }
+
+    /// Removes and returns connections announced by the sessions parsed in this layer.
+    pub(crate) fn drain_expected(&mut self) -> Vec<ExpectedConn> {
+        match self {
+            Layer::L7(session) => session.parser.drain_expected(),
+        }
+    }
 }
 
 impl TrackableLayer for Layer {
@@ -232,7 +247,8 @@ impl L7Session {
 
 impl TrackableLayer for L7Session {
     fn end_state_tx(&mut self) {
-        // Nothing to parse if in payload and no more sessions expected
+        // Nothing to parse if in payload and no more sessions expected.
+        // A session that hands the stream to another protocol (e.g., STARTTLS) is `Probing`.
         if self.linfo.actions.needs_parse()
             && matches!(self.linfo.state, LayerState::Payload)
             && !matches!(
@@ -299,6 +315,11 @@ impl TrackableLayer for L7Session {
     fn process_stream(&mut self, pdu: &mut L4Pdu, registry: &ParserRegistry) -> StateTransition {
         match self.linfo.state {
             LayerState::Discovery => {
+                if !matches!(self.parser, ConnParser::Unknown) {
+                    // Application-layer protocol known from an expected connection
+                    self.linfo.state = LayerState::Headers;
+                    return StateTransition::L7OnDisc;
+                }
                 match registry.probe_all(pdu) {
                     ProbeRegistryResult::Some(conn_parser) => {
                         // Application-layer protocol known
@@ -341,11 +362,32 @@ impl TrackableLayer for L7Session {
                 }
             }
             LayerState::Payload => {
+                if self.linfo.actions.needs_parse()
+                    && matches!(self.parser.session_parsed_state(), ParsingState::Probing)
+                {
+                    // The current session handed the remainder of the stream
+                    // to another protocol (e.g., STARTTLS), which is parsed as
+                    // a new session in this layer.
+                    if let Some(parser) = self.parser.upgrade() {
+                        match parser.probe(pdu) {
+                            ProbeResult::Certain => {
+                                self.parser = parser;
+                                self.linfo.state = LayerState::Headers;
+                                return self.process_stream(pdu, registry);
+                            }
+                            ProbeResult::NotForUs => {
+                                self.linfo.state = LayerState::None;
+                                return StateTransition::Packet;
+                            }
+                            _ => { /* skip */ }
+                        }
+                    }
+                }
                 pdu.ctxt.app_offset = Some(0);
                 if self.linfo.actions.needs_parse() {
                     match self.parser.session_parsed_state() {
                         ParsingState::Probing => {
-                            // TODO unimplemented: nested sessions
+                            // TODO unimplemented: nested sessions without a known upgrade
                         }
                         ParsingState::Parsing => {
                             // TODO unimplemented: pipelined sessions
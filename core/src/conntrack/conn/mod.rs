@@ -15,6 +15,7 @@ pub use conn_info::ConnInfo;
 use self::tcp_conn::TcpConn;
 use crate::conntrack::conn::udp_conn::UdpConn;
 use crate::conntrack::pdu::{L4Context, L4Pdu};
+use crate::conntrack::sample::ConnSample;
 use crate::lcore::CoreId;
 use crate::protocols::packet::tcp::{ACK, RST, SYN};
 use crate::protocols::stream::ParserRegistry;
@@ -62,6 +63,7 @@ where
         max_ooo: usize,
         pdu: &L4Pdu,
         core_id: CoreId,
+        sample: ConnSample,
     ) -> Result<Self> {
         let tcp_conn = if pdu.ctxt.flags & SYN != 0
             && pdu.ctxt.flags & ACK == 0
@@ -77,20 +79,25 @@ where
             last_seen_ts: pdu.ts.clone(),
             inactivity_window: initial_timeout,
             l4conn: L4Conn::Tcp(tcp_conn),
-            info: ConnInfo::new(pdu, core_id),
+            info: ConnInfo::new(pdu, core_id, sample),
         })
     }
 
     /// Creates a new UDP connection from `ctxt` with an initial inactivity window of
     /// `initial_timeout`.
     #[allow(clippy::unnecessary_wraps)]
-    pub(super) fn new_udp(initial_timeout: usize, pdu: &L4Pdu, core_id: CoreId) -> Result<Self> {
+    pub(super) fn new_udp(
+        initial_timeout: usize,
+        pdu: &L4Pdu,
+        core_id: CoreId,
+        sample: ConnSample,
+    ) -> Result<Self> {
         let udp_conn = UdpConn;
         Ok(Conn {
             last_seen_ts: pdu.ts.clone(),
             inactivity_window: initial_timeout,
             l4conn: L4Conn::Udp(udp_conn),
-            info: ConnInfo::new(pdu, core_id),
+            info: ConnInfo::new(pdu, core_id, sample),
         })
     }
 
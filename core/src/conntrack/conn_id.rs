@@ -9,7 +9,8 @@ use crate::protocols::packet::tcp::TCP_PROTOCOL;
 use crate::protocols::packet::udp::UDP_PROTOCOL;
 use std::cmp;
 use std::fmt;
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddr::V4, SocketAddr::V6};
+use std::hash::Hasher;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddr::V4, SocketAddr::V6};
 
 use serde::Serialize;
 
@@ -121,6 +122,26 @@ impl ConnId {
     pub(super) fn new(src: SocketAddr, dst: SocketAddr, protocol: usize) -> Self {
         ConnId(cmp::max(src, dst), cmp::min(src, dst), protocol)
     }
+
+    /// Writes the connection ID to `state` in a fixed byte encoding.
+    ///
+    /// Unlike the derived `Hash`, the encoding does not depend on the target or Rust version.
+    pub(super) fn write_stable<H: Hasher>(&self, state: &mut H) {
+        for addr in [&self.0, &self.1] {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    state.write_u8(4);
+                    state.write(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    state.write_u8(6);
+                    state.write(&ip.octets());
+                }
+            }
+            state.write(&addr.port().to_be_bytes());
+        }
+        state.write(&(self.2 as u64).to_be_bytes());
+    }
 }
 
 impl fmt::Display for ConnId {
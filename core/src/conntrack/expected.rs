@@ -0,0 +1,110 @@
+//! Expected connections.
+//!
+//! Signalling protocols such as SIP negotiate the endpoints of future connections in-band. When a
+//! parser observes such a negotiation, the announced endpoints are registered with the
+//! `ConnTracker` as expected connections. A new UDP connection to or from a registered endpoint is
+//! then associated with the session that announced it, and its protocol is known without probing.
+//!
+//! RSS places the media flows of a session on arbitrary cores, independently of the core that
+//! parsed the signalling session, so expectations are registered in a table shared by all cores.
+
+use super::pdu::L4Context;
+use crate::protocols::stream::sip::RtpMap;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    static ref EXPECTED: RwLock<HashMap<SocketAddr, (ExpectedConn, Instant)>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Number of registered expectations, so that cores only take the lock once a signalling
+/// session has announced a connection.
+static NB_EXPECTED: AtomicUsize = AtomicUsize::new(0);
+
+/// A media stream announced by a signalling session.
+#[derive(Debug, Clone)]
+pub struct ExpectedConn {
+    /// Endpoint that receives the stream.
+    pub endpoint: SocketAddr,
+    /// `Call-ID` of the SIP session that announced the stream.
+    pub call_id: String,
+    /// Media type (e.g., `audio`).
+    pub media: String,
+    /// Payload type mappings negotiated for the stream.
+    pub rtpmap: Vec<RtpMap>,
+}
+
+/// Handle to the expected connections of all cores, keyed by announced endpoint.
+///
+/// Expectations are not removed when matched, so that both directions of asymmetric media flows,
+/// and flows that resume after expiring for inactivity, are associated with their session. An
+/// expectation is replaced if its endpoint is announced again, and expires after a configurable
+/// timeout.
+#[derive(Debug)]
+pub(crate) struct ExpectedTable {
+    /// Time to expire an expectation after it is announced.
+    timeout: Duration,
+    /// Maximum number of expectations.
+    capacity: usize,
+}
+
+impl ExpectedTable {
+    pub(crate) fn new(timeout: usize, capacity: usize) -> Self {
+        ExpectedTable {
+            timeout: Duration::from_millis(timeout as u64),
+            capacity,
+        }
+    }
+
+    /// Returns `true` if there are no expectations.
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        NB_EXPECTED.load(Ordering::Relaxed) == 0
+    }
+
+    /// Registers expected connections announced at `now`.
+    pub(crate) fn insert(&self, expected: Vec<ExpectedConn>, now: Instant) {
+        if expected.is_empty() {
+            return;
+        }
+        let mut entries = EXPECTED.write().unwrap();
+        for conn in expected {
+            if entries.len() >= self.capacity && !entries.contains_key(&conn.endpoint) {
+                entries.retain(|_, (_, expiry)| *expiry > now);
+                if entries.len() >= self.capacity {
+                    log::debug!("Expected connection table full");
+                    break;
+                }
+            }
+            log::debug!("Expecting {} for call {}", conn.endpoint, conn.call_id);
+            entries.insert(conn.endpoint, (conn, now + self.timeout));
+        }
+        NB_EXPECTED.store(entries.len(), Ordering::Relaxed);
+    }
+
+    /// Returns the unexpired expectation matching a new connection with context `ctxt` at `now`,
+    /// if any. The destination is matched before the source.
+    pub(crate) fn get(&self, ctxt: &L4Context, now: Instant) -> Option<ExpectedConn> {
+        let entries = EXPECTED.read().unwrap();
+        [ctxt.dst, ctxt.src]
+            .iter()
+            .filter_map(|endpoint| entries.get(endpoint))
+            .find(|(_, expiry)| *expiry > now)
+            .map(|(conn, _)| conn.clone())
+    }
+
+    /// Removes expectations that have expired at `now`.
+    pub(crate) fn expire(&self, now: Instant) {
+        if self.is_empty() {
+            return;
+        }
+        let mut entries = EXPECTED.write().unwrap();
+        entries.retain(|_, (_, expiry)| *expiry > now);
+        NB_EXPECTED.store(entries.len(), Ordering::Relaxed);
+    }
+}
@@ -6,7 +6,10 @@
 
 pub mod conn;
 pub mod conn_id;
+pub(crate) mod expected;
 pub mod pdu;
+pub mod sample;
+mod shunt;
 mod timerwheel;
 
 #[cfg(test)]
@@ -16,18 +19,22 @@ pub use conn::conn_actions::{Actions, TrackedActions};
 pub use conn::conn_layers::Layer;
 pub use conn::conn_state::{DataLevel, LayerState, StateTransition, StateTxData};
 pub use conn::ConnInfo;
+pub use sample::ConnSample;
 
 use self::conn::{Conn, L4Conn};
 use self::conn_id::ConnId;
+use self::expected::ExpectedTable;
 use self::pdu::{L4Context, L4Pdu};
+use self::shunt::Shunter;
 use self::timerwheel::TimerWheel;
-use crate::config::ConnTrackConfig;
+use crate::config::{ConnTrackConfig, SheddingConfig, ShuntConfig};
 use crate::lcore::CoreId;
 use crate::memory::mbuf::Mbuf;
 use crate::protocols::packet::tcp::TCP_PROTOCOL;
 use crate::protocols::packet::udp::UDP_PROTOCOL;
 use crate::protocols::stream::ParserRegistry;
-use crate::stats::{StatExt, TCP_NEW_CONNECTIONS, UDP_NEW_CONNECTIONS};
+use crate::stats::{StatExt, NOT_SAMPLED_PKT, TCP_NEW_CONNECTIONS, UDP_NEW_CONNECTIONS};
+use crate::subscription::budget::{self, Priority};
 use crate::subscription::{Subscription, Trackable};
 
 use std::cmp;
@@ -54,8 +61,14 @@ where
     table: LinkedHashMap<ConnId, Conn<T>>,
     /// Manages connection timeouts.
     timerwheel: TimerWheel,
+    /// Connections announced by signalling sessions (e.g., SIP media streams) on any core.
+    expected: ExpectedTable,
     /// ID of the core that the table is assigned to.
     core_id: CoreId,
+    /// Offloads connections that no longer require processing, if configured.
+    shunter: Option<Shunter>,
+    /// Fraction of the core's RX descriptors in use, as last reported by the RX core.
+    rx_load: f64,
 }
 
 impl<T> ConnTracker<T>
@@ -69,12 +82,17 @@ where
             cmp::max(config.tcp_inactivity_timeout, config.udp_inactivity_timeout),
             config.timeout_resolution,
         );
+        let expected = ExpectedTable::new(config.expected_timeout, config.max_connections);
+        let shunter = config.shunt.clone().map(Shunter::new);
         ConnTracker {
             config,
             registry,
             table,
             timerwheel,
+            expected,
             core_id,
+            shunter,
+            rx_load: 0.0,
         }
     }
 
@@ -92,6 +110,12 @@ where
         subscription: &Subscription<T::Subscribed>,
     ) {
         let conn_id = ConnId::new(ctxt.src, ctxt.dst, ctxt.proto);
+        if let Some(shunter) = &mut self.shunter {
+            if shunter.is_emulated_drop(&conn_id) {
+                return;
+            }
+        }
+        let port_id = mbuf.port();
         match self.table.raw_entry_mut().from_key(&conn_id) {
             RawEntryMut::Occupied(mut occupied) => {
                 let conn = occupied.get_mut();
@@ -118,18 +142,33 @@ where
                 );
                 // Consume PDU for update, reassembly, and/or parsing
                 conn.update(pdu, subscription, &self.registry);
+                self.expected
+                    .insert(conn.info.drain_expected(), conn.last_seen_ts);
 
                 // Delete stale data for connections no longer matching
                 if conn.remove_from_table() {
+                    if let Some(shunter) = &mut self.shunter {
+                        shunter.shunt(&conn_id, &ctxt, port_id, conn.last_seen_ts);
+                    }
                     occupied.remove();
                 } else if conn.drop_pdu() {
                     conn.info.clear();
+                    if let Some(shunter) = &mut self.shunter {
+                        shunter.shunt(&conn_id, &ctxt, port_id, conn.last_seen_ts);
+                    }
                 } else if conn.terminated() {
                     conn.terminate(subscription);
                     occupied.remove();
                 }
             }
             RawEntryMut::Vacant(_) => {
+                let sample =
+                    ConnSample::new(&conn_id, self.config.sample_key, self.config.sample_rate);
+                if !sample.is_tracked() {
+                    NOT_SAMPLED_PKT.inc();
+                    return;
+                }
+                self.update_shed_level();
                 if self.size() < self.config.max_connections {
                     let mut pdu = L4Pdu::new(mbuf, ctxt, true, Instant::now(), Some(0), Some(0));
                     let conn = match ctxt.proto {
@@ -138,6 +177,7 @@ where
                             self.config.max_out_of_order,
                             &pdu,
                             self.core_id,
+                            sample,
                         ),
                         UDP_PROTOCOL => {
                             pdu.flow_ord = None;
@@ -146,15 +186,27 @@ where
                                 self.config.udp_inactivity_timeout,
                                 &pdu,
                                 self.core_id,
+                                sample,
                             )
                         }
                         _ => Err(anyhow!("Invalid L4 Protocol")),
                     };
                     if let Ok(mut conn) = conn {
+                        if ctxt.proto == UDP_PROTOCOL && !self.expected.is_empty() {
+                            if let Some(parser) = self
+                                .expected
+                                .get(&ctxt, conn.last_seen_ts)
+                                .and_then(|expected| self.registry.expected(&expected))
+                            {
+                                conn.info.set_expected(parser);
+                            }
+                        }
                         conn.info.filter_first_packet(subscription);
                         if conn.info.needs_reassembly() {
                             conn.info
                                 .consume_stream(&mut pdu, subscription, &self.registry);
+                            self.expected
+                                .insert(conn.info.drain_expected(), conn.last_seen_ts);
                         } else {
                             conn.info.new_packet(&pdu, subscription);
                         }
@@ -199,6 +251,35 @@ where
     ) {
         self.timerwheel
             .check_inactive(&mut self.table, subscription, now);
+        self.expected.expire(now);
+        if let Some(shunter) = &mut self.shunter {
+            shunter.expire(now);
+        }
+        self.update_shed_level();
+    }
+
+    /// Records the fraction of the core's RX descriptors in use, so that subscriptions are shed
+    /// before the NIC drops packets.
+    pub(crate) fn set_rx_load(&mut self, rx_load: f64) {
+        self.rx_load = rx_load;
+        self.update_shed_level();
+    }
+
+    /// Sets the priority class up to which subscriptions stop admitting new connections on this
+    /// core, based on the occupancy of the table or of the RX queues, whichever is higher.
+    fn update_shed_level(&self) {
+        if let Some(shedding) = &self.config.shedding {
+            let table_load = self.size() as f64 / self.config.max_connections as f64;
+            let load = table_load.max(self.rx_load);
+            let level = if load >= shedding.normal {
+                Some(Priority::Normal)
+            } else if load >= shedding.low {
+                Some(Priority::Low)
+            } else {
+                None
+            };
+            budget::set_shed_level(level);
+        }
     }
 
     /// Clears the parser registry. Used in testing.
@@ -223,6 +304,25 @@ pub(crate) struct TrackerConfig {
     pub(super) tcp_establish_timeout: usize,
     /// Frequency to check for inactive streams (in milliseconds).
     pub(super) timeout_resolution: usize,
+    /// Time to expire connections announced by signalling sessions (in milliseconds).
+    pub(super) expected_timeout: usize,
+    /// Connection shunting options.
+    pub(super) shunt: Option<ShuntConfig>,
+    /// Tracks one in `sample_rate` connections.
+    pub(super) sample_rate: u32,
+    /// Key of the connection sampling hash.
+    pub(super) sample_key: u64,
+    /// Load shedding options.
+    pub(super) shedding: Option<SheddingConfig>,
+}
+
+impl TrackerConfig {
+    /// Emulates shunting rules in software, for runtimes without a NIC to install them on.
+    pub(crate) fn emulate_shunting(&mut self) {
+        if let Some(shunt) = &mut self.shunt {
+            shunt.emulate = true;
+        }
+    }
 }
 
 impl From<&ConnTrackConfig> for TrackerConfig {
@@ -234,6 +334,11 @@ impl From<&ConnTrackConfig> for TrackerConfig {
             tcp_inactivity_timeout: config.tcp_inactivity_timeout,
             tcp_establish_timeout: config.tcp_establish_timeout,
             timeout_resolution: config.timeout_resolution,
+            expected_timeout: config.expected_timeout,
+            shunt: config.shunt.clone(),
+            sample_rate: config.sampling.as_ref().map_or(1, |s| s.rate),
+            sample_key: config.sampling.as_ref().map_or(0, |s| s.key),
+            shedding: config.shedding.clone(),
         }
     }
 }
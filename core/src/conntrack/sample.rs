@@ -0,0 +1,62 @@
+//! Deterministic connection sampling.
+//!
+//! Connections are sampled by a keyed hash of their `ConnId`. Since the `ConnId` is independent of
+//! packet direction, both directions of a connection share a sampling decision, and the same
+//! connections are sampled on every core and run for a given key. The hash is SipHash-1-3 over a
+//! fixed encoding of the `ConnId`, so sampling decisions are also stable across builds.
+
+use super::conn_id::ConnId;
+
+use siphasher::sip::SipHasher13;
+use std::hash::Hasher;
+
+/// Sampling metadata of a connection.
+///
+/// Callbacks can request `&ConnSample` as a datatype to re-weight results computed over a sample
+/// of connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnSample {
+    /// Keyed hash of the connection's `ConnId`.
+    pub(super) hash: u64,
+    /// The connection tracker tracks one in `rate` connections.
+    rate: u32,
+}
+
+impl ConnSample {
+    /// Computes the sampling hash of `conn_id` under `key`, for a tracker that samples one in
+    /// `rate` connections.
+    pub(crate) fn new(conn_id: &ConnId, key: u64, rate: u32) -> Self {
+        // The 64-bit sampling key is used as both halves of the SipHash key
+        let mut hasher = SipHasher13::new_with_keys(key, key);
+        conn_id.write_stable(&mut hasher);
+        ConnSample {
+            hash: hasher.finish(),
+            rate: rate.max(1),
+        }
+    }
+
+    /// Returns `true` if the connection is in a one-in-`rate` sample of connections.
+    ///
+    /// Samples are nested: a connection sampled at one rate is sampled at every lower rate.
+    #[inline]
+    pub fn is_sampled(&self, rate: u32) -> bool {
+        rate <= 1 || self.hash < u64::MAX / rate as u64
+    }
+
+    /// Returns `true` if the connection is sampled by the connection tracker.
+    #[inline]
+    pub(crate) fn is_tracked(&self) -> bool {
+        self.is_sampled(self.rate)
+    }
+
+    /// Returns the connection tracker's sampling rate (one in `rate` connections).
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Returns the factor to scale results by for a subscription that samples one in `rate`
+    /// connections (`1` if the subscription is not sampled).
+    pub fn weight(&self, rate: u32) -> u32 {
+        self.rate.max(rate)
+    }
+}
@@ -0,0 +1,188 @@
+//! Dynamic offloading of connections that no longer require processing.
+//!
+//! When no subscription needs further data from a connection, the `ConnTracker` asks its
+//! `Shunter` to drop the remainder of the connection before it reaches the CPU. The `Shunter`
+//! installs one drop rule per direction, bounded by a per-core installation rate and a global rule
+//! budget, and removes rules once they have stopped matching traffic for the configured idle
+//! timeout.
+
+use super::conn_id::ConnId;
+use super::pdu::L4Context;
+use crate::config::ShuntConfig;
+#[cfg(not(feature = "heap_mbuf"))]
+use crate::filter::ShuntRule;
+#[cfg(not(feature = "heap_mbuf"))]
+use crate::port::PortId;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+/// Number of rules installed across all cores.
+static NB_RULES: AtomicUsize = AtomicUsize::new(0);
+
+/// Rules dropping both directions of a shunted connection.
+enum Shunted {
+    /// Flow rules installed on the NIC.
+    #[cfg(not(feature = "heap_mbuf"))]
+    Hardware([ShuntRule; 2]),
+    /// Rules emulated in software, with the number of packets matched since the last check.
+    Emulated(u64),
+}
+
+/// Installs and expires per-connection drop rules for a single core.
+pub(crate) struct Shunter {
+    config: ShuntConfig,
+    idle_timeout: Duration,
+    /// Shunted connections.
+    shunted: HashMap<ConnId, Shunted>,
+    /// Shunted connections, in order of their next idle check.
+    checks: VecDeque<(Instant, ConnId)>,
+    /// Start of the current one-second rate limiting window.
+    window_start: Instant,
+    /// Number of connections shunted in the current window.
+    window_installs: usize,
+}
+
+impl Shunter {
+    pub(crate) fn new(config: ShuntConfig) -> Self {
+        let idle_timeout = Duration::from_millis(config.idle_timeout as u64);
+        Shunter {
+            config,
+            idle_timeout,
+            shunted: HashMap::new(),
+            checks: VecDeque::new(),
+            window_start: Instant::now(),
+            window_installs: 0,
+        }
+    }
+
+    /// Returns `true` if the packet belongs to a connection shunted in software, in which case it
+    /// should be discarded.
+    #[inline]
+    pub(crate) fn is_emulated_drop(&mut self, conn_id: &ConnId) -> bool {
+        if self.shunted.is_empty() {
+            return false;
+        }
+        match self.shunted.get_mut(conn_id) {
+            Some(Shunted::Emulated(hits)) => {
+                *hits += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Attempts to drop the remainder of the connection of `ctxt`, received on port `port_id`.
+    /// Returns `true` if rules were installed.
+    pub(crate) fn shunt(
+        &mut self,
+        conn_id: &ConnId,
+        ctxt: &L4Context,
+        port_id: u16,
+        now: Instant,
+    ) -> bool {
+        if self.shunted.contains_key(conn_id) {
+            return false;
+        }
+        self.expire(now);
+
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.window_installs = 0;
+        }
+        if self.window_installs >= self.config.max_rate {
+            return false;
+        }
+
+        // Reserve budget for one rule per direction
+        if NB_RULES.fetch_add(2, Ordering::Relaxed) + 2 > self.config.max_rules {
+            NB_RULES.fetch_sub(2, Ordering::Relaxed);
+            return false;
+        }
+        self.window_installs += 1;
+        match self.install(ctxt, port_id) {
+            Ok(shunted) => {
+                log::debug!("Shunted connection {}", conn_id);
+                self.shunted.insert(conn_id.clone(), shunted);
+                self.checks
+                    .push_back((now + self.idle_timeout, conn_id.clone()));
+                true
+            }
+            Err(error) => {
+                log::debug!("Failed to shunt connection {}: {}", conn_id, error);
+                NB_RULES.fetch_sub(2, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Removes rules that have not matched any packets for the idle timeout.
+    pub(crate) fn expire(&mut self, now: Instant) {
+        while let Some((check_at, _)) = self.checks.front() {
+            if *check_at > now {
+                break;
+            }
+            let (_, conn_id) = self.checks.pop_front().unwrap();
+            let active = match self.shunted.get_mut(&conn_id) {
+                Some(shunted) => Self::take_hits(shunted) > 0,
+                None => continue,
+            };
+            if active {
+                self.checks.push_back((now + self.idle_timeout, conn_id));
+            } else if let Some(shunted) = self.shunted.remove(&conn_id) {
+                log::debug!("Expiring shunt rules for {}", conn_id);
+                if let Err(error) = Self::remove(shunted) {
+                    log::warn!("{}", error);
+                }
+                NB_RULES.fetch_sub(2, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the number of packets matched since the last check.
+    fn take_hits(shunted: &mut Shunted) -> u64 {
+        match shunted {
+            #[cfg(not(feature = "heap_mbuf"))]
+            Shunted::Hardware(rules) => rules
+                .iter()
+                .map(|rule| rule.query_hits().unwrap_or(0))
+                .sum(),
+            Shunted::Emulated(hits) => std::mem::take(hits),
+        }
+    }
+
+    #[cfg(not(feature = "heap_mbuf"))]
+    fn install(&self, ctxt: &L4Context, port_id: u16) -> Result<Shunted> {
+        if self.config.emulate {
+            return Ok(Shunted::Emulated(0));
+        }
+        let port_id = PortId(port_id);
+        let forward = ShuntRule::install(port_id, ctxt.src, ctxt.dst, ctxt.proto)?;
+        match ShuntRule::install(port_id, ctxt.dst, ctxt.src, ctxt.proto) {
+            Ok(reverse) => Ok(Shunted::Hardware([forward, reverse])),
+            Err(error) => {
+                forward.destroy()?;
+                Err(error)
+            }
+        }
+    }
+
+    #[cfg(feature = "heap_mbuf")]
+    fn install(&self, _ctxt: &L4Context, _port_id: u16) -> Result<Shunted> {
+        Ok(Shunted::Emulated(0))
+    }
+
+    fn remove(shunted: Shunted) -> Result<()> {
+        match shunted {
+            #[cfg(not(feature = "heap_mbuf"))]
+            Shunted::Hardware([forward, reverse]) => {
+                forward.destroy()?;
+                reverse.destroy()
+            }
+            Shunted::Emulated(_) => Ok(()),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::config::default_config;
 use crate::conntrack::conn::conn_state::NUM_STATE_TRANSITIONS;
 use crate::conntrack::*;
@@ -8,8 +9,10 @@ use crate::protocols::packet::tcp::{SYN, TCP_PROTOCOL};
 use crate::protocols::stream::ParserRegistry;
 use crate::subscription::{Subscribable, Trackable};
 use crate::L4Pdu;
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::Runtime;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
 
 ///// Dummy types /////
 
@@ -82,6 +85,11 @@ fn tracker_config() -> TrackerConfig {
         tcp_inactivity_timeout: 60,
         tcp_establish_timeout: 30,
         timeout_resolution: 10,
+        expected_timeout: 60,
+        shunt: None,
+        sample_rate: 1,
+        sample_key: 0,
+        shedding: None,
     }
 }
 
@@ -106,16 +114,20 @@ fn init_ctxt() -> L4Context {
     }
 }
 
-// Test must be run as `root`
+// Test must be run as `root`, unless built with `heap_mbuf`
 #[test]
 fn core_state_tx() {
+    #[cfg(not(feature = "heap_mbuf"))]
     if !nix::unistd::Uid::effective().is_root() {
         println!("****This test must be run as root. Skipping.****");
         return;
     }
     // Shortcut for initializing DPDK layer
+    #[cfg(not(feature = "heap_mbuf"))]
     let runtime_config = default_config();
+    #[cfg(not(feature = "heap_mbuf"))]
     let runtime: Runtime<TestSubscribable> = Runtime::new(runtime_config, filter).unwrap();
+    #[cfg(not(feature = "heap_mbuf"))]
     let mempool = runtime.offline.unwrap().get_mempool_raw();
 
     // Set up test
@@ -123,7 +135,10 @@ fn core_state_tx() {
     let config = tracker_config();
     let mut conntrack =
         ConnTracker::<TestTrackable>::new(config, TestTrackable::parsers(), CoreId(0));
+    #[cfg(not(feature = "heap_mbuf"))]
     let mbuf = Mbuf::from_bytes(&MBUF, mempool).unwrap();
+    #[cfg(feature = "heap_mbuf")]
+    let mbuf = Mbuf::from_bytes(&MBUF).unwrap();
     let mut ctxt = init_ctxt();
     let conn_id = ConnId::new(ctxt.src, ctxt.dst, ctxt.proto);
 
@@ -207,3 +222,337 @@ fn core_state_tx() {
         );
     }
 }
+
+#[test]
+fn core_shunt_emulated() {
+    let config = ShuntConfig {
+        max_rules: 4,
+        max_rate: 10,
+        idle_timeout: 1000,
+        emulate: true,
+    };
+    let mut shunter = Shunter::new(config);
+    let ctxt = init_ctxt();
+    let conn_id = ConnId::new(ctxt.src, ctxt.dst, ctxt.proto);
+    let start = Instant::now();
+
+    assert!(!shunter.is_emulated_drop(&conn_id));
+    assert!(shunter.shunt(&conn_id, &ctxt, 0, start));
+    assert!(
+        !shunter.shunt(&conn_id, &ctxt, 0, start),
+        "Connection should only be shunted once."
+    );
+    assert!(shunter.is_emulated_drop(&conn_id));
+
+    // Rule matched traffic in the last interval, so it is kept
+    shunter.expire(start + Duration::from_millis(1000));
+    assert!(shunter.is_emulated_drop(&conn_id));
+
+    // Rule is kept for another interval, then expires once idle
+    shunter.expire(start + Duration::from_millis(2000));
+    assert!(shunter.is_emulated_drop(&conn_id));
+    shunter.expire(start + Duration::from_millis(3000));
+    shunter.expire(start + Duration::from_millis(4000));
+    assert!(
+        !shunter.is_emulated_drop(&conn_id),
+        "Idle rule should have expired."
+    );
+}
+
+#[test]
+fn core_conn_sampling() {
+    let ctxt = init_ctxt();
+    let mut sampled = 0;
+    for port in 1..=4096 {
+        let src = SocketAddr::new(ctxt.src.ip(), port);
+        let fwd = ConnSample::new(&ConnId::new(src, ctxt.dst, TCP_PROTOCOL), 7, 16);
+        let rev = ConnSample::new(&ConnId::new(ctxt.dst, src, TCP_PROTOCOL), 7, 16);
+        assert_eq!(
+            fwd, rev,
+            "Both directions should share a sampling decision."
+        );
+        assert!(fwd.is_sampled(1));
+        // Samples are nested
+        if fwd.is_sampled(64) {
+            assert!(fwd.is_sampled(16));
+        }
+        if fwd.is_tracked() {
+            sampled += 1;
+        }
+        assert_eq!(fwd.weight(1), 16);
+        assert_eq!(fwd.weight(64), 64);
+    }
+    // Expect ~256 of 4096 connections
+    assert!(
+        (128..384).contains(&sampled),
+        "Sampled {} of 4096 connections at a rate of 1/16",
+        sampled
+    );
+}
+
+#[test]
+fn core_conn_sample_hash() {
+    // Sampling decisions must not change across builds
+    let src = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), 51000);
+    let dst = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34)), 443);
+    let conn_id = ConnId::new(src, dst, TCP_PROTOCOL);
+    let sample = ConnSample::new(&conn_id, 0x0123_4567_89ab_cdef, 16);
+    assert_eq!(sample.hash, 0xe56d_e00c_ad6c_c67a);
+    assert!(!sample.is_tracked());
+}
+
+#[test]
+fn core_shed_rx_load() {
+    use crate::config::SheddingConfig;
+    use crate::subscription::budget::{self, Priority};
+
+    let config = TrackerConfig {
+        shedding: Some(SheddingConfig {
+            low: 0.5,
+            normal: 0.75,
+        }),
+        ..tracker_config()
+    };
+    let mut conntrack =
+        ConnTracker::<TestTrackable>::new(config, TestTrackable::parsers(), CoreId(0));
+
+    // The table is empty, so the level follows the RX queues
+    conntrack.set_rx_load(0.6);
+    assert_eq!(budget::shed_level(), Some(Priority::Low));
+    conntrack.set_rx_load(0.9);
+    assert_eq!(budget::shed_level(), Some(Priority::Normal));
+    conntrack.set_rx_load(0.1);
+    assert_eq!(budget::shed_level(), None);
+}
+
+#[cfg(feature = "heap_mbuf")]
+#[test]
+fn core_expected_conn() {
+    use crate::protocols::packet::udp::UDP_PROTOCOL;
+    use crate::protocols::stream::{testing, SessionData, SessionProto};
+
+    const INVITE: &[u8] = b"INVITE sip:bob@example.com SIP/2.0\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK74bf9\r\n\
+        From: <sip:alice@example.com>;tag=9fxced76sl\r\n\
+        To: <sip:bob@example.com>\r\n\
+        Call-ID: 3848276298220188511@atlanta.example.com\r\n\
+        CSeq: 1 INVITE\r\n\
+        Content-Type: application/sdp\r\n\
+        \r\n\
+        v=0\r\n\
+        o=alice 2890844526 2890844526 IN IP4 10.0.0.1\r\n\
+        s=-\r\n\
+        c=IN IP4 10.0.0.1\r\n\
+        t=0 0\r\n\
+        m=audio 42170 RTP/AVP 0\r\n\
+        a=rtpmap:0 PCMU/8000\r\n";
+    const OK: &[u8] = b"SIP/2.0 200 OK\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK74bf9\r\n\
+        From: <sip:alice@example.com>;tag=9fxced76sl\r\n\
+        To: <sip:bob@example.com>;tag=8321234356\r\n\
+        Call-ID: 3848276298220188511@atlanta.example.com\r\n\
+        CSeq: 1 INVITE\r\n\
+        Content-Type: application/sdp\r\n\
+        \r\n\
+        v=0\r\n\
+        o=bob 2808844564 2808844564 IN IP4 10.0.0.2\r\n\
+        s=-\r\n\
+        c=IN IP4 10.0.0.2\r\n\
+        t=0 0\r\n\
+        m=audio 43456 RTP/AVP 0\r\n\
+        a=rtpmap:0 PCMU/8000\r\n\
+        a=rtcp-mux\r\n";
+
+    fn filter() -> FilterFactory<TestTrackable> {
+        fn packet_filter(_mbuf: &Mbuf, _core_id: &CoreId) -> bool {
+            true
+        }
+        fn state_tx(conn: &mut ConnInfo<TestTrackable>, tx: &StateTransition) {
+            if matches!(tx, StateTransition::L4FirstPacket) {
+                conn.linfo.actions.active |= Actions::PassThrough;
+                conn.layers[0].layer_info_mut().actions.active |= Actions::Parse;
+            }
+        }
+        fn update(_conn: &mut ConnInfo<TestTrackable>, _pdu: &L4Pdu, _state: DataLevel) -> bool {
+            false
+        }
+        FilterFactory::new("", packet_filter, state_tx, update)
+    }
+
+    let subscription = Subscription::<TestSubscribable>::new(filter());
+    let registry = || ParserRegistry::from_strings(vec!["sip", "rtp"]);
+    let config = || TrackerConfig {
+        expected_timeout: 60_000,
+        ..tracker_config()
+    };
+    let mut signalling = ConnTracker::<TestTrackable>::new(config(), registry(), CoreId(0));
+    let mut media = ConnTracker::<TestTrackable>::new(config(), registry(), CoreId(1));
+
+    // The 200 OK announces the callee's media endpoint
+    for pdu in [
+        testing::udp(INVITE, 5060, true),
+        testing::udp(OK, 5060, false),
+    ] {
+        signalling.process(pdu.mbuf, pdu.ctxt, &subscription);
+    }
+    assert_eq!(signalling.size(), 1);
+
+    // RTP (not RTCP) cannot be probed, so the media flow is only recognized as announced, even
+    // when RSS assigns it to another core
+    let mut rtp = vec![0x80, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xa0];
+    rtp.extend_from_slice(&0x1234_5678u32.to_be_bytes());
+    rtp.resize(12 + 160, 0xff);
+    let pdu = testing::pdu(&rtp, UDP_PROTOCOL, 42170, 43456, true);
+    let conn_id = ConnId::new(pdu.ctxt.src, pdu.ctxt.dst, pdu.ctxt.proto);
+    media.process(pdu.mbuf, pdu.ctxt, &subscription);
+    let conn = media
+        .table
+        .get_mut(&conn_id)
+        .expect("Connection should exist");
+    assert!(matches!(
+        conn.info.layers[0].last_protocol(),
+        SessionProto::Rtp
+    ));
+    let sessions = conn.info.layers[0].drain_sessions();
+    match &sessions[0].data {
+        SessionData::Rtp(session) => assert_eq!(
+            session.call_id.as_deref(),
+            Some("3848276298220188511@atlanta.example.com")
+        ),
+        _ => panic!("Expected an RTP session"),
+    }
+
+    // A flow to an endpoint that was not announced is not associated with the call
+    let pdu = testing::pdu(&rtp, UDP_PROTOCOL, 40000, 40002, true);
+    let conn_id = ConnId::new(pdu.ctxt.src, pdu.ctxt.dst, pdu.ctxt.proto);
+    media.process(pdu.mbuf, pdu.ctxt, &subscription);
+    let conn = media.table.get(&conn_id).expect("Connection should exist");
+    assert!(!matches!(
+        conn.info.layers[0].last_protocol(),
+        SessionProto::Rtp
+    ));
+}
+
+#[cfg(feature = "heap_mbuf")]
+#[test]
+fn core_starttls() {
+    use crate::protocols::packet::tcp::ACK;
+    use crate::protocols::stream::{testing, Session, SessionData, SessionProto};
+    use std::cell::RefCell;
+
+    const BANNER: &[u8] = b"220 mx.example.com ESMTP Postfix (Debian/GNU)\r\n";
+    const EHLO_REPLY: &[u8] = b"250-mx.example.com\r\n250-PIPELINING\r\n250-STARTTLS\r\n\
+        250 8BITMIME\r\n";
+    // Fatal handshake_failure alert
+    const ALERT: &[u8] = &[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28];
+
+    thread_local! {
+        // Sessions in the L7 layer at each L7EndHdrs state transition
+        static SEEN: RefCell<Vec<Vec<String>>> = RefCell::new(vec![]);
+    }
+
+    fn client_hello(sni: &str) -> Vec<u8> {
+        let name = sni.as_bytes();
+        let mut ext = vec![0x00, 0x00]; // server_name
+        ext.extend_from_slice(&(name.len() as u16 + 5).to_be_bytes());
+        ext.extend_from_slice(&(name.len() as u16 + 3).to_be_bytes());
+        ext.push(0x00); // host_name
+        ext.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        ext.extend_from_slice(name);
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0; 32]); // random
+        body.push(0x00); // session ID
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // TLS_AES_128_GCM_SHA256
+        body.extend_from_slice(&[0x01, 0x00]); // null compression
+        body.extend_from_slice(&(ext.len() as u16).to_be_bytes());
+        body.extend_from_slice(&ext);
+        let mut handshake = vec![0x01, 0x00];
+        handshake.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(&body);
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    fn label(session: &Session) -> String {
+        match &session.data {
+            SessionData::Smtp(_) => "smtp".to_string(),
+            SessionData::Tls(tls) => tls.sni().to_string(),
+            _ => "other".to_string(),
+        }
+    }
+
+    // Parses until the end of each session's headers, as generated for a subscription that
+    // requires the session following the SMTP session
+    fn filter() -> FilterFactory<TestTrackable> {
+        fn packet_filter(_mbuf: &Mbuf, _core_id: &CoreId) -> bool {
+            true
+        }
+        fn state_tx(conn: &mut ConnInfo<TestTrackable>, tx: &StateTransition) {
+            let parse = |conn: &mut ConnInfo<TestTrackable>| {
+                let actions = &mut conn.layers[0].layer_info_mut().actions;
+                actions.active |= Actions::Parse;
+                actions.refresh_at[StateTransition::L7EndHdrs.as_usize()] |= Actions::Parse;
+            };
+            match tx {
+                StateTransition::L4FirstPacket => {
+                    conn.linfo.actions.active |= Actions::PassThrough;
+                    parse(conn);
+                }
+                StateTransition::L7EndHdrs => {
+                    let sessions: Vec<String> =
+                        conn.layers[0].sessions().iter().map(label).collect();
+                    SEEN.with(|seen| seen.borrow_mut().push(sessions));
+                    if matches!(conn.layers[0].last_protocol(), SessionProto::Smtp) {
+                        parse(conn);
+                    }
+                }
+                _ => (),
+            }
+        }
+        fn update(_conn: &mut ConnInfo<TestTrackable>, _pdu: &L4Pdu, _state: DataLevel) -> bool {
+            false
+        }
+        FilterFactory::new("", packet_filter, state_tx, update)
+    }
+
+    let subscription = Subscription::<TestSubscribable>::new(filter());
+    let mut conntrack = ConnTracker::<TestTrackable>::new(
+        tracker_config(),
+        ParserRegistry::from_strings(vec!["smtp", "tls"]),
+        CoreId(0),
+    );
+
+    // Next sequence number of the server (0) and client (1)
+    let mut next_seq = [5000, 1000];
+    let mut send = |payload: &[u8], dir: bool, flags: u8| {
+        let mut pdu = testing::tcp(payload, 25, dir);
+        pdu.ctxt.seq_no = next_seq[dir as usize];
+        pdu.ctxt.ack_no = next_seq[!dir as usize];
+        pdu.ctxt.flags = flags;
+        next_seq[dir as usize] += payload.len() as u32;
+        if flags & SYN != 0 {
+            next_seq[dir as usize] += 1;
+        }
+        conntrack.process(pdu.mbuf, pdu.ctxt, &subscription);
+    };
+    send(b"", true, SYN);
+    send(b"", false, SYN | ACK);
+    send(BANNER, false, ACK);
+    send(b"EHLO client.example.org\r\n", true, ACK);
+    send(EHLO_REPLY, false, ACK);
+    send(b"STARTTLS\r\n", true, ACK);
+    send(b"220 2.0.0 Ready to start TLS\r\n", false, ACK);
+    // The SMTP session is done, but the stream is still parsed as TLS
+    send(&client_hello("mail.example.com"), true, ACK);
+    send(ALERT, false, ACK);
+
+    SEEN.with(|seen| {
+        assert_eq!(
+            *seen.borrow(),
+            vec![vec!["smtp"], vec!["smtp", "mail.example.com"]],
+            "The TLS session should follow the SMTP session in the same connection."
+        );
+    });
+}
@@ -0,0 +1,36 @@
+//! Replacements for the DPDK functions exported by Iris when built with the `heap_mbuf` feature,
+//! which does not link against DPDK.
+
+/// Returns the application thread ID of the execution unit.
+///
+/// ## Remarks
+/// Without DPDK, offline analysis runs on the calling thread, which is always reported as core
+/// `0`. This is `unsafe` only for compatibility with the DPDK version.
+#[inline]
+pub unsafe fn rte_lcore_id() -> u16 {
+    0
+}
+
+/// Reads the timestamp counter (TSC) register.
+///
+/// ## Remarks
+/// This is `unsafe` only for compatibility with the DPDK version.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn rte_rdtsc() -> u64 {
+    std::arch::x86_64::_rdtsc()
+}
+
+/// Returns nanoseconds elapsed since the first call, on platforms without a TSC register.
+///
+/// ## Remarks
+/// This is `unsafe` only for compatibility with the DPDK version.
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn rte_rdtsc() -> u64 {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_nanos() as u64
+}
@@ -81,6 +81,14 @@ impl rte_mbuf {
         unsafe { self.data_off }
     }
 
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn get_ol_flags(&self) -> u64 {
+        self.ol_flags
+    }
+
     pub fn get_rss_hash(&self) -> u32 {
         unsafe { self.__bindgen_anon_2.hash.rss }
     }
@@ -120,6 +128,14 @@ impl rte_mbuf {
         unsafe { self.__bindgen_anon_1.__bindgen_anon_1.data_off }
     }
 
+    pub fn get_port(&self) -> u16 {
+        unsafe { self.__bindgen_anon_1.__bindgen_anon_1.port }
+    }
+
+    pub fn get_ol_flags(&self) -> u64 {
+        self.ol_flags
+    }
+
     pub fn get_rss_hash(&self) -> u32 {
         unsafe {
             self.__bindgen_anon_2
@@ -181,6 +197,9 @@ pub use RTE_ETH_RSS_TCP as ETH_RSS_TCP;
 pub use RTE_ETH_RSS_UDP as ETH_RSS_UDP;
 #[cfg(dpdk_ge_2311)]
 pub use RTE_ETH_VLAN_STRIP_OFFLOAD as DEV_RX_OFFLOAD_VLAN_STRIP;
+// PKT_RX_* flags are deprecated in v21.11 and removed in v22.11.
+#[cfg(dpdk_ge_2111)]
+pub use RTE_MBUF_F_RX_FDIR_ID as PKT_RX_FDIR_ID;
 
 #[link(name = "inlined")]
 extern "C" {
@@ -198,6 +217,7 @@ extern "C" {
         rx_pkts: *mut *mut rte_mbuf,
         nb_pkts: u16,
     ) -> u16;
+    fn rte_eth_rx_queue_count_(port_id: u16, queue_id: u16) -> c_int;
     fn rte_mbuf_refcnt_read_(m: *const rte_mbuf) -> u16;
     fn rte_mbuf_refcnt_update_(m: *mut rte_mbuf, value: i16) -> u16;
     fn rte_mbuf_refcnt_set_(m: *mut rte_mbuf, value: i16);
@@ -269,6 +289,11 @@ pub unsafe fn rte_eth_rx_burst(
     rte_eth_rx_burst_(port_id, queue_id, rx_pkts, nb_pkts)
 }
 
+#[inline]
+pub unsafe fn rte_eth_rx_queue_count(port_id: u16, queue_id: u16) -> c_int {
+    rte_eth_rx_queue_count_(port_id, queue_id)
+}
+
 #[inline]
 pub unsafe fn rte_mbuf_refcnt_read(m: *const rte_mbuf) -> u16 {
     rte_mbuf_refcnt_read_(m)
@@ -1,3 +1,4 @@
+#[cfg(not(feature = "heap_mbuf"))]
 use super::hardware;
 
 use std::collections::HashSet;
@@ -15,8 +16,6 @@ use petgraph::graph::Graph;
 use petgraph::graph::NodeIndex;
 use regex::Regex;
 
-use crate::port::Port;
-
 lazy_static! {
     pub(crate) static ref LAYERS: Graph::<ProtocolName, ()> = {
         let mut g = Graph::<ProtocolName, ()>::new();
@@ -30,6 +29,30 @@ lazy_static! {
         let dns      = g.add_node(protocol!("dns"));
         let quic     = g.add_node(protocol!("quic"));
         let ssh      = g.add_node(protocol!("ssh"));
+        let smtp     = g.add_node(protocol!("smtp"));
+        let imap     = g.add_node(protocol!("imap"));
+        let pop3     = g.add_node(protocol!("pop3"));
+        let dhcp     = g.add_node(protocol!("dhcp"));
+        let dhcpv6   = g.add_node(protocol!("dhcpv6"));
+        let ntp      = g.add_node(protocol!("ntp"));
+        let mdns     = g.add_node(protocol!("mdns"));
+        let llmnr    = g.add_node(protocol!("llmnr"));
+        let ssdp     = g.add_node(protocol!("ssdp"));
+        let openvpn  = g.add_node(protocol!("openvpn"));
+        let wireguard = g.add_node(protocol!("wireguard"));
+        let smb      = g.add_node(protocol!("smb"));
+        let kerberos = g.add_node(protocol!("kerberos"));
+        let postgres = g.add_node(protocol!("postgres"));
+        let mysql    = g.add_node(protocol!("mysql"));
+        let redis    = g.add_node(protocol!("redis"));
+        let sip      = g.add_node(protocol!("sip"));
+        let rtp      = g.add_node(protocol!("rtp"));
+        let mqtt     = g.add_node(protocol!("mqtt"));
+        let coap     = g.add_node(protocol!("coap"));
+        let modbus   = g.add_node(protocol!("modbus"));
+        let bittorrent = g.add_node(protocol!("bittorrent"));
+        let rdp      = g.add_node(protocol!("rdp"));
+        let vnc      = g.add_node(protocol!("vnc"));
         // define valid outer layers for each protocol header
         g.extend_with_edges([
             (ipv4, ethernet),
@@ -41,6 +64,30 @@ lazy_static! {
             (dns, udp), (dns, tcp),
             (quic, udp), //TODO: tls over quic
             (ssh, tcp),
+            (smtp, tcp),
+            (imap, tcp),
+            (pop3, tcp),
+            (dhcp, udp),
+            (dhcpv6, udp),
+            (ntp, udp),
+            (mdns, udp),
+            (llmnr, udp),
+            (ssdp, udp),
+            (openvpn, udp), (openvpn, tcp),
+            (wireguard, udp),
+            (smb, tcp),
+            (kerberos, udp), (kerberos, tcp),
+            (postgres, tcp),
+            (mysql, tcp),
+            (redis, tcp),
+            (sip, udp), (sip, tcp),
+            (rtp, udp),
+            (mqtt, tcp),
+            (coap, udp),
+            (modbus, tcp),
+            (bittorrent, tcp), (bittorrent, udp),
+            (rdp, tcp),
+            (vnc, tcp),
         ]);
         g
     };
@@ -87,6 +134,15 @@ pub enum Predicate {
         op: BinOp,
         value: Value,
     },
+    /// Compares two expressions over the fields of a protocol,
+    /// e.g., `tcp.src_port < tcp.dst_port` or `len(tls.sni) > 64`.
+    /// Comparisons that reduce to a field and a literal are `Binary`.
+    Compare {
+        protocol: ProtocolName,
+        lhs: Expr,
+        op: BinOp,
+        rhs: Expr,
+    },
     /// Opaque filter function provided by user
     Custom {
         /// Filter name (function or struct (group) if present)
@@ -122,6 +178,7 @@ impl Predicate {
         match self {
             Predicate::Unary { protocol } => protocol,
             Predicate::Binary { protocol, .. } => protocol,
+            Predicate::Compare { protocol, .. } => protocol,
             _ => ProtocolName::none(),
         }
     }
@@ -141,8 +198,14 @@ impl Predicate {
     }
 
     // Returns `true` if predicate is a binary constraint.
+    // This includes comparisons between expressions.
     pub fn is_binary(&self) -> bool {
-        matches!(self, Predicate::Binary { .. })
+        matches!(self, Predicate::Binary { .. } | Predicate::Compare { .. })
+    }
+
+    // Returns `true` if predicate compares two expressions.
+    pub fn is_compare(&self) -> bool {
+        matches!(self, Predicate::Compare { .. })
     }
 
     // Returns `true` if predicate is a black-box function defined by a user.
@@ -248,6 +311,13 @@ impl Predicate {
                 && field_name.name() != "addr"
                 && !ConnData::supported_fields().contains(&field_name.name());
         }
+        if let Predicate::Compare { lhs, rhs, .. } = self {
+            return lhs
+                .fields()
+                .into_iter()
+                .chain(rhs.fields())
+                .any(|f| !ConnData::supported_fields().contains(&f.name()));
+        }
         !ConnData::supported_protocols().contains(&self.get_protocol().name())
     }
 
@@ -406,8 +476,52 @@ impl Predicate {
         }
     }
 
-    // Returns `true` if predicate can be pushed down to hardware port.
-    pub(super) fn is_hardware_filterable(&self, port: &Port) -> bool {
+    // Builds a comparison between two (validated) expressions on `protocol`.
+    // Symmetric comparisons are written in a canonical order so that e.g.
+    // `tcp.src_port < tcp.dst_port` and `tcp.dst_port > tcp.src_port` are
+    // deduplicated. A field compared to a literal is returned as `Binary`.
+    pub(crate) fn compare(protocol: ProtocolName, lhs: Expr, op: BinOp, rhs: Expr) -> Predicate {
+        let (mut lhs, mut op, mut rhs) = (lhs.normalize(), op, rhs.normalize());
+        if let Some(flipped) = op.flip() {
+            let swap = match (lhs.is_literal(), rhs.is_literal()) {
+                (true, false) => true,
+                (false, true) => false,
+                _ => rhs < lhs,
+            };
+            if swap {
+                std::mem::swap(&mut lhs, &mut rhs);
+                op = flipped;
+            }
+        }
+        match (lhs, rhs) {
+            (Expr::Field { protocol, field }, Expr::Int(val)) => Predicate::Binary {
+                protocol,
+                field,
+                op,
+                value: Value::Int(val),
+            },
+            (Expr::Field { protocol, field }, Expr::Text(text)) => Predicate::Binary {
+                protocol,
+                field,
+                op,
+                value: Value::Text(text),
+            },
+            (lhs, rhs) => Predicate::Compare {
+                protocol,
+                lhs,
+                op,
+                rhs,
+            },
+        }
+    }
+
+    // Returns Ok if predicate can be pushed down to hardware port,
+    // or the reason it cannot.
+    #[cfg(not(feature = "heap_mbuf"))]
+    pub(super) fn is_hardware_filterable(
+        &self,
+        port: &dyn hardware::FlowDevice,
+    ) -> Result<(), hardware::HardwareFilterError> {
         hardware::device_supported(self, port)
     }
 
@@ -508,6 +622,28 @@ impl Predicate {
                 }
             }
         }
+
+        // Same expression compared to integers
+        // E.g.: `len(tls.sni) > 64` | `len(tls.sni) < 32`
+        if let (
+            Predicate::Compare {
+                lhs,
+                op,
+                rhs: Expr::Int(v),
+                ..
+            },
+            Predicate::Compare {
+                lhs: peer_lhs,
+                op: peer_op,
+                rhs: Expr::Int(peer_v),
+                ..
+            },
+        ) = (self, pred)
+        {
+            if lhs == peer_lhs {
+                return is_excl_int(*v, *v, op, *peer_v, *peer_v, peer_op);
+            }
+        }
         false
     }
 
@@ -643,6 +779,29 @@ impl Predicate {
             }
         }
 
+        // Same expression compared to integers
+        // E.g.: `len(tls.sni) > 128` is a child of `len(tls.sni) > 64`
+        if let (
+            Predicate::Compare {
+                lhs,
+                op,
+                rhs: Expr::Int(v),
+                ..
+            },
+            Predicate::Compare {
+                lhs: parent_lhs,
+                op: parent_op,
+                rhs: Expr::Int(parent_v),
+                ..
+            },
+        ) = (self, pred)
+        {
+            if lhs == parent_lhs {
+                return is_parent_int(*v, *v, op, *parent_v, *parent_v, parent_op);
+            }
+            return false;
+        }
+
         self.is_binary() && pred.is_unary()
     }
 }
@@ -1036,6 +1195,7 @@ impl fmt::Display for Predicate {
                 op,
                 value,
             } => write!(f, "{}.{} {} {}", protocol, field, op, value),
+            Predicate::Compare { lhs, op, rhs, .. } => write!(f, "{} {} {}", lhs, op, rhs),
             Predicate::Custom { name, matched, .. } => {
                 let state = if *matched { "matched" } else { "matching" };
                 write!(f, "{name}.{state}")
@@ -1155,6 +1315,22 @@ impl fmt::Display for BinOp {
     }
 }
 
+impl BinOp {
+    // Returns the operator with its operands swapped (e.g., `a < b` -> `b > a`),
+    // or `None` if the operator is not symmetric.
+    pub fn flip(&self) -> Option<BinOp> {
+        match self {
+            BinOp::Eq => Some(BinOp::Eq),
+            BinOp::Ne => Some(BinOp::Ne),
+            BinOp::Ge => Some(BinOp::Le),
+            BinOp::Le => Some(BinOp::Ge),
+            BinOp::Gt => Some(BinOp::Lt),
+            BinOp::Lt => Some(BinOp::Gt),
+            _ => None,
+        }
+    }
+}
+
 /// Allowed RHS values in a binary predicate
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Value {
@@ -1183,9 +1359,169 @@ impl fmt::Display for Value {
     }
 }
 
+/// Functions that can be applied to fields in a comparison
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExprFn {
+    /// Length of a string or byte field
+    Len,
+    /// Lowercase copy of a string field
+    Lower,
+    /// Number of elements in a list field
+    Count,
+}
+
+impl fmt::Display for ExprFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            ExprFn::Len => write!(f, "len"),
+            ExprFn::Lower => write!(f, "lower"),
+            ExprFn::Count => write!(f, "count"),
+        }
+    }
+}
+
+/// Integer arithmetic operators in a comparison
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl ArithOp {
+    // Returns `true` if the operands can be swapped
+    pub fn is_commutative(&self) -> bool {
+        matches!(self, ArithOp::Add | ArithOp::Mul)
+    }
+}
+
+impl fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            ArithOp::Add => write!(f, "+"),
+            ArithOp::Sub => write!(f, "-"),
+            ArithOp::Mul => write!(f, "*"),
+            ArithOp::Div => write!(f, "/"),
+            ArithOp::Rem => write!(f, "%"),
+        }
+    }
+}
+
+/// Operand of a comparison between expressions.
+/// Integer expressions are evaluated as u64.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Expr {
+    Field {
+        protocol: ProtocolName,
+        field: FieldName,
+    },
+    Int(u64),
+    Text(String),
+    Call {
+        func: ExprFn,
+        arg: Box<Expr>,
+    },
+    Arith {
+        lhs: Box<Expr>,
+        op: ArithOp,
+        rhs: Box<Expr>,
+    },
+}
+
+impl Expr {
+    // Returns `true` if the expression is a literal value.
+    pub fn is_literal(&self) -> bool {
+        matches!(self, Expr::Int(_) | Expr::Text(_))
+    }
+
+    // Returns `true` if the expression always evaluates to an integer.
+    // Fields may be of any type.
+    pub fn is_int(&self) -> bool {
+        match self {
+            Expr::Int(_) | Expr::Arith { .. } => true,
+            Expr::Call { func, .. } => matches!(func, ExprFn::Len | ExprFn::Count),
+            _ => false,
+        }
+    }
+
+    // Returns `true` if the expression always evaluates to a string.
+    pub fn is_text(&self) -> bool {
+        match self {
+            Expr::Text(_) => true,
+            Expr::Call { func, .. } => matches!(func, ExprFn::Lower),
+            _ => false,
+        }
+    }
+
+    // Returns the fields referenced by the expression.
+    pub fn fields(&self) -> Vec<&FieldName> {
+        match self {
+            Expr::Field { field, .. } => vec![field],
+            Expr::Int(_) | Expr::Text(_) => vec![],
+            Expr::Call { arg, .. } => arg.fields(),
+            Expr::Arith { lhs, rhs, .. } => lhs.fields().into_iter().chain(rhs.fields()).collect(),
+        }
+    }
+
+    // Returns the protocols referenced by the expression.
+    pub fn protocols(&self) -> Vec<&ProtocolName> {
+        match self {
+            Expr::Field { protocol, .. } => vec![protocol],
+            Expr::Int(_) | Expr::Text(_) => vec![],
+            Expr::Call { arg, .. } => arg.protocols(),
+            Expr::Arith { lhs, rhs, .. } => {
+                lhs.protocols().into_iter().chain(rhs.protocols()).collect()
+            }
+        }
+    }
+
+    // Returns an equivalent expression with the operands of commutative
+    // operators in sorted order, so that e.g. `a + b` and `b + a` are equal.
+    pub fn normalize(self) -> Expr {
+        match self {
+            Expr::Call { func, arg } => Expr::Call {
+                func,
+                arg: Box::new(arg.normalize()),
+            },
+            Expr::Arith { lhs, op, rhs } => {
+                let (lhs, rhs) = (lhs.normalize(), rhs.normalize());
+                if op.is_commutative() && rhs < lhs {
+                    Expr::Arith {
+                        lhs: Box::new(rhs),
+                        op,
+                        rhs: Box::new(lhs),
+                    }
+                } else {
+                    Expr::Arith {
+                        lhs: Box::new(lhs),
+                        op,
+                        rhs: Box::new(rhs),
+                    }
+                }
+            }
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Expr::Field { protocol, field } => write!(f, "{}.{}", protocol, field),
+            Expr::Int(val) => write!(f, "{}", val),
+            Expr::Text(val) => write!(f, "'{}'", val),
+            Expr::Call { func, arg } => write!(f, "{}({})", func, arg),
+            Expr::Arith { lhs, op, rhs } => write!(f, "({} {} {})", lhs, op, rhs),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::filter::parser::FilterParser;
     use std::net::Ipv4Addr;
 
     #[test]
@@ -1560,4 +1896,46 @@ mod tests {
         assert!(ssh_eq_byte2.is_excl(&ssh_contains_byte));
         assert!(ssh_contains_byte.is_excl(&ssh_eq_byte2));
     }
+
+    #[test]
+    fn core_ast_compare() {
+        let parse = |filter: &str| FilterParser::parse_filter(filter).unwrap()[0][0].clone();
+
+        // Equivalent comparisons are written the same way
+        assert_eq!(
+            parse("tcp.src_port < tcp.dst_port"),
+            parse("tcp.dst_port > tcp.src_port")
+        );
+        assert_eq!(parse("len(tls.sni) > 64"), parse("64 < len(tls.sni)"));
+        assert_eq!(parse("ipv4.ttl + 1 > 64"), parse("1 + ipv4.ttl > 64"));
+        // A field compared to a literal is an ordinary binary predicate
+        assert_eq!(parse("80 = tcp.dst_port"), parse("tcp.dst_port = 80"));
+        assert!(!parse("80 = tcp.dst_port").is_compare());
+
+        let pred = parse("ipv4.ttl % 64 > 50");
+        assert!(pred.is_compare() && pred.on_packet() && pred.req_packet());
+        let pred = parse("tcp.src_port < tcp.dst_port");
+        assert!(pred.on_packet() && !pred.req_packet());
+        let pred = parse("lower(http.user_agent) contains 'curl'");
+        assert!(pred.is_compare() && pred.on_session());
+
+        // Fields from different protocols, type mismatches, combined fields
+        assert!(FilterParser::parse_filter("ipv4.ttl > tcp.src_port").is_err());
+        assert!(FilterParser::parse_filter("len(tls.sni) = 'abc'").is_err());
+        assert!(FilterParser::parse_filter("lower(tls.sni) > 'abc'").is_err());
+        assert!(FilterParser::parse_filter("tcp.port % 2 = 0").is_err());
+        assert!(FilterParser::parse_filter("ipv4.ttl / 0 = 1").is_err());
+
+        // Comparisons of the same expression to integers
+        let long = parse("len(tls.sni) > 128");
+        let short = parse("len(tls.sni) > 64");
+        let tiny = parse("len(tls.sni) < 32");
+        assert!(long.is_child(&short));
+        assert!(!short.is_child(&long));
+        assert!(long.is_excl(&tiny));
+        assert!(!long.is_excl(&parse("count(tls.cipher_suites) < 32")));
+        assert!(short.is_child(&Predicate::Unary {
+            protocol: protocol!("tls")
+        }));
+    }
 }
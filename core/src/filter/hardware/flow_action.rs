@@ -25,7 +25,6 @@ impl FlowAction {
             port_id,
             rss: Vec::<dpdk::rte_flow_action_rss>::new(),
             jump: Vec::<dpdk::rte_flow_action_jump>::new(),
-            #[allow(dead_code)]
             mark: Vec::<dpdk::rte_flow_action_mark>::new(),
         }
     }
@@ -48,7 +47,6 @@ impl FlowAction {
         self.jump.push(jump_conf);
     }
 
-    #[allow(dead_code)]
     pub(super) fn append_mark(&mut self, mark: u32) {
         let mut a_mark: dpdk::rte_flow_action = unsafe { mem::zeroed() };
         a_mark.type_ = dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_MARK;
@@ -83,7 +81,13 @@ impl FlowAction {
         self.rss.push(a_rss_conf);
     }
 
-    #[allow(dead_code)]
+    pub(super) fn append_count(&mut self) {
+        // Counter with default configuration (no conf)
+        let mut a_count: dpdk::rte_flow_action = unsafe { mem::zeroed() };
+        a_count.type_ = dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_COUNT;
+        self.rules.push(a_count);
+    }
+
     pub(super) fn append_drop(&mut self) {
         let mut a_drop: dpdk::rte_flow_action = unsafe { mem::zeroed() };
         a_drop.type_ = dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_DROP;
@@ -94,6 +94,9 @@ impl FlowPattern {
                     bail!(FilterError::InvalidPredType("layerstate".to_owned()))
                 }
                 Predicate::Unary { .. } => bail!(FilterError::InvalidPredType("unary".to_owned())),
+                Predicate::Compare { .. } => {
+                    bail!(FilterError::InvalidPredType("compare".to_owned()))
+                }
                 Predicate::Binary {
                     protocol: _,
                     field,
@@ -232,6 +235,9 @@ impl FlowPattern {
                     bail!(FilterError::InvalidPredType("layerstate".to_owned()))
                 }
                 Predicate::Unary { .. } => bail!(FilterError::InvalidPredType("unary".to_owned())),
+                Predicate::Compare { .. } => {
+                    bail!(FilterError::InvalidPredType("compare".to_owned()))
+                }
                 Predicate::Binary {
                     protocol: _,
                     field,
@@ -334,6 +340,9 @@ impl FlowPattern {
                     bail!(FilterError::InvalidPredType("layerstate".to_owned()))
                 }
                 Predicate::Unary { .. } => bail!(FilterError::InvalidPredType("unary".to_owned())),
+                Predicate::Compare { .. } => {
+                    bail!(FilterError::InvalidPredType("compare".to_owned()))
+                }
                 Predicate::Binary {
                     protocol: _,
                     field,
@@ -471,6 +480,9 @@ impl FlowPattern {
                     bail!(FilterError::InvalidPredType("layerstate".to_owned()))
                 }
                 Predicate::Unary { .. } => bail!(FilterError::InvalidPredType("unary".to_owned())),
+                Predicate::Compare { .. } => {
+                    bail!(FilterError::InvalidPredType("compare".to_owned()))
+                }
                 Predicate::Binary {
                     protocol: _,
                     field,
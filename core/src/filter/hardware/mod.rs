@@ -14,32 +14,109 @@ use super::Filter;
 use crate::dpdk;
 use crate::port::*;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::{c_void, CStr};
 use std::fmt;
 use std::mem;
+use std::net::{IpAddr, SocketAddr};
 
 use anyhow::{bail, Result};
+use ipnet::{Ipv4Net, Ipv6Net};
 use log::{debug, error, info, warn};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::Serialize;
 use thiserror::Error;
 
 // priority levels for ingress rules
-const HIGH_PRIORITY: u32 = 0;
+// Shunt rules must take precedence over the (broader) filter rules
+const SHUNT_PRIORITY: u32 = 0;
+const HIGH_PRIORITY: u32 = 1;
 const LOW_PRIORITY: u32 = 3;
 
+// A device that hardware filter rules are validated and installed on.
+pub(crate) trait FlowDevice: fmt::Debug {
+    // Returns the PCI address or interface name of the device.
+    fn device(&self) -> &str;
+
+    // Returns `true` if a rule matching `lpattern` validates on table `group`.
+    fn pattern_supported(&self, lpattern: &LayeredPattern, group: u32, priority: u32) -> bool;
+
+    // Installs a rule matching `lpattern`, or only validates it if `dry_run` is set.
+    fn install_pattern(
+        &self,
+        lpattern: &LayeredPattern,
+        group: u32,
+        priority: u32,
+        mark: Option<u32>,
+        dry_run: bool,
+    ) -> Result<(), HardwareFilterError>;
+
+    // Redirects traffic that matches no rule on table `from_group` to `to_group`.
+    fn add_redirect(
+        &self,
+        from_group: u32,
+        to_group: u32,
+        priority: u32,
+        dry_run: bool,
+    ) -> Result<(), HardwareFilterError>;
+
+    // Removes all flow rules from the device.
+    fn flush_rules(&self);
+}
+
+impl FlowDevice for Port {
+    fn device(&self) -> &str {
+        &self.device
+    }
+
+    fn pattern_supported(&self, lpattern: &LayeredPattern, group: u32, priority: u32) -> bool {
+        pattern_supported(lpattern, self, group, priority)
+    }
+
+    fn install_pattern(
+        &self,
+        lpattern: &LayeredPattern,
+        group: u32,
+        priority: u32,
+        mark: Option<u32>,
+        dry_run: bool,
+    ) -> Result<(), HardwareFilterError> {
+        install_pattern(lpattern, self, group, priority, mark, dry_run)
+    }
+
+    fn add_redirect(
+        &self,
+        from_group: u32,
+        to_group: u32,
+        priority: u32,
+        dry_run: bool,
+    ) -> Result<(), HardwareFilterError> {
+        add_redirect(self, from_group, to_group, priority, dry_run)
+    }
+
+    fn flush_rules(&self) {
+        flush_rules(self)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct HardwareFilter<'a> {
     patterns: Vec<LayeredPattern>,
-    port: &'a Port,
+    filter: &'a Filter,
+    port: &'a dyn FlowDevice,
+    // Predicates left to the software filter
+    dropped: BTreeMap<Predicate, HardwareFilterError>,
 }
 
 impl<'a> HardwareFilter<'a> {
     // Creates a new HardwareFilter for port given a filter.
     // Prunes all predicates not supported by the device.
-    pub(crate) fn new(filter: &Filter, port: &'a Port) -> Self {
+    pub(crate) fn new(filter: &'a Filter, port: &'a dyn FlowDevice) -> Self {
+        let mut dropped = BTreeMap::new();
         let hw_patterns = filter
             .get_patterns_flat()
             .iter()
-            .map(|p| p.retain_hardware_predicates(port))
+            .map(|p| p.retain_hardware_predicates(port, &mut dropped))
             .collect::<Vec<_>>();
 
         // Prune some redundant patterns.
@@ -51,12 +128,13 @@ impl<'a> HardwareFilter<'a> {
         let mut hw_patterns = hw_ptree.to_flat_patterns();
 
         let mut layered = vec![];
+        let mut broadened = vec![];
         for pattern in hw_patterns.iter_mut() {
             // Only retaining hardware filterable predicates may make
             // a pattern no-longer fully qualified. Therefore, we must
             // broaden pattern until hardware filterable again
             while !pattern.is_fully_qualified() {
-                pattern.predicates.pop();
+                broadened.extend(pattern.predicates.pop());
             }
             // converts to LayeredPattern
             layered.extend(pattern.to_fully_qualified().expect("fully qualified"));
@@ -66,33 +144,97 @@ impl<'a> HardwareFilter<'a> {
         layered.sort();
         layered.dedup();
 
+        // Broadened predicates may still be offloaded by another pattern
+        let offloaded = offloaded_predicates(&layered);
+        for pred in broadened {
+            if !offloaded.contains(&pred) {
+                dropped
+                    .entry(pred.clone())
+                    .or_insert(HardwareFilterError::UnsupportedLayer(pred));
+            }
+        }
+
         HardwareFilter {
             patterns: layered,
+            filter,
             port,
+            dropped,
         }
     }
 
-    // Installs the hardware filter to the port.
-    pub(crate) fn install(&self) -> Result<()> {
+    // Installs the hardware filter to the port, or only validates its rules
+    // if `dry_run` is set. Stops at the first failed rule unless `dry_run`.
+    pub(crate) fn install(self, dry_run: bool) -> HardwareReport {
         debug!("{}", self);
+        let mut report = HardwareReport {
+            device: self.port.device().to_string(),
+            dry_run,
+            offloaded: offloaded_predicates(&self.patterns).into_iter().collect(),
+            dropped: self.dropped.into_iter().collect(),
+            rules: vec![],
+            errors: vec![],
+        };
         if self.patterns.iter().all(|p| p.is_empty()) {
             info!("Empty filter, skipping.");
-            return Ok(());
+            return report;
         }
 
-        info!("Applying hardware filter rules on Port {}...", self.port.id);
+        match dry_run {
+            true => info!(
+                "Validating hardware filter rules on {}...",
+                self.port.device()
+            ),
+            false => info!(
+                "Applying hardware filter rules on {}...",
+                self.port.device()
+            ),
+        }
         for pattern in self.patterns.iter() {
-            install_pattern(pattern, self.port, 0, HIGH_PRIORITY)?;
+            // MARK packets if the software filter can resume from this pattern.
+            // Not all devices support MARK, so fall back to an unmarked rule.
+            let mut mark = self.filter.get_mark(&pattern.to_flat_pattern());
+            if mark.is_some() {
+                if let Err(error) =
+                    self.port
+                        .install_pattern(pattern, 0, HIGH_PRIORITY, mark, dry_run)
+                {
+                    warn!("Failed to MARK {}: {}", pattern, error);
+                    mark = None;
+                }
+            }
+            if mark.is_none() {
+                if let Err(error) =
+                    self.port
+                        .install_pattern(pattern, 0, HIGH_PRIORITY, None, dry_run)
+                {
+                    report.errors.push(error);
+                    if !dry_run {
+                        return report;
+                    }
+                    continue;
+                }
+            }
+            report.rules.push((pattern.clone(), mark));
         }
         // Non-matching traffic will be dropped by default on table 1
         // Redirect is faster than using a default DROP rule
-        add_redirect(self.port, 0, 1, LOW_PRIORITY)?;
+        if let Err(error) = self.port.add_redirect(0, 1, LOW_PRIORITY, dry_run) {
+            report.errors.push(error);
+        }
         // drop_eth_traffic(self.port, 0, LOW_PRIORITY)?;
 
-        Ok(())
+        report
     }
 }
 
+// Returns the predicates matched by at least one of `patterns`
+fn offloaded_predicates(patterns: &[LayeredPattern]) -> BTreeSet<Predicate> {
+    patterns
+        .iter()
+        .flat_map(|p| p.to_flat_pattern().predicates)
+        .collect()
+}
+
 impl fmt::Display for HardwareFilter<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         writeln!(f, "[HardwareFilter]: ")?;
@@ -103,8 +245,128 @@ impl fmt::Display for HardwareFilter<'_> {
     }
 }
 
-#[derive(Error, Debug)]
-pub(crate) enum HardwareFilterError {
+/// Outcome of offloading a filter to a port.
+///
+/// Predicates that are not offloaded are still applied by the software filter, so they only
+/// affect how much traffic reaches the CPU. Predicates, patterns, and errors serialize as their
+/// display strings.
+#[derive(Debug, Clone)]
+pub struct HardwareReport {
+    /// PCI address or interface name of the port.
+    pub device: String,
+    /// `true` if rules were only validated, not installed.
+    pub dry_run: bool,
+    /// Predicates matched by at least one hardware rule.
+    pub offloaded: Vec<Predicate>,
+    /// Predicates left entirely to the software filter, and why.
+    pub dropped: Vec<(Predicate, HardwareFilterError)>,
+    /// Patterns installed as hardware rules, with the MARK that each tags packets with.
+    pub rules: Vec<(LayeredPattern, Option<u32>)>,
+    /// Rules that failed validation or creation. Unless this is a dry run, no rules are
+    /// installed on the port if any failed.
+    pub errors: Vec<HardwareFilterError>,
+}
+
+impl HardwareReport {
+    /// Returns `true` if every rule validated, or was installed.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns the number of flow rules used by the filter, including the default redirect.
+    pub fn nb_rules(&self) -> usize {
+        match self.rules.is_empty() {
+            true => 0,
+            false => self.rules.len() + 1,
+        }
+    }
+}
+
+impl Serialize for HardwareReport {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct Dropped {
+            predicate: String,
+            reason: String,
+        }
+
+        #[derive(Serialize)]
+        struct Rule {
+            pattern: String,
+            mark: Option<u32>,
+        }
+
+        let offloaded = self
+            .offloaded
+            .iter()
+            .map(|pred| pred.to_string())
+            .collect::<Vec<_>>();
+        let dropped = self
+            .dropped
+            .iter()
+            .map(|(pred, reason)| Dropped {
+                predicate: pred.to_string(),
+                reason: reason.to_string(),
+            })
+            .collect::<Vec<_>>();
+        let rules = self
+            .rules
+            .iter()
+            .map(|(pattern, mark)| Rule {
+                pattern: pattern.to_string(),
+                mark: *mark,
+            })
+            .collect::<Vec<_>>();
+        let errors = self
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("HardwareReport", 7)?;
+        state.serialize_field("device", &self.device)?;
+        state.serialize_field("dry_run", &self.dry_run)?;
+        state.serialize_field("offloaded", &offloaded)?;
+        state.serialize_field("dropped", &dropped)?;
+        state.serialize_field("rules", &rules)?;
+        state.serialize_field("nb_rules", &self.nb_rules())?;
+        state.serialize_field("errors", &errors)?;
+        state.end()
+    }
+}
+
+impl fmt::Display for HardwareReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let mode = if self.dry_run { " (dry run)" } else { "" };
+        writeln!(f, "[HardwareReport{}]: {}", mode, self.device)?;
+        writeln!(f, "Offloaded:")?;
+        for pred in self.offloaded.iter() {
+            writeln!(f, "  {}", pred)?;
+        }
+        writeln!(f, "Software only:")?;
+        for (_, reason) in self.dropped.iter() {
+            writeln!(f, "  {}", reason)?;
+        }
+        writeln!(f, "Rules ({}):", self.nb_rules())?;
+        for (pattern, mark) in self.rules.iter() {
+            match mark {
+                Some(mark) => writeln!(f, "  {} MARK {}", pattern, mark)?,
+                None => writeln!(f, "  {}", pattern)?,
+            }
+        }
+        for error in self.errors.iter() {
+            writeln!(f, "Failed: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reasons a predicate or rule could not be offloaded to hardware.
+#[derive(Error, Debug, Clone)]
+pub enum HardwareFilterError {
     #[error("Rule validation for {lpattern} failed on creation attempt. Reason: {reason}")]
     Validation {
         lpattern: LayeredPattern,
@@ -119,9 +381,25 @@ pub(crate) enum HardwareFilterError {
 
     #[error("Hardware flow rule invalid: {0}")]
     InvalidRule(LayeredPattern),
+
+    #[error("Hardware filter does not support protocol for: [{0}]")]
+    UnsupportedProtocol(Predicate),
+
+    #[error("Hardware filter does not support binary comparison operator for: [{0}]")]
+    UnsupportedOperator(Predicate),
+
+    #[error("Hardware filter does not support predicate: [{0}]")]
+    UnsupportedPredicate(Predicate),
+
+    #[error("Hardware filter does not support an enclosing layer of: [{0}]")]
+    UnsupportedLayer(Predicate),
 }
 
-pub(crate) fn device_supported(pred: &Predicate, port: &Port) -> bool {
+// Returns Ok if the predicate can be pushed down to hardware port.
+pub(crate) fn device_supported(
+    pred: &Predicate,
+    port: &dyn FlowDevice,
+) -> Result<(), HardwareFilterError> {
     // Device supported protocols
     let hw_filterable_protos = hashset! {
         protocol!("ipv4"),
@@ -131,8 +409,7 @@ pub(crate) fn device_supported(pred: &Predicate, port: &Port) -> bool {
     };
     let proto_supported = hw_filterable_protos.contains(pred.get_protocol());
     if !proto_supported {
-        info!("Hardware filter does not support protocol for: [{}]", pred);
-        return false;
+        return Err(HardwareFilterError::UnsupportedProtocol(pred.to_owned()));
     }
 
     // Only allow equality predicates
@@ -152,11 +429,7 @@ pub(crate) fn device_supported(pred: &Predicate, port: &Port) -> bool {
         _ => false,
     };
     if !op_supported {
-        info!(
-            "Hardware filter does not support binary comparison operator for: [{}]",
-            pred
-        );
-        return false;
+        return Err(HardwareFilterError::UnsupportedOperator(pred.to_owned()));
     }
 
     // The only way to truly tell if predicate on a field is supported is to
@@ -167,20 +440,24 @@ pub(crate) fn device_supported(pred: &Predicate, port: &Port) -> bool {
     // matching.
     let pred_supported = predicate_supported(pred, port, 0, HIGH_PRIORITY);
     if !pred_supported {
-        info!("Hardware filter does not support predicate: [{}]", pred);
-        return false;
+        return Err(HardwareFilterError::UnsupportedPredicate(pred.to_owned()));
     }
-    true
+    Ok(())
 }
 
-fn predicate_supported(predicate: &Predicate, port: &Port, group: u32, priority: u32) -> bool {
+fn predicate_supported(
+    predicate: &Predicate,
+    port: &dyn FlowDevice,
+    group: u32,
+    priority: u32,
+) -> bool {
     let pattern = FlatPattern {
         predicates: vec![predicate.to_owned()],
     };
     let fq_patterns = pattern.to_fully_qualified().expect("fully qualified");
     fq_patterns
         .iter()
-        .all(|p| pattern_supported(p, port, group, priority))
+        .all(|p| port.pattern_supported(p, group, priority))
 }
 
 fn pattern_supported(lpattern: &LayeredPattern, port: &Port, group: u32, priority: u32) -> bool {
@@ -249,28 +526,34 @@ fn install_pattern(
     port: &Port,
     group: u32,
     priority: u32,
-) -> Result<()> {
+    mark: Option<u32>,
+    dry_run: bool,
+) -> Result<(), HardwareFilterError> {
     let attr = FlowAttribute::new(group, priority);
     if let Ok(mut pattern) = FlowPattern::from_layered_pattern(lpattern) {
         let mut action = FlowAction::new(port.id);
-        // action.append_mark(tag as u32);
+        if let Some(mark) = mark {
+            action.append_mark(mark);
+        }
 
         action.append_rss();
         action.finish();
 
-        create_rule(lpattern, port, attr, &mut pattern, &mut action)
+        create_rule(lpattern, port, attr, &mut pattern, &mut action, dry_run)
     } else {
-        bail!(HardwareFilterError::InvalidRule(lpattern.to_owned()));
+        Err(HardwareFilterError::InvalidRule(lpattern.to_owned()))
     }
 }
 
+// Validates and creates a rule, or only validates it if `dry_run` is set.
 fn create_rule(
     lpattern: &LayeredPattern,
     port: &Port,
     attr: FlowAttribute,
     pattern: &mut FlowPattern,
     action: &mut FlowAction,
-) -> Result<()> {
+    dry_run: bool,
+) -> Result<(), HardwareFilterError> {
     let mut pattern_rules: PatternRules = vec![];
     flow_item::append_eth(&mut pattern_rules);
     for item in pattern.items.iter() {
@@ -286,10 +569,16 @@ fn create_rule(
     // reta_raw needs to stay in scope until after rte_flow_create() succeeds
     let reta_raw = port.reta.iter().map(|q| q.raw()).collect::<Vec<_>>();
     for a in action.rules.iter_mut() {
-        if let dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_RSS = a.type_ {
-            action.rss[0].queue_num = port.queue_map.len() as u32;
-            action.rss[0].queue = reta_raw.as_ptr();
-            a.conf = &action.rss[0] as *const _ as *const c_void;
+        match a.type_ {
+            dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_RSS => {
+                action.rss[0].queue_num = port.queue_map.len() as u32;
+                action.rss[0].queue = reta_raw.as_ptr();
+                a.conf = &action.rss[0] as *const _ as *const c_void;
+            }
+            dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_MARK => {
+                a.conf = &action.mark[0] as *const _ as *const c_void;
+            }
+            _ => (),
         }
     }
 
@@ -304,10 +593,13 @@ fn create_rule(
         );
         if ret != 0 {
             let msg: &CStr = CStr::from_ptr(error.message);
-            bail!(HardwareFilterError::Validation {
+            Err(HardwareFilterError::Validation {
                 lpattern: lpattern.to_owned(),
-                reason: msg.to_str().unwrap().to_string()
-            });
+                reason: msg.to_str().unwrap().to_string(),
+            })
+        } else if dry_run {
+            info!("Hardware flow rule validated: {}", lpattern);
+            Ok(())
         } else {
             let ret = dpdk::rte_flow_create(
                 port.id.raw(),
@@ -318,10 +610,10 @@ fn create_rule(
             );
             if ret.is_null() {
                 let msg: &CStr = CStr::from_ptr(error.message);
-                bail!(HardwareFilterError::Creation {
+                Err(HardwareFilterError::Creation {
                     lpattern: lpattern.to_owned(),
-                    reason: msg.to_str().unwrap().to_string()
-                });
+                    reason: msg.to_str().unwrap().to_string(),
+                })
             } else {
                 info!("Hardware flow rule created: {}", lpattern);
                 Ok(())
@@ -330,7 +622,13 @@ fn create_rule(
     }
 }
 
-fn add_redirect(port: &Port, from_group: u32, to_group: u32, priority: u32) -> Result<()> {
+fn add_redirect(
+    port: &Port,
+    from_group: u32,
+    to_group: u32,
+    priority: u32,
+    dry_run: bool,
+) -> Result<(), HardwareFilterError> {
     let attr = FlowAttribute::new(from_group, priority);
 
     // Pattern matches all Ethernet traffic
@@ -365,11 +663,11 @@ fn add_redirect(port: &Port, from_group: u32, to_group: u32, priority: u32) -> R
         if ret != 0 {
             let msg: &CStr = CStr::from_ptr(error.message);
             error!("Redirect rule failed validation: {}", msg.to_str().unwrap());
-            bail!(HardwareFilterError::Validation {
+            return Err(HardwareFilterError::Validation {
                 lpattern: LayeredPattern::new(),
-                reason: msg.to_str().unwrap().to_string()
+                reason: msg.to_str().unwrap().to_string(),
             });
-        } else {
+        } else if !dry_run {
             let ret = dpdk::rte_flow_create(
                 port.id.raw(),
                 attr.raw() as *const _,
@@ -380,9 +678,9 @@ fn add_redirect(port: &Port, from_group: u32, to_group: u32, priority: u32) -> R
             if ret.is_null() {
                 error!("Redirect rule failed creation.");
                 let msg: &CStr = CStr::from_ptr(error.message);
-                bail!(HardwareFilterError::Creation {
+                return Err(HardwareFilterError::Creation {
                     lpattern: LayeredPattern::new(),
-                    reason: msg.to_str().unwrap().to_string()
+                    reason: msg.to_str().unwrap().to_string(),
                 });
             } else {
                 info!("Created hardware flow rule for redirect.");
@@ -450,8 +748,150 @@ fn drop_eth_traffic(port: &Port, group: u32, priority: u32) -> Result<()> {
     Ok(())
 }
 
+/// A flow rule installed at runtime to drop one direction of a connection in hardware.
+#[derive(Debug)]
+pub(crate) struct ShuntRule {
+    port_id: PortId,
+    flow: *mut dpdk::rte_flow,
+}
+
+impl ShuntRule {
+    // Installs a rule dropping packets from `src` to `dst` with L4 protocol `proto` on port.
+    pub(crate) fn install(
+        port_id: PortId,
+        src: SocketAddr,
+        dst: SocketAddr,
+        proto: usize,
+    ) -> Result<ShuntRule> {
+        let lpattern = shunt_pattern(src, dst, proto)?;
+        let attr = FlowAttribute::new(0, SHUNT_PRIORITY);
+        let pattern = FlowPattern::from_layered_pattern(&lpattern)?;
+
+        let mut pattern_rules: PatternRules = vec![];
+        flow_item::append_eth(&mut pattern_rules);
+        for item in pattern.items.iter() {
+            let mut p_item: dpdk::rte_flow_item = unsafe { mem::zeroed() };
+            p_item.type_ = item.item_type();
+            p_item.spec = item.spec();
+            p_item.mask = item.mask();
+            pattern_rules.push(p_item);
+        }
+        flow_item::append_end(&mut pattern_rules);
+
+        // Count hits so that idle rules can be expired
+        let mut action = FlowAction::new(port_id);
+        action.append_count();
+        action.append_drop();
+        action.finish();
+
+        let mut error: dpdk::rte_flow_error = unsafe { mem::zeroed() };
+        let flow = unsafe {
+            dpdk::rte_flow_create(
+                port_id.raw(),
+                attr.raw() as *const _,
+                pattern_rules.as_ptr(),
+                action.rules.as_ptr(),
+                &mut error as *mut _,
+            )
+        };
+        if flow.is_null() {
+            bail!(HardwareFilterError::Creation {
+                lpattern,
+                reason: flow_error_message(&error),
+            });
+        }
+        debug!("Shunt rule created: {}", lpattern);
+        Ok(ShuntRule { port_id, flow })
+    }
+
+    // Returns the number of packets matched since the last query.
+    pub(crate) fn query_hits(&self) -> Result<u64> {
+        let mut a_count: dpdk::rte_flow_action = unsafe { mem::zeroed() };
+        a_count.type_ = dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_COUNT;
+        let mut a_end: dpdk::rte_flow_action = unsafe { mem::zeroed() };
+        a_end.type_ = dpdk::rte_flow_action_type_RTE_FLOW_ACTION_TYPE_END;
+        let actions: ActionRules = vec![a_count, a_end];
+
+        let mut count: dpdk::rte_flow_query_count = unsafe { mem::zeroed() };
+        count.set_reset(1);
+        let mut error: dpdk::rte_flow_error = unsafe { mem::zeroed() };
+        let ret = unsafe {
+            dpdk::rte_flow_query(
+                self.port_id.raw(),
+                self.flow,
+                actions.as_ptr(),
+                &mut count as *mut _ as *mut c_void,
+                &mut error as *mut _,
+            )
+        };
+        if ret != 0 {
+            bail!("Shunt rule query failed: {}", flow_error_message(&error));
+        }
+        Ok(count.hits)
+    }
+
+    // Removes the rule from the port.
+    pub(crate) fn destroy(self) -> Result<()> {
+        let mut error: dpdk::rte_flow_error = unsafe { mem::zeroed() };
+        let ret =
+            unsafe { dpdk::rte_flow_destroy(self.port_id.raw(), self.flow, &mut error as *mut _) };
+        if ret != 0 {
+            bail!("Shunt rule removal failed: {}", flow_error_message(&error));
+        }
+        Ok(())
+    }
+}
+
+// Builds the fully qualified pattern matching packets from `src` to `dst`.
+fn shunt_pattern(src: SocketAddr, dst: SocketAddr, proto: usize) -> Result<LayeredPattern> {
+    let (l3, src_addr, dst_addr) = match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => (
+            protocol!("ipv4"),
+            Value::Ipv4(Ipv4Net::new(src, 32)?),
+            Value::Ipv4(Ipv4Net::new(dst, 32)?),
+        ),
+        (IpAddr::V6(src), IpAddr::V6(dst)) => (
+            protocol!("ipv6"),
+            Value::Ipv6(Ipv6Net::new(src, 128)?),
+            Value::Ipv6(Ipv6Net::new(dst, 128)?),
+        ),
+        _ => bail!("Mismatched IP versions: {} -> {}", src, dst),
+    };
+    let l4 = match proto {
+        6 => protocol!("tcp"),
+        17 => protocol!("udp"),
+        _ => bail!("Unsupported L4 protocol: {}", proto),
+    };
+    let binary = |protocol: &ProtocolName, field: &str, value: Value| Predicate::Binary {
+        protocol: protocol.clone(),
+        field: field!(field),
+        op: BinOp::Eq,
+        value,
+    };
+    let pattern = FlatPattern {
+        predicates: vec![
+            binary(&l3, "src_addr", src_addr),
+            binary(&l3, "dst_addr", dst_addr),
+            binary(&l4, "src_port", Value::Int(src.port() as u64)),
+            binary(&l4, "dst_port", Value::Int(dst.port() as u64)),
+        ],
+    };
+    match pattern.to_fully_qualified()?.pop() {
+        Some(lpattern) => Ok(lpattern),
+        None => bail!(HardwareFilterError::InvalidRule(LayeredPattern::new())),
+    }
+}
+
+fn flow_error_message(error: &dpdk::rte_flow_error) -> String {
+    if error.message.is_null() {
+        return "unknown".to_string();
+    }
+    let msg: &CStr = unsafe { CStr::from_ptr(error.message) };
+    msg.to_string_lossy().to_string()
+}
+
 // Flush all flow rules associated with port
-pub(crate) fn flush_rules(port: &Port) {
+fn flush_rules(port: &Port) {
     info!("Flushing flow rules on Port {}", port.id);
     unsafe {
         let mut error: dpdk::rte_flow_error = mem::zeroed();
@@ -465,3 +905,252 @@ pub(crate) fn flush_rules(port: &Port) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    // Device that accepts the rules `supports` returns `true` for, without reaching DPDK
+    #[derive(Debug)]
+    struct MockDevice {
+        supports: fn(&LayeredPattern) -> bool,
+        // Rules created on the device, excluding the default redirect
+        rules: RefCell<Vec<LayeredPattern>>,
+        redirected: Cell<bool>,
+        flushed: Cell<bool>,
+    }
+
+    impl MockDevice {
+        fn new(supports: fn(&LayeredPattern) -> bool) -> Self {
+            MockDevice {
+                supports,
+                rules: RefCell::new(vec![]),
+                redirected: Cell::new(false),
+                flushed: Cell::new(false),
+            }
+        }
+    }
+
+    impl FlowDevice for MockDevice {
+        fn device(&self) -> &str {
+            "mock"
+        }
+
+        fn pattern_supported(&self, lpattern: &LayeredPattern, _: u32, _: u32) -> bool {
+            (self.supports)(lpattern)
+        }
+
+        fn install_pattern(
+            &self,
+            lpattern: &LayeredPattern,
+            _group: u32,
+            _priority: u32,
+            _mark: Option<u32>,
+            dry_run: bool,
+        ) -> Result<(), HardwareFilterError> {
+            if !(self.supports)(lpattern) {
+                return Err(HardwareFilterError::Validation {
+                    lpattern: lpattern.to_owned(),
+                    reason: "unsupported".to_string(),
+                });
+            }
+            if !dry_run {
+                self.rules.borrow_mut().push(lpattern.to_owned());
+            }
+            Ok(())
+        }
+
+        fn add_redirect(
+            &self,
+            _: u32,
+            _: u32,
+            _: u32,
+            dry_run: bool,
+        ) -> Result<(), HardwareFilterError> {
+            if !dry_run {
+                self.redirected.set(true);
+            }
+            Ok(())
+        }
+
+        fn flush_rules(&self) {
+            self.rules.borrow_mut().clear();
+            self.redirected.set(false);
+            self.flushed.set(true);
+        }
+    }
+
+    fn unary(protocol: &str) -> Predicate {
+        Predicate::Unary {
+            protocol: protocol!(protocol),
+        }
+    }
+
+    fn tcp_port(field: &str, op: BinOp, port: u64) -> Predicate {
+        Predicate::Binary {
+            protocol: protocol!("tcp"),
+            field: field!(field),
+            op,
+            value: Value::Int(port),
+        }
+    }
+
+    // Sets the filter as the runtime does for each port
+    fn install(filter_raw: &str, device: &MockDevice, dry_run: bool) -> HardwareReport {
+        let filter = Filter::new(filter_raw, &vec![]).unwrap();
+        filter.set_hardware_filter(device, dry_run)
+    }
+
+    fn dropped_reasons(report: &HardwareReport) -> Vec<String> {
+        report
+            .dropped
+            .iter()
+            .map(|(_, reason)| reason.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn core_hardware_unsupported_protocol() {
+        let device = MockDevice::new(|_| true);
+        let report = install("ipv4 and tls", &device, true);
+        assert!(report.is_ok());
+        assert_eq!(report.offloaded, vec![unary("ipv4"), unary("tcp")]);
+        assert_eq!(report.dropped.len(), 1);
+        assert!(matches!(
+            &report.dropped[0],
+            (pred, HardwareFilterError::UnsupportedProtocol(_)) if *pred == unary("tls")
+        ));
+    }
+
+    #[test]
+    fn core_hardware_unsupported_operator() {
+        let device = MockDevice::new(|_| true);
+        let report = install("tcp.port > 1024", &device, true);
+        assert!(report.is_ok());
+        assert_eq!(
+            report.offloaded,
+            vec![unary("ipv4"), unary("ipv6"), unary("tcp")]
+        );
+        let dropped = report
+            .dropped
+            .iter()
+            .map(|(pred, _)| pred)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dropped,
+            vec![
+                &tcp_port("dst_port", BinOp::Gt, 1024),
+                &tcp_port("src_port", BinOp::Gt, 1024)
+            ]
+        );
+        assert!(report
+            .dropped
+            .iter()
+            .all(|(_, reason)| matches!(reason, HardwareFilterError::UnsupportedOperator(_))));
+        // Both patterns are broadened to `tcp` on each IP version
+        assert_eq!(report.rules.len(), 2);
+    }
+
+    #[test]
+    fn core_hardware_unsupported_predicate() {
+        // Device that cannot match on TCP ports
+        let device = MockDevice::new(|lpattern| {
+            !lpattern
+                .to_flat_pattern()
+                .predicates
+                .iter()
+                .any(|pred| matches!(pred, Predicate::Binary { protocol, .. } if protocol == &protocol!("tcp")))
+        });
+        let report = install("ipv4 and tcp.port = 80", &device, true);
+        assert_eq!(report.offloaded, vec![unary("ipv4"), unary("tcp")]);
+        assert!(report
+            .dropped
+            .iter()
+            .all(|(_, reason)| matches!(reason, HardwareFilterError::UnsupportedPredicate(_))));
+        assert_eq!(
+            dropped_reasons(&report),
+            vec![
+                "Hardware filter does not support predicate: [tcp.dst_port = 80]",
+                "Hardware filter does not support predicate: [tcp.src_port = 80]",
+            ]
+        );
+        assert_eq!(report.rules.len(), 1);
+    }
+
+    #[test]
+    fn core_hardware_unsupported_layer() {
+        // Device that can only match TCP on a port
+        let device = MockDevice::new(|lpattern| {
+            let predicates = lpattern.to_flat_pattern().predicates;
+            !predicates.contains(&unary("tcp")) || predicates.len() > 2
+        });
+        let report = install("ipv4 and tcp.dst_port = 80", &device, true);
+        assert_eq!(report.offloaded, vec![unary("ipv4")]);
+        assert_eq!(report.dropped.len(), 2);
+        assert!(matches!(
+            report.dropped[0].1,
+            HardwareFilterError::UnsupportedPredicate(_)
+        ));
+        assert!(matches!(
+            report.dropped[1].1,
+            HardwareFilterError::UnsupportedLayer(_)
+        ));
+        assert_eq!(
+            dropped_reasons(&report),
+            vec![
+                "Hardware filter does not support predicate: [tcp]",
+                "Hardware filter does not support an enclosing layer of: [tcp.dst_port = 80]",
+            ]
+        );
+    }
+
+    #[test]
+    fn core_hardware_dry_run() {
+        let filter_raw = "ipv4.src_addr in 10.0.0.0/8 and tcp.port = 443";
+        let device = MockDevice::new(|_| true);
+        let report = install(filter_raw, &device, true);
+        assert!(report.dry_run && report.is_ok());
+        assert_eq!(report.nb_rules(), 3);
+        assert!(device.rules.borrow().is_empty());
+        assert!(!device.redirected.get());
+
+        let report = install(filter_raw, &device, false);
+        assert!(!report.dry_run && report.is_ok());
+        assert_eq!(report.nb_rules(), 3);
+        assert_eq!(device.rules.borrow().len(), 2);
+        assert!(device.redirected.get());
+    }
+
+    #[test]
+    fn core_hardware_failed_rule() {
+        // Device that validates each predicate, but not rules with all of them
+        let device = MockDevice::new(|lpattern| lpattern.to_flat_pattern().predicates.len() < 4);
+        let filter_raw = "ipv4.src_addr in 10.0.0.0/8 and tcp.port = 443";
+
+        // A dry run reports every failed rule
+        let report = install(filter_raw, &device, true);
+        assert!(report.dropped.is_empty());
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.nb_rules(), 0);
+        assert!(!device.flushed.get());
+
+        // Otherwise the first failed rule removes all rules from the device
+        let report = install(filter_raw, &device, false);
+        assert_eq!(report.errors.len(), 1);
+        assert!(device.flushed.get());
+        assert!(device.rules.borrow().is_empty() && !device.redirected.get());
+    }
+
+    #[test]
+    fn core_hardware_report_serialize() {
+        let device = MockDevice::new(|_| true);
+        let report = install("ipv4 and tls", &device, true);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["device"], "mock");
+        assert_eq!(json["offloaded"], serde_json::json!(["ipv4", "tcp"]));
+        assert_eq!(json["dropped"][0]["predicate"], "tls");
+        assert_eq!(json["nb_rules"], 2);
+        assert_eq!(json["rules"][0]["mark"], serde_json::Value::Null);
+    }
+}
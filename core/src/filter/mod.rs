@@ -10,6 +10,7 @@
 pub mod macros;
 #[doc(hidden)]
 pub mod ast;
+#[cfg(not(feature = "heap_mbuf"))]
 mod hardware;
 #[allow(clippy::upper_case_acronyms)]
 mod parser;
@@ -23,16 +24,22 @@ pub mod subscription;
 
 use crate::conntrack::{ConnInfo, DataLevel, StateTransition};
 use crate::filter::ast::Predicate;
-use crate::filter::hardware::{flush_rules, HardwareFilter};
+#[cfg(not(feature = "heap_mbuf"))]
+pub(crate) use crate::filter::hardware::ShuntRule;
+#[cfg(not(feature = "heap_mbuf"))]
+use crate::filter::hardware::{FlowDevice, HardwareFilter};
+#[cfg(not(feature = "heap_mbuf"))]
+pub use crate::filter::hardware::{HardwareFilterError, HardwareReport};
 use crate::filter::parser::FilterParser;
 use crate::filter::pattern::{FlatPattern, LayeredPattern};
 use crate::filter::pred_ptree::PredPTree;
 use crate::lcore::CoreId;
 use crate::memory::mbuf::Mbuf;
-use crate::port::Port;
+use crate::subscription::budget::Budget;
 use crate::subscription::Trackable;
 use crate::L4Pdu;
 
+use std::collections::BTreeSet;
 use std::fmt;
 
 use anyhow::{bail, Result};
@@ -61,6 +68,12 @@ where
     pub packet_filter: PacketFilterFn,
     pub state_tx: StateTxFn<T>,
     pub update_fn: UpdateFn<T>,
+    /// Filter strings that hardware rules may MARK packets with, keyed by
+    /// the ID that `packet_filter` expects in `Mbuf::flow_mark`.
+    pub marks: Vec<(u32, String)>,
+    /// Budgets of subscriptions that limit their load, indexed by the ID
+    /// that generated code passes to `budget::admit_*`.
+    pub budgets: Vec<Budget>,
 }
 
 impl<T> FilterFactory<T>
@@ -78,13 +91,34 @@ where
             packet_filter,
             state_tx,
             update_fn,
+            marks: vec![],
+            budgets: vec![],
         }
     }
+
+    /// Declares the filter strings that `packet_filter` can skip when
+    /// a packet carries the corresponding MARK.
+    pub fn with_marks(mut self, marks: &[(u32, &str)]) -> Self {
+        self.marks = marks
+            .iter()
+            .map(|(mark, filter_str)| (*mark, filter_str.to_string()))
+            .collect();
+        self
+    }
+
+    /// Declares the budgets of subscriptions that limit their load.
+    pub fn with_budgets(mut self, budgets: &[Budget]) -> Self {
+        self.budgets = budgets.to_vec();
+        self
+    }
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct Filter {
     patterns: Vec<LayeredPattern>,
+    // MARKs to apply to hardware rules, with the predicates a rule must
+    // match exactly to carry each MARK
+    marks: Vec<(u32, BTreeSet<Predicate>)>,
 }
 
 impl Filter {
@@ -117,9 +151,39 @@ impl Filter {
 
         Ok(Filter {
             patterns: ptree.to_layered_patterns(),
+            marks: vec![],
         })
     }
 
+    // Adds the MARKs that hardware rules may tag packets with.
+    // Each filter string must be a single conjunction of predicates.
+    pub fn with_marks(mut self, marks: &[(u32, String)]) -> Result<Filter> {
+        for (mark, filter_raw) in marks {
+            let raw_patterns = FilterParser::parse_filter(filter_raw)?;
+            if raw_patterns.len() != 1 {
+                bail!(FilterError::InvalidFormat);
+            }
+            let pattern = FlatPattern {
+                predicates: raw_patterns.into_iter().next().unwrap(),
+            };
+            for fq_pattern in pattern.to_fully_qualified()? {
+                let predicates = fq_pattern.to_flat_pattern().predicates;
+                self.marks.push((*mark, predicates.into_iter().collect()));
+            }
+        }
+        Ok(self)
+    }
+
+    // Returns the MARK for a pattern, if its predicates exactly match
+    // those of a declared MARK
+    pub fn get_mark(&self, pattern: &FlatPattern) -> Option<u32> {
+        let predicates = pattern.predicates.iter().cloned().collect::<BTreeSet<_>>();
+        self.marks
+            .iter()
+            .find(|(_, mark_preds)| *mark_preds == predicates)
+            .map(|(mark, _)| *mark)
+    }
+
     // Returns disjunct of layered patterns
     pub fn get_patterns_layered(&self) -> Vec<LayeredPattern> {
         self.patterns.clone()
@@ -144,15 +208,20 @@ impl Filter {
         todo!();
     }
 
-    pub(crate) fn set_hardware_filter(&self, port: &Port) -> Result<()> {
+    // Installs the filter in the port's hardware, or only validates it if
+    // `dry_run` is set. If any rule fails to install, no rules are kept.
+    #[cfg(not(feature = "heap_mbuf"))]
+    pub(crate) fn set_hardware_filter(
+        &self,
+        port: &dyn FlowDevice,
+        dry_run: bool,
+    ) -> HardwareReport {
         let hw_filter = HardwareFilter::new(self, port);
-        match hw_filter.install() {
-            Ok(_) => Ok(()),
-            Err(error) => {
-                flush_rules(port);
-                bail!(error);
-            }
+        let report = hw_filter.install(dry_run);
+        if !dry_run && !report.is_ok() {
+            port.flush_rules();
         }
+        report
     }
 }
 
@@ -195,6 +264,9 @@ pub enum FilterError {
     #[error("Invalid RHS value for predicate: {0}")]
     InvalidRhsValue(String),
 
+    #[error("Invalid expression in predicate: {0}")]
+    InvalidExpr(String),
+
     #[error("Invalid Integer")]
     InvalidInt {
         #[from]
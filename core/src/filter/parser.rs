@@ -4,6 +4,7 @@ use crate::filter::FilterError;
 use ipnet::{Ipv4Net, Ipv6Net};
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
+use std::collections::HashSet;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::ops::RangeInclusive;
 use std::str::FromStr;
@@ -104,6 +105,11 @@ impl FilterParser {
     fn parse_predicate(pair: Pair<Rule>) -> Result<Vec<Node>> {
         let mut inner = pair.into_inner();
         let protocol = inner.next().unwrap();
+        if let Rule::compare = protocol.as_rule() {
+            return Ok(vec![Node::Predicate(FilterParser::parse_compare(
+                protocol,
+            )?)]);
+        }
         match inner.next() {
             Some(field) => {
                 let op = inner.next().unwrap();
@@ -172,6 +178,116 @@ impl FilterParser {
         }
     }
 
+    fn parse_compare(pair: Pair<Rule>) -> Result<Predicate> {
+        let compare_str = pair.as_str().to_string();
+        let mut inner = pair.into_inner();
+        let lhs = FilterParser::parse_expr(inner.next().unwrap())?;
+        let op = FilterParser::parse_binop(inner.next().unwrap())?;
+        let rhs = FilterParser::parse_expr(inner.next().unwrap())?;
+
+        // Fields must all belong to one protocol, so that the predicate
+        // can be placed in the filter tree.
+        let protocols = lhs
+            .protocols()
+            .into_iter()
+            .chain(rhs.protocols())
+            .collect::<HashSet<_>>();
+        if protocols.len() != 1 {
+            bail!(FilterError::InvalidExpr(compare_str));
+        }
+        let protocol = protocols.into_iter().next().unwrap().clone();
+
+        let valid = match op {
+            BinOp::Eq | BinOp::Ne => {
+                !(lhs.is_int() && rhs.is_text() || lhs.is_text() && rhs.is_int())
+            }
+            BinOp::Ge | BinOp::Le | BinOp::Gt | BinOp::Lt => !lhs.is_text() && !rhs.is_text(),
+            // Patterns must be literals
+            BinOp::Re | BinOp::Contains | BinOp::NotContains => {
+                !lhs.is_int() && matches!(rhs, Expr::Text(_))
+            }
+            _ => false,
+        };
+        if !valid {
+            bail!(FilterError::InvalidExpr(compare_str));
+        }
+        Ok(Predicate::compare(protocol, lhs, op, rhs))
+    }
+
+    fn parse_expr(pair: Pair<Rule>) -> Result<Expr> {
+        let expr_str = pair.as_str().to_string();
+        match pair.as_rule() {
+            Rule::sum | Rule::product => {
+                let mut inner = pair.into_inner();
+                let mut expr = FilterParser::parse_expr(inner.next().unwrap())?;
+                while let Some(op) = inner.next() {
+                    let op = FilterParser::parse_arith_op(op)?;
+                    let rhs = FilterParser::parse_expr(inner.next().unwrap())?;
+                    // Arithmetic is only defined on integers
+                    if expr.is_text()
+                        || rhs.is_text()
+                        || matches!(op, ArithOp::Div | ArithOp::Rem) && rhs == Expr::Int(0)
+                    {
+                        bail!(FilterError::InvalidExpr(expr_str));
+                    }
+                    expr = Expr::Arith {
+                        lhs: Box::new(expr),
+                        op,
+                        rhs: Box::new(rhs),
+                    };
+                }
+                Ok(expr)
+            }
+            Rule::func_call => {
+                let mut inner = pair.into_inner();
+                let func = match inner.next().unwrap().as_str() {
+                    "len" => ExprFn::Len,
+                    "lower" => ExprFn::Lower,
+                    "count" => ExprFn::Count,
+                    _ => bail!(FilterError::InvalidExpr(expr_str)),
+                };
+                let arg = FilterParser::parse_expr(inner.next().unwrap())?;
+                let valid = match func {
+                    ExprFn::Lower => matches!(arg, Expr::Field { .. }),
+                    ExprFn::Len | ExprFn::Count => !arg.is_int() && !arg.is_literal(),
+                };
+                if !valid {
+                    bail!(FilterError::InvalidExpr(expr_str));
+                }
+                Ok(Expr::Call {
+                    func,
+                    arg: Box::new(arg),
+                })
+            }
+            Rule::field_ref => {
+                let mut inner = pair.into_inner();
+                let protocol = FilterParser::parse_protocol(inner.next().unwrap());
+                let field = FilterParser::parse_field(inner.next().unwrap());
+                // `addr` and `port` expand to a disjunct and cannot be used in expressions
+                if field.is_combined() {
+                    bail!(FilterError::InvalidField(expr_str));
+                }
+                Ok(Expr::Field { protocol, field })
+            }
+            Rule::int_lit => Ok(Expr::Int(pair.as_str().parse::<u64>()?)),
+            Rule::text => Ok(Expr::Text(pair.as_str().to_owned())),
+            _ => bail!(FilterError::InvalidExpr(expr_str)),
+        }
+    }
+
+    fn parse_arith_op(pair: Pair<Rule>) -> Result<ArithOp> {
+        let op_str = pair.as_str().to_string();
+        let mut inner = pair.into_inner();
+        match inner.next().unwrap().as_rule() {
+            Rule::add_op => Ok(ArithOp::Add),
+            Rule::sub_op => Ok(ArithOp::Sub),
+            Rule::mul_op => Ok(ArithOp::Mul),
+            Rule::div_op => Ok(ArithOp::Div),
+            Rule::rem_op => Ok(ArithOp::Rem),
+            _ => bail!(FilterError::InvalidExpr(op_str)),
+        }
+    }
+
     fn parse_protocol(pair: Pair<Rule>) -> ProtocolName {
         protocol!(pair.as_str())
     }
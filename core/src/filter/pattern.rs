@@ -1,7 +1,11 @@
 use super::ast::*;
+#[cfg(not(feature = "heap_mbuf"))]
+use super::hardware::{FlowDevice, HardwareFilterError};
 use super::subscription::DataLevelSpec;
 
 use std::cmp::Ordering;
+#[cfg(not(feature = "heap_mbuf"))]
+use std::collections::BTreeMap;
 use std::collections::HashSet;
 use std::fmt;
 
@@ -12,7 +16,6 @@ use petgraph::graph::NodeIndex;
 use crate::conntrack::conn::conn_layers::SupportedLayer;
 use crate::conntrack::conn::conn_state::StateTxOrd;
 use crate::conntrack::LayerState;
-use crate::port::Port;
 use crate::{conntrack::StateTransition, filter::FilterError};
 
 use anyhow::{bail, Result};
@@ -44,7 +47,7 @@ impl FlatPattern {
                     ret = ret && layers.contains_edge(*cur_header, *prev_header);
                     prev_header = cur_header;
                 }
-                Predicate::Binary { protocol, .. } => {
+                Predicate::Binary { protocol, .. } | Predicate::Compare { protocol, .. } => {
                     let cur_header = unwrap_or_ret_false!(labels.get_by_right(protocol));
                     ret = ret && (*cur_header == *prev_header)
                 }
@@ -343,14 +346,32 @@ impl FlatPattern {
         Self { predicates }
     }
 
-    // Returns FlatPattern of only predicates that can be filtered in hardware
-    pub(super) fn retain_hardware_predicates(&self, port: &Port) -> FlatPattern {
+    // Returns FlatPattern of only predicates that can be filtered in hardware.
+    // Predicates that cannot are added to `dropped` with the reason.
+    #[cfg(not(feature = "heap_mbuf"))]
+    pub(super) fn retain_hardware_predicates(
+        &self,
+        port: &dyn FlowDevice,
+        dropped: &mut BTreeMap<Predicate, HardwareFilterError>,
+    ) -> FlatPattern {
         FlatPattern {
             predicates: self
                 .predicates
                 .clone()
                 .into_iter()
-                .filter(|p| p.is_hardware_filterable(port))
+                .filter(|p| {
+                    if dropped.contains_key(p) {
+                        return false;
+                    }
+                    match p.is_hardware_filterable(port) {
+                        Ok(()) => true,
+                        Err(error) => {
+                            log::info!("{}", error);
+                            dropped.insert(p.clone(), error);
+                            false
+                        }
+                    }
+                })
                 .collect::<Vec<_>>(),
         }
     }
@@ -444,7 +465,8 @@ impl LayeredPattern {
                 ret = ret
                     && match pred {
                         Predicate::Unary { .. } => false,
-                        Predicate::Binary { protocol, .. } => protocol == &proto_name,
+                        Predicate::Binary { protocol, .. }
+                        | Predicate::Compare { protocol, .. } => protocol == &proto_name,
                         Predicate::Custom { .. }
                         | Predicate::Callback { .. }
                         | Predicate::LayerState { .. } => false,
@@ -650,4 +672,53 @@ mod tests {
         assert!(with_state.predicates[3].is_custom());
         assert!(with_state.predicates[4].is_state());
     }
+
+    #[test]
+    fn test_compare_dedup() {
+        let filter_raw = "tcp.src_port < tcp.dst_port or tcp.dst_port > tcp.src_port";
+        let filter = Filter::new(filter_raw, &vec![]).unwrap();
+        // One pattern per IP version
+        assert!(filter.get_patterns_flat().len() == 2);
+        assert!(filter
+            .get_patterns_flat()
+            .iter()
+            .all(|p| p.predicates.iter().any(|x| x.is_compare())));
+
+        let filter = Filter::new("len(tls.sni) > 64 or 64 < len(tls.sni)", &vec![]).unwrap();
+        assert!(filter.get_patterns_flat().len() == 2);
+        let filter = Filter::new("lower(http.user_agent) contains 'curl'", &vec![]).unwrap();
+        assert!(filter
+            .get_patterns_flat()
+            .iter()
+            .all(|p| p.predicates.iter().any(|x| x.is_compare())));
+        let filter = Filter::new("ipv4.ttl % 64 > 50", &vec![]).unwrap();
+        assert!(filter.get_patterns_flat().len() == 1);
+        // Divisor that is only zero at runtime
+        assert!(Filter::new("ipv4.ttl % (ipv4.ttl - ipv4.ttl) = 0", &vec![]).is_ok());
+    }
+
+    #[test]
+    fn test_compare_invalid() {
+        let invalid = [
+            // Fields from different protocols
+            "ipv4.ttl > tcp.src_port",
+            "tcp.src_port + ipv4.ttl > 10",
+            // Literal divide-by-zero
+            "ipv4.ttl / 0 > 1",
+            "ipv4.ttl % 0 > 1",
+            // Type mismatches
+            "len(tls.sni) = 'abc'",
+            "lower(tls.sni) > 'abc'",
+            "lower(http.user_agent) + 1 > 2",
+            "len(tls.sni) contains 'x'",
+            "tls.sni ~ tls.sni",
+        ];
+        for filter_raw in invalid {
+            assert!(
+                Filter::new(filter_raw, &vec![]).is_err(),
+                "Should have failed on {}",
+                filter_raw
+            );
+        }
+    }
 }
@@ -275,6 +275,27 @@ impl PredPTree {
         all_filters.join(" or ")
     }
 
+    // Returns the filter string matched by the root->node path of each
+    // node (excluding the root), keyed by node ID.
+    // Used to MARK packets that satisfy a path in hardware.
+    pub fn to_path_strings(&self) -> Vec<(usize, String)> {
+        fn to_path_strings(p: &PredPNode, all: &mut Vec<(usize, String)>, curr: &str) {
+            for child in &p.children {
+                let mut path = curr.to_string();
+                if !path.is_empty() {
+                    path.push_str(" and ");
+                }
+                path.push_str(&format!("({})", child.pred));
+                all.push((child.id, path.clone()));
+                to_path_strings(child, all, &path);
+            }
+        }
+
+        let mut all_paths = Vec::new();
+        to_path_strings(&self.root, &mut all_paths, "");
+        all_paths
+    }
+
     // modified from https://vallentin.dev/2019/05/14/pretty-print-tree
     fn pprint(&self) -> String {
         fn pprint(s: &mut String, node: &PredPNode, prefix: String, last: bool) {
@@ -338,6 +338,15 @@ impl Ord for PNode {
             }
         }
 
+        // Same protocol, at least one comparison between expressions;
+        // sort by field name or by the expression
+        if self.pred.is_binary()
+            && other.pred.is_binary()
+            && self.pred.get_protocol() == other.pred.get_protocol()
+        {
+            return sort_key(&self.pred).cmp(&sort_key(&other.pred));
+        }
+
         // If either has a protocol name, sort by that
         // This also will put all ProtocolName::none predicates
         // (i.e., LayerState or user-defined) next to each other
@@ -357,6 +366,16 @@ impl Ord for PNode {
     }
 }
 
+// Key for ordering binary predicates on the same protocol.
+// Field comparisons sort by field name; comparisons between
+// expressions sort by their string representation.
+fn sort_key(pred: &Predicate) -> String {
+    match pred {
+        Predicate::Binary { field, .. } => field.name().to_owned(),
+        _ => format!("{}", pred),
+    }
+}
+
 // A n-ary tree representing a Filter.
 // Paths from root to leaf represent a pattern for data to match.
 // Filter returns action(s) or delivers data.
@@ -1,18 +1,23 @@
 //! Utilities for managing and monitoring Iris cores.
 
+#[cfg(not(feature = "heap_mbuf"))]
 pub(crate) mod monitor;
 // pub(crate) mod ring;
+#[cfg(not(feature = "heap_mbuf"))]
 pub(crate) mod rx_core;
 
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::dpdk;
 
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
+#[cfg(not(feature = "heap_mbuf"))]
 #[derive(Debug, Copy, Clone, Hash, Ord, Eq, PartialEq, PartialOrd)]
 pub(crate) struct SocketId(pub(crate) u32);
 
+#[cfg(not(feature = "heap_mbuf"))]
 impl SocketId {
     // For DPDK functions
     pub(crate) fn raw(&self) -> u32 {
@@ -20,6 +25,7 @@ impl SocketId {
     }
 }
 
+#[cfg(not(feature = "heap_mbuf"))]
 impl fmt::Display for SocketId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -33,6 +39,7 @@ impl fmt::Display for SocketId {
 pub struct CoreId(pub u32);
 
 impl CoreId {
+    #[cfg(not(feature = "heap_mbuf"))]
     pub(crate) fn socket_id(&self) -> SocketId {
         unsafe { SocketId(dpdk::rte_lcore_to_socket_id(self.0)) }
     }
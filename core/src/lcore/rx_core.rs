@@ -72,6 +72,39 @@ where
         }
     }
 
+    /// Returns the number of descriptors of each RX queue, or `0` if it is unknown.
+    fn rx_descs(&self) -> Vec<u16> {
+        self.rxqueues
+            .iter()
+            .map(|rxqueue| {
+                let mut info: dpdk::rte_eth_rxq_info = unsafe { std::mem::zeroed() };
+                let ret = unsafe {
+                    dpdk::rte_eth_rx_queue_info_get(rxqueue.pid.raw(), rxqueue.qid.raw(), &mut info)
+                };
+                match ret {
+                    0 => info.nb_desc,
+                    _ => 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the largest fraction of descriptors in use across the RX queues with `nb_descs`
+    /// descriptors. Queues whose driver does not report the number of descriptors in use are
+    /// ignored.
+    fn rx_load(&self, nb_descs: &[u16]) -> f64 {
+        self.rxqueues
+            .iter()
+            .zip(nb_descs)
+            .filter(|(_, nb_desc)| **nb_desc > 0)
+            .map(|(rxqueue, nb_desc)| {
+                let used =
+                    unsafe { dpdk::rte_eth_rx_queue_count(rxqueue.pid.raw(), rxqueue.qid.raw()) };
+                used.max(0) as f64 / *nb_desc as f64
+            })
+            .fold(0.0, f64::max)
+    }
+
     pub(crate) fn rx_loop(&self) {
         // TODO: need check to enforce that each core only has same queue types
         if self.rxqueues[0].ty == RxQueueType::Receive {
@@ -95,6 +128,10 @@ where
         let registry = S::Tracked::parsers();
         log::debug!("{:#?}", registry);
         let mut conn_table = ConnTracker::<S::Tracked>::new(config, registry, self.id);
+        let nb_descs = match self.conntrack.shedding {
+            Some(_) => self.rx_descs(),
+            None => vec![],
+        };
 
         let mut now = Instant::now();
 
@@ -108,6 +145,9 @@ where
                 TOTAL_CYCLES.inc();
                 if TOTAL_CYCLES.get() & 1023 == 512 {
                     now = Instant::now();
+                    if !nb_descs.is_empty() {
+                        conn_table.set_rx_load(self.rx_load(&nb_descs));
+                    }
                 }
                 #[cfg(feature = "prometheus")]
                 if TOTAL_CYCLES.get() & 1023 == 0 && self.is_prometheus_enabled {
@@ -7,12 +7,19 @@
 mod timing;
 pub mod config;
 pub mod conntrack;
+#[cfg(not(feature = "heap_mbuf"))]
 #[doc(hidden)]
 #[allow(clippy::all)]
 mod dpdk;
+#[cfg(feature = "heap_mbuf")]
+#[doc(hidden)]
+#[path = "dpdk/heap.rs"]
+mod dpdk;
 pub mod filter;
 pub mod lcore;
 pub mod memory;
+pub mod output;
+#[cfg(not(feature = "heap_mbuf"))]
 mod port;
 pub mod protocols;
 mod runtime;
@@ -23,7 +30,7 @@ pub mod utils;
 
 pub use self::conntrack::conn_id::{ConnId, FiveTuple};
 pub use self::conntrack::pdu::L4Pdu;
-pub use self::conntrack::{DataLevel, StateTransition, StateTxData};
+pub use self::conntrack::{ConnSample, DataLevel, StateTransition, StateTxData};
 pub use self::lcore::CoreId;
 pub use self::memory::mbuf::Mbuf;
 pub use self::runtime::Runtime;
@@ -6,20 +6,31 @@
 //! will be allocated with the specified size, so allowing jumbo frames will limit the maximum
 //! number of Mbufs available in the memory pool.
 //!
+//! With the `heap_mbuf` feature, Mbufs are instead backed by reference-counted heap memory and
+//! DPDK is not used. This backend only supports offline analysis.
+//!
 //! This module is adapted from
 //! [capsule::Mbuf](https://docs.rs/capsule/0.1.5/capsule/struct.Mbuf.html).
 
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::dpdk;
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::memory::mempool::MempoolError;
 use crate::protocols::packet::{Packet, PacketHeader, PacketParseError};
 
 use std::fmt;
+#[cfg(not(feature = "heap_mbuf"))]
 use std::ptr::NonNull;
 use std::slice;
+#[cfg(feature = "heap_mbuf")]
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "heap_mbuf")]
+use std::sync::Arc;
 
 use anyhow::{bail, Result};
 use thiserror::Error;
 
+#[cfg(not(feature = "heap_mbuf"))]
 #[derive(Clone)]
 /// A packet buffer.
 ///
@@ -28,6 +39,25 @@ pub struct Mbuf {
     raw: NonNull<dpdk::rte_mbuf>,
 }
 
+#[cfg(feature = "heap_mbuf")]
+#[derive(Clone)]
+/// A packet buffer.
+///
+/// This is a reference-counted heap buffer that represents a single Ethernet frame. Clones share
+/// the same frame.
+pub struct Mbuf {
+    buf: Arc<HeapBuf>,
+}
+
+/// Backing storage of a heap-allocated Mbuf.
+#[cfg(feature = "heap_mbuf")]
+struct HeapBuf {
+    data: Box<[u8]>,
+    mark: AtomicU32,
+    rss_hash: u32,
+}
+
+#[cfg(not(feature = "heap_mbuf"))]
 impl Mbuf {
     /// Creates a new Mbuf from rte_mbuf raw pointer. `mbuf` must be non-null.
     pub(crate) fn new_unchecked(mbuf: *mut dpdk::rte_mbuf) -> Mbuf {
@@ -85,15 +115,112 @@ impl Mbuf {
         unsafe { self.raw.as_mut() }
     }
 
-    /// Returns the UNIX timestamp of the packet.
+    /// Returns the length of the data in the Mbuf.
+    pub fn data_len(&self) -> usize {
+        self.raw().get_data_len() as usize
+    }
+
+    /// Returns the raw pointer from the offset.
+    fn get_data_address(&self, offset: usize) -> *const u8 {
+        let raw = self.raw();
+        unsafe { (raw.buf_addr as *const u8).offset(raw.get_data_off() as isize + offset as isize) }
+    }
+
+    /// Returns the ID of the port the Mbuf was received on.
+    pub(crate) fn port(&self) -> u16 {
+        self.raw().get_port()
+    }
+
+    /// Returns the RSS hash of the Mbuf computed by the NIC.
     #[allow(dead_code)]
-    pub(crate) fn timestamp(&self) -> usize {
-        unimplemented!();
+    pub(crate) fn rss_hash(&self) -> u32 {
+        self.raw().get_rss_hash()
+    }
+
+    /// Returns any MARKs tagged on the Mbuf by the NIC.
+    #[allow(dead_code)]
+    pub(crate) fn mark(&self) -> u32 {
+        self.raw().get_mark()
+    }
+
+    /// Returns the MARK tagged on the Mbuf by a hardware flow rule, if any.
+    #[inline]
+    pub fn flow_mark(&self) -> Option<u32> {
+        if self.raw().get_ol_flags() & dpdk::PKT_RX_FDIR_ID as u64 != 0 {
+            Some(self.raw().get_mark())
+        } else {
+            None
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn add_mark(&mut self, mark: u32) {
+        self.raw_mut().set_mark(mark);
+    }
+}
+
+#[cfg(feature = "heap_mbuf")]
+impl Mbuf {
+    pub fn new_ref(mbuf: &Mbuf) -> Mbuf {
+        mbuf.clone()
+    }
+
+    /// Creates a new Mbuf from a byte slice.
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Mbuf> {
+        Ok(Mbuf {
+            buf: Arc::new(HeapBuf {
+                data: data.into(),
+                mark: AtomicU32::new(0),
+                rss_hash: 0,
+            }),
+        })
     }
 
     /// Returns the length of the data in the Mbuf.
     pub fn data_len(&self) -> usize {
-        self.raw().get_data_len() as usize
+        self.buf.data.len()
+    }
+
+    /// Returns the raw pointer from the offset.
+    fn get_data_address(&self, offset: usize) -> *const u8 {
+        self.buf.data.as_ptr().wrapping_add(offset)
+    }
+
+    /// Returns the ID of the port the Mbuf was received on. Always `0`, as there are no ports.
+    pub(crate) fn port(&self) -> u16 {
+        0
+    }
+
+    /// Returns the RSS hash of the Mbuf. Always `0`, as there is no NIC to compute it.
+    #[allow(dead_code)]
+    pub(crate) fn rss_hash(&self) -> u32 {
+        self.buf.rss_hash
+    }
+
+    /// Returns any MARKs tagged on the Mbuf.
+    #[allow(dead_code)]
+    pub(crate) fn mark(&self) -> u32 {
+        self.buf.mark.load(Ordering::Relaxed)
+    }
+
+    /// Returns the MARK tagged on the Mbuf by a hardware flow rule. Always `None`, as there is no
+    /// NIC to apply flow rules.
+    #[inline]
+    pub fn flow_mark(&self) -> Option<u32> {
+        None
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn add_mark(&mut self, mark: u32) {
+        self.buf.mark.store(mark, Ordering::Relaxed);
+    }
+}
+
+impl Mbuf {
+    /// Returns the UNIX timestamp of the packet.
+    #[allow(dead_code)]
+    pub(crate) fn timestamp(&self) -> usize {
+        unimplemented!();
     }
 
     /// Returns the contents of the Mbuf as a byte slice.
@@ -134,34 +261,9 @@ impl Mbuf {
         }
     }
 
-    /// Returns the raw pointer from the offset.
-    fn get_data_address(&self, offset: usize) -> *const u8 {
-        let raw = self.raw();
-        unsafe { (raw.buf_addr as *const u8).offset(raw.get_data_off() as isize + offset as isize) }
-    }
-
-    /// Returns the RSS hash of the Mbuf computed by the NIC.
-    #[allow(dead_code)]
-    pub(crate) fn rss_hash(&self) -> u32 {
-        self.raw().get_rss_hash()
-    }
-
-    /// Returns any MARKs tagged on the Mbuf by the NIC.
-    #[allow(dead_code)]
-    pub(crate) fn mark(&self) -> u32 {
-        self.raw().get_mark()
-    }
-
-    #[allow(dead_code)]
-    pub(crate) fn add_mark(&mut self, mark: u32) {
-        self.raw_mut().set_mark(mark);
-    }
-
-    // TODO
-    #[allow(dead_code)]
-    pub fn has_mark(&mut self, _mark: u32) -> bool {
-        // unsafe { self.raw().ol_flags & PKT_RX_FDIR != 0 }
-        true
+    /// Returns `true` if the Mbuf was tagged with `mark` by a hardware flow rule.
+    pub fn has_mark(&self, mark: u32) -> bool {
+        self.flow_mark() == Some(mark)
     }
 }
 
@@ -191,6 +293,7 @@ impl<'a> Packet<'a> for Mbuf {
     }
 }
 
+#[cfg(not(feature = "heap_mbuf"))]
 impl Drop for Mbuf {
     fn drop(&mut self) {
         // log::debug!("Dropping a Mbuf, freeing mbuf@{:p}", self.raw().buf_addr);
@@ -204,6 +307,7 @@ impl Drop for Mbuf {
     }
 }
 
+#[cfg(not(feature = "heap_mbuf"))]
 impl fmt::Debug for Mbuf {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let raw = self.raw();
@@ -217,15 +321,21 @@ impl fmt::Debug for Mbuf {
     }
 }
 
+#[cfg(feature = "heap_mbuf")]
+impl fmt::Debug for Mbuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mbuf")
+            .field("buf_addr", &self.buf.data.as_ptr())
+            .field("data_len", &self.data_len())
+            .finish()
+    }
+}
+
 // displays the actual packet data of the frame (first segment only)
 impl fmt::Display for Mbuf {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for byte in 0..self.raw().get_data_len() {
-            write!(
-                f,
-                "{:02x} ",
-                self.get_data_slice(byte as usize, 1).unwrap()[0]
-            )?;
+        for byte in 0..self.data_len() {
+            write!(f, "{:02x} ", self.get_data_slice(byte, 1).unwrap()[0])?;
             if byte % 16 == 15 {
                 writeln!(f,)?;
             }
@@ -242,6 +352,7 @@ pub(crate) enum MbufError {
     #[error("Data read exceeds Mbuf segment buffer")]
     ReadPastBuffer,
 
+    #[cfg(not(feature = "heap_mbuf"))]
     #[error("Data write exceeds Mbuf segment buffer")]
     WritePastBuffer,
 }
@@ -1,4 +1,5 @@
 //! Packet memory buffer management.
 
 pub mod mbuf;
+#[cfg(not(feature = "heap_mbuf"))]
 pub(crate) mod mempool;
@@ -0,0 +1,215 @@
+//! Rotated per-core JSONL and CSV files.
+
+use super::Sink;
+use crate::config::OutputFormat;
+use crate::lcore::CoreId;
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde_json::Value;
+
+/// An output file of a single core that is replaced by a new file once it exceeds a size limit.
+struct RotatingFile {
+    path: String,
+    core_id: CoreId,
+    ext: &'static str,
+    rotate_bytes: Option<u64>,
+    /// Number of bytes written to the current file.
+    written: u64,
+    writer: BufWriter<File>,
+    /// All files opened, in order.
+    files: Vec<PathBuf>,
+}
+
+impl RotatingFile {
+    fn new(
+        path: &str,
+        core_id: CoreId,
+        ext: &'static str,
+        rotate_bytes: Option<u64>,
+    ) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let file = Self::file_name(path, core_id, 0, ext);
+        Ok(RotatingFile {
+            path: path.to_string(),
+            core_id,
+            ext,
+            rotate_bytes,
+            written: 0,
+            writer: BufWriter::new(File::create(&file)?),
+            files: vec![file],
+        })
+    }
+
+    /// `{path}_{core}.{ext}` for the first file, and `{path}_{core}.{index}.{ext}` after.
+    fn file_name(path: &str, core_id: CoreId, index: usize, ext: &str) -> PathBuf {
+        match index {
+            0 => PathBuf::from(format!("{}_{}.{}", path, core_id.raw(), ext)),
+            _ => PathBuf::from(format!("{}_{}.{}.{}", path, core_id.raw(), index, ext)),
+        }
+    }
+
+    /// Starts a new file if the current file exceeds the size limit. Returns `true` if a new file
+    /// was started.
+    fn rotate(&mut self) -> Result<bool> {
+        match self.rotate_bytes {
+            Some(limit) if self.written >= limit => {
+                self.writer.flush()?;
+                let file = Self::file_name(&self.path, self.core_id, self.files.len(), self.ext);
+                self.writer = BufWriter::new(File::create(&file)?);
+                self.files.push(file);
+                self.written = 0;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<()> {
+        self.writer.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<PathBuf>> {
+        self.writer.flush()?;
+        Ok(self.files.clone())
+    }
+}
+
+/// Writes records as newline-delimited JSON.
+pub struct JsonlSink {
+    file: RotatingFile,
+}
+
+impl JsonlSink {
+    pub fn new(path: &str, core_id: CoreId, rotate_bytes: Option<u64>) -> Result<Self> {
+        Ok(JsonlSink {
+            file: RotatingFile::new(path, core_id, "jsonl", rotate_bytes)?,
+        })
+    }
+}
+
+impl Sink for JsonlSink {
+    fn write_batch(&mut self, records: &[Value]) -> Result<()> {
+        let mut line = vec![];
+        for record in records {
+            self.file.rotate()?;
+            line.clear();
+            serde_json::to_writer(&mut line, record)?;
+            line.push(b'\n');
+            self.file.write(&line)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<PathBuf>> {
+        self.file.finish()
+    }
+}
+
+/// Writes records as CSV, with one column per field of the first record.
+///
+/// Fields that are not in the first record are dropped, and nested values are written as JSON.
+/// Records that are not objects are written to a single `value` column.
+pub struct CsvSink {
+    file: RotatingFile,
+    /// Column names, taken from the first record.
+    header: Option<Vec<String>>,
+}
+
+impl CsvSink {
+    pub fn new(path: &str, core_id: CoreId, rotate_bytes: Option<u64>) -> Result<Self> {
+        Ok(CsvSink {
+            file: RotatingFile::new(path, core_id, "csv", rotate_bytes)?,
+            header: None,
+        })
+    }
+
+    fn write_row<I, S>(&mut self, fields: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u8]>,
+    {
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.write_record(fields)?;
+        let row = wtr.into_inner().map_err(|e| e.into_error())?;
+        self.file.write(&row)
+    }
+
+    fn field(value: Option<&Value>) -> String {
+        match value {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+        }
+    }
+}
+
+impl Sink for CsvSink {
+    fn write_batch(&mut self, records: &[Value]) -> Result<()> {
+        for record in records {
+            let rotated = self.file.rotate()?;
+            let header = match &self.header {
+                Some(header) => header.clone(),
+                None => {
+                    let header = match record {
+                        Value::Object(fields) => fields.keys().cloned().collect(),
+                        _ => vec!["value".to_string()],
+                    };
+                    self.header = Some(header.clone());
+                    self.write_row(&header)?;
+                    header
+                }
+            };
+            if rotated {
+                self.write_row(&header)?;
+            }
+            let row: Vec<String> = match record {
+                Value::Object(fields) => header
+                    .iter()
+                    .map(|column| Self::field(fields.get(column)))
+                    .collect(),
+                value => vec![Self::field(Some(value))],
+            };
+            self.write_row(&row)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<PathBuf>> {
+        self.file.finish()
+    }
+}
+
+/// Concatenates per-core `files` into `{path}.{ext}` and removes them. CSV headers are only
+/// kept from the first file.
+pub(super) fn merge(format: OutputFormat, path: &str, files: &[PathBuf]) -> Result<()> {
+    let (ext, has_header) = match format {
+        OutputFormat::Jsonl => ("jsonl", false),
+        OutputFormat::Csv => ("csv", true),
+        _ => return Ok(()),
+    };
+    let merged = PathBuf::from(format!("{}.{}", path, ext));
+    log::info!("Merging {} output files into {:?}", files.len(), merged);
+    let mut writer = BufWriter::new(File::create(&merged)?);
+    for (i, file) in files.iter().enumerate() {
+        let mut reader = BufReader::new(File::open(file)?);
+        if has_header && i > 0 {
+            reader.read_line(&mut String::new())?;
+        }
+        io::copy(&mut reader, &mut writer)?;
+    }
+    writer.flush()?;
+    for file in files {
+        fs::remove_file(file)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,321 @@
+//! Minimal Kafka producer.
+//!
+//! Produces each batch of records as a v2 record batch in a single v3 `Produce` request, the
+//! oldest versions supported by Kafka 4. Records are encoded as JSON record values without keys.
+//! Only the configured broker is contacted, so it must lead the configured partition.
+//!
+//! Requests are sent by a producer thread, which waits for the partition leader to acknowledge
+//! each one, so that cores never block on the broker. Batches are dropped if the producer thread
+//! falls too far behind.
+
+use super::Sink;
+use crate::config::KafkaConfig;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+/// API key of `Produce` requests.
+const PRODUCE_API_KEY: i16 = 0;
+/// Version of `Produce` requests.
+const PRODUCE_VERSION: i16 = 3;
+/// Partition leaders acknowledge each request.
+const ACKS: i16 = 1;
+/// Maximum number of requests waiting to be sent by the producer thread.
+const MAX_PENDING: usize = 64;
+
+/// Produces records to a partition of a Kafka topic.
+pub struct KafkaSink {
+    topic: String,
+    partition: i32,
+    client_id: String,
+    timeout: Duration,
+    correlation_id: i32,
+    /// Requests waiting to be sent by the producer thread, with their correlation IDs.
+    requests: Option<SyncSender<(i32, Vec<u8>)>>,
+    producer: Option<JoinHandle<Result<()>>>,
+}
+
+impl KafkaSink {
+    pub fn new(config: &KafkaConfig) -> Result<Self> {
+        let timeout = Duration::from_millis(config.timeout);
+        let stream = TcpStream::connect(&config.broker)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+        let (requests, pending) = mpsc::sync_channel(MAX_PENDING);
+        let mut producer = Producer {
+            stream,
+            topic: config.topic.clone(),
+            partition: config.partition,
+        };
+        let producer = thread::Builder::new()
+            .name("iris-kafka".to_string())
+            .spawn(move || producer.run(pending))?;
+        Ok(KafkaSink {
+            topic: config.topic.clone(),
+            partition: config.partition,
+            client_id: config.client_id.clone(),
+            timeout,
+            correlation_id: 0,
+            requests: Some(requests),
+            producer: Some(producer),
+        })
+    }
+
+    /// Encodes a `Produce` request (v3) for `records`.
+    fn produce_request(&self, records: &[Value]) -> Result<Vec<u8>> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        let batch = record_batch(records, timestamp)?;
+
+        let mut request = vec![];
+        put_i16(&mut request, PRODUCE_API_KEY);
+        put_i16(&mut request, PRODUCE_VERSION);
+        put_i32(&mut request, self.correlation_id);
+        put_string(&mut request, &self.client_id);
+        put_i16(&mut request, -1); // null transactional ID
+        put_i16(&mut request, ACKS);
+        put_i32(&mut request, self.timeout.as_millis() as i32);
+        put_i32(&mut request, 1); // topics
+        put_string(&mut request, &self.topic);
+        put_i32(&mut request, 1); // partitions
+        put_i32(&mut request, self.partition);
+        put_bytes(&mut request, &batch);
+
+        let mut framed = Vec::with_capacity(request.len() + 4);
+        put_i32(&mut framed, request.len() as i32);
+        framed.extend_from_slice(&request);
+        Ok(framed)
+    }
+}
+
+impl Sink for KafkaSink {
+    fn write_batch(&mut self, records: &[Value]) -> Result<()> {
+        self.correlation_id = self.correlation_id.wrapping_add(1);
+        let request = self.produce_request(records)?;
+        let requests = match &self.requests {
+            Some(requests) => requests,
+            None => bail!("Kafka producer closed"),
+        };
+        match requests.try_send((self.correlation_id, request)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                bail!("Kafka producer is {} requests behind", MAX_PENDING)
+            }
+            Err(TrySendError::Disconnected(_)) => bail!("Kafka producer stopped"),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<PathBuf>> {
+        // Closing the channel stops the producer thread once it has sent all pending requests
+        self.requests.take();
+        if let Some(producer) = self.producer.take() {
+            producer
+                .join()
+                .map_err(|_| anyhow!("Kafka producer panicked"))??;
+        }
+        Ok(vec![])
+    }
+}
+
+/// Sends `Produce` requests to the broker and waits for their acknowledgements.
+struct Producer {
+    stream: TcpStream,
+    topic: String,
+    partition: i32,
+}
+
+impl Producer {
+    /// Sends requests until the sink is closed. Returns early if the connection fails.
+    fn run(&mut self, pending: Receiver<(i32, Vec<u8>)>) -> Result<()> {
+        for (correlation_id, request) in pending {
+            self.stream.write_all(&request)?;
+            match self.read_response(correlation_id)? {
+                0 => (),
+                error_code => log::warn!("Kafka produce failed with error code {}", error_code),
+            }
+        }
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Reads a `Produce` response (v3) and returns the partition's error code.
+    fn read_response(&mut self, correlation_id: i32) -> Result<i16> {
+        let mut size = [0; 4];
+        self.stream.read_exact(&mut size)?;
+        let mut response = vec![0; i32::from_be_bytes(size).max(0) as usize];
+        self.stream.read_exact(&mut response)?;
+
+        let mut rdr = Reader(&response);
+        if rdr.i32()? != correlation_id {
+            bail!("Unexpected Kafka correlation ID");
+        }
+        for _ in 0..rdr.i32()? {
+            let topic = rdr.string()?;
+            for _ in 0..rdr.i32()? {
+                let partition = rdr.i32()?;
+                let error_code = rdr.i16()?;
+                let _base_offset = rdr.i64()?;
+                let _log_append_time = rdr.i64()?;
+                if topic == self.topic && partition == self.partition {
+                    return Ok(error_code);
+                }
+            }
+        }
+        bail!("Kafka response missing partition {}", self.partition)
+    }
+}
+
+/// Encodes `records` as a record batch (v2) created at `timestamp` (in milliseconds since the
+/// Unix epoch).
+fn record_batch(records: &[Value], timestamp: i64) -> Result<Vec<u8>> {
+    // Fields covered by the CRC
+    let mut body = vec![];
+    put_i16(&mut body, 0); // attributes (no compression, create time)
+    put_i32(&mut body, records.len() as i32 - 1); // last offset delta
+    put_i64(&mut body, timestamp); // first timestamp
+    put_i64(&mut body, timestamp); // max timestamp
+    put_i64(&mut body, -1); // no producer ID
+    put_i16(&mut body, -1); // no producer epoch
+    put_i32(&mut body, -1); // no base sequence
+    put_i32(&mut body, records.len() as i32);
+    for (offset_delta, record) in records.iter().enumerate() {
+        let value = serde_json::to_vec(record)?;
+        let mut encoded = vec![0]; // attributes
+        put_varint(&mut encoded, 0); // timestamp delta
+        put_varint(&mut encoded, offset_delta as i64);
+        put_varint(&mut encoded, -1); // null key
+        put_varint(&mut encoded, value.len() as i64);
+        encoded.extend_from_slice(&value);
+        put_varint(&mut encoded, 0); // headers
+        put_varint(&mut body, encoded.len() as i64);
+        body.extend_from_slice(&encoded);
+    }
+
+    let mut batch = vec![];
+    put_i64(&mut batch, 0); // base offset, assigned by the broker
+    put_i32(&mut batch, body.len() as i32 + 9);
+    put_i32(&mut batch, -1); // partition leader epoch
+    put_i8(&mut batch, 2); // magic
+    put_u32(&mut batch, crc32c(&body));
+    batch.extend_from_slice(&body);
+    Ok(batch)
+}
+
+fn put_i8(buf: &mut Vec<u8>, v: i8) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_i16(buf: &mut Vec<u8>, v: i16) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_i32(buf: &mut Vec<u8>, v: i32) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_i64(buf: &mut Vec<u8>, v: i64) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    put_i16(buf, s.len() as i16);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, b: &[u8]) {
+    put_i32(buf, b.len() as i32);
+    buf.extend_from_slice(b);
+}
+
+/// Appends a zigzag-encoded variable-length integer.
+fn put_varint(buf: &mut Vec<u8>, v: i64) {
+    let mut v = ((v << 1) ^ (v >> 63)) as u64;
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+/// Reads big-endian Kafka protocol fields.
+pub(super) struct Reader<'a>(pub(super) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.0.len() < N {
+            bail!("Truncated Kafka message");
+        }
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        Ok(head.try_into()?)
+    }
+
+    #[cfg(test)]
+    pub(super) fn i8(&mut self) -> Result<i8> {
+        Ok(i8::from_be_bytes(self.take()?))
+    }
+
+    pub(super) fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.take()?))
+    }
+
+    pub(super) fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take()?))
+    }
+
+    pub(super) fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_be_bytes(self.take()?))
+    }
+
+    /// Reads a zigzag-encoded variable-length integer.
+    #[cfg(test)]
+    pub(super) fn varint(&mut self) -> Result<i64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let [byte] = self.take()?;
+            v |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((v >> 1) as i64 ^ -((v & 1) as i64));
+            }
+        }
+        bail!("Invalid Kafka varint")
+    }
+
+    pub(super) fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("Truncated Kafka message");
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    pub(super) fn string(&mut self) -> Result<String> {
+        let len = self.i16()?.max(0) as usize;
+        Ok(String::from_utf8(self.bytes(len)?.to_vec())?)
+    }
+}
+
+/// CRC-32C (Castagnoli) checksum of Kafka record batches.
+pub(super) fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0x82F6_3B78 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,299 @@
+//! Per-core output of callback results.
+//!
+//! Callbacks emit `Serialize` records with [emit], along with the core they run on. Each core
+//! buffers its records in thread-local state and writes them in batches to its own [Sink], created
+//! on the core's first record, so cores never share a writer or contend on a lock. The runtime
+//! flushes and closes all sinks and merges per-core files when it shuts down.
+//!
+//! Built-in sinks write rotated JSONL or CSV files, Parquet files (with the `parquet` feature), or
+//! produce to a Kafka broker, as configured in [OutputConfig](crate::config::OutputConfig).
+//! Applications can provide their own sink with [register_sink].
+//!
+//! ## Example
+//! ```rust,ignore
+//! #[callback("tls,level=L4Terminated")]
+//! fn log_tls(tls: &TlsHandshake, conn: &ConnRecord, core_id: &CoreId) {
+//!     iris_core::output::emit(core_id, &(tls.sni(), conn));
+//! }
+//! ```
+
+mod file;
+mod kafka;
+#[cfg(feature = "parquet")]
+mod parquet;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::file::{CsvSink, JsonlSink};
+pub use self::kafka::KafkaSink;
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetSink;
+
+use crate::config::{OutputConfig, OutputFormat};
+use crate::lcore::CoreId;
+
+use std::cell::{OnceCell, UnsafeCell};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Destination for the records emitted on a single core.
+pub trait Sink: Send {
+    /// Writes a batch of records.
+    fn write_batch(&mut self, records: &[Value]) -> Result<()>;
+
+    /// Flushes and closes the sink. Returns the files written, in order, if they should be merged
+    /// on shutdown.
+    fn finish(&mut self) -> Result<Vec<PathBuf>>;
+}
+
+type SinkFactory = Box<dyn Fn(CoreId) -> Result<Box<dyn Sink>> + Send + Sync>;
+type MergeFn = Box<dyn Fn(&[PathBuf]) -> Result<()> + Send + Sync>;
+
+/// Output settings shared by all cores.
+struct Output {
+    /// Creates the sink of a core.
+    factory: SinkFactory,
+    /// Number of records buffered per core before they are written.
+    batch_size: usize,
+    /// Merges the files written by all cores on shutdown.
+    merge: Option<MergeFn>,
+}
+
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+/// Handles to the output state of every core that has emitted records, flushed by [finish]. Only
+/// locked when a core emits its first record.
+static CORES: Mutex<Vec<Arc<CoreCell>>> = Mutex::new(vec![]);
+
+thread_local! {
+    /// Output state of the current core.
+    static CORE: OnceCell<Arc<CoreCell>> = const { OnceCell::new() };
+}
+
+/// Output state of a core, accessed without locking.
+struct CoreCell(UnsafeCell<CoreOutput>);
+
+// SAFETY: the state is only accessed by the thread that created it, and by `finish` once no core
+// emits records.
+unsafe impl Sync for CoreCell {}
+
+/// Records buffered on a single core.
+struct CoreOutput {
+    /// Core that created the output, used to name its sink.
+    core_id: CoreId,
+    batch: Vec<Value>,
+    /// `None` if the sink could not be created, in which case records are dropped.
+    sink: Option<Box<dyn Sink>>,
+    /// Number of records that could not be written.
+    dropped: u64,
+}
+
+impl CoreOutput {
+    fn new(core_id: CoreId, output: &Output) -> Self {
+        let sink = match (output.factory)(core_id) {
+            Ok(sink) => Some(sink),
+            Err(error) => {
+                log::error!(
+                    "Failed to create output sink on core {}: {}",
+                    core_id,
+                    error
+                );
+                None
+            }
+        };
+        CoreOutput {
+            core_id,
+            batch: Vec::with_capacity(output.batch_size),
+            sink,
+            dropped: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        match &mut self.sink {
+            Some(sink) => {
+                if let Err(error) = sink.write_batch(&self.batch) {
+                    log::warn!("Failed to write output on core {}: {}", self.core_id, error);
+                    self.dropped += self.batch.len() as u64;
+                }
+            }
+            None => self.dropped += self.batch.len() as u64,
+        }
+        self.batch.clear();
+    }
+
+    fn finish(&mut self) -> Vec<PathBuf> {
+        self.flush();
+        if self.dropped > 0 {
+            log::warn!(
+                "Dropped {} output records on core {}",
+                self.dropped,
+                self.core_id
+            );
+        }
+        match self.sink.take().map(|mut sink| sink.finish()) {
+            Some(Ok(files)) => files,
+            Some(Err(error)) => {
+                log::error!("Failed to close output on core {}: {}", self.core_id, error);
+                vec![]
+            }
+            None => vec![],
+        }
+    }
+}
+
+/// Initializes output from the runtime configuration.
+pub(crate) fn init(config: &OutputConfig) -> Result<()> {
+    if OUTPUT.get().is_some() {
+        log::info!("Output sink already registered; ignoring output configuration");
+        return Ok(());
+    }
+    let factory: SinkFactory = match config.format {
+        OutputFormat::Jsonl => {
+            let config = config.clone();
+            Box::new(move |core_id| {
+                let sink = JsonlSink::new(&config.path, core_id, config.rotate_bytes)?;
+                Ok(Box::new(sink) as Box<dyn Sink>)
+            })
+        }
+        OutputFormat::Csv => {
+            let config = config.clone();
+            Box::new(move |core_id| {
+                let sink = CsvSink::new(&config.path, core_id, config.rotate_bytes)?;
+                Ok(Box::new(sink) as Box<dyn Sink>)
+            })
+        }
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet => {
+            let config = config.clone();
+            Box::new(move |core_id| {
+                let sink = ParquetSink::new(&config.path, core_id)?;
+                Ok(Box::new(sink) as Box<dyn Sink>)
+            })
+        }
+        #[cfg(not(feature = "parquet"))]
+        OutputFormat::Parquet => bail!("Parquet output requires the `parquet` feature"),
+        OutputFormat::Kafka => {
+            let kafka = match &config.kafka {
+                Some(kafka) => kafka.clone(),
+                None => bail!("Kafka output requires [output.kafka] settings"),
+            };
+            Box::new(move |_core_id| {
+                let sink = KafkaSink::new(&kafka)?;
+                Ok(Box::new(sink) as Box<dyn Sink>)
+            })
+        }
+    };
+    let merge: Option<MergeFn> =
+        match config.merge && matches!(config.format, OutputFormat::Jsonl | OutputFormat::Csv) {
+            true => {
+                let (format, path) = (config.format, config.path.clone());
+                Some(Box::new(move |files| file::merge(format, &path, files)))
+            }
+            false => None,
+        };
+    set_output(Output {
+        factory,
+        batch_size: config.batch_size.max(1),
+        merge,
+    })
+}
+
+/// Sends emitted records to sinks created by `factory`, in batches of `batch_size` records.
+///
+/// # Remarks
+///
+/// Must be called before the runtime is created. Takes precedence over `[output]` in the runtime
+/// configuration. `factory` is invoked on each core the first time it emits a record.
+pub fn register_sink<F>(batch_size: usize, factory: F) -> Result<()>
+where
+    F: Fn(CoreId) -> Result<Box<dyn Sink>> + Send + Sync + 'static,
+{
+    set_output(Output {
+        factory: Box::new(factory),
+        batch_size: batch_size.max(1),
+        merge: None,
+    })
+}
+
+/// Like [register_sink], but passes the files returned by all sinks to `merge` on shutdown.
+pub fn register_sink_with_merge<F, M>(batch_size: usize, factory: F, merge: M) -> Result<()>
+where
+    F: Fn(CoreId) -> Result<Box<dyn Sink>> + Send + Sync + 'static,
+    M: Fn(&[PathBuf]) -> Result<()> + Send + Sync + 'static,
+{
+    set_output(Output {
+        factory: Box::new(factory),
+        batch_size: batch_size.max(1),
+        merge: Some(Box::new(merge)),
+    })
+}
+
+fn set_output(output: Output) -> Result<()> {
+    if OUTPUT.set(output).is_err() {
+        bail!("Output sink already initialized");
+    }
+    Ok(())
+}
+
+/// Emits a record from the core `core_id`.
+///
+/// Records are buffered and written to the core's sink in batches. Records are discarded if no
+/// output is configured.
+///
+/// # Remarks
+///
+/// Output state is kept per thread: `core_id` only names the sink created on the thread's first
+/// record.
+pub fn emit<T: Serialize + ?Sized>(core_id: &CoreId, record: &T) {
+    let output = match OUTPUT.get() {
+        Some(output) => output,
+        None => return,
+    };
+    CORE.with(|core| {
+        let core = core.get_or_init(|| {
+            let core = Arc::new(CoreCell(UnsafeCell::new(CoreOutput::new(*core_id, output))));
+            CORES.lock().unwrap().push(Arc::clone(&core));
+            core
+        });
+        // SAFETY: no other thread accesses the state until `finish`.
+        let core = unsafe { &mut *core.0.get() };
+        match serde_json::to_value(record) {
+            Ok(value) => core.batch.push(value),
+            Err(error) => {
+                log::warn!("Failed to serialize output record: {}", error);
+                core.dropped += 1;
+                return;
+            }
+        }
+        if core.batch.len() >= output.batch_size {
+            core.flush();
+        }
+    });
+}
+
+/// Flushes and closes the sinks of all cores, then merges per-core files if configured.
+///
+/// Must only be called once no core emits records.
+pub(crate) fn finish() -> Result<()> {
+    let mut files = vec![];
+    for core in CORES.lock().unwrap().iter() {
+        // SAFETY: cores have stopped emitting records, so their state is no longer accessed.
+        let core = unsafe { &mut *core.0.get() };
+        files.extend(core.finish());
+    }
+    if let Some(merge) = OUTPUT.get().and_then(|output| output.merge.as_ref()) {
+        if !files.is_empty() {
+            merge(&files)?;
+        }
+    }
+    Ok(())
+}
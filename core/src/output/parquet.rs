@@ -0,0 +1,197 @@
+//! Per-core Parquet files.
+
+use super::Sink;
+use crate::lcore::CoreId;
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde_json::Value;
+
+/// Physical type of a column, inferred from the first record.
+#[derive(Debug, Clone, Copy)]
+enum Column {
+    Bool,
+    Int,
+    Double,
+    /// Strings, or other values encoded as JSON.
+    Utf8,
+}
+
+impl Column {
+    fn infer(value: &Value) -> Self {
+        match value {
+            Value::Bool(_) => Column::Bool,
+            Value::Number(n) if n.is_i64() => Column::Int,
+            Value::Number(_) => Column::Double,
+            _ => Column::Utf8,
+        }
+    }
+
+    fn schema(&self) -> &'static str {
+        match self {
+            Column::Bool => "BOOLEAN",
+            Column::Int => "INT64",
+            Column::Double => "DOUBLE",
+            Column::Utf8 => "BINARY (UTF8)",
+        }
+    }
+}
+
+/// Writes records to `{path}_{core}.parquet`, with one row group per batch.
+///
+/// The schema is inferred from the first record: booleans, integers, and floats are stored as
+/// such, and all other values as JSON strings. All columns are optional. Fields that are not in
+/// the first record are dropped, and values that do not match their column's type are null.
+/// Records that are not objects are written to a single `value` column.
+pub struct ParquetSink {
+    file: PathBuf,
+    /// Column names and types, inferred from the first record.
+    columns: Vec<(String, Column)>,
+    writer: Option<SerializedFileWriter<File>>,
+}
+
+impl ParquetSink {
+    pub fn new(path: &str, core_id: CoreId) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        Ok(ParquetSink {
+            file: PathBuf::from(format!("{}_{}.parquet", path, core_id.raw())),
+            columns: vec![],
+            writer: None,
+        })
+    }
+
+    fn open(&mut self, record: &Value) -> Result<()> {
+        self.columns = match record {
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(name, value)| (name.clone(), Column::infer(value)))
+                .collect(),
+            value => vec![("value".to_string(), Column::infer(value))],
+        };
+        if self.columns.is_empty() {
+            bail!("Cannot write Parquet records without fields");
+        }
+        let fields: String = self
+            .columns
+            .iter()
+            .map(|(name, column)| format!("optional {} {};", column.schema(), sanitize(name)))
+            .collect();
+        let schema = Arc::new(parse_message_type(&format!(
+            "message record {{ {} }}",
+            fields
+        ))?);
+        let props = Arc::new(WriterProperties::builder().build());
+        self.writer = Some(SerializedFileWriter::new(
+            File::create(&self.file)?,
+            schema,
+            props,
+        )?);
+        Ok(())
+    }
+}
+
+impl Sink for ParquetSink {
+    fn write_batch(&mut self, records: &[Value]) -> Result<()> {
+        if self.writer.is_none() {
+            match records.first() {
+                Some(record) => self.open(record)?,
+                None => return Ok(()),
+            }
+        }
+        let writer = self.writer.as_mut().unwrap();
+        let mut row_group = writer.next_row_group()?;
+        let mut columns = self.columns.iter();
+        while let Some(mut col_writer) = row_group.next_column()? {
+            let (name, column) = match columns.next() {
+                Some(column) => column,
+                None => bail!("Parquet schema does not match columns"),
+            };
+            let values = records.iter().map(|record| match record {
+                Value::Object(fields) => fields.get(name).filter(|v| !v.is_null()),
+                value => Some(value).filter(|v| !v.is_null()),
+            });
+            let mut defs = Vec::with_capacity(records.len());
+            match column {
+                Column::Bool => {
+                    let vals = defined(values.map(|v| v.and_then(Value::as_bool)), &mut defs);
+                    col_writer
+                        .typed::<BoolType>()
+                        .write_batch(&vals, Some(&defs), None)?;
+                }
+                Column::Int => {
+                    let vals = defined(values.map(|v| v.and_then(Value::as_i64)), &mut defs);
+                    col_writer
+                        .typed::<Int64Type>()
+                        .write_batch(&vals, Some(&defs), None)?;
+                }
+                Column::Double => {
+                    let vals = defined(values.map(|v| v.and_then(Value::as_f64)), &mut defs);
+                    col_writer
+                        .typed::<DoubleType>()
+                        .write_batch(&vals, Some(&defs), None)?;
+                }
+                Column::Utf8 => {
+                    let vals = defined(
+                        values.map(|v| {
+                            v.map(|v| match v {
+                                Value::String(s) => ByteArray::from(s.as_str()),
+                                v => ByteArray::from(v.to_string().into_bytes()),
+                            })
+                        }),
+                        &mut defs,
+                    );
+                    col_writer
+                        .typed::<ByteArrayType>()
+                        .write_batch(&vals, Some(&defs), None)?;
+                }
+            }
+            col_writer.close()?;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<PathBuf>> {
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        // Parquet files are not concatenated.
+        Ok(vec![])
+    }
+}
+
+/// Collects the non-null values of a column and fills in its definition levels.
+fn defined<T>(values: impl Iterator<Item = Option<T>>, defs: &mut Vec<i16>) -> Vec<T> {
+    let mut vals = vec![];
+    for value in values {
+        match value {
+            Some(value) => {
+                defs.push(1);
+                vals.push(value);
+            }
+            None => defs.push(0),
+        }
+    }
+    vals
+}
+
+/// Replaces characters that are not valid in Parquet schema field names.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,234 @@
+use super::kafka::{crc32c, Reader};
+use super::*;
+use crate::config::{KafkaConfig, OutputFormat};
+use crate::lcore::CoreId;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::json;
+
+///// Helpers /////
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("iris_output_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn records(n: u64) -> Vec<Value> {
+    (0..n)
+        .map(|i| json!({ "id": i, "sni": format!("{}.example.com", i), "tags": [i] }))
+        .collect()
+}
+
+///// Tests /////
+
+#[test]
+fn core_output_jsonl_rotate() {
+    let dir = test_dir("jsonl");
+    let path = dir.join("out").to_str().unwrap().to_string();
+
+    let mut files = vec![];
+    for core in 0..2 {
+        let mut sink = JsonlSink::new(&path, CoreId(core), Some(64)).unwrap();
+        sink.write_batch(&records(4)).unwrap();
+        sink.write_batch(&records(4)).unwrap();
+        files.extend(sink.finish().unwrap());
+    }
+    assert!(files.len() > 2);
+    assert_eq!(files[0], dir.join("out_0.jsonl"));
+    assert_eq!(files[1], dir.join("out_0.1.jsonl"));
+
+    file::merge(OutputFormat::Jsonl, &path, &files).unwrap();
+    assert!(files.iter().all(|file| !file.exists()));
+    let merged = fs::read_to_string(dir.join("out.jsonl")).unwrap();
+    let lines: Vec<Value> = merged
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 16);
+    assert_eq!(lines[5], records(4)[1]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn core_output_csv_merge() {
+    let dir = test_dir("csv");
+    let path = dir.join("out").to_str().unwrap().to_string();
+
+    let mut files = vec![];
+    for core in 0..2 {
+        let mut sink = CsvSink::new(&path, CoreId(core), Some(64)).unwrap();
+        sink.write_batch(&records(3)).unwrap();
+        sink.write_batch(&[json!({ "id": 3, "extra": true })])
+            .unwrap();
+        files.extend(sink.finish().unwrap());
+    }
+    assert!(files.len() > 2);
+
+    file::merge(OutputFormat::Csv, &path, &files).unwrap();
+    let merged = fs::read_to_string(dir.join("out.csv")).unwrap();
+    let rows: Vec<&str> = merged.lines().collect();
+    // One header, then four rows per core. Missing fields are empty, and extra fields dropped.
+    assert_eq!(rows.len(), 9);
+    assert_eq!(rows[0], "id,sni,tags");
+    assert_eq!(rows[1], "0,0.example.com,[0]");
+    assert_eq!(rows[4], "3,,");
+    assert_eq!(rows[5], "0,0.example.com,[0]");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn core_output_kafka_produce() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let broker = listener.local_addr().unwrap().to_string();
+
+    // Stub broker: acknowledges two `Produce` requests, and returns the records it received.
+    let stub = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut messages = vec![];
+        for _ in 0..2 {
+            let mut size = [0; 4];
+            stream.read_exact(&mut size).unwrap();
+            let mut request = vec![0; i32::from_be_bytes(size) as usize];
+            stream.read_exact(&mut request).unwrap();
+
+            let mut rdr = Reader(&request);
+            assert_eq!(rdr.i16().unwrap(), 0); // Produce
+            assert_eq!(rdr.i16().unwrap(), 3); // v3
+            let correlation_id = rdr.i32().unwrap();
+            assert_eq!(rdr.string().unwrap(), "iris-test");
+            assert_eq!(rdr.i16().unwrap(), -1); // null transactional ID
+            assert_eq!(rdr.i16().unwrap(), 1); // acks
+            let _timeout = rdr.i32().unwrap();
+            assert_eq!(rdr.i32().unwrap(), 1);
+            assert_eq!(rdr.string().unwrap(), "conns");
+            assert_eq!(rdr.i32().unwrap(), 1);
+            assert_eq!(rdr.i32().unwrap(), 3); // partition
+            let len = rdr.i32().unwrap() as usize;
+            let mut batch = Reader(rdr.bytes(len).unwrap());
+            let _base_offset = batch.i64().unwrap();
+            let len = batch.i32().unwrap() as usize;
+            let mut batch = Reader(batch.bytes(len).unwrap());
+            let _leader_epoch = batch.i32().unwrap();
+            assert_eq!(batch.i8().unwrap(), 2); // magic
+            let crc = batch.i32().unwrap() as u32;
+            assert_eq!(crc, crc32c(batch.0));
+            assert_eq!(batch.i16().unwrap(), 0); // attributes
+            let last_offset_delta = batch.i32().unwrap();
+            let _timestamps = batch.bytes(16).unwrap();
+            let _producer = batch.bytes(14).unwrap();
+            let count = batch.i32().unwrap();
+            assert_eq!(last_offset_delta, count - 1);
+            for offset_delta in 0..count as i64 {
+                let len = batch.varint().unwrap() as usize;
+                let mut record = Reader(batch.bytes(len).unwrap());
+                assert_eq!(record.i8().unwrap(), 0); // attributes
+                assert_eq!(record.varint().unwrap(), 0); // timestamp delta
+                assert_eq!(record.varint().unwrap(), offset_delta);
+                assert_eq!(record.varint().unwrap(), -1); // null key
+                let len = record.varint().unwrap() as usize;
+                let value: Value = serde_json::from_slice(record.bytes(len).unwrap()).unwrap();
+                messages.push(value);
+                assert_eq!(record.varint().unwrap(), 0); // headers
+                assert!(record.0.is_empty());
+            }
+            assert!(batch.0.is_empty());
+
+            let mut response = vec![];
+            response.extend_from_slice(&correlation_id.to_be_bytes());
+            response.extend_from_slice(&1i32.to_be_bytes());
+            response.extend_from_slice(&5i16.to_be_bytes());
+            response.extend_from_slice(b"conns");
+            response.extend_from_slice(&1i32.to_be_bytes());
+            response.extend_from_slice(&3i32.to_be_bytes());
+            response.extend_from_slice(&0i16.to_be_bytes());
+            response.extend_from_slice(&0i64.to_be_bytes());
+            response.extend_from_slice(&(-1i64).to_be_bytes()); // log append time
+            response.extend_from_slice(&0i32.to_be_bytes()); // throttle time
+            stream
+                .write_all(&(response.len() as i32).to_be_bytes())
+                .unwrap();
+            stream.write_all(&response).unwrap();
+        }
+        messages
+    });
+
+    let config = KafkaConfig {
+        broker,
+        topic: "conns".to_string(),
+        partition: 3,
+        client_id: "iris-test".to_string(),
+        timeout: 5000,
+    };
+    let mut sink = KafkaSink::new(&config).unwrap();
+    sink.write_batch(&records(3)).unwrap();
+    sink.write_batch(&records(2)).unwrap();
+    assert!(sink.finish().unwrap().is_empty());
+
+    let messages = stub.join().unwrap();
+    assert_eq!(messages.len(), 5);
+    assert_eq!(messages[2], records(3)[2]);
+    assert_eq!(messages[4], records(2)[1]);
+}
+
+#[test]
+fn core_output_emit() {
+    // Collects the records written by each core
+    struct TestSink(CoreId, Arc<Mutex<Vec<(CoreId, Value)>>>);
+    impl Sink for TestSink {
+        fn write_batch(&mut self, records: &[Value]) -> Result<()> {
+            let mut written = self.1.lock().unwrap();
+            written.extend(records.iter().map(|record| (self.0, record.clone())));
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<Vec<PathBuf>> {
+            Ok(vec![])
+        }
+    }
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let sinks = Arc::clone(&written);
+    register_sink(4, move |core_id| {
+        Ok(Box::new(TestSink(core_id, Arc::clone(&sinks))) as Box<dyn Sink>)
+    })
+    .unwrap();
+
+    let cores: Vec<_> = (0..4)
+        .map(|core| {
+            thread::spawn(move || {
+                for record in records(10) {
+                    emit(&CoreId(core), &record);
+                }
+            })
+        })
+        .collect();
+    for core in cores {
+        core.join().unwrap();
+    }
+    // Full batches are written as they fill up, and the rest on `finish`
+    assert_eq!(written.lock().unwrap().len(), 4 * 8);
+    finish().unwrap();
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), 4 * 10);
+    for core in 0..4 {
+        let records: Vec<_> = written
+            .iter()
+            .filter(|(core_id, _)| *core_id == CoreId(core))
+            .map(|(_, record)| record.clone())
+            .collect();
+        assert_eq!(records, self::records(10));
+    }
+}
+
+#[test]
+fn core_output_crc32c() {
+    assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+}
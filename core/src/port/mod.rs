@@ -99,7 +99,7 @@ pub(crate) struct Port {
     /// Port ID
     pub(crate) id: PortId,
 
-    /// Device PCI ID
+    /// Device PCI ID or interface name
     pub(crate) device: String,
 
     /// Mapping of receive queues to cores
@@ -110,8 +110,9 @@ pub(crate) struct Port {
 }
 
 impl Port {
-    pub(crate) fn new(port_map: &PortMap) -> Port {
-        let port_id = PortId::new_from_device(port_map.device.clone());
+    /// Creates a port for `port_map`, attached to DPDK as device `dev_name`.
+    pub(crate) fn new(port_map: &PortMap, dev_name: String) -> Port {
+        let port_id = PortId::new_from_device(dev_name);
 
         let mut queue_map: BTreeMap<RxQueue, CoreId> = BTreeMap::new();
         let mut rx_core_ids = port_map.cores.to_vec();
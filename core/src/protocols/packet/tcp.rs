@@ -19,9 +19,15 @@ pub const RST: u8 = 0b0000_0100;
 pub const SYN: u8 = 0b0000_0010;
 pub const FIN: u8 = 0b0000_0001;
 
+// TCP option kinds.
+pub const OPT_EOL: u8 = 0;
+pub const OPT_NOP: u8 = 1;
+pub const OPT_MSS: u8 = 2;
+pub const OPT_WSCALE: u8 = 3;
+
 /// A TCP packet.
 ///
-/// TCP options are not parsed by default.
+/// TCP options are not parsed by default, but can be retrieved with [`Tcp::options`].
 #[derive(Debug)]
 pub struct Tcp<'a> {
     /// Fixed header.
@@ -160,6 +166,60 @@ impl Tcp<'_> {
     pub fn synack(&self) -> u8 {
         ((self.flags() & (ACK | SYN)) != 0) as u8
     }
+
+    // ------------------------------------------------
+
+    /// Returns the raw TCP options, or an empty slice if the header is malformed.
+    pub fn options(&self) -> &[u8] {
+        let fixed_len = TcpHeader::size_of();
+        self.header_len()
+            .checked_sub(fixed_len)
+            .and_then(|len| self.mbuf.get_data_slice(self.offset + fixed_len, len).ok())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the JA4T fingerprint of the segment.
+    ///
+    /// ## Remarks
+    /// The JA4T fingerprint is defined as `window_options_mss_wscale`, where `options` lists the
+    /// TCP option kinds in the order they were sent, separated by `-`. Missing values are reported
+    /// as `0` (`00` for the option list). It is intended to be computed over the client SYN. See
+    /// [FoxIO-LLC/ja4](https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4T.md) for
+    /// more details.
+    pub fn ja4t(&self) -> String {
+        let options = self.options();
+        let mut kinds = vec![];
+        let (mut mss, mut wscale) = (0, 0);
+        let mut i = 0;
+        while i < options.len() {
+            let kind = options[i];
+            kinds.push(kind.to_string());
+            if kind == OPT_EOL {
+                break;
+            }
+            if kind == OPT_NOP {
+                i += 1;
+                continue;
+            }
+            let len = match options.get(i + 1) {
+                Some(len) if *len >= 2 => *len as usize,
+                _ => break,
+            };
+            match (kind, options.get(i + 2..i + len)) {
+                (OPT_MSS, Some(value)) if len == 4 => {
+                    mss = u16::from_be_bytes([value[0], value[1]])
+                }
+                (OPT_WSCALE, Some(value)) if len == 3 => wscale = value[0],
+                _ => (),
+            }
+            i += len;
+        }
+        let kinds = match kinds.is_empty() {
+            true => "00".to_string(),
+            false => kinds.join("-"),
+        };
+        format!("{}_{}_{}_{}", self.window(), kinds, mss, wscale)
+    }
 }
 
 impl<'a> Packet<'a> for Tcp<'a> {
@@ -223,3 +283,61 @@ impl PacketHeader for TcpHeader {
         ((self.data_offset_to_ns & 0xf0) >> 2).into()
     }
 }
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::packet::{ethernet::Ethernet, ipv4::Ipv4};
+
+    /// Returns an Ethernet frame carrying an IPv4 SYN with the given window and TCP options.
+    fn syn(window: u16, options: &[u8]) -> Mbuf {
+        let tcp_len = TcpHeader::size_of() + options.len();
+        let mut frame = vec![0; 34];
+        frame[12..14].copy_from_slice(&0x0800u16.to_be_bytes());
+        frame[14] = 0x45;
+        frame[16..18].copy_from_slice(&(20 + tcp_len as u16).to_be_bytes());
+        frame[22] = 64;
+        frame[23] = TCP_PROTOCOL as u8;
+        frame[26..30].copy_from_slice(&[10, 0, 0, 1]);
+        frame[30..34].copy_from_slice(&[10, 0, 0, 2]);
+        let mut tcp = vec![0; TcpHeader::size_of()];
+        tcp[0..2].copy_from_slice(&50000u16.to_be_bytes());
+        tcp[2..4].copy_from_slice(&443u16.to_be_bytes());
+        tcp[12] = ((tcp_len / 4) as u8) << 4;
+        tcp[13] = SYN;
+        tcp[14..16].copy_from_slice(&window.to_be_bytes());
+        frame.extend_from_slice(&tcp);
+        frame.extend_from_slice(options);
+        Mbuf::from_bytes(&frame).unwrap()
+    }
+
+    fn ja4t(mbuf: &Mbuf) -> String {
+        let eth = mbuf.parse_to::<Ethernet>().unwrap();
+        let ipv4 = eth.parse_to::<Ipv4>().unwrap();
+        ipv4.parse_to::<Tcp>().unwrap().ja4t()
+    }
+
+    #[test]
+    fn core_tcp_ja4t() {
+        // Windows 10: MSS, NOP, window scale, NOP, NOP, SACK permitted
+        let windows = syn(64240, b"\x02\x04\x05\xb4\x01\x03\x03\x08\x01\x01\x04\x02");
+        assert_eq!(ja4t(&windows), "64240_2-1-3-1-1-4_1460_8");
+
+        // Linux: MSS, SACK permitted, timestamps, NOP, window scale
+        let linux = syn(
+            64240,
+            b"\x02\x04\x05\xb4\x04\x02\x08\x0a\x9c\x3e\x51\x07\x00\x00\x00\x00\x01\x03\x03\x07",
+        );
+        assert_eq!(ja4t(&linux), "64240_2-4-8-1-3_1460_7");
+
+        // No options
+        assert_eq!(ja4t(&syn(1024, b"")), "1024_00_0_0");
+
+        // Options list ends at EOL, and a truncated option is not read
+        assert_eq!(
+            ja4t(&syn(8192, b"\x02\x04\x05\x64\x00\x00\x00\x00")),
+            "8192_2-0_1380_0"
+        );
+        assert_eq!(ja4t(&syn(8192, b"\x01\x01\x01\x02")), "8192_1-1-1-2_0_0");
+    }
+}
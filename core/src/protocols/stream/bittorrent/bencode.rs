@@ -0,0 +1,109 @@
+//! Bencoding.
+//!
+//! See [BEP 3](https://www.bittorrent.org/beps/bep_0003.html). Values borrow from the encoded
+//! message.
+
+use anyhow::{bail, Result};
+
+/// Maximum nesting depth of lists and dictionaries.
+const MAX_DEPTH: usize = 8;
+
+/// A bencoded value.
+#[derive(Debug)]
+pub(super) enum Bencode<'a> {
+    Int(i64),
+    Bytes(&'a [u8]),
+    List(Vec<Bencode<'a>>),
+    Dict(Vec<(&'a [u8], Bencode<'a>)>),
+}
+
+impl<'a> Bencode<'a> {
+    /// Parses a value from the start of `data`, returning it and its encoded length.
+    pub(super) fn parse_from(data: &'a [u8]) -> Result<(Self, usize)> {
+        Self::parse(data, 0)
+    }
+
+    fn parse(data: &'a [u8], depth: usize) -> Result<(Self, usize)> {
+        if depth > MAX_DEPTH {
+            bail!("maximum nesting depth exceeded");
+        }
+        match data.first() {
+            Some(b'i') => {
+                let end = match memchr::memchr(b'e', data) {
+                    Some(end) => end,
+                    None => bail!("unterminated integer"),
+                };
+                let value = std::str::from_utf8(&data[1..end])?.parse()?;
+                Ok((Bencode::Int(value), end + 1))
+            }
+            Some(b'l') => {
+                let mut items = vec![];
+                let mut len = 1;
+                while data.get(len) != Some(&b'e') {
+                    let (item, item_len) = Self::parse(&data[len..], depth + 1)?;
+                    items.push(item);
+                    len += item_len;
+                }
+                Ok((Bencode::List(items), len + 1))
+            }
+            Some(b'd') => {
+                let mut entries = vec![];
+                let mut len = 1;
+                while data.get(len) != Some(&b'e') {
+                    let (key, key_len) = match Self::parse(&data[len..], depth + 1)? {
+                        (Bencode::Bytes(key), key_len) => (key, key_len),
+                        _ => bail!("dictionary key is not a string"),
+                    };
+                    len += key_len;
+                    let (value, value_len) = Self::parse(&data[len..], depth + 1)?;
+                    entries.push((key, value));
+                    len += value_len;
+                }
+                Ok((Bencode::Dict(entries), len + 1))
+            }
+            Some(b'0'..=b'9') => {
+                let colon = match memchr::memchr(b':', data) {
+                    Some(colon) => colon,
+                    None => bail!("unterminated string length"),
+                };
+                let str_len: usize = std::str::from_utf8(&data[..colon])?.parse()?;
+                let start = colon + 1;
+                match data.get(start..start + str_len) {
+                    Some(value) => Ok((Bencode::Bytes(value), start + str_len)),
+                    None => bail!("truncated string"),
+                }
+            }
+            Some(b) => bail!("invalid value prefix {:#x}", b),
+            None => bail!("truncated value"),
+        }
+    }
+
+    /// Returns the value of `key` in a dictionary.
+    pub(super) fn get(&self, key: &[u8]) -> Option<&Bencode<'a>> {
+        match self {
+            Bencode::Dict(entries) => entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Bencode::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_int(&self) -> Option<i64> {
+        match self {
+            Bencode::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_list(&self) -> Option<&[Bencode<'a>]> {
+        match self {
+            Bencode::List(items) => Some(items),
+            _ => None,
+        }
+    }
+}
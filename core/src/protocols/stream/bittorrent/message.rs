@@ -0,0 +1,306 @@
+//! BitTorrent message components.
+//!
+//! See [BEP 3](https://www.bittorrent.org/beps/bep_0003.html) for the peer wire handshake,
+//! [BEP 29](https://www.bittorrent.org/beps/bep_0029.html) for uTP framing, and
+//! [BEP 5](https://www.bittorrent.org/beps/bep_0005.html) for Mainline DHT KRPC messages. Peer
+//! IDs are interpreted following [BEP 20](https://www.bittorrent.org/beps/bep_0020.html).
+
+use super::bencode::Bencode;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// Protocol string that starts a peer wire handshake, prefixed with its length.
+pub(super) const PROTOCOL_PREFIX: &[u8] = b"\x13BitTorrent protocol";
+/// Length of a peer wire handshake.
+pub(super) const HANDSHAKE_LEN: usize = 68;
+/// Length of the fixed uTP header.
+const UTP_HDR_LEN: usize = 20;
+/// Length of a compact node info entry (node ID, IPv4 address, and port).
+const COMPACT_NODE_LEN: usize = 26;
+
+pub const ST_DATA: u8 = 0;
+pub const ST_FIN: u8 = 1;
+pub const ST_STATE: u8 = 2;
+pub const ST_RESET: u8 = 3;
+pub const ST_SYN: u8 = 4;
+
+/// Returns the name of the client identified by a BEP 20 client code (e.g., `"qBittorrent"`).
+pub fn client_name(code: &str) -> Option<&'static str> {
+    let name = match code {
+        "AZ" => "Vuze",
+        "BI" => "BiglyBT",
+        "BT" => "BitTorrent",
+        "DE" => "Deluge",
+        "FD" => "Free Download Manager",
+        "KT" => "KTorrent",
+        "LT" => "libtorrent",
+        "lt" => "rTorrent",
+        "M" => "Mainline",
+        "qB" => "qBittorrent",
+        "TL" => "Tribler",
+        "TR" => "Transmission",
+        "UM" => "uTorrent Mac",
+        "UT" => "uTorrent",
+        "UW" => "uTorrent Web",
+        "WD" => "WebTorrent Desktop",
+        "WW" => "WebTorrent",
+        "XL" => "Xunlei",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// A peer wire handshake.
+#[derive(Clone, Debug, Serialize)]
+pub struct BtHandshake {
+    /// Reserved bytes, indicating supported extensions.
+    pub reserved: [u8; 8],
+    /// Hex-encoded SHA-1 info hash of the torrent.
+    pub info_hash: String,
+    /// Peer ID, lossily decoded as UTF-8.
+    pub peer_id: String,
+    /// BEP 20 client code from the peer ID (e.g., `"qB"`), if the peer ID follows a known
+    /// convention.
+    pub client: Option<String>,
+    /// Client version from the peer ID (e.g., `"4.2.5.0"`), if the peer ID follows a known
+    /// convention.
+    pub client_version: Option<String>,
+}
+
+impl BtHandshake {
+    /// Parses a peer wire handshake from the start of `data`.
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(PROTOCOL_PREFIX) {
+            bail!("invalid protocol string");
+        }
+        if data.len() < HANDSHAKE_LEN {
+            bail!("truncated handshake");
+        }
+        let mut reserved = [0; 8];
+        reserved.copy_from_slice(&data[20..28]);
+        let peer_id = &data[48..68];
+        let (client, client_version) = match parse_peer_id(peer_id) {
+            Some((client, version)) => (Some(client), Some(version)),
+            None => (None, None),
+        };
+        Ok(BtHandshake {
+            reserved,
+            info_hash: hex::encode(&data[28..48]),
+            peer_id: String::from_utf8_lossy(peer_id).into_owned(),
+            client,
+            client_version,
+        })
+    }
+
+    /// Returns `true` if the peer supports the extension protocol (BEP 10).
+    pub fn extension_protocol(&self) -> bool {
+        self.reserved[5] & 0x10 != 0
+    }
+
+    /// Returns `true` if the peer supports the DHT (BEP 5).
+    pub fn dht(&self) -> bool {
+        self.reserved[7] & 0x01 != 0
+    }
+
+    /// Returns `true` if the peer supports the fast extension (BEP 6).
+    pub fn fast(&self) -> bool {
+        self.reserved[7] & 0x04 != 0
+    }
+}
+
+/// Parses the client code and version from a peer ID, in either the Azureus style
+/// (`-qB4250-...`) or the Mainline style (`M7-2-2--...`).
+fn parse_peer_id(peer_id: &[u8]) -> Option<(String, String)> {
+    if peer_id.first() == Some(&b'-') && peer_id.get(7) == Some(&b'-') {
+        let code = &peer_id[1..3];
+        let version = &peer_id[3..7];
+        if code.iter().all(u8::is_ascii_alphabetic) && version.iter().all(u8::is_ascii_alphanumeric)
+        {
+            let version: Vec<String> = version.iter().map(|b| (*b as char).to_string()).collect();
+            return Some((
+                String::from_utf8_lossy(code).into_owned(),
+                version.join("."),
+            ));
+        }
+    }
+    if peer_id.first() == Some(&b'M') {
+        // Mainline style: M<major>-<minor>-<patch>--
+        let version = peer_id[1..]
+            .split(|b| *b == b'-')
+            .take_while(|part| !part.is_empty() && part.iter().all(u8::is_ascii_digit))
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .collect::<Vec<_>>();
+        if version.len() == 3 {
+            return Some(("M".to_owned(), version.join(".")));
+        }
+    }
+    None
+}
+
+/// A uTP packet header.
+#[derive(Debug)]
+pub(super) struct UtpHeader {
+    pub(super) packet_type: u8,
+    /// Length of the header, including extensions.
+    pub(super) length: usize,
+}
+
+impl UtpHeader {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.len() < UTP_HDR_LEN {
+            bail!("truncated header");
+        }
+        if data[0] & 0x0f != 1 {
+            bail!("invalid version {}", data[0] & 0x0f);
+        }
+        let packet_type = data[0] >> 4;
+        if packet_type > ST_SYN {
+            bail!("invalid packet type {}", packet_type);
+        }
+        // Extension chain: each extension gives the type of the next
+        let mut extension = data[1];
+        let mut length = UTP_HDR_LEN;
+        while extension != 0 {
+            if extension > 4 {
+                bail!("invalid extension {}", extension);
+            }
+            match data.get(length..length + 2) {
+                Some(ext) => {
+                    extension = ext[0];
+                    length += 2 + ext[1] as usize;
+                }
+                None => bail!("truncated extension"),
+            }
+        }
+        if length > data.len() {
+            bail!("truncated extension");
+        }
+        Ok(UtpHeader {
+            packet_type,
+            length,
+        })
+    }
+}
+
+/// A Mainline DHT KRPC message.
+#[derive(Clone, Debug, Serialize)]
+pub struct KrpcMessage {
+    /// Hex-encoded transaction ID.
+    pub transaction_id: String,
+    /// Message type: `"q"` for queries, `"r"` for responses, and `"e"` for errors.
+    pub msg_type: String,
+    /// Query method (e.g., `"get_peers"`), for queries.
+    pub method: Option<String>,
+    /// Hex-encoded node ID of the sender, if present.
+    pub node_id: Option<String>,
+    /// Hex-encoded info hash of a `get_peers` or `announce_peer` query.
+    pub info_hash: Option<String>,
+    /// Hex-encoded target of a `find_node` or `sample_infohashes` query.
+    pub target: Option<String>,
+    /// Port announced by an `announce_peer` query.
+    pub port: Option<u16>,
+    /// Client version (`v`) of the sender: a BEP 20 client code and two version bytes.
+    pub version: Option<Vec<u8>>,
+    /// `true` if the sender is a read-only node (BEP 43).
+    pub read_only: bool,
+    /// Number of compact IPv4 nodes in a response.
+    pub num_nodes: usize,
+    /// Number of peers in a `get_peers` response.
+    pub num_values: usize,
+    /// Error code and message of an error.
+    pub error: Option<(i64, String)>,
+}
+
+impl KrpcMessage {
+    /// Parses a KRPC message from a UDP payload.
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.first() != Some(&b'd') {
+            bail!("not a dictionary");
+        }
+        let (msg, len) = Bencode::parse_from(data)?;
+        if len != data.len() {
+            bail!("trailing data");
+        }
+        let transaction_id = match bytes(msg.get(b"t")) {
+            Some(t) => hex::encode(t),
+            None => bail!("missing transaction ID"),
+        };
+        let msg_type = match bytes(msg.get(b"y")) {
+            Some(y @ (b"q" | b"r" | b"e")) => String::from_utf8_lossy(y).into_owned(),
+            _ => bail!("invalid message type"),
+        };
+        let mut krpc = KrpcMessage {
+            transaction_id,
+            msg_type,
+            method: None,
+            node_id: None,
+            info_hash: None,
+            target: None,
+            port: None,
+            version: bytes(msg.get(b"v")).map(|v| v.to_vec()),
+            read_only: msg.get(b"ro").and_then(|ro| ro.as_int()) == Some(1),
+            num_nodes: 0,
+            num_values: 0,
+            error: None,
+        };
+        match krpc.msg_type.as_str() {
+            "q" => {
+                krpc.method = match bytes(msg.get(b"q")) {
+                    Some(q) => Some(String::from_utf8_lossy(q).into_owned()),
+                    None => bail!("missing query method"),
+                };
+                if let Some(args) = msg.get(b"a") {
+                    krpc.node_id = bytes(args.get(b"id")).map(hex::encode);
+                    krpc.info_hash = bytes(args.get(b"info_hash")).map(hex::encode);
+                    krpc.target = bytes(args.get(b"target")).map(hex::encode);
+                    krpc.port = args
+                        .get(b"port")
+                        .and_then(|port| port.as_int())
+                        .and_then(|port| u16::try_from(port).ok());
+                }
+            }
+            "r" => {
+                if let Some(r) = msg.get(b"r") {
+                    krpc.node_id = bytes(r.get(b"id")).map(hex::encode);
+                    krpc.num_nodes =
+                        bytes(r.get(b"nodes")).map_or(0, |n| n.len() / COMPACT_NODE_LEN);
+                    krpc.num_values = r
+                        .get(b"values")
+                        .and_then(|values| values.as_list())
+                        .map_or(0, |values| values.len());
+                }
+            }
+            _ => {
+                if let Some([code, message, ..]) = msg.get(b"e").and_then(|e| e.as_list()) {
+                    krpc.error = Some((
+                        code.as_int().unwrap_or(0),
+                        String::from_utf8_lossy(message.as_bytes().unwrap_or_default())
+                            .into_owned(),
+                    ));
+                }
+            }
+        }
+        Ok(krpc)
+    }
+
+    /// Returns `true` if the message is a query.
+    pub fn is_query(&self) -> bool {
+        self.msg_type == "q"
+    }
+
+    /// Returns the BEP 20 client code of the sender's version (e.g., `"UT"`), if present.
+    pub fn client(&self) -> Option<String> {
+        match &self.version {
+            Some(v) if v.len() >= 2 && v[..2].iter().all(u8::is_ascii_alphabetic) => {
+                Some(String::from_utf8_lossy(&v[..2]).into_owned())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the contents of an optional string value.
+fn bytes<'a>(value: Option<&Bencode<'a>>) -> Option<&'a [u8]> {
+    value.and_then(|v| v.as_bytes())
+}
@@ -0,0 +1,155 @@
+//! BitTorrent protocol identification.
+//!
+//! ## Remarks
+//! Three components of BitTorrent are identified: the peer wire handshake over TCP, the same
+//! handshake carried over uTP (a reliable transport over UDP), and Mainline DHT messages over UDP.
+//! A peer wire connection is recorded as a single session with the handshakes sent by both peers.
+//! DHT queries are paired with their responses by transaction ID, and each query is recorded as a
+//! separate session.
+//!
+//! The info hash, peer ID, and extension bits are recorded from each handshake, and the client is
+//! identified from the peer ID prefix or from the DHT version field. Connections that use message
+//! stream encryption (MSE) have no plaintext handshake and are not identified. uTP connections are
+//! only identified from their first (SYN) packet, and the handshake is only parsed if its packets
+//! arrive in order.
+
+mod bencode;
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// BitTorrent component carried by a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum BtTransport {
+    /// Peer wire protocol over TCP.
+    Tcp,
+    /// Peer wire protocol over uTP.
+    Utp,
+    /// Mainline DHT.
+    Dht,
+}
+
+/// Parsed BitTorrent peer wire handshake or DHT transaction.
+#[derive(Debug, Serialize)]
+pub struct Bittorrent {
+    pub transport: BtTransport,
+    /// Handshake sent by the originator of a peer wire connection, if observed.
+    pub handshake: Option<BtHandshake>,
+    /// Handshake sent by the responder of a peer wire connection, if observed.
+    pub peer_handshake: Option<BtHandshake>,
+    /// DHT query, if observed.
+    pub query: Option<KrpcMessage>,
+    /// DHT response or error, if observed.
+    pub response: Option<KrpcMessage>,
+}
+
+impl Bittorrent {
+    /// Returns the BitTorrent component (`"tcp"`, `"utp"`, or `"dht"`).
+    pub fn transport(&self) -> &str {
+        match self.transport {
+            BtTransport::Tcp => "tcp",
+            BtTransport::Utp => "utp",
+            BtTransport::Dht => "dht",
+        }
+    }
+
+    /// Returns the hex-encoded info hash of the torrent, from the handshakes or from a DHT
+    /// `get_peers` or `announce_peer` query. Returns `""` if there is none.
+    pub fn info_hash(&self) -> &str {
+        self.handshakes()
+            .map(|h| h.info_hash.as_str())
+            .chain(self.query.as_ref().and_then(|q| q.info_hash.as_deref()))
+            .next()
+            .unwrap_or("")
+    }
+
+    /// Returns the client of the originator (e.g., `"qBittorrent"`), identified from its peer ID
+    /// or DHT version field. Returns the BEP 20 client code if the client is not known, or `""`
+    /// if it cannot be identified.
+    pub fn client(&self) -> String {
+        self.client_code(true)
+            .map(|code| client_name(&code).map_or(code, |name| name.to_owned()))
+            .unwrap_or_default()
+    }
+
+    /// Returns the client of the responder, as in [`client`](Self::client).
+    pub fn peer_client(&self) -> String {
+        self.client_code(false)
+            .map(|code| client_name(&code).map_or(code, |name| name.to_owned()))
+            .unwrap_or_default()
+    }
+
+    /// Returns the client version of the originator from its peer ID (e.g., `"4.2.5.0"`), or
+    /// `""` if it cannot be identified.
+    pub fn client_version(&self) -> &str {
+        self.handshake
+            .as_ref()
+            .and_then(|h| h.client_version.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the peer ID of the originator, or `""` if no handshake was observed.
+    pub fn peer_id(&self) -> &str {
+        self.handshake.as_ref().map_or("", |h| &h.peer_id)
+    }
+
+    /// Returns `true` if both peers sent a handshake for the same torrent.
+    pub fn handshake_done(&self) -> bool {
+        match (&self.handshake, &self.peer_handshake) {
+            (Some(handshake), Some(peer)) => handshake.info_hash == peer.info_hash,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the originator supports the extension protocol (BEP 10).
+    pub fn extension_protocol(&self) -> bool {
+        self.handshake
+            .as_ref()
+            .is_some_and(|h| h.extension_protocol())
+    }
+
+    /// Returns the DHT query method (e.g., `"get_peers"`), or `""` if there is none.
+    pub fn dht_method(&self) -> &str {
+        self.query
+            .as_ref()
+            .and_then(|q| q.method.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the hex-encoded DHT node ID of the querying node, or `""` if there is none.
+    pub fn node_id(&self) -> &str {
+        self.query
+            .iter()
+            .chain(self.response.iter())
+            .find_map(|m| m.node_id.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the DHT error code, or 0 if the query did not fail.
+    pub fn dht_error(&self) -> i64 {
+        self.response
+            .as_ref()
+            .and_then(|r| r.error.as_ref())
+            .map_or(0, |(code, _)| *code)
+    }
+
+    /// Returns the observed handshakes, originator first.
+    fn handshakes(&self) -> impl Iterator<Item = &BtHandshake> {
+        self.handshake.iter().chain(self.peer_handshake.iter())
+    }
+
+    /// Returns the BEP 20 client code of the originator if `orig`, otherwise of the responder.
+    fn client_code(&self, orig: bool) -> Option<String> {
+        let (handshake, krpc) = match orig {
+            true => (&self.handshake, &self.query),
+            false => (&self.peer_handshake, &self.response),
+        };
+        match handshake {
+            Some(handshake) => handshake.client.clone(),
+            None => krpc.as_ref().and_then(|m| m.client()),
+        }
+    }
+}
@@ -0,0 +1,533 @@
+//! BitTorrent parser.
+//!
+//! Parses peer wire handshakes over TCP and uTP, and pairs DHT queries with their responses.
+
+use super::message::*;
+use super::{Bittorrent, BtTransport};
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::packet::tcp::TCP_PROTOCOL;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+/// Maximum number of completed DHT transactions recorded before parsing stops.
+const MAX_TRANSACTIONS: usize = 64;
+/// Maximum number of uTP packets inspected for the handshakes.
+const MAX_UTP_PACKETS: usize = 16;
+
+#[derive(Default, Debug)]
+pub struct BittorrentParser {
+    /// Maps session ID to BitTorrent session
+    sessions: HashMap<usize, Bittorrent>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Component carried by the connection, once identified
+    transport: Option<BtTransport>,
+    /// Maps transaction ID to the session ID of a DHT query awaiting a response
+    pending: HashMap<String, usize>,
+    /// Number of completed DHT transactions
+    completed: usize,
+    /// Number of uTP packets inspected
+    utp_packets: usize,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// Set when the originator's handshake was parsed or cannot be parsed
+    client_done: bool,
+    /// Set when the responder's handshake was parsed or cannot be parsed
+    server_done: bool,
+}
+
+impl ConnParsable for BittorrentParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            let transport = match self.transport {
+                Some(transport) => transport,
+                None => {
+                    let transport = if pdu.ctxt.proto == TCP_PROTOCOL {
+                        BtTransport::Tcp
+                    } else if data[0] == b'd' {
+                        BtTransport::Dht
+                    } else {
+                        BtTransport::Utp
+                    };
+                    if transport != BtTransport::Dht {
+                        self.insert(Bittorrent::new(transport));
+                    }
+                    *self.transport.insert(transport)
+                }
+            };
+            match transport {
+                BtTransport::Tcp => self.process_handshake(data, pdu.dir),
+                BtTransport::Utp => self.process_utp(data, pdu.dir),
+                BtTransport::Dht => self.process_dht(data),
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+        if !pdu.dir {
+            // The originator speaks first
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            if pdu.ctxt.proto == TCP_PROTOCOL {
+                if data.starts_with(PROTOCOL_PREFIX) {
+                    ProbeResult::Certain
+                } else if PROTOCOL_PREFIX.starts_with(data) {
+                    ProbeResult::Unsure
+                } else {
+                    ProbeResult::NotForUs
+                }
+            } else if KrpcMessage::parse_from(data).is_ok() {
+                ProbeResult::Certain
+            } else {
+                // uTP connections start with a SYN packet without payload
+                match UtpHeader::parse_from(data) {
+                    Ok(hdr) if hdr.packet_type == ST_SYN && hdr.length == data.len() => {
+                        ProbeResult::Certain
+                    }
+                    _ => ProbeResult::NotForUs,
+                }
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|bittorrent| Session {
+            data: SessionData::Bittorrent(Box::new(bittorrent)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, bittorrent)| Session {
+                data: SessionData::Bittorrent(Box::new(bittorrent)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        match self.transport {
+            Some(BtTransport::Dht) => ParsingState::Parsing,
+            _ => ParsingState::Stop,
+        }
+    }
+
+    /// We consider BitTorrent to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl Bittorrent {
+    fn new(transport: BtTransport) -> Self {
+        Bittorrent {
+            transport,
+            handshake: None,
+            peer_handshake: None,
+            query: None,
+            response: None,
+        }
+    }
+}
+
+impl BittorrentParser {
+    /// Process peer wire data in direction `dir`, until both handshakes are parsed.
+    fn process_handshake(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let (buffer, done) = match dir {
+            true => (&mut self.client_buffer, &mut self.client_done),
+            false => (&mut self.server_buffer, &mut self.server_done),
+        };
+        if !*done {
+            let needed = HANDSHAKE_LEN - buffer.len();
+            buffer.extend_from_slice(&data[..needed.min(data.len())]);
+            let prefix_len = buffer.len().min(PROTOCOL_PREFIX.len());
+            if buffer.len() == HANDSHAKE_LEN
+                || buffer[..prefix_len] != PROTOCOL_PREFIX[..prefix_len]
+            {
+                *done = true;
+                let handshake = match BtHandshake::parse_from(buffer) {
+                    Ok(handshake) => Some(handshake),
+                    Err(e) => {
+                        log::debug!("parse error: {:?}", e);
+                        None
+                    }
+                };
+                buffer.clear();
+                if let Some(bittorrent) = self.sessions.get_mut(&0) {
+                    match dir {
+                        true => bittorrent.handshake = handshake,
+                        false => bittorrent.peer_handshake = handshake,
+                    }
+                }
+            }
+        }
+        if self.client_done && self.server_done {
+            return ParseResult::Done(0);
+        }
+        ParseResult::Continue(0)
+    }
+
+    /// Process a uTP packet in direction `dir`.
+    fn process_utp(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let hdr = match UtpHeader::parse_from(data) {
+            Ok(hdr) => hdr,
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                return ParseResult::Skipped;
+            }
+        };
+        self.utp_packets += 1;
+        if self.utp_packets >= MAX_UTP_PACKETS {
+            return ParseResult::Done(0);
+        }
+        match hdr.packet_type {
+            ST_DATA if hdr.length < data.len() => self.process_handshake(&data[hdr.length..], dir),
+            ST_FIN | ST_RESET => ParseResult::Done(0),
+            _ => ParseResult::Continue(0),
+        }
+    }
+
+    /// Process a DHT message.
+    fn process_dht(&mut self, data: &[u8]) -> ParseResult {
+        let msg = match KrpcMessage::parse_from(data) {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                return ParseResult::Skipped;
+            }
+        };
+        log::debug!("DHT {} {:?}", msg.msg_type, msg.method);
+
+        if msg.is_query() {
+            let transaction_id = msg.transaction_id.clone();
+            let mut bittorrent = Bittorrent::new(BtTransport::Dht);
+            bittorrent.query = Some(msg);
+            let session_id = self.insert(bittorrent);
+            self.pending.insert(transaction_id, session_id);
+            return ParseResult::Continue(session_id);
+        }
+
+        let session_id = match self.pending.remove(&msg.transaction_id) {
+            Some(session_id) => {
+                if let Some(bittorrent) = self.sessions.get_mut(&session_id) {
+                    bittorrent.response = Some(msg);
+                }
+                session_id
+            }
+            None => {
+                let mut bittorrent = Bittorrent::new(BtTransport::Dht);
+                bittorrent.response = Some(msg);
+                self.insert(bittorrent)
+            }
+        };
+        self.completed += 1;
+        if self.completed >= MAX_TRANSACTIONS {
+            return ParseResult::HeadersDone(session_id);
+        }
+        ParseResult::Continue(session_id)
+    }
+
+    fn insert(&mut self, bittorrent: Bittorrent) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, bittorrent);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{tcp, udp};
+
+    /// Handshake sent by qBittorrent 4.2.5.0, with the extension protocol and DHT bits set.
+    const HANDSHAKE: &[u8] = b"\
+        \x13\x42\x69\x74\x54\x6f\x72\x72\x65\x6e\x74\x20\x70\x72\x6f\x74\
+        \x6f\x63\x6f\x6c\x00\x00\x00\x00\x00\x10\x00\x05\x3e\x9a\xfe\x23\
+        \x5a\x0e\xbe\xe7\x2d\x19\x69\xe8\xb4\xa8\x86\xd5\xbd\x30\xc5\x15\
+        \x2d\x71\x42\x34\x32\x35\x30\x2d\x61\x31\x62\x32\x63\x33\x64\x34\
+        \x65\x35\x66\x36";
+
+    /// Handshake sent by Mainline 7.2.2 for the same torrent.
+    const PEER_HANDSHAKE: &[u8] = b"\
+        \x13\x42\x69\x74\x54\x6f\x72\x72\x65\x6e\x74\x20\x70\x72\x6f\x74\
+        \x6f\x63\x6f\x6c\x00\x00\x00\x00\x00\x00\x00\x01\x3e\x9a\xfe\x23\
+        \x5a\x0e\xbe\xe7\x2d\x19\x69\xe8\xb4\xa8\x86\xd5\xbd\x30\xc5\x15\
+        \x4d\x37\x2d\x32\x2d\x32\x2d\x2d\x39\x66\x38\x65\x37\x64\x36\x63\
+        \x35\x62\x34\x61";
+
+    /// uTP SYN packet.
+    const UTP_SYN: &[u8] = b"\
+        \x41\x00\x30\x39\x00\x0f\x42\x40\x00\x00\x00\x00\x00\x10\x00\x00\
+        \x00\x01\x00\x00";
+
+    /// uTP DATA packet carrying the qBittorrent handshake.
+    const UTP_DATA: &[u8] = b"\
+        \x01\x00\x30\x3a\x00\x0f\x44\x34\x00\x00\x00\x00\x00\x10\x00\x00\
+        \x00\x02\x00\x00\x13\x42\x69\x74\x54\x6f\x72\x72\x65\x6e\x74\x20\
+        \x70\x72\x6f\x74\x6f\x63\x6f\x6c\x00\x00\x00\x00\x00\x10\x00\x05\
+        \x3e\x9a\xfe\x23\x5a\x0e\xbe\xe7\x2d\x19\x69\xe8\xb4\xa8\x86\xd5\
+        \xbd\x30\xc5\x15\x2d\x71\x42\x34\x32\x35\x30\x2d\x61\x31\x62\x32\
+        \x63\x33\x64\x34\x65\x35\x66\x36";
+
+    /// DHT `get_peers` query sent by uTorrent.
+    const GET_PEERS: &[u8] = b"\
+        \x64\x31\x3a\x61\x64\x32\x3a\x69\x64\x32\x30\x3a\x01\x02\x03\x04\
+        \x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10\x11\x12\x13\x14\
+        \x39\x3a\x69\x6e\x66\x6f\x5f\x68\x61\x73\x68\x32\x30\x3a\x3e\x9a\
+        \xfe\x23\x5a\x0e\xbe\xe7\x2d\x19\x69\xe8\xb4\xa8\x86\xd5\xbd\x30\
+        \xc5\x15\x65\x31\x3a\x71\x39\x3a\x67\x65\x74\x5f\x70\x65\x65\x72\
+        \x73\x31\x3a\x74\x32\x3a\x61\x61\x31\x3a\x76\x34\x3a\x55\x54\x01\
+        \x02\x31\x3a\x79\x31\x3a\x71\x65";
+
+    /// DHT `get_peers` response with two peers, sent by libtorrent.
+    const PEERS: &[u8] = b"\
+        \x64\x31\x3a\x72\x64\x32\x3a\x69\x64\x32\x30\x3a\x21\x22\x23\x24\
+        \x25\x26\x27\x28\x29\x2a\x2b\x2c\x2d\x2e\x2f\x30\x31\x32\x33\x34\
+        \x35\x3a\x74\x6f\x6b\x65\x6e\x34\x3a\x74\x6f\x6b\x6e\x36\x3a\x76\
+        \x61\x6c\x75\x65\x73\x6c\x36\x3a\x0a\x00\x00\x07\x1a\xe1\x36\x3a\
+        \x0a\x00\x00\x08\x1a\xe2\x65\x65\x31\x3a\x74\x32\x3a\x61\x61\x31\
+        \x3a\x76\x34\x3a\x4c\x54\x01\x00\x31\x3a\x79\x31\x3a\x72\x65";
+
+    /// DHT `find_node` query.
+    const FIND_NODE: &[u8] = b"\
+        \x64\x31\x3a\x61\x64\x32\x3a\x69\x64\x32\x30\x3a\x01\x02\x03\x04\
+        \x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10\x11\x12\x13\x14\
+        \x36\x3a\x74\x61\x72\x67\x65\x74\x32\x30\x3a\x21\x22\x23\x24\x25\
+        \x26\x27\x28\x29\x2a\x2b\x2c\x2d\x2e\x2f\x30\x31\x32\x33\x34\x65\
+        \x31\x3a\x71\x39\x3a\x66\x69\x6e\x64\x5f\x6e\x6f\x64\x65\x31\x3a\
+        \x74\x32\x3a\x61\x62\x31\x3a\x79\x31\x3a\x71\x65";
+
+    /// DHT protocol error response to the `find_node` query.
+    const PROTOCOL_ERROR: &[u8] = b"\
+        \x64\x31\x3a\x65\x6c\x69\x32\x30\x33\x65\x31\x34\x3a\x50\x72\x6f\
+        \x74\x6f\x63\x6f\x6c\x20\x45\x72\x72\x6f\x72\x65\x31\x3a\x74\x32\
+        \x3a\x61\x62\x31\x3a\x79\x31\x3a\x65\x65";
+
+    const INFO_HASH: &str = "3e9afe235a0ebee72d1969e8b4a886d5bd30c515";
+
+    #[test]
+    fn core_bittorrent_probe() {
+        let parser = BittorrentParser::default();
+        assert_eq!(
+            parser.probe(&tcp(HANDSHAKE, 6881, true)),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            parser.probe(&tcp(&HANDSHAKE[..5], 6881, true)),
+            ProbeResult::Unsure
+        );
+        assert_eq!(
+            parser.probe(&tcp(PEER_HANDSHAKE, 6881, false)),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            parser.probe(&tcp(b"GET /announce HTTP/1.1\r\n", 6881, true)),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            parser.probe(&udp(GET_PEERS, 6881, true)),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            parser.probe(&udp(UTP_SYN, 6881, true)),
+            ProbeResult::Certain
+        );
+        // uTP packets other than a SYN without payload
+        assert_eq!(
+            parser.probe(&udp(UTP_DATA, 6881, true)),
+            ProbeResult::NotForUs
+        );
+        let syn = [UTP_SYN, b"\x00"].concat();
+        assert_eq!(parser.probe(&udp(&syn, 6881, true)), ProbeResult::NotForUs);
+        assert_eq!(
+            parser.probe(&udp(&GET_PEERS[..50], 6881, true)),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_bittorrent_tcp_split() {
+        let mut parser = BittorrentParser::default();
+        // Split within the protocol string, then within the info hash
+        assert_eq!(
+            parser.parse(&tcp(&HANDSHAKE[..10], 6881, true)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&tcp(&HANDSHAKE[10..40], 6881, true)),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.sessions[&0].handshake.is_none());
+        // Handshake followed by an extended message
+        let rest = [
+            &HANDSHAKE[40..],
+            b"\x00\x00\x00\x1a\x14\x00d1:md11:ut_metadatai1eee",
+        ]
+        .concat();
+        assert_eq!(
+            parser.parse(&tcp(&rest, 6881, true)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&tcp(PEER_HANDSHAKE, 6881, false)),
+            ParseResult::Done(0)
+        );
+        assert!(matches!(parser.session_parsed_state(), ParsingState::Stop));
+
+        let bittorrent = &parser.sessions[&0];
+        assert_eq!(bittorrent.transport(), "tcp");
+        assert_eq!(bittorrent.info_hash(), INFO_HASH);
+        assert_eq!(bittorrent.peer_id(), "-qB4250-a1b2c3d4e5f6");
+        assert_eq!(bittorrent.client(), "qBittorrent");
+        assert_eq!(bittorrent.client_version(), "4.2.5.0");
+        assert_eq!(bittorrent.peer_client(), "Mainline");
+        assert!(bittorrent.handshake_done());
+        assert!(bittorrent.extension_protocol());
+        let handshake = bittorrent.handshake.as_ref().unwrap();
+        assert!(handshake.dht());
+        assert!(handshake.fast());
+        let peer_handshake = bittorrent.peer_handshake.as_ref().unwrap();
+        assert_eq!(peer_handshake.client_version.as_deref(), Some("7.2.2"));
+        assert!(!peer_handshake.extension_protocol());
+    }
+
+    #[test]
+    fn core_bittorrent_utp() {
+        let mut parser = BittorrentParser::default();
+        assert_eq!(
+            parser.parse(&udp(UTP_SYN, 6881, true)),
+            ParseResult::Continue(0)
+        );
+        // ST_STATE acknowledging the SYN
+        let mut state = UTP_SYN.to_vec();
+        state[0] = 0x21;
+        assert_eq!(
+            parser.parse(&udp(&state, 6881, false)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(UTP_DATA, 6881, true)),
+            ParseResult::Continue(0)
+        );
+        let bittorrent = &parser.sessions[&0];
+        assert_eq!(bittorrent.transport(), "utp");
+        assert_eq!(bittorrent.info_hash(), INFO_HASH);
+        assert_eq!(bittorrent.client(), "qBittorrent");
+        assert!(bittorrent.peer_handshake.is_none());
+        assert!(!bittorrent.handshake_done());
+
+        let mut fin = UTP_SYN.to_vec();
+        fin[0] = 0x11;
+        assert_eq!(parser.parse(&udp(&fin, 6881, false)), ParseResult::Done(0));
+    }
+
+    #[test]
+    fn core_bittorrent_dht() {
+        let mut parser = BittorrentParser::default();
+        assert_eq!(
+            parser.parse(&udp(GET_PEERS, 6881, true)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(FIND_NODE, 6881, true)),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(
+            parser.parse(&udp(PEERS, 6881, false)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(PROTOCOL_ERROR, 6881, false)),
+            ParseResult::Continue(1)
+        );
+        assert!(matches!(
+            parser.session_parsed_state(),
+            ParsingState::Parsing
+        ));
+
+        let get_peers = &parser.sessions[&0];
+        assert_eq!(get_peers.transport(), "dht");
+        assert_eq!(get_peers.dht_method(), "get_peers");
+        assert_eq!(get_peers.info_hash(), INFO_HASH);
+        assert_eq!(
+            get_peers.node_id(),
+            "0102030405060708090a0b0c0d0e0f1011121314"
+        );
+        assert_eq!(get_peers.client(), "uTorrent");
+        assert_eq!(get_peers.peer_client(), "libtorrent");
+        assert_eq!(get_peers.response.as_ref().unwrap().num_values, 2);
+        assert_eq!(get_peers.dht_error(), 0);
+
+        let find_node = &parser.sessions[&1];
+        assert_eq!(find_node.dht_method(), "find_node");
+        assert_eq!(find_node.info_hash(), "");
+        assert_eq!(
+            find_node.query.as_ref().unwrap().target.as_deref(),
+            Some("2122232425262728292a2b2c2d2e2f3031323334")
+        );
+        assert_eq!(find_node.client(), "");
+        assert_eq!(find_node.dht_error(), 203);
+    }
+
+    #[test]
+    fn core_bittorrent_truncated() {
+        // Originator's handshake cut short, responder's handshake not recognized
+        let mut parser = BittorrentParser::default();
+        assert_eq!(
+            parser.parse(&tcp(&HANDSHAKE[..40], 6881, true)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&tcp(b"HTTP/1.1 400 Bad Request\r\n", 6881, false)),
+            ParseResult::Continue(0)
+        );
+        let bittorrent = &parser.sessions[&0];
+        assert!(bittorrent.handshake.is_none());
+        assert!(bittorrent.peer_handshake.is_none());
+
+        // Bencoded string longer than the datagram
+        let mut parser = BittorrentParser::default();
+        assert_eq!(
+            parser.parse(&udp(&GET_PEERS[..50], 6881, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&udp(&PEERS[..PEERS.len() - 1], 6881, false)),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+
+        // uTP header cut short
+        let mut parser = BittorrentParser::default();
+        assert_eq!(
+            parser.parse(&udp(&UTP_DATA[..12], 6881, true)),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions[&0].handshake.is_none());
+    }
+}
@@ -0,0 +1,206 @@
+//! CoAP message components.
+//!
+//! See [RFC 7252](https://datatracker.ietf.org/doc/html/rfc7252) for the message format, and
+//! [RFC 7641](https://datatracker.ietf.org/doc/html/rfc7641) and
+//! [RFC 8132](https://datatracker.ietf.org/doc/html/rfc8132) for the Observe option and the
+//! FETCH and PATCH methods. Only options that identify the target resource and the
+//! representation are recorded, and payloads are not recorded.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+
+/// Length of the fixed CoAP header.
+const HDR_LEN: usize = 4;
+/// Marker that separates options from the payload.
+const PAYLOAD_MARKER: u8 = 0xff;
+/// Maximum number of path segments or query parameters recorded per message.
+const MAX_SEGMENTS: usize = 16;
+
+pub const OPT_URI_HOST: u16 = 3;
+pub const OPT_OBSERVE: u16 = 6;
+pub const OPT_URI_PORT: u16 = 7;
+pub const OPT_URI_PATH: u16 = 11;
+pub const OPT_CONTENT_FORMAT: u16 = 12;
+pub const OPT_URI_QUERY: u16 = 15;
+pub const OPT_BLOCK2: u16 = 23;
+pub const OPT_BLOCK1: u16 = 27;
+
+/// Returns the name of a CoAP message type (e.g., `"CON"`).
+pub fn msg_type_name(msg_type: u8) -> &'static str {
+    match msg_type {
+        0 => "CON",
+        1 => "NON",
+        2 => "ACK",
+        3 => "RST",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Returns the name of a CoAP request method code (e.g., `"GET"`).
+pub fn method_name(code: u8) -> &'static str {
+    match code {
+        1 => "GET",
+        2 => "POST",
+        3 => "PUT",
+        4 => "DELETE",
+        5 => "FETCH",
+        6 => "PATCH",
+        7 => "iPATCH",
+        _ => "UNKNOWN",
+    }
+}
+
+/// A CoAP message.
+#[derive(Clone, Debug, Serialize)]
+pub struct CoapMessage {
+    /// Message type (0 for Confirmable, 1 for Non-confirmable, 2 for Acknowledgement, 3 for
+    /// Reset).
+    pub msg_type: u8,
+    /// Method or response code, as the 3-bit class and 5-bit detail (e.g., 69 for 2.05).
+    pub code: u8,
+    pub message_id: u16,
+    pub token: Vec<u8>,
+    /// Uri-Host option, if present.
+    pub uri_host: Option<String>,
+    /// Uri-Port option, if present.
+    pub uri_port: Option<u16>,
+    /// Uri-Path option segments, in order.
+    pub uri_path: Vec<String>,
+    /// Uri-Query option parameters, in order.
+    pub uri_query: Vec<String>,
+    /// Content-Format option, if present (e.g., 50 for `application/json`).
+    pub content_format: Option<u16>,
+    /// Observe option, if present.
+    pub observe: Option<u32>,
+    /// `true` if the message is part of a block-wise transfer.
+    pub block: bool,
+    /// Length of the payload in bytes.
+    pub payload_len: usize,
+}
+
+impl CoapMessage {
+    /// Parses a CoAP message from a UDP payload.
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.len() < HDR_LEN {
+            bail!("truncated header");
+        }
+        if data[0] >> 6 != 1 {
+            bail!("invalid version {}", data[0] >> 6);
+        }
+        let token_len = (data[0] & 0x0f) as usize;
+        if token_len > 8 {
+            bail!("invalid token length {}", token_len);
+        }
+        let code = data[1];
+        if matches!(code >> 5, 1 | 6 | 7) {
+            bail!("invalid code class {}", code >> 5);
+        }
+        if data.len() < HDR_LEN + token_len {
+            bail!("truncated token");
+        }
+        let mut msg = CoapMessage {
+            msg_type: (data[0] >> 4) & 0x03,
+            code,
+            message_id: BigEndian::read_u16(&data[2..4]),
+            token: data[HDR_LEN..HDR_LEN + token_len].to_vec(),
+            uri_host: None,
+            uri_port: None,
+            uri_path: vec![],
+            uri_query: vec![],
+            content_format: None,
+            observe: None,
+            block: false,
+            payload_len: 0,
+        };
+        if code == 0 && data.len() > HDR_LEN + token_len {
+            bail!("empty message with contents");
+        }
+
+        let mut rest = &data[HDR_LEN + token_len..];
+        let mut number = 0;
+        while let Some(&byte) = rest.first() {
+            if byte == PAYLOAD_MARKER {
+                if rest.len() == 1 {
+                    bail!("payload marker without payload");
+                }
+                msg.payload_len = rest.len() - 1;
+                break;
+            }
+            rest = &rest[1..];
+            let delta = parse_option_nibble(byte >> 4, &mut rest)?;
+            let len = parse_option_nibble(byte & 0x0f, &mut rest)? as usize;
+            if rest.len() < len {
+                bail!("truncated option");
+            }
+            let (value, tail) = rest.split_at(len);
+            rest = tail;
+            number += delta;
+            msg.parse_option(number, value);
+        }
+        Ok(msg)
+    }
+
+    fn parse_option(&mut self, number: u32, value: &[u8]) {
+        let number = match u16::try_from(number) {
+            Ok(number) => number,
+            Err(_) => return,
+        };
+        match number {
+            OPT_URI_HOST => self.uri_host = Some(String::from_utf8_lossy(value).into_owned()),
+            OPT_URI_PORT => self.uri_port = Some(parse_uint(value) as u16),
+            OPT_URI_PATH if self.uri_path.len() < MAX_SEGMENTS => self
+                .uri_path
+                .push(String::from_utf8_lossy(value).into_owned()),
+            OPT_URI_QUERY if self.uri_query.len() < MAX_SEGMENTS => self
+                .uri_query
+                .push(String::from_utf8_lossy(value).into_owned()),
+            OPT_CONTENT_FORMAT => self.content_format = Some(parse_uint(value) as u16),
+            OPT_OBSERVE => self.observe = Some(parse_uint(value)),
+            OPT_BLOCK1 | OPT_BLOCK2 => self.block = true,
+            _ => (),
+        }
+    }
+
+    /// Returns `true` if the message is a request.
+    pub fn is_request(&self) -> bool {
+        self.code >> 5 == 0 && self.code != 0
+    }
+
+    /// Returns `true` if the message is a response.
+    pub fn is_response(&self) -> bool {
+        self.code >> 5 >= 2
+    }
+
+    /// Returns the code in `c.dd` notation (e.g., `"2.05"`).
+    pub fn code_str(&self) -> String {
+        format!("{}.{:02}", self.code >> 5, self.code & 0x1f)
+    }
+}
+
+/// Parses an option delta or length nibble, reading its extended value from `rest`.
+fn parse_option_nibble(nibble: u8, rest: &mut &[u8]) -> Result<u32> {
+    match nibble {
+        0..=12 => Ok(nibble as u32),
+        13 if !rest.is_empty() => {
+            let value = rest[0] as u32 + 13;
+            *rest = &rest[1..];
+            Ok(value)
+        }
+        14 if rest.len() >= 2 => {
+            let value = BigEndian::read_u16(rest) as u32 + 269;
+            *rest = &rest[2..];
+            Ok(value)
+        }
+        15 => bail!("reserved option nibble"),
+        _ => bail!("truncated option"),
+    }
+}
+
+/// Parses a variable-length unsigned integer option value.
+fn parse_uint(value: &[u8]) -> u32 {
+    value
+        .iter()
+        .take(4)
+        .fold(0, |acc, byte| (acc << 8) | *byte as u32)
+}
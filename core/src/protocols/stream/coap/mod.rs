@@ -0,0 +1,125 @@
+//! CoAP transaction parsing.
+//!
+//! ## Remarks
+//! A transaction is a request paired with its response by token, whether the response is
+//! piggybacked on the acknowledgement or sent separately. Empty messages (acknowledgements, resets,
+//! and pings) and retransmitted requests are not recorded. Responses that do not match an
+//! outstanding request, such as Observe notifications after the first, are recorded alone.
+//!
+//! Endpoints often exchange many requests over the same flow, so completed transactions are
+//! retained and delivered when the connection terminates, or once a bounded number of them have
+//! been observed. Only CoAP over UDP on port 5683 is parsed; CoAP over DTLS, TCP, and WebSockets
+//! is not.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed CoAP transaction contents.
+#[derive(Debug, Serialize)]
+pub struct Coap {
+    /// Request, if observed.
+    pub request: Option<CoapMessage>,
+    /// Response, if observed.
+    pub response: Option<CoapMessage>,
+}
+
+impl Coap {
+    /// Returns the request method (e.g., `"GET"`), or `""` if no request was observed.
+    pub fn method(&self) -> &str {
+        self.request.as_ref().map_or("", |m| method_name(m.code))
+    }
+
+    /// Returns the message type of the request (e.g., `"CON"`), or of the response if no request
+    /// was observed.
+    pub fn msg_type(&self) -> &str {
+        self.message().map_or("", |m| msg_type_name(m.msg_type))
+    }
+
+    /// Returns the URI path of the request (e.g., `"/sensors/temp"`), or `""` if no request was
+    /// observed.
+    pub fn uri_path(&self) -> String {
+        match &self.request {
+            Some(request) => format!("/{}", request.uri_path.join("/")),
+            None => String::new(),
+        }
+    }
+
+    /// Returns the URI query of the request (e.g., `"rt=temp&if=sensor"`), or `""` if there is
+    /// none.
+    pub fn uri_query(&self) -> String {
+        self.request
+            .as_ref()
+            .map_or(String::new(), |m| m.uri_query.join("&"))
+    }
+
+    /// Returns the Uri-Host option of the request, or `""` if there is none.
+    pub fn uri_host(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|m| m.uri_host.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the token of the transaction as a hex string, or `""` if it is empty.
+    pub fn token(&self) -> String {
+        self.message().map_or(String::new(), |m| {
+            m.token.iter().map(|b| format!("{:02x}", b)).collect()
+        })
+    }
+
+    /// Returns the message ID of the request, or of the response if no request was observed.
+    pub fn message_id(&self) -> u16 {
+        self.message().map_or(0, |m| m.message_id)
+    }
+
+    /// Returns the response code in `c.dd` notation (e.g., `"2.05"`), or `""` if no response was
+    /// observed.
+    pub fn response_code(&self) -> String {
+        self.response
+            .as_ref()
+            .map_or(String::new(), |m| m.code_str())
+    }
+
+    /// Returns the class of the response code (e.g., 4 for client errors), or 0 if no response
+    /// was observed.
+    pub fn response_class(&self) -> u8 {
+        self.response.as_ref().map_or(0, |m| m.code >> 5)
+    }
+
+    /// Returns the Content-Format of the response, or of the request if the response has none.
+    /// Returns 0 (`text/plain`) if neither has one.
+    pub fn content_format(&self) -> u16 {
+        self.response
+            .iter()
+            .chain(self.request.iter())
+            .find_map(|m| m.content_format)
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if the request registers or deregisters an observation, or the response is
+    /// a notification.
+    pub fn observe(&self) -> bool {
+        self.request
+            .iter()
+            .chain(self.response.iter())
+            .any(|m| m.observe.is_some())
+    }
+
+    /// Returns the payload length of the request and response in bytes.
+    pub fn payload_len(&self) -> usize {
+        self.request
+            .iter()
+            .chain(self.response.iter())
+            .map(|m| m.payload_len)
+            .sum()
+    }
+
+    /// Returns the request, or the response if no request was observed.
+    fn message(&self) -> Option<&CoapMessage> {
+        self.request.as_ref().or(self.response.as_ref())
+    }
+}
@@ -0,0 +1,307 @@
+//! CoAP transaction parser.
+//!
+//! Parses CoAP messages and pairs requests with their responses by token.
+
+use super::message::CoapMessage;
+use super::Coap;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+/// Maximum number of completed transactions recorded before parsing stops.
+const MAX_TRANSACTIONS: usize = 64;
+/// Default CoAP port.
+const COAP_PORT: u16 = 5683;
+
+#[derive(Default, Debug)]
+pub struct CoapParser {
+    /// Maps session ID to CoAP transaction
+    sessions: HashMap<usize, Coap>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Maps token to the session ID of a request awaiting a response
+    pending: HashMap<Vec<u8>, usize>,
+    /// Number of completed transactions
+    completed: usize,
+}
+
+impl ConnParsable for CoapParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.src.port() != COAP_PORT && pdu.ctxt.dst.port() != COAP_PORT {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match CoapMessage::parse_from(data) {
+                Ok(_) => ProbeResult::Certain,
+                Err(_) => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|coap| Session {
+            data: SessionData::Coap(Box::new(coap)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, coap)| Session {
+                data: SessionData::Coap(Box::new(coap)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider CoAP to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl CoapParser {
+    fn process(&mut self, data: &[u8]) -> ParseResult {
+        let msg = match CoapMessage::parse_from(data) {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                return ParseResult::Skipped;
+            }
+        };
+        log::debug!("CoAP {}", msg.code_str());
+
+        if msg.is_request() {
+            if let Some(session_id) = self.pending.get(&msg.token) {
+                let retransmitted = self.sessions.get(session_id).is_some_and(|coap| {
+                    coap.request
+                        .as_ref()
+                        .is_some_and(|req| req.message_id == msg.message_id)
+                });
+                if retransmitted {
+                    return ParseResult::Skipped;
+                }
+            }
+            let token = msg.token.clone();
+            let session_id = self.insert(Coap {
+                request: Some(msg),
+                response: None,
+            });
+            self.pending.insert(token, session_id);
+            return ParseResult::Continue(session_id);
+        }
+        if !msg.is_response() {
+            // Empty acknowledgement, reset, or ping
+            return ParseResult::Skipped;
+        }
+
+        let session_id = match self.pending.remove(&msg.token) {
+            Some(session_id) => {
+                if let Some(coap) = self.sessions.get_mut(&session_id) {
+                    coap.response = Some(msg);
+                }
+                session_id
+            }
+            None => self.insert(Coap {
+                request: None,
+                response: Some(msg),
+            }),
+        };
+        self.completed += 1;
+        if self.completed >= MAX_TRANSACTIONS {
+            return ParseResult::HeadersDone(session_id);
+        }
+        ParseResult::Continue(session_id)
+    }
+
+    fn insert(&mut self, coap: Coap) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, coap);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{udp, Flow};
+
+    /// Confirmable GET of `/sensors/temp?unit=c` on `sensor.local` registering an observation.
+    const GET: &[u8] = b"\
+        \x42\x01\x1a\x2b\x71\x3c\x3c\x73\x65\x6e\x73\x6f\x72\x2e\x6c\x6f\
+        \x63\x61\x6c\x30\x57\x73\x65\x6e\x73\x6f\x72\x73\x04\x74\x65\x6d\
+        \x70\x46\x75\x6e\x69\x74\x3d\x63";
+
+    /// Piggybacked 2.05 Content acknowledgement with a JSON payload.
+    const CONTENT: &[u8] = b"\
+        \x62\x45\x1a\x2b\x71\x3c\x61\x0c\x61\x32\xff\x7b\x22\x74\x22\x3a\
+        \x32\x31\x2e\x35\x7d";
+
+    /// Non-confirmable Observe notification.
+    const NOTIFICATION: &[u8] = b"\
+        \x52\x45\x9c\x01\x71\x3c\x61\x0d\x61\x32\xff\x7b\x22\x74\x22\x3a\
+        \x32\x31\x2e\x37\x7d";
+
+    /// Confirmable POST to `/actuators/valve`.
+    const POST: &[u8] = b"\
+        \x44\x02\x01\x00\xaa\xbb\xcc\xdd\xb9\x61\x63\x74\x75\x61\x74\x6f\
+        \x72\x73\x05\x76\x61\x6c\x76\x65\xff\x6f\x70\x65\x6e";
+
+    /// Empty acknowledgement of the POST.
+    const EMPTY_ACK: &[u8] = b"\
+        \x60\x00\x01\x00";
+
+    /// Separate 2.04 Changed response to the POST.
+    const CHANGED: &[u8] = b"\
+        \x44\x44\x7f\x00\xaa\xbb\xcc\xdd";
+
+    const FLOW: Flow = Flow::udp(COAP_PORT);
+
+    #[test]
+    fn core_coap_probe() {
+        let parser = CoapParser::default();
+        assert_eq!(FLOW.probe(&parser, GET, true), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, CONTENT, false), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, b"", true), ProbeResult::Unsure);
+        assert_eq!(parser.probe(&udp(GET, 5684, true)), ProbeResult::NotForUs);
+        // Version 2
+        assert_eq!(
+            FLOW.probe(&parser, b"\x82\x01\x1a\x2b\x71\x3c", true),
+            ProbeResult::NotForUs
+        );
+        // Reserved code class 1
+        assert_eq!(
+            FLOW.probe(&parser, b"\x40\x21\x1a\x2b", true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(FLOW.probe(&parser, &GET[..10], true), ProbeResult::NotForUs);
+    }
+
+    #[test]
+    fn core_coap_transactions() {
+        let mut parser = CoapParser::default();
+        assert_eq!(FLOW.parse(&mut parser, GET, true), ParseResult::Continue(0));
+        // Retransmission
+        assert_eq!(FLOW.parse(&mut parser, GET, true), ParseResult::Skipped);
+        assert_eq!(
+            FLOW.parse(&mut parser, CONTENT, false),
+            ParseResult::Continue(0)
+        );
+        // Notification after the first is recorded alone
+        assert_eq!(
+            FLOW.parse(&mut parser, NOTIFICATION, false),
+            ParseResult::Continue(1)
+        );
+
+        // Separate response in a later datagram
+        assert_eq!(
+            FLOW.parse(&mut parser, POST, true),
+            ParseResult::Continue(2)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, EMPTY_ACK, false),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, CHANGED, false),
+            ParseResult::Continue(2)
+        );
+
+        let get = &parser.sessions[&0];
+        assert_eq!(get.method(), "GET");
+        assert_eq!(get.msg_type(), "CON");
+        assert_eq!(get.uri_host(), "sensor.local");
+        assert_eq!(get.uri_path(), "/sensors/temp");
+        assert_eq!(get.uri_query(), "unit=c");
+        assert_eq!(get.token(), "713c");
+        assert_eq!(get.message_id(), 0x1a2b);
+        assert_eq!(get.response_code(), "2.05");
+        assert_eq!(get.response_class(), 2);
+        assert_eq!(get.content_format(), 50);
+        assert!(get.observe());
+        assert_eq!(get.payload_len(), 10);
+
+        let notification = &parser.sessions[&1];
+        assert!(notification.request.is_none());
+        assert_eq!(notification.method(), "");
+        assert_eq!(notification.msg_type(), "NON");
+        assert_eq!(notification.response.as_ref().unwrap().observe, Some(13));
+
+        let post = &parser.sessions[&2];
+        assert_eq!(post.method(), "POST");
+        assert_eq!(post.uri_path(), "/actuators/valve");
+        assert_eq!(post.token(), "aabbccdd");
+        assert_eq!(post.response_code(), "2.04");
+        assert_eq!(post.response.as_ref().unwrap().message_id, 0x7f00);
+        assert!(!post.observe());
+        assert_eq!(post.payload_len(), 4);
+    }
+
+    #[test]
+    fn core_coap_truncated() {
+        let mut parser = CoapParser::default();
+        // Header
+        assert_eq!(
+            FLOW.parse(&mut parser, &GET[..3], true),
+            ParseResult::Skipped
+        );
+        // Token
+        assert_eq!(
+            FLOW.parse(&mut parser, &GET[..5], true),
+            ParseResult::Skipped
+        );
+        // Uri-Host option value
+        assert_eq!(
+            FLOW.parse(&mut parser, &GET[..10], true),
+            ParseResult::Skipped
+        );
+        // Payload marker without payload
+        assert_eq!(
+            FLOW.parse(&mut parser, &CONTENT[..11], false),
+            ParseResult::Skipped
+        );
+        // Extended option delta
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x40\x01\x00\x01\xd0", true),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+        assert_eq!(
+            FLOW.parse(&mut parser, POST, true),
+            ParseResult::Continue(0)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! DHCP message components.
+//!
+//! See [RFC 2131](https://datatracker.ietf.org/doc/html/rfc2131) for the message format and
+//! [RFC 2132](https://datatracker.ietf.org/doc/html/rfc2132) for options.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+use std::net::Ipv4Addr;
+
+/// Length of the fixed-format portion of a DHCP message, up to and including the magic cookie.
+pub(super) const FIXED_LEN: usize = 240;
+/// DHCP magic cookie, which distinguishes DHCP from BOOTP.
+pub(super) const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+
+/// DHCP message type (option 53).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum DhcpMessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+    Unknown(u8),
+}
+
+impl DhcpMessageType {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => DhcpMessageType::Discover,
+            2 => DhcpMessageType::Offer,
+            3 => DhcpMessageType::Request,
+            4 => DhcpMessageType::Decline,
+            5 => DhcpMessageType::Ack,
+            6 => DhcpMessageType::Nak,
+            7 => DhcpMessageType::Release,
+            8 => DhcpMessageType::Inform,
+            _ => DhcpMessageType::Unknown(value),
+        }
+    }
+
+    /// Returns the lowercase name of the message type (e.g., `"discover"`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DhcpMessageType::Discover => "discover",
+            DhcpMessageType::Offer => "offer",
+            DhcpMessageType::Request => "request",
+            DhcpMessageType::Decline => "decline",
+            DhcpMessageType::Ack => "ack",
+            DhcpMessageType::Nak => "nak",
+            DhcpMessageType::Release => "release",
+            DhcpMessageType::Inform => "inform",
+            DhcpMessageType::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Returns `true` if the message is sent by a client.
+    pub fn is_from_client(&self) -> bool {
+        matches!(
+            self,
+            DhcpMessageType::Discover
+                | DhcpMessageType::Request
+                | DhcpMessageType::Decline
+                | DhcpMessageType::Release
+                | DhcpMessageType::Inform
+        )
+    }
+}
+
+/// A DHCP message.
+#[derive(Clone, Debug, Serialize)]
+pub struct DhcpMessage {
+    /// Message type (option 53).
+    pub msg_type: DhcpMessageType,
+    /// Transaction ID (`xid`).
+    pub transaction_id: u32,
+    /// Client hardware address (`chaddr`), colon-separated hex.
+    pub client_mac: String,
+    /// Client address, if already bound (renewing or rebinding).
+    pub ciaddr: Ipv4Addr,
+    /// Address assigned to the client ("your" address).
+    pub yiaddr: Ipv4Addr,
+    /// Address of the next server to use in bootstrap.
+    pub siaddr: Ipv4Addr,
+    /// Relay agent address.
+    pub giaddr: Ipv4Addr,
+    /// Requested IP address (option 50).
+    pub requested_addr: Option<Ipv4Addr>,
+    /// Lease time in seconds (option 51).
+    pub lease_time: Option<u32>,
+    /// Server identifier (option 54).
+    pub server_id: Option<Ipv4Addr>,
+    /// Host name (option 12).
+    pub hostname: Option<String>,
+    /// Vendor class identifier (option 60).
+    pub vendor_class: Option<String>,
+    /// Client identifier as a hex string (option 61).
+    pub client_id: Option<String>,
+    /// Parameter request list (option 55), in client order.
+    pub param_request_list: Vec<u8>,
+}
+
+impl DhcpMessage {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.len() < FIXED_LEN || data[236..240] != MAGIC_COOKIE {
+            bail!("not a DHCP message");
+        }
+        let hlen = std::cmp::min(data[2] as usize, 16);
+        let client_mac = data[28..28 + hlen]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":");
+        let mut msg = DhcpMessage {
+            msg_type: DhcpMessageType::Unknown(0),
+            transaction_id: BigEndian::read_u32(&data[4..8]),
+            client_mac,
+            ciaddr: read_addr(&data[12..16]),
+            yiaddr: read_addr(&data[16..20]),
+            siaddr: read_addr(&data[20..24]),
+            giaddr: read_addr(&data[24..28]),
+            requested_addr: None,
+            lease_time: None,
+            server_id: None,
+            hostname: None,
+            vendor_class: None,
+            client_id: None,
+            param_request_list: vec![],
+        };
+
+        let mut found_type = false;
+        let mut offset = FIXED_LEN;
+        while offset < data.len() {
+            let code = data[offset];
+            match code {
+                0 => {
+                    // Pad
+                    offset += 1;
+                    continue;
+                }
+                255 => break, // End
+                _ => (),
+            }
+            let len = match data.get(offset + 1) {
+                Some(len) => *len as usize,
+                None => bail!("truncated option"),
+            };
+            let value = match data.get(offset + 2..offset + 2 + len) {
+                Some(value) => value,
+                None => bail!("truncated option"),
+            };
+            match (code, len) {
+                (53, 1) => {
+                    msg.msg_type = DhcpMessageType::from_u8(value[0]);
+                    found_type = true;
+                }
+                (50, 4) => msg.requested_addr = Some(read_addr(value)),
+                (51, 4) => msg.lease_time = Some(BigEndian::read_u32(value)),
+                (54, 4) => msg.server_id = Some(read_addr(value)),
+                (12, _) => msg.hostname = Some(String::from_utf8_lossy(value).into_owned()),
+                (60, _) => msg.vendor_class = Some(String::from_utf8_lossy(value).into_owned()),
+                (61, _) => msg.client_id = Some(hex::encode(value)),
+                (55, _) => msg.param_request_list = value.to_vec(),
+                _ => (),
+            }
+            offset += 2 + len;
+        }
+        if !found_type {
+            bail!("missing DHCP message type");
+        }
+        Ok(msg)
+    }
+}
+
+fn read_addr(data: &[u8]) -> Ipv4Addr {
+    Ipv4Addr::new(data[0], data[1], data[2], data[3])
+}
@@ -0,0 +1,128 @@
+//! DHCP transaction parsing.
+//!
+//! ## Remarks
+//! Messages are paired into transactions by transaction ID (`xid`), which is shared by the
+//! DISCOVER, OFFER, REQUEST, and ACK of a lease acquisition. A transaction is done once the
+//! server acknowledges (or rejects) the client, or the client releases or declines an address.
+//!
+//! Broadcast exchanges are tracked as separate connections for each direction (e.g., the client
+//! sends from `0.0.0.0:68` to `255.255.255.255:67`, and the server replies to port 68), so a
+//! transaction may only include the messages of one side. Unacknowledged transactions are
+//! reported when the connection terminates.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+use std::net::Ipv4Addr;
+
+/// Parsed DHCP transaction contents.
+#[derive(Clone, Debug, Serialize)]
+pub struct Dhcp {
+    /// DHCP transaction ID.
+    pub transaction_id: u32,
+    /// Messages in the transaction, in order of arrival.
+    pub messages: Vec<DhcpMessage>,
+}
+
+impl Dhcp {
+    /// Returns the most recent message of type `msg_type` in the transaction, if any.
+    pub fn message(&self, msg_type: DhcpMessageType) -> Option<&DhcpMessage> {
+        self.messages.iter().rev().find(|m| m.msg_type == msg_type)
+    }
+
+    /// Returns the most recent message sent by the client, if any.
+    fn client_message(&self) -> Option<&DhcpMessage> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.msg_type.is_from_client())
+    }
+
+    /// Returns the type of the most recent message (e.g., `"ack"`), or `""` if there is none.
+    pub fn message_type(&self) -> &str {
+        self.messages.last().map_or("", |m| m.msg_type.as_str())
+    }
+
+    /// Returns the client hardware (MAC) address.
+    pub fn client_mac(&self) -> &str {
+        self.messages.first().map_or("", |m| m.client_mac.as_str())
+    }
+
+    /// Returns the address requested by the client (option 50, or `ciaddr` when renewing), or
+    /// `0.0.0.0` if none was requested.
+    pub fn requested_addr(&self) -> Ipv4Addr {
+        self.messages
+            .iter()
+            .filter(|m| m.msg_type.is_from_client())
+            .find_map(|m| {
+                m.requested_addr
+                    .or(Some(m.ciaddr).filter(|addr| !addr.is_unspecified()))
+            })
+            .unwrap_or(Ipv4Addr::UNSPECIFIED)
+    }
+
+    /// Returns the address assigned to the client (in the ACK, or else the OFFER), or `0.0.0.0`
+    /// if none was assigned.
+    pub fn assigned_addr(&self) -> Ipv4Addr {
+        self.message(DhcpMessageType::Ack)
+            .or_else(|| self.message(DhcpMessageType::Offer))
+            .map_or(Ipv4Addr::UNSPECIFIED, |m| m.yiaddr)
+    }
+
+    /// Returns the lease time (in seconds) granted by the server, or `0` if none was granted.
+    pub fn lease_time(&self) -> u32 {
+        self.message(DhcpMessageType::Ack)
+            .or_else(|| self.message(DhcpMessageType::Offer))
+            .and_then(|m| m.lease_time)
+            .unwrap_or_default()
+    }
+
+    /// Returns the server identifier, or `0.0.0.0` if none was observed.
+    pub fn server_id(&self) -> Ipv4Addr {
+        self.messages
+            .iter()
+            .rev()
+            .find_map(|m| m.server_id)
+            .unwrap_or(Ipv4Addr::UNSPECIFIED)
+    }
+
+    /// Returns the client's hostname (option 12), or `""` if it was not sent.
+    pub fn hostname(&self) -> &str {
+        self.messages
+            .iter()
+            .rev()
+            .find_map(|m| m.hostname.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the client's vendor class identifier (option 60), or `""` if it was not sent.
+    pub fn vendor_class(&self) -> &str {
+        self.client_message()
+            .and_then(|m| m.vendor_class.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the client identifier (option 61) as a hex string, or `""` if it was not sent.
+    pub fn client_id(&self) -> &str {
+        self.client_message()
+            .and_then(|m| m.client_id.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the parameter request list (option 55) as comma-separated option codes in client
+    /// order (e.g., `"1,3,6,15"`), commonly used to fingerprint client operating systems.
+    pub fn param_request_list(&self) -> String {
+        self.client_message()
+            .map(|m| {
+                m.param_request_list
+                    .iter()
+                    .map(|code| code.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .unwrap_or_default()
+    }
+}
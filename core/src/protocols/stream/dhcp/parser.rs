@@ -0,0 +1,273 @@
+//! DHCP transaction parser.
+//!
+//! Parses DHCP messages and maintains state for pairing the messages of each transaction by
+//! transaction ID.
+
+use super::message::{DhcpMessage, DhcpMessageType, FIXED_LEN, MAGIC_COOKIE};
+use super::Dhcp;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+#[derive(Default, Debug)]
+pub struct DhcpParser {
+    /// Maps session ID to DHCP transaction
+    sessions: HashMap<usize, Dhcp>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+}
+
+impl ConnParsable for DhcpParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        let dst_port = pdu.ctxt.dst.port();
+        let src_port = pdu.ctxt.src.port();
+        if !matches!(src_port, 67 | 68) && !matches!(dst_port, 67 | 68) {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() < FIXED_LEN {
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            // BOOTREQUEST or BOOTREPLY, followed by the DHCP magic cookie
+            if matches!(data[0], 1 | 2) && data[236..240] == MAGIC_COOKIE {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|dhcp| Session {
+            data: SessionData::Dhcp(Box::new(dhcp)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, dhcp)| Session {
+                data: SessionData::Dhcp(Box::new(dhcp)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider DHCP to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl DhcpParser {
+    pub(crate) fn process(&mut self, data: &[u8]) -> ParseResult {
+        match DhcpMessage::parse_from(data) {
+            Ok(msg) => {
+                log::debug!("DHCP {}", msg.msg_type.as_str());
+                // Server response, or client message without a reply
+                let done = matches!(
+                    msg.msg_type,
+                    DhcpMessageType::Ack
+                        | DhcpMessageType::Nak
+                        | DhcpMessageType::Release
+                        | DhcpMessageType::Decline
+                );
+                let transaction_id = msg.transaction_id;
+                for (session_id, dhcp) in self.sessions.iter_mut() {
+                    if transaction_id == dhcp.transaction_id {
+                        dhcp.messages.push(msg);
+                        if done {
+                            return ParseResult::Done(*session_id);
+                        }
+                        return ParseResult::Continue(*session_id);
+                    }
+                }
+                let dhcp = Dhcp {
+                    transaction_id,
+                    messages: vec![msg],
+                };
+                let session_id = self.cnt;
+                self.cnt += 1;
+                self.sessions.insert(session_id, dhcp);
+                if done {
+                    return ParseResult::Done(session_id);
+                }
+                ParseResult::Continue(session_id)
+            }
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{udp, Flow};
+    use std::net::Ipv4Addr;
+
+    const XID: u32 = 0x3903f326;
+
+    /// Options of a DISCOVER from dhclient: message type, requested address, host name, and
+    /// parameter request list.
+    const DISCOVER_OPTIONS: &[u8] = b"\x35\x01\x01\x32\x04\xc0\xa8\x01\x64\x0c\x06laptop\
+        \x37\x0d\x01\x1c\x02\x03\x0f\x06\x77\x0c\x2c\x2f\x1a\x79\x2a\xff";
+    /// Options of an OFFER: message type, server identifier, lease time, subnet mask, and router.
+    const OFFER_OPTIONS: &[u8] = b"\x35\x01\x02\x36\x04\xc0\xa8\x01\x01\x33\x04\x00\x01\x51\x80\
+        \x01\x04\xff\xff\xff\x00\x03\x04\xc0\xa8\x01\x01\xff";
+    const REQUEST_OPTIONS: &[u8] = b"\x35\x01\x03\x36\x04\xc0\xa8\x01\x01\x32\x04\xc0\xa8\x01\x64\
+        \x0c\x06laptop\x37\x0d\x01\x1c\x02\x03\x0f\x06\x77\x0c\x2c\x2f\x1a\x79\x2a\xff";
+    const ACK_OPTIONS: &[u8] = b"\x35\x01\x05\x36\x04\xc0\xa8\x01\x01\x33\x04\x00\x01\x51\x80\
+        \x01\x04\xff\xff\xff\x00\x03\x04\xc0\xa8\x01\x01\xff";
+
+    /// Returns a message from the client `52:54:00:12:34:56` with `options`, assigning `yiaddr`.
+    fn message(xid: u32, yiaddr: [u8; 4], options: &[u8]) -> Vec<u8> {
+        let op = match options[2] {
+            2 | 5 | 6 => 2,
+            _ => 1,
+        };
+        let mut msg = vec![op, 1, 6, 0];
+        msg.extend_from_slice(&xid.to_be_bytes());
+        msg.extend_from_slice(&[0; 8]);
+        msg.extend_from_slice(&yiaddr);
+        msg.extend_from_slice(&[0; 8]);
+        msg.extend_from_slice(&[0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+        msg.resize(236, 0);
+        msg.extend_from_slice(&MAGIC_COOKIE);
+        msg.extend_from_slice(options);
+        msg
+    }
+
+    const FLOW: Flow = Flow::udp(67);
+
+    #[test]
+    fn core_dhcp_probe() {
+        let parser = DhcpParser::default();
+        let discover = message(XID, [0; 4], DISCOVER_OPTIONS);
+        assert!(matches!(
+            FLOW.probe(&parser, &discover, true),
+            ProbeResult::Certain
+        ));
+        // Not a DHCP port
+        assert!(matches!(
+            parser.probe(&udp(&discover, 53, true)),
+            ProbeResult::NotForUs
+        ));
+        // BOOTP without the magic cookie
+        let mut bootp = discover.clone();
+        bootp[236..240].copy_from_slice(&[0; 4]);
+        assert!(matches!(
+            FLOW.probe(&parser, &bootp, true),
+            ProbeResult::NotForUs
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, &discover[..200], true),
+            ProbeResult::NotForUs
+        ));
+    }
+
+    #[test]
+    fn core_dhcp_transaction() {
+        let mut parser = DhcpParser::default();
+        let lease = [192, 168, 1, 100];
+        let discover = message(XID, [0; 4], DISCOVER_OPTIONS);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &discover, true),
+            ParseResult::Continue(0)
+        ));
+        let offer = message(XID, lease, OFFER_OPTIONS);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &offer, false),
+            ParseResult::Continue(0)
+        ));
+        // Another client's transaction
+        let other = message(0x7d1c44a0, [0; 4], DISCOVER_OPTIONS);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &other, true),
+            ParseResult::Continue(1)
+        ));
+        let request = message(XID, [0; 4], REQUEST_OPTIONS);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &request, true),
+            ParseResult::Continue(0)
+        ));
+        let ack = message(XID, lease, ACK_OPTIONS);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &ack, false),
+            ParseResult::Done(0)
+        ));
+
+        let session = parser.remove_session(0).unwrap();
+        match &session.data {
+            SessionData::Dhcp(dhcp) => {
+                assert_eq!(dhcp.transaction_id, XID);
+                assert_eq!(dhcp.messages.len(), 4);
+                assert_eq!(dhcp.message_type(), "ack");
+                assert_eq!(dhcp.client_mac(), "52:54:00:12:34:56");
+                assert_eq!(dhcp.requested_addr(), Ipv4Addr::new(192, 168, 1, 100));
+                assert_eq!(dhcp.assigned_addr(), Ipv4Addr::new(192, 168, 1, 100));
+                assert_eq!(dhcp.server_id(), Ipv4Addr::new(192, 168, 1, 1));
+                assert_eq!(dhcp.lease_time(), 86400);
+                assert_eq!(dhcp.hostname(), "laptop");
+                assert_eq!(
+                    dhcp.param_request_list(),
+                    "1,28,2,3,15,6,119,12,44,47,26,121,42"
+                );
+            }
+            _ => panic!("Expected DHCP session"),
+        }
+    }
+
+    #[test]
+    fn core_dhcp_truncated() {
+        let mut parser = DhcpParser::default();
+        let discover = message(XID, [0; 4], DISCOVER_OPTIONS);
+        // Fixed-format fields only
+        assert!(matches!(
+            FLOW.parse(&mut parser, &discover[..200], true),
+            ParseResult::Skipped
+        ));
+        // Parameter request list cut short
+        assert!(matches!(
+            FLOW.parse(&mut parser, &discover[..discover.len() - 5], true),
+            ParseResult::Skipped
+        ));
+        // No message type
+        assert!(matches!(
+            FLOW.parse(&mut parser, &discover[..FIXED_LEN], true),
+            ParseResult::Skipped
+        ));
+        assert!(parser.drain_sessions().is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+//! DHCPv6 message components.
+//!
+//! See [RFC 8415](https://datatracker.ietf.org/doc/html/rfc8415) for the message format and
+//! options.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+use std::net::Ipv6Addr;
+
+const RELAY_FORW: u8 = 12;
+const RELAY_REPL: u8 = 13;
+/// Maximum number of nested relay messages.
+const MAX_RELAY_DEPTH: usize = 8;
+
+/// DHCPv6 message type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Dhcpv6MessageType {
+    Solicit,
+    Advertise,
+    Request,
+    Confirm,
+    Renew,
+    Rebind,
+    Reply,
+    Release,
+    Decline,
+    Reconfigure,
+    InformationRequest,
+    Unknown(u8),
+}
+
+impl Dhcpv6MessageType {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Dhcpv6MessageType::Solicit,
+            2 => Dhcpv6MessageType::Advertise,
+            3 => Dhcpv6MessageType::Request,
+            4 => Dhcpv6MessageType::Confirm,
+            5 => Dhcpv6MessageType::Renew,
+            6 => Dhcpv6MessageType::Rebind,
+            7 => Dhcpv6MessageType::Reply,
+            8 => Dhcpv6MessageType::Release,
+            9 => Dhcpv6MessageType::Decline,
+            10 => Dhcpv6MessageType::Reconfigure,
+            11 => Dhcpv6MessageType::InformationRequest,
+            _ => Dhcpv6MessageType::Unknown(value),
+        }
+    }
+
+    /// Returns the lowercase name of the message type (e.g., `"solicit"`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dhcpv6MessageType::Solicit => "solicit",
+            Dhcpv6MessageType::Advertise => "advertise",
+            Dhcpv6MessageType::Request => "request",
+            Dhcpv6MessageType::Confirm => "confirm",
+            Dhcpv6MessageType::Renew => "renew",
+            Dhcpv6MessageType::Rebind => "rebind",
+            Dhcpv6MessageType::Reply => "reply",
+            Dhcpv6MessageType::Release => "release",
+            Dhcpv6MessageType::Decline => "decline",
+            Dhcpv6MessageType::Reconfigure => "reconfigure",
+            Dhcpv6MessageType::InformationRequest => "information-request",
+            Dhcpv6MessageType::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Returns `true` if the message is sent by a client.
+    pub fn is_from_client(&self) -> bool {
+        matches!(
+            self,
+            Dhcpv6MessageType::Solicit
+                | Dhcpv6MessageType::Request
+                | Dhcpv6MessageType::Confirm
+                | Dhcpv6MessageType::Renew
+                | Dhcpv6MessageType::Rebind
+                | Dhcpv6MessageType::Release
+                | Dhcpv6MessageType::Decline
+                | Dhcpv6MessageType::InformationRequest
+        )
+    }
+}
+
+/// A DHCPv6 client or server message.
+#[derive(Clone, Debug, Serialize)]
+pub struct Dhcpv6Message {
+    /// Message type.
+    pub msg_type: Dhcpv6MessageType,
+    /// Transaction ID (24 bits).
+    pub transaction_id: u32,
+    /// `true` if the message was encapsulated in a Relay-forward or Relay-reply message.
+    pub relayed: bool,
+    /// Client DUID as a hex string (option 1).
+    pub client_duid: Option<String>,
+    /// Server DUID as a hex string (option 2).
+    pub server_duid: Option<String>,
+    /// Link-layer address embedded in the client DUID (DUID-LLT or DUID-LL), colon-separated hex.
+    pub client_mac: Option<String>,
+    /// Addresses in IA_NA and IA_TA options.
+    pub addresses: Vec<Ipv6Addr>,
+    /// Delegated prefixes in IA_PD options, in CIDR notation.
+    pub prefixes: Vec<String>,
+    /// Domain name in the Client FQDN option (option 39).
+    pub hostname: Option<String>,
+    /// Vendor class data (option 16), with data items separated by commas.
+    pub vendor_class: Option<String>,
+    /// Enterprise number of the vendor class (option 16).
+    pub vendor_enterprise: Option<u32>,
+    /// Option request option (option 6), in client order.
+    pub option_request: Vec<u16>,
+}
+
+impl Dhcpv6Message {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        Self::parse_relayed(data, 0)
+    }
+
+    fn parse_relayed(data: &[u8], depth: usize) -> Result<Self> {
+        if data.len() < 4 {
+            bail!("message too short");
+        }
+        if matches!(data[0], RELAY_FORW | RELAY_REPL) {
+            // hop-count, link-address, and peer-address precede options
+            if depth >= MAX_RELAY_DEPTH || data.len() < 34 {
+                bail!("invalid relay message");
+            }
+            for (code, value) in Options::new(&data[34..]) {
+                if code == 9 {
+                    let mut msg = Self::parse_relayed(value, depth + 1)?;
+                    msg.relayed = true;
+                    return Ok(msg);
+                }
+            }
+            bail!("relay message without encapsulated message");
+        }
+
+        let mut msg = Dhcpv6Message {
+            msg_type: Dhcpv6MessageType::from_u8(data[0]),
+            transaction_id: BigEndian::read_u24(&data[1..4]),
+            relayed: false,
+            client_duid: None,
+            server_duid: None,
+            client_mac: None,
+            addresses: vec![],
+            prefixes: vec![],
+            hostname: None,
+            vendor_class: None,
+            vendor_enterprise: None,
+            option_request: vec![],
+        };
+        if let Dhcpv6MessageType::Unknown(value) = msg.msg_type {
+            bail!("unknown message type {}", value);
+        }
+        let mut options = Options::new(&data[4..]);
+        for (code, value) in &mut options {
+            match code {
+                1 => {
+                    msg.client_duid = Some(hex::encode(value));
+                    msg.client_mac = duid_link_layer_addr(value);
+                }
+                2 => msg.server_duid = Some(hex::encode(value)),
+                // IA_NA: IAID, T1, T2, then IA options
+                3 if value.len() >= 12 => msg.parse_ia_options(&value[12..]),
+                // IA_TA: IAID, then IA options
+                4 if value.len() >= 4 => msg.parse_ia_options(&value[4..]),
+                // IA_PD: IAID, T1, T2, then IA options
+                25 if value.len() >= 12 => msg.parse_ia_options(&value[12..]),
+                6 => msg.option_request = value.chunks_exact(2).map(BigEndian::read_u16).collect(),
+                16 if value.len() >= 4 => {
+                    msg.vendor_enterprise = Some(BigEndian::read_u32(&value[..4]));
+                    let items: Vec<String> = Options::data_items(&value[4..])
+                        .map(|item| String::from_utf8_lossy(item).into_owned())
+                        .collect();
+                    msg.vendor_class = Some(items.join(","));
+                }
+                // Flags, then domain name
+                39 if !value.is_empty() => msg.hostname = Some(domain_name(&value[1..])),
+                _ => (),
+            }
+        }
+        if options.truncated {
+            bail!("truncated option");
+        }
+        Ok(msg)
+    }
+
+    /// Parses the options encapsulated in an IA_NA, IA_TA, or IA_PD option.
+    fn parse_ia_options(&mut self, data: &[u8]) {
+        for (code, value) in Options::new(data) {
+            match code {
+                // IA Address: address, preferred and valid lifetimes
+                5 if value.len() >= 16 => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(&value[..16]);
+                    self.addresses.push(Ipv6Addr::from(octets));
+                }
+                // IA Prefix: preferred and valid lifetimes, prefix length, prefix
+                26 if value.len() >= 25 => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(&value[9..25]);
+                    self.prefixes
+                        .push(format!("{}/{}", Ipv6Addr::from(octets), value[8]));
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Iterator over DHCPv6 options (2-byte code, 2-byte length, value).
+struct Options<'a> {
+    data: &'a [u8],
+    offset: usize,
+    /// Set if the last option extends past the end of the data.
+    truncated: bool,
+}
+
+impl<'a> Options<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Options {
+            data,
+            offset: 0,
+            truncated: false,
+        }
+    }
+
+    /// Returns the length-prefixed data items in a vendor class or user class option.
+    fn data_items(data: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let len = BigEndian::read_u16(data.get(offset..offset + 2)?) as usize;
+            let item = data.get(offset + 2..offset + 2 + len)?;
+            offset += 2 + len;
+            Some(item)
+        })
+    }
+}
+
+impl<'a> Iterator for Options<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset + 4 > self.data.len() {
+            return None;
+        }
+        let code = BigEndian::read_u16(&self.data[self.offset..]);
+        let len = BigEndian::read_u16(&self.data[self.offset + 2..]) as usize;
+        match self.data.get(self.offset + 4..self.offset + 4 + len) {
+            Some(value) => {
+                self.offset += 4 + len;
+                Some((code, value))
+            }
+            None => {
+                self.truncated = true;
+                None
+            }
+        }
+    }
+}
+
+/// Returns the link-layer address in a DUID-LLT or DUID-LL, if present.
+fn duid_link_layer_addr(duid: &[u8]) -> Option<String> {
+    let lladdr = match BigEndian::read_u16(duid.get(..2)?) {
+        1 => duid.get(8..)?, // type, hardware type, time
+        3 => duid.get(4..)?, // type, hardware type
+        _ => return None,
+    };
+    if lladdr.is_empty() {
+        return None;
+    }
+    Some(
+        lladdr
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+/// Decodes a domain name in DNS wire format (uncompressed), as used in the Client FQDN option.
+fn domain_name(data: &[u8]) -> String {
+    let mut labels = vec![];
+    let mut offset = 0;
+    while let Some(len) = data.get(offset).map(|len| *len as usize) {
+        if len == 0 {
+            break;
+        }
+        match data.get(offset + 1..offset + 1 + len) {
+            Some(label) => labels.push(String::from_utf8_lossy(label).into_owned()),
+            None => break,
+        }
+        offset += 1 + len;
+    }
+    labels.join(".")
+}
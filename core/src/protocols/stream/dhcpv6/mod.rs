@@ -0,0 +1,123 @@
+//! DHCPv6 transaction parsing.
+//!
+//! ## Remarks
+//! Messages are paired into transactions by transaction ID. Unlike DHCP, each DHCPv6 exchange
+//! uses its own transaction ID, so a SOLICIT/ADVERTISE exchange and the REQUEST/REPLY exchange
+//! that follows are separate transactions. A transaction is done once the server advertises or
+//! replies. Messages encapsulated by relay agents are parsed from the innermost Relay Message
+//! option.
+//!
+//! As with DHCP, clients send to a multicast address and servers reply to the client's
+//! link-local address, so each direction of an exchange may be tracked as a separate connection.
+//! Transactions without a server response are reported when the connection terminates.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+use std::net::Ipv6Addr;
+
+/// Parsed DHCPv6 transaction contents.
+#[derive(Clone, Debug, Serialize)]
+pub struct Dhcpv6 {
+    /// DHCPv6 transaction ID.
+    pub transaction_id: u32,
+    /// Messages in the transaction, in order of arrival.
+    pub messages: Vec<Dhcpv6Message>,
+}
+
+impl Dhcpv6 {
+    /// Returns the most recent message of type `msg_type` in the transaction, if any.
+    pub fn message(&self, msg_type: Dhcpv6MessageType) -> Option<&Dhcpv6Message> {
+        self.messages.iter().rev().find(|m| m.msg_type == msg_type)
+    }
+
+    /// Returns the most recent message sent by the client, if any.
+    fn client_message(&self) -> Option<&Dhcpv6Message> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.msg_type.is_from_client())
+    }
+
+    /// Returns the type of the most recent message (e.g., `"reply"`), or `""` if there is none.
+    pub fn message_type(&self) -> &str {
+        self.messages.last().map_or("", |m| m.msg_type.as_str())
+    }
+
+    /// Returns the client DUID as a hex string, or `""` if it was not observed.
+    pub fn client_duid(&self) -> &str {
+        self.messages
+            .iter()
+            .find_map(|m| m.client_duid.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the link-layer address embedded in the client DUID, or `""` if the DUID does not
+    /// contain one.
+    pub fn client_mac(&self) -> &str {
+        self.messages
+            .iter()
+            .find_map(|m| m.client_mac.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the server DUID as a hex string, or `""` if it was not observed.
+    pub fn server_duid(&self) -> &str {
+        self.messages
+            .iter()
+            .rev()
+            .find_map(|m| m.server_duid.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the first address requested by the client, or `::` if none was requested.
+    pub fn requested_addr(&self) -> Ipv6Addr {
+        self.messages
+            .iter()
+            .filter(|m| m.msg_type.is_from_client())
+            .find_map(|m| m.addresses.first().copied())
+            .unwrap_or(Ipv6Addr::UNSPECIFIED)
+    }
+
+    /// Returns the first address assigned to the client by the server, or `::` if none was
+    /// assigned.
+    pub fn assigned_addr(&self) -> Ipv6Addr {
+        self.message(Dhcpv6MessageType::Reply)
+            .or_else(|| self.message(Dhcpv6MessageType::Advertise))
+            .and_then(|m| m.addresses.first().copied())
+            .unwrap_or(Ipv6Addr::UNSPECIFIED)
+    }
+
+    /// Returns the client's fully qualified domain name, or `""` if it was not sent.
+    pub fn hostname(&self) -> &str {
+        self.messages
+            .iter()
+            .rev()
+            .find_map(|m| m.hostname.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the client's vendor class data, or `""` if it was not sent.
+    pub fn vendor_class(&self) -> &str {
+        self.client_message()
+            .and_then(|m| m.vendor_class.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the options requested by the client (option 6) as comma-separated option codes in
+    /// client order (e.g., `"23,24"`), the DHCPv6 counterpart of the DHCP parameter request list.
+    pub fn option_request(&self) -> String {
+        self.client_message()
+            .map(|m| {
+                m.option_request
+                    .iter()
+                    .map(|code| code.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .unwrap_or_default()
+    }
+}
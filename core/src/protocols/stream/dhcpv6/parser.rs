@@ -0,0 +1,269 @@
+//! DHCPv6 transaction parser.
+//!
+//! Parses DHCPv6 messages and maintains state for pairing the messages of each transaction by
+//! transaction ID.
+
+use super::message::{Dhcpv6Message, Dhcpv6MessageType};
+use super::Dhcpv6;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+#[derive(Default, Debug)]
+pub struct Dhcpv6Parser {
+    /// Maps session ID to DHCPv6 transaction
+    sessions: HashMap<usize, Dhcpv6>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+}
+
+impl ConnParsable for Dhcpv6Parser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        let dst_port = pdu.ctxt.dst.port();
+        let src_port = pdu.ctxt.src.port();
+        if !matches!(src_port, 546 | 547) && !matches!(dst_port, 546 | 547) {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() < 4 {
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match Dhcpv6Message::parse_from(data) {
+                Ok(_) => ProbeResult::Certain,
+                Err(_) => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|dhcpv6| Session {
+            data: SessionData::Dhcpv6(Box::new(dhcpv6)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, dhcpv6)| Session {
+                data: SessionData::Dhcpv6(Box::new(dhcpv6)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider DHCPv6 to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl Dhcpv6Parser {
+    pub(crate) fn process(&mut self, data: &[u8]) -> ParseResult {
+        match Dhcpv6Message::parse_from(data) {
+            Ok(msg) => {
+                log::debug!("DHCPv6 {}", msg.msg_type.as_str());
+                // Server response
+                let done = matches!(
+                    msg.msg_type,
+                    Dhcpv6MessageType::Advertise | Dhcpv6MessageType::Reply
+                );
+                let transaction_id = msg.transaction_id;
+                for (session_id, dhcpv6) in self.sessions.iter_mut() {
+                    if transaction_id == dhcpv6.transaction_id {
+                        dhcpv6.messages.push(msg);
+                        if done {
+                            return ParseResult::Done(*session_id);
+                        }
+                        return ParseResult::Continue(*session_id);
+                    }
+                }
+                let dhcpv6 = Dhcpv6 {
+                    transaction_id,
+                    messages: vec![msg],
+                };
+                let session_id = self.cnt;
+                self.cnt += 1;
+                self.sessions.insert(session_id, dhcpv6);
+                if done {
+                    return ParseResult::Done(session_id);
+                }
+                ParseResult::Continue(session_id)
+            }
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    use crate::protocols::stream::testing::{udp, Flow};
+
+    /// SOLICIT with a DUID-LLT client identifier, option request (DNS servers and domain list),
+    /// elapsed time, an empty IA_NA, and a Client FQDN.
+    const SOLICIT: &[u8] = b"\x01\x10\x08\x74\
+        \x00\x01\x00\x0e\x00\x01\x00\x01\x2c\x3b\x1a\x2f\x52\x54\x00\x12\x34\x56\
+        \x00\x06\x00\x04\x00\x17\x00\x18\
+        \x00\x08\x00\x02\x00\x00\
+        \x00\x03\x00\x0c\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x27\x00\x09\x00\x06laptop\x00";
+
+    /// ADVERTISE offering `2001:db8::1:100` in the IA_NA.
+    const ADVERTISE: &[u8] = b"\x02\x10\x08\x74\
+        \x00\x01\x00\x0e\x00\x01\x00\x01\x2c\x3b\x1a\x2f\x52\x54\x00\x12\x34\x56\
+        \x00\x02\x00\x0a\x00\x03\x00\x01\x52\x54\x00\xab\xcd\xef\
+        \x00\x03\x00\x28\x00\x00\x00\x01\x00\x00\x0e\x10\x00\x00\x15\x18\
+        \x00\x05\x00\x18\x20\x01\x0d\xb8\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x01\x00\
+        \x00\x00\x1c\x20\x00\x00\x1d\x4c";
+
+    const FLOW: Flow = Flow::udp(547);
+
+    /// Returns `msg` encapsulated in a Relay-forward message.
+    fn relay_forward(msg: &[u8]) -> Vec<u8> {
+        let mut relay = vec![12, 0];
+        relay.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        relay.resize(18, 0);
+        relay.extend_from_slice(&[0xfe, 0x80]);
+        relay.resize(33, 0);
+        relay.push(0x01);
+        relay.extend_from_slice(&[0x00, 0x09]);
+        relay.extend_from_slice(&(msg.len() as u16).to_be_bytes());
+        relay.extend_from_slice(msg);
+        relay
+    }
+
+    #[test]
+    fn core_dhcpv6_probe() {
+        let parser = Dhcpv6Parser::default();
+        assert!(matches!(
+            FLOW.probe(&parser, SOLICIT, true),
+            ProbeResult::Certain
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, &relay_forward(SOLICIT), true),
+            ProbeResult::Certain
+        ));
+        // Not a DHCPv6 port
+        assert!(matches!(
+            parser.probe(&udp(SOLICIT, 53, true)),
+            ProbeResult::NotForUs
+        ));
+        // Unknown message type
+        let mut unknown = SOLICIT.to_vec();
+        unknown[0] = 0x7f;
+        assert!(matches!(
+            FLOW.probe(&parser, &unknown, true),
+            ProbeResult::NotForUs
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, &SOLICIT[..3], true),
+            ProbeResult::NotForUs
+        ));
+    }
+
+    #[test]
+    fn core_dhcpv6_transaction() {
+        let mut parser = Dhcpv6Parser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, SOLICIT, true),
+            ParseResult::Continue(0)
+        ));
+        // Another exchange
+        let mut other = SOLICIT.to_vec();
+        other[1..4].copy_from_slice(&[0x9a, 0x51, 0x0c]);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &other, true),
+            ParseResult::Continue(1)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, ADVERTISE, false),
+            ParseResult::Done(0)
+        ));
+
+        let session = parser.remove_session(0).unwrap();
+        match &session.data {
+            SessionData::Dhcpv6(dhcpv6) => {
+                assert_eq!(dhcpv6.transaction_id, 0x100874);
+                assert_eq!(dhcpv6.messages.len(), 2);
+                assert_eq!(dhcpv6.message_type(), "advertise");
+                assert_eq!(dhcpv6.client_duid(), "000100012c3b1a2f525400123456");
+                assert_eq!(dhcpv6.client_mac(), "52:54:00:12:34:56");
+                assert_eq!(dhcpv6.server_duid(), "00030001525400abcdef");
+                assert_eq!(dhcpv6.requested_addr(), Ipv6Addr::UNSPECIFIED);
+                assert_eq!(
+                    dhcpv6.assigned_addr(),
+                    "2001:db8::1:100".parse::<Ipv6Addr>().unwrap()
+                );
+                assert_eq!(dhcpv6.hostname(), "laptop");
+                assert_eq!(dhcpv6.option_request(), "23,24");
+            }
+            _ => panic!("Expected DHCPv6 session"),
+        }
+    }
+
+    #[test]
+    fn core_dhcpv6_relayed() {
+        let mut parser = Dhcpv6Parser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, &relay_forward(SOLICIT), true),
+            ParseResult::Continue(0)
+        ));
+        assert!(parser.sessions[&0].messages[0].relayed);
+    }
+
+    #[test]
+    fn core_dhcpv6_truncated() {
+        let mut parser = Dhcpv6Parser::default();
+        // Client FQDN cut short
+        assert!(matches!(
+            FLOW.parse(&mut parser, &SOLICIT[..SOLICIT.len() - 4], true),
+            ParseResult::Skipped
+        ));
+        // IA Address cut short
+        assert!(matches!(
+            FLOW.parse(&mut parser, &ADVERTISE[..ADVERTISE.len() - 10], false),
+            ParseResult::Skipped
+        ));
+        // Relay message without room for the encapsulated message
+        let relay = relay_forward(SOLICIT);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &relay[..40], true),
+            ParseResult::Skipped
+        ));
+        assert!(parser.drain_sessions().is_empty());
+    }
+}
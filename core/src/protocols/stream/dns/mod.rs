@@ -31,6 +31,11 @@ impl Dns {
         ""
     }
 
+    /// Returns the response code, or `None` if no response was observed in the transaction.
+    pub fn response_code(&self) -> Option<u8> {
+        self.response.as_ref().map(|resp| resp.response_code.into())
+    }
+
     /// Returns a string representation of the answers
     pub fn answers(&self) -> String {
         if let Some(resp) = &self.response {
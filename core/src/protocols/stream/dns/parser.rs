@@ -11,6 +11,8 @@
 use super::transaction::{DnsQuery, DnsResponse};
 use super::Dns;
 use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::llmnr::parser::LLMNR_PORT;
+use crate::protocols::stream::mdns::parser::MDNS_PORT;
 use crate::protocols::stream::{
     ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
 };
@@ -48,6 +50,11 @@ impl ConnParsable for DnsParser {
             // NetBIOS NBSS looks like DNS, but parser will fail on labels
             return ProbeResult::NotForUs;
         }
+        if matches!(src_port, MDNS_PORT | LLMNR_PORT) || matches!(dst_port, MDNS_PORT | LLMNR_PORT)
+        {
+            // DNS wire format with multicast semantics, handled by the mDNS and LLMNR parsers
+            return ProbeResult::NotForUs;
+        }
         let offset = pdu.offset();
         let length = pdu.length();
         if pdu.length() == 0 {
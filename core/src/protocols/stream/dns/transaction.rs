@@ -14,7 +14,7 @@ pub struct DnsQuery {
 }
 
 impl DnsQuery {
-    pub(super) fn parse_query(pkt: &Packet) -> Self {
+    pub(crate) fn parse_query(pkt: &Packet) -> Self {
         let mut queries = Vec::new();
         for q in &pkt.questions {
             log::debug!("  query: {}/{:?}", q.qname, q.qtype);
@@ -43,7 +43,7 @@ pub struct DnsResponse {
 }
 
 impl DnsResponse {
-    pub(super) fn parse_response(pkt: &Packet) -> Self {
+    pub(crate) fn parse_response(pkt: &Packet) -> Self {
         let mut answers = Vec::new();
         for answer in &pkt.answers {
             log::debug!("  answer: {}/{:?}", answer.name, answer.data);
@@ -22,6 +22,8 @@ mod transaction;
 
 pub use self::transaction::{HttpRequest, HttpResponse};
 
+use super::tls::ja4_hash;
+use itertools::Itertools;
 use serde::Serialize;
 
 /// Parsed HTTP transaction contents.
@@ -113,5 +115,152 @@ impl Http {
         self.response.transfer_encoding.as_deref().unwrap_or("")
     }
 
+    /// Returns the JA4H fingerprint of the request, or `""` if no request method was observed.
+    ///
+    /// ## Remarks
+    /// The JA4H fingerprint is defined as `a_b_c_d`, where `a` summarizes the method, version,
+    /// presence of cookies and referer, header count, and first accepted language, `b` is the
+    /// truncated SHA-256 hash of the header names in the order they were sent (excluding `Cookie`
+    /// and `Referer`), and `c` and `d` are the truncated SHA-256 hashes of the sorted cookie names
+    /// and cookie name-value pairs, respectively. See
+    /// [FoxIO-LLC/ja4](https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4H.md) for
+    /// more details.
+    pub fn ja4h(&self) -> String {
+        self.ja4h_fingerprint(false)
+    }
+
+    /// Returns the JA4H fingerprint with unhashed headers and cookies (JA4H_r), or `""` if no
+    /// request method was observed.
+    pub fn ja4h_r(&self) -> String {
+        self.ja4h_fingerprint(true)
+    }
+
+    fn ja4h_fingerprint(&self, raw: bool) -> String {
+        let method = match &self.request.method {
+            Some(method) => method.to_lowercase(),
+            None => return "".to_string(),
+        };
+        let version = match self.request.version.as_deref() {
+            Some("HTTP/1.0") => "10",
+            Some("HTTP/1.1") => "11",
+            _ => "00",
+        };
+        let headers = self
+            .request
+            .headers
+            .iter()
+            .filter(|h| !h.eq_ignore_ascii_case("cookie") && !h.eq_ignore_ascii_case("referer"))
+            .collect::<Vec<_>>();
+        let language = self
+            .request
+            .accept_language
+            .as_deref()
+            .unwrap_or("")
+            .split([',', ';'])
+            .next()
+            .unwrap_or("")
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .chain(std::iter::repeat('0'))
+            .take(4)
+            .collect::<String>();
+
+        let a = format!(
+            "{}{}{}{}{:02}{}",
+            method
+                .chars()
+                .chain(std::iter::repeat('0'))
+                .take(2)
+                .collect::<String>(),
+            version,
+            if self.request.cookie.is_some() {
+                'c'
+            } else {
+                'n'
+            },
+            if self.request.referer.is_some() {
+                'r'
+            } else {
+                'n'
+            },
+            headers.len().min(99),
+            language,
+        );
+        let b = headers.iter().join(",");
+        let cookies = self
+            .request
+            .cookie
+            .as_deref()
+            .unwrap_or("")
+            .split(';')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .sorted()
+            .collect::<Vec<_>>();
+        let c = cookies
+            .iter()
+            .map(|c| c.split('=').next().unwrap_or(c))
+            .sorted()
+            .join(",");
+        let d = cookies.iter().join(",");
+        match raw {
+            true => format!("{}_{}_{}_{}", a, b, c, d),
+            false => format!("{}_{}_{}_{}", a, ja4_hash(&b), ja4_hash(&c), ja4_hash(&d)),
+        }
+    }
+
     // TODO: more methods...
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Browser request with a referer and three cookies.
+    const GET: &[u8] = b"GET /index.html HTTP/1.1\r\n\
+        Host: www.example.com\r\n\
+        User-Agent: Mozilla/5.0\r\n\
+        Accept: text/html\r\n\
+        Accept-Language: en-US,en;q=0.9\r\n\
+        Accept-Encoding: gzip, deflate\r\n\
+        Referer: https://www.example.com/\r\n\
+        Cookie: session=abc123; _ga=GA1.2.1; lang=en\r\n\r\n";
+    /// HTTP/1.0 request without cookies, referer, or accepted languages.
+    const POST: &[u8] = b"POST /api HTTP/1.0\r\n\
+        Host: api.example.com\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: 2\r\n\r\n{}";
+
+    fn http(request: &[u8]) -> Http {
+        Http {
+            request: HttpRequest::parse_from(request).unwrap(),
+            response: HttpResponse::default(),
+            trans_depth: 0,
+        }
+    }
+
+    #[test]
+    fn core_http_ja4h() {
+        let get = http(GET);
+        assert_eq!(
+            get.ja4h(),
+            "ge11cr05enus_f3bb7aa45ec4_47a6a5b4285c_fa4d84ceaef0"
+        );
+        assert_eq!(
+            get.ja4h_r(),
+            "ge11cr05enus_Host,User-Agent,Accept,Accept-Language,Accept-Encoding_\
+             _ga,lang,session__ga=GA1.2.1,lang=en,session=abc123"
+        );
+
+        let post = http(POST);
+        assert_eq!(
+            post.ja4h(),
+            "po10nn030000_51b2f3543123_000000000000_000000000000"
+        );
+        assert_eq!(
+            post.ja4h_r(),
+            "po10nn030000_Host,Content-Type,Content-Length__"
+        );
+    }
+}
@@ -20,6 +20,10 @@ pub struct HttpRequest {
     pub content_length: Option<usize>,
     pub content_type: Option<String>,
     pub transfer_encoding: Option<String>,
+    pub referer: Option<String>,
+    pub accept_language: Option<String>,
+    /// Header names in the order they were sent.
+    pub headers: Vec<String>,
     // /// `false` if request body needs continuation pub is_complete: bool, /// Actual length in
     // bytes of body data transferred from the client. pub body_len: usize,
 }
@@ -45,7 +49,11 @@ impl HttpRequest {
         if let Some(version) = req.version {
             request.version = Some(format!("HTTP/1.{}", version));
         }
-        for hdr in &headers {
+        for hdr in req.headers.iter() {
+            if hdr.name.is_empty() {
+                continue;
+            }
+            request.headers.push(hdr.name.to_owned());
             let name = hdr.name.to_lowercase();
             match name.as_ref() {
                 "user-agent" => {
@@ -60,6 +68,14 @@ impl HttpRequest {
                     let s = String::from_utf8_lossy(hdr.value);
                     request.host = Some(s.to_string());
                 }
+                "referer" => {
+                    let s = String::from_utf8_lossy(hdr.value).into_owned();
+                    request.referer = Some(s);
+                }
+                "accept-language" => {
+                    let s = String::from_utf8_lossy(hdr.value).into_owned();
+                    request.accept_language = Some(s);
+                }
                 "content-length" => {
                     if let Ok(s) = std::str::from_utf8(hdr.value) {
                         if let Ok(length) = str::parse::<usize>(s) {
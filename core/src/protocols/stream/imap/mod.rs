@@ -0,0 +1,92 @@
+//! IMAP session parsing.
+//!
+//! ## Remarks
+//! The parser follows the not-authenticated state of an IMAP session: the server greeting,
+//! advertised capabilities, and the tagged commands and completion responses that precede
+//! authentication. The session headers are considered done once the client authenticates, logs
+//! out, or the server accepts STARTTLS. In the latter case, the remainder of the stream is handed
+//! to the TLS parser and the TLS handshake is parsed as a second session in the same connection.
+//! Mailbox contents are not parsed.
+
+pub mod parser;
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Parsed IMAP session contents.
+#[derive(Debug, Default, Serialize)]
+pub struct Imap {
+    /// Server greeting (text following `* OK` or `* PREAUTH`).
+    pub greeting: Option<String>,
+    /// `true` if the server greeted with PREAUTH (connection is already authenticated).
+    pub preauth: bool,
+    /// Capabilities advertised by the server (e.g., `IMAP4rev1`, `STARTTLS`).
+    pub capabilities: Vec<String>,
+    /// SASL mechanism requested by the client in an AUTHENTICATE command, or `LOGIN`.
+    pub auth_mechanism: Option<String>,
+    /// `true` if the server accepted the client's authentication.
+    pub authenticated: bool,
+    /// `true` if the server accepted STARTTLS.
+    pub starttls: bool,
+    /// Commands sent by the client, in order.
+    pub commands: Vec<ImapCommand>,
+    /// Tagged completion responses sent by the server, in order.
+    pub responses: Vec<ImapResponse>,
+
+    /// Commands awaiting a completion response, keyed by tag.
+    #[serde(skip_serializing)]
+    pub(crate) pending: HashMap<String, String>,
+    /// `true` while a SASL exchange is in progress (client lines are not commands).
+    #[serde(skip_serializing)]
+    pub(crate) in_auth: bool,
+}
+
+/// A tagged IMAP command.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ImapCommand {
+    /// Command tag chosen by the client.
+    pub tag: String,
+    /// Command name, uppercased (e.g., `LOGIN`).
+    pub command: String,
+    /// Command arguments. Passwords and SASL initial responses are not recorded.
+    pub args: String,
+}
+
+/// A tagged IMAP completion response.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ImapResponse {
+    /// Tag of the command being completed.
+    pub tag: String,
+    /// Command the response completes, if it was observed.
+    pub command: Option<String>,
+    /// Completion status (`OK`, `NO`, or `BAD`).
+    pub status: String,
+    /// Human-readable response text, including any response code.
+    pub text: String,
+}
+
+impl Imap {
+    /// Returns the server greeting, or `""` if it was not observed.
+    pub fn greeting(&self) -> &str {
+        self.greeting.as_deref().unwrap_or("")
+    }
+
+    /// Returns the capabilities advertised by the server, separated by spaces.
+    pub fn capabilities(&self) -> String {
+        self.capabilities.join(" ")
+    }
+
+    /// Returns the authentication mechanism used by the client, or `""` if the client did not
+    /// authenticate.
+    pub fn auth_mechanism(&self) -> &str {
+        self.auth_mechanism.as_deref().unwrap_or("")
+    }
+
+    /// Returns the completion status of the most recent tagged response, or `""` if none was
+    /// observed.
+    pub fn status(&self) -> &str {
+        self.responses
+            .last()
+            .map_or("", |resp| resp.status.as_str())
+    }
+}
@@ -0,0 +1,360 @@
+//! IMAP session parser.
+//!
+//! Commands and responses are reassembled into lines. Tagged completion responses are matched to
+//! commands by tag, and capabilities are collected from both untagged `CAPABILITY` responses and
+//! `[CAPABILITY ...]` response codes. Literals are not reassembled.
+
+use super::{Imap, ImapCommand, ImapResponse};
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::line::{split_verb, LineBuffer};
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+/// Maximum number of client commands parsed before the session is considered done.
+const MAX_COMMANDS: usize = 64;
+
+/// Parses a single IMAP session per connection.
+#[derive(Debug)]
+pub struct ImapParser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<Imap>,
+    client_lines: LineBuffer,
+    server_lines: LineBuffer,
+    /// `true` if the server accepted STARTTLS and the stream continues as TLS.
+    starttls: bool,
+}
+
+impl Default for ImapParser {
+    fn default() -> Self {
+        ImapParser {
+            sessions: vec![Imap::default()],
+            client_lines: LineBuffer::default(),
+            server_lines: LineBuffer::default(),
+            starttls: false,
+        }
+    }
+}
+
+impl ImapParser {
+    /// Returns `true` if the remainder of the stream should be parsed as TLS.
+    pub(crate) fn starttls(&self) -> bool {
+        self.starttls
+    }
+
+    /// Process data segments from client to server
+    fn process_ctos(&mut self, data: &[u8]) -> ParseResult {
+        let imap = &mut self.sessions[0];
+        let lines = self.client_lines.lines(data);
+        if lines.is_empty() {
+            return ParseResult::Skipped;
+        }
+        for line in lines {
+            if imap.in_auth {
+                // SASL response (credentials), not a command
+                continue;
+            }
+            let (tag, rest) = split_verb(&line);
+            let (command, args) = split_verb(rest);
+            let command = command.to_ascii_uppercase();
+            let args = match command.as_str() {
+                "LOGIN" => {
+                    imap.auth_mechanism = Some(command.clone());
+                    // Record the user name only
+                    split_verb(args).0.to_string()
+                }
+                "AUTHENTICATE" => {
+                    let mechanism = split_verb(args).0.to_ascii_uppercase();
+                    imap.auth_mechanism = Some(mechanism.clone());
+                    imap.in_auth = true;
+                    mechanism
+                }
+                _ => args.to_string(),
+            };
+            imap.pending.insert(tag.to_string(), command.clone());
+            imap.commands.push(ImapCommand {
+                tag: tag.to_string(),
+                command,
+                args,
+            });
+        }
+        if imap.commands.len() >= MAX_COMMANDS {
+            return ParseResult::HeadersDone(0);
+        }
+        ParseResult::Continue(0)
+    }
+
+    /// Process data segments from server to client
+    fn process_stoc(&mut self, data: &[u8]) -> ParseResult {
+        let imap = &mut self.sessions[0];
+        let lines = self.server_lines.lines(data);
+        if lines.is_empty() {
+            return ParseResult::Skipped;
+        }
+        for line in lines {
+            let (tag, rest) = split_verb(&line);
+            let (status, text) = split_verb(rest);
+            let status = status.to_ascii_uppercase();
+            if let Some(capabilities) = capability_code(text) {
+                imap.capabilities = capabilities;
+            }
+            match tag {
+                "*" => {
+                    match status.as_str() {
+                        "OK" | "PREAUTH" if imap.greeting.is_none() => {
+                            imap.greeting = Some(text.to_string());
+                            if status == "PREAUTH" {
+                                imap.preauth = true;
+                                return ParseResult::HeadersDone(0);
+                            }
+                        }
+                        "CAPABILITY" => {
+                            imap.capabilities = text.split(' ').map(|c| c.to_string()).collect();
+                        }
+                        "BYE" if imap.greeting.is_some() => {
+                            return ParseResult::HeadersDone(0);
+                        }
+                        _ => (),
+                    }
+                    continue;
+                }
+                "+" => {
+                    // Continuation request
+                    continue;
+                }
+                _ => (),
+            }
+            let command = imap.pending.remove(tag);
+            let ok = status == "OK";
+            imap.responses.push(ImapResponse {
+                tag: tag.to_string(),
+                command: command.clone(),
+                status,
+                text: text.to_string(),
+            });
+            match command.as_deref() {
+                Some("STARTTLS") if ok => {
+                    imap.starttls = true;
+                    self.starttls = true;
+                    return ParseResult::HeadersDone(0);
+                }
+                Some("LOGIN") | Some("AUTHENTICATE") => {
+                    imap.in_auth = false;
+                    if ok {
+                        imap.authenticated = true;
+                        return ParseResult::HeadersDone(0);
+                    }
+                }
+                Some("LOGOUT") => {
+                    return ParseResult::HeadersDone(0);
+                }
+                _ => (),
+            }
+        }
+        ParseResult::Continue(0)
+    }
+}
+
+impl ConnParsable for ImapParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 || self.sessions.is_empty() {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.dir {
+                self.process_ctos(data)
+            } else {
+                self.process_stoc(data)
+            }
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() < 4 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            let line = String::from_utf8_lossy(data).to_ascii_uppercase();
+            if pdu.dir {
+                // Capture may have started after the server greeting
+                let (_tag, rest) = split_verb(&line);
+                match split_verb(rest).0 {
+                    "CAPABILITY" | "STARTTLS" | "LOGIN" | "AUTHENTICATE" | "ID" => {
+                        ProbeResult::Certain
+                    }
+                    _ => ProbeResult::NotForUs,
+                }
+            } else if line.starts_with("* OK") || line.starts_with("* PREAUTH") {
+                if line.contains("IMAP") {
+                    ProbeResult::Certain
+                } else {
+                    ProbeResult::Unsure
+                }
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|imap| Session {
+            data: SessionData::Imap(Box::new(imap)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|imap| Session {
+                data: SessionData::Imap(Box::new(imap)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        if self.starttls {
+            // TLS session expected next
+            ParsingState::Probing
+        } else {
+            ParsingState::Stop
+        }
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+/// Returns the capabilities in a `[CAPABILITY ...]` response code at the start of `text`, if
+/// present.
+fn capability_code(text: &str) -> Option<Vec<String>> {
+    let code = text.strip_prefix('[')?.split(']').next()?;
+    let (name, capabilities) = split_verb(code);
+    if !name.eq_ignore_ascii_case("CAPABILITY") {
+        return None;
+    }
+    Some(capabilities.split(' ').map(|c| c.to_string()).collect())
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    const GREETING: &[u8] = b"* OK [CAPABILITY IMAP4rev1 SASL-IR LOGIN-REFERRALS ID ENABLE IDLE \
+        LITERAL+ STARTTLS AUTH=PLAIN] Dovecot (Debian) ready.\r\n";
+
+    const FLOW: Flow = Flow::tcp(143);
+
+    #[test]
+    fn core_imap_probe() {
+        let parser = ImapParser::default();
+        assert_eq!(FLOW.probe(&parser, GREETING, false), ProbeResult::Certain);
+        assert_eq!(
+            FLOW.probe(&parser, b"a001 CAPABILITY\r\n", true),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"* OK ready\r\n", false),
+            ProbeResult::Unsure
+        );
+        assert_eq!(FLOW.probe(&parser, b"* O", false), ProbeResult::Unsure);
+        assert_eq!(
+            FLOW.probe(&parser, b"a001 SELECT INBOX\r\n", true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"+OK POP3 ready\r\n", false),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_imap_parse_split() {
+        let mut parser = ImapParser::default();
+        FLOW.parse(&mut parser, &GREETING[..50], false);
+        FLOW.parse(&mut parser, &GREETING[50..], false);
+        FLOW.parse(&mut parser, b"a001 LOGIN alice \"secret\"\r\na0", true);
+        FLOW.parse(&mut parser, b"02 CAPABILITY\r\n", true);
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                b"a001 NO [AUTHENTICATIONFAILED] Authentication failed.\r\n",
+                false
+            ),
+            ParseResult::Continue(0)
+        );
+        FLOW.parse(&mut parser, b"* CAPABILITY IMAP4rev1 LITERAL+ ", false);
+        FLOW.parse(
+            &mut parser,
+            b"IDLE\r\na002 OK Capability completed.\r\n",
+            false,
+        );
+        FLOW.parse(&mut parser, b"a003 LOGIN alice \"s3cret\"\r\n", true);
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                b"a003 OK [CAPABILITY IMAP4rev1 IDLE MOVE] Logged in\r\n",
+                false
+            ),
+            ParseResult::HeadersDone(0)
+        );
+
+        let imap = &parser.sessions[0];
+        assert_eq!(imap.greeting(), "[CAPABILITY IMAP4rev1 SASL-IR LOGIN-REFERRALS ID ENABLE IDLE LITERAL+ STARTTLS AUTH=PLAIN] Dovecot (Debian) ready.");
+        assert_eq!(imap.capabilities(), "IMAP4rev1 IDLE MOVE");
+        assert_eq!(imap.auth_mechanism(), "LOGIN");
+        assert!(imap.authenticated);
+        assert_eq!(imap.commands[0].args, "alice");
+        assert_eq!(imap.responses[0].status, "NO");
+        assert_eq!(imap.responses[1].command.as_deref(), Some("CAPABILITY"));
+        assert_eq!(imap.status(), "OK");
+    }
+
+    #[test]
+    fn core_imap_starttls() {
+        let mut parser = ImapParser::default();
+        FLOW.parse(&mut parser, GREETING, false);
+        FLOW.parse(&mut parser, b"a001 STARTTLS\r\n", true);
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                b"a001 OK Begin TLS negotiation now.\r\n",
+                false
+            ),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.starttls());
+    }
+
+    #[test]
+    fn core_imap_truncated() {
+        let mut parser = ImapParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &GREETING[..30], false),
+            ParseResult::Skipped
+        );
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Imap(imap) => {
+                assert_eq!(imap.greeting(), "");
+                assert_eq!(imap.capabilities(), "");
+            }
+            _ => panic!("Expected IMAP session"),
+        }
+    }
+}
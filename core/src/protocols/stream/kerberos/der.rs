@@ -0,0 +1,106 @@
+//! Minimal DER decoding for Kerberos messages.
+//!
+//! Kerberos messages are DER-encoded ASN.1 with explicitly tagged sequence fields. Only the
+//! subset of DER used by the fields of interest is supported: single-byte tags and definite
+//! lengths.
+
+use anyhow::{bail, Result};
+
+pub(super) const TAG_INTEGER: u8 = 0x02;
+pub(super) const TAG_BIT_STRING: u8 = 0x03;
+pub(super) const TAG_SEQUENCE: u8 = 0x30;
+
+/// Returns the tag, header length, and value length of the DER element at the start of `data`,
+/// without requiring the value to be present.
+pub(super) fn read_header(data: &[u8]) -> Result<(u8, usize, usize)> {
+    if data.len() < 2 {
+        bail!("truncated DER header");
+    }
+    let tag = data[0];
+    if tag & 0x1f == 0x1f {
+        bail!("unsupported multi-byte tag");
+    }
+    match data[1] {
+        len if len < 0x80 => Ok((tag, 2, len as usize)),
+        0x80 => bail!("unsupported indefinite length"),
+        len => {
+            let n = (len & 0x7f) as usize;
+            if n > 4 || data.len() < 2 + n {
+                bail!("invalid DER length");
+            }
+            let len = data[2..2 + n]
+                .iter()
+                .fold(0, |len, b| (len << 8) | *b as usize);
+            Ok((tag, 2 + n, len))
+        }
+    }
+}
+
+/// Reads the DER element at the start of `data`, returning its tag, value, and the remaining
+/// data.
+pub(super) fn read_tlv(data: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+    let (tag, hdr_len, len) = read_header(data)?;
+    if data.len() < hdr_len + len {
+        bail!("truncated DER element");
+    }
+    Ok((tag, &data[hdr_len..hdr_len + len], &data[hdr_len + len..]))
+}
+
+/// Reads the DER element at the start of `data`, which must have tag `tag`, and returns its
+/// value.
+pub(super) fn read_expected(data: &[u8], tag: u8) -> Result<&[u8]> {
+    match read_tlv(data)? {
+        (t, value, _) if t == tag => Ok(value),
+        (t, _, _) => bail!("unexpected tag {:#04x}, expected {:#04x}", t, tag),
+    }
+}
+
+/// Returns an iterator over the elements of a constructed value as (tag, value) pairs. Iteration
+/// stops at the first malformed element.
+pub(super) fn elements(data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || match read_tlv(rest) {
+        Ok((tag, value, remaining)) => {
+            rest = remaining;
+            Some((tag, value))
+        }
+        Err(_) => None,
+    })
+}
+
+/// Returns an iterator over the explicitly tagged fields (`[n]`) of a SEQUENCE value as
+/// (n, inner element) pairs.
+pub(super) fn fields(data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    elements(data)
+        .filter(|(tag, _)| tag & 0xe0 == 0xa0)
+        .map(|(tag, value)| (tag & 0x1f, value))
+}
+
+/// Reads an INTEGER of up to 8 bytes.
+pub(super) fn read_int(data: &[u8]) -> Result<i64> {
+    let value = read_expected(data, TAG_INTEGER)?;
+    if value.is_empty() || value.len() > 8 {
+        bail!("invalid INTEGER length {}", value.len());
+    }
+    let init = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(value.iter().fold(init, |n: i64, b| (n << 8) | *b as i64))
+}
+
+/// Reads a string type (e.g., GeneralString or KerberosString).
+pub(super) fn read_string(data: &[u8]) -> Result<String> {
+    let (_, value, _) = read_tlv(data)?;
+    Ok(String::from_utf8_lossy(value).into_owned())
+}
+
+/// Reads a BIT STRING of up to 32 bits as an integer, with the first bit as the most significant.
+pub(super) fn read_bits(data: &[u8]) -> Result<u32> {
+    let value = read_expected(data, TAG_BIT_STRING)?;
+    if value.is_empty() {
+        bail!("empty BIT STRING");
+    }
+    Ok(value[1..]
+        .iter()
+        .take(4)
+        .enumerate()
+        .fold(0, |bits, (i, b)| bits | ((*b as u32) << (24 - 8 * i))))
+}
@@ -0,0 +1,287 @@
+//! Kerberos message components.
+//!
+//! See [RFC 4120](https://datatracker.ietf.org/doc/html/rfc4120#section-5) for the ASN.1 message
+//! definitions. Encrypted parts of messages are not decrypted.
+
+use super::der::*;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt;
+
+pub const KRB_AS_REQ: u8 = 10;
+pub const KRB_AS_REP: u8 = 11;
+pub const KRB_TGS_REQ: u8 = 12;
+pub const KRB_TGS_REP: u8 = 13;
+pub const KRB_ERROR: u8 = 30;
+
+/// Encrypted timestamp pre-authentication data type.
+pub const PA_ENC_TIMESTAMP: i32 = 2;
+
+/// Returns the name of a Kerberos message type (e.g., `"as-req"`).
+pub fn msg_type_name(msg_type: u8) -> &'static str {
+    match msg_type {
+        KRB_AS_REQ => "as-req",
+        KRB_AS_REP => "as-rep",
+        KRB_TGS_REQ => "tgs-req",
+        KRB_TGS_REP => "tgs-rep",
+        KRB_ERROR => "krb-error",
+        _ => "unknown",
+    }
+}
+
+/// Returns the name of a Kerberos error code (e.g., `"KDC_ERR_PREAUTH_REQUIRED"`).
+pub fn error_name(error_code: i32) -> &'static str {
+    match error_code {
+        0 => "KDC_ERR_NONE",
+        6 => "KDC_ERR_C_PRINCIPAL_UNKNOWN",
+        7 => "KDC_ERR_S_PRINCIPAL_UNKNOWN",
+        12 => "KDC_ERR_POLICY",
+        14 => "KDC_ERR_ETYPE_NOSUPP",
+        18 => "KDC_ERR_CLIENT_REVOKED",
+        23 => "KDC_ERR_KEY_EXPIRED",
+        24 => "KDC_ERR_PREAUTH_FAILED",
+        25 => "KDC_ERR_PREAUTH_REQUIRED",
+        31 => "KRB_AP_ERR_BAD_INTEGRITY",
+        32 => "KRB_AP_ERR_TKT_EXPIRED",
+        37 => "KRB_AP_ERR_SKEW",
+        41 => "KRB_AP_ERR_MODIFIED",
+        52 => "KRB_ERR_RESPONSE_TOO_BIG",
+        60 => "KRB_ERR_GENERIC",
+        68 => "KDC_ERR_WRONG_REALM",
+        _ => "unknown",
+    }
+}
+
+/// A Kerberos principal name.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct KrbPrincipal {
+    pub name_type: i32,
+    pub name: Vec<String>,
+}
+
+impl KrbPrincipal {
+    fn parse_from(data: &[u8]) -> Result<Self> {
+        let mut principal = KrbPrincipal::default();
+        for (field, value) in fields(read_expected(data, TAG_SEQUENCE)?) {
+            match field {
+                0 => principal.name_type = read_int(value)? as i32,
+                1 => {
+                    principal.name = elements(read_expected(value, TAG_SEQUENCE)?)
+                        .map(|(_, s)| String::from_utf8_lossy(s).into_owned())
+                        .collect()
+                }
+                _ => (),
+            }
+        }
+        Ok(principal)
+    }
+}
+
+impl fmt::Display for KrbPrincipal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name.join("/"))
+    }
+}
+
+/// An AS-REQ or TGS-REQ message.
+#[derive(Clone, Debug, Serialize)]
+pub struct KdcRequest {
+    pub msg_type: u8,
+    /// Types of the pre-authentication data.
+    pub padata_types: Vec<i32>,
+    pub kdc_options: u32,
+    /// Client principal (AS-REQ only).
+    pub cname: Option<KrbPrincipal>,
+    pub realm: String,
+    /// Service principal.
+    pub sname: Option<KrbPrincipal>,
+    pub nonce: u32,
+    /// Encryption types supported by the client, in order of preference.
+    pub etypes: Vec<i32>,
+}
+
+/// An AS-REP or TGS-REP message.
+#[derive(Clone, Debug, Serialize)]
+pub struct KdcReply {
+    pub msg_type: u8,
+    pub crealm: String,
+    pub cname: KrbPrincipal,
+    /// Realm of the issued ticket.
+    pub ticket_realm: String,
+    /// Service principal of the issued ticket.
+    pub sname: KrbPrincipal,
+    /// Encryption type of the issued ticket.
+    pub ticket_etype: i32,
+    /// Encryption type of the reply's encrypted part.
+    pub etype: i32,
+}
+
+/// A KRB-ERROR message.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct KrbError {
+    pub error_code: i32,
+    pub crealm: Option<String>,
+    pub cname: Option<KrbPrincipal>,
+    pub realm: String,
+    pub sname: KrbPrincipal,
+    pub e_text: Option<String>,
+}
+
+/// A Kerberos KDC message.
+#[derive(Clone, Debug, Serialize)]
+pub enum KerberosMessage {
+    Request(KdcRequest),
+    Reply(KdcReply),
+    Error(KrbError),
+}
+
+impl KerberosMessage {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        let (tag, value, _) = read_tlv(data)?;
+        // [APPLICATION n], constructed
+        if tag & 0xe0 != 0x60 {
+            bail!("not a Kerberos application message");
+        }
+        let msg_type = tag & 0x1f;
+        let seq = read_expected(value, TAG_SEQUENCE)?;
+        match msg_type {
+            KRB_AS_REQ | KRB_TGS_REQ => Ok(KerberosMessage::Request(parse_request(seq, msg_type)?)),
+            KRB_AS_REP | KRB_TGS_REP => Ok(KerberosMessage::Reply(parse_reply(seq, msg_type)?)),
+            KRB_ERROR => Ok(KerberosMessage::Error(parse_error(seq)?)),
+            _ => bail!("unsupported message type {}", msg_type),
+        }
+    }
+
+    /// Returns the message type.
+    pub fn msg_type(&self) -> u8 {
+        match self {
+            KerberosMessage::Request(req) => req.msg_type,
+            KerberosMessage::Reply(rep) => rep.msg_type,
+            KerberosMessage::Error(_) => KRB_ERROR,
+        }
+    }
+}
+
+fn parse_request(seq: &[u8], msg_type: u8) -> Result<KdcRequest> {
+    let mut req = KdcRequest {
+        msg_type,
+        padata_types: vec![],
+        kdc_options: 0,
+        cname: None,
+        realm: String::new(),
+        sname: None,
+        nonce: 0,
+        etypes: vec![],
+    };
+    let mut has_body = false;
+    for (field, value) in fields(seq) {
+        match field {
+            3 => req.padata_types = parse_padata_types(value)?,
+            4 => {
+                has_body = true;
+                for (field, value) in fields(read_expected(value, TAG_SEQUENCE)?) {
+                    match field {
+                        0 => req.kdc_options = read_bits(value)?,
+                        1 => req.cname = Some(KrbPrincipal::parse_from(value)?),
+                        2 => req.realm = read_string(value)?,
+                        3 => req.sname = Some(KrbPrincipal::parse_from(value)?),
+                        7 => req.nonce = read_int(value)? as u32,
+                        8 => {
+                            req.etypes = elements(read_expected(value, TAG_SEQUENCE)?)
+                                .filter_map(|(_, n)| int_value(n))
+                                .collect()
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    if !has_body {
+        bail!("missing KDC-REQ-BODY");
+    }
+    Ok(req)
+}
+
+fn parse_reply(seq: &[u8], msg_type: u8) -> Result<KdcReply> {
+    let mut rep = KdcReply {
+        msg_type,
+        crealm: String::new(),
+        cname: KrbPrincipal::default(),
+        ticket_realm: String::new(),
+        sname: KrbPrincipal::default(),
+        ticket_etype: 0,
+        etype: 0,
+    };
+    for (field, value) in fields(seq) {
+        match field {
+            3 => rep.crealm = read_string(value)?,
+            4 => rep.cname = KrbPrincipal::parse_from(value)?,
+            5 => {
+                // Ticket ::= [APPLICATION 1] SEQUENCE
+                let (_, ticket, _) = read_tlv(value)?;
+                for (field, value) in fields(read_expected(ticket, TAG_SEQUENCE)?) {
+                    match field {
+                        1 => rep.ticket_realm = read_string(value)?,
+                        2 => rep.sname = KrbPrincipal::parse_from(value)?,
+                        3 => rep.ticket_etype = parse_etype(value)?,
+                        _ => (),
+                    }
+                }
+            }
+            6 => rep.etype = parse_etype(value)?,
+            _ => (),
+        }
+    }
+    Ok(rep)
+}
+
+fn parse_error(seq: &[u8]) -> Result<KrbError> {
+    let mut err = KrbError::default();
+    for (field, value) in fields(seq) {
+        match field {
+            6 => err.error_code = read_int(value)? as i32,
+            7 => err.crealm = Some(read_string(value)?),
+            8 => err.cname = Some(KrbPrincipal::parse_from(value)?),
+            9 => err.realm = read_string(value)?,
+            10 => err.sname = KrbPrincipal::parse_from(value)?,
+            11 => err.e_text = Some(read_string(value)?),
+            _ => (),
+        }
+    }
+    Ok(err)
+}
+
+/// Returns the types of a SEQUENCE OF PA-DATA.
+fn parse_padata_types(data: &[u8]) -> Result<Vec<i32>> {
+    let mut types = vec![];
+    for (_, padata) in elements(read_expected(data, TAG_SEQUENCE)?) {
+        for (field, value) in fields(padata) {
+            if field == 1 {
+                types.push(read_int(value)? as i32);
+            }
+        }
+    }
+    Ok(types)
+}
+
+/// Returns the encryption type of an EncryptedData.
+fn parse_etype(data: &[u8]) -> Result<i32> {
+    for (field, value) in fields(read_expected(data, TAG_SEQUENCE)?) {
+        if field == 0 {
+            return Ok(read_int(value)? as i32);
+        }
+    }
+    bail!("missing etype")
+}
+
+/// Decodes the value of an INTEGER element that has already been read.
+fn int_value(value: &[u8]) -> Option<i32> {
+    if value.is_empty() || value.len() > 4 {
+        return None;
+    }
+    let init = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    Some(value.iter().fold(init, |n: i32, b| (n << 8) | *b as i32))
+}
@@ -0,0 +1,117 @@
+//! Kerberos transaction parsing.
+//!
+//! ## Remarks
+//! The parser records KDC exchanges: AS and TGS requests, paired with the corresponding reply or
+//! KRB-ERROR. The nonce that links a request to its reply is only present in the encrypted part of
+//! the reply, so responses are paired with the oldest outstanding request of the matching type.
+//! Application exchanges (AP-REQ/AP-REP) are carried inside other protocols and are not parsed.
+//! Encrypted parts are not decrypted; only their encryption types are recorded.
+
+mod der;
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed Kerberos KDC request/reply transaction.
+#[derive(Debug, Default, Serialize)]
+pub struct Kerberos {
+    /// AS-REQ or TGS-REQ.
+    pub request: Option<KdcRequest>,
+    /// AS-REP or TGS-REP.
+    pub reply: Option<KdcReply>,
+    /// KRB-ERROR.
+    pub error: Option<KrbError>,
+}
+
+impl Kerberos {
+    /// Returns the type of the request (e.g., `"as-req"`), or of the response if the request was
+    /// not observed.
+    pub fn msg_type(&self) -> &str {
+        match (&self.request, &self.reply) {
+            (Some(req), _) => msg_type_name(req.msg_type),
+            (None, Some(rep)) => msg_type_name(rep.msg_type),
+            (None, None) => msg_type_name(KRB_ERROR),
+        }
+    }
+
+    /// Returns the realm of the request or, if the request was not observed, of the response.
+    pub fn realm(&self) -> &str {
+        if let Some(req) = &self.request {
+            return &req.realm;
+        }
+        if let Some(rep) = &self.reply {
+            return &rep.crealm;
+        }
+        self.error.as_ref().map_or("", |err| &err.realm)
+    }
+
+    /// Returns the client principal name (e.g., `"alice"`), or `""` if absent.
+    pub fn client_principal(&self) -> String {
+        self.request
+            .as_ref()
+            .and_then(|req| req.cname.as_ref())
+            .or(self.reply.as_ref().map(|rep| &rep.cname))
+            .or(self.error.as_ref().and_then(|err| err.cname.as_ref()))
+            .map_or(String::new(), |p| p.to_string())
+    }
+
+    /// Returns the service principal name (e.g., `"krbtgt/EXAMPLE.COM"`), or `""` if absent.
+    pub fn service_principal(&self) -> String {
+        self.request
+            .as_ref()
+            .and_then(|req| req.sname.as_ref())
+            .or(self.reply.as_ref().map(|rep| &rep.sname))
+            .or(self.error.as_ref().map(|err| &err.sname))
+            .map_or(String::new(), |p| p.to_string())
+    }
+
+    /// Returns a comma-separated list of the encryption types supported by the client.
+    pub fn etypes(&self) -> String {
+        self.request.as_ref().map_or(String::new(), |req| {
+            req.etypes
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+    }
+
+    /// Returns the encryption type of the issued ticket (e.g., 23 for RC4-HMAC), or 0 if no
+    /// reply was observed.
+    pub fn ticket_etype(&self) -> i32 {
+        self.reply.as_ref().map_or(0, |rep| rep.ticket_etype)
+    }
+
+    /// Returns the encryption type of the reply's encrypted part, or 0 if no reply was observed.
+    pub fn reply_etype(&self) -> i32 {
+        self.reply.as_ref().map_or(0, |rep| rep.etype)
+    }
+
+    /// Returns the KRB-ERROR error code, or 0 if no error was observed.
+    pub fn error_code(&self) -> i32 {
+        self.error.as_ref().map_or(0, |err| err.error_code)
+    }
+
+    /// Returns the name of the KRB-ERROR error code (e.g., `"KDC_ERR_PREAUTH_REQUIRED"`), or `""`
+    /// if no error was observed.
+    pub fn error_name(&self) -> &str {
+        self.error
+            .as_ref()
+            .map_or("", |err| error_name(err.error_code))
+    }
+
+    /// Returns `true` if the request carried encrypted timestamp pre-authentication.
+    pub fn preauth(&self) -> bool {
+        self.request
+            .as_ref()
+            .is_some_and(|req| req.padata_types.contains(&PA_ENC_TIMESTAMP))
+    }
+
+    /// Returns the request KDC options flags, or 0 if the request was not observed.
+    pub fn kdc_options(&self) -> u32 {
+        self.request.as_ref().map_or(0, |req| req.kdc_options)
+    }
+}
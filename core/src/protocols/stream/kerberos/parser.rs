@@ -0,0 +1,439 @@
+//! Kerberos transaction parser.
+//!
+//! Parses Kerberos KDC messages over UDP and TCP framing, and maintains state for pairing
+//! requests with replies.
+
+use super::der::{read_header, TAG_SEQUENCE};
+use super::message::*;
+use super::Kerberos;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::packet::tcp::TCP_PROTOCOL;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::HashMap;
+
+/// Upper bound on the size of a buffered Kerberos message over TCP.
+const MAX_BUFFER_LEN: usize = 1 << 16;
+
+#[derive(Default, Debug)]
+pub struct KerberosParser {
+    /// Maps session ID to Kerberos transaction
+    sessions: HashMap<usize, Kerberos>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+}
+
+impl ConnParsable for KerberosParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.ctxt.proto == TCP_PROTOCOL {
+                self.process_tcp(data, pdu.dir)
+            } else {
+                self.process(data)
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            let (msg, msg_len) = if pdu.ctxt.proto == TCP_PROTOCOL {
+                // Record mark: 4-byte length with the high bit reserved
+                if data.len() < 4 || data[0] & 0x80 != 0 {
+                    return ProbeResult::NotForUs;
+                }
+                (&data[4..], BigEndian::read_u32(data) as usize)
+            } else {
+                (data, data.len())
+            };
+            if msg.is_empty()
+                || !matches!(
+                    msg[0] & 0x1f,
+                    KRB_AS_REQ | KRB_AS_REP | KRB_TGS_REQ | KRB_TGS_REP | KRB_ERROR
+                )
+                || msg[0] & 0xe0 != 0x60
+            {
+                return ProbeResult::NotForUs;
+            }
+            // The outer element spans the message and holds a SEQUENCE
+            match read_header(msg) {
+                Ok((_, hdr_len, len))
+                    if hdr_len + len == msg_len && msg.get(hdr_len) == Some(&TAG_SEQUENCE) =>
+                {
+                    ProbeResult::Certain
+                }
+                _ => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|kerberos| Session {
+            data: SessionData::Kerberos(Box::new(kerberos)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, kerberos)| Session {
+                data: SessionData::Kerberos(Box::new(kerberos)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider Kerberos to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl KerberosParser {
+    /// Reassembles length-prefixed Kerberos messages over TCP.
+    fn process_tcp(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        while cur_data.len() >= 4 {
+            let len = (BigEndian::read_u32(cur_data) & 0x7fff_ffff) as usize;
+            if cur_data.len() < 4 + len {
+                break;
+            }
+            result = self.process(&cur_data[4..4 + len]);
+            if matches!(result, ParseResult::Done(_)) {
+                return result;
+            }
+            cur_data = &cur_data[4 + len..];
+        }
+        if cur_data.len() > MAX_BUFFER_LEN {
+            log::debug!("Kerberos message exceeds maximum buffer size");
+            return ParseResult::Skipped;
+        }
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        buffer.extend_from_slice(cur_data);
+        result
+    }
+
+    pub(crate) fn process(&mut self, data: &[u8]) -> ParseResult {
+        match KerberosMessage::parse_from(data) {
+            Ok(KerberosMessage::Request(req)) => {
+                log::debug!("Kerberos {}", msg_type_name(req.msg_type));
+                let kerberos = Kerberos {
+                    request: Some(req),
+                    ..Default::default()
+                };
+                ParseResult::Continue(self.insert(kerberos))
+            }
+            Ok(msg) => {
+                log::debug!("Kerberos {}", msg_type_name(msg.msg_type()));
+                // Oldest outstanding request that this message answers
+                let session_id = self
+                    .sessions
+                    .iter()
+                    .filter(|(_, kerberos)| {
+                        kerberos.reply.is_none()
+                            && kerberos.error.is_none()
+                            && kerberos.request.as_ref().is_some_and(|req| {
+                                msg.msg_type() == KRB_ERROR || msg.msg_type() == req.msg_type + 1
+                            })
+                    })
+                    .map(|(session_id, _)| *session_id)
+                    .min();
+                let session_id = match session_id {
+                    Some(session_id) => session_id,
+                    None => self.insert(Kerberos::default()),
+                };
+                if let Some(kerberos) = self.sessions.get_mut(&session_id) {
+                    match msg {
+                        KerberosMessage::Reply(rep) => kerberos.reply = Some(rep),
+                        KerberosMessage::Error(err) => kerberos.error = Some(err),
+                        KerberosMessage::Request(_) => (),
+                    }
+                }
+                ParseResult::Done(session_id)
+            }
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+
+    fn insert(&mut self, kerberos: Kerberos) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, kerberos);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{tcp, udp};
+
+    /// AS-REQ for `alice@EXAMPLE.COM` without pre-authentication, supporting AES256, AES128, and RC4.
+    const AS_REQ: &[u8] = b"\
+        \x6a\x81\x9e\x30\x81\x9b\xa1\x03\x02\x01\x05\xa2\x03\x02\x01\x0a\
+        \xa3\x15\x30\x13\x30\x11\xa1\x04\x02\x02\x00\x80\xa2\x09\x04\x07\
+        \x30\x05\xa0\x03\x01\x01\xff\xa4\x78\x30\x76\xa0\x07\x03\x05\x00\
+        \x40\x81\x00\x10\xa1\x12\x30\x10\xa0\x03\x02\x01\x01\xa1\x09\x30\
+        \x07\x1b\x05\x61\x6c\x69\x63\x65\xa2\x0d\x1b\x0b\x45\x58\x41\x4d\
+        \x50\x4c\x45\x2e\x43\x4f\x4d\xa3\x20\x30\x1e\xa0\x03\x02\x01\x02\
+        \xa1\x17\x30\x15\x1b\x06\x6b\x72\x62\x74\x67\x74\x1b\x0b\x45\x58\
+        \x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa5\x11\x18\x0f\x32\x30\x33\
+        \x37\x30\x39\x31\x33\x30\x32\x34\x38\x30\x35\x5a\xa7\x06\x02\x04\
+        \x1f\x2e\x3d\x4c\xa8\x0b\x30\x09\x02\x01\x12\x02\x01\x11\x02\x01\
+        \x17";
+
+    /// KRB-ERROR with KDC_ERR_PREAUTH_REQUIRED.
+    const PREAUTH_REQUIRED: &[u8] = b"\
+        \x7e\x5c\x30\x5a\xa0\x03\x02\x01\x05\xa1\x03\x02\x01\x1e\xa4\x11\
+        \x18\x0f\x32\x30\x32\x36\x31\x30\x31\x38\x31\x30\x31\x35\x30\x30\
+        \x5a\xa5\x05\x02\x03\x06\x6d\xd9\xa6\x03\x02\x01\x19\xa9\x0d\x1b\
+        \x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xaa\x20\x30\x1e\
+        \xa0\x03\x02\x01\x02\xa1\x17\x30\x15\x1b\x06\x6b\x72\x62\x74\x67\
+        \x74\x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d";
+
+    /// AS-REQ for `alice@EXAMPLE.COM` with encrypted timestamp pre-authentication.
+    const AS_REQ_PREAUTH: &[u8] = b"\
+        \x6a\x81\xc4\x30\x81\xc1\xa1\x03\x02\x01\x05\xa2\x03\x02\x01\x0a\
+        \xa3\x3b\x30\x39\x30\x24\xa1\x03\x02\x01\x02\xa2\x1d\x04\x1b\x30\
+        \x19\xa0\x03\x02\x01\x12\xa2\x12\x04\x10\x20\x21\x22\x23\x24\x25\
+        \x26\x27\x28\x29\x2a\x2b\x2c\x2d\x2e\x2f\x30\x11\xa1\x04\x02\x02\
+        \x00\x80\xa2\x09\x04\x07\x30\x05\xa0\x03\x01\x01\xff\xa4\x78\x30\
+        \x76\xa0\x07\x03\x05\x00\x40\x81\x00\x10\xa1\x12\x30\x10\xa0\x03\
+        \x02\x01\x01\xa1\x09\x30\x07\x1b\x05\x61\x6c\x69\x63\x65\xa2\x0d\
+        \x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa3\x20\x30\
+        \x1e\xa0\x03\x02\x01\x02\xa1\x17\x30\x15\x1b\x06\x6b\x72\x62\x74\
+        \x67\x74\x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa5\
+        \x11\x18\x0f\x32\x30\x33\x37\x30\x39\x31\x33\x30\x32\x34\x38\x30\
+        \x35\x5a\xa7\x06\x02\x04\x5a\x6b\x7c\x8d\xa8\x0b\x30\x09\x02\x01\
+        \x12\x02\x01\x11\x02\x01\x17";
+
+    /// AS-REP with an AES256 ticket for `krbtgt/EXAMPLE.COM`.
+    const AS_REP: &[u8] = b"\
+        \x6b\x81\xc0\x30\x81\xbd\xa0\x03\x02\x01\x05\xa1\x03\x02\x01\x0b\
+        \xa3\x0d\x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa4\
+        \x12\x30\x10\xa0\x03\x02\x01\x01\xa1\x09\x30\x07\x1b\x05\x61\x6c\
+        \x69\x63\x65\xa5\x6c\x61\x6a\x30\x68\xa0\x03\x02\x01\x05\xa1\x0d\
+        \x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa2\x20\x30\
+        \x1e\xa0\x03\x02\x01\x02\xa1\x17\x30\x15\x1b\x06\x6b\x72\x62\x74\
+        \x67\x74\x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa3\
+        \x30\x30\x2e\xa0\x03\x02\x01\x12\xa1\x03\x02\x01\x02\xa2\x22\x04\
+        \x20\x40\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\
+        \x4f\x50\x51\x52\x53\x54\x55\x56\x57\x58\x59\x5a\x5b\x5c\x5d\x5e\
+        \x5f\xa6\x20\x30\x1e\xa0\x03\x02\x01\x12\xa1\x03\x02\x01\x02\xa2\
+        \x12\x04\x10\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\
+        \x6d\x6e\x6f";
+
+    /// TGS-REQ for `cifs/fs01.example.com` (AP-REQ elided).
+    const TGS_REQ: &[u8] = b"\
+        \x6c\x81\x96\x30\x81\x93\xa1\x03\x02\x01\x05\xa2\x03\x02\x01\x0c\
+        \xa3\x1e\x30\x1c\x30\x1a\xa1\x03\x02\x01\x01\xa2\x13\x04\x11\x6e\
+        \x0f\x30\x0d\xa0\x03\x02\x01\x05\xa1\x03\x02\x01\x0e\xa2\x03\x00\
+        \xa4\x67\x30\x65\xa0\x07\x03\x05\x00\x40\x81\x00\x10\xa2\x0d\x1b\
+        \x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa3\x23\x30\x21\
+        \xa0\x03\x02\x01\x02\xa1\x1a\x30\x18\x1b\x04\x63\x69\x66\x73\x1b\
+        \x10\x66\x73\x30\x31\x2e\x65\x78\x61\x6d\x70\x6c\x65\x2e\x63\x6f\
+        \x6d\xa5\x11\x18\x0f\x32\x30\x33\x37\x30\x39\x31\x33\x30\x32\x34\
+        \x38\x30\x35\x5a\xa7\x06\x02\x04\x0b\xad\xca\xfe\xa8\x0b\x30\x09\
+        \x02\x01\x12\x02\x01\x11\x02\x01\x17";
+
+    /// TGS-REP with an RC4 ticket for `cifs/fs01.example.com`.
+    const TGS_REP: &[u8] = b"\
+        \x6d\x81\xbe\x30\x81\xbb\xa0\x03\x02\x01\x05\xa1\x03\x02\x01\x0d\
+        \xa3\x0d\x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa4\
+        \x12\x30\x10\xa0\x03\x02\x01\x01\xa1\x09\x30\x07\x1b\x05\x61\x6c\
+        \x69\x63\x65\xa5\x6f\x61\x6d\x30\x6b\xa0\x03\x02\x01\x05\xa1\x0d\
+        \x1b\x0b\x45\x58\x41\x4d\x50\x4c\x45\x2e\x43\x4f\x4d\xa2\x23\x30\
+        \x21\xa0\x03\x02\x01\x02\xa1\x1a\x30\x18\x1b\x04\x63\x69\x66\x73\
+        \x1b\x10\x66\x73\x30\x31\x2e\x65\x78\x61\x6d\x70\x6c\x65\x2e\x63\
+        \x6f\x6d\xa3\x30\x30\x2e\xa0\x03\x02\x01\x17\xa1\x03\x02\x01\x05\
+        \xa2\x22\x04\x20\x80\x81\x82\x83\x84\x85\x86\x87\x88\x89\x8a\x8b\
+        \x8c\x8d\x8e\x8f\x90\x91\x92\x93\x94\x95\x96\x97\x98\x99\x9a\x9b\
+        \x9c\x9d\x9e\x9f\xa6\x1b\x30\x19\xa0\x03\x02\x01\x12\xa2\x12\x04\
+        \x10\xa0\xa1\xa2\xa3\xa4\xa5\xa6\xa7\xa8\xa9\xaa\xab\xac\xad\xae\
+        \xaf";
+
+    /// Returns `msg` with a TCP record mark.
+    fn framed(msg: &[u8]) -> Vec<u8> {
+        let mut data = (msg.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(msg);
+        data
+    }
+
+    #[test]
+    fn core_kerberos_probe() {
+        let parser = KerberosParser::default();
+        assert_eq!(parser.probe(&udp(AS_REQ, 88, true)), ProbeResult::Certain);
+        assert_eq!(parser.probe(&udp(AS_REP, 88, false)), ProbeResult::Certain);
+        assert_eq!(
+            parser.probe(&udp(PREAUTH_REQUIRED, 88, false)),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            parser.probe(&tcp(&framed(TGS_REQ), 88, true)),
+            ProbeResult::Certain
+        );
+        // Record mark is checked against the message length
+        assert_eq!(parser.probe(&tcp(TGS_REQ, 88, true)), ProbeResult::NotForUs);
+        // DNS query
+        let dns = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        assert_eq!(parser.probe(&udp(dns, 88, true)), ProbeResult::NotForUs);
+        // AP-REQ is not a KDC message
+        let ap_req = b"\x6e\x0f\x30\x0d\xa0\x03\x02\x01\x05\xa1\x03\x02\x01\x0e\xa2\x03\x00";
+        assert_eq!(parser.probe(&udp(ap_req, 88, true)), ProbeResult::NotForUs);
+    }
+
+    #[test]
+    fn core_kerberos_as_exchange() {
+        let mut parser = KerberosParser::default();
+        assert_eq!(
+            parser.parse(&udp(AS_REQ, 88, true)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(PREAUTH_REQUIRED, 88, false)),
+            ParseResult::Done(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(AS_REQ_PREAUTH, 88, true)),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(parser.parse(&udp(AS_REP, 88, false)), ParseResult::Done(1));
+
+        let kerberos = &parser.sessions[&0];
+        assert_eq!(kerberos.msg_type(), "as-req");
+        assert_eq!(kerberos.realm(), "EXAMPLE.COM");
+        assert_eq!(kerberos.client_principal(), "alice");
+        assert_eq!(kerberos.service_principal(), "krbtgt/EXAMPLE.COM");
+        assert_eq!(kerberos.etypes(), "18,17,23");
+        assert_eq!(kerberos.kdc_options(), 0x4081_0010);
+        assert!(!kerberos.preauth());
+        assert_eq!(kerberos.error_code(), 25);
+        assert_eq!(kerberos.error_name(), "KDC_ERR_PREAUTH_REQUIRED");
+        assert!(kerberos.reply.is_none());
+
+        let kerberos = &parser.sessions[&1];
+        assert!(kerberos.preauth());
+        assert_eq!(kerberos.request.as_ref().unwrap().nonce, 0x5a6b_7c8d);
+        assert_eq!(kerberos.error_code(), 0);
+        assert_eq!(kerberos.ticket_etype(), 18);
+        assert_eq!(kerberos.reply_etype(), 18);
+        let reply = kerberos.reply.as_ref().unwrap();
+        assert_eq!(reply.crealm, "EXAMPLE.COM");
+        assert_eq!(reply.cname.to_string(), "alice");
+        assert_eq!(reply.ticket_realm, "EXAMPLE.COM");
+    }
+
+    #[test]
+    fn core_kerberos_tcp_split() {
+        let mut parser = KerberosParser::default();
+        let req = framed(TGS_REQ);
+        // Split within the record mark, then within the message
+        assert_eq!(
+            parser.parse(&tcp(&req[..2], 88, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&tcp(&req[2..100], 88, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&tcp(&req[100..], 88, true)),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.client_buffer.is_empty());
+        let rep = framed(TGS_REP);
+        assert_eq!(
+            parser.parse(&tcp(&rep[..120], 88, false)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&tcp(&rep[120..], 88, false)),
+            ParseResult::Done(0)
+        );
+
+        let kerberos = &parser.sessions[&0];
+        assert_eq!(kerberos.msg_type(), "tgs-req");
+        assert_eq!(kerberos.service_principal(), "cifs/fs01.example.com");
+        // TGS-REQ has no client name, which is only in the reply
+        assert_eq!(kerberos.client_principal(), "alice");
+        assert_eq!(kerberos.ticket_etype(), 23);
+        assert_eq!(kerberos.reply_etype(), 18);
+        assert!(!kerberos.preauth());
+    }
+
+    #[test]
+    fn core_kerberos_truncated() {
+        let mut parser = KerberosParser::default();
+        let truncated = &AS_REQ[..AS_REQ.len() - 10];
+        assert_eq!(
+            parser.probe(&udp(truncated, 88, true)),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            parser.parse(&udp(truncated, 88, true)),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+
+        // Record mark without the complete message
+        let req = framed(TGS_REQ);
+        assert_eq!(
+            parser.parse(&tcp(&req[..60], 88, true)),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+        assert_eq!(parser.client_buffer.len(), 60);
+
+        // Reply without a request
+        assert_eq!(parser.parse(&udp(AS_REP, 88, false)), ParseResult::Done(0));
+        assert!(parser.sessions[&0].request.is_none());
+        assert_eq!(parser.sessions[&0].msg_type(), "as-rep");
+    }
+}
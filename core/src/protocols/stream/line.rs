@@ -0,0 +1,46 @@
+//! Line framing for text-based protocols.
+//!
+//! Command/response protocols such as SMTP, IMAP, and POP3 exchange CRLF-terminated lines that
+//! may be split across (or packed into) TCP segments. [`LineBuffer`] reassembles complete lines
+//! per direction and carries over any trailing partial line to the next segment.
+
+/// Maximum number of bytes buffered for a single incomplete line. Longer lines are discarded.
+const MAX_LINE_LEN: usize = 8192;
+
+/// Reassembles lines that span multiple segments in one direction of a stream.
+#[derive(Debug, Default)]
+pub(crate) struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Appends `data` to any buffered partial line and returns the complete lines, without line
+    /// terminators. Non-UTF-8 bytes are replaced lossily.
+    pub(crate) fn lines(&mut self, data: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(data);
+        let mut lines = vec![];
+        let mut start = 0;
+        while let Some(pos) = memchr::memchr(b'\n', &self.buf[start..]) {
+            let mut line = &self.buf[start..start + pos];
+            if let Some(stripped) = line.strip_suffix(b"\r") {
+                line = stripped;
+            }
+            lines.push(String::from_utf8_lossy(line).into_owned());
+            start += pos + 1;
+        }
+        self.buf.drain(..start);
+        if self.buf.len() > MAX_LINE_LEN {
+            log::debug!("Discarding {} bytes of unterminated line", self.buf.len());
+            self.buf.clear();
+        }
+        lines
+    }
+}
+
+/// Splits `line` into its first whitespace-delimited word and the (trimmed) remainder.
+pub(crate) fn split_verb(line: &str) -> (&str, &str) {
+    match line.trim_start().split_once(' ') {
+        Some((verb, rest)) => (verb, rest.trim()),
+        None => (line.trim(), ""),
+    }
+}
@@ -0,0 +1,56 @@
+//! Link-Local Multicast Name Resolution (LLMNR) transaction parsing.
+//!
+//! ## Remarks
+//! LLMNR ([RFC 4795](https://datatracker.ietf.org/doc/html/rfc4795)) messages use the DNS wire
+//! format, so queries and responses are decoded into the same [`DnsQuery`] and [`DnsResponse`]
+//! types as unicast DNS, and are paired by transaction ID. LLMNR repurposes two DNS header flags:
+//! the conflict (C) bit, in place of AA, and the tentative (T) bit, in place of RD.
+//!
+//! Queries are usually sent to a multicast group and answered by unicast, so a query and its
+//! response are typically tracked as separate connections. Responses without an observed query
+//! are reported as response-only transactions, and queries without a response are reported when
+//! the connection terminates.
+
+pub mod parser;
+
+use crate::protocols::stream::dns::{DnsQuery, DnsResponse};
+
+use serde::Serialize;
+
+/// Parsed LLMNR transaction contents.
+#[derive(Clone, Debug, Serialize)]
+pub struct Llmnr {
+    /// LLMNR transaction ID.
+    pub transaction_id: u16,
+    /// LLMNR query.
+    pub query: Option<DnsQuery>,
+    /// LLMNR response.
+    pub response: Option<DnsResponse>,
+    /// Conflict (C) bit of the response, or of the query if it was retransmitted after a
+    /// conflict was detected.
+    pub conflict: bool,
+    /// Tentative (T) bit of the response.
+    pub tentative: bool,
+}
+
+impl Llmnr {
+    /// Returns the queried name, or `""` if no query was observed in the transaction.
+    pub fn query_domain(&self) -> &str {
+        if let Some(query) = &self.query {
+            if !query.queries.is_empty() {
+                return &query.queries[0];
+            }
+        }
+        ""
+    }
+
+    /// Returns a string representation of the answers
+    pub fn answers(&self) -> String {
+        if let Some(resp) = &self.response {
+            if !resp.answers.is_empty() {
+                return serde_json::to_string(&resp.answers).unwrap_or(String::new());
+            }
+        }
+        String::new()
+    }
+}
@@ -0,0 +1,240 @@
+//! LLMNR transaction parser.
+//!
+//! Parses LLMNR messages with the DNS wire-format parser and pairs queries with responses by
+//! transaction ID.
+
+use super::Llmnr;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::dns::{DnsQuery, DnsResponse};
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+/// LLMNR port.
+pub(crate) const LLMNR_PORT: u16 = 5355;
+
+/// Conflict (C) bit in the third byte of the header.
+const FLAG_CONFLICT: u8 = 0x04;
+/// Tentative (T) bit in the third byte of the header.
+const FLAG_TENTATIVE: u8 = 0x01;
+
+#[derive(Default, Debug)]
+pub struct LlmnrParser {
+    /// Maps session ID to LLMNR transaction
+    sessions: HashMap<usize, Llmnr>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+}
+
+impl ConnParsable for LlmnrParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.src.port() != LLMNR_PORT && pdu.ctxt.dst.port() != LLMNR_PORT {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match dns_parser::Packet::parse(data) {
+                // Each LLMNR query has exactly one question
+                Ok(packet) if packet.questions.len() == 1 => ProbeResult::Certain,
+                _ => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|llmnr| Session {
+            data: SessionData::Llmnr(Box::new(llmnr)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, llmnr)| Session {
+                data: SessionData::Llmnr(Box::new(llmnr)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider LLMNR to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl LlmnrParser {
+    pub(crate) fn process(&mut self, data: &[u8]) -> ParseResult {
+        match dns_parser::Packet::parse(data) {
+            Ok(pkt) => {
+                let conflict = data[2] & FLAG_CONFLICT != 0;
+                let id = pkt.header.id;
+                if pkt.header.query {
+                    log::debug!("LLMNR query");
+                    let llmnr = Llmnr {
+                        transaction_id: id,
+                        query: Some(DnsQuery::parse_query(&pkt)),
+                        response: None,
+                        conflict,
+                        tentative: false,
+                    };
+                    ParseResult::Continue(self.insert(llmnr))
+                } else {
+                    log::debug!("LLMNR response");
+                    let response = DnsResponse::parse_response(&pkt);
+                    let tentative = data[2] & FLAG_TENTATIVE != 0;
+                    for (session_id, llmnr) in self.sessions.iter_mut() {
+                        if id == llmnr.transaction_id && llmnr.response.is_none() {
+                            llmnr.response = Some(response);
+                            llmnr.conflict |= conflict;
+                            llmnr.tentative = tentative;
+                            return ParseResult::Done(*session_id);
+                        }
+                    }
+                    let llmnr = Llmnr {
+                        transaction_id: id,
+                        query: None,
+                        response: Some(response),
+                        conflict,
+                        tentative,
+                    };
+                    ParseResult::Done(self.insert(llmnr))
+                }
+            }
+            e => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+
+    fn insert(&mut self, llmnr: Llmnr) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, llmnr);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{udp, Flow};
+
+    /// Query for `wpad` from a Windows host.
+    const QUERY: &[u8] = b"\
+        \x8a\x3c\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x04\x77\x70\x61\
+        \x64\x00\x00\x01\x00\x01";
+
+    /// Response resolving `wpad` to 192.168.1.20.
+    const RESPONSE: &[u8] = b"\
+        \x8a\x3c\x80\x00\x00\x01\x00\x01\x00\x00\x00\x00\x04\x77\x70\x61\
+        \x64\x00\x00\x01\x00\x01\xc0\x0c\x00\x01\x00\x01\x00\x00\x00\x1e\
+        \x00\x04\xc0\xa8\x01\x14";
+
+    const FLOW: Flow = Flow::udp(LLMNR_PORT);
+
+    #[test]
+    fn core_llmnr_probe() {
+        let parser = LlmnrParser::default();
+        assert!(matches!(
+            FLOW.probe(&parser, QUERY, true),
+            ProbeResult::Certain
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, RESPONSE, false),
+            ProbeResult::Certain
+        ));
+        // Not the LLMNR port
+        assert!(matches!(
+            parser.probe(&udp(QUERY, 53, true)),
+            ProbeResult::NotForUs
+        ));
+        // Query without questions
+        assert!(matches!(
+            FLOW.probe(&parser, &QUERY[..12], true),
+            ProbeResult::NotForUs
+        ));
+    }
+
+    #[test]
+    fn core_llmnr_query_response() {
+        let mut parser = LlmnrParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, QUERY, true),
+            ParseResult::Continue(0)
+        ));
+        // Query from another host
+        let mut other = QUERY.to_vec();
+        other[..2].copy_from_slice(&[0x11, 0x07]);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &other, true),
+            ParseResult::Continue(1)
+        ));
+        // Response with the conflict bit set
+        let mut response = RESPONSE.to_vec();
+        response[2] |= FLAG_CONFLICT;
+        assert!(matches!(
+            FLOW.parse(&mut parser, &response, false),
+            ParseResult::Done(0)
+        ));
+
+        let session = parser.remove_session(0).unwrap();
+        match &session.data {
+            SessionData::Llmnr(llmnr) => {
+                assert_eq!(llmnr.transaction_id, 0x8a3c);
+                assert_eq!(llmnr.query_domain(), "wpad");
+                assert!(llmnr.conflict);
+                assert!(!llmnr.tentative);
+                let answers = &llmnr.response.as_ref().unwrap().answers;
+                assert_eq!(answers.len(), 1);
+                assert!(llmnr.answers().contains("192.168.1.20"));
+            }
+            _ => panic!("Expected LLMNR session"),
+        }
+    }
+
+    #[test]
+    fn core_llmnr_truncated() {
+        let mut parser = LlmnrParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, &QUERY[..QUERY.len() - 1], true),
+            ParseResult::Skipped
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, &RESPONSE[..RESPONSE.len() - 2], false),
+            ParseResult::Skipped
+        ));
+        assert!(parser.drain_sessions().is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+//! Multicast DNS (mDNS) transaction parsing.
+//!
+//! ## Remarks
+//! mDNS messages use the DNS wire format, so queries and responses are decoded into the same
+//! [`DnsQuery`] and [`DnsResponse`] types as unicast DNS. Unlike unicast DNS, mDNS queries and
+//! responses usually carry a transaction ID of 0 and are sent to a multicast group, so responses
+//! are paired with an outstanding query by matching answer names to question names. Legacy
+//! unicast queries (sent from a port other than 5353) carry a nonzero ID and are paired by ID.
+//!
+//! Multicast queries and responses are addressed to the group rather than to each other, so a
+//! query and its responses are typically tracked as separate connections. Responses without an
+//! observed query (including unsolicited announcements) are reported as response-only
+//! transactions, and queries without a response are reported when the connection terminates.
+//! Only the first response to a query is paired with it.
+
+pub mod parser;
+
+use crate::protocols::stream::dns::{DnsQuery, DnsResponse};
+
+use serde::Serialize;
+
+/// Parsed mDNS transaction contents.
+#[derive(Clone, Debug, Serialize)]
+pub struct Mdns {
+    /// DNS transaction ID (0 for multicast queries and responses).
+    pub transaction_id: u16,
+    /// mDNS query.
+    pub query: Option<DnsQuery>,
+    /// mDNS response.
+    pub response: Option<DnsResponse>,
+    /// `true` if any question requested a unicast response (the QU bit).
+    pub unicast_response: bool,
+    /// `true` if any response record has the cache-flush bit set.
+    pub cache_flush: bool,
+}
+
+impl Mdns {
+    /// Returns the first queried name, or `""` if no query was observed in the transaction.
+    pub fn query_domain(&self) -> &str {
+        if let Some(query) = &self.query {
+            if !query.queries.is_empty() {
+                return &query.queries[0];
+            }
+        }
+        ""
+    }
+
+    /// Returns the name of the first answer record, or `""` if no answers were observed.
+    pub fn answer_domain(&self) -> &str {
+        if let Some(resp) = &self.response {
+            if !resp.answers.is_empty() {
+                return &resp.answers[0].name;
+            }
+        }
+        ""
+    }
+
+    /// Returns a string representation of the answers
+    pub fn answers(&self) -> String {
+        if let Some(resp) = &self.response {
+            if !resp.answers.is_empty() {
+                return serde_json::to_string(&resp.answers).unwrap_or(String::new());
+            }
+        }
+        String::new()
+    }
+
+    /// Returns a string representation of the response additionals
+    pub fn additionals(&self) -> String {
+        if let Some(resp) = &self.response {
+            if !resp.additionals.is_empty() {
+                return serde_json::to_string(&resp.additionals).unwrap_or(String::new());
+            }
+        }
+        String::new()
+    }
+
+    /// Returns `true` if the transaction is an unsolicited announcement (a response without a
+    /// query).
+    pub fn is_announcement(&self) -> bool {
+        self.query.is_none() && self.response.is_some()
+    }
+
+    /// Returns `true` if `response` answers the query in this transaction.
+    fn answered_by(&self, transaction_id: u16, response: &DnsResponse) -> bool {
+        let query = match (&self.query, &self.response) {
+            (Some(query), None) => query,
+            _ => return false,
+        };
+        if transaction_id != 0 || self.transaction_id != 0 {
+            return transaction_id == self.transaction_id;
+        }
+        response.answers.iter().any(|answer| {
+            query
+                .queries
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&answer.name))
+        })
+    }
+}
@@ -0,0 +1,279 @@
+//! mDNS transaction parser.
+//!
+//! Parses mDNS messages with the DNS wire-format parser and pairs responses with outstanding
+//! queries.
+
+use super::Mdns;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::dns::{DnsQuery, DnsResponse};
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+/// mDNS port.
+pub(crate) const MDNS_PORT: u16 = 5353;
+
+#[derive(Default, Debug)]
+pub struct MdnsParser {
+    /// Maps session ID to mDNS transaction
+    sessions: HashMap<usize, Mdns>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+}
+
+impl ConnParsable for MdnsParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.src.port() != MDNS_PORT && pdu.ctxt.dst.port() != MDNS_PORT {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match dns_parser::Packet::parse(data) {
+                Ok(packet) => {
+                    if packet.header.query {
+                        if packet.questions.is_empty() {
+                            return ProbeResult::NotForUs;
+                        }
+                    } else if packet.answers.is_empty() && packet.additional.is_empty() {
+                        return ProbeResult::NotForUs;
+                    }
+                    ProbeResult::Certain
+                }
+                _ => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|mdns| Session {
+            data: SessionData::Mdns(Box::new(mdns)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, mdns)| Session {
+                data: SessionData::Mdns(Box::new(mdns)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider mDNS to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl MdnsParser {
+    pub(crate) fn process(&mut self, data: &[u8]) -> ParseResult {
+        match dns_parser::Packet::parse(data) {
+            Ok(pkt) => {
+                if pkt.header.query {
+                    log::debug!("mDNS query");
+                    let mdns = Mdns {
+                        transaction_id: pkt.header.id,
+                        query: Some(DnsQuery::parse_query(&pkt)),
+                        response: None,
+                        unicast_response: pkt.questions.iter().any(|q| q.prefer_unicast),
+                        cache_flush: false,
+                    };
+                    ParseResult::Continue(self.insert(mdns))
+                } else {
+                    log::debug!("mDNS response");
+                    let response = DnsResponse::parse_response(&pkt);
+                    let cache_flush = pkt
+                        .answers
+                        .iter()
+                        .chain(pkt.additional.iter())
+                        .any(|rr| rr.multicast_unique);
+                    for (session_id, mdns) in self.sessions.iter_mut() {
+                        if mdns.answered_by(pkt.header.id, &response) {
+                            mdns.response = Some(response);
+                            mdns.cache_flush = cache_flush;
+                            return ParseResult::Done(*session_id);
+                        }
+                    }
+                    let mdns = Mdns {
+                        transaction_id: pkt.header.id,
+                        query: None,
+                        response: Some(response),
+                        unicast_response: false,
+                        cache_flush,
+                    };
+                    ParseResult::Done(self.insert(mdns))
+                }
+            }
+            e => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+
+    fn insert(&mut self, mdns: Mdns) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, mdns);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{udp, Flow};
+
+    /// Multicast query for Google Cast devices, requesting a unicast response (QU bit).
+    const QUERY: &[u8] = b"\
+        \x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x0b\x5f\x67\x6f\
+        \x6f\x67\x6c\x65\x63\x61\x73\x74\x04\x5f\x74\x63\x70\x05\x6c\x6f\
+        \x63\x61\x6c\x00\x00\x0c\x80\x01";
+
+    /// Response with the PTR answer, and SRV and A additionals with the cache-flush bit set.
+    const RESPONSE: &[u8] = b"\
+        \x00\x00\x84\x00\x00\x00\x00\x01\x00\x00\x00\x02\x0b\x5f\x67\x6f\
+        \x6f\x67\x6c\x65\x63\x61\x73\x74\x04\x5f\x74\x63\x70\x05\x6c\x6f\
+        \x63\x61\x6c\x00\x00\x0c\x00\x01\x00\x00\x00\x78\x00\x12\x0f\x43\
+        \x68\x72\x6f\x6d\x65\x63\x61\x73\x74\x2d\x31\x61\x32\x62\xc0\x0c\
+        \xc0\x2e\x00\x21\x80\x01\x00\x00\x00\x78\x00\x1d\x00\x00\x00\x00\
+        \x1f\x49\x0f\x43\x68\x72\x6f\x6d\x65\x63\x61\x73\x74\x2d\x31\x61\
+        \x32\x62\x05\x6c\x6f\x63\x61\x6c\x00\xc0\x52\x00\x01\x80\x01\x00\
+        \x00\x00\x78\x00\x04\xc0\xa8\x01\x32";
+
+    const FLOW: Flow = Flow::udp(MDNS_PORT);
+
+    #[test]
+    fn core_mdns_probe() {
+        let parser = MdnsParser::default();
+        assert!(matches!(
+            FLOW.probe(&parser, QUERY, true),
+            ProbeResult::Certain
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, RESPONSE, false),
+            ProbeResult::Certain
+        ));
+        // Not the mDNS port
+        assert!(matches!(
+            parser.probe(&udp(QUERY, 53, true)),
+            ProbeResult::NotForUs
+        ));
+        // Query without questions
+        assert!(matches!(
+            FLOW.probe(&parser, &QUERY[..12], true),
+            ProbeResult::NotForUs
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, b"M-SEARCH * HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        ));
+    }
+
+    #[test]
+    fn core_mdns_query_response() {
+        let mut parser = MdnsParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, QUERY, true),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, RESPONSE, false),
+            ParseResult::Done(0)
+        ));
+        // A second response to the same query is reported on its own.
+        assert!(matches!(
+            FLOW.parse(&mut parser, RESPONSE, false),
+            ParseResult::Done(1)
+        ));
+
+        let session = parser.remove_session(0).unwrap();
+        match &session.data {
+            SessionData::Mdns(mdns) => {
+                assert_eq!(mdns.query_domain(), "_googlecast._tcp.local");
+                assert_eq!(mdns.answer_domain(), "_googlecast._tcp.local");
+                assert!(mdns.unicast_response);
+                assert!(mdns.cache_flush);
+                assert!(!mdns.is_announcement());
+                let response = mdns.response.as_ref().unwrap();
+                assert_eq!(response.additionals.len(), 2);
+                assert_eq!(
+                    response.additionals[0].name,
+                    "Chromecast-1a2b._googlecast._tcp.local"
+                );
+            }
+            _ => panic!("Expected mDNS session"),
+        }
+        match &parser.remove_session(1).unwrap().data {
+            SessionData::Mdns(mdns) => assert!(mdns.is_announcement()),
+            _ => panic!("Expected mDNS session"),
+        }
+    }
+
+    #[test]
+    fn core_mdns_legacy_unicast() {
+        let mut parser = MdnsParser::default();
+        let mut query = QUERY.to_vec();
+        query[..2].copy_from_slice(&[0x4d, 0x21]);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &query, true),
+            ParseResult::Continue(0)
+        ));
+        // Legacy unicast queries are paired by ID rather than by name.
+        let mut response = RESPONSE.to_vec();
+        assert!(matches!(
+            FLOW.parse(&mut parser, &response, false),
+            ParseResult::Done(1)
+        ));
+        response[..2].copy_from_slice(&[0x4d, 0x21]);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &response, false),
+            ParseResult::Done(0)
+        ));
+    }
+
+    #[test]
+    fn core_mdns_truncated() {
+        let mut parser = MdnsParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, &QUERY[..QUERY.len() - 2], true),
+            ParseResult::Skipped
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, &RESPONSE[..RESPONSE.len() - 3], false),
+            ParseResult::Skipped
+        ));
+        assert!(parser.drain_sessions().is_empty());
+    }
+}
@@ -4,22 +4,74 @@
 //! considered a "stream-level" protocol, even if it is a datagram-based protocol in the
 //! traditional-sense.
 
+pub mod bittorrent;
+pub mod coap;
 #[doc(hidden)]
 pub mod conn;
+pub mod dhcp;
+pub mod dhcpv6;
 pub mod dns;
 pub mod http;
+pub mod imap;
+pub mod kerberos;
+mod line;
+pub mod llmnr;
+pub mod mdns;
+pub mod modbus;
+pub mod mqtt;
+pub mod mysql;
+pub mod ntp;
+pub mod openvpn;
+pub mod pop3;
+pub mod postgres;
 pub mod quic;
+pub mod rdp;
+pub mod redis;
+pub mod rtp;
+pub mod sip;
+pub mod smb;
+pub mod smtp;
+pub mod ssdp;
 pub mod ssh;
+#[cfg(all(test, feature = "heap_mbuf"))]
+pub(crate) mod testing;
 pub mod tls;
+pub mod vnc;
+pub mod wireguard;
 
+use self::bittorrent::{parser::BittorrentParser, Bittorrent};
+use self::coap::{parser::CoapParser, Coap};
 use self::conn::ConnField;
 use self::conn::{Ipv4CData, Ipv6CData, TcpCData, UdpCData};
+use self::dhcp::{parser::DhcpParser, Dhcp};
+use self::dhcpv6::{parser::Dhcpv6Parser, Dhcpv6};
 use self::dns::{parser::DnsParser, Dns};
 use self::http::{parser::HttpParser, Http};
+use self::imap::{parser::ImapParser, Imap};
+use self::kerberos::{parser::KerberosParser, Kerberos};
+use self::llmnr::{parser::LlmnrParser, Llmnr};
+use self::mdns::{parser::MdnsParser, Mdns};
+use self::modbus::{parser::ModbusParser, Modbus};
+use self::mqtt::{parser::MqttParser, Mqtt};
+use self::mysql::{parser::MySqlParser, MySql};
+use self::ntp::{parser::NtpParser, Ntp};
+use self::openvpn::{parser::OpenVpnParser, OpenVpn};
+use self::pop3::{parser::Pop3Parser, Pop3};
+use self::postgres::{parser::PostgresParser, Postgres};
 use self::quic::parser::QuicParser;
+use self::rdp::{parser::RdpParser, Rdp};
+use self::redis::{parser::RedisParser, Redis};
+use self::rtp::{parser::RtpParser, Rtp};
+use self::sip::{parser::SipParser, Sip};
+use self::smb::{parser::SmbParser, Smb};
+use self::smtp::{parser::SmtpParser, Smtp};
+use self::ssdp::{parser::SsdpParser, Ssdp};
 use self::ssh::{parser::SshParser, Ssh};
 use self::tls::{parser::TlsParser, Tls};
+use self::vnc::{parser::VncParser, Vnc};
+use self::wireguard::{parser::WireGuardParser, WireGuard};
 use crate::conntrack::conn_id::FiveTuple;
+use crate::conntrack::expected::ExpectedConn;
 use crate::conntrack::pdu::L4Pdu;
 
 use std::collections::HashSet;
@@ -29,7 +81,37 @@ use anyhow::Result;
 use quic::QuicConn;
 use strum_macros::EnumString;
 
-pub const IMPLEMENTED_PROTOCOLS: [&str; 5] = ["tls", "dns", "http", "quic", "ssh"];
+pub const IMPLEMENTED_PROTOCOLS: [&str; 29] = [
+    "tls",
+    "dns",
+    "http",
+    "quic",
+    "ssh",
+    "smtp",
+    "imap",
+    "pop3",
+    "dhcp",
+    "dhcpv6",
+    "ntp",
+    "mdns",
+    "llmnr",
+    "ssdp",
+    "openvpn",
+    "wireguard",
+    "smb",
+    "kerberos",
+    "postgres",
+    "mysql",
+    "redis",
+    "sip",
+    "rtp",
+    "mqtt",
+    "coap",
+    "modbus",
+    "bittorrent",
+    "rdp",
+    "vnc",
+];
 
 /// Represents the result of parsing one packet as a protocol message.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -90,6 +172,15 @@ impl ParserRegistry {
         ParserRegistry(parsers)
     }
 
+    /// Returns a parser for an expected connection announced by a signalling session, if its
+    /// protocol is registered.
+    pub(crate) fn expected(&self, expected: &ExpectedConn) -> Option<ConnParser> {
+        self.0
+            .iter()
+            .any(|parser| matches!(parser, ConnParser::Rtp(_)))
+            .then(|| ConnParser::Rtp(RtpParser::from_expected(expected)))
+    }
+
     /// Probe the packet `pdu` with all registered protocol parsers.
     pub(crate) fn probe_all(&self, pdu: &L4Pdu) -> ProbeRegistryResult {
         if self.0.is_empty() {
@@ -204,6 +295,30 @@ pub enum SessionData {
     Http(Box<Http>),
     Quic(Box<QuicConn>),
     Ssh(Box<Ssh>),
+    Smtp(Box<Smtp>),
+    Imap(Box<Imap>),
+    Pop3(Box<Pop3>),
+    Dhcp(Box<Dhcp>),
+    Dhcpv6(Box<Dhcpv6>),
+    Ntp(Box<Ntp>),
+    Mdns(Box<Mdns>),
+    Llmnr(Box<Llmnr>),
+    Ssdp(Box<Ssdp>),
+    Openvpn(Box<OpenVpn>),
+    Wireguard(Box<WireGuard>),
+    Smb(Box<Smb>),
+    Kerberos(Box<Kerberos>),
+    Postgres(Box<Postgres>),
+    Mysql(Box<MySql>),
+    Redis(Box<Redis>),
+    Sip(Box<Sip>),
+    Rtp(Box<Rtp>),
+    Mqtt(Box<Mqtt>),
+    Coap(Box<Coap>),
+    Modbus(Box<Modbus>),
+    Bittorrent(Box<Bittorrent>),
+    Rdp(Box<Rdp>),
+    Vnc(Box<Vnc>),
     Null,
 }
 
@@ -216,6 +331,30 @@ pub enum SessionProto {
     Http,
     Quic,
     Ssh,
+    Smtp,
+    Imap,
+    Pop3,
+    Dhcp,
+    Dhcpv6,
+    Ntp,
+    Mdns,
+    Llmnr,
+    Ssdp,
+    Openvpn,
+    Wireguard,
+    Smb,
+    Kerberos,
+    Postgres,
+    Mysql,
+    Redis,
+    Sip,
+    Rtp,
+    Mqtt,
+    Coap,
+    Modbus,
+    Bittorrent,
+    Rdp,
+    Vnc,
     Ipv4,
     Ipv6,
     Tcp,
@@ -266,6 +405,30 @@ pub enum ConnParser {
     Http(HttpParser),
     Quic(QuicParser),
     Ssh(SshParser),
+    Smtp(SmtpParser),
+    Imap(ImapParser),
+    Pop3(Pop3Parser),
+    Dhcp(DhcpParser),
+    Dhcpv6(Dhcpv6Parser),
+    Ntp(NtpParser),
+    Mdns(MdnsParser),
+    Llmnr(LlmnrParser),
+    Ssdp(SsdpParser),
+    Openvpn(OpenVpnParser),
+    Wireguard(WireGuardParser),
+    Smb(SmbParser),
+    Kerberos(KerberosParser),
+    Postgres(PostgresParser),
+    Mysql(MySqlParser),
+    Redis(RedisParser),
+    Sip(SipParser),
+    Rtp(RtpParser),
+    Mqtt(MqttParser),
+    Coap(CoapParser),
+    Modbus(ModbusParser),
+    Bittorrent(BittorrentParser),
+    Rdp(RdpParser),
+    Vnc(VncParser),
     Unknown,
 }
 
@@ -278,6 +441,30 @@ impl ConnParser {
             ConnParser::Http(_) => ConnParser::Http(HttpParser::default()),
             ConnParser::Quic(_) => ConnParser::Quic(QuicParser::default()),
             ConnParser::Ssh(_) => ConnParser::Ssh(SshParser::default()),
+            ConnParser::Smtp(_) => ConnParser::Smtp(SmtpParser::default()),
+            ConnParser::Imap(_) => ConnParser::Imap(ImapParser::default()),
+            ConnParser::Pop3(_) => ConnParser::Pop3(Pop3Parser::default()),
+            ConnParser::Dhcp(_) => ConnParser::Dhcp(DhcpParser::default()),
+            ConnParser::Dhcpv6(_) => ConnParser::Dhcpv6(Dhcpv6Parser::default()),
+            ConnParser::Ntp(_) => ConnParser::Ntp(NtpParser::default()),
+            ConnParser::Mdns(_) => ConnParser::Mdns(MdnsParser::default()),
+            ConnParser::Llmnr(_) => ConnParser::Llmnr(LlmnrParser::default()),
+            ConnParser::Ssdp(_) => ConnParser::Ssdp(SsdpParser::default()),
+            ConnParser::Openvpn(_) => ConnParser::Openvpn(OpenVpnParser::default()),
+            ConnParser::Wireguard(_) => ConnParser::Wireguard(WireGuardParser::default()),
+            ConnParser::Smb(_) => ConnParser::Smb(SmbParser::default()),
+            ConnParser::Kerberos(_) => ConnParser::Kerberos(KerberosParser::default()),
+            ConnParser::Postgres(_) => ConnParser::Postgres(PostgresParser::default()),
+            ConnParser::Mysql(_) => ConnParser::Mysql(MySqlParser::default()),
+            ConnParser::Redis(_) => ConnParser::Redis(RedisParser::default()),
+            ConnParser::Sip(_) => ConnParser::Sip(SipParser::default()),
+            ConnParser::Rtp(_) => ConnParser::Rtp(RtpParser::default()),
+            ConnParser::Mqtt(_) => ConnParser::Mqtt(MqttParser::default()),
+            ConnParser::Coap(_) => ConnParser::Coap(CoapParser::default()),
+            ConnParser::Modbus(_) => ConnParser::Modbus(ModbusParser::default()),
+            ConnParser::Bittorrent(_) => ConnParser::Bittorrent(BittorrentParser::default()),
+            ConnParser::Rdp(_) => ConnParser::Rdp(RdpParser::default()),
+            ConnParser::Vnc(_) => ConnParser::Vnc(VncParser::default()),
             ConnParser::Unknown => ConnParser::Unknown,
         }
     }
@@ -290,6 +477,30 @@ impl ConnParser {
             ConnParser::Http(parser) => parser.parse(pdu),
             ConnParser::Quic(parser) => parser.parse(pdu),
             ConnParser::Ssh(parser) => parser.parse(pdu),
+            ConnParser::Smtp(parser) => parser.parse(pdu),
+            ConnParser::Imap(parser) => parser.parse(pdu),
+            ConnParser::Pop3(parser) => parser.parse(pdu),
+            ConnParser::Dhcp(parser) => parser.parse(pdu),
+            ConnParser::Dhcpv6(parser) => parser.parse(pdu),
+            ConnParser::Ntp(parser) => parser.parse(pdu),
+            ConnParser::Mdns(parser) => parser.parse(pdu),
+            ConnParser::Llmnr(parser) => parser.parse(pdu),
+            ConnParser::Ssdp(parser) => parser.parse(pdu),
+            ConnParser::Openvpn(parser) => parser.parse(pdu),
+            ConnParser::Wireguard(parser) => parser.parse(pdu),
+            ConnParser::Smb(parser) => parser.parse(pdu),
+            ConnParser::Kerberos(parser) => parser.parse(pdu),
+            ConnParser::Postgres(parser) => parser.parse(pdu),
+            ConnParser::Mysql(parser) => parser.parse(pdu),
+            ConnParser::Redis(parser) => parser.parse(pdu),
+            ConnParser::Sip(parser) => parser.parse(pdu),
+            ConnParser::Rtp(parser) => parser.parse(pdu),
+            ConnParser::Mqtt(parser) => parser.parse(pdu),
+            ConnParser::Coap(parser) => parser.parse(pdu),
+            ConnParser::Modbus(parser) => parser.parse(pdu),
+            ConnParser::Bittorrent(parser) => parser.parse(pdu),
+            ConnParser::Rdp(parser) => parser.parse(pdu),
+            ConnParser::Vnc(parser) => parser.parse(pdu),
             ConnParser::Unknown => ParseResult::None,
         }
     }
@@ -302,6 +513,30 @@ impl ConnParser {
             ConnParser::Http(parser) => parser.probe(pdu),
             ConnParser::Quic(parser) => parser.probe(pdu),
             ConnParser::Ssh(parser) => parser.probe(pdu),
+            ConnParser::Smtp(parser) => parser.probe(pdu),
+            ConnParser::Imap(parser) => parser.probe(pdu),
+            ConnParser::Pop3(parser) => parser.probe(pdu),
+            ConnParser::Dhcp(parser) => parser.probe(pdu),
+            ConnParser::Dhcpv6(parser) => parser.probe(pdu),
+            ConnParser::Ntp(parser) => parser.probe(pdu),
+            ConnParser::Mdns(parser) => parser.probe(pdu),
+            ConnParser::Llmnr(parser) => parser.probe(pdu),
+            ConnParser::Ssdp(parser) => parser.probe(pdu),
+            ConnParser::Openvpn(parser) => parser.probe(pdu),
+            ConnParser::Wireguard(parser) => parser.probe(pdu),
+            ConnParser::Smb(parser) => parser.probe(pdu),
+            ConnParser::Kerberos(parser) => parser.probe(pdu),
+            ConnParser::Postgres(parser) => parser.probe(pdu),
+            ConnParser::Mysql(parser) => parser.probe(pdu),
+            ConnParser::Redis(parser) => parser.probe(pdu),
+            ConnParser::Sip(parser) => parser.probe(pdu),
+            ConnParser::Rtp(parser) => parser.probe(pdu),
+            ConnParser::Mqtt(parser) => parser.probe(pdu),
+            ConnParser::Coap(parser) => parser.probe(pdu),
+            ConnParser::Modbus(parser) => parser.probe(pdu),
+            ConnParser::Bittorrent(parser) => parser.probe(pdu),
+            ConnParser::Rdp(parser) => parser.probe(pdu),
+            ConnParser::Vnc(parser) => parser.probe(pdu),
             ConnParser::Unknown => ProbeResult::Error,
         }
     }
@@ -315,6 +550,30 @@ impl ConnParser {
             ConnParser::Http(parser) => parser.remove_session(session_id),
             ConnParser::Quic(parser) => parser.remove_session(session_id),
             ConnParser::Ssh(parser) => parser.remove_session(session_id),
+            ConnParser::Smtp(parser) => parser.remove_session(session_id),
+            ConnParser::Imap(parser) => parser.remove_session(session_id),
+            ConnParser::Pop3(parser) => parser.remove_session(session_id),
+            ConnParser::Dhcp(parser) => parser.remove_session(session_id),
+            ConnParser::Dhcpv6(parser) => parser.remove_session(session_id),
+            ConnParser::Ntp(parser) => parser.remove_session(session_id),
+            ConnParser::Mdns(parser) => parser.remove_session(session_id),
+            ConnParser::Llmnr(parser) => parser.remove_session(session_id),
+            ConnParser::Ssdp(parser) => parser.remove_session(session_id),
+            ConnParser::Openvpn(parser) => parser.remove_session(session_id),
+            ConnParser::Wireguard(parser) => parser.remove_session(session_id),
+            ConnParser::Smb(parser) => parser.remove_session(session_id),
+            ConnParser::Kerberos(parser) => parser.remove_session(session_id),
+            ConnParser::Postgres(parser) => parser.remove_session(session_id),
+            ConnParser::Mysql(parser) => parser.remove_session(session_id),
+            ConnParser::Redis(parser) => parser.remove_session(session_id),
+            ConnParser::Sip(parser) => parser.remove_session(session_id),
+            ConnParser::Rtp(parser) => parser.remove_session(session_id),
+            ConnParser::Mqtt(parser) => parser.remove_session(session_id),
+            ConnParser::Coap(parser) => parser.remove_session(session_id),
+            ConnParser::Modbus(parser) => parser.remove_session(session_id),
+            ConnParser::Bittorrent(parser) => parser.remove_session(session_id),
+            ConnParser::Rdp(parser) => parser.remove_session(session_id),
+            ConnParser::Vnc(parser) => parser.remove_session(session_id),
             ConnParser::Unknown => None,
         }
     }
@@ -327,6 +586,30 @@ impl ConnParser {
             ConnParser::Http(parser) => parser.drain_sessions(),
             ConnParser::Quic(parser) => parser.drain_sessions(),
             ConnParser::Ssh(parser) => parser.drain_sessions(),
+            ConnParser::Smtp(parser) => parser.drain_sessions(),
+            ConnParser::Imap(parser) => parser.drain_sessions(),
+            ConnParser::Pop3(parser) => parser.drain_sessions(),
+            ConnParser::Dhcp(parser) => parser.drain_sessions(),
+            ConnParser::Dhcpv6(parser) => parser.drain_sessions(),
+            ConnParser::Ntp(parser) => parser.drain_sessions(),
+            ConnParser::Mdns(parser) => parser.drain_sessions(),
+            ConnParser::Llmnr(parser) => parser.drain_sessions(),
+            ConnParser::Ssdp(parser) => parser.drain_sessions(),
+            ConnParser::Openvpn(parser) => parser.drain_sessions(),
+            ConnParser::Wireguard(parser) => parser.drain_sessions(),
+            ConnParser::Smb(parser) => parser.drain_sessions(),
+            ConnParser::Kerberos(parser) => parser.drain_sessions(),
+            ConnParser::Postgres(parser) => parser.drain_sessions(),
+            ConnParser::Mysql(parser) => parser.drain_sessions(),
+            ConnParser::Redis(parser) => parser.drain_sessions(),
+            ConnParser::Sip(parser) => parser.drain_sessions(),
+            ConnParser::Rtp(parser) => parser.drain_sessions(),
+            ConnParser::Mqtt(parser) => parser.drain_sessions(),
+            ConnParser::Coap(parser) => parser.drain_sessions(),
+            ConnParser::Modbus(parser) => parser.drain_sessions(),
+            ConnParser::Bittorrent(parser) => parser.drain_sessions(),
+            ConnParser::Rdp(parser) => parser.drain_sessions(),
+            ConnParser::Vnc(parser) => parser.drain_sessions(),
             ConnParser::Unknown => vec![],
         }
     }
@@ -338,6 +621,30 @@ impl ConnParser {
             ConnParser::Http(parser) => parser.session_parsed_state(),
             ConnParser::Quic(parser) => parser.session_parsed_state(),
             ConnParser::Ssh(parser) => parser.session_parsed_state(),
+            ConnParser::Smtp(parser) => parser.session_parsed_state(),
+            ConnParser::Imap(parser) => parser.session_parsed_state(),
+            ConnParser::Pop3(parser) => parser.session_parsed_state(),
+            ConnParser::Dhcp(parser) => parser.session_parsed_state(),
+            ConnParser::Dhcpv6(parser) => parser.session_parsed_state(),
+            ConnParser::Ntp(parser) => parser.session_parsed_state(),
+            ConnParser::Mdns(parser) => parser.session_parsed_state(),
+            ConnParser::Llmnr(parser) => parser.session_parsed_state(),
+            ConnParser::Ssdp(parser) => parser.session_parsed_state(),
+            ConnParser::Openvpn(parser) => parser.session_parsed_state(),
+            ConnParser::Wireguard(parser) => parser.session_parsed_state(),
+            ConnParser::Smb(parser) => parser.session_parsed_state(),
+            ConnParser::Kerberos(parser) => parser.session_parsed_state(),
+            ConnParser::Postgres(parser) => parser.session_parsed_state(),
+            ConnParser::Mysql(parser) => parser.session_parsed_state(),
+            ConnParser::Redis(parser) => parser.session_parsed_state(),
+            ConnParser::Sip(parser) => parser.session_parsed_state(),
+            ConnParser::Rtp(parser) => parser.session_parsed_state(),
+            ConnParser::Mqtt(parser) => parser.session_parsed_state(),
+            ConnParser::Coap(parser) => parser.session_parsed_state(),
+            ConnParser::Modbus(parser) => parser.session_parsed_state(),
+            ConnParser::Bittorrent(parser) => parser.session_parsed_state(),
+            ConnParser::Rdp(parser) => parser.session_parsed_state(),
+            ConnParser::Vnc(parser) => parser.session_parsed_state(),
             ConnParser::Unknown => ParsingState::Stop,
         }
     }
@@ -349,6 +656,30 @@ impl ConnParser {
             ConnParser::Http(parser) => parser.body_offset(),
             ConnParser::Quic(parser) => parser.body_offset(),
             ConnParser::Ssh(parser) => parser.body_offset(),
+            ConnParser::Smtp(parser) => parser.body_offset(),
+            ConnParser::Imap(parser) => parser.body_offset(),
+            ConnParser::Pop3(parser) => parser.body_offset(),
+            ConnParser::Dhcp(parser) => parser.body_offset(),
+            ConnParser::Dhcpv6(parser) => parser.body_offset(),
+            ConnParser::Ntp(parser) => parser.body_offset(),
+            ConnParser::Mdns(parser) => parser.body_offset(),
+            ConnParser::Llmnr(parser) => parser.body_offset(),
+            ConnParser::Ssdp(parser) => parser.body_offset(),
+            ConnParser::Openvpn(parser) => parser.body_offset(),
+            ConnParser::Wireguard(parser) => parser.body_offset(),
+            ConnParser::Smb(parser) => parser.body_offset(),
+            ConnParser::Kerberos(parser) => parser.body_offset(),
+            ConnParser::Postgres(parser) => parser.body_offset(),
+            ConnParser::Mysql(parser) => parser.body_offset(),
+            ConnParser::Redis(parser) => parser.body_offset(),
+            ConnParser::Sip(parser) => parser.body_offset(),
+            ConnParser::Rtp(parser) => parser.body_offset(),
+            ConnParser::Mqtt(parser) => parser.body_offset(),
+            ConnParser::Coap(parser) => parser.body_offset(),
+            ConnParser::Modbus(parser) => parser.body_offset(),
+            ConnParser::Bittorrent(parser) => parser.body_offset(),
+            ConnParser::Rdp(parser) => parser.body_offset(),
+            ConnParser::Vnc(parser) => parser.body_offset(),
             ConnParser::Unknown => None,
         }
     }
@@ -362,6 +693,30 @@ impl ConnParser {
             ConnParser::Http(_parser) => Some("http".into()),
             ConnParser::Quic(_parser) => Some("quic".into()),
             ConnParser::Ssh(_parser) => Some("ssh".into()),
+            ConnParser::Smtp(_parser) => Some("smtp".into()),
+            ConnParser::Imap(_parser) => Some("imap".into()),
+            ConnParser::Pop3(_parser) => Some("pop3".into()),
+            ConnParser::Dhcp(_parser) => Some("dhcp".into()),
+            ConnParser::Dhcpv6(_parser) => Some("dhcpv6".into()),
+            ConnParser::Ntp(_parser) => Some("ntp".into()),
+            ConnParser::Mdns(_parser) => Some("mdns".into()),
+            ConnParser::Llmnr(_parser) => Some("llmnr".into()),
+            ConnParser::Ssdp(_parser) => Some("ssdp".into()),
+            ConnParser::Openvpn(_parser) => Some("openvpn".into()),
+            ConnParser::Wireguard(_parser) => Some("wireguard".into()),
+            ConnParser::Smb(_parser) => Some("smb".into()),
+            ConnParser::Kerberos(_parser) => Some("kerberos".into()),
+            ConnParser::Postgres(_parser) => Some("postgres".into()),
+            ConnParser::Mysql(_parser) => Some("mysql".into()),
+            ConnParser::Redis(_parser) => Some("redis".into()),
+            ConnParser::Sip(_parser) => Some("sip".into()),
+            ConnParser::Rtp(_parser) => Some("rtp".into()),
+            ConnParser::Mqtt(_parser) => Some("mqtt".into()),
+            ConnParser::Coap(_parser) => Some("coap".into()),
+            ConnParser::Modbus(_parser) => Some("modbus".into()),
+            ConnParser::Bittorrent(_parser) => Some("bittorrent".into()),
+            ConnParser::Rdp(_parser) => Some("rdp".into()),
+            ConnParser::Vnc(_parser) => Some("vnc".into()),
             ConnParser::Unknown => None,
         }
     }
@@ -373,10 +728,69 @@ impl ConnParser {
             ConnParser::Http(_) => SessionProto::Http,
             ConnParser::Quic(_) => SessionProto::Quic,
             ConnParser::Ssh(_) => SessionProto::Ssh,
+            ConnParser::Smtp(_) => SessionProto::Smtp,
+            ConnParser::Imap(_) => SessionProto::Imap,
+            ConnParser::Pop3(_) => SessionProto::Pop3,
+            ConnParser::Dhcp(_) => SessionProto::Dhcp,
+            ConnParser::Dhcpv6(_) => SessionProto::Dhcpv6,
+            ConnParser::Ntp(_) => SessionProto::Ntp,
+            ConnParser::Mdns(_) => SessionProto::Mdns,
+            ConnParser::Llmnr(_) => SessionProto::Llmnr,
+            ConnParser::Ssdp(_) => SessionProto::Ssdp,
+            ConnParser::Openvpn(_) => SessionProto::Openvpn,
+            ConnParser::Wireguard(_) => SessionProto::Wireguard,
+            ConnParser::Smb(_) => SessionProto::Smb,
+            ConnParser::Kerberos(_) => SessionProto::Kerberos,
+            ConnParser::Postgres(_) => SessionProto::Postgres,
+            ConnParser::Mysql(_) => SessionProto::Mysql,
+            ConnParser::Redis(_) => SessionProto::Redis,
+            ConnParser::Sip(_) => SessionProto::Sip,
+            ConnParser::Rtp(_) => SessionProto::Rtp,
+            ConnParser::Mqtt(_) => SessionProto::Mqtt,
+            ConnParser::Coap(_) => SessionProto::Coap,
+            ConnParser::Modbus(_) => SessionProto::Modbus,
+            ConnParser::Bittorrent(_) => SessionProto::Bittorrent,
+            ConnParser::Rdp(_) => SessionProto::Rdp,
+            ConnParser::Vnc(_) => SessionProto::Vnc,
             ConnParser::Unknown => SessionProto::Null,
         }
     }
 
+    /// Returns a parser for the protocol that the remainder of the stream has been handed to
+    /// after the current session, if any (e.g., TLS following STARTTLS).
+    pub(crate) fn upgrade(&self) -> Option<ConnParser> {
+        match self {
+            ConnParser::Smtp(parser) if parser.starttls() => {
+                Some(ConnParser::Tls(TlsParser::default()))
+            }
+            ConnParser::Imap(parser) if parser.starttls() => {
+                Some(ConnParser::Tls(TlsParser::default()))
+            }
+            ConnParser::Pop3(parser) if parser.starttls() => {
+                Some(ConnParser::Tls(TlsParser::default()))
+            }
+            ConnParser::Postgres(parser) if parser.starttls() => {
+                Some(ConnParser::Tls(TlsParser::default()))
+            }
+            ConnParser::Mysql(parser) if parser.starttls() => {
+                Some(ConnParser::Tls(TlsParser::default()))
+            }
+            ConnParser::Rdp(parser) if parser.starttls() => {
+                Some(ConnParser::Tls(TlsParser::default()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes and returns the connections announced by the parser's sessions since the last
+    /// call (e.g., the media streams negotiated by SIP).
+    pub(crate) fn drain_expected(&mut self) -> Vec<ExpectedConn> {
+        match self {
+            ConnParser::Sip(parser) => parser.drain_expected(),
+            _ => vec![],
+        }
+    }
+
     pub fn requires_parsing(filter_str: &str) -> HashSet<&'static str> {
         let mut out = hashset! {};
 
@@ -0,0 +1,187 @@
+//! Modbus/TCP message components.
+//!
+//! See the [Modbus application protocol](https://modbus.org/docs/Modbus_Application_Protocol_V1_1b3.pdf)
+//! and [Modbus messaging on TCP/IP](https://modbus.org/docs/Modbus_Messaging_Implementation_Guide_V1_0b.pdf)
+//! specifications. The addresses and quantities of the public data access functions are parsed;
+//! register and coil values are not recorded.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+
+/// Length of the MBAP header, including the unit identifier.
+pub(super) const MBAP_LEN: usize = 7;
+/// Maximum length of a Modbus PDU.
+const MAX_PDU_LEN: usize = 253;
+/// Bit set in the function code of an exception response.
+const EXCEPTION_BIT: u8 = 0x80;
+
+pub const READ_COILS: u8 = 1;
+pub const READ_DISCRETE_INPUTS: u8 = 2;
+pub const READ_HOLDING_REGISTERS: u8 = 3;
+pub const READ_INPUT_REGISTERS: u8 = 4;
+pub const WRITE_SINGLE_COIL: u8 = 5;
+pub const WRITE_SINGLE_REGISTER: u8 = 6;
+pub const DIAGNOSTICS: u8 = 8;
+pub const WRITE_MULTIPLE_COILS: u8 = 15;
+pub const WRITE_MULTIPLE_REGISTERS: u8 = 16;
+pub const WRITE_FILE_RECORD: u8 = 21;
+pub const MASK_WRITE_REGISTER: u8 = 22;
+pub const READ_WRITE_MULTIPLE_REGISTERS: u8 = 23;
+pub const ENCAPSULATED_INTERFACE: u8 = 43;
+
+/// Returns the name of a public function code (e.g., `"read_holding_registers"`).
+pub fn function_name(function: u8) -> &'static str {
+    match function {
+        READ_COILS => "read_coils",
+        READ_DISCRETE_INPUTS => "read_discrete_inputs",
+        READ_HOLDING_REGISTERS => "read_holding_registers",
+        READ_INPUT_REGISTERS => "read_input_registers",
+        WRITE_SINGLE_COIL => "write_single_coil",
+        WRITE_SINGLE_REGISTER => "write_single_register",
+        7 => "read_exception_status",
+        DIAGNOSTICS => "diagnostics",
+        11 => "get_comm_event_counter",
+        12 => "get_comm_event_log",
+        WRITE_MULTIPLE_COILS => "write_multiple_coils",
+        WRITE_MULTIPLE_REGISTERS => "write_multiple_registers",
+        17 => "report_server_id",
+        20 => "read_file_record",
+        WRITE_FILE_RECORD => "write_file_record",
+        MASK_WRITE_REGISTER => "mask_write_register",
+        READ_WRITE_MULTIPLE_REGISTERS => "read_write_multiple_registers",
+        24 => "read_fifo_queue",
+        ENCAPSULATED_INTERFACE => "encapsulated_interface_transport",
+        _ => "unknown",
+    }
+}
+
+/// A Modbus/TCP request or response.
+#[derive(Clone, Debug, Serialize)]
+pub struct ModbusMessage {
+    pub transaction_id: u16,
+    /// Unit identifier of the addressed device behind a gateway (255 if unused).
+    pub unit_id: u8,
+    /// Function code, without the exception bit.
+    pub function: u8,
+    /// Exception code of an exception response.
+    pub exception_code: Option<u8>,
+    /// Starting address of the coils or registers read or written, if applicable.
+    pub address: Option<u16>,
+    /// Number of coils or registers read or written, if applicable.
+    pub quantity: Option<u16>,
+    /// Starting address of the registers written by a read/write multiple registers request.
+    pub write_address: Option<u16>,
+    /// Number of registers written by a read/write multiple registers request.
+    pub write_quantity: Option<u16>,
+    /// Value written by a single write request or response.
+    pub value: Option<u16>,
+    /// Sub-function code of a diagnostics request, or MEI type of an encapsulated interface
+    /// transport request.
+    pub sub_function: Option<u16>,
+    /// Encoded length of the message in bytes, including the MBAP header.
+    pub length: usize,
+}
+
+impl ModbusMessage {
+    /// Parses a Modbus/TCP message sent by the client if `request`, otherwise by the server.
+    /// Returns `None` if `data` does not contain the complete message.
+    pub(super) fn parse_from(data: &[u8], request: bool) -> Result<Option<Self>> {
+        let length = match parse_mbap(data)? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        if data.len() < length {
+            return Ok(None);
+        }
+        let pdu = &data[MBAP_LEN..length];
+        let mut msg = ModbusMessage {
+            transaction_id: BigEndian::read_u16(&data[0..2]),
+            unit_id: data[6],
+            function: pdu[0] & !EXCEPTION_BIT,
+            exception_code: None,
+            address: None,
+            quantity: None,
+            write_address: None,
+            write_quantity: None,
+            value: None,
+            sub_function: None,
+            length,
+        };
+        if msg.function == 0 {
+            bail!("invalid function code 0");
+        }
+        let fields = &pdu[1..];
+        if pdu[0] & EXCEPTION_BIT != 0 {
+            if request {
+                bail!("exception in request");
+            }
+            msg.exception_code = fields.first().copied();
+            return Ok(Some(msg));
+        }
+
+        let word =
+            |i: usize| -> Option<u16> { fields.get(2 * i..2 * i + 2).map(BigEndian::read_u16) };
+        match (msg.function, request) {
+            (READ_COILS..=READ_INPUT_REGISTERS, true) => {
+                msg.address = word(0);
+                msg.quantity = word(1);
+            }
+            (WRITE_SINGLE_COIL | WRITE_SINGLE_REGISTER, _) => {
+                msg.address = word(0);
+                msg.quantity = Some(1);
+                msg.value = word(1);
+            }
+            (WRITE_MULTIPLE_COILS | WRITE_MULTIPLE_REGISTERS, _) => {
+                msg.address = word(0);
+                msg.quantity = word(1);
+            }
+            (MASK_WRITE_REGISTER, _) => {
+                msg.address = word(0);
+                msg.quantity = Some(1);
+            }
+            (READ_WRITE_MULTIPLE_REGISTERS, true) => {
+                msg.address = word(0);
+                msg.quantity = word(1);
+                msg.write_address = word(2);
+                msg.write_quantity = word(3);
+            }
+            (DIAGNOSTICS, _) => msg.sub_function = word(0),
+            (ENCAPSULATED_INTERFACE, _) => msg.sub_function = fields.first().map(|b| *b as u16),
+            _ => (),
+        }
+        Ok(Some(msg))
+    }
+
+    /// Returns `true` if the function writes coils or registers.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self.function,
+            WRITE_SINGLE_COIL
+                | WRITE_SINGLE_REGISTER
+                | WRITE_MULTIPLE_COILS
+                | WRITE_MULTIPLE_REGISTERS
+                | MASK_WRITE_REGISTER
+                | WRITE_FILE_RECORD
+                | READ_WRITE_MULTIPLE_REGISTERS
+        )
+    }
+}
+
+/// Parses the MBAP header, returning the total length of the message. Returns `None` if `data`
+/// does not contain the complete header.
+pub(super) fn parse_mbap(data: &[u8]) -> Result<Option<usize>> {
+    if data.len() < MBAP_LEN + 1 {
+        return Ok(None);
+    }
+    let protocol_id = BigEndian::read_u16(&data[2..4]);
+    if protocol_id != 0 {
+        bail!("invalid protocol identifier {}", protocol_id);
+    }
+    // Length of the unit identifier and PDU
+    let len = BigEndian::read_u16(&data[4..6]) as usize;
+    if !(2..=MAX_PDU_LEN + 1).contains(&len) {
+        bail!("invalid length {}", len);
+    }
+    Ok(Some(MBAP_LEN - 1 + len))
+}
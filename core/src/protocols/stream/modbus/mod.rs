@@ -0,0 +1,110 @@
+//! Modbus/TCP transaction parsing.
+//!
+//! ## Remarks
+//! A transaction is a request paired with its response by transaction identifier. The function
+//! code, the unit identifier, and the range of coils or registers addressed are recorded for each
+//! transaction, along with the exception code if the server rejected the request. Responses that
+//! do not match an outstanding request are recorded alone.
+//!
+//! Supervisory systems typically poll devices over a single long-lived connection, so completed
+//! transactions are retained and delivered when the connection terminates, or once a bounded
+//! number of them have been observed. Only Modbus/TCP on port 502 is parsed; Modbus RTU
+//! encapsulated over TCP and Modbus/TCP Security (TLS) are not.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed Modbus/TCP transaction contents.
+#[derive(Debug, Serialize)]
+pub struct Modbus {
+    /// Request, if observed.
+    pub request: Option<ModbusMessage>,
+    /// Response, if observed.
+    pub response: Option<ModbusMessage>,
+}
+
+impl Modbus {
+    /// Returns the transaction identifier.
+    pub fn transaction_id(&self) -> u16 {
+        self.message().map_or(0, |m| m.transaction_id)
+    }
+
+    /// Returns the unit identifier.
+    pub fn unit_id(&self) -> u8 {
+        self.message().map_or(0, |m| m.unit_id)
+    }
+
+    /// Returns the function code (e.g., 16 for Write Multiple Registers).
+    pub fn function(&self) -> u8 {
+        self.message().map_or(0, |m| m.function)
+    }
+
+    /// Returns the name of the function (e.g., `"write_multiple_registers"`).
+    pub fn function_name(&self) -> &str {
+        function_name(self.function())
+    }
+
+    /// Returns the starting address of the coils or registers read or written, or 0 if the
+    /// function does not address any.
+    pub fn address(&self) -> u16 {
+        self.messages().find_map(|m| m.address).unwrap_or(0)
+    }
+
+    /// Returns the number of coils or registers read or written, or 0 if the function does not
+    /// address any.
+    pub fn quantity(&self) -> u16 {
+        self.messages().find_map(|m| m.quantity).unwrap_or(0)
+    }
+
+    /// Returns the range of coils or registers read or written (e.g., `"100-109"`), including the
+    /// registers written by a read/write multiple registers request. Returns `""` if the function
+    /// does not address any.
+    pub fn registers(&self) -> String {
+        let ranges = self.messages().find_map(|m| {
+            m.address.zip(m.quantity).map(|read| {
+                [Some(read), m.write_address.zip(m.write_quantity)]
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, quantity)| *quantity > 0)
+                    .map(|(address, quantity)| {
+                        format!("{}-{}", address, address as u32 + quantity as u32 - 1)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+        ranges.unwrap_or_default().join(" ")
+    }
+
+    /// Returns `true` if the function writes coils or registers.
+    pub fn is_write(&self) -> bool {
+        self.message().is_some_and(|m| m.is_write())
+    }
+
+    /// Returns `true` if the server responded with an exception.
+    pub fn is_exception(&self) -> bool {
+        self.exception_code() != 0
+    }
+
+    /// Returns the exception code of the response (e.g., 2 for Illegal Data Address), or 0 if the
+    /// response was not an exception.
+    pub fn exception_code(&self) -> u8 {
+        self.response
+            .as_ref()
+            .and_then(|m| m.exception_code)
+            .unwrap_or(0)
+    }
+
+    /// Returns the request, or the response if no request was observed.
+    fn message(&self) -> Option<&ModbusMessage> {
+        self.request.as_ref().or(self.response.as_ref())
+    }
+
+    /// Returns the request and response.
+    fn messages(&self) -> impl Iterator<Item = &ModbusMessage> {
+        self.request.iter().chain(self.response.iter())
+    }
+}
@@ -0,0 +1,387 @@
+//! Modbus/TCP transaction parser.
+//!
+//! Reassembles Modbus/TCP messages and pairs each response with its request by transaction
+//! identifier.
+
+use super::message::*;
+use super::Modbus;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+/// Maximum number of completed transactions recorded before parsing stops.
+const MAX_TRANSACTIONS: usize = 64;
+/// Modbus/TCP port.
+const MODBUS_PORT: u16 = 502;
+
+#[derive(Default, Debug)]
+pub struct ModbusParser {
+    /// Maps session ID to Modbus transaction
+    sessions: HashMap<usize, Modbus>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Maps transaction identifier to the session ID of a request awaiting a response
+    pending: HashMap<u16, usize>,
+    /// Number of completed transactions
+    completed: usize,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+}
+
+impl ConnParsable for ModbusParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.dst.port() != MODBUS_PORT && pdu.ctxt.src.port() != MODBUS_PORT {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match ModbusMessage::parse_from(data, pdu.dir) {
+                Ok(Some(_)) => ProbeResult::Certain,
+                Ok(None) => ProbeResult::Unsure,
+                Err(_) => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|modbus| Session {
+            data: SessionData::Modbus(Box::new(modbus)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, modbus)| Session {
+                data: SessionData::Modbus(Box::new(modbus)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider Modbus to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl ModbusParser {
+    /// Process data segments in direction `dir`
+    fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        loop {
+            match ModbusMessage::parse_from(cur_data, dir) {
+                Ok(Some(msg)) => {
+                    log::debug!("Modbus {}", function_name(msg.function));
+                    cur_data = &cur_data[msg.length..];
+                    let session_id = match dir {
+                        true => self.process_request(msg),
+                        false => self.process_response(msg),
+                    };
+                    if self.completed >= MAX_TRANSACTIONS {
+                        return ParseResult::HeadersDone(session_id);
+                    }
+                    result = ParseResult::Continue(session_id);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    self.client_buffer.clear();
+                    self.server_buffer.clear();
+                    return ParseResult::HeadersDone(self.cnt.saturating_sub(1));
+                }
+            }
+        }
+        match dir {
+            true => self.client_buffer.extend_from_slice(cur_data),
+            false => self.server_buffer.extend_from_slice(cur_data),
+        }
+        result
+    }
+
+    fn process_request(&mut self, msg: ModbusMessage) -> usize {
+        let transaction_id = msg.transaction_id;
+        let session_id = self.insert(Modbus {
+            request: Some(msg),
+            response: None,
+        });
+        self.pending.insert(transaction_id, session_id);
+        session_id
+    }
+
+    fn process_response(&mut self, msg: ModbusMessage) -> usize {
+        self.completed += 1;
+        match self.pending.remove(&msg.transaction_id) {
+            Some(session_id) => {
+                if let Some(modbus) = self.sessions.get_mut(&session_id) {
+                    modbus.response = Some(msg);
+                }
+                session_id
+            }
+            None => self.insert(Modbus {
+                request: None,
+                response: Some(msg),
+            }),
+        }
+    }
+
+    fn insert(&mut self, modbus: Modbus) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, modbus);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{tcp, Flow};
+
+    /// Read Holding Registers request for registers 100 to 109 of unit 1.
+    const READ_REQUEST: &[u8] = b"\
+        \x00\x01\x00\x00\x00\x06\x01\x03\x00\x64\x00\x0a";
+
+    /// Read Holding Registers response.
+    const READ_RESPONSE: &[u8] = b"\
+        \x00\x01\x00\x00\x00\x17\x01\x03\x14\x01\x00\x01\x01\x01\x02\x01\
+        \x03\x01\x04\x01\x05\x01\x06\x01\x07\x01\x08\x01\x09";
+
+    /// Write Multiple Registers request for registers 200 and 201.
+    const WRITE_REQUEST: &[u8] = b"\
+        \x00\x02\x00\x00\x00\x0b\x01\x10\x00\xc8\x00\x02\x04\x12\x34\x56\
+        \x78";
+
+    /// Write Multiple Registers response.
+    const WRITE_RESPONSE: &[u8] = b"\
+        \x00\x02\x00\x00\x00\x06\x01\x10\x00\xc8\x00\x02";
+
+    /// Write Single Register request for register 9999.
+    const WRITE_SINGLE_REQUEST: &[u8] = b"\
+        \x00\x03\x00\x00\x00\x06\x01\x06\x27\x0f\x00\x01";
+
+    /// Illegal Data Address exception response to the Write Single Register request.
+    const EXCEPTION: &[u8] = b"\
+        \x00\x03\x00\x00\x00\x03\x01\x86\x02";
+
+    /// Read/Write Multiple Registers request to unit 17, reading registers 0 to 3 and writing
+    /// registers 10 and 11.
+    const READ_WRITE_REQUEST: &[u8] = b"\
+        \x00\x04\x00\x00\x00\x0f\x11\x17\x00\x00\x00\x04\x00\x0a\x00\x02\
+        \x04\x00\x01\x00\x02";
+
+    const FLOW: Flow = Flow::tcp(MODBUS_PORT);
+
+    #[test]
+    fn core_modbus_probe() {
+        let parser = ModbusParser::default();
+        assert_eq!(
+            FLOW.probe(&parser, READ_REQUEST, true),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            FLOW.probe(&parser, READ_RESPONSE, false),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            FLOW.probe(&parser, &READ_REQUEST[..6], true),
+            ProbeResult::Unsure
+        );
+        assert_eq!(
+            parser.probe(&tcp(READ_REQUEST, 5020, true)),
+            ProbeResult::NotForUs
+        );
+        // Exception sent by the client
+        assert_eq!(FLOW.probe(&parser, EXCEPTION, true), ProbeResult::NotForUs);
+        // Non-zero protocol identifier
+        assert_eq!(
+            FLOW.probe(
+                &parser,
+                b"\x00\x01\x00\x01\x00\x06\x01\x03\x00\x64\x00\x0a",
+                true
+            ),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_modbus_transactions() {
+        let mut parser = ModbusParser::default();
+        // Pipelined requests
+        let requests = [READ_REQUEST, WRITE_REQUEST].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &requests, true),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, READ_RESPONSE, false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, WRITE_RESPONSE, false),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, WRITE_SINGLE_REQUEST, true),
+            ParseResult::Continue(2)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, EXCEPTION, false),
+            ParseResult::Continue(2)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, READ_WRITE_REQUEST, true),
+            ParseResult::Continue(3)
+        );
+        // Response without an outstanding request
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                b"\x00\x09\x00\x00\x00\x04\x01\x01\x01\x05",
+                false
+            ),
+            ParseResult::Continue(4)
+        );
+
+        let read = &parser.sessions[&0];
+        assert_eq!(read.transaction_id(), 1);
+        assert_eq!(read.unit_id(), 1);
+        assert_eq!(read.function_name(), "read_holding_registers");
+        assert_eq!(read.address(), 100);
+        assert_eq!(read.quantity(), 10);
+        assert_eq!(read.registers(), "100-109");
+        assert!(!read.is_write());
+        assert!(!read.is_exception());
+        assert_eq!(read.response.as_ref().unwrap().length, READ_RESPONSE.len());
+
+        let write = &parser.sessions[&1];
+        assert_eq!(write.function(), WRITE_MULTIPLE_REGISTERS);
+        assert_eq!(write.registers(), "200-201");
+        assert!(write.is_write());
+        assert!(write.response.is_some());
+
+        let exception = &parser.sessions[&2];
+        assert_eq!(exception.function_name(), "write_single_register");
+        assert_eq!(exception.registers(), "9999-9999");
+        assert_eq!(exception.request.as_ref().unwrap().value, Some(1));
+        assert!(exception.is_exception());
+        assert_eq!(exception.exception_code(), 2);
+
+        let read_write = &parser.sessions[&3];
+        assert_eq!(read_write.unit_id(), 17);
+        assert_eq!(read_write.registers(), "0-3 10-11");
+        assert!(read_write.is_write());
+        assert!(read_write.response.is_none());
+
+        let unmatched = &parser.sessions[&4];
+        assert!(unmatched.request.is_none());
+        assert_eq!(unmatched.transaction_id(), 9);
+        assert_eq!(unmatched.function_name(), "read_coils");
+        assert_eq!(unmatched.registers(), "");
+    }
+
+    #[test]
+    fn core_modbus_split() {
+        let mut parser = ModbusParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, READ_REQUEST, true),
+            ParseResult::Continue(0)
+        );
+        // Split within the MBAP header, then within the register values
+        assert_eq!(
+            FLOW.parse(&mut parser, &READ_RESPONSE[..5], false),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &READ_RESPONSE[5..15], false),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions[&0].response.is_none());
+        let rest = [&READ_RESPONSE[15..], &WRITE_REQUEST[..3]].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &rest, false),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.sessions[&0].response.is_some());
+        assert_eq!(parser.server_buffer, &WRITE_REQUEST[..3]);
+    }
+
+    #[test]
+    fn core_modbus_truncated() {
+        let mut parser = ModbusParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &WRITE_REQUEST[..WRITE_REQUEST.len() - 1], true),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+
+        // Length too short for a function code
+        let mut parser = ModbusParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, READ_REQUEST, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x00\x01\x00\x00\x00\x01\x01\x03", false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.server_buffer.is_empty());
+
+        // Function code 0
+        let mut parser = ModbusParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x00\x01\x00\x00\x00\x02\x01\x00", true),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.sessions.is_empty());
+    }
+}
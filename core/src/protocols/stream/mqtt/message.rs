@@ -0,0 +1,362 @@
+//! MQTT control packet components.
+//!
+//! See the [MQTT 3.1.1](https://docs.oasis-open.org/mqtt/mqtt/v3.1.1/mqtt-v3.1.1.html) and
+//! [MQTT 5.0](https://docs.oasis-open.org/mqtt/mqtt/v5.0/mqtt-v5.0.html) specifications. MQTT 3.1
+//! (protocol level 3) is parsed as 3.1.1. Properties introduced in MQTT 5.0 are skipped, and
+//! message payloads and passwords are not recorded.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+
+pub const CONNECT: u8 = 1;
+pub const CONNACK: u8 = 2;
+pub const PUBLISH: u8 = 3;
+pub const PUBACK: u8 = 4;
+pub const PUBREC: u8 = 5;
+pub const PUBREL: u8 = 6;
+pub const PUBCOMP: u8 = 7;
+pub const SUBSCRIBE: u8 = 8;
+pub const SUBACK: u8 = 9;
+pub const UNSUBSCRIBE: u8 = 10;
+pub const UNSUBACK: u8 = 11;
+pub const PINGREQ: u8 = 12;
+pub const PINGRESP: u8 = 13;
+pub const DISCONNECT: u8 = 14;
+pub const AUTH: u8 = 15;
+
+/// Protocol level of MQTT 5.0.
+pub const MQTT_V5: u8 = 5;
+
+/// Maximum number of topic filters recorded per SUBSCRIBE or UNSUBSCRIBE packet.
+const MAX_TOPICS: usize = 16;
+
+/// Returns the name of a control packet type (e.g., `"PUBLISH"`).
+pub fn packet_type_name(packet_type: u8) -> &'static str {
+    match packet_type {
+        CONNECT => "CONNECT",
+        CONNACK => "CONNACK",
+        PUBLISH => "PUBLISH",
+        PUBACK => "PUBACK",
+        PUBREC => "PUBREC",
+        PUBREL => "PUBREL",
+        PUBCOMP => "PUBCOMP",
+        SUBSCRIBE => "SUBSCRIBE",
+        SUBACK => "SUBACK",
+        UNSUBSCRIBE => "UNSUBSCRIBE",
+        UNSUBACK => "UNSUBACK",
+        PINGREQ => "PINGREQ",
+        PINGRESP => "PINGRESP",
+        DISCONNECT => "DISCONNECT",
+        AUTH => "AUTH",
+        _ => "UNKNOWN",
+    }
+}
+
+/// An MQTT control packet.
+#[derive(Clone, Debug, Serialize)]
+pub struct MqttPacket {
+    /// Control packet type (e.g., 3 for PUBLISH).
+    pub packet_type: u8,
+    /// Flags of the fixed header.
+    pub flags: u8,
+    /// Packet identifier, if the packet type carries one.
+    pub packet_id: Option<u16>,
+    /// Encoded length of the packet in bytes, including the fixed header.
+    pub length: usize,
+    /// Type-specific packet contents.
+    pub data: MqttData,
+}
+
+/// Type-specific MQTT packet contents.
+#[derive(Clone, Debug, Serialize)]
+pub enum MqttData {
+    Connect(MqttConnect),
+    ConnAck {
+        session_present: bool,
+        /// Connect return code (3.1.1) or reason code (5.0).
+        return_code: u8,
+    },
+    Publish {
+        topic: String,
+        /// Length of the application message in bytes.
+        payload_len: usize,
+    },
+    Subscribe(Vec<MqttSubscription>),
+    /// Return codes (3.1.1) or reason codes (5.0), one per topic filter.
+    SubAck(Vec<u8>),
+    Unsubscribe(Vec<String>),
+    /// Acknowledgement (PUBACK, PUBREC, PUBREL, PUBCOMP, UNSUBACK), DISCONNECT, or AUTH packet,
+    /// with its reason code, if present.
+    Ack(Option<u8>),
+    /// Packet without contents (PINGREQ, PINGRESP).
+    Empty,
+}
+
+/// Contents of a CONNECT packet.
+#[derive(Clone, Debug, Serialize)]
+pub struct MqttConnect {
+    /// Protocol name (`"MQTT"`, or `"MQIsdp"` for MQTT 3.1).
+    pub protocol_name: String,
+    /// Protocol level (e.g., 4 for MQTT 3.1.1, 5 for MQTT 5.0).
+    pub protocol_level: u8,
+    pub client_id: String,
+    pub username: Option<String>,
+    /// `true` if a password was sent.
+    pub has_password: bool,
+    pub will_topic: Option<String>,
+    pub clean_session: bool,
+    /// Keep alive interval in seconds.
+    pub keep_alive: u16,
+}
+
+/// A topic filter of a SUBSCRIBE packet.
+#[derive(Clone, Debug, Serialize)]
+pub struct MqttSubscription {
+    pub topic: String,
+    /// Maximum QoS requested.
+    pub qos: u8,
+}
+
+impl MqttPacket {
+    /// Parses a control packet of a connection using protocol level `version`. Returns `None` if
+    /// `data` does not contain the complete packet.
+    pub(super) fn parse_from(data: &[u8], version: u8) -> Result<Option<Self>> {
+        let (header_len, remaining) = match parse_fixed_header(data)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let length = header_len + remaining;
+        if data.len() < length {
+            return Ok(None);
+        }
+        let packet_type = data[0] >> 4;
+        let flags = data[0] & 0x0f;
+        let mut reader = Reader::new(&data[header_len..length]);
+        let mut packet_id = None;
+        let data = match packet_type {
+            CONNECT => MqttData::Connect(MqttConnect::parse_from(&mut reader)?),
+            CONNACK => MqttData::ConnAck {
+                session_present: reader.u8()? & 0x01 != 0,
+                return_code: reader.u8()?,
+            },
+            PUBLISH => {
+                let topic = reader.string()?;
+                if (flags >> 1) & 0x03 > 0 {
+                    packet_id = Some(reader.u16()?);
+                }
+                reader.properties(version)?;
+                MqttData::Publish {
+                    topic,
+                    payload_len: reader.len(),
+                }
+            }
+            PUBACK | PUBREC | PUBREL | PUBCOMP => {
+                packet_id = Some(reader.u16()?);
+                MqttData::Ack(reader.reason_code())
+            }
+            SUBSCRIBE => {
+                packet_id = Some(reader.u16()?);
+                reader.properties(version)?;
+                let mut topics = vec![];
+                while !reader.is_empty() && topics.len() < MAX_TOPICS {
+                    topics.push(MqttSubscription {
+                        topic: reader.string()?,
+                        qos: reader.u8()? & 0x03,
+                    });
+                }
+                MqttData::Subscribe(topics)
+            }
+            SUBACK => {
+                packet_id = Some(reader.u16()?);
+                reader.properties(version)?;
+                MqttData::SubAck(reader.take(reader.len())?.to_vec())
+            }
+            UNSUBSCRIBE => {
+                packet_id = Some(reader.u16()?);
+                reader.properties(version)?;
+                let mut topics = vec![];
+                while !reader.is_empty() && topics.len() < MAX_TOPICS {
+                    topics.push(reader.string()?);
+                }
+                MqttData::Unsubscribe(topics)
+            }
+            UNSUBACK => {
+                packet_id = Some(reader.u16()?);
+                if version >= MQTT_V5 {
+                    reader.properties(version)?;
+                }
+                MqttData::Ack(reader.reason_code())
+            }
+            DISCONNECT | AUTH => MqttData::Ack(reader.reason_code()),
+            PINGREQ | PINGRESP => MqttData::Empty,
+            _ => bail!("invalid control packet type {}", packet_type),
+        };
+        Ok(Some(MqttPacket {
+            packet_type,
+            flags,
+            packet_id,
+            length,
+            data,
+        }))
+    }
+
+    /// Parses the header of a PUBLISH packet that is too large to buffer, without its payload.
+    pub(super) fn parse_publish_header(data: &[u8], version: u8) -> Option<Self> {
+        if data.first().map(|b| b >> 4) != Some(PUBLISH) {
+            return None;
+        }
+        let (header_len, remaining) = parse_fixed_header(data).ok()??;
+        let flags = data[0] & 0x0f;
+        let mut reader = Reader::new(&data[header_len..]);
+        let topic = reader.string().ok()?;
+        let packet_id = match (flags >> 1) & 0x03 {
+            0 => None,
+            _ => Some(reader.u16().ok()?),
+        };
+        reader.properties(version).ok()?;
+        let payload_len = remaining.checked_sub(data.len() - header_len - reader.len())?;
+        Some(MqttPacket {
+            packet_type: PUBLISH,
+            flags,
+            packet_id,
+            length: header_len + remaining,
+            data: MqttData::Publish { topic, payload_len },
+        })
+    }
+
+    /// Returns the QoS level of a PUBLISH packet.
+    pub fn qos(&self) -> u8 {
+        (self.flags >> 1) & 0x03
+    }
+
+    /// Returns `true` if the RETAIN flag of a PUBLISH packet is set.
+    pub fn retain(&self) -> bool {
+        self.packet_type == PUBLISH && self.flags & 0x01 != 0
+    }
+
+    /// Returns `true` if the packet is acknowledged by the receiver.
+    pub fn expects_ack(&self) -> bool {
+        match self.packet_type {
+            CONNECT | SUBSCRIBE | UNSUBSCRIBE => true,
+            PUBLISH => self.qos() > 0,
+            _ => false,
+        }
+    }
+}
+
+impl MqttConnect {
+    fn parse_from(reader: &mut Reader) -> Result<Self> {
+        let protocol_name = reader.string()?;
+        if protocol_name != "MQTT" && protocol_name != "MQIsdp" {
+            bail!("invalid protocol name {}", protocol_name);
+        }
+        let protocol_level = reader.u8()?;
+        let flags = reader.u8()?;
+        let keep_alive = reader.u16()?;
+        reader.properties(protocol_level)?;
+        let client_id = reader.string()?;
+        let mut will_topic = None;
+        if flags & 0x04 != 0 {
+            reader.properties(protocol_level)?;
+            will_topic = Some(reader.string()?);
+            let len = reader.u16()? as usize;
+            reader.take(len)?;
+        }
+        let username = match flags & 0x80 {
+            0 => None,
+            _ => Some(reader.string()?),
+        };
+        Ok(MqttConnect {
+            protocol_name,
+            protocol_level,
+            client_id,
+            username,
+            has_password: flags & 0x40 != 0,
+            will_topic,
+            clean_session: flags & 0x02 != 0,
+            keep_alive,
+        })
+    }
+}
+
+/// Parses the fixed header, returning its length and the remaining length of the packet.
+/// Returns `None` if `data` does not contain the complete fixed header.
+pub(super) fn parse_fixed_header(data: &[u8]) -> Result<Option<(usize, usize)>> {
+    let mut remaining = 0;
+    for i in 0..4 {
+        let byte = match data.get(i + 1) {
+            Some(byte) => *byte,
+            None => return Ok(None),
+        };
+        remaining |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((i + 2, remaining)));
+        }
+    }
+    bail!("invalid remaining length")
+}
+
+/// Reads big-endian integers and strings from a packet.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.data.len() < n {
+            bail!("truncated packet");
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(BigEndian::read_u16(self.take(2)?))
+    }
+
+    /// Reads a length-prefixed UTF-8 string.
+    fn string(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    /// Skips the properties of an MQTT 5.0 packet.
+    fn properties(&mut self, version: u8) -> Result<()> {
+        if version < MQTT_V5 {
+            return Ok(());
+        }
+        let mut len = 0;
+        for i in 0..4 {
+            let byte = self.u8()?;
+            len |= ((byte & 0x7f) as usize) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.take(len)?;
+                return Ok(());
+            }
+        }
+        bail!("invalid property length")
+    }
+
+    /// Reads the optional reason code of an MQTT 5.0 acknowledgement.
+    fn reason_code(&mut self) -> Option<u8> {
+        self.u8().ok()
+    }
+}
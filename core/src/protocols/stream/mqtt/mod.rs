@@ -0,0 +1,118 @@
+//! MQTT message parsing.
+//!
+//! ## Remarks
+//! The parser records each CONNECT, PUBLISH, SUBSCRIBE, UNSUBSCRIBE, and DISCONNECT packet as a
+//! separate session, paired with its acknowledgement (CONNACK, PUBACK or PUBREC, SUBACK, or
+//! UNSUBACK), if any. Messages are published in both directions, and each session is tagged with
+//! the client identifier and username of the connection. Keep-alive packets and the later steps
+//! of the QoS 2 handshake are not recorded. Application message payloads and passwords are not
+//! recorded.
+//!
+//! MQTT connections are often long-lived, so completed messages are retained and delivered when
+//! the connection terminates, or once a bounded number of them have been observed. MQTT over
+//! WebSockets and over TLS (port 8883) are not parsed.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed MQTT message and acknowledgement.
+#[derive(Debug, Serialize)]
+pub struct Mqtt {
+    /// Protocol level of the connection (e.g., 4 for MQTT 3.1.1, 5 for MQTT 5.0).
+    pub version: u8,
+    /// Client identifier of the connection, if the CONNECT packet was observed.
+    pub client_id: Option<String>,
+    /// Username of the connection, if the CONNECT packet was observed and carried one.
+    pub username: Option<String>,
+    /// Control packet.
+    pub packet: MqttPacket,
+    /// Acknowledgement, if observed.
+    pub ack: Option<MqttPacket>,
+}
+
+impl Mqtt {
+    /// Returns the control packet type (e.g., `"PUBLISH"`).
+    pub fn packet_type(&self) -> &str {
+        packet_type_name(self.packet.packet_type)
+    }
+
+    /// Returns the client identifier of the connection, or `""` if it was not observed.
+    pub fn client_id(&self) -> &str {
+        self.client_id.as_deref().unwrap_or("")
+    }
+
+    /// Returns the username of the connection, or `""` if there is none.
+    pub fn username(&self) -> &str {
+        self.username.as_deref().unwrap_or("")
+    }
+
+    /// Returns the topic name of a PUBLISH packet, the first topic filter of a SUBSCRIBE or
+    /// UNSUBSCRIBE packet, or the will topic of a CONNECT packet. Returns `""` otherwise.
+    pub fn topic(&self) -> &str {
+        match &self.packet.data {
+            MqttData::Publish { topic, .. } => topic,
+            MqttData::Subscribe(topics) => topics.first().map_or("", |s| &s.topic),
+            MqttData::Unsubscribe(topics) => topics.first().map_or("", |t| t),
+            MqttData::Connect(connect) => connect.will_topic.as_deref().unwrap_or(""),
+            _ => "",
+        }
+    }
+
+    /// Returns all topic names or topic filters of the packet, separated by spaces.
+    pub fn topics(&self) -> String {
+        match &self.packet.data {
+            MqttData::Subscribe(topics) => topics
+                .iter()
+                .map(|s| s.topic.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            MqttData::Unsubscribe(topics) => topics.join(" "),
+            _ => self.topic().to_owned(),
+        }
+    }
+
+    /// Returns the QoS level of a PUBLISH packet, or the highest QoS level requested by a
+    /// SUBSCRIBE packet. Returns 0 otherwise.
+    pub fn qos(&self) -> u8 {
+        match &self.packet.data {
+            MqttData::Publish { .. } => self.packet.qos(),
+            MqttData::Subscribe(topics) => topics.iter().map(|s| s.qos).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Returns `true` if the packet is a PUBLISH packet with the RETAIN flag set.
+    pub fn retain(&self) -> bool {
+        self.packet.retain()
+    }
+
+    /// Returns the packet identifier, or 0 if the packet does not carry one.
+    pub fn packet_id(&self) -> u16 {
+        self.packet.packet_id.unwrap_or(0)
+    }
+
+    /// Returns the return or reason code of the acknowledgement (the first, for a SUBACK), or of
+    /// a DISCONNECT packet. Returns 0 (success) if there is none.
+    pub fn return_code(&self) -> u8 {
+        let packet = self.ack.as_ref().unwrap_or(&self.packet);
+        match &packet.data {
+            MqttData::ConnAck { return_code, .. } => *return_code,
+            MqttData::SubAck(codes) => codes.first().copied().unwrap_or(0),
+            MqttData::Ack(code) => code.unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Returns the length of the application message of a PUBLISH packet in bytes, or 0 if the
+    /// packet is not a PUBLISH packet.
+    pub fn payload_len(&self) -> usize {
+        match &self.packet.data {
+            MqttData::Publish { payload_len, .. } => *payload_len,
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,513 @@
+//! MQTT message parser.
+//!
+//! Reassembles control packets in both directions, and pairs each acknowledgement with the
+//! outstanding packet that it acknowledges.
+
+use super::message::*;
+use super::Mqtt;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+/// Maximum number of completed messages recorded before parsing stops.
+const MAX_MESSAGES: usize = 64;
+/// Upper bound on the size of a buffered control packet.
+const MAX_BUFFER_LEN: usize = 1 << 16;
+
+#[derive(Default, Debug)]
+pub struct MqttParser {
+    /// Maps session ID to MQTT message
+    sessions: HashMap<usize, Mqtt>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Protocol level of the connection, if the CONNECT packet was observed
+    version: Option<u8>,
+    client_id: Option<String>,
+    username: Option<String>,
+    /// Session ID of the CONNECT packet awaiting a CONNACK
+    connect: Option<usize>,
+    /// Maps direction and packet identifier to the session ID of a packet awaiting an
+    /// acknowledgement
+    pending: HashMap<(bool, u16), usize>,
+    /// Number of completed messages
+    completed: usize,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// Remaining bytes of a large client packet that are not buffered
+    client_skip: usize,
+    /// Remaining bytes of a large server packet that are not buffered
+    server_skip: usize,
+}
+
+impl ConnParsable for MqttParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+        if !pdu.dir {
+            // The client speaks first
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            // CONNECT packet with a valid protocol name
+            if data[0] != CONNECT << 4 {
+                return ProbeResult::NotForUs;
+            }
+            match parse_fixed_header(data) {
+                Ok(Some((header_len, _))) => {
+                    let name = &data[header_len..];
+                    if name.starts_with(b"\x00\x04MQTT") || name.starts_with(b"\x00\x06MQIsdp") {
+                        ProbeResult::Certain
+                    } else if name.len() < 8 {
+                        ProbeResult::Unsure
+                    } else {
+                        ProbeResult::NotForUs
+                    }
+                }
+                Ok(None) => ProbeResult::Unsure,
+                Err(_) => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|mqtt| Session {
+            data: SessionData::Mqtt(Box::new(mqtt)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, mqtt)| Session {
+                data: SessionData::Mqtt(Box::new(mqtt)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider MQTT to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl MqttParser {
+    /// Process data segments in direction `dir`
+    fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let skip = match dir {
+            true => &mut self.client_skip,
+            false => &mut self.server_skip,
+        };
+        let skipped = (*skip).min(data.len());
+        *skip -= skipped;
+        let data = &data[skipped..];
+
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        while !cur_data.is_empty() {
+            let version = self.version.unwrap_or(4);
+            let packet = match MqttPacket::parse_from(cur_data, version) {
+                Ok(Some(packet)) => {
+                    cur_data = &cur_data[packet.length..];
+                    packet
+                }
+                Ok(None) if cur_data.len() > MAX_BUFFER_LEN => {
+                    match MqttPacket::parse_publish_header(cur_data, version) {
+                        Some(packet) => {
+                            let skip = packet.length - cur_data.len();
+                            match dir {
+                                true => self.client_skip = skip,
+                                false => self.server_skip = skip,
+                            }
+                            cur_data = &[];
+                            packet
+                        }
+                        None => {
+                            log::debug!("MQTT packet exceeds maximum buffer size");
+                            return self.stop();
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return self.stop();
+                }
+            };
+            log::debug!("MQTT {}", packet_type_name(packet.packet_type));
+            match self.process_packet(packet, dir) {
+                Some(ParseResult::HeadersDone(session_id)) => {
+                    return ParseResult::HeadersDone(session_id);
+                }
+                Some(res) => result = res,
+                None => (),
+            }
+        }
+        match dir {
+            true => self.client_buffer.extend_from_slice(cur_data),
+            false => self.server_buffer.extend_from_slice(cur_data),
+        }
+        result
+    }
+
+    /// Records a control packet sent in direction `dir`. Returns `None` if the packet is not
+    /// recorded.
+    fn process_packet(&mut self, packet: MqttPacket, dir: bool) -> Option<ParseResult> {
+        let session_id = match packet.packet_type {
+            CONNECT => {
+                if let MqttData::Connect(connect) = &packet.data {
+                    self.version = Some(connect.protocol_level);
+                    self.client_id = Some(connect.client_id.clone());
+                    self.username = connect.username.clone();
+                }
+                let session_id = self.insert(packet);
+                self.connect = Some(session_id);
+                return Some(ParseResult::Continue(session_id));
+            }
+            PUBLISH | SUBSCRIBE | UNSUBSCRIBE | DISCONNECT => {
+                let pending = packet.expects_ack().then_some(packet.packet_id).flatten();
+                let session_id = self.insert(packet);
+                match pending {
+                    Some(packet_id) => {
+                        self.pending.insert((dir, packet_id), session_id);
+                        return Some(ParseResult::Continue(session_id));
+                    }
+                    None => session_id,
+                }
+            }
+            CONNACK => {
+                let session_id = self.connect.take()?;
+                self.sessions.get_mut(&session_id)?.ack = Some(packet);
+                session_id
+            }
+            PUBACK | PUBREC | SUBACK | UNSUBACK => {
+                let packet_id = packet.packet_id?;
+                let session_id = self.pending.remove(&(!dir, packet_id))?;
+                self.sessions.get_mut(&session_id)?.ack = Some(packet);
+                session_id
+            }
+            _ => return None,
+        };
+        self.completed += 1;
+        if self.completed >= MAX_MESSAGES {
+            return Some(ParseResult::HeadersDone(session_id));
+        }
+        Some(ParseResult::Continue(session_id))
+    }
+
+    /// Stops parsing, reporting the most recent session.
+    fn stop(&mut self) -> ParseResult {
+        self.client_buffer.clear();
+        self.server_buffer.clear();
+        ParseResult::HeadersDone(self.cnt.saturating_sub(1))
+    }
+
+    fn insert(&mut self, packet: MqttPacket) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(
+            session_id,
+            Mqtt {
+                version: self.version.unwrap_or(4),
+                client_id: self.client_id.clone(),
+                username: self.username.clone(),
+                packet,
+                ack: None,
+            },
+        );
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    /// MQTT 3.1.1 CONNECT from client `sensor-17` with username `iot`.
+    const CONNECT: &[u8] = b"\
+        \x10\x22\x00\x04\x4d\x51\x54\x54\x04\xc2\x00\x3c\x00\x09\x73\x65\
+        \x6e\x73\x6f\x72\x2d\x31\x37\x00\x03\x69\x6f\x74\x00\x06\x73\x65\
+        \x63\x72\x65\x74";
+
+    /// CONNACK accepting the connection.
+    const CONNACK: &[u8] = b"\
+        \x20\x02\x00\x00";
+
+    /// SUBSCRIBE to `home/+/temp` (QoS 1) and `home/alarm` (QoS 0).
+    const SUBSCRIBE: &[u8] = b"\
+        \x82\x1d\x00\x01\x00\x0b\x68\x6f\x6d\x65\x2f\x2b\x2f\x74\x65\x6d\
+        \x70\x01\x00\x0a\x68\x6f\x6d\x65\x2f\x61\x6c\x61\x72\x6d\x00";
+
+    /// SUBACK granting QoS 1 and QoS 0.
+    const SUBACK: &[u8] = b"\
+        \x90\x04\x00\x01\x01\x00";
+
+    /// QoS 1 PUBLISH to `home/kitchen/temp` sent by the broker.
+    const PUBLISH_QOS1: &[u8] = b"\
+        \x32\x19\x00\x11\x68\x6f\x6d\x65\x2f\x6b\x69\x74\x63\x68\x65\x6e\
+        \x2f\x74\x65\x6d\x70\x00\x07\x32\x31\x2e\x35";
+
+    /// PUBACK sent by the client.
+    const PUBACK: &[u8] = b"\
+        \x40\x02\x00\x07";
+
+    /// Retained QoS 0 PUBLISH to `home/status`.
+    const PUBLISH_RETAIN: &[u8] = b"\
+        \x31\x13\x00\x0b\x68\x6f\x6d\x65\x2f\x73\x74\x61\x74\x75\x73\x6f\
+        \x6e\x6c\x69\x6e\x65";
+
+    /// DISCONNECT.
+    const DISCONNECT: &[u8] = b"\
+        \xe0\x00";
+
+    /// MQTT 5.0 CONNECT from client `gw-01` with a session expiry interval property.
+    const CONNECT_V5: &[u8] = b"\
+        \x10\x17\x00\x04\x4d\x51\x54\x54\x05\x02\x00\x1e\x05\x11\x00\x00\
+        \x0e\x10\x00\x05\x67\x77\x2d\x30\x31";
+
+    /// MQTT 5.0 CONNACK rejecting the connection (0x87, not authorized).
+    const CONNACK_V5: &[u8] = b"\
+        \x20\x03\x00\x87\x00";
+
+    const FLOW: Flow = Flow::tcp(1883);
+
+    #[test]
+    fn core_mqtt_probe() {
+        let parser = MqttParser::default();
+        assert_eq!(FLOW.probe(&parser, CONNECT, true), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, CONNECT_V5, true), ProbeResult::Certain);
+        assert_eq!(
+            FLOW.probe(&parser, &CONNECT[..4], true),
+            ProbeResult::Unsure
+        );
+        assert_eq!(FLOW.probe(&parser, CONNACK, false), ProbeResult::NotForUs);
+        assert_eq!(FLOW.probe(&parser, SUBSCRIBE, true), ProbeResult::NotForUs);
+        assert_eq!(
+            FLOW.probe(&parser, b"\x10\x0a\x00\x04AMQP\x00\x00\x00\x00", true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_mqtt_session() {
+        let mut parser = MqttParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, CONNECT, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, CONNACK, false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, SUBSCRIBE, true),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, SUBACK, false),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, PUBLISH_QOS1, false),
+            ParseResult::Continue(2)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, PUBACK, true),
+            ParseResult::Continue(2)
+        );
+        let tail = [PUBLISH_RETAIN, DISCONNECT].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &tail, true),
+            ParseResult::Continue(4)
+        );
+
+        let connect = &parser.sessions[&0];
+        assert_eq!(connect.packet_type(), "CONNECT");
+        assert_eq!(connect.version, 4);
+        assert_eq!(connect.client_id(), "sensor-17");
+        assert_eq!(connect.username(), "iot");
+        assert_eq!(connect.return_code(), 0);
+        match &connect.packet.data {
+            MqttData::Connect(c) => {
+                assert!(c.has_password);
+                assert!(c.clean_session);
+                assert_eq!(c.keep_alive, 60);
+            }
+            _ => panic!("Expected CONNECT packet"),
+        }
+
+        let subscribe = &parser.sessions[&1];
+        assert_eq!(subscribe.packet_type(), "SUBSCRIBE");
+        assert_eq!(subscribe.topic(), "home/+/temp");
+        assert_eq!(subscribe.topics(), "home/+/temp home/alarm");
+        assert_eq!(subscribe.qos(), 1);
+        assert_eq!(subscribe.packet_id(), 1);
+        assert!(subscribe.ack.is_some());
+
+        let publish = &parser.sessions[&2];
+        assert_eq!(publish.topic(), "home/kitchen/temp");
+        assert_eq!(publish.client_id(), "sensor-17");
+        assert_eq!(publish.qos(), 1);
+        assert_eq!(publish.packet_id(), 7);
+        assert_eq!(publish.payload_len(), 4);
+        assert_eq!(
+            packet_type_name(publish.ack.as_ref().unwrap().packet_type),
+            "PUBACK"
+        );
+
+        let retained = &parser.sessions[&3];
+        assert!(retained.retain());
+        assert_eq!(retained.qos(), 0);
+        assert_eq!(retained.payload_len(), 6);
+        assert!(retained.ack.is_none());
+        assert_eq!(parser.sessions[&4].packet_type(), "DISCONNECT");
+    }
+
+    #[test]
+    fn core_mqtt_v5() {
+        let mut parser = MqttParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, CONNECT_V5, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, CONNACK_V5, false),
+            ParseResult::Continue(0)
+        );
+        let connect = &parser.sessions[&0];
+        assert_eq!(connect.version, 5);
+        assert_eq!(connect.client_id(), "gw-01");
+        assert_eq!(connect.username(), "");
+        assert_eq!(connect.return_code(), 0x87);
+    }
+
+    #[test]
+    fn core_mqtt_split() {
+        let mut parser = MqttParser::default();
+        // Split within the remaining length, then within the client identifier
+        let data = [CONNECT, SUBSCRIBE].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &data[..1], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &data[1..18], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &data[18..40], true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &data[40..], true),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(parser.sessions[&0].client_id(), "sensor-17");
+        assert_eq!(parser.sessions[&1].topics(), "home/+/temp home/alarm");
+
+        // PUBLISH too large to buffer is recorded from its header, and the rest is skipped
+        let mut publish = vec![0x30, 0xec, 0xa2, 0x04, 0x00, 0x05];
+        publish.extend_from_slice(b"files");
+        publish.resize(70_000, 0xaa);
+        assert_eq!(
+            FLOW.parse(&mut parser, &publish[..MAX_BUFFER_LEN], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                &publish[MAX_BUFFER_LEN..MAX_BUFFER_LEN + 10],
+                true
+            ),
+            ParseResult::Continue(2)
+        );
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                &[&publish[MAX_BUFFER_LEN + 10..], DISCONNECT].concat(),
+                true
+            ),
+            ParseResult::Continue(3)
+        );
+        assert_eq!(parser.sessions[&2].topic(), "files");
+        assert_eq!(parser.sessions[&2].payload_len(), 70_000 - 11);
+        assert_eq!(parser.sessions[&3].packet_type(), "DISCONNECT");
+    }
+
+    #[test]
+    fn core_mqtt_truncated() {
+        let mut parser = MqttParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &CONNECT[..CONNECT.len() - 1], true),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+
+        // Remaining length too short for the CONNACK contents
+        let mut parser = MqttParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, CONNECT, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x20\x01\x00", false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.sessions[&0].ack.is_none());
+
+        // Remaining length longer than four bytes
+        let mut parser = MqttParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x30\xff\xff\xff\xff\x01", true),
+            ParseResult::HeadersDone(0)
+        );
+    }
+}
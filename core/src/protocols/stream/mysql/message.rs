@@ -0,0 +1,239 @@
+//! MySQL message components.
+//!
+//! See the [connection phase
+//! packets](https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase_packets.html).
+//! Integers are little-endian, and strings are NUL-terminated or length-encoded.
+
+use anyhow::{bail, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Serialize;
+
+/// Length of the packet header (3-byte payload length and 1-byte sequence ID).
+pub(super) const HDR_LEN: usize = 4;
+/// Initial handshake protocol version.
+pub(super) const PROTOCOL_V10: u8 = 0x0a;
+/// Payload length of an SSLRequest, which is a truncated HandshakeResponse41.
+pub(super) const SSL_REQUEST_LEN: usize = 32;
+
+pub const OK_PACKET: u8 = 0x00;
+pub const ERR_PACKET: u8 = 0xff;
+pub const AUTH_SWITCH_REQUEST: u8 = 0xfe;
+pub const AUTH_MORE_DATA: u8 = 0x01;
+
+pub const CLIENT_CONNECT_WITH_DB: u32 = 0x0000_0008;
+pub const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+pub const CLIENT_SSL: u32 = 0x0000_0800;
+pub const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+pub const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+pub const CLIENT_CONNECT_ATTRS: u32 = 0x0010_0000;
+pub const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 0x0020_0000;
+
+/// A MySQL ERR packet.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MySqlError {
+    pub code: u16,
+    /// SQL state (e.g., `28000`), absent in errors sent before the handshake.
+    pub sql_state: Option<String>,
+    pub message: String,
+}
+
+impl MySqlError {
+    /// Parses an ERR packet payload, including the header byte.
+    pub(super) fn parse_from(payload: &[u8]) -> Result<Self> {
+        if payload.len() < 3 || payload[0] != ERR_PACKET {
+            bail!("not an ERR packet");
+        }
+        let code = LittleEndian::read_u16(&payload[1..3]);
+        let (sql_state, message) = match payload.get(3) {
+            Some(b'#') if payload.len() >= 9 => (
+                Some(String::from_utf8_lossy(&payload[4..9]).into_owned()),
+                &payload[9..],
+            ),
+            _ => (None, &payload[3..]),
+        };
+        Ok(MySqlError {
+            code,
+            sql_state,
+            message: String::from_utf8_lossy(message).into_owned(),
+        })
+    }
+}
+
+/// The initial handshake sent by the server.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MySqlGreeting {
+    pub protocol_version: u8,
+    pub server_version: String,
+    pub connection_id: u32,
+    pub capabilities: u32,
+    pub charset: u8,
+    pub status: u16,
+    /// Default authentication plugin (e.g., `caching_sha2_password`).
+    pub auth_plugin: Option<String>,
+}
+
+impl MySqlGreeting {
+    pub(super) fn parse_from(payload: &[u8]) -> Result<Self> {
+        if payload.first() != Some(&PROTOCOL_V10) {
+            bail!("unsupported protocol version");
+        }
+        let mut reader = Reader::new(&payload[1..]);
+        let server_version = reader.cstring()?;
+        let connection_id = reader.u32()?;
+        reader.skip(8 + 1)?; // auth-plugin-data-part-1, filler
+        let mut greeting = MySqlGreeting {
+            protocol_version: PROTOCOL_V10,
+            server_version,
+            connection_id,
+            capabilities: reader.u16()? as u32,
+            ..Default::default()
+        };
+        if reader.is_empty() {
+            return Ok(greeting);
+        }
+        greeting.charset = reader.u8()?;
+        greeting.status = reader.u16()?;
+        greeting.capabilities |= (reader.u16()? as u32) << 16;
+        let auth_data_len = reader.u8()? as usize;
+        reader.skip(10)?; // reserved
+        if greeting.capabilities & CLIENT_SECURE_CONNECTION != 0 {
+            reader.skip(std::cmp::max(13, auth_data_len.saturating_sub(8)))?;
+        }
+        if greeting.capabilities & CLIENT_PLUGIN_AUTH != 0 {
+            greeting.auth_plugin = reader.cstring().ok();
+        }
+        Ok(greeting)
+    }
+}
+
+/// The handshake response (or SSLRequest) sent by the client.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MySqlLogin {
+    pub capabilities: u32,
+    pub charset: u8,
+    /// `true` if this is an SSLRequest, after which the client starts a TLS handshake.
+    pub ssl_request: bool,
+    pub user: Option<String>,
+    pub database: Option<String>,
+    /// Authentication plugin used by the client (e.g., `mysql_native_password`).
+    pub auth_plugin: Option<String>,
+    /// Connection attributes (e.g., `_client_name`, `program_name`), in order.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl MySqlLogin {
+    pub(super) fn parse_from(payload: &[u8]) -> Result<Self> {
+        if payload.len() < 4 {
+            bail!("truncated handshake response");
+        }
+        let mut login = MySqlLogin::default();
+        let mut reader = Reader::new(payload);
+        if LittleEndian::read_u16(payload) as u32 & CLIENT_PROTOCOL_41 == 0 {
+            // HandshakeResponse320
+            login.capabilities = reader.u16()? as u32;
+            reader.skip(3)?; // max packet size
+            login.user = reader.cstring().ok();
+            return Ok(login);
+        }
+        login.capabilities = reader.u32()?;
+        reader.skip(4)?; // max packet size
+        login.charset = reader.u8()?;
+        reader.skip(23)?; // filler
+        if payload.len() == SSL_REQUEST_LEN && login.capabilities & CLIENT_SSL != 0 {
+            login.ssl_request = true;
+            return Ok(login);
+        }
+        login.user = Some(reader.cstring()?);
+        let auth_len = if login.capabilities & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+            reader.lenenc()? as usize
+        } else if login.capabilities & CLIENT_SECURE_CONNECTION != 0 {
+            reader.u8()? as usize
+        } else {
+            reader.cstring()?;
+            0
+        };
+        reader.skip(auth_len)?;
+        if login.capabilities & CLIENT_CONNECT_WITH_DB != 0 {
+            login.database = reader.cstring().ok();
+        }
+        if login.capabilities & CLIENT_PLUGIN_AUTH != 0 {
+            login.auth_plugin = reader.cstring().ok();
+        }
+        if login.capabilities & CLIENT_CONNECT_ATTRS != 0 {
+            if let Ok(len) = reader.lenenc() {
+                let mut attrs = Reader::new(reader.take(len as usize).unwrap_or_default());
+                while let (Ok(key), Ok(value)) = (attrs.lenenc_string(), attrs.lenenc_string()) {
+                    login.attributes.push((key, value));
+                }
+            }
+        }
+        Ok(login)
+    }
+}
+
+/// Reads little-endian integers and strings from a packet payload.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.data.len() < n {
+            bail!("truncated packet");
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    /// Reads a length-encoded integer.
+    fn lenenc(&mut self) -> Result<u64> {
+        match self.u8()? {
+            n @ 0..=0xfa => Ok(n as u64),
+            0xfc => Ok(LittleEndian::read_u16(self.take(2)?) as u64),
+            0xfd => Ok(LittleEndian::read_u24(self.take(3)?) as u64),
+            0xfe => Ok(LittleEndian::read_u64(self.take(8)?)),
+            n => bail!("invalid length-encoded integer prefix {:#x}", n),
+        }
+    }
+
+    fn lenenc_string(&mut self) -> Result<String> {
+        let len = self.lenenc()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn cstring(&mut self) -> Result<String> {
+        match memchr::memchr(0, self.data) {
+            Some(pos) => {
+                let s = String::from_utf8_lossy(&self.data[..pos]).into_owned();
+                self.data = &self.data[pos + 1..];
+                Ok(s)
+            }
+            None => bail!("unterminated string"),
+        }
+    }
+}
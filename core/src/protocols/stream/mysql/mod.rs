@@ -0,0 +1,124 @@
+//! MySQL handshake parsing.
+//!
+//! ## Remarks
+//! The parser follows the connection phase of a MySQL connection: the server greeting, the
+//! client's handshake response, and the authentication exchange that follows. The session headers
+//! are considered done once the server accepts or rejects the client, or the client sends an
+//! SSLRequest. In the latter case, the remainder of the stream is handed to the TLS parser and
+//! the TLS handshake is parsed as a second session in the same connection. Commands are not
+//! parsed. MariaDB servers use the same connection phase and are parsed as MySQL.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed MySQL handshake contents.
+#[derive(Debug, Default, Serialize)]
+pub struct MySql {
+    /// Initial handshake sent by the server.
+    pub greeting: Option<MySqlGreeting>,
+    /// Handshake response (or SSLRequest) sent by the client.
+    pub login: Option<MySqlLogin>,
+    /// Authentication plugin requested by an AuthSwitchRequest.
+    pub auth_switch_plugin: Option<String>,
+    /// `true` if the server accepted the client.
+    pub authenticated: bool,
+    /// ERR packet sent by the server during the connection phase.
+    pub error: Option<MySqlError>,
+}
+
+impl MySql {
+    /// Returns the server version string (e.g., `"8.0.36"`), or `""` if unknown.
+    pub fn server_version(&self) -> &str {
+        self.greeting.as_ref().map_or("", |g| &g.server_version)
+    }
+
+    /// Returns the handshake protocol version, or 0 if unknown.
+    pub fn protocol_version(&self) -> u8 {
+        self.greeting.as_ref().map_or(0, |g| g.protocol_version)
+    }
+
+    /// Returns the connection (thread) ID assigned by the server, or 0 if unknown.
+    pub fn connection_id(&self) -> u32 {
+        self.greeting.as_ref().map_or(0, |g| g.connection_id)
+    }
+
+    /// Returns the capability flags advertised by the server.
+    pub fn server_capabilities(&self) -> u32 {
+        self.greeting.as_ref().map_or(0, |g| g.capabilities)
+    }
+
+    /// Returns the capability flags requested by the client.
+    pub fn client_capabilities(&self) -> u32 {
+        self.login.as_ref().map_or(0, |l| l.capabilities)
+    }
+
+    /// Returns the server's default authentication plugin, or `""` if unknown.
+    pub fn auth_plugin(&self) -> &str {
+        self.greeting
+            .as_ref()
+            .and_then(|g| g.auth_plugin.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the authentication plugin the client authenticated with, taking an
+    /// AuthSwitchRequest into account, or `""` if unknown.
+    pub fn client_auth_plugin(&self) -> &str {
+        self.auth_switch_plugin
+            .as_deref()
+            .or(self.login.as_ref().and_then(|l| l.auth_plugin.as_deref()))
+            .unwrap_or("")
+    }
+
+    /// Returns the user name, or `""` if unknown.
+    pub fn user(&self) -> &str {
+        self.login
+            .as_ref()
+            .and_then(|l| l.user.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the initial database, or `""` if not specified.
+    pub fn database(&self) -> &str {
+        self.login
+            .as_ref()
+            .and_then(|l| l.database.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the value of a client connection attribute, or `""` if not sent.
+    fn attribute(&self, name: &str) -> &str {
+        self.login
+            .as_ref()
+            .and_then(|l| l.attributes.iter().find(|(k, _)| k == name))
+            .map_or("", |(_, v)| v)
+    }
+
+    /// Returns the client library name (e.g., `"libmysql"`), or `""` if not sent.
+    pub fn client_name(&self) -> &str {
+        self.attribute("_client_name")
+    }
+
+    /// Returns the client program name, or `""` if not sent.
+    pub fn program_name(&self) -> &str {
+        self.attribute("program_name")
+    }
+
+    /// Returns `true` if the client requested TLS.
+    pub fn ssl_request(&self) -> bool {
+        self.login.as_ref().is_some_and(|l| l.ssl_request)
+    }
+
+    /// Returns the server error code (e.g., 1045 for access denied), or 0 if none.
+    pub fn error_code(&self) -> u16 {
+        self.error.as_ref().map_or(0, |e| e.code)
+    }
+
+    /// Returns the server error message, or `""` if none.
+    pub fn error_message(&self) -> &str {
+        self.error.as_ref().map_or("", |e| &e.message)
+    }
+}
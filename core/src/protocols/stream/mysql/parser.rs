@@ -0,0 +1,446 @@
+//! MySQL handshake parser.
+//!
+//! Reassembles MySQL packets in each direction until the connection phase completes.
+
+use super::message::*;
+use super::MySql;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Upper bound on the size of a buffered MySQL packet.
+const MAX_BUFFER_LEN: usize = 1 << 16;
+
+/// Parses a single MySQL connection phase per connection.
+#[derive(Debug)]
+pub struct MySqlParser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<MySql>,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// `true` if the client sent an SSLRequest and the stream continues as TLS.
+    starttls: bool,
+}
+
+impl Default for MySqlParser {
+    fn default() -> Self {
+        MySqlParser {
+            sessions: vec![MySql::default()],
+            client_buffer: vec![],
+            server_buffer: vec![],
+            starttls: false,
+        }
+    }
+}
+
+impl MySqlParser {
+    /// Returns `true` if the remainder of the stream should be parsed as TLS.
+    pub(crate) fn starttls(&self) -> bool {
+        self.starttls
+    }
+
+    fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        while cur_data.len() >= HDR_LEN {
+            let len = HDR_LEN + LittleEndian::read_u24(cur_data) as usize;
+            if cur_data.len() < len {
+                if len > MAX_BUFFER_LEN {
+                    log::debug!("MySQL packet exceeds maximum buffer size");
+                    return ParseResult::Skipped;
+                }
+                break;
+            }
+            let payload = &cur_data[HDR_LEN..len];
+            result = match dir {
+                true => self.process_client_packet(payload),
+                false => self.process_server_packet(payload),
+            };
+            if matches!(result, ParseResult::HeadersDone(_)) {
+                return result;
+            }
+            cur_data = &cur_data[len..];
+        }
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        buffer.extend_from_slice(cur_data);
+        result
+    }
+
+    fn process_client_packet(&mut self, payload: &[u8]) -> ParseResult {
+        let mysql = &mut self.sessions[0];
+        if mysql.login.is_some() {
+            // Authentication data (e.g., AuthSwitchResponse)
+            return ParseResult::Continue(0);
+        }
+        match MySqlLogin::parse_from(payload) {
+            Ok(login) => {
+                let ssl_request = login.ssl_request;
+                mysql.login = Some(login);
+                if ssl_request {
+                    self.starttls = true;
+                    return ParseResult::HeadersDone(0);
+                }
+                ParseResult::Continue(0)
+            }
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+
+    fn process_server_packet(&mut self, payload: &[u8]) -> ParseResult {
+        let mysql = &mut self.sessions[0];
+        if payload.is_empty() {
+            return ParseResult::Skipped;
+        }
+        if mysql.greeting.is_none() && mysql.login.is_none() {
+            if payload[0] == ERR_PACKET {
+                // Connection refused before the handshake (e.g., host not allowed)
+                mysql.error = MySqlError::parse_from(payload).ok();
+                return ParseResult::HeadersDone(0);
+            }
+            return match MySqlGreeting::parse_from(payload) {
+                Ok(greeting) => {
+                    mysql.greeting = Some(greeting);
+                    ParseResult::Continue(0)
+                }
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    ParseResult::Skipped
+                }
+            };
+        }
+        match payload[0] {
+            OK_PACKET => {
+                mysql.authenticated = true;
+                ParseResult::HeadersDone(0)
+            }
+            ERR_PACKET => {
+                mysql.error = MySqlError::parse_from(payload).ok();
+                ParseResult::HeadersDone(0)
+            }
+            AUTH_SWITCH_REQUEST => {
+                let plugin = &payload[1..];
+                let end = memchr::memchr(0, plugin).unwrap_or(plugin.len());
+                mysql.auth_switch_plugin =
+                    Some(String::from_utf8_lossy(&plugin[..end]).into_owned());
+                ParseResult::Continue(0)
+            }
+            _ => ParseResult::Continue(0),
+        }
+    }
+}
+
+impl ConnParsable for MySqlParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 || self.sessions.is_empty() {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+        if pdu.dir || pdu.length() < HDR_LEN + 2 {
+            // The server speaks first
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            // The greeting is sent alone with sequence ID 0
+            let len = LittleEndian::read_u24(data) as usize;
+            if len != data.len() - HDR_LEN || data[3] != 0 {
+                return ProbeResult::NotForUs;
+            }
+            let payload = &data[HDR_LEN..];
+            let valid = match payload[0] {
+                PROTOCOL_V10 => MySqlGreeting::parse_from(payload).is_ok_and(|g| {
+                    !g.server_version.is_empty()
+                        && g.server_version.bytes().all(|b| b.is_ascii_graphic())
+                }),
+                ERR_PACKET => MySqlError::parse_from(payload).is_ok(),
+                _ => false,
+            };
+            if valid {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|mysql| Session {
+            data: SessionData::Mysql(Box::new(mysql)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|mysql| Session {
+                data: SessionData::Mysql(Box::new(mysql)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        if self.starttls {
+            // TLS session expected next
+            ParsingState::Probing
+        } else {
+            ParsingState::Stop
+        }
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    /// Initial handshake of a MySQL 8.0.36 server with `caching_sha2_password` authentication.
+    const GREETING: &[u8] = b"\
+        \x4a\x00\x00\x00\x0a\x38\x2e\x30\x2e\x33\x36\x00\x1b\x00\x00\x00\
+        \x3d\x4c\x1f\x7a\x2b\x5e\x09\x12\x00\xff\xf7\xff\x02\x00\xff\xdf\
+        \x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x6e\x1a\x7f\x3c\x55\
+        \x3d\x2a\x0b\x7c\x1e\x4d\x00\x00\x63\x61\x63\x68\x69\x6e\x67\x5f\
+        \x73\x68\x61\x32\x5f\x70\x61\x73\x73\x77\x6f\x72\x64\x00";
+
+    /// HandshakeResponse41 of the mysql client for user `app` and database `inventory`.
+    const LOGIN: &[u8] = b"\
+        \xb0\x00\x00\x01\x8d\xa6\xbe\x01\x00\x00\x00\x01\xff\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x61\x70\x70\x00\x20\x30\x31\x32\x33\x34\x35\x36\
+        \x37\x38\x39\x3a\x3b\x3c\x3d\x3e\x3f\x40\x41\x42\x43\x44\x45\x46\
+        \x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x4f\x69\x6e\x76\x65\x6e\x74\x6f\
+        \x72\x79\x00\x63\x61\x63\x68\x69\x6e\x67\x5f\x73\x68\x61\x32\x5f\
+        \x70\x61\x73\x73\x77\x6f\x72\x64\x00\x4a\x03\x5f\x6f\x73\x05\x4c\
+        \x69\x6e\x75\x78\x0c\x5f\x63\x6c\x69\x65\x6e\x74\x5f\x6e\x61\x6d\
+        \x65\x08\x6c\x69\x62\x6d\x79\x73\x71\x6c\x0f\x5f\x63\x6c\x69\x65\
+        \x6e\x74\x5f\x76\x65\x72\x73\x69\x6f\x6e\x06\x38\x2e\x30\x2e\x33\
+        \x36\x0c\x70\x72\x6f\x67\x72\x61\x6d\x5f\x6e\x61\x6d\x65\x05\x6d\
+        \x79\x73\x71\x6c";
+
+    /// Fast authentication success (AuthMoreData) and OK packet.
+    const ACCEPTED: &[u8] = b"\
+        \x02\x00\x00\x02\x01\x03\x07\x00\x00\x03\x00\x00\x00\x02\x00\x00\
+        \x00";
+
+    /// ERR packet rejecting the password.
+    const ACCESS_DENIED: &[u8] = b"\
+        \x46\x00\x00\x02\xff\x15\x04\x23\x32\x38\x30\x30\x30\x41\x63\x63\
+        \x65\x73\x73\x20\x64\x65\x6e\x69\x65\x64\x20\x66\x6f\x72\x20\x75\
+        \x73\x65\x72\x20\x27\x61\x70\x70\x27\x40\x27\x31\x30\x2e\x30\x2e\
+        \x30\x2e\x31\x27\x20\x28\x75\x73\x69\x6e\x67\x20\x70\x61\x73\x73\
+        \x77\x6f\x72\x64\x3a\x20\x59\x45\x53\x29";
+
+    /// SSLRequest of the mysql client.
+    const SSL_REQUEST: &[u8] = b"\
+        \x20\x00\x00\x01\x8d\xae\xbe\x01\x00\x00\x00\x01\xff\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00";
+
+    /// ERR packet sent instead of the greeting to a host that is not allowed to connect.
+    const HOST_REFUSED: &[u8] = b"\
+        \x41\x00\x00\x00\xff\x6a\x04\x48\x6f\x73\x74\x20\x27\x31\x30\x2e\
+        \x30\x2e\x30\x2e\x31\x27\x20\x69\x73\x20\x6e\x6f\x74\x20\x61\x6c\
+        \x6c\x6f\x77\x65\x64\x20\x74\x6f\x20\x63\x6f\x6e\x6e\x65\x63\x74\
+        \x20\x74\x6f\x20\x74\x68\x69\x73\x20\x4d\x79\x53\x51\x4c\x20\x73\
+        \x65\x72\x76\x65\x72";
+
+    const FLOW: Flow = Flow::tcp(3306);
+
+    #[test]
+    fn core_mysql_probe() {
+        let parser = MySqlParser::default();
+        assert_eq!(FLOW.probe(&parser, GREETING, false), ProbeResult::Certain);
+        assert_eq!(
+            FLOW.probe(&parser, HOST_REFUSED, false),
+            ProbeResult::Certain
+        );
+        // The server speaks first, and the greeting fills the segment
+        assert_eq!(FLOW.probe(&parser, GREETING, true), ProbeResult::NotForUs);
+        assert_eq!(
+            FLOW.probe(&parser, &GREETING[..40], false),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"SSH-2.0-OpenSSH_9.6\r\n", false),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_mysql_login() {
+        let mut parser = MySqlParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &GREETING[..30], false),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &GREETING[30..], false),
+            ParseResult::Continue(0)
+        );
+        // Split within the packet header, then within the connection attributes
+        assert_eq!(
+            FLOW.parse(&mut parser, &LOGIN[..2], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &LOGIN[2..150], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &LOGIN[150..], true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, ACCEPTED, false),
+            ParseResult::HeadersDone(0)
+        );
+
+        let mysql = &parser.sessions[0];
+        assert_eq!(mysql.protocol_version(), 10);
+        assert_eq!(mysql.server_version(), "8.0.36");
+        assert_eq!(mysql.connection_id(), 27);
+        assert_eq!(mysql.server_capabilities() & CLIENT_SSL, 0);
+        assert_eq!(mysql.auth_plugin(), "caching_sha2_password");
+        assert_eq!(mysql.user(), "app");
+        assert_eq!(mysql.database(), "inventory");
+        assert_eq!(mysql.client_auth_plugin(), "caching_sha2_password");
+        assert_eq!(mysql.client_name(), "libmysql");
+        assert_eq!(mysql.program_name(), "mysql");
+        assert_eq!(mysql.login.as_ref().unwrap().attributes.len(), 4);
+        assert!(!mysql.ssl_request());
+        assert!(mysql.authenticated);
+        assert_eq!(mysql.error_code(), 0);
+    }
+
+    #[test]
+    fn core_mysql_access_denied() {
+        let mut parser = MySqlParser::default();
+        FLOW.parse(&mut parser, GREETING, false);
+        FLOW.parse(&mut parser, LOGIN, true);
+        assert_eq!(
+            FLOW.parse(&mut parser, ACCESS_DENIED, false),
+            ParseResult::HeadersDone(0)
+        );
+        let mysql = &parser.sessions[0];
+        assert!(!mysql.authenticated);
+        assert_eq!(mysql.error_code(), 1045);
+        assert_eq!(
+            mysql.error.as_ref().unwrap().sql_state.as_deref(),
+            Some("28000")
+        );
+        assert_eq!(
+            mysql.error_message(),
+            "Access denied for user 'app'@'10.0.0.1' (using password: YES)"
+        );
+    }
+
+    #[test]
+    fn core_mysql_ssl() {
+        let mut parser = MySqlParser::default();
+        FLOW.parse(&mut parser, GREETING, false);
+        assert_eq!(
+            FLOW.parse(&mut parser, SSL_REQUEST, true),
+            ParseResult::HeadersDone(0)
+        );
+        let mysql = &parser.sessions[0];
+        assert!(mysql.ssl_request());
+        assert_eq!(mysql.user(), "");
+        assert!(parser.starttls());
+        assert!(matches!(
+            parser.session_parsed_state(),
+            ParsingState::Probing
+        ));
+    }
+
+    #[test]
+    fn core_mysql_host_refused() {
+        let mut parser = MySqlParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, HOST_REFUSED, false),
+            ParseResult::HeadersDone(0)
+        );
+        let mysql = &parser.sessions[0];
+        assert!(mysql.greeting.is_none());
+        assert_eq!(mysql.error_code(), 1130);
+        assert!(mysql.error.as_ref().unwrap().sql_state.is_none());
+    }
+
+    #[test]
+    fn core_mysql_truncated() {
+        let mut parser = MySqlParser::default();
+        // Greeting truncated within its packet
+        let mut greeting = GREETING[..30].to_vec();
+        greeting[0] = 26;
+        assert_eq!(
+            FLOW.parse(&mut parser, &greeting, false),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions[0].greeting.is_none());
+        assert!(parser.server_buffer.is_empty());
+        // Packet larger than the buffer
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\xff\xff\xff\x00\x0a8.0.36\x00", false),
+            ParseResult::Skipped
+        );
+        assert!(parser.server_buffer.is_empty());
+        // Incomplete packet at the end of the connection
+        assert_eq!(
+            FLOW.parse(&mut parser, &LOGIN[..100], true),
+            ParseResult::Skipped
+        );
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Mysql(mysql) => {
+                assert_eq!(mysql.server_version(), "");
+                assert_eq!(mysql.user(), "");
+            }
+            _ => panic!("Expected MySQL session"),
+        }
+    }
+}
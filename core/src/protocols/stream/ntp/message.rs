@@ -0,0 +1,208 @@
+//! NTP message components.
+//!
+//! See [RFC 5905](https://datatracker.ietf.org/doc/html/rfc5905) for the time synchronization
+//! packet format. Control (mode 6) messages follow
+//! [RFC 9327](https://datatracker.ietf.org/doc/html/rfc9327), and private (mode 7) messages follow
+//! the ntpd reference implementation.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+use std::net::Ipv4Addr;
+
+/// Length of an NTP time synchronization packet, excluding extension fields and MAC.
+const TIME_HDR_LEN: usize = 48;
+/// Length of an NTP control message header.
+const CONTROL_HDR_LEN: usize = 12;
+/// Length of an NTP private message header.
+const PRIVATE_HDR_LEN: usize = 8;
+
+/// Mode 7 request code for `MON_GETLIST`.
+pub const REQ_MON_GETLIST: u8 = 20;
+/// Mode 7 request code for `MON_GETLIST_1`, the "monlist" request abused for amplification.
+pub const REQ_MON_GETLIST_1: u8 = 42;
+
+/// An NTP message.
+#[derive(Clone, Debug, Serialize)]
+pub struct NtpMessage {
+    /// Leap indicator (0 for mode 6 and 7 messages).
+    pub leap: u8,
+    /// Protocol version.
+    pub version: u8,
+    /// Association mode (e.g., 3 for client, 4 for server, 7 for private).
+    pub mode: u8,
+    /// Length of the UDP payload in bytes.
+    pub length: usize,
+    /// Mode-specific message contents.
+    pub data: NtpData,
+}
+
+/// Mode-specific NTP message contents.
+#[derive(Clone, Debug, Serialize)]
+pub enum NtpData {
+    /// Time synchronization packet (modes 1-5).
+    Time(NtpTime),
+    /// Control message (mode 6).
+    Control(NtpControl),
+    /// Private message (mode 7).
+    Private(NtpPrivate),
+}
+
+/// An NTP time synchronization packet.
+#[derive(Clone, Debug, Serialize)]
+pub struct NtpTime {
+    pub stratum: u8,
+    pub poll: i8,
+    pub precision: i8,
+    pub root_delay: u32,
+    pub root_dispersion: u32,
+    /// Reference clock identifier: an ASCII code for stratum 0 and 1 (e.g., `"GPS"`), otherwise
+    /// the IPv4 address (or IPv6 address hash) of the upstream server.
+    pub reference_id: String,
+    pub reference_ts: u64,
+    pub origin_ts: u64,
+    pub receive_ts: u64,
+    pub transmit_ts: u64,
+}
+
+/// An NTP control (mode 6) message.
+#[derive(Clone, Debug, Serialize)]
+pub struct NtpControl {
+    pub response: bool,
+    pub error: bool,
+    pub more: bool,
+    pub opcode: u8,
+    pub sequence: u16,
+    pub status: u16,
+    pub association_id: u16,
+    pub count: u16,
+}
+
+/// An NTP private (mode 7) message.
+#[derive(Clone, Debug, Serialize)]
+pub struct NtpPrivate {
+    pub response: bool,
+    pub more: bool,
+    pub authenticated: bool,
+    pub sequence: u8,
+    pub implementation: u8,
+    pub request_code: u8,
+    pub error: u8,
+    pub num_items: u16,
+    pub item_size: u16,
+}
+
+impl NtpMessage {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.is_empty() {
+            bail!("empty message");
+        }
+        let mode = data[0] & 0x07;
+        let version = (data[0] >> 3) & 0x07;
+        if !(1..=4).contains(&version) {
+            bail!("invalid version {}", version);
+        }
+        let (leap, body) = match mode {
+            1..=5 => {
+                if data.len() < TIME_HDR_LEN {
+                    bail!("time packet too short");
+                }
+                let stratum = data[1];
+                let reference_id = match stratum {
+                    0 | 1 => String::from_utf8_lossy(&data[12..16])
+                        .trim_end_matches('\0')
+                        .to_owned(),
+                    _ => Ipv4Addr::new(data[12], data[13], data[14], data[15]).to_string(),
+                };
+                let time = NtpTime {
+                    stratum,
+                    poll: data[2] as i8,
+                    precision: data[3] as i8,
+                    root_delay: BigEndian::read_u32(&data[4..8]),
+                    root_dispersion: BigEndian::read_u32(&data[8..12]),
+                    reference_id,
+                    reference_ts: BigEndian::read_u64(&data[16..24]),
+                    origin_ts: BigEndian::read_u64(&data[24..32]),
+                    receive_ts: BigEndian::read_u64(&data[32..40]),
+                    transmit_ts: BigEndian::read_u64(&data[40..48]),
+                };
+                (data[0] >> 6, NtpData::Time(time))
+            }
+            6 => {
+                if data.len() < CONTROL_HDR_LEN {
+                    bail!("control message too short");
+                }
+                let control = NtpControl {
+                    response: data[1] & 0x80 != 0,
+                    error: data[1] & 0x40 != 0,
+                    more: data[1] & 0x20 != 0,
+                    opcode: data[1] & 0x1f,
+                    sequence: BigEndian::read_u16(&data[2..4]),
+                    status: BigEndian::read_u16(&data[4..6]),
+                    association_id: BigEndian::read_u16(&data[6..8]),
+                    count: BigEndian::read_u16(&data[10..12]),
+                };
+                (0, NtpData::Control(control))
+            }
+            7 => {
+                if data.len() < PRIVATE_HDR_LEN {
+                    bail!("private message too short");
+                }
+                let private = NtpPrivate {
+                    response: data[0] & 0x80 != 0,
+                    more: data[0] & 0x40 != 0,
+                    authenticated: data[1] & 0x80 != 0,
+                    sequence: data[1] & 0x7f,
+                    implementation: data[2],
+                    request_code: data[3],
+                    error: data[4] >> 4,
+                    num_items: BigEndian::read_u16(&data[4..6]) & 0x0fff,
+                    item_size: BigEndian::read_u16(&data[6..8]) & 0x0fff,
+                };
+                (0, NtpData::Private(private))
+            }
+            _ => bail!("reserved mode"),
+        };
+        Ok(NtpMessage {
+            leap,
+            version,
+            mode,
+            length: data.len(),
+            data: body,
+        })
+    }
+
+    /// Returns `true` if the message is a request: a client (mode 3) or symmetric active (mode 1)
+    /// packet, or a mode 6 or 7 message without the response bit set.
+    pub fn is_request(&self) -> bool {
+        match &self.data {
+            NtpData::Time(_) => matches!(self.mode, 1 | 3),
+            NtpData::Control(control) => !control.response,
+            NtpData::Private(private) => !private.response,
+        }
+    }
+
+    /// Returns `true` if the message is the last (or only) response to a request.
+    pub(super) fn is_last_response(&self) -> bool {
+        match &self.data {
+            NtpData::Time(_) => true,
+            NtpData::Control(control) => !control.more,
+            NtpData::Private(private) => !private.more,
+        }
+    }
+
+    /// Returns `true` if `self` is a response to the request `req`, or a later fragment of the
+    /// same mode 6 or 7 response as `req`.
+    pub(super) fn responds_to(&self, req: &NtpMessage) -> bool {
+        match (&req.data, &self.data) {
+            (NtpData::Time(req), NtpData::Time(resp)) => resp.origin_ts == req.transmit_ts,
+            (NtpData::Control(req), NtpData::Control(resp)) => {
+                resp.opcode == req.opcode && resp.sequence == req.sequence
+            }
+            (NtpData::Private(req), NtpData::Private(resp)) => {
+                resp.implementation == req.implementation && resp.request_code == req.request_code
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! NTP transaction parsing.
+//!
+//! ## Remarks
+//! A transaction consists of a request and the responses to it. Client (mode 3) and symmetric
+//! active (mode 1) packets are paired with the server or symmetric passive packet whose origin
+//! timestamp echoes the request's transmit timestamp. Control (mode 6) and private (mode 7)
+//! requests are paired with every response fragment until one arrives without the "more" bit set,
+//! which captures the full size of amplified responses such as mode 7 `monlist`.
+//!
+//! Broadcast packets and responses without an observed request (e.g., reflected traffic in an
+//! amplification attack) are reported as single-message transactions.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed NTP transaction contents.
+#[derive(Clone, Debug, Serialize)]
+pub struct Ntp {
+    /// Request, if observed.
+    pub request: Option<NtpMessage>,
+    /// Responses to the request, in order of arrival.
+    pub responses: Vec<NtpMessage>,
+}
+
+impl Ntp {
+    /// Returns the first message in the transaction.
+    fn first(&self) -> Option<&NtpMessage> {
+        self.request.as_ref().or_else(|| self.responses.first())
+    }
+
+    /// Returns the association mode of the first message in the transaction (e.g., 3 for client,
+    /// 7 for private), or 0 if there is none.
+    pub fn mode(&self) -> u8 {
+        self.first().map_or(0, |m| m.mode)
+    }
+
+    /// Returns the protocol version of the first message in the transaction, or 0 if there is
+    /// none.
+    pub fn version(&self) -> u8 {
+        self.first().map_or(0, |m| m.version)
+    }
+
+    /// Returns the stratum advertised by the server, or 0 if no time synchronization response
+    /// was observed.
+    pub fn stratum(&self) -> u8 {
+        self.time_response().map_or(0, |t| t.stratum)
+    }
+
+    /// Returns the server's reference clock identifier, or `""` if no time synchronization
+    /// response was observed.
+    pub fn reference_id(&self) -> &str {
+        self.time_response().map_or("", |t| t.reference_id.as_str())
+    }
+
+    /// Returns the time synchronization packet sent by the server, if any.
+    fn time_response(&self) -> Option<&NtpTime> {
+        self.responses
+            .iter()
+            .chain(self.request.iter())
+            .find_map(|m| match &m.data {
+                NtpData::Time(time) if !m.is_request() => Some(time),
+                _ => None,
+            })
+    }
+
+    /// Returns the mode 7 request code (e.g., 42 for `MON_GETLIST_1`), or 0 if this is not a
+    /// private mode transaction.
+    pub fn request_code(&self) -> u8 {
+        match self.first().map(|m| &m.data) {
+            Some(NtpData::Private(private)) => private.request_code,
+            _ => 0,
+        }
+    }
+
+    /// Returns the mode 6 opcode (e.g., 2 for `READVAR`), or 0 if this is not a control mode
+    /// transaction.
+    pub fn opcode(&self) -> u8 {
+        match self.first().map(|m| &m.data) {
+            Some(NtpData::Control(control)) => control.opcode,
+            _ => 0,
+        }
+    }
+
+    /// Returns `true` if the transaction is a mode 7 `monlist` query.
+    pub fn is_monlist(&self) -> bool {
+        self.mode() == 7 && matches!(self.request_code(), REQ_MON_GETLIST | REQ_MON_GETLIST_1)
+    }
+
+    /// Returns the number of responses in the transaction.
+    pub fn response_count(&self) -> usize {
+        self.responses.len()
+    }
+
+    /// Returns the request size in bytes, or 0 if no request was observed.
+    pub fn request_bytes(&self) -> usize {
+        self.request.as_ref().map_or(0, |m| m.length)
+    }
+
+    /// Returns the total size of all responses in bytes.
+    pub fn response_bytes(&self) -> usize {
+        self.responses.iter().map(|m| m.length).sum()
+    }
+
+    /// Returns the bandwidth amplification factor (response bytes per request byte), rounded
+    /// down, or 0 if no request was observed.
+    pub fn amplification(&self) -> usize {
+        match self.request_bytes() {
+            0 => 0,
+            len => self.response_bytes() / len,
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! NTP transaction parser.
+//!
+//! Parses NTP messages and maintains state for pairing requests with their responses.
+
+use super::message::NtpMessage;
+use super::Ntp;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+#[derive(Default, Debug)]
+pub struct NtpParser {
+    /// Maps session ID to NTP transaction
+    sessions: HashMap<usize, Ntp>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+}
+
+impl ConnParsable for NtpParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.src.port() != 123 && pdu.ctxt.dst.port() != 123 {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match NtpMessage::parse_from(data) {
+                Ok(_) => ProbeResult::Certain,
+                Err(_) => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|ntp| Session {
+            data: SessionData::Ntp(Box::new(ntp)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, ntp)| Session {
+                data: SessionData::Ntp(Box::new(ntp)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider NTP to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl NtpParser {
+    pub(crate) fn process(&mut self, data: &[u8]) -> ParseResult {
+        match NtpMessage::parse_from(data) {
+            Ok(msg) => {
+                log::debug!("NTP mode {}", msg.mode);
+                if msg.is_request() {
+                    let ntp = Ntp {
+                        request: Some(msg),
+                        responses: vec![],
+                    };
+                    return ParseResult::Continue(self.insert(ntp));
+                }
+                for (session_id, ntp) in self.sessions.iter_mut() {
+                    let matched = match (&ntp.request, ntp.responses.last()) {
+                        (Some(req), _) => msg.responds_to(req),
+                        // Fragment of an unsolicited multi-packet response
+                        (None, Some(prev)) => !prev.is_last_response() && msg.responds_to(prev),
+                        (None, None) => false,
+                    };
+                    if matched {
+                        let done = msg.is_last_response();
+                        ntp.responses.push(msg);
+                        if done {
+                            return ParseResult::Done(*session_id);
+                        }
+                        return ParseResult::Continue(*session_id);
+                    }
+                }
+                // Broadcast or unsolicited response
+                let done = msg.is_last_response();
+                let ntp = Ntp {
+                    request: None,
+                    responses: vec![msg],
+                };
+                let session_id = self.insert(ntp);
+                if done {
+                    return ParseResult::Done(session_id);
+                }
+                ParseResult::Continue(session_id)
+            }
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+
+    fn insert(&mut self, ntp: Ntp) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, ntp);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::ntp::REQ_MON_GETLIST_1;
+    use crate::protocols::stream::testing::{udp, Flow};
+
+    /// NTPv4 client request from chrony, with only the transmit timestamp set.
+    const CLIENT_REQUEST: &[u8] = b"\
+        \x23\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x6a\x1f\x3c\x52\x9d\x4b\x1e\x07";
+
+    /// Stratum 2 server response echoing the request's transmit timestamp as origin timestamp.
+    const SERVER_RESPONSE: &[u8] = b"\
+        \x24\x02\x03\xe9\x00\x00\x02\x1c\x00\x00\x04\x8b\xc0\xa8\x01\x01\
+        \xea\x9d\x0c\x11\x35\x7f\x2a\x40\x6a\x1f\x3c\x52\x9d\x4b\x1e\x07\
+        \xea\x9d\x0c\x52\x10\x6c\x88\x01\xea\x9d\x0c\x52\x10\x71\x93\x2e";
+
+    /// Mode 7 MON_GETLIST_1 ("monlist") request, padded to 48 bytes as sent by ntpdc.
+    const MONLIST_REQUEST: &[u8] = b"\
+        \x17\x00\x03\x2a\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+    /// Returns a monlist response fragment with six 72-byte entries.
+    fn monlist_response(more: bool, sequence: u8) -> Vec<u8> {
+        let first = match more {
+            true => 0xd7,
+            false => 0x97,
+        };
+        let mut resp = vec![first, sequence, 0x03, 0x2a, 0x00, 0x06, 0x00, 0x48];
+        resp.resize(8 + 6 * 72, 0x11);
+        resp
+    }
+
+    const FLOW: Flow = Flow::udp(123);
+
+    #[test]
+    fn core_ntp_probe() {
+        let parser = NtpParser::default();
+        assert!(matches!(
+            FLOW.probe(&parser, CLIENT_REQUEST, true),
+            ProbeResult::Certain
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, MONLIST_REQUEST, true),
+            ProbeResult::Certain
+        ));
+        // Not the NTP port
+        assert!(matches!(
+            parser.probe(&udp(CLIENT_REQUEST, 53, true)),
+            ProbeResult::NotForUs
+        ));
+        // Version 0
+        let mut invalid = CLIENT_REQUEST.to_vec();
+        invalid[0] = 0x03;
+        assert!(matches!(
+            FLOW.probe(&parser, &invalid, true),
+            ProbeResult::NotForUs
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        ));
+    }
+
+    #[test]
+    fn core_ntp_client_server() {
+        let mut parser = NtpParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, CLIENT_REQUEST, true),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, SERVER_RESPONSE, false),
+            ParseResult::Done(0)
+        ));
+        let session = parser.remove_session(0).unwrap();
+        match &session.data {
+            SessionData::Ntp(ntp) => {
+                assert_eq!(ntp.mode(), 3);
+                assert_eq!(ntp.version(), 4);
+                assert_eq!(ntp.stratum(), 2);
+                assert_eq!(ntp.reference_id(), "192.168.1.1");
+                assert_eq!(ntp.response_count(), 1);
+                assert_eq!(ntp.amplification(), 1);
+            }
+            _ => panic!("Expected NTP session"),
+        }
+    }
+
+    #[test]
+    fn core_ntp_monlist() {
+        let mut parser = NtpParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, MONLIST_REQUEST, true),
+            ParseResult::Continue(0)
+        ));
+        // The response spans several datagrams.
+        for sequence in 0..3 {
+            assert!(matches!(
+                FLOW.parse(&mut parser, &monlist_response(true, sequence), false),
+                ParseResult::Continue(0)
+            ));
+        }
+        assert!(matches!(
+            FLOW.parse(&mut parser, &monlist_response(false, 3), false),
+            ParseResult::Done(0)
+        ));
+        let session = parser.remove_session(0).unwrap();
+        match &session.data {
+            SessionData::Ntp(ntp) => {
+                assert!(ntp.is_monlist());
+                assert_eq!(ntp.request_code(), REQ_MON_GETLIST_1);
+                assert_eq!(ntp.response_count(), 4);
+                assert_eq!(ntp.request_bytes(), 48);
+                assert_eq!(ntp.response_bytes(), 4 * 440);
+                assert_eq!(ntp.amplification(), 36);
+            }
+            _ => panic!("Expected NTP session"),
+        }
+    }
+
+    #[test]
+    fn core_ntp_truncated() {
+        let mut parser = NtpParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, &CLIENT_REQUEST[..47], true),
+            ParseResult::Skipped
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, &MONLIST_REQUEST[..7], true),
+            ParseResult::Skipped
+        ));
+        assert!(parser.drain_sessions().is_empty());
+        // An unsolicited response is reported on its own.
+        assert!(matches!(
+            FLOW.parse(&mut parser, SERVER_RESPONSE, false),
+            ParseResult::Done(0)
+        ));
+    }
+}
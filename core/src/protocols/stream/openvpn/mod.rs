@@ -0,0 +1,116 @@
+//! OpenVPN handshake parsing.
+//!
+//! ## Remarks
+//! The parser identifies OpenVPN by the client hard reset that opens a session, over either UDP
+//! or TCP framing. It then records the headers of control channel packets (opcode, key ID, and
+//! session ID) until the first data channel packet, at which point the handshake is considered
+//! done. The TLS handshake carried in the control channel is not parsed, and packets obfuscated
+//! by third-party patches (e.g., XOR scrambling) are not identified.
+
+mod packet;
+pub mod parser;
+
+pub use self::packet::*;
+
+use serde::Serialize;
+
+/// Parsed OpenVPN handshake contents.
+#[derive(Debug, Default, Serialize)]
+pub struct OpenVpn {
+    /// `true` if the session is carried over TCP.
+    pub tcp: bool,
+    /// Control channel packets, in order of arrival, followed by the first data channel packet.
+    pub packets: Vec<OpenVpnPacket>,
+
+    /// Client buffer for TCP packets that span multiple segments.
+    #[serde(skip_serializing)]
+    pub(crate) client_buffer: Vec<u8>,
+    /// Server buffer for TCP packets that span multiple segments.
+    #[serde(skip_serializing)]
+    pub(crate) server_buffer: Vec<u8>,
+}
+
+impl OpenVpn {
+    /// Returns the transport protocol (`"tcp"` or `"udp"`).
+    pub fn transport(&self) -> &str {
+        match self.tcp {
+            true => "tcp",
+            false => "udp",
+        }
+    }
+
+    /// Returns the opcode of the client hard reset that opened the session, or 0 if it was not
+    /// observed.
+    pub fn client_reset_opcode(&self) -> u8 {
+        self.packets
+            .iter()
+            .find(|p| is_client_reset(p.opcode))
+            .map_or(0, |p| p.opcode)
+    }
+
+    /// Returns the version of the client hard reset (1, 2, or 3 for `tls-crypt-v2`), or 0 if it
+    /// was not observed.
+    pub fn reset_version(&self) -> u8 {
+        match self.client_reset_opcode() {
+            P_CONTROL_HARD_RESET_CLIENT_V1 => 1,
+            P_CONTROL_HARD_RESET_CLIENT_V2 => 2,
+            P_CONTROL_HARD_RESET_CLIENT_V3 => 3,
+            _ => 0,
+        }
+    }
+
+    /// Returns the opcodes observed in the session, in order of first appearance, as
+    /// comma-separated numbers (e.g., `"7,8,5,4,9"`).
+    pub fn opcodes(&self) -> String {
+        let mut opcodes: Vec<u8> = vec![];
+        for packet in &self.packets {
+            if !opcodes.contains(&packet.opcode) {
+                opcodes.push(packet.opcode);
+            }
+        }
+        opcodes
+            .iter()
+            .map(|op| op.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Returns the key ID of the most recent packet, or 0 if there is none.
+    pub fn key_id(&self) -> u8 {
+        self.packets.last().map_or(0, |p| p.key_id)
+    }
+
+    /// Returns the client's session ID as a hex string, or `""` if it was not observed.
+    pub fn client_session_id(&self) -> String {
+        self.session_id(true)
+    }
+
+    /// Returns the server's session ID as a hex string, or `""` if it was not observed.
+    pub fn server_session_id(&self) -> String {
+        self.session_id(false)
+    }
+
+    fn session_id(&self, dir: bool) -> String {
+        self.packets
+            .iter()
+            .filter(|p| p.dir == dir)
+            .find_map(|p| p.session_id)
+            .map(|id| format!("{:016x}", id))
+            .unwrap_or_default()
+    }
+
+    /// Returns the peer ID of the first `P_DATA_V2` packet, or 0 if there is none.
+    pub fn peer_id(&self) -> u32 {
+        self.packets.iter().find_map(|p| p.peer_id).unwrap_or(0)
+    }
+
+    /// Returns the number of control channel packets (including ACKs) observed.
+    pub fn control_packets(&self) -> usize {
+        self.packets.iter().filter(|p| !p.is_data()).count()
+    }
+
+    /// Returns `true` if a data channel packet was observed, indicating the handshake completed.
+    pub fn handshake_done(&self) -> bool {
+        self.packets.iter().any(|p| p.is_data())
+    }
+}
@@ -0,0 +1,116 @@
+//! OpenVPN packet components.
+//!
+//! See the [OpenVPN wire protocol](https://github.com/OpenVPN/openvpn-rfc) for the packet format.
+//! Over UDP, each datagram carries one packet. Over TCP, each packet is prefixed with a 2-byte
+//! big-endian length.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+
+pub const P_CONTROL_HARD_RESET_CLIENT_V1: u8 = 1;
+pub const P_CONTROL_HARD_RESET_SERVER_V1: u8 = 2;
+pub const P_CONTROL_SOFT_RESET_V1: u8 = 3;
+pub const P_CONTROL_V1: u8 = 4;
+pub const P_ACK_V1: u8 = 5;
+pub const P_DATA_V1: u8 = 6;
+pub const P_CONTROL_HARD_RESET_CLIENT_V2: u8 = 7;
+pub const P_CONTROL_HARD_RESET_SERVER_V2: u8 = 8;
+pub const P_DATA_V2: u8 = 9;
+pub const P_CONTROL_HARD_RESET_CLIENT_V3: u8 = 10;
+pub const P_CONTROL_WKC_V1: u8 = 11;
+
+/// Length of a client hard reset without control channel authentication or encryption: opcode,
+/// session ID, ACK array length, and message packet ID.
+pub(super) const MIN_RESET_LEN: usize = 14;
+/// Upper bound on the length of a client hard reset, including a wrapped client key for
+/// `tls-crypt-v2`.
+pub(super) const MAX_RESET_LEN: usize = 1024;
+
+/// Returns the name of an OpenVPN opcode (e.g., `"P_CONTROL_V1"`).
+pub fn opcode_name(opcode: u8) -> &'static str {
+    match opcode {
+        P_CONTROL_HARD_RESET_CLIENT_V1 => "P_CONTROL_HARD_RESET_CLIENT_V1",
+        P_CONTROL_HARD_RESET_SERVER_V1 => "P_CONTROL_HARD_RESET_SERVER_V1",
+        P_CONTROL_SOFT_RESET_V1 => "P_CONTROL_SOFT_RESET_V1",
+        P_CONTROL_V1 => "P_CONTROL_V1",
+        P_ACK_V1 => "P_ACK_V1",
+        P_DATA_V1 => "P_DATA_V1",
+        P_CONTROL_HARD_RESET_CLIENT_V2 => "P_CONTROL_HARD_RESET_CLIENT_V2",
+        P_CONTROL_HARD_RESET_SERVER_V2 => "P_CONTROL_HARD_RESET_SERVER_V2",
+        P_DATA_V2 => "P_DATA_V2",
+        P_CONTROL_HARD_RESET_CLIENT_V3 => "P_CONTROL_HARD_RESET_CLIENT_V3",
+        P_CONTROL_WKC_V1 => "P_CONTROL_WKC_V1",
+        _ => "unknown",
+    }
+}
+
+/// Returns `true` if `opcode` is a client hard reset, which opens an OpenVPN session.
+pub(super) fn is_client_reset(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        P_CONTROL_HARD_RESET_CLIENT_V1
+            | P_CONTROL_HARD_RESET_CLIENT_V2
+            | P_CONTROL_HARD_RESET_CLIENT_V3
+    )
+}
+
+/// An OpenVPN packet header.
+#[derive(Clone, Debug, Serialize)]
+pub struct OpenVpnPacket {
+    /// Direction (`true` if sent by the client).
+    pub dir: bool,
+    /// Packet opcode (high 5 bits of the first byte).
+    pub opcode: u8,
+    /// Key ID (low 3 bits of the first byte).
+    pub key_id: u8,
+    /// Sender's session ID, present in control channel packets.
+    pub session_id: Option<u64>,
+    /// Peer ID, present in `P_DATA_V2` packets.
+    pub peer_id: Option<u32>,
+    /// Packet length in bytes, excluding the TCP length prefix.
+    pub length: usize,
+}
+
+impl OpenVpnPacket {
+    pub(super) fn parse_from(data: &[u8], dir: bool) -> Result<Self> {
+        if data.is_empty() {
+            bail!("empty packet");
+        }
+        let opcode = data[0] >> 3;
+        let key_id = data[0] & 0x07;
+        let (session_id, peer_id) = match opcode {
+            P_DATA_V1 => (None, None),
+            P_DATA_V2 => {
+                if data.len() < 4 {
+                    bail!("truncated data packet");
+                }
+                (None, Some(BigEndian::read_u24(&data[1..4])))
+            }
+            P_CONTROL_HARD_RESET_CLIENT_V1..=P_ACK_V1
+            | P_CONTROL_HARD_RESET_CLIENT_V2
+            | P_CONTROL_HARD_RESET_SERVER_V2
+            | P_CONTROL_HARD_RESET_CLIENT_V3
+            | P_CONTROL_WKC_V1 => {
+                if data.len() < 9 {
+                    bail!("truncated control packet");
+                }
+                (Some(BigEndian::read_u64(&data[1..9])), None)
+            }
+            _ => bail!("invalid opcode {}", opcode),
+        };
+        Ok(OpenVpnPacket {
+            dir,
+            opcode,
+            key_id,
+            session_id,
+            peer_id,
+            length: data.len(),
+        })
+    }
+
+    /// Returns `true` if this is a data channel packet.
+    pub fn is_data(&self) -> bool {
+        matches!(self.opcode, P_DATA_V1 | P_DATA_V2)
+    }
+}
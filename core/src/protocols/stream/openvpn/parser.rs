@@ -0,0 +1,336 @@
+//! OpenVPN parser.
+//!
+//! Parses OpenVPN packet headers over UDP and TCP framing.
+
+use super::packet::*;
+use super::OpenVpn;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::packet::tcp::TCP_PROTOCOL;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use byteorder::{BigEndian, ByteOrder};
+
+/// Maximum number of control channel packets recorded before the handshake is considered done.
+const MAX_CONTROL_PACKETS: usize = 64;
+/// Upper bound on the size of a buffered OpenVPN packet over TCP.
+const MAX_BUFFER_LEN: usize = 1 << 16;
+
+#[derive(Debug, Default)]
+pub struct OpenVpnParser {
+    sessions: Vec<OpenVpn>,
+}
+
+impl ConnParsable for OpenVpnParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if self.sessions.is_empty() {
+                self.sessions.push(OpenVpn {
+                    tcp: pdu.ctxt.proto == TCP_PROTOCOL,
+                    ..Default::default()
+                });
+            }
+            self.sessions[0].process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            let packet = if pdu.ctxt.proto == TCP_PROTOCOL {
+                // The client hard reset is sent alone in the first segment
+                if data.len() < 2 || BigEndian::read_u16(data) as usize != data.len() - 2 {
+                    return ProbeResult::NotForUs;
+                }
+                &data[2..]
+            } else {
+                data
+            };
+            // Client hard reset with key ID 0
+            if (MIN_RESET_LEN..=MAX_RESET_LEN).contains(&packet.len())
+                && is_client_reset(packet[0] >> 3)
+                && packet[0] & 0x07 == 0
+            {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|openvpn| Session {
+            data: SessionData::Openvpn(Box::new(openvpn)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|openvpn| Session {
+                data: SessionData::Openvpn(Box::new(openvpn)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Stop
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl OpenVpn {
+    pub(crate) fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        if !self.tcp {
+            return match OpenVpnPacket::parse_from(data, dir) {
+                Ok(packet) => self.push(packet),
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    ParseResult::Skipped
+                }
+            };
+        }
+
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        while cur_data.len() >= 2 {
+            let len = BigEndian::read_u16(cur_data) as usize;
+            if cur_data.len() < 2 + len {
+                break;
+            }
+            match OpenVpnPacket::parse_from(&cur_data[2..2 + len], dir) {
+                Ok(packet) => {
+                    result = self.push(packet);
+                    if matches!(result, ParseResult::HeadersDone(_)) {
+                        return result;
+                    }
+                }
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return ParseResult::Skipped;
+                }
+            }
+            cur_data = &cur_data[2 + len..];
+        }
+        if cur_data.len() > MAX_BUFFER_LEN {
+            log::debug!("OpenVPN packet exceeds maximum buffer size");
+            return ParseResult::Skipped;
+        }
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        buffer.extend_from_slice(cur_data);
+        match result {
+            ParseResult::Skipped => ParseResult::Continue(0),
+            result => result,
+        }
+    }
+
+    /// Records a packet header and returns whether the handshake is done.
+    fn push(&mut self, packet: OpenVpnPacket) -> ParseResult {
+        log::trace!("OpenVPN {}", opcode_name(packet.opcode));
+        let done = packet.is_data();
+        self.packets.push(packet);
+        if done || self.control_packets() >= MAX_CONTROL_PACKETS {
+            return ParseResult::HeadersDone(0);
+        }
+        ParseResult::Continue(0)
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{tcp, udp};
+
+    /// P_CONTROL_HARD_RESET_CLIENT_V2 without control channel authentication.
+    const CLIENT_RESET: &[u8] = b"\x38\x9c\x4a\x71\xe2\x05\xbd\x33\x18\x00\x00\x00\x00\x00";
+    /// P_CONTROL_HARD_RESET_SERVER_V2 acknowledging the client reset.
+    const SERVER_RESET: &[u8] = b"\x40\x2f\xe8\x61\x0c\x93\x57\xd4\xa1\x01\x00\x00\x00\x00\
+        \x9c\x4a\x71\xe2\x05\xbd\x33\x18\x00\x00\x00\x00";
+    /// P_ACK_V1 from the client acknowledging the server reset.
+    const CLIENT_ACK: &[u8] = b"\x28\x9c\x4a\x71\xe2\x05\xbd\x33\x18\x01\x00\x00\x00\x00\
+        \x2f\xe8\x61\x0c\x93\x57\xd4\xa1";
+    /// P_CONTROL_V1 from the client carrying the start of a TLS ClientHello.
+    const CLIENT_CONTROL: &[u8] = b"\x20\x9c\x4a\x71\xe2\x05\xbd\x33\x18\x00\x00\x00\x00\x01\
+        \x16\x03\x01\x01\x2c\x01\x00\x01\x28\x03\x03";
+    /// P_DATA_V2 with peer ID 1.
+    const DATA: &[u8] = b"\x48\x00\x00\x01\x00\x00\x00\x01\xd1\x7f\x05\x3a\x8e\x42\x6b\x90";
+
+    /// Prefixes `packet` with its length, as sent over TCP.
+    fn framed(packet: &[u8]) -> Vec<u8> {
+        let mut data = (packet.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(packet);
+        data
+    }
+
+    #[test]
+    fn core_openvpn_probe() {
+        let parser = OpenVpnParser::default();
+        assert!(matches!(
+            parser.probe(&udp(CLIENT_RESET, 1194, true)),
+            ProbeResult::Certain
+        ));
+        assert!(matches!(
+            parser.probe(&tcp(&framed(CLIENT_RESET), 443, true)),
+            ProbeResult::Certain
+        ));
+        // Not a client reset
+        assert!(matches!(
+            parser.probe(&udp(SERVER_RESET, 1194, false)),
+            ProbeResult::NotForUs
+        ));
+        assert!(matches!(
+            parser.probe(&udp(DATA, 1194, true)),
+            ProbeResult::NotForUs
+        ));
+        // Nonzero key ID
+        let mut reset = CLIENT_RESET.to_vec();
+        reset[0] |= 0x01;
+        assert!(matches!(
+            parser.probe(&udp(&reset, 1194, true)),
+            ProbeResult::NotForUs
+        ));
+        // Unframed over TCP
+        assert!(matches!(
+            parser.probe(&tcp(CLIENT_RESET, 443, true)),
+            ProbeResult::NotForUs
+        ));
+        assert!(matches!(
+            parser.probe(&udp(&CLIENT_RESET[..MIN_RESET_LEN - 1], 1194, true)),
+            ProbeResult::NotForUs
+        ));
+    }
+
+    #[test]
+    fn core_openvpn_udp() {
+        let mut parser = OpenVpnParser::default();
+        for (packet, dir) in [
+            (CLIENT_RESET, true),
+            (SERVER_RESET, false),
+            (CLIENT_ACK, true),
+            (CLIENT_CONTROL, true),
+        ] {
+            assert!(matches!(
+                parser.parse(&udp(packet, 1194, dir)),
+                ParseResult::Continue(0)
+            ));
+        }
+        assert!(matches!(
+            parser.parse(&udp(DATA, 1194, true)),
+            ParseResult::HeadersDone(0)
+        ));
+
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Openvpn(openvpn) => {
+                assert_eq!(openvpn.transport(), "udp");
+                assert_eq!(openvpn.reset_version(), 2);
+                assert_eq!(openvpn.opcodes(), "7,8,5,4,9");
+                assert_eq!(openvpn.client_session_id(), "9c4a71e205bd3318");
+                assert_eq!(openvpn.server_session_id(), "2fe8610c9357d4a1");
+                assert_eq!(openvpn.peer_id(), 1);
+                assert_eq!(openvpn.control_packets(), 4);
+                assert!(openvpn.handshake_done());
+            }
+            _ => panic!("Expected OpenVPN session"),
+        }
+    }
+
+    #[test]
+    fn core_openvpn_tcp_split() {
+        let mut parser = OpenVpnParser::default();
+        let client = [framed(CLIENT_RESET), framed(CLIENT_ACK)].concat();
+        // Length prefix split from the packet
+        assert!(matches!(
+            parser.parse(&tcp(&client[..1], 443, true)),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            parser.parse(&tcp(&client[1..20], 443, true)),
+            ParseResult::Continue(0)
+        ));
+        assert_eq!(parser.sessions[0].packets.len(), 1);
+        assert!(matches!(
+            parser.parse(&tcp(&framed(SERVER_RESET), 443, false)),
+            ParseResult::Continue(0)
+        ));
+        // The rest of the ACK, followed by a control packet and the first data packet
+        let rest = [
+            &client[20..],
+            &framed(CLIENT_CONTROL)[..],
+            &framed(DATA)[..],
+        ]
+        .concat();
+        assert!(matches!(
+            parser.parse(&tcp(&rest, 443, true)),
+            ParseResult::HeadersDone(0)
+        ));
+        let openvpn = &parser.sessions[0];
+        assert!(openvpn.tcp);
+        assert_eq!(openvpn.opcodes(), "7,8,5,4,9");
+        assert_eq!(openvpn.packets[2].length, CLIENT_ACK.len());
+    }
+
+    #[test]
+    fn core_openvpn_truncated() {
+        let mut parser = OpenVpnParser::default();
+        // Control packet without a full session ID
+        assert!(matches!(
+            parser.parse(&udp(&CLIENT_RESET[..8], 1194, true)),
+            ParseResult::Skipped
+        ));
+        assert!(matches!(
+            parser.parse(&udp(&DATA[..3], 1194, true)),
+            ParseResult::Skipped
+        ));
+        assert!(parser.sessions[0].packets.is_empty());
+
+        // Framed packet whose length exceeds the remaining data is buffered.
+        let mut parser = OpenVpnParser::default();
+        let reset = framed(CLIENT_RESET);
+        assert!(matches!(
+            parser.parse(&tcp(&reset[..reset.len() - 1], 443, true)),
+            ParseResult::Continue(0)
+        ));
+        assert!(parser.sessions[0].packets.is_empty());
+        assert_eq!(parser.sessions[0].client_buffer.len(), reset.len() - 1);
+    }
+}
@@ -0,0 +1,82 @@
+//! POP3 session parsing.
+//!
+//! ## Remarks
+//! The parser follows the authorization state of a POP3 session: the server greeting, advertised
+//! capabilities, and the commands and status indicators that precede authentication. The session
+//! headers are considered done once the client authenticates, quits, or the server accepts STLS.
+//! In the latter case, the remainder of the stream is handed to the TLS parser and the TLS
+//! handshake is parsed as a second session in the same connection. Messages are not parsed.
+
+pub mod parser;
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Parsed POP3 session contents.
+#[derive(Debug, Default, Serialize)]
+pub struct Pop3 {
+    /// Server greeting (text following `+OK`).
+    pub greeting: Option<String>,
+    /// Capabilities advertised by the server in response to CAPA (e.g., `STLS`).
+    pub capabilities: Vec<String>,
+    /// Authentication mechanism used by the client (`USER`, `APOP`, or a SASL mechanism).
+    pub auth_mechanism: Option<String>,
+    /// `true` if the server accepted the client's authentication.
+    pub authenticated: bool,
+    /// `true` if the server accepted STLS.
+    pub starttls: bool,
+    /// Commands sent by the client, in order.
+    pub commands: Vec<Pop3Command>,
+    /// Responses sent by the server, in order (including the greeting).
+    pub responses: Vec<Pop3Response>,
+
+    /// Commands awaiting a response (commands may be pipelined).
+    #[serde(skip_serializing)]
+    pub(crate) pending: VecDeque<String>,
+    /// `true` while receiving a multi-line CAPA response.
+    #[serde(skip_serializing)]
+    pub(crate) in_capa: bool,
+    /// `true` while a SASL exchange is in progress (client lines are not commands).
+    #[serde(skip_serializing)]
+    pub(crate) in_auth: bool,
+}
+
+/// A POP3 command.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Pop3Command {
+    /// Command keyword, uppercased (e.g., `USER`).
+    pub keyword: String,
+    /// Command arguments. Passwords and SASL initial responses are not recorded.
+    pub args: String,
+}
+
+/// The first line of a POP3 response.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Pop3Response {
+    /// `true` for a positive (`+OK`) status indicator.
+    pub ok: bool,
+    /// Text following the status indicator.
+    pub text: String,
+}
+
+impl Pop3 {
+    /// Returns the server greeting, or `""` if it was not observed.
+    pub fn greeting(&self) -> &str {
+        self.greeting.as_deref().unwrap_or("")
+    }
+
+    /// Returns the capabilities advertised by the server, separated by spaces.
+    pub fn capabilities(&self) -> String {
+        self.capabilities
+            .iter()
+            .map(|cap| cap.split(' ').next().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the authentication mechanism used by the client, or `""` if the client did not
+    /// authenticate.
+    pub fn auth_mechanism(&self) -> &str {
+        self.auth_mechanism.as_deref().unwrap_or("")
+    }
+}
@@ -0,0 +1,308 @@
+//! POP3 session parser.
+//!
+//! Commands and responses are reassembled into lines and matched in order. The only multi-line
+//! response parsed is the reply to CAPA
+//! ([RFC 2449](https://datatracker.ietf.org/doc/html/rfc2449)).
+
+use super::{Pop3, Pop3Command, Pop3Response};
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::line::{split_verb, LineBuffer};
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+/// Maximum number of client commands parsed before the session is considered done.
+const MAX_COMMANDS: usize = 64;
+
+/// Parses a single POP3 session per connection.
+#[derive(Debug)]
+pub struct Pop3Parser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<Pop3>,
+    client_lines: LineBuffer,
+    server_lines: LineBuffer,
+    /// `true` if the server accepted STLS and the stream continues as TLS.
+    starttls: bool,
+}
+
+impl Default for Pop3Parser {
+    fn default() -> Self {
+        Pop3Parser {
+            sessions: vec![Pop3::default()],
+            client_lines: LineBuffer::default(),
+            server_lines: LineBuffer::default(),
+            starttls: false,
+        }
+    }
+}
+
+impl Pop3Parser {
+    /// Returns `true` if the remainder of the stream should be parsed as TLS.
+    pub(crate) fn starttls(&self) -> bool {
+        self.starttls
+    }
+
+    /// Process data segments from client to server
+    fn process_ctos(&mut self, data: &[u8]) -> ParseResult {
+        let pop3 = &mut self.sessions[0];
+        let lines = self.client_lines.lines(data);
+        if lines.is_empty() {
+            return ParseResult::Skipped;
+        }
+        for line in lines {
+            if pop3.in_auth {
+                // SASL response (credentials), not a command
+                continue;
+            }
+            let (keyword, args) = split_verb(&line);
+            let keyword = keyword.to_ascii_uppercase();
+            let args = match keyword.as_str() {
+                "USER" => {
+                    pop3.auth_mechanism = Some(keyword.clone());
+                    args.to_string()
+                }
+                "APOP" => {
+                    pop3.auth_mechanism = Some(keyword.clone());
+                    // Record the mailbox name only
+                    split_verb(args).0.to_string()
+                }
+                "AUTH" if !args.is_empty() => {
+                    let mechanism = split_verb(args).0.to_ascii_uppercase();
+                    pop3.auth_mechanism = Some(mechanism.clone());
+                    pop3.in_auth = true;
+                    mechanism
+                }
+                "PASS" => String::new(),
+                _ => args.to_string(),
+            };
+            pop3.pending.push_back(keyword.clone());
+            pop3.commands.push(Pop3Command { keyword, args });
+        }
+        if pop3.commands.len() >= MAX_COMMANDS {
+            return ParseResult::HeadersDone(0);
+        }
+        ParseResult::Continue(0)
+    }
+
+    /// Process data segments from server to client
+    fn process_stoc(&mut self, data: &[u8]) -> ParseResult {
+        let pop3 = &mut self.sessions[0];
+        let lines = self.server_lines.lines(data);
+        if lines.is_empty() {
+            return ParseResult::Skipped;
+        }
+        for line in lines {
+            if pop3.in_capa {
+                if line == "." {
+                    pop3.in_capa = false;
+                } else {
+                    pop3.capabilities.push(line);
+                }
+                continue;
+            }
+            let (status, text) = split_verb(&line);
+            let ok = match status {
+                "+OK" => true,
+                "-ERR" => false,
+                "+" => continue, // SASL challenge
+                _ => {
+                    log::debug!("Invalid POP3 response line: {}", line);
+                    continue;
+                }
+            };
+            pop3.responses.push(Pop3Response {
+                ok,
+                text: text.to_string(),
+            });
+            if pop3.greeting.is_none() && pop3.pending.is_empty() && pop3.responses.len() == 1 {
+                pop3.greeting = Some(text.to_string());
+                continue;
+            }
+            match pop3.pending.pop_front().as_deref() {
+                Some("CAPA") if ok => {
+                    pop3.in_capa = true;
+                }
+                Some("STLS") if ok => {
+                    pop3.starttls = true;
+                    self.starttls = true;
+                    return ParseResult::HeadersDone(0);
+                }
+                Some("PASS") | Some("APOP") | Some("AUTH") => {
+                    pop3.in_auth = false;
+                    if ok {
+                        pop3.authenticated = true;
+                        return ParseResult::HeadersDone(0);
+                    }
+                }
+                Some("QUIT") => {
+                    return ParseResult::HeadersDone(0);
+                }
+                _ => (),
+            }
+        }
+        ParseResult::Continue(0)
+    }
+}
+
+impl ConnParsable for Pop3Parser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 || self.sessions.is_empty() {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.dir {
+                self.process_ctos(data)
+            } else {
+                self.process_stoc(data)
+            }
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() < 4 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.dir {
+                // Capture may have started after the server greeting
+                match &data[..4].to_ascii_uppercase()[..] {
+                    b"CAPA" | b"USER" | b"APOP" | b"STLS" => ProbeResult::Certain,
+                    _ => ProbeResult::NotForUs,
+                }
+            } else if data.starts_with(b"+OK") {
+                // Servers greet first, which rules out client-first protocols using `+OK`
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|pop3| Session {
+            data: SessionData::Pop3(Box::new(pop3)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|pop3| Session {
+                data: SessionData::Pop3(Box::new(pop3)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        if self.starttls {
+            // TLS session expected next
+            ParsingState::Probing
+        } else {
+            ParsingState::Stop
+        }
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    const GREETING: &[u8] = b"+OK Dovecot (Debian) ready.\r\n";
+
+    const FLOW: Flow = Flow::tcp(110);
+
+    #[test]
+    fn core_pop3_probe() {
+        let parser = Pop3Parser::default();
+        assert_eq!(FLOW.probe(&parser, GREETING, false), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, b"CAPA\r\n", true), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, b"+OK", false), ProbeResult::Unsure);
+        assert_eq!(
+            FLOW.probe(&parser, b"-ERR unknown\r\n", false),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"EHLO client.example.org\r\n", true),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_pop3_parse_split() {
+        let mut parser = Pop3Parser::default();
+        FLOW.parse(&mut parser, GREETING, false);
+        FLOW.parse(&mut parser, b"CA", true);
+        FLOW.parse(&mut parser, b"PA\r\n", true);
+        FLOW.parse(
+            &mut parser,
+            b"+OK\r\nCAPA\r\nTOP\r\nUIDL\r\nRESP-CODES\r\nSTLS\r\nSASL PL",
+            false,
+        );
+        FLOW.parse(&mut parser, b"AIN\r\n.\r\n", false);
+        FLOW.parse(&mut parser, b"USER alice\r\nPASS secret\r\n", true);
+        assert_eq!(
+            FLOW.parse(&mut parser, b"+OK\r\n", false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"+OK Logged in.\r\n", false),
+            ParseResult::HeadersDone(0)
+        );
+
+        let pop3 = &parser.sessions[0];
+        assert_eq!(pop3.greeting(), "Dovecot (Debian) ready.");
+        assert_eq!(pop3.capabilities(), "CAPA TOP UIDL RESP-CODES STLS SASL");
+        assert_eq!(pop3.auth_mechanism(), "USER");
+        assert!(pop3.authenticated);
+        // Passwords are not recorded
+        assert_eq!(pop3.commands[2].args, "");
+    }
+
+    #[test]
+    fn core_pop3_starttls() {
+        let mut parser = Pop3Parser::default();
+        FLOW.parse(&mut parser, GREETING, false);
+        FLOW.parse(&mut parser, b"STLS\r\n", true);
+        assert_eq!(
+            FLOW.parse(&mut parser, b"+OK Begin TLS negotiation now.\r\n", false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.starttls());
+    }
+
+    #[test]
+    fn core_pop3_truncated() {
+        let mut parser = Pop3Parser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &GREETING[..10], false),
+            ParseResult::Skipped
+        );
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Pop3(pop3) => {
+                assert_eq!(pop3.greeting(), "");
+                assert!(pop3.responses.is_empty());
+            }
+            _ => panic!("Expected POP3 session"),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! PostgreSQL message components.
+//!
+//! See the [message formats](https://www.postgresql.org/docs/current/protocol-message-formats.html).
+//! Integers are big-endian, and strings are NUL-terminated.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+
+/// Protocol version 3.0.
+pub const PROTOCOL_3_0: u32 = 196608;
+pub const SSL_REQUEST_CODE: u32 = 80877103;
+pub const GSSENC_REQUEST_CODE: u32 = 80877104;
+pub const CANCEL_REQUEST_CODE: u32 = 80877102;
+
+pub const AUTH_OK: u32 = 0;
+pub const AUTH_SASL: u32 = 10;
+
+/// Upper bound on the length of a StartupMessage.
+pub(super) const MAX_STARTUP_LEN: usize = 10000;
+
+/// Returns the name of an authentication request code (e.g., `"md5"`).
+pub fn auth_method_name(code: u32) -> &'static str {
+    match code {
+        0 => "ok",
+        2 => "kerberos_v5",
+        3 => "cleartext",
+        5 => "md5",
+        7 => "gss",
+        9 => "sspi",
+        10..=12 => "sasl",
+        _ => "unknown",
+    }
+}
+
+/// A PostgreSQL ErrorResponse.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PostgresError {
+    /// Severity (e.g., `FATAL`).
+    pub severity: String,
+    /// SQLSTATE code (e.g., `28P01` for an invalid password).
+    pub code: String,
+    pub message: String,
+}
+
+/// An untyped message sent by the client at the start of the connection.
+#[derive(Debug)]
+pub(super) enum StartupMessage {
+    SslRequest,
+    GssEncRequest,
+    CancelRequest,
+    Startup {
+        version: u32,
+        parameters: Vec<(String, String)>,
+    },
+}
+
+impl StartupMessage {
+    /// Parses a message including its 4-byte length.
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.len() < 8 {
+            bail!("truncated startup message");
+        }
+        let code = BigEndian::read_u32(&data[4..8]);
+        match code {
+            SSL_REQUEST_CODE => Ok(StartupMessage::SslRequest),
+            GSSENC_REQUEST_CODE => Ok(StartupMessage::GssEncRequest),
+            CANCEL_REQUEST_CODE => Ok(StartupMessage::CancelRequest),
+            version if version >> 16 == 3 => {
+                // Name/value pairs terminated by an empty name. Values may be empty.
+                let mut strings = data[8..]
+                    .split(|b| *b == 0)
+                    .map(|s| String::from_utf8_lossy(s).into_owned());
+                let mut parameters = vec![];
+                while let (Some(name), Some(value)) = (strings.next(), strings.next()) {
+                    if name.is_empty() {
+                        break;
+                    }
+                    parameters.push((name, value));
+                }
+                Ok(StartupMessage::Startup {
+                    version,
+                    parameters,
+                })
+            }
+            _ => bail!("unsupported startup code {}", code),
+        }
+    }
+}
+
+/// A typed message sent by the server.
+#[derive(Debug)]
+pub(super) enum BackendMessage {
+    Authentication { code: u32, mechanisms: Vec<String> },
+    ParameterStatus { name: String, value: String },
+    ErrorResponse(PostgresError),
+    ReadyForQuery,
+    Other,
+}
+
+impl BackendMessage {
+    /// Parses a message body with the given type byte.
+    pub(super) fn parse_from(msg_type: u8, body: &[u8]) -> Result<Self> {
+        match msg_type {
+            b'R' => {
+                if body.len() < 4 {
+                    bail!("truncated authentication request");
+                }
+                let code = BigEndian::read_u32(&body[..4]);
+                let mechanisms = match code {
+                    AUTH_SASL => cstrings(&body[4..]),
+                    _ => vec![],
+                };
+                Ok(BackendMessage::Authentication { code, mechanisms })
+            }
+            b'S' => {
+                // Values may be empty
+                let mut strings = body
+                    .split(|b| *b == 0)
+                    .map(|s| String::from_utf8_lossy(s).into_owned());
+                match (strings.next(), strings.next()) {
+                    (Some(name), Some(value)) => {
+                        Ok(BackendMessage::ParameterStatus { name, value })
+                    }
+                    _ => bail!("truncated parameter status"),
+                }
+            }
+            b'E' => {
+                let mut error = PostgresError::default();
+                // Fields are a type byte followed by a string, terminated by a zero byte
+                for field in body.split(|b| *b == 0) {
+                    if let Some((field_type, value)) = field.split_first() {
+                        let value = String::from_utf8_lossy(value).into_owned();
+                        match field_type {
+                            b'S' => error.severity = value,
+                            b'C' => error.code = value,
+                            b'M' => error.message = value,
+                            _ => (),
+                        }
+                    }
+                }
+                Ok(BackendMessage::ErrorResponse(error))
+            }
+            b'Z' => Ok(BackendMessage::ReadyForQuery),
+            _ => Ok(BackendMessage::Other),
+        }
+    }
+}
+
+/// Splits a sequence of NUL-terminated strings, stopping at an empty string.
+fn cstrings(data: &[u8]) -> Vec<String> {
+    data.split(|b| *b == 0)
+        .take_while(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
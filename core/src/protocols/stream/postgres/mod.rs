@@ -0,0 +1,111 @@
+//! PostgreSQL startup parsing.
+//!
+//! ## Remarks
+//! The parser follows the startup phase of a PostgreSQL connection
+//! ([protocol documentation](https://www.postgresql.org/docs/current/protocol-flow.html#PROTOCOL-FLOW-START-UP)):
+//! the optional SSLRequest or GSSENCRequest, the StartupMessage parameters, the authentication
+//! exchange, and the run-time parameters reported by the server. The session headers are
+//! considered done once the server is ready for queries, reports an error, or accepts an
+//! SSLRequest. In the latter case, the remainder of the stream is handed to the TLS parser and
+//! the TLS handshake is parsed as a second session in the same connection. Queries are not
+//! parsed.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed PostgreSQL startup contents.
+#[derive(Debug, Default, Serialize)]
+pub struct Postgres {
+    /// `true` if the client sent an SSLRequest.
+    pub ssl_request: bool,
+    /// `true` if the client sent a GSSENCRequest.
+    pub gssenc_request: bool,
+    /// `true` if the server accepted an SSLRequest or GSSENCRequest.
+    pub encryption_accepted: bool,
+    /// `true` if the client sent a CancelRequest instead of starting a session.
+    pub cancel_request: bool,
+    /// Protocol version requested in the StartupMessage (major version in the upper 16 bits).
+    pub protocol_version: Option<u32>,
+    /// StartupMessage parameters (e.g., `user`, `database`), in order.
+    pub parameters: Vec<(String, String)>,
+    /// Authentication request codes sent by the server, in order.
+    pub auth_requests: Vec<u32>,
+    /// SASL mechanisms offered by the server.
+    pub sasl_mechanisms: Vec<String>,
+    /// `true` if the server sent AuthenticationOk.
+    pub authenticated: bool,
+    /// Run-time parameters reported by the server (e.g., `server_version`), in order.
+    pub server_parameters: Vec<(String, String)>,
+    /// ErrorResponse sent by the server.
+    pub error: Option<PostgresError>,
+}
+
+impl Postgres {
+    fn parameter(&self, name: &str) -> &str {
+        self.parameters
+            .iter()
+            .find(|(k, _)| k == name)
+            .map_or("", |(_, v)| v)
+    }
+
+    /// Returns the protocol version (e.g., `"3.0"`), or `""` if no StartupMessage was observed.
+    pub fn protocol_version(&self) -> String {
+        self.protocol_version
+            .map_or(String::new(), |v| format!("{}.{}", v >> 16, v & 0xffff))
+    }
+
+    /// Returns the database user name.
+    pub fn user(&self) -> &str {
+        self.parameter("user")
+    }
+
+    /// Returns the database name, which defaults to the user name if not specified.
+    pub fn database(&self) -> &str {
+        match self.parameter("database") {
+            "" => self.user(),
+            database => database,
+        }
+    }
+
+    /// Returns the client application name, or `""` if not specified.
+    pub fn application_name(&self) -> &str {
+        self.parameter("application_name")
+    }
+
+    /// Returns the authentication method requested by the server (e.g., `"md5"`), `"trust"` if
+    /// the server accepted the client without authentication, or `""` if unknown.
+    pub fn auth_method(&self) -> &str {
+        match self.auth_requests.iter().find(|code| **code != AUTH_OK) {
+            Some(code) => auth_method_name(*code),
+            None if self.authenticated => "trust",
+            None => "",
+        }
+    }
+
+    /// Returns a comma-separated list of the SASL mechanisms offered by the server.
+    pub fn sasl_mechanisms(&self) -> String {
+        self.sasl_mechanisms.join(",")
+    }
+
+    /// Returns the server version reported by the server (e.g., `"16.2"`), or `""` if unknown.
+    pub fn server_version(&self) -> &str {
+        self.server_parameters
+            .iter()
+            .find(|(k, _)| k == "server_version")
+            .map_or("", |(_, v)| v)
+    }
+
+    /// Returns the SQLSTATE code of the server error (e.g., `"28P01"`), or `""` if none.
+    pub fn error_code(&self) -> &str {
+        self.error.as_ref().map_or("", |e| &e.code)
+    }
+
+    /// Returns the message of the server error, or `""` if none.
+    pub fn error_message(&self) -> &str {
+        self.error.as_ref().map_or("", |e| &e.message)
+    }
+}
@@ -0,0 +1,483 @@
+//! PostgreSQL startup parser.
+//!
+//! Reassembles the untyped startup messages sent by the client and the typed messages sent by the
+//! server until the connection is ready for queries.
+
+use super::message::*;
+use super::Postgres;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use byteorder::{BigEndian, ByteOrder};
+
+/// Upper bound on the size of a buffered server message.
+const MAX_BUFFER_LEN: usize = 1 << 16;
+
+/// Parses a single PostgreSQL startup per connection.
+#[derive(Debug)]
+pub struct PostgresParser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<Postgres>,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// `true` once the client has sent a StartupMessage. Later client messages are not parsed.
+    startup_sent: bool,
+    /// `true` while an SSLRequest or GSSENCRequest awaits the server's single-byte response.
+    encryption_pending: bool,
+    /// `true` if the server accepted an SSLRequest and the stream continues as TLS.
+    starttls: bool,
+}
+
+impl Default for PostgresParser {
+    fn default() -> Self {
+        PostgresParser {
+            sessions: vec![Postgres::default()],
+            client_buffer: vec![],
+            server_buffer: vec![],
+            startup_sent: false,
+            encryption_pending: false,
+            starttls: false,
+        }
+    }
+}
+
+impl PostgresParser {
+    /// Returns `true` if the remainder of the stream should be parsed as TLS.
+    pub(crate) fn starttls(&self) -> bool {
+        self.starttls
+    }
+
+    /// Process data segments from client to server
+    fn process_ctos(&mut self, data: &[u8]) -> ParseResult {
+        if self.startup_sent {
+            return ParseResult::Skipped;
+        }
+        let postgres = &mut self.sessions[0];
+        let mut v: Vec<u8>;
+        let mut cur_data = match self.client_buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(&mut self.client_buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        while cur_data.len() >= 4 && !self.startup_sent {
+            let len = BigEndian::read_u32(cur_data) as usize;
+            if !(8..=MAX_STARTUP_LEN).contains(&len) {
+                log::debug!("Invalid PostgreSQL startup message length {}", len);
+                return ParseResult::Skipped;
+            }
+            if cur_data.len() < len {
+                break;
+            }
+            match StartupMessage::parse_from(&cur_data[..len]) {
+                Ok(StartupMessage::SslRequest) => {
+                    postgres.ssl_request = true;
+                    self.encryption_pending = true;
+                }
+                Ok(StartupMessage::GssEncRequest) => {
+                    postgres.gssenc_request = true;
+                    self.encryption_pending = true;
+                }
+                Ok(StartupMessage::CancelRequest) => {
+                    postgres.cancel_request = true;
+                    return ParseResult::HeadersDone(0);
+                }
+                Ok(StartupMessage::Startup {
+                    version,
+                    parameters,
+                }) => {
+                    postgres.protocol_version = Some(version);
+                    postgres.parameters = parameters;
+                    self.startup_sent = true;
+                }
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return ParseResult::Skipped;
+                }
+            }
+            cur_data = &cur_data[len..];
+        }
+        if !self.startup_sent {
+            self.client_buffer.extend_from_slice(cur_data);
+        }
+        ParseResult::Continue(0)
+    }
+
+    /// Process data segments from server to client
+    fn process_stoc(&mut self, data: &[u8]) -> ParseResult {
+        let postgres = &mut self.sessions[0];
+        let mut data = data;
+        if self.encryption_pending && self.server_buffer.is_empty() {
+            self.encryption_pending = false;
+            match data[0] {
+                b'S' if postgres.ssl_request => {
+                    postgres.encryption_accepted = true;
+                    self.starttls = true;
+                    return ParseResult::HeadersDone(0);
+                }
+                b'G' if postgres.gssenc_request => {
+                    // Remainder of the stream is GSSAPI-encrypted
+                    postgres.encryption_accepted = true;
+                    return ParseResult::HeadersDone(0);
+                }
+                b'N' => data = &data[1..],
+                // Older servers reply with an ErrorResponse
+                _ => (),
+            }
+        }
+
+        let mut v: Vec<u8>;
+        let mut cur_data = match self.server_buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(&mut self.server_buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        while cur_data.len() >= 5 {
+            let len = 1 + BigEndian::read_u32(&cur_data[1..5]) as usize;
+            if len < 5 {
+                log::debug!("Invalid PostgreSQL message length");
+                return ParseResult::Skipped;
+            }
+            if cur_data.len() < len {
+                if len > MAX_BUFFER_LEN {
+                    log::debug!("PostgreSQL message exceeds maximum buffer size");
+                    return ParseResult::Skipped;
+                }
+                break;
+            }
+            match BackendMessage::parse_from(cur_data[0], &cur_data[5..len]) {
+                Ok(BackendMessage::Authentication { code, mechanisms }) => {
+                    postgres.auth_requests.push(code);
+                    postgres.sasl_mechanisms.extend(mechanisms);
+                    postgres.authenticated |= code == AUTH_OK;
+                }
+                Ok(BackendMessage::ParameterStatus { name, value }) => {
+                    postgres.server_parameters.push((name, value));
+                }
+                Ok(BackendMessage::ErrorResponse(error)) => {
+                    postgres.error = Some(error);
+                    return ParseResult::HeadersDone(0);
+                }
+                Ok(BackendMessage::ReadyForQuery) => {
+                    return ParseResult::HeadersDone(0);
+                }
+                Ok(BackendMessage::Other) => (),
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return ParseResult::Skipped;
+                }
+            }
+            cur_data = &cur_data[len..];
+        }
+        self.server_buffer.extend_from_slice(cur_data);
+        ParseResult::Continue(0)
+    }
+}
+
+impl ConnParsable for PostgresParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 || self.sessions.is_empty() {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.dir {
+                self.process_ctos(data)
+            } else {
+                self.process_stoc(data)
+            }
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+        if !pdu.dir || pdu.length() < 8 {
+            // The client speaks first
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            let len = BigEndian::read_u32(data) as usize;
+            let code = BigEndian::read_u32(&data[4..8]);
+            let valid = match code {
+                SSL_REQUEST_CODE | GSSENC_REQUEST_CODE => len == 8,
+                CANCEL_REQUEST_CODE => len == 16,
+                code => code >> 16 == 3 && len <= MAX_STARTUP_LEN,
+            };
+            if valid && len == data.len() {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|postgres| Session {
+            data: SessionData::Postgres(Box::new(postgres)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|postgres| Session {
+                data: SessionData::Postgres(Box::new(postgres)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        if self.starttls {
+            // TLS session expected next
+            ParsingState::Probing
+        } else {
+            ParsingState::Stop
+        }
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    const SSL_REQUEST: &[u8] = b"\x00\x00\x00\x08\x04\xd2\x16\x2f";
+    const CANCEL_REQUEST: &[u8] =
+        b"\x00\x00\x00\x10\x04\xd2\x16\x2e\x00\x00\x10\x92\x1b\xad\xf0\x0d";
+
+    /// StartupMessage sent by psql for user `alice` and database `inventory`.
+    const STARTUP: &[u8] = b"\
+        \x00\x00\x00\x52\x00\x03\x00\x00\x75\x73\x65\x72\x00\x61\x6c\x69\
+        \x63\x65\x00\x64\x61\x74\x61\x62\x61\x73\x65\x00\x69\x6e\x76\x65\
+        \x6e\x74\x6f\x72\x79\x00\x61\x70\x70\x6c\x69\x63\x61\x74\x69\x6f\
+        \x6e\x5f\x6e\x61\x6d\x65\x00\x70\x73\x71\x6c\x00\x63\x6c\x69\x65\
+        \x6e\x74\x5f\x65\x6e\x63\x6f\x64\x69\x6e\x67\x00\x55\x54\x46\x38\
+        \x00\x00";
+
+    /// AuthenticationSASL, AuthenticationSASLContinue, and AuthenticationSASLFinal.
+    const SASL: &[u8] = b"\
+        \x52\x00\x00\x00\x2a\x00\x00\x00\x0a\x53\x43\x52\x41\x4d\x2d\x53\
+        \x48\x41\x2d\x32\x35\x36\x2d\x50\x4c\x55\x53\x00\x53\x43\x52\x41\
+        \x4d\x2d\x53\x48\x41\x2d\x32\x35\x36\x00\x00\x52\x00\x00\x00\x4e\
+        \x00\x00\x00\x0b\x72\x3d\x66\x79\x6b\x6f\x2b\x64\x32\x6c\x62\x62\
+        \x46\x67\x4f\x4e\x52\x76\x39\x71\x6b\x78\x64\x61\x77\x4c\x33\x72\
+        \x66\x63\x4e\x48\x59\x4a\x59\x31\x5a\x56\x76\x57\x56\x73\x37\x6a\
+        \x2c\x73\x3d\x51\x53\x58\x43\x52\x2b\x51\x36\x73\x65\x6b\x38\x62\
+        \x66\x39\x32\x2c\x69\x3d\x34\x30\x39\x36\x52\x00\x00\x00\x36\x00\
+        \x00\x00\x0c\x76\x3d\x36\x72\x72\x69\x54\x52\x42\x69\x32\x33\x57\
+        \x70\x52\x52\x2f\x77\x74\x75\x70\x2b\x6d\x4d\x68\x55\x5a\x55\x6e\
+        \x2f\x64\x42\x35\x6e\x4c\x54\x4a\x52\x73\x6a\x6c\x39\x35\x47\x34\
+        \x3d";
+
+    /// AuthenticationOk, ParameterStatus of a PostgreSQL 16.2 server, BackendKeyData, and
+    /// ReadyForQuery.
+    const READY: &[u8] = b"\
+        \x52\x00\x00\x00\x08\x00\x00\x00\x00\x53\x00\x00\x00\x1a\x61\x70\
+        \x70\x6c\x69\x63\x61\x74\x69\x6f\x6e\x5f\x6e\x61\x6d\x65\x00\x70\
+        \x73\x71\x6c\x00\x53\x00\x00\x00\x19\x63\x6c\x69\x65\x6e\x74\x5f\
+        \x65\x6e\x63\x6f\x64\x69\x6e\x67\x00\x55\x54\x46\x38\x00\x53\x00\
+        \x00\x00\x18\x73\x65\x72\x76\x65\x72\x5f\x76\x65\x72\x73\x69\x6f\
+        \x6e\x00\x31\x36\x2e\x32\x00\x53\x00\x00\x00\x11\x54\x69\x6d\x65\
+        \x5a\x6f\x6e\x65\x00\x55\x54\x43\x00\x4b\x00\x00\x00\x0c\x00\x00\
+        \x10\x92\x1b\xad\xf0\x0d\x5a\x00\x00\x00\x05\x49";
+
+    /// AuthenticationMD5Password followed by an ErrorResponse for an invalid password.
+    const MD5_FAILED: &[u8] = b"\
+        \x52\x00\x00\x00\x0c\x00\x00\x00\x05\x9a\x2f\x11\xc4\x45\x00\x00\
+        \x00\x65\x53\x46\x41\x54\x41\x4c\x00\x56\x46\x41\x54\x41\x4c\x00\
+        \x43\x32\x38\x50\x30\x31\x00\x4d\x70\x61\x73\x73\x77\x6f\x72\x64\
+        \x20\x61\x75\x74\x68\x65\x6e\x74\x69\x63\x61\x74\x69\x6f\x6e\x20\
+        \x66\x61\x69\x6c\x65\x64\x20\x66\x6f\x72\x20\x75\x73\x65\x72\x20\
+        \x22\x61\x6c\x69\x63\x65\x22\x00\x46\x61\x75\x74\x68\x2e\x63\x00\
+        \x4c\x33\x32\x36\x00\x52\x61\x75\x74\x68\x5f\x66\x61\x69\x6c\x65\
+        \x64\x00\x00";
+
+    const FLOW: Flow = Flow::tcp(5432);
+
+    #[test]
+    fn core_postgres_probe() {
+        let parser = PostgresParser::default();
+        assert_eq!(FLOW.probe(&parser, STARTUP, true), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, SSL_REQUEST, true), ProbeResult::Certain);
+        assert_eq!(
+            FLOW.probe(&parser, CANCEL_REQUEST, true),
+            ProbeResult::Certain
+        );
+        // The length must span the segment
+        assert_eq!(
+            FLOW.probe(&parser, &STARTUP[..40], true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(FLOW.probe(&parser, READY, false), ProbeResult::NotForUs);
+        assert_eq!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_postgres_startup() {
+        let mut parser = PostgresParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &STARTUP[..30], true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &STARTUP[30..], true),
+            ParseResult::Continue(0)
+        );
+        // Split within the mechanism list, then within the SASLContinue header
+        assert_eq!(
+            FLOW.parse(&mut parser, &SASL[..20], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &SASL[20..47], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &SASL[47..], false),
+            ParseResult::Continue(0)
+        );
+        // SASLInitialResponse and SASLResponse are not parsed
+        assert_eq!(
+            FLOW.parse(&mut parser, b"p\x00\x00\x00\x08\x00\x00\x00", true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &READY[..50], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &READY[50..], false),
+            ParseResult::HeadersDone(0)
+        );
+
+        let postgres = &parser.sessions[0];
+        assert_eq!(postgres.protocol_version(), "3.0");
+        assert_eq!(postgres.user(), "alice");
+        assert_eq!(postgres.database(), "inventory");
+        assert_eq!(postgres.application_name(), "psql");
+        assert_eq!(postgres.parameters.len(), 4);
+        assert_eq!(postgres.auth_requests, [10, 11, 12, 0]);
+        assert_eq!(postgres.auth_method(), "sasl");
+        assert_eq!(
+            postgres.sasl_mechanisms(),
+            "SCRAM-SHA-256-PLUS,SCRAM-SHA-256"
+        );
+        assert!(postgres.authenticated);
+        assert_eq!(postgres.server_version(), "16.2");
+        assert_eq!(postgres.server_parameters.len(), 4);
+        assert!(postgres.error.is_none());
+        assert!(!parser.starttls());
+    }
+
+    #[test]
+    fn core_postgres_ssl() {
+        let mut parser = PostgresParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, SSL_REQUEST, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"S", false),
+            ParseResult::HeadersDone(0)
+        );
+        let postgres = &parser.sessions[0];
+        assert!(postgres.ssl_request);
+        assert!(postgres.encryption_accepted);
+        assert!(parser.starttls());
+        assert!(matches!(
+            parser.session_parsed_state(),
+            ParsingState::Probing
+        ));
+    }
+
+    #[test]
+    fn core_postgres_auth_failed() {
+        let mut parser = PostgresParser::default();
+        FLOW.parse(&mut parser, SSL_REQUEST, true);
+        // SSL refused, then a cleartext startup
+        assert_eq!(
+            FLOW.parse(&mut parser, b"N", false),
+            ParseResult::Continue(0)
+        );
+        FLOW.parse(&mut parser, STARTUP, true);
+        assert_eq!(
+            FLOW.parse(&mut parser, MD5_FAILED, false),
+            ParseResult::HeadersDone(0)
+        );
+        let postgres = &parser.sessions[0];
+        assert!(postgres.ssl_request);
+        assert!(!postgres.encryption_accepted);
+        assert_eq!(postgres.auth_method(), "md5");
+        assert!(!postgres.authenticated);
+        assert_eq!(postgres.error_code(), "28P01");
+        assert_eq!(
+            postgres.error_message(),
+            "password authentication failed for user \"alice\""
+        );
+        assert!(!parser.starttls());
+    }
+
+    #[test]
+    fn core_postgres_truncated() {
+        let mut parser = PostgresParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &STARTUP[..40], true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.client_buffer.len(), 40);
+        assert_eq!(
+            FLOW.parse(&mut parser, &SASL[..30], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.server_buffer.len(), 30);
+        // Message length shorter than the length field
+        let mut parser = PostgresParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x00\x00\x00\x02\x00\x03\x00\x00", true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"R\x00\x00\x00\x02", false),
+            ParseResult::Skipped
+        );
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Postgres(postgres) => {
+                assert_eq!(postgres.protocol_version(), "");
+                assert!(postgres.auth_requests.is_empty());
+            }
+            _ => panic!("Expected PostgreSQL session"),
+        }
+    }
+}
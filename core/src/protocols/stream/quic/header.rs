@@ -17,6 +17,8 @@ pub struct QuicLongHeader {
     pub token_len: Option<u64>,    // length of token in bytes, if packet is of type Init or Retry
     pub token: Option<String>,     // hex string, if packet is of type Init or Retry
     pub retry_tag: Option<String>, // hex string, if packet is of type Retry
+    // supported versions, if packet is of type VersionNegotiation
+    pub versions: Option<Vec<u32>>,
 }
 
 /// Quic Short Header
@@ -32,6 +34,7 @@ pub enum LongHeaderPacketType {
     ZeroRTT,
     Handshake,
     Retry,
+    VersionNegotiation, // identified by version 0 rather than a packet type
 }
 
 impl LongHeaderPacketType {
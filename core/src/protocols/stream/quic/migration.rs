@@ -0,0 +1,164 @@
+//! Linking of QUIC connections across paths.
+//!
+//! A QUIC connection survives changes to its five-tuple (NAT rebinding or path migration, see
+//! [RFC 9000 Section 9](https://datatracker.ietf.org/doc/html/rfc9000#section-9)), so a migrated
+//! connection arrives as a new UDP flow carrying only short headers. Connection IDs observed in
+//! long headers are registered in a table shared by all cores, which lets the parser on the new
+//! five-tuple associate short-header packets with the logical connection that issued the IDs.
+//!
+//! Entries are reference-counted by the parsers (one per path) that use them, and are removed
+//! once every path of the logical connection has been dropped from the connection table.
+
+use crate::conntrack::conn_id::FiveTuple;
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Maximum number of logical connections tracked at once. New connections are not linked once
+/// the table is full.
+const MAX_LINKED_CONNS: usize = 1 << 16;
+
+lazy_static! {
+    static ref CID_TABLE: RwLock<CidTable> = RwLock::new(CidTable::default());
+}
+
+#[derive(Debug, Default)]
+struct CidTable {
+    /// Maps connection IDs (hex strings) to logical connection IDs.
+    cids: HashMap<String, u64>,
+    /// Number of registered connection IDs of each length (in bytes).
+    cid_lens: HashMap<usize, usize>,
+    /// Logical connections, keyed by logical connection ID.
+    conns: HashMap<u64, LinkedConn>,
+    /// Next logical connection ID to assign.
+    next_id: u64,
+}
+
+#[derive(Debug, Default)]
+struct LinkedConn {
+    /// Connection IDs issued for the logical connection.
+    cids: Vec<String>,
+    /// Five-tuples the logical connection has been observed on, in order of first observation.
+    paths: Vec<FiveTuple>,
+    /// Number of parsers (one per path in the connection table) referencing the connection.
+    refs: usize,
+}
+
+impl CidTable {
+    fn insert_cid(&mut self, logical_id: u64, cid: &str) {
+        if self.cids.contains_key(cid) {
+            return;
+        }
+        if let Some(conn) = self.conns.get_mut(&logical_id) {
+            conn.cids.push(cid.to_string());
+            self.cids.insert(cid.to_string(), logical_id);
+            *self.cid_lens.entry(cid.len() / 2).or_default() += 1;
+        }
+    }
+}
+
+/// Registers the connection IDs `cids` of a connection observed on `path` and takes a reference
+/// to the logical connection they belong to, allocating one if none of them has been seen
+/// before. Returns the logical connection ID, or `None` if the table is full.
+pub(crate) fn register<'a>(cids: impl Iterator<Item = &'a String>, path: FiveTuple) -> Option<u64> {
+    let mut table = CID_TABLE.write().unwrap();
+    let cids: Vec<&String> = cids.collect();
+    let logical_id = match cids.iter().find_map(|cid| table.cids.get(*cid).copied()) {
+        Some(id) => id,
+        None => {
+            if table.conns.len() >= MAX_LINKED_CONNS {
+                log::debug!("QUIC CID table full");
+                return None;
+            }
+            let id = table.next_id;
+            table.next_id += 1;
+            table.conns.insert(id, LinkedConn::default());
+            id
+        }
+    };
+    let conn = table.conns.get_mut(&logical_id)?;
+    if !conn.paths.contains(&path) {
+        conn.paths.push(path);
+    }
+    conn.refs += 1;
+    for cid in cids {
+        table.insert_cid(logical_id, cid);
+    }
+    Some(logical_id)
+}
+
+/// Adds connection IDs issued after registration to the logical connection `logical_id`.
+pub(crate) fn add_cids<'a>(logical_id: u64, cids: impl Iterator<Item = &'a String>) {
+    let mut table = CID_TABLE.write().unwrap();
+    for cid in cids {
+        table.insert_cid(logical_id, cid);
+    }
+}
+
+/// Looks up the logical connection that issued the destination connection ID at the start of
+/// `dcid` (a short header DCID of unknown length). Returns the logical connection ID and the
+/// matched connection ID.
+pub(crate) fn lookup(dcid: &[u8]) -> Option<(u64, String)> {
+    let table = CID_TABLE.read().unwrap();
+    table
+        .cid_lens
+        .keys()
+        .filter(|len| **len > 0 && **len <= dcid.len())
+        .find_map(|len| {
+            let cid = hex::encode(&dcid[..*len]);
+            table.cids.get(&cid).map(|id| (*id, cid))
+        })
+}
+
+/// Adds `path` to the logical connection `logical_id`, takes a reference to it, and returns all
+/// of its connection IDs.
+pub(crate) fn join(logical_id: u64, path: FiveTuple) -> Vec<String> {
+    let mut table = CID_TABLE.write().unwrap();
+    match table.conns.get_mut(&logical_id) {
+        Some(conn) => {
+            if !conn.paths.contains(&path) {
+                conn.paths.push(path);
+            }
+            conn.refs += 1;
+            conn.cids.clone()
+        }
+        None => vec![],
+    }
+}
+
+/// Returns the paths the logical connection `logical_id` has been observed on.
+pub(crate) fn paths(logical_id: u64) -> Vec<FiveTuple> {
+    let table = CID_TABLE.read().unwrap();
+    table
+        .conns
+        .get(&logical_id)
+        .map(|conn| conn.paths.clone())
+        .unwrap_or_default()
+}
+
+/// Releases a reference to the logical connection `logical_id`, removing it (and its connection
+/// IDs) once no references remain.
+pub(crate) fn release(logical_id: u64) {
+    let mut table = CID_TABLE.write().unwrap();
+    let remove = match table.conns.get_mut(&logical_id) {
+        Some(conn) => {
+            conn.refs = conn.refs.saturating_sub(1);
+            conn.refs == 0
+        }
+        None => false,
+    };
+    if remove {
+        if let Some(conn) = table.conns.remove(&logical_id) {
+            for cid in conn.cids {
+                table.cids.remove(&cid);
+                let len = cid.len() / 2;
+                if let Some(cnt) = table.cid_lens.get_mut(&len) {
+                    *cnt -= 1;
+                    if *cnt == 0 {
+                        table.cid_lens.remove(&len);
+                    }
+                }
+            }
+        }
+    }
+}
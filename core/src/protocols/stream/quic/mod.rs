@@ -15,6 +15,12 @@
 //! - The parser will not parse a short header dcid if it is not a part of a pre-identified connection
 //! - The payload bytes count is a lazy counter which does not try to exclude tokens for encryption,
 //!   which is a process that happens in wireshark.
+//! - Packets are associated with a session (connection attempt) by connection ID. A client Initial
+//!   to an unseen DCID starts a new session, and packets with unknown connection IDs are attributed
+//!   to the most recent session.
+//! - Connection IDs are shared across cores so that a connection that migrates to a new five-tuple
+//!   is linked to its original path. Sessions on all paths of a connection report the same
+//!   `logical_id`, along with the paths observed so far.
 /*
 TODO: support parsing the tls out of the initial quic packet setup
 TODO support dns over quic
@@ -31,9 +37,11 @@ use header::LongHeaderPacketType;
 use serde::Serialize;
 
 use super::tls::Tls;
+use crate::conntrack::conn_id::FiveTuple;
 pub(crate) mod crypto;
 pub(crate) mod frame;
 pub(crate) mod header;
+pub(crate) mod migration;
 
 /// Errors Thrown throughout QUIC parsing. These are handled by Iris and used to skip packets.
 #[derive(Debug)]
@@ -65,6 +73,21 @@ pub struct QuicConn {
     // Parsed TLS messsages
     pub tls: Tls,
 
+    // Versions offered by the server in a Version Negotiation packet, if any
+    pub supported_versions: Vec<u32>,
+
+    // Token (hex string) provided by the server in a Retry packet, if any
+    pub retry_token: Option<String>,
+
+    // Identifier shared by all paths (five-tuples) of the same logical connection
+    pub logical_id: Option<u64>,
+
+    // Paths the logical connection has been observed on when the session was reported
+    pub paths: Vec<FiveTuple>,
+
+    // True if the session was first observed on a path the connection migrated to
+    pub migrated: bool,
+
     // Crypto needed to decrypt initial packets sent by client
     pub client_opener: Option<Open>,
 
@@ -78,6 +101,36 @@ pub struct QuicConn {
     // Server buffer for multi-packet TLS messages
     #[serde(skip_serializing)]
     pub server_buffer: Vec<u8>,
+
+    // Number of cids registered for cross-path linking
+    #[serde(skip_serializing)]
+    pub(crate) linked_cids: usize,
+}
+
+impl QuicConn {
+    /// Returns the client JA4 fingerprint of the ClientHello carried in the Initial packets, or
+    /// `""` if it was not observed. See [`Tls::ja4`].
+    pub fn ja4(&self) -> String {
+        self.tls.ja4_fingerprint('q', false)
+    }
+
+    /// Returns the client JA4 fingerprint with unhashed cipher suites and extensions (JA4_r), or
+    /// `""` if no ClientHello was observed.
+    pub fn ja4_r(&self) -> String {
+        self.tls.ja4_fingerprint('q', true)
+    }
+
+    /// Returns the server JA4S fingerprint of the ServerHello carried in the Initial packets, or
+    /// `""` if it was not observed. See [`Tls::ja4s`].
+    pub fn ja4s(&self) -> String {
+        self.tls.ja4s_fingerprint('q', false)
+    }
+
+    /// Returns the server JA4S fingerprint with unhashed extensions (JA4S_r), or `""` if no
+    /// ServerHello was observed.
+    pub fn ja4s_r(&self) -> String {
+        self.tls.ja4s_fingerprint('q', true)
+    }
 }
 
 /// Parsed Quic Packet contents
@@ -2,6 +2,7 @@
 //! Custom Quic Parser with many design choices borrowed from
 //! [Wireshark Quic Disector](https://gitlab.com/wireshark/wireshark/-/blob/master/epan/dissectors/packet-quic.c)
 //!
+use crate::conntrack::conn_id::FiveTuple;
 use crate::protocols::stream::quic::crypto::calc_init_keys;
 use crate::protocols::stream::quic::frame::QuicFrame;
 use crate::protocols::stream::quic::header::{
@@ -13,24 +14,120 @@ use crate::protocols::stream::{
     ConnParsable, L4Pdu, ParseResult, ParsingState, ProbeResult, Session, SessionData,
 };
 use byteorder::{BigEndian, ByteOrder};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use tls_parser::parse_tls_message_handshake;
 
-use super::QuicConn;
+use super::{migration, QuicConn};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct QuicParser {
-    // /// Maps session ID to Quic transaction
-    // sessions: HashMap<usize, QuicPacket>,
-    // /// Total sessions ever seen (Running session ID)
-    // cnt: usize,
-    sessions: Vec<QuicConn>,
+    /// Maps session ID to Quic connection
+    sessions: HashMap<usize, QuicConn>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Logical connections referenced by this path, released when the parser is dropped
+    linked: HashSet<u64>,
 }
 
-impl Default for QuicParser {
-    fn default() -> Self {
-        QuicParser {
-            sessions: vec![QuicConn::new()],
+impl QuicParser {
+    /// Returns the ID of the session that the datagram `data` belongs to, starting a new session
+    /// if applicable.
+    fn find_session(&mut self, data: &[u8], dir: bool, path: FiveTuple) -> Option<usize> {
+        if (data[0] & 0x80) != 0 {
+            // Long Header: match either connection ID
+            let (dcid, scid) = QuicPacket::long_header_cids(data)?;
+            let (dcid, scid) = (hex::encode(dcid), hex::encode(scid));
+            let found = self
+                .sessions
+                .iter()
+                .find(|(_, conn)| conn.cids.contains(&dcid) || conn.cids.contains(&scid))
+                .map(|(id, _)| *id);
+            if found.is_some() {
+                return found;
+            }
+            // A client Initial to an unseen DCID starts a new connection attempt
+            let initial = (data[0] & 0x30) == 0 && QuicPacket::long_header_version(data) != 0;
+            if (dir && initial) || self.sessions.is_empty() {
+                return Some(self.insert_session(QuicConn::new()));
+            }
+        } else {
+            // Short Header: match DCID prefix
+            let dcid_hex =
+                QuicPacket::vec_u8_to_hex_string(&data[1..std::cmp::min(data.len(), 21)]);
+            let found = self
+                .sessions
+                .iter()
+                .find(|(_, conn)| conn.cids.iter().any(|cid| dcid_hex.starts_with(cid)))
+                .map(|(id, _)| *id);
+            if found.is_some() {
+                return found;
+            }
+            if let Some((logical_id, _)) = migration::lookup(&data[1..]) {
+                // Connection migrated from another path
+                let mut conn = QuicConn::new();
+                conn.cids = migration::join(logical_id, path).into_iter().collect();
+                conn.linked_cids = conn.cids.len();
+                conn.logical_id = Some(logical_id);
+                conn.migrated = true;
+                if !self.linked.insert(logical_id) {
+                    migration::release(logical_id);
+                }
+                return Some(self.insert_session(conn));
+            }
+            if self.sessions.is_empty() {
+                return Some(self.insert_session(QuicConn::new()));
+            }
+        }
+        // Unknown connection IDs (e.g., issued in encrypted NEW_CONNECTION_ID frames) are
+        // attributed to the most recent session.
+        self.sessions.keys().max().copied()
+    }
+
+    fn insert_session(&mut self, conn: QuicConn) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, conn);
+        session_id
+    }
+
+    /// Registers connection IDs newly seen in session `session_id` for cross-path linking.
+    fn link(&mut self, session_id: usize, path: FiveTuple) {
+        let conn = match self.sessions.get_mut(&session_id) {
+            Some(conn) => conn,
+            None => return,
+        };
+        if conn.cids.len() == conn.linked_cids {
+            return;
+        }
+        match conn.logical_id {
+            Some(logical_id) => migration::add_cids(logical_id, conn.cids.iter()),
+            None => {
+                if let Some(logical_id) = migration::register(conn.cids.iter(), path) {
+                    conn.logical_id = Some(logical_id);
+                    if !self.linked.insert(logical_id) {
+                        migration::release(logical_id);
+                    }
+                }
+            }
+        }
+        conn.linked_cids = conn.cids.len();
+    }
+
+    fn to_session(mut quic: QuicConn, session_id: usize) -> Session {
+        if let Some(logical_id) = quic.logical_id {
+            quic.paths = migration::paths(logical_id);
+        }
+        Session {
+            data: SessionData::Quic(Box::new(quic)),
+            id: session_id,
+        }
+    }
+}
+
+impl Drop for QuicParser {
+    fn drop(&mut self) {
+        for logical_id in self.linked.drain() {
+            migration::release(logical_id);
         }
     }
 }
@@ -44,10 +141,26 @@ impl ConnParsable for QuicParser {
         }
 
         if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
-            if !self.sessions.is_empty() {
-                return self.sessions[0].parse_packet(data, pdu.dir);
-            }
-            ParseResult::Skipped
+            // Path in the orientation of the connection
+            let path = if pdu.dir {
+                FiveTuple::from_ctxt(pdu.ctxt)
+            } else {
+                FiveTuple {
+                    orig: pdu.ctxt.dst,
+                    resp: pdu.ctxt.src,
+                    proto: pdu.ctxt.proto,
+                }
+            };
+            let session_id = match self.find_session(data, pdu.dir, path) {
+                Some(session_id) => session_id,
+                None => return ParseResult::Skipped,
+            };
+            let result = match self.sessions.get_mut(&session_id) {
+                Some(conn) => conn.parse_packet(data, pdu.dir, session_id),
+                None => ParseResult::Skipped,
+            };
+            self.link(session_id, path);
+            result
         } else {
             log::warn!("Malformed packet on parse");
             ParseResult::Skipped
@@ -83,6 +196,9 @@ impl ConnParsable for QuicParser {
                     QuicVersion::Unknown => ProbeResult::NotForUs,
                     _ => ProbeResult::Certain,
                 }
+            } else if migration::lookup(&data[1..]).is_some() {
+                // Short header on a path the connection migrated to
+                ProbeResult::Certain
             } else {
                 ProbeResult::Unsure
             }
@@ -93,19 +209,15 @@ impl ConnParsable for QuicParser {
     }
 
     fn remove_session(&mut self, session_id: usize) -> Option<Session> {
-        self.sessions.pop().map(|quic| Session {
-            data: SessionData::Quic(Box::new(quic)),
-            id: session_id,
-        })
+        self.sessions
+            .remove(&session_id)
+            .map(|quic| QuicParser::to_session(quic, session_id))
     }
 
     fn drain_sessions(&mut self) -> Vec<Session> {
         self.sessions
-            .drain(..)
-            .map(|quic| Session {
-                data: SessionData::Quic(Box::new(quic)),
-                id: 0,
-            })
+            .drain()
+            .map(|(session_id, quic)| QuicParser::to_session(quic, session_id))
             .collect()
     }
 
@@ -194,6 +306,25 @@ impl QuicPacket {
         Ok(&data[start..end])
     }
 
+    /// Returns the version field of the long header packet at the start of `data`, or `0` if it
+    /// is too short.
+    pub(crate) fn long_header_version(data: &[u8]) -> u32 {
+        match data.get(1..5) {
+            Some(version_bytes) => BigEndian::read_u32(version_bytes),
+            None => 0,
+        }
+    }
+
+    /// Returns the destination and source connection IDs of the long header packet at the start
+    /// of `data`.
+    pub(crate) fn long_header_cids(data: &[u8]) -> Option<(&[u8], &[u8])> {
+        let dcid_len = *data.get(5)? as usize;
+        let dcid = data.get(6..6 + dcid_len)?;
+        let scid_len = *data.get(6 + dcid_len)? as usize;
+        let scid = data.get(7 + dcid_len..7 + dcid_len + scid_len)?;
+        Some((dcid, scid))
+    }
+
     /// Parses Quic packet from bytes
     pub fn parse_from(
         conn: &mut QuicConn,
@@ -203,25 +334,29 @@ impl QuicPacket {
     ) -> Result<(QuicPacket, usize), QuicError> {
         let packet_header_byte = QuicPacket::access_data(data, offset, offset + 1)?[0];
         offset += 1;
-        // Check the fixed bit
-        if (packet_header_byte & 0x40) == 0 {
-            return Err(QuicError::FixedBitNotSet);
-        }
         // Check the Header form
         if (packet_header_byte & 0x80) != 0 {
             // Long Header
-            // Parse packet type
-            let packet_type = LongHeaderPacketType::from_u8((packet_header_byte & 0x30) >> 4)?;
-            let type_specific = packet_header_byte & 0x0F; // Remainder of information from header byte, Reserved and protected packet number length
-                                                           // Parse version
+            // Parse version
             let version_bytes = QuicPacket::access_data(data, offset, offset + 4)?;
             let version = ((version_bytes[0] as u32) << 24)
                 | ((version_bytes[1] as u32) << 16)
                 | ((version_bytes[2] as u32) << 8)
                 | (version_bytes[3] as u32);
-            if QuicVersion::from_u32(version) == QuicVersion::Unknown {
-                return Err(QuicError::UnknownVersion);
-            }
+            let packet_type = match QuicVersion::from_u32(version) {
+                QuicVersion::Unknown => return Err(QuicError::UnknownVersion),
+                // Version Negotiation packets do not set the fixed bit or packet type
+                QuicVersion::ReservedNegotiation => LongHeaderPacketType::VersionNegotiation,
+                _ => {
+                    // Check the fixed bit
+                    if (packet_header_byte & 0x40) == 0 {
+                        return Err(QuicError::FixedBitNotSet);
+                    }
+                    // Parse packet type
+                    LongHeaderPacketType::from_u8((packet_header_byte & 0x30) >> 4)?
+                }
+            };
+            let type_specific = packet_header_byte & 0x0F; // Remainder of information from header byte, Reserved and protected packet number length
             offset += 4;
             // Parse DCID
             let dcid_len = QuicPacket::access_data(data, offset, offset + 1)?[0];
@@ -247,6 +382,7 @@ impl QuicPacket {
             let packet_len;
             let retry_tag;
             let decrypted_payload;
+            let mut supported_versions = None;
             // Parse packet type specific fields
             match packet_type {
                 LongHeaderPacketType::Initial => {
@@ -275,6 +411,10 @@ impl QuicPacket {
                     packet_len = Some(QuicPacket::slice_to_u64(packet_len_bytes)?);
                     offset += packet_len_len;
                     if conn.client_opener.is_none() {
+                        if !dir {
+                            // Initial keys are derived from the DCID chosen by the client
+                            return Err(QuicError::CryptoFail);
+                        }
                         // Derive initial keys
                         let [client_opener, server_opener] = calc_init_keys(dcid_bytes, version)?;
                         conn.client_opener = Some(client_opener);
@@ -364,6 +504,8 @@ impl QuicPacket {
                         offset + packet_len_len,
                     )?)?);
                     offset += packet_len_len;
+                    // Skip the encrypted payload
+                    QuicPacket::access_data(data, offset, offset + packet_len.unwrap() as usize)?;
                     offset += packet_len.unwrap() as usize;
                 }
                 LongHeaderPacketType::Retry => {
@@ -386,6 +528,28 @@ impl QuicPacket {
                     let retry_tag_bytes = QuicPacket::access_data(data, offset, offset + 16)?;
                     retry_tag = Some(QuicPacket::vec_u8_to_hex_string(retry_tag_bytes));
                     offset += 16;
+                    // The client's next Initial is sent to the SCID of the Retry, so its
+                    // initial keys are derived anew.
+                    conn.retry_token = token.clone();
+                    conn.reset_initial_keys();
+                }
+                LongHeaderPacketType::VersionNegotiation => {
+                    token_len = None;
+                    token = None;
+                    retry_tag = None;
+                    packet_len = None;
+                    decrypted_payload = None;
+                    // Remainder of the datagram is the list of supported versions
+                    let versions: Vec<u32> = QuicPacket::access_data(data, offset, data.len())?
+                        .chunks_exact(4)
+                        .map(BigEndian::read_u32)
+                        .collect();
+                    offset = data.len();
+                    // The client restarts the handshake with one of the supported versions,
+                    // whose initial keys may differ.
+                    conn.supported_versions = versions.clone();
+                    supported_versions = Some(versions);
+                    conn.reset_initial_keys();
                 }
             }
 
@@ -434,6 +598,7 @@ impl QuicPacket {
                         token_len,
                         token,
                         retry_tag,
+                        versions: supported_versions,
                     }),
                     frames,
                 },
@@ -441,6 +606,10 @@ impl QuicPacket {
             ))
         } else {
             // Short Header
+            // Check the fixed bit
+            if (packet_header_byte & 0x40) == 0 {
+                return Err(QuicError::FixedBitNotSet);
+            }
             let mut dcid_len = 20;
             if data.len() < 1 + dcid_len {
                 dcid_len = data.len() - 1;
@@ -481,14 +650,28 @@ impl QuicConn {
             packets: Vec::new(),
             cids: HashSet::new(),
             tls: Tls::new(),
+            supported_versions: Vec::new(),
+            retry_token: None,
+            logical_id: None,
+            paths: Vec::new(),
+            migrated: false,
             client_opener: None,
             server_opener: None,
             client_buffer: Vec::new(),
             server_buffer: Vec::new(),
+            linked_cids: 0,
         }
     }
 
-    fn parse_packet(&mut self, data: &[u8], direction: bool) -> ParseResult {
+    /// Discards initial keys and partial CRYPTO data after a Retry or Version Negotiation.
+    fn reset_initial_keys(&mut self) {
+        self.client_opener = None;
+        self.server_opener = None;
+        self.client_buffer.clear();
+        self.server_buffer.clear();
+    }
+
+    fn parse_packet(&mut self, data: &[u8], direction: bool, session_id: usize) -> ParseResult {
         let mut offset = 0;
         // Iterate over all of the data in the datagram
         // Parse as many QUIC packets as possible
@@ -506,8 +689,238 @@ impl QuicConn {
             .last()
             .is_some_and(|p| p.short_header.is_some())
         {
-            return ParseResult::HeadersDone(0);
+            return ParseResult::HeadersDone(session_id);
+        }
+        ParseResult::Continue(session_id)
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    /// Destination and source connection IDs of the client Initials.
+    const CLIENT_DCID: &[u8] = b"\x83\x94\xc8\xf0\x3e\x51\x57\x08";
+    const CLIENT_SCID: &[u8] = b"\xc3\xa1\xb2\xd4";
+
+    /// Client Initial carrying the first 120 bytes of the ClientHello.
+    const INITIAL_1: &[u8] = b"\
+        \xcd\x00\x00\x00\x01\x08\x83\x94\xc8\xf0\x3e\x51\x57\x08\x04\xc3\
+        \xa1\xb2\xd4\x00\x40\x8d\xbc\x46\xb4\x51\xa2\x9b\x7c\xa3\xc3\xc0\
+        \xa5\x76\x63\xdb\xc7\x58\x66\x2e\x44\xda\xd6\xde\x86\x8f\xed\x8f\
+        \x87\x2f\xa6\xe6\x83\xa8\x8f\xd9\x59\x73\x0b\xf9\x48\x06\xa7\x37\
+        \x02\x07\x5a\xd0\x22\x4a\x85\x2e\x95\xec\xc5\x79\xf8\xaa\x52\xd7\
+        \x67\x9a\x6f\x22\x5d\xa1\x90\xde\xa5\xb5\x1e\x00\x09\x6b\xc9\x02\
+        \x54\x57\x7c\xd2\x2f\xbf\x52\xae\xa7\x31\xee\x62\xfd\x88\x50\xf6\
+        \x17\xdf\x44\x34\xa0\x17\x02\x82\xf2\x54\x8f\x05\x7b\x7f\x47\x44\
+        \xfd\x90\x60\x32\x46\x7a\x9f\xe7\x8b\x25\xca\xc6\x9b\x48\xc3\x8a\
+        \x48\xc7\x02\x84\xd3\x59\x35\x59\x9f\xb6\x72\x46\x7e\x72\x42\x09\
+        \x8e\x9c\xc3";
+
+    /// Client Initial carrying the rest of the ClientHello.
+    const INITIAL_2: &[u8] = b"\
+        \xcd\x00\x00\x00\x01\x08\x83\x94\xc8\xf0\x3e\x51\x57\x08\x04\xc3\
+        \xa1\xb2\xd4\x00\x40\x86\x16\x6c\x7a\xd3\xfc\x77\x87\xc6\xce\xe3\
+        \xdd\x81\xa4\x22\x7c\xe3\x25\x60\x65\xe2\xb8\xa3\xe1\x60\x08\x84\
+        \xf7\x24\x55\xe5\x53\x04\xec\x39\xc7\xc0\xf8\x4a\x57\xb1\x84\x89\
+        \x3a\x2c\x3c\x59\x86\xf0\x4d\x11\x02\xce\xcd\x9a\xd9\x1e\x42\x76\
+        \x19\xb3\x0c\xbf\xc7\x1a\x24\xc8\xbc\xb8\x2e\x4b\x89\xaa\x1a\x83\
+        \x28\x26\x87\x64\x53\x6c\x4b\x59\x98\xa1\x7a\x19\x14\xdc\x1a\x8d\
+        \x3b\xe8\x6a\xb8\x4a\x92\xc2\x9c\xa1\x14\x38\x91\x7c\x13\x5d\xc7\
+        \xac\xef\xf5\x65\x5b\xec\xaa\xaf\x87\xf7\xd0\x4e\xa9\x84\xc6\x54\
+        \x7a\x5b\xb4\xd2\xda\xc4\x71\xdf\x6f\x3d\x41\x8d";
+
+    const FLOW: Flow = Flow::udp(443);
+
+    fn probe(data: &[u8]) -> ProbeResult {
+        FLOW.probe(&QuicParser::default(), data, true)
+    }
+
+    /// Returns a QUIC v1 long header packet with header byte `first` and `rest` following the
+    /// connection IDs.
+    fn long_header(first: u8, version: u32, dcid: &[u8], scid: &[u8], rest: &[u8]) -> Vec<u8> {
+        let mut pkt = vec![first];
+        pkt.extend_from_slice(&version.to_be_bytes());
+        pkt.push(dcid.len() as u8);
+        pkt.extend_from_slice(dcid);
+        pkt.push(scid.len() as u8);
+        pkt.extend_from_slice(scid);
+        pkt.extend_from_slice(rest);
+        pkt
+    }
+
+    /// Returns a Handshake packet with an opaque (encrypted) payload.
+    fn handshake(dcid: &[u8], scid: &[u8]) -> Vec<u8> {
+        let mut rest = vec![0x40, 0x20];
+        rest.extend_from_slice(&[0x5a; 0x20]);
+        long_header(0xe1, 1, dcid, scid, &rest)
+    }
+
+    /// Returns a short header packet with an opaque (encrypted) payload.
+    fn short_header(dcid: &[u8]) -> Vec<u8> {
+        let mut pkt = vec![0x41];
+        pkt.extend_from_slice(dcid);
+        pkt.extend_from_slice(&[0xa5; 24]);
+        pkt
+    }
+
+    #[test]
+    fn core_quic_probe() {
+        assert!(matches!(probe(INITIAL_1), ProbeResult::Certain));
+        assert!(matches!(
+            probe(&handshake(CLIENT_SCID, b"\x01\x02\x03\x04")),
+            ProbeResult::Certain
+        ));
+        // Unknown version
+        let mut unknown = INITIAL_1.to_vec();
+        unknown[1..5].copy_from_slice(&[0x0a, 0x1a, 0x2a, 0x3a]);
+        assert!(matches!(probe(&unknown), ProbeResult::NotForUs));
+        // Fixed bit unset
+        assert!(matches!(
+            probe(b"\x16\x03\x01\x00\xe8\x01\x00\x00\xe4"),
+            ProbeResult::NotForUs
+        ));
+        // Short header of an unknown connection
+        assert!(matches!(
+            probe(&short_header(b"\x9e\x11\x7c\x42\xd0\x05\x6b\x3f")),
+            ProbeResult::Unsure
+        ));
+        assert!(matches!(probe(b"\xc0\x00\x00"), ProbeResult::Unsure));
+    }
+
+    #[test]
+    fn core_quic_initial_split() {
+        let mut parser = QuicParser::default();
+        // The first Initial carries part of the ClientHello, which is buffered.
+        assert!(matches!(
+            FLOW.parse(&mut parser, INITIAL_1, true),
+            ParseResult::Skipped
+        ));
+        assert_eq!(parser.sessions[&0].client_buffer.len(), 120);
+        assert!(matches!(
+            FLOW.parse(&mut parser, INITIAL_2, true),
+            ParseResult::Continue(0)
+        ));
+        let quic = &parser.sessions[&0];
+        assert!(quic.client_buffer.is_empty());
+        assert!(quic.cids.contains(&hex::encode(CLIENT_DCID)));
+        assert!(quic.cids.contains(&hex::encode(CLIENT_SCID)));
+        assert_eq!(quic.tls.sni(), "quic.example.org");
+        let header = quic.packets[0].long_header.as_ref().unwrap();
+        assert!(matches!(header.packet_type, LongHeaderPacketType::Initial));
+        assert_eq!(header.token_len, Some(0));
+    }
+
+    #[test]
+    fn core_quic_headers_done() {
+        let mut parser = QuicParser::default();
+        let server_cid = b"\x5e\x7a\x61\x0b\xc4\x28\x93\xd1";
+        FLOW.parse(&mut parser, INITIAL_1, true);
+        FLOW.parse(&mut parser, INITIAL_2, true);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &handshake(CLIENT_SCID, server_cid), false),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, &short_header(server_cid), true),
+            ParseResult::HeadersDone(0)
+        ));
+        let sessions = parser.drain_sessions();
+        assert_eq!(sessions.len(), 1);
+        match &sessions[0].data {
+            SessionData::Quic(quic) => {
+                assert_eq!(quic.packets.len(), 3);
+                let short = quic.packets[2].short_header.as_ref().unwrap();
+                assert_eq!(short.dcid, Some(hex::encode(server_cid)));
+            }
+            _ => panic!("Expected QUIC session"),
         }
-        ParseResult::Continue(0)
+    }
+
+    #[test]
+    fn core_quic_migration() {
+        let server_cid = b"\x27\x4f\x90\x3d\xe2\x16\xab\x58";
+        let mut parser = QuicParser::default();
+        FLOW.parse(&mut parser, INITIAL_1, true);
+        FLOW.parse(&mut parser, INITIAL_2, true);
+        FLOW.parse(&mut parser, &handshake(CLIENT_SCID, server_cid), false);
+        // Short headers to a connection ID seen on another path
+        let migrated = short_header(server_cid);
+        assert!(matches!(probe(&migrated), ProbeResult::Certain));
+        let mut other = QuicParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut other, &migrated, true),
+            ParseResult::HeadersDone(0)
+        ));
+        assert!(other.sessions[&0].migrated);
+        assert_eq!(
+            other.sessions[&0].logical_id,
+            parser.sessions[&0].logical_id
+        );
+    }
+
+    #[test]
+    fn core_quic_version_negotiation() {
+        let mut parser = QuicParser::default();
+        FLOW.parse(&mut parser, INITIAL_1, true);
+        let versions = [0x6b3343cf_u32.to_be_bytes(), 1_u32.to_be_bytes()].concat();
+        let vn = long_header(0xa7, 0, CLIENT_SCID, CLIENT_DCID, &versions);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &vn, false),
+            ParseResult::Continue(0)
+        ));
+        let quic = &parser.sessions[&0];
+        assert_eq!(quic.supported_versions, vec![0x6b3343cf, 1]);
+        assert!(quic.client_opener.is_none());
+        assert!(quic.client_buffer.is_empty());
+    }
+
+    #[test]
+    fn core_quic_retry() {
+        let mut parser = QuicParser::default();
+        FLOW.parse(&mut parser, INITIAL_1, true);
+        let mut rest = b"\x7f\x3c\x01\x9a\x22".to_vec();
+        rest.extend_from_slice(&[0xee; 16]);
+        let retry = long_header(0xf0, 1, CLIENT_SCID, b"\x61\x8c\x0e\x45", &rest);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &retry, false),
+            ParseResult::Continue(0)
+        ));
+        let quic = &parser.sessions[&0];
+        assert_eq!(quic.retry_token.as_deref(), Some("7f3c019a22"));
+        let header = quic.packets[0].long_header.as_ref().unwrap();
+        assert!(matches!(header.packet_type, LongHeaderPacketType::Retry));
+        assert_eq!(header.retry_tag, Some("ee".repeat(16)));
+        assert!(quic.client_opener.is_none());
+    }
+
+    #[test]
+    fn core_quic_truncated() {
+        let mut parser = QuicParser::default();
+        // Too short for the connection IDs
+        assert!(matches!(
+            FLOW.parse(&mut parser, &INITIAL_1[..8], true),
+            ParseResult::Skipped
+        ));
+        // Too short for the encrypted payload
+        assert!(matches!(
+            FLOW.parse(&mut parser, &INITIAL_1[..100], true),
+            ParseResult::Skipped
+        ));
+        assert!(parser.sessions[&0].packets.is_empty());
+        // The complete datagrams are still parsed.
+        FLOW.parse(&mut parser, INITIAL_1, true);
+        assert!(matches!(
+            FLOW.parse(&mut parser, INITIAL_2, true),
+            ParseResult::Continue(0)
+        ));
+        assert_eq!(parser.sessions[&0].tls.sni(), "quic.example.org");
+        // Truncated Handshake packet
+        let truncated = handshake(CLIENT_SCID, b"\x01\x02\x03\x04");
+        assert!(matches!(
+            FLOW.parse(&mut parser, &truncated[..truncated.len() - 1], false),
+            ParseResult::Skipped
+        ));
     }
 }
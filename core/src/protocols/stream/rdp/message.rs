@@ -0,0 +1,171 @@
+//! RDP connection initiation components.
+//!
+//! See [MS-RDPBCGR](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/)
+//! sections 2.2.1.1 and 2.2.1.2. The X.224 Connection Request and Connection Confirm TPDUs are
+//! carried in TPKT headers ([RFC 1006](https://datatracker.ietf.org/doc/html/rfc1006)). Integers
+//! in the RDP negotiation structures are little-endian.
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::Serialize;
+
+/// Length of the TPKT header.
+pub(super) const TPKT_HDR_LEN: usize = 4;
+/// Length of the fixed part of an X.224 Connection Request or Connection Confirm TPDU.
+const X224_HDR_LEN: usize = 7;
+/// Length of an RDP negotiation request, response, or failure.
+const NEG_LEN: usize = 8;
+
+pub(super) const X224_CONNECTION_REQUEST: u8 = 0xe0;
+pub(super) const X224_CONNECTION_CONFIRM: u8 = 0xd0;
+
+const TYPE_RDP_NEG_REQ: u8 = 0x01;
+const TYPE_RDP_NEG_RSP: u8 = 0x02;
+const TYPE_RDP_NEG_FAILURE: u8 = 0x03;
+
+pub const PROTOCOL_RDP: u32 = 0x00;
+pub const PROTOCOL_SSL: u32 = 0x01;
+pub const PROTOCOL_HYBRID: u32 = 0x02;
+pub const PROTOCOL_RDSTLS: u32 = 0x04;
+pub const PROTOCOL_HYBRID_EX: u32 = 0x08;
+pub const PROTOCOL_RDSAAD: u32 = 0x10;
+
+/// Returns the name of a security protocol (e.g., `"hybrid"` for CredSSP).
+pub fn protocol_name(protocol: u32) -> &'static str {
+    match protocol {
+        PROTOCOL_RDP => "rdp",
+        PROTOCOL_SSL => "ssl",
+        PROTOCOL_HYBRID => "hybrid",
+        PROTOCOL_RDSTLS => "rdstls",
+        PROTOCOL_HYBRID_EX => "hybrid_ex",
+        PROTOCOL_RDSAAD => "rdsaad",
+        _ => "unknown",
+    }
+}
+
+/// Returns the names of the security protocols in a bitmask of requested protocols, separated by
+/// spaces. Standard RDP security is always included.
+pub fn protocol_names(protocols: u32) -> String {
+    let mut names = vec![protocol_name(PROTOCOL_RDP)];
+    for bit in 0..32 {
+        if protocols & (1 << bit) != 0 {
+            names.push(protocol_name(1 << bit));
+        }
+    }
+    names.join(" ")
+}
+
+/// Returns `true` if the security protocol is carried over TLS.
+pub fn uses_tls(protocol: u32) -> bool {
+    protocol != PROTOCOL_RDP
+}
+
+/// An X.224 Connection Request.
+#[derive(Clone, Debug, Serialize)]
+pub struct RdpConnectionRequest {
+    /// Username from an `mstshash` cookie, if present.
+    pub cookie: Option<String>,
+    /// Load balancer routing token, if present (e.g., `"msts=3640205228.15629.0000"`).
+    pub routing_token: Option<String>,
+    /// Negotiation request flags.
+    pub flags: u8,
+    /// Bitmask of requested security protocols, if the request carried an RDP negotiation
+    /// request.
+    pub requested_protocols: Option<u32>,
+}
+
+/// An X.224 Connection Confirm.
+#[derive(Clone, Debug, Serialize)]
+pub struct RdpConnectionConfirm {
+    /// Negotiation response flags.
+    pub flags: u8,
+    /// Security protocol selected by the server, if the confirm carried an RDP negotiation
+    /// response.
+    pub selected_protocol: Option<u32>,
+    /// Failure code, if the server rejected the negotiation (e.g., 2 for
+    /// `SSL_NOT_ALLOWED_BY_SERVER`).
+    pub failure_code: Option<u32>,
+}
+
+/// Returns the length of the TPKT at the start of `data`, or `None` if the header is incomplete.
+pub(super) fn tpkt_len(data: &[u8]) -> Result<Option<usize>> {
+    if data.len() < TPKT_HDR_LEN {
+        return Ok(None);
+    }
+    if data[0] != 3 || data[1] != 0 {
+        bail!("invalid TPKT version {}", data[0]);
+    }
+    let len = BigEndian::read_u16(&data[2..4]) as usize;
+    if len < TPKT_HDR_LEN + X224_HDR_LEN {
+        bail!("invalid TPKT length {}", len);
+    }
+    Ok(Some(len))
+}
+
+/// Returns the variable part of the X.224 TPDU with the given code in a TPKT.
+fn x224_data(tpkt: &[u8], code: u8) -> Result<&[u8]> {
+    let x224 = &tpkt[TPKT_HDR_LEN..];
+    let len = x224[0] as usize + 1;
+    if len < X224_HDR_LEN || len > x224.len() {
+        bail!("invalid X.224 length {}", len);
+    }
+    if x224[1] & 0xf0 != code {
+        bail!("unexpected X.224 TPDU code {:#x}", x224[1]);
+    }
+    Ok(&x224[X224_HDR_LEN..len])
+}
+
+impl RdpConnectionRequest {
+    /// Parses a Connection Request from a complete TPKT.
+    pub(super) fn parse_from(tpkt: &[u8]) -> Result<Self> {
+        let mut data = x224_data(tpkt, X224_CONNECTION_REQUEST)?;
+        let mut request = RdpConnectionRequest {
+            cookie: None,
+            routing_token: None,
+            flags: 0,
+            requested_protocols: None,
+        };
+        if data.starts_with(b"Cookie: ") {
+            let end = match data.windows(2).position(|w| w == b"\r\n") {
+                Some(end) => end,
+                None => bail!("unterminated cookie"),
+            };
+            let token = String::from_utf8_lossy(&data[8..end]).into_owned();
+            match token.strip_prefix("mstshash=") {
+                Some(username) => request.cookie = Some(username.to_owned()),
+                None => request.routing_token = Some(token),
+            }
+            data = &data[end + 2..];
+        }
+        if data.len() >= NEG_LEN && data[0] == TYPE_RDP_NEG_REQ {
+            request.flags = data[1];
+            request.requested_protocols = Some(LittleEndian::read_u32(&data[4..8]));
+        }
+        Ok(request)
+    }
+}
+
+impl RdpConnectionConfirm {
+    /// Parses a Connection Confirm from a complete TPKT.
+    pub(super) fn parse_from(tpkt: &[u8]) -> Result<Self> {
+        let data = x224_data(tpkt, X224_CONNECTION_CONFIRM)?;
+        let mut confirm = RdpConnectionConfirm {
+            flags: 0,
+            selected_protocol: None,
+            failure_code: None,
+        };
+        if data.len() >= NEG_LEN {
+            match data[0] {
+                TYPE_RDP_NEG_RSP => {
+                    confirm.flags = data[1];
+                    confirm.selected_protocol = Some(LittleEndian::read_u32(&data[4..8]));
+                }
+                TYPE_RDP_NEG_FAILURE => {
+                    confirm.failure_code = Some(LittleEndian::read_u32(&data[4..8]));
+                }
+                t => bail!("invalid negotiation type {}", t),
+            }
+        }
+        Ok(confirm)
+    }
+}
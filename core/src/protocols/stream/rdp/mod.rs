@@ -0,0 +1,90 @@
+//! RDP connection initiation parsing.
+//!
+//! ## Remarks
+//! The parser follows the connection initiation phase of RDP: the client's X.224 Connection
+//! Request, with its cookie or routing token and requested security protocols, and the server's
+//! Connection Confirm, with the selected security protocol or a negotiation failure. If an
+//! enhanced security protocol carried over TLS (TLS, CredSSP, RDSTLS, or RDSAAD) is selected, the
+//! remainder of the stream is handed to the TLS parser and the TLS handshake is parsed as a second
+//! session in the same connection. With standard RDP security, the rest of the connection is not
+//! parsed.
+//!
+//! The `mstshash` cookie carries the username (often truncated) typed by the user in some
+//! clients, and is not authenticated.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed RDP connection initiation contents.
+#[derive(Debug, Default, Serialize)]
+pub struct Rdp {
+    /// Connection Request, if observed.
+    pub request: Option<RdpConnectionRequest>,
+    /// Connection Confirm, if observed.
+    pub confirm: Option<RdpConnectionConfirm>,
+}
+
+impl Rdp {
+    /// Returns the username from the `mstshash` cookie, or `""` if there is none.
+    pub fn cookie(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|r| r.cookie.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the load balancer routing token, or `""` if there is none.
+    pub fn routing_token(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|r| r.routing_token.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the security protocols requested by the client (e.g., `"rdp ssl hybrid"`).
+    pub fn requested_protocols(&self) -> String {
+        match &self.request {
+            Some(request) => protocol_names(request.requested_protocols.unwrap_or(PROTOCOL_RDP)),
+            None => String::new(),
+        }
+    }
+
+    /// Returns the security protocol selected by the server (e.g., `"hybrid"`), or `""` if the
+    /// negotiation did not complete.
+    pub fn selected_protocol(&self) -> &str {
+        self.selected().map_or("", protocol_name)
+    }
+
+    /// Returns `true` if the server selected a security protocol carried over TLS.
+    pub fn tls(&self) -> bool {
+        self.selected().is_some_and(uses_tls)
+    }
+
+    /// Returns `true` if the server selected Network Level Authentication (CredSSP).
+    pub fn nla(&self) -> bool {
+        matches!(self.selected(), Some(PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX))
+    }
+
+    /// Returns the negotiation failure code, or 0 if the negotiation did not fail.
+    pub fn failure_code(&self) -> u32 {
+        self.confirm
+            .as_ref()
+            .and_then(|c| c.failure_code)
+            .unwrap_or(0)
+    }
+
+    /// Returns the selected security protocol. A Connection Confirm without a negotiation
+    /// response selects standard RDP security.
+    fn selected(&self) -> Option<u32> {
+        match &self.confirm {
+            Some(confirm) if confirm.failure_code.is_none() => {
+                Some(confirm.selected_protocol.unwrap_or(PROTOCOL_RDP))
+            }
+            _ => None,
+        }
+    }
+}
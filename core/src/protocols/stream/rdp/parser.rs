@@ -0,0 +1,341 @@
+//! RDP connection initiation parser.
+//!
+//! Parses the X.224 Connection Request and Connection Confirm, and hands the stream to the TLS
+//! parser if an enhanced security protocol was selected.
+
+use super::message::*;
+use super::Rdp;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+/// Parses a single RDP connection initiation per connection.
+#[derive(Debug)]
+pub struct RdpParser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<Rdp>,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// `true` if the server selected a security protocol carried over TLS.
+    starttls: bool,
+}
+
+impl Default for RdpParser {
+    fn default() -> Self {
+        RdpParser {
+            sessions: vec![Rdp::default()],
+            client_buffer: vec![],
+            server_buffer: vec![],
+            starttls: false,
+        }
+    }
+}
+
+impl RdpParser {
+    /// Returns `true` if the remainder of the stream should be parsed as TLS.
+    pub(crate) fn starttls(&self) -> bool {
+        self.starttls
+    }
+
+    /// Process data segments in direction `dir`
+    fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        buffer.extend_from_slice(data);
+        let len = match tpkt_len(buffer) {
+            Ok(Some(len)) if buffer.len() >= len => len,
+            Ok(_) => return ParseResult::Continue(0),
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                return ParseResult::HeadersDone(0);
+            }
+        };
+        let tpkt = std::mem::take(buffer);
+        let rdp = match self.sessions.first_mut() {
+            Some(rdp) => rdp,
+            None => return ParseResult::Skipped,
+        };
+        if dir {
+            match RdpConnectionRequest::parse_from(&tpkt[..len]) {
+                Ok(request) => {
+                    log::debug!("RDP connection request {:?}", request.requested_protocols);
+                    rdp.request = Some(request);
+                    ParseResult::Continue(0)
+                }
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    ParseResult::HeadersDone(0)
+                }
+            }
+        } else {
+            match RdpConnectionConfirm::parse_from(&tpkt[..len]) {
+                Ok(confirm) => {
+                    log::debug!("RDP connection confirm {:?}", confirm.selected_protocol);
+                    rdp.confirm = Some(confirm);
+                    self.starttls = rdp.tls();
+                }
+                Err(e) => log::debug!("parse error: {:?}", e),
+            }
+            ParseResult::HeadersDone(0)
+        }
+    }
+}
+
+impl ConnParsable for RdpParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+        if !pdu.dir {
+            // The client speaks first
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match tpkt_len(data) {
+                Ok(Some(_)) if data.len() > TPKT_HDR_LEN + 1 => {
+                    if data[TPKT_HDR_LEN + 1] & 0xf0 == X224_CONNECTION_REQUEST {
+                        ProbeResult::Certain
+                    } else {
+                        ProbeResult::NotForUs
+                    }
+                }
+                Ok(_) => ProbeResult::Unsure,
+                Err(_) => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|rdp| Session {
+            data: SessionData::Rdp(Box::new(rdp)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|rdp| Session {
+                data: SessionData::Rdp(Box::new(rdp)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        if self.starttls {
+            // TLS session expected next
+            ParsingState::Probing
+        } else {
+            ParsingState::Stop
+        }
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    /// Connection Request from `alice` requesting TLS, CredSSP, and CredSSP with Early User
+    /// Authorization.
+    const CONNECTION_REQUEST: &[u8] = b"\
+        \x03\x00\x00\x2b\x26\xe0\x00\x00\x00\x00\x00\x43\x6f\x6f\x6b\x69\
+        \x65\x3a\x20\x6d\x73\x74\x73\x68\x61\x73\x68\x3d\x61\x6c\x69\x63\
+        \x65\x0d\x0a\x01\x00\x08\x00\x0b\x00\x00\x00";
+
+    /// Connection Confirm selecting CredSSP.
+    const CONNECTION_CONFIRM: &[u8] = b"\
+        \x03\x00\x00\x13\x0e\xd0\x00\x00\x12\x34\x00\x02\x1f\x08\x00\x02\
+        \x00\x00\x00";
+
+    /// Connection Confirm rejecting the negotiation (`SSL_NOT_ALLOWED_BY_SERVER`).
+    const NEGOTIATION_FAILURE: &[u8] = b"\
+        \x03\x00\x00\x13\x0e\xd0\x00\x00\x12\x34\x00\x03\x00\x08\x00\x02\
+        \x00\x00\x00";
+
+    /// Connection Request with a load balancer routing token, requesting standard RDP security.
+    const ROUTED_REQUEST: &[u8] = b"\
+        \x03\x00\x00\x37\x32\xe0\x00\x00\x00\x00\x00\x43\x6f\x6f\x6b\x69\
+        \x65\x3a\x20\x6d\x73\x74\x73\x3d\x33\x36\x34\x30\x32\x30\x35\x32\
+        \x32\x38\x2e\x31\x35\x36\x32\x39\x2e\x30\x30\x30\x30\x0d\x0a\x01\
+        \x00\x08\x00\x00\x00\x00\x00";
+
+    /// Connection Confirm without a negotiation response.
+    const LEGACY_CONFIRM: &[u8] = b"\
+        \x03\x00\x00\x0b\x06\xd0\x00\x00\x12\x34\x00";
+
+    const FLOW: Flow = Flow::tcp(3389);
+
+    #[test]
+    fn core_rdp_probe() {
+        let parser = RdpParser::default();
+        assert_eq!(
+            FLOW.probe(&parser, CONNECTION_REQUEST, true),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            FLOW.probe(&parser, &CONNECTION_REQUEST[..3], true),
+            ProbeResult::Unsure
+        );
+        assert_eq!(
+            FLOW.probe(&parser, &CONNECTION_REQUEST[..5], true),
+            ProbeResult::Unsure
+        );
+        assert_eq!(
+            FLOW.probe(&parser, CONNECTION_CONFIRM, false),
+            ProbeResult::NotForUs
+        );
+        // Connection Confirm sent by the client
+        assert_eq!(
+            FLOW.probe(&parser, CONNECTION_CONFIRM, true),
+            ProbeResult::NotForUs
+        );
+        // TLS ClientHello
+        assert_eq!(
+            FLOW.probe(
+                &parser,
+                b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03",
+                true
+            ),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_rdp_negotiation() {
+        let mut parser = RdpParser::default();
+        // Split within the TPKT header, then within the cookie
+        assert_eq!(
+            FLOW.parse(&mut parser, &CONNECTION_REQUEST[..3], true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &CONNECTION_REQUEST[3..20], true),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.sessions[0].request.is_none());
+        assert_eq!(
+            FLOW.parse(&mut parser, &CONNECTION_REQUEST[20..], true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, CONNECTION_CONFIRM, false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.starttls());
+        assert!(matches!(
+            parser.session_parsed_state(),
+            ParsingState::Probing
+        ));
+
+        let rdp = &parser.sessions[0];
+        assert_eq!(rdp.cookie(), "alice");
+        assert_eq!(rdp.routing_token(), "");
+        assert_eq!(rdp.requested_protocols(), "rdp ssl hybrid hybrid_ex");
+        assert_eq!(rdp.selected_protocol(), "hybrid");
+        assert!(rdp.tls());
+        assert!(rdp.nla());
+        assert_eq!(rdp.failure_code(), 0);
+        assert_eq!(rdp.confirm.as_ref().unwrap().flags, 0x1f);
+    }
+
+    #[test]
+    fn core_rdp_failure() {
+        let mut parser = RdpParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, CONNECTION_REQUEST, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, NEGOTIATION_FAILURE, false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(!parser.starttls());
+        let rdp = &parser.sessions[0];
+        assert_eq!(rdp.failure_code(), 2);
+        assert_eq!(rdp.selected_protocol(), "");
+        assert!(!rdp.tls());
+    }
+
+    #[test]
+    fn core_rdp_standard_security() {
+        let mut parser = RdpParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, ROUTED_REQUEST, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, LEGACY_CONFIRM, false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(!parser.starttls());
+        assert!(matches!(parser.session_parsed_state(), ParsingState::Stop));
+
+        let rdp = &parser.sessions[0];
+        assert_eq!(rdp.cookie(), "");
+        assert_eq!(rdp.routing_token(), "msts=3640205228.15629.0000");
+        assert_eq!(rdp.requested_protocols(), "rdp");
+        assert_eq!(rdp.selected_protocol(), "rdp");
+        assert!(!rdp.nla());
+    }
+
+    #[test]
+    fn core_rdp_truncated() {
+        // X.224 length beyond the end of the TPKT
+        let mut parser = RdpParser::default();
+        let mut request = CONNECTION_REQUEST[..11].to_vec();
+        request[3] = 11;
+        assert_eq!(
+            FLOW.parse(&mut parser, &request, true),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.sessions[0].request.is_none());
+
+        // Cookie without its terminating CRLF
+        let mut parser = RdpParser::default();
+        let mut request = CONNECTION_REQUEST[..33].to_vec();
+        request[3] = 33;
+        request[4] = 28;
+        assert_eq!(
+            FLOW.parse(&mut parser, &request, true),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.sessions[0].request.is_none());
+
+        // TPKT too short for an X.224 header
+        let mut parser = RdpParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x03\x00\x00\x08\x03\xe0\x00\x00", true),
+            ParseResult::HeadersDone(0)
+        );
+    }
+}
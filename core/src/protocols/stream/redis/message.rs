@@ -0,0 +1,268 @@
+//! Redis serialization protocol (RESP) components.
+//!
+//! See the [RESP specification](https://redis.io/docs/latest/develop/reference/protocol-spec/).
+//! Both RESP2 and RESP3 types are supported. Values are decoded only as far as needed to record
+//! commands and summarize replies.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// Maximum nesting depth of aggregate replies.
+const MAX_DEPTH: usize = 8;
+/// Maximum number of command arguments recorded.
+pub(super) const MAX_ARGS: usize = 8;
+/// Maximum length of a recorded command argument.
+pub(super) const MAX_ARG_LEN: usize = 256;
+
+/// Returns the name of a RESP type prefix (e.g., `"bulk_string"`).
+pub fn resp_type_name(prefix: u8) -> &'static str {
+    match prefix {
+        b'+' => "simple_string",
+        b'-' => "error",
+        b':' => "integer",
+        b'$' => "bulk_string",
+        b'*' => "array",
+        b'_' => "null",
+        b'#' => "boolean",
+        b',' => "double",
+        b'(' => "big_number",
+        b'!' => "bulk_error",
+        b'=' => "verbatim_string",
+        b'%' => "map",
+        b'~' => "set",
+        b'>' => "push",
+        b'|' => "attribute",
+        _ => "unknown",
+    }
+}
+
+/// A Redis command.
+#[derive(Clone, Debug, Serialize)]
+pub struct RedisCommand {
+    /// Command name, uppercased (e.g., `GET`).
+    pub name: String,
+    /// Leading arguments, truncated. Credentials are not recorded.
+    pub args: Vec<String>,
+    /// Total number of arguments.
+    pub num_args: usize,
+    /// `true` if sent as an inline command rather than a RESP array.
+    pub inline: bool,
+    /// Encoded length in bytes.
+    pub length: usize,
+}
+
+/// Summary of a Redis reply.
+#[derive(Clone, Debug, Serialize)]
+pub struct RedisReply {
+    /// RESP type prefix.
+    pub prefix: u8,
+    /// Error message, for error replies.
+    pub error: Option<String>,
+    /// Integer value, string length, or number of elements, as applicable. -1 for a RESP2 null.
+    pub size: i64,
+    /// Encoded length in bytes.
+    pub length: usize,
+}
+
+impl RedisCommand {
+    /// Parses a command at the start of `data`. Returns `Ok(None)` if the command is incomplete.
+    pub(super) fn parse_from(data: &[u8]) -> Result<Option<Self>> {
+        if data.is_empty() {
+            return Ok(None);
+        }
+        if data[0] != b'*' {
+            // Inline command
+            let (line, length) = match read_line(data) {
+                Some((line, rest)) => (line, data.len() - rest.len()),
+                None => return Ok(None),
+            };
+            let mut words = line
+                .split(|b| *b == b' ')
+                .filter(|w| !w.is_empty())
+                .map(|w| String::from_utf8_lossy(w).into_owned());
+            let name = match words.next() {
+                Some(name) => name.to_ascii_uppercase(),
+                None => bail!("empty inline command"),
+            };
+            let args: Vec<String> = words.collect();
+            return Ok(Some(RedisCommand::new(name, args, true, length)));
+        }
+
+        let (header, mut rest) = match read_line(&data[1..]) {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        let count = parse_int(header)?;
+        if count < 1 {
+            bail!("invalid command array length {}", count);
+        }
+        let mut args = vec![];
+        for _ in 0..count {
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            if rest[0] != b'$' {
+                bail!("command argument is not a bulk string");
+            }
+            let (header, body) = match read_line(&rest[1..]) {
+                Some(line) => line,
+                None => return Ok(None),
+            };
+            let len = parse_int(header)?;
+            if len < 0 {
+                bail!("null command argument");
+            }
+            let len = len as usize;
+            if body.len() < len + 2 {
+                return Ok(None);
+            }
+            args.push(String::from_utf8_lossy(&body[..len.min(MAX_ARG_LEN)]).into_owned());
+            rest = &body[len + 2..];
+        }
+        let name = args.remove(0).to_ascii_uppercase();
+        let length = data.len() - rest.len();
+        Ok(Some(RedisCommand::new(name, args, false, length)))
+    }
+
+    fn new(name: String, mut args: Vec<String>, inline: bool, length: usize) -> Self {
+        let num_args = args.len();
+        match name.as_str() {
+            // AUTH [username] password
+            "AUTH" => args.clear(),
+            // HELLO [protover [AUTH username password] [SETNAME clientname]]
+            "HELLO" => {
+                if let Some(pos) = args.iter().position(|a| a.eq_ignore_ascii_case("AUTH")) {
+                    args.truncate(pos + 1);
+                }
+            }
+            _ => args.truncate(MAX_ARGS),
+        }
+        RedisCommand {
+            name,
+            args,
+            num_args,
+            inline,
+            length,
+        }
+    }
+}
+
+impl RedisReply {
+    /// Parses a reply at the start of `data`. Returns `Ok(None)` if the reply is incomplete.
+    pub(super) fn parse_from(data: &[u8]) -> Result<Option<Self>> {
+        let (prefix, line, body) = match split_header(data) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let length = match value_len(data, 0)? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        let (size, error) = match prefix {
+            b'-' => (0, Some(String::from_utf8_lossy(line).into_owned())),
+            b'!' => {
+                let len = parse_int(line)?;
+                let message = &body[..len.max(0) as usize];
+                (len, Some(String::from_utf8_lossy(message).into_owned()))
+            }
+            b':' | b'$' | b'*' | b'=' | b'%' | b'~' | b'>' | b'|' => (parse_int(line)?, None),
+            b'+' | b',' | b'(' => (line.len() as i64, None),
+            _ => (0, None),
+        };
+        Ok(Some(RedisReply {
+            prefix,
+            error,
+            size,
+            length,
+        }))
+    }
+
+    /// Parses the header of a bulk string reply at the start of `data` without requiring its
+    /// contents, for replies too large to buffer.
+    pub(super) fn parse_bulk_header(data: &[u8]) -> Option<Self> {
+        match split_header(data) {
+            Some((b'$', line, body)) => {
+                let size = parse_int(line).ok().filter(|len| *len >= 0)?;
+                Some(RedisReply {
+                    prefix: b'$',
+                    error: None,
+                    size,
+                    length: data.len() - body.len() + size as usize + 2,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns `true` for an error reply.
+    pub fn is_error(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// Splits the RESP value at the start of `data` into its type prefix, first line, and the data
+/// following the first line, or returns `None` if the first line is incomplete.
+fn split_header(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (prefix, rest) = data.split_first()?;
+    let (line, body) = read_line(rest)?;
+    Some((*prefix, line, body))
+}
+
+/// Returns the total encoded length of the RESP value at the start of `data`, or `None` if the
+/// value is incomplete.
+pub(super) fn value_len(data: &[u8], depth: usize) -> Result<Option<usize>> {
+    if depth > MAX_DEPTH {
+        bail!("RESP value nested too deeply");
+    }
+    let (prefix, line, body) = match split_header(data) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let header_len = data.len() - body.len();
+    match prefix {
+        b'+' | b'-' | b':' | b'_' | b'#' | b',' | b'(' => Ok(Some(header_len)),
+        b'$' | b'!' | b'=' => {
+            let len = parse_int(line)?;
+            if len < 0 {
+                return Ok(Some(header_len));
+            }
+            let len = header_len + len as usize + 2;
+            Ok((data.len() >= len).then_some(len))
+        }
+        b'*' | b'~' | b'>' | b'%' | b'|' => {
+            let count = parse_int(line)?;
+            if count < 0 {
+                return Ok(Some(header_len));
+            }
+            let count = match prefix {
+                b'%' | b'|' => 2 * count as usize,
+                _ => count as usize,
+            };
+            let mut len = header_len;
+            for _ in 0..count {
+                match value_len(&data[len..], depth + 1)? {
+                    Some(element_len) => len += element_len,
+                    None => return Ok(None),
+                }
+            }
+            if prefix == b'|' {
+                // Attributes precede the actual reply
+                return Ok(value_len(&data[len..], depth + 1)?.map(|reply_len| len + reply_len));
+            }
+            Ok(Some(len))
+        }
+        _ => bail!("invalid RESP type prefix {:#x}", prefix),
+    }
+}
+
+/// Splits `data` at the first CRLF, returning the line and the data following it.
+pub(super) fn read_line(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    memchr::memmem::find(data, b"\r\n").map(|pos| (&data[..pos], &data[pos + 2..]))
+}
+
+pub(super) fn parse_int(line: &[u8]) -> Result<i64> {
+    match std::str::from_utf8(line).ok().and_then(|s| s.parse().ok()) {
+        Some(n) => Ok(n),
+        None => bail!("invalid RESP integer"),
+    }
+}
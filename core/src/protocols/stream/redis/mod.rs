@@ -0,0 +1,81 @@
+//! Redis command parsing.
+//!
+//! ## Remarks
+//! The parser records each command sent by the client as a separate session, paired with its
+//! reply in order (Redis replies to pipelined commands in the order they were sent). Only a
+//! summary of each reply is recorded: its type, size, and error message, if any. Credentials sent
+//! with `AUTH` and `HELLO` are not recorded.
+//!
+//! Redis connections are often long-lived, so completed commands are retained and delivered when
+//! the connection terminates, or once a bounded number of them have been observed. Parsing stops
+//! once the connection enters a mode where replies are no longer paired with commands (e.g.,
+//! after `SUBSCRIBE` or `MONITOR`), or a message exceeds the maximum buffer size.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed Redis command and reply.
+#[derive(Debug, Serialize)]
+pub struct Redis {
+    /// Command sent by the client.
+    pub command: Option<RedisCommand>,
+    /// Reply sent by the server.
+    pub reply: Option<RedisReply>,
+}
+
+impl Redis {
+    /// Returns the command name, uppercased (e.g., `"CONFIG"`), or `""` if the command was not
+    /// observed.
+    pub fn command(&self) -> &str {
+        self.command.as_ref().map_or("", |c| &c.name)
+    }
+
+    /// Returns the first argument of the command (typically the key or subcommand), or `""` if
+    /// there is none.
+    pub fn key(&self) -> &str {
+        self.command
+            .as_ref()
+            .and_then(|c| c.args.first())
+            .map_or("", |arg| arg)
+    }
+
+    /// Returns the recorded arguments of the command, separated by spaces.
+    pub fn args(&self) -> String {
+        self.command
+            .as_ref()
+            .map_or(String::new(), |c| c.args.join(" "))
+    }
+
+    /// Returns the total number of arguments of the command.
+    pub fn num_args(&self) -> usize {
+        self.command.as_ref().map_or(0, |c| c.num_args)
+    }
+
+    /// Returns the reply type (e.g., `"bulk_string"`), or `""` if no reply was observed.
+    pub fn reply_type(&self) -> &str {
+        self.reply.as_ref().map_or("", |r| resp_type_name(r.prefix))
+    }
+
+    /// Returns the error message of an error reply (e.g., `"NOAUTH Authentication required."`),
+    /// or `""` if the reply was not an error.
+    pub fn error(&self) -> &str {
+        self.reply
+            .as_ref()
+            .and_then(|r| r.error.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the encoded length of the command in bytes.
+    pub fn request_bytes(&self) -> usize {
+        self.command.as_ref().map_or(0, |c| c.length)
+    }
+
+    /// Returns the encoded length of the reply in bytes.
+    pub fn reply_bytes(&self) -> usize {
+        self.reply.as_ref().map_or(0, |r| r.length)
+    }
+}
@@ -0,0 +1,417 @@
+//! Redis command parser.
+//!
+//! Reassembles RESP commands and replies, and pairs each reply with the oldest outstanding
+//! command.
+
+use super::message::*;
+use super::Redis;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::{HashMap, VecDeque};
+
+/// Maximum number of completed commands recorded before parsing stops.
+const MAX_COMMANDS: usize = 64;
+/// Upper bound on the size of a buffered command or reply.
+const MAX_BUFFER_LEN: usize = 1 << 16;
+/// Inline commands recognized when probing.
+const INLINE_COMMANDS: [&[u8]; 4] = [b"PING", b"INFO", b"AUTH", b"HELLO"];
+
+#[derive(Default, Debug)]
+pub struct RedisParser {
+    /// Maps session ID to Redis command
+    sessions: HashMap<usize, Redis>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Session IDs of commands awaiting a reply, in order
+    pending: VecDeque<usize>,
+    /// Number of commands with a reply
+    completed: usize,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// Remaining bytes of a large reply that are not buffered
+    server_skip: usize,
+}
+
+impl ConnParsable for RedisParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.dir {
+                self.process_ctos(data)
+            } else {
+                self.process_stoc(data)
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+        if !pdu.dir || pdu.length() < 4 {
+            // The client speaks first
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            let valid = if data[0] == b'*' {
+                // Array of bulk strings
+                match read_line(&data[1..]) {
+                    Some((count, rest)) => {
+                        parse_int(count).is_ok_and(|n| n > 0) && rest.first() == Some(&b'$')
+                    }
+                    None => false,
+                }
+            } else {
+                match read_line(data) {
+                    Some((line, _)) => {
+                        let name = line.split(|b| *b == b' ').next().unwrap_or_default();
+                        INLINE_COMMANDS
+                            .iter()
+                            .any(|command| name.eq_ignore_ascii_case(command))
+                    }
+                    None => false,
+                }
+            };
+            if valid {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|redis| Session {
+            data: SessionData::Redis(Box::new(redis)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, redis)| Session {
+                data: SessionData::Redis(Box::new(redis)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider Redis to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl RedisParser {
+    /// Process data segments from client to server
+    fn process_ctos(&mut self, data: &[u8]) -> ParseResult {
+        let mut v: Vec<u8>;
+        let mut cur_data = match self.client_buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(&mut self.client_buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        loop {
+            match RedisCommand::parse_from(cur_data) {
+                Ok(Some(command)) => {
+                    log::debug!("Redis {}", command.name);
+                    cur_data = &cur_data[command.length..];
+                    let session_id = self.insert(Redis {
+                        command: Some(command),
+                        reply: None,
+                    });
+                    self.pending.push_back(session_id);
+                    result = ParseResult::Continue(session_id);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return result;
+                }
+            }
+        }
+        if cur_data.len() > MAX_BUFFER_LEN {
+            log::debug!("Redis command exceeds maximum buffer size");
+            return self.stop();
+        }
+        self.client_buffer.extend_from_slice(cur_data);
+        result
+    }
+
+    /// Process data segments from server to client
+    fn process_stoc(&mut self, data: &[u8]) -> ParseResult {
+        let skipped = self.server_skip.min(data.len());
+        self.server_skip -= skipped;
+        let data = &data[skipped..];
+
+        let mut v: Vec<u8>;
+        let mut cur_data = match self.server_buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(&mut self.server_buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        while !cur_data.is_empty() {
+            let reply = match RedisReply::parse_from(cur_data) {
+                Ok(Some(reply)) => {
+                    cur_data = &cur_data[reply.length..];
+                    reply
+                }
+                Ok(None) if cur_data.len() > MAX_BUFFER_LEN => {
+                    match RedisReply::parse_bulk_header(cur_data) {
+                        Some(reply) => {
+                            self.server_skip = reply.length - cur_data.len();
+                            cur_data = &[];
+                            reply
+                        }
+                        None => {
+                            log::debug!("Redis reply exceeds maximum buffer size");
+                            return self.stop();
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return result;
+                }
+            };
+            if reply.prefix == b'>' {
+                // Out-of-band push data (RESP3)
+                continue;
+            }
+
+            let session_id = match self.pending.pop_front() {
+                Some(session_id) => session_id,
+                None => self.insert(Redis {
+                    command: None,
+                    reply: None,
+                }),
+            };
+            let mut unpaired = false;
+            if let Some(redis) = self.sessions.get_mut(&session_id) {
+                unpaired = !reply.is_error()
+                    && matches!(
+                        redis.command(),
+                        "SUBSCRIBE" | "PSUBSCRIBE" | "SSUBSCRIBE" | "MONITOR"
+                    );
+                redis.reply = Some(reply);
+            }
+            self.completed += 1;
+            if unpaired || self.completed >= MAX_COMMANDS {
+                return ParseResult::HeadersDone(session_id);
+            }
+            result = ParseResult::Continue(session_id);
+        }
+        self.server_buffer.extend_from_slice(cur_data);
+        result
+    }
+
+    /// Stops parsing, reporting the most recent session.
+    fn stop(&mut self) -> ParseResult {
+        self.client_buffer.clear();
+        self.server_buffer.clear();
+        ParseResult::HeadersDone(self.cnt.saturating_sub(1))
+    }
+
+    fn insert(&mut self, redis: Redis) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, redis);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    /// Pipelined `AUTH`, `SET`, and `GET` commands sent by redis-cli.
+    const PIPELINE: &[u8] = b"*3\r\n$4\r\nAUTH\r\n$7\r\ndefault\r\n$6\r\nhunter\r\n\
+        *3\r\n$3\r\nSET\r\n$11\r\nsession:123\r\n$5\r\nhello\r\n\
+        *2\r\n$3\r\nGET\r\n$11\r\nsession:123\r\n";
+    const REPLIES: &[u8] = b"+OK\r\n+OK\r\n$5\r\nhello\r\n";
+
+    const FLOW: Flow = Flow::tcp(6379);
+
+    #[test]
+    fn core_redis_probe() {
+        let parser = RedisParser::default();
+        assert_eq!(FLOW.probe(&parser, PIPELINE, true), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, b"ping\r\n", true), ProbeResult::Certain);
+        assert_eq!(
+            FLOW.probe(&parser, b"INFO server\r\n", true),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"+PONG\r\n", false),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(FLOW.probe(&parser, b"*0\r\n", true), ProbeResult::NotForUs);
+        assert_eq!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(FLOW.probe(&parser, b"PING", true), ProbeResult::NotForUs);
+    }
+
+    #[test]
+    fn core_redis_pipeline() {
+        let mut parser = RedisParser::default();
+        // Split within bulk strings
+        assert_eq!(
+            FLOW.parse(&mut parser, &PIPELINE[..20], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &PIPELINE[20..50], true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &PIPELINE[50..], true),
+            ParseResult::Continue(2)
+        );
+        assert!(parser.client_buffer.is_empty());
+        assert_eq!(
+            FLOW.parse(&mut parser, &REPLIES[..14], false),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &REPLIES[14..], false),
+            ParseResult::Continue(2)
+        );
+
+        let auth = &parser.sessions[&0];
+        assert_eq!(auth.command(), "AUTH");
+        assert_eq!(auth.args(), "");
+        assert_eq!(auth.num_args(), 2);
+        assert_eq!(auth.reply_type(), "simple_string");
+        let set = &parser.sessions[&1];
+        assert_eq!(set.command(), "SET");
+        assert_eq!(set.key(), "session:123");
+        assert_eq!(set.args(), "session:123 hello");
+        assert_eq!(set.request_bytes(), 42);
+        let get = &parser.sessions[&2];
+        assert_eq!(get.reply_type(), "bulk_string");
+        assert_eq!(get.reply.as_ref().unwrap().size, 5);
+        assert_eq!(get.reply_bytes(), 11);
+        assert_eq!(get.error(), "");
+        assert!(parser.pending.is_empty());
+    }
+
+    #[test]
+    fn core_redis_error() {
+        let mut parser = RedisParser::default();
+        FLOW.parse(&mut parser, b"CONFIG GET dir\r\n", true);
+        assert_eq!(
+            FLOW.parse(&mut parser, b"-NOAUTH Authentication required.\r\n", false),
+            ParseResult::Continue(0)
+        );
+        let redis = &parser.sessions[&0];
+        assert!(redis.command.as_ref().unwrap().inline);
+        assert_eq!(redis.command(), "CONFIG");
+        assert_eq!(redis.args(), "GET dir");
+        assert_eq!(redis.reply_type(), "error");
+        assert_eq!(redis.error(), "NOAUTH Authentication required.");
+    }
+
+    #[test]
+    fn core_redis_subscribe() {
+        let mut parser = RedisParser::default();
+        FLOW.parse(
+            &mut parser,
+            b"*2\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n",
+            true,
+        );
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n",
+                false
+            ),
+            ParseResult::HeadersDone(0)
+        );
+        assert_eq!(parser.sessions[&0].reply_type(), "array");
+    }
+
+    #[test]
+    fn core_redis_large_reply() {
+        let mut parser = RedisParser::default();
+        FLOW.parse(&mut parser, b"*2\r\n$3\r\nGET\r\n$4\r\nblob\r\n", true);
+        FLOW.parse(&mut parser, b"PING\r\n", true);
+        let mut reply = b"$100000\r\n".to_vec();
+        reply.resize(9 + 100000 + 2, b'x');
+        reply.extend_from_slice(b"+PONG\r\n");
+        // Buffered until the maximum buffer size, then skipped
+        for chunk in reply[..4 * 16384].chunks(16384) {
+            assert_eq!(FLOW.parse(&mut parser, chunk, false), ParseResult::Skipped);
+        }
+        assert_eq!(
+            FLOW.parse(&mut parser, &reply[4 * 16384..5 * 16384], false),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.server_buffer.is_empty());
+        assert!(parser.server_skip > 0);
+        assert_eq!(parser.sessions[&0].reply.as_ref().unwrap().size, 100000);
+        assert_eq!(
+            FLOW.parse(&mut parser, &reply[5 * 16384..], false),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(parser.server_skip, 0);
+        assert_eq!(parser.sessions[&1].reply_type(), "simple_string");
+    }
+
+    #[test]
+    fn core_redis_truncated() {
+        let mut parser = RedisParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, b"*2\r\n$3\r\nGET\r\n$4\r\nbl", true),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+        assert_eq!(
+            FLOW.parse(&mut parser, b"$5\r\nhel", false),
+            ParseResult::Skipped
+        );
+        assert_eq!(parser.server_buffer.len(), 7);
+        // Argument that is not a bulk string
+        let mut parser = RedisParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, b"*2\r\n:1\r\n:2\r\n", true),
+            ParseResult::Skipped
+        );
+        assert!(parser.drain_sessions().is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! RTP and RTCP packet components.
+//!
+//! See [RFC 3550](https://datatracker.ietf.org/doc/html/rfc3550). RTP and RTCP packets
+//! multiplexed on one port are distinguished by their packet type, as described in
+//! [RFC 5761](https://datatracker.ietf.org/doc/html/rfc5761#section-4).
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+
+pub const RTP_VERSION: u8 = 2;
+const RTP_HDR_LEN: usize = 12;
+const RTCP_HDR_LEN: usize = 4;
+const REPORT_BLOCK_LEN: usize = 24;
+
+pub const RTCP_SR: u8 = 200;
+pub const RTCP_RR: u8 = 201;
+pub const RTCP_SDES: u8 = 202;
+pub const RTCP_BYE: u8 = 203;
+const SDES_CNAME: u8 = 1;
+
+/// Returns the encoding name and clock rate of a static RTP payload type, as assigned by
+/// [RFC 3551](https://datatracker.ietf.org/doc/html/rfc3551#section-6).
+pub fn static_payload_type(payload_type: u8) -> Option<(&'static str, u32)> {
+    match payload_type {
+        0 => Some(("PCMU", 8000)),
+        3 => Some(("GSM", 8000)),
+        4 => Some(("G723", 8000)),
+        5 => Some(("DVI4", 8000)),
+        6 => Some(("DVI4", 16000)),
+        7 => Some(("LPC", 8000)),
+        8 => Some(("PCMA", 8000)),
+        9 => Some(("G722", 8000)),
+        10 | 11 => Some(("L16", 44100)),
+        12 => Some(("QCELP", 8000)),
+        13 => Some(("CN", 8000)),
+        14 => Some(("MPA", 90000)),
+        15 => Some(("G728", 8000)),
+        16 => Some(("DVI4", 11025)),
+        17 => Some(("DVI4", 22050)),
+        18 => Some(("G729", 8000)),
+        25 => Some(("CelB", 90000)),
+        26 => Some(("JPEG", 90000)),
+        28 => Some(("nv", 90000)),
+        31 => Some(("H261", 90000)),
+        32 => Some(("MPV", 90000)),
+        33 => Some(("MP2T", 90000)),
+        34 => Some(("H263", 90000)),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `data` is an RTCP packet rather than an RTP packet.
+pub(super) fn is_rtcp(data: &[u8]) -> bool {
+    data.len() >= 2 && (192..=223).contains(&data[1])
+}
+
+/// An RTP fixed header.
+#[derive(Debug)]
+pub(super) struct RtpHeader {
+    pub(super) payload_type: u8,
+    pub(super) seq: u16,
+    pub(super) timestamp: u32,
+    pub(super) ssrc: u32,
+    /// Length of the payload, excluding padding.
+    pub(super) payload_len: usize,
+}
+
+impl RtpHeader {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.len() < RTP_HDR_LEN {
+            bail!("truncated RTP header");
+        }
+        if data[0] >> 6 != RTP_VERSION {
+            bail!("invalid RTP version {}", data[0] >> 6);
+        }
+        let padding = data[0] & 0x20 != 0;
+        let extension = data[0] & 0x10 != 0;
+        let csrc_count = (data[0] & 0x0f) as usize;
+
+        let mut hdr_len = RTP_HDR_LEN + 4 * csrc_count;
+        if extension {
+            if data.len() < hdr_len + 4 {
+                bail!("truncated RTP header extension");
+            }
+            hdr_len += 4 + 4 * BigEndian::read_u16(&data[hdr_len + 2..]) as usize;
+        }
+        let padding_len = match padding {
+            true => *data.last().unwrap_or(&0) as usize,
+            false => 0,
+        };
+        if (padding && padding_len == 0) || data.len() < hdr_len + padding_len {
+            bail!("invalid RTP packet length");
+        }
+        Ok(RtpHeader {
+            payload_type: data[1] & 0x7f,
+            seq: BigEndian::read_u16(&data[2..4]),
+            timestamp: BigEndian::read_u32(&data[4..8]),
+            ssrc: BigEndian::read_u32(&data[8..12]),
+            payload_len: data.len() - hdr_len - padding_len,
+        })
+    }
+}
+
+/// A reception report block of an RTCP sender or receiver report.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportBlock {
+    /// Source that the report is about.
+    pub ssrc: u32,
+    /// Fraction of packets lost since the previous report, in units of 1/256.
+    pub fraction_lost: u8,
+    /// Cumulative number of packets lost. May be negative due to duplicates.
+    pub cumulative_lost: i32,
+    /// Extended highest sequence number received.
+    pub highest_seq: u32,
+    /// Interarrival jitter, in timestamp units.
+    pub jitter: u32,
+}
+
+impl ReportBlock {
+    fn parse_from(data: &[u8]) -> Self {
+        ReportBlock {
+            ssrc: BigEndian::read_u32(&data[0..4]),
+            fraction_lost: data[4],
+            // Sign-extend the 24-bit count
+            cumulative_lost: (BigEndian::read_u32(&data[4..8]) << 8) as i32 >> 8,
+            highest_seq: BigEndian::read_u32(&data[8..12]),
+            jitter: BigEndian::read_u32(&data[12..16]),
+        }
+    }
+}
+
+/// An RTCP packet within a compound packet.
+#[derive(Debug)]
+pub(super) enum RtcpPacket {
+    SenderReport {
+        packet_count: u32,
+        octet_count: u32,
+        reports: Vec<ReportBlock>,
+    },
+    ReceiverReport {
+        reports: Vec<ReportBlock>,
+    },
+    SourceDescription {
+        cname: Option<String>,
+    },
+    Goodbye,
+    Other,
+}
+
+impl RtcpPacket {
+    /// Parses the packets of a compound RTCP packet. Trailing data that does not form a packet
+    /// (e.g., the SRTCP index and authentication tag) is ignored.
+    pub(super) fn parse_compound(mut data: &[u8]) -> Result<Vec<Self>> {
+        let mut packets = vec![];
+        while data.len() >= RTCP_HDR_LEN && data[0] >> 6 == RTP_VERSION {
+            let len = (BigEndian::read_u16(&data[2..4]) as usize + 1) * 4;
+            if data.len() < len {
+                break;
+            }
+            let count = (data[0] & 0x1f) as usize;
+            packets.push(RtcpPacket::parse_from(
+                data[1],
+                count,
+                &data[RTCP_HDR_LEN..len],
+            )?);
+            data = &data[len..];
+        }
+        if packets.is_empty() {
+            bail!("invalid RTCP packet");
+        }
+        Ok(packets)
+    }
+
+    /// Parses the body of a packet with type `packet_type` and item count `count`.
+    fn parse_from(packet_type: u8, count: usize, body: &[u8]) -> Result<Self> {
+        match packet_type {
+            RTCP_SR => {
+                if body.len() < 24 + count * REPORT_BLOCK_LEN {
+                    bail!("truncated sender report");
+                }
+                // Sender SSRC, NTP timestamp, and RTP timestamp precede the sender info
+                Ok(RtcpPacket::SenderReport {
+                    packet_count: BigEndian::read_u32(&body[16..20]),
+                    octet_count: BigEndian::read_u32(&body[20..24]),
+                    reports: report_blocks(&body[24..], count),
+                })
+            }
+            RTCP_RR => {
+                if body.len() < 4 + count * REPORT_BLOCK_LEN {
+                    bail!("truncated receiver report");
+                }
+                // Reporter SSRC precedes the report blocks
+                Ok(RtcpPacket::ReceiverReport {
+                    reports: report_blocks(&body[4..], count),
+                })
+            }
+            RTCP_SDES => Ok(RtcpPacket::SourceDescription { cname: cname(body) }),
+            RTCP_BYE => Ok(RtcpPacket::Goodbye),
+            _ => Ok(RtcpPacket::Other),
+        }
+    }
+}
+
+fn report_blocks(data: &[u8], count: usize) -> Vec<ReportBlock> {
+    data.chunks_exact(REPORT_BLOCK_LEN)
+        .take(count)
+        .map(ReportBlock::parse_from)
+        .collect()
+}
+
+/// Returns the CNAME item of the first chunk of a source description, if present.
+fn cname(body: &[u8]) -> Option<String> {
+    // Items of the first chunk follow its SSRC
+    let mut items = body.get(4..)?;
+    while items.len() >= 2 && items[0] != 0 {
+        let (item_type, len) = (items[0], items[1] as usize);
+        let value = items.get(2..2 + len)?;
+        if item_type == SDES_CNAME {
+            return Some(String::from_utf8_lossy(value).into_owned());
+        }
+        items = &items[2 + len..];
+    }
+    None
+}
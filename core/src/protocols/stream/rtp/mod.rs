@@ -0,0 +1,263 @@
+//! RTP stream parsing.
+//!
+//! ## Remarks
+//! RTP has no handshake or reliable signature, so RTP flows are identified from the media
+//! endpoints announced by an observed SIP session (see
+//! [SIP](crate::protocols::stream::sip)), and are tagged with the session's `Call-ID`. Flows that
+//! were not announced are identified only by a valid RTCP compound packet, e.g., on an RTCP port
+//! or when RTCP is multiplexed with RTP.
+//!
+//! Each flow is recorded as a single session with per-source (SSRC) statistics: packet and byte
+//! counts, sequence gaps, packets lost and reordered, and the interarrival jitter estimate of
+//! [RFC 3550](https://datatracker.ietf.org/doc/html/rfc3550#appendix-A.8). Jitter can only be
+//! estimated when the clock rate of the payload type is known, either from the announcing session
+//! description or from a static payload type. RTCP reports sent in the flow are summarized
+//! separately. Statistics accumulate over the lifetime of the flow, so the session is delivered
+//! when the connection terminates.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use crate::protocols::stream::sip::RtpMap;
+
+use serde::Serialize;
+
+/// Maximum out-of-order distance of a packet in sequence (RFC 3550 Appendix A.1).
+const MAX_MISORDER: u16 = 100;
+/// Maximum forward jump in sequence before the sequence is considered restarted.
+const MAX_DROPOUT: u16 = 3000;
+
+/// Parsed RTP flow statistics.
+#[derive(Debug, Default, Serialize)]
+pub struct Rtp {
+    /// `Call-ID` of the SIP session that announced the flow, if observed.
+    pub call_id: Option<String>,
+    /// Media type announced for the flow (e.g., `audio`), if observed.
+    pub media: Option<String>,
+    /// Statistics for each synchronization source, in order of appearance.
+    pub sources: Vec<RtpSource>,
+    /// Summary of RTCP packets in the flow.
+    pub rtcp: RtcpSummary,
+}
+
+/// Statistics for a synchronization source (SSRC) of an RTP flow.
+#[derive(Clone, Debug, Serialize)]
+pub struct RtpSource {
+    pub ssrc: u32,
+    /// Payload type of the first packet.
+    pub payload_type: u8,
+    /// Encoding name of the payload type (e.g., `PCMU`), if known.
+    pub encoding: Option<String>,
+    /// RTP timestamp clock rate of the payload type in Hz, if known.
+    pub clock_rate: Option<u32>,
+    pub packets: u64,
+    /// Payload bytes, excluding headers and padding.
+    pub bytes: u64,
+    /// Number of forward jumps in sequence number.
+    pub gaps: u64,
+    /// Number of late or duplicated packets.
+    pub reordered: u64,
+    /// Number of times the sequence number restarted.
+    pub restarts: u64,
+    /// Interarrival jitter estimate in timestamp units.
+    pub jitter: f64,
+    /// Extended sequence number of the first packet since the last restart.
+    #[serde(skip)]
+    base_seq: u64,
+    /// Extended highest sequence number received.
+    #[serde(skip)]
+    max_seq: u64,
+    /// Packets received since the last restart.
+    #[serde(skip)]
+    received: u64,
+    /// Packets lost before the last restart.
+    #[serde(skip)]
+    lost_prior: i64,
+    /// Arrival time (in seconds since the start of the flow) and timestamp of the last packet in
+    /// sequence.
+    #[serde(skip)]
+    last: Option<(f64, u32)>,
+}
+
+/// Summary of the RTCP packets of an RTP flow.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RtcpSummary {
+    /// Number of compound RTCP packets.
+    pub packets: u64,
+    pub sender_reports: u64,
+    pub receiver_reports: u64,
+    /// Packet count of the most recent sender report.
+    pub sender_packets: Option<u32>,
+    /// Octet count of the most recent sender report.
+    pub sender_octets: Option<u32>,
+    /// Canonical name (CNAME) of the first source description.
+    pub cname: Option<String>,
+    /// Most recent reception report block.
+    pub last_report: Option<ReportBlock>,
+    /// `true` if a BYE packet was observed.
+    pub bye: bool,
+}
+
+impl RtpSource {
+    fn new(hdr: &message::RtpHeader, rtpmap: &[RtpMap]) -> Self {
+        let (encoding, clock_rate) =
+            match rtpmap.iter().find(|m| m.payload_type == hdr.payload_type) {
+                Some(m) => (Some(m.encoding.clone()), Some(m.clock_rate)),
+                None => match static_payload_type(hdr.payload_type) {
+                    Some((encoding, clock_rate)) => (Some(encoding.to_owned()), Some(clock_rate)),
+                    None => (None, None),
+                },
+            };
+        RtpSource {
+            ssrc: hdr.ssrc,
+            payload_type: hdr.payload_type,
+            encoding,
+            clock_rate,
+            packets: 0,
+            bytes: 0,
+            gaps: 0,
+            reordered: 0,
+            restarts: 0,
+            jitter: 0.0,
+            base_seq: hdr.seq as u64,
+            max_seq: hdr.seq as u64,
+            received: 0,
+            lost_prior: 0,
+            last: None,
+        }
+    }
+
+    /// Updates the statistics with a packet that arrived `arrival` seconds after the start of the
+    /// flow, following RFC 3550 Appendix A.1 and A.8.
+    fn update(&mut self, hdr: &message::RtpHeader, arrival: f64) {
+        self.packets += 1;
+        self.bytes += hdr.payload_len as u64;
+
+        let max = self.max_seq as u16;
+        let delta = hdr.seq.wrapping_sub(max);
+        if self.received == 0 || (delta > 0 && delta < MAX_DROPOUT) {
+            if self.received > 0 {
+                if hdr.seq < max {
+                    // Sequence number wrapped around
+                    self.max_seq += 1 << 16;
+                }
+                if delta > 1 {
+                    self.gaps += 1;
+                }
+            }
+            self.max_seq = (self.max_seq & !0xffff) | hdr.seq as u64;
+            self.received += 1;
+            self.update_jitter(hdr, arrival);
+        } else if delta == 0 || delta > u16::MAX - MAX_MISORDER {
+            self.reordered += 1;
+            self.received += 1;
+        } else {
+            // Large jump, e.g. the sender restarted
+            self.restarts += 1;
+            self.lost_prior = self.lost();
+            self.base_seq = hdr.seq as u64;
+            self.max_seq = hdr.seq as u64;
+            self.received = 1;
+            self.last = None;
+        }
+    }
+
+    fn update_jitter(&mut self, hdr: &message::RtpHeader, arrival: f64) {
+        let clock_rate = match self.clock_rate {
+            Some(clock_rate) if hdr.payload_type == self.payload_type => clock_rate as f64,
+            _ => return,
+        };
+        if let Some((last_arrival, last_timestamp)) = self.last {
+            let elapsed = hdr.timestamp.wrapping_sub(last_timestamp) as i32 as f64;
+            let d = (arrival - last_arrival) * clock_rate - elapsed;
+            self.jitter += (d.abs() - self.jitter) / 16.0;
+        }
+        self.last = Some((arrival, hdr.timestamp));
+    }
+
+    /// Returns the number of packets lost. May be negative due to duplicates.
+    pub fn lost(&self) -> i64 {
+        let expected = (self.max_seq - self.base_seq + 1) as i64;
+        self.lost_prior + expected - self.received as i64
+    }
+
+    /// Returns the interarrival jitter estimate in milliseconds, or `None` if the clock rate is
+    /// unknown.
+    pub fn jitter_ms(&self) -> Option<f64> {
+        self.clock_rate
+            .map(|clock_rate| self.jitter * 1000.0 / clock_rate as f64)
+    }
+}
+
+impl Rtp {
+    /// Returns the `Call-ID` of the SIP session that announced the flow, or `""` if the flow was
+    /// not announced.
+    pub fn call_id(&self) -> &str {
+        self.call_id.as_deref().unwrap_or("")
+    }
+
+    /// Returns the announced media type (e.g., `"audio"`), or `""` if the flow was not announced.
+    pub fn media(&self) -> &str {
+        self.media.as_deref().unwrap_or("")
+    }
+
+    /// Returns the SSRC of the first source, or 0 if no RTP packets were observed.
+    pub fn ssrc(&self) -> u32 {
+        self.sources.first().map_or(0, |s| s.ssrc)
+    }
+
+    /// Returns the payload type of the first source, or 0 if no RTP packets were observed.
+    pub fn payload_type(&self) -> u8 {
+        self.sources.first().map_or(0, |s| s.payload_type)
+    }
+
+    /// Returns the encoding name of the first source (e.g., `"opus"`), or `""` if unknown.
+    pub fn codec(&self) -> &str {
+        self.sources
+            .first()
+            .and_then(|s| s.encoding.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the number of sources in the flow.
+    pub fn num_sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns the number of RTP packets in the flow.
+    pub fn packets(&self) -> u64 {
+        self.sources.iter().map(|s| s.packets).sum()
+    }
+
+    /// Returns the number of RTP packets lost across all sources.
+    pub fn lost(&self) -> i64 {
+        self.sources.iter().map(|s| s.lost()).sum()
+    }
+
+    /// Returns the number of sequence gaps across all sources.
+    pub fn gaps(&self) -> u64 {
+        self.sources.iter().map(|s| s.gaps).sum()
+    }
+
+    /// Returns the highest interarrival jitter estimate of any source, rounded to the nearest
+    /// millisecond, or 0 if it could not be estimated.
+    pub fn jitter_ms(&self) -> u64 {
+        self.sources
+            .iter()
+            .filter_map(|s| s.jitter_ms())
+            .fold(0.0, f64::max)
+            .round() as u64
+    }
+
+    /// Returns the number of compound RTCP packets in the flow.
+    pub fn rtcp_packets(&self) -> u64 {
+        self.rtcp.packets
+    }
+
+    /// Returns the RTCP canonical name (e.g., `"user@host"`), or `""` if none was observed.
+    pub fn cname(&self) -> &str {
+        self.rtcp.cname.as_deref().unwrap_or("")
+    }
+}
@@ -0,0 +1,416 @@
+//! RTP stream parser.
+//!
+//! Updates per-source statistics with each RTP packet, and summarizes RTCP packets multiplexed
+//! in the same flow.
+
+use super::message::*;
+use super::{Rtp, RtpSource};
+use crate::conntrack::expected::ExpectedConn;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::packet::udp::UDP_PROTOCOL;
+use crate::protocols::stream::sip::RtpMap;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::time::Instant;
+
+/// Maximum number of sources tracked per flow.
+const MAX_SOURCES: usize = 8;
+
+/// Parses a single RTP flow per connection.
+#[derive(Debug)]
+pub struct RtpParser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<Rtp>,
+    /// Payload type mappings announced for the flow.
+    rtpmap: Vec<RtpMap>,
+    /// Arrival time of the first packet.
+    start: Option<Instant>,
+}
+
+impl Default for RtpParser {
+    fn default() -> Self {
+        RtpParser {
+            sessions: vec![Rtp::default()],
+            rtpmap: vec![],
+            start: None,
+        }
+    }
+}
+
+impl RtpParser {
+    /// Creates a parser for a flow announced by a signalling session.
+    pub(crate) fn from_expected(expected: &ExpectedConn) -> Self {
+        RtpParser {
+            sessions: vec![Rtp {
+                call_id: Some(expected.call_id.clone()),
+                media: Some(expected.media.clone()),
+                ..Default::default()
+            }],
+            rtpmap: expected.rtpmap.clone(),
+            start: None,
+        }
+    }
+
+    fn process_rtp(&mut self, data: &[u8], ts: Instant) -> ParseResult {
+        let hdr = match RtpHeader::parse_from(data) {
+            Ok(hdr) => hdr,
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                return ParseResult::Skipped;
+            }
+        };
+        let start = *self.start.get_or_insert(ts);
+        let arrival = ts.saturating_duration_since(start).as_secs_f64();
+
+        let rtp = &mut self.sessions[0];
+        let source = match rtp.sources.iter().position(|s| s.ssrc == hdr.ssrc) {
+            Some(idx) => &mut rtp.sources[idx],
+            None if rtp.sources.len() < MAX_SOURCES => {
+                rtp.sources.push(RtpSource::new(&hdr, &self.rtpmap));
+                rtp.sources.last_mut().unwrap()
+            }
+            None => return ParseResult::Skipped,
+        };
+        source.update(&hdr, arrival);
+        ParseResult::Continue(0)
+    }
+
+    fn process_rtcp(&mut self, data: &[u8]) -> ParseResult {
+        let packets = match RtcpPacket::parse_compound(data) {
+            Ok(packets) => packets,
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                return ParseResult::Skipped;
+            }
+        };
+        let rtcp = &mut self.sessions[0].rtcp;
+        rtcp.packets += 1;
+        for packet in packets {
+            match packet {
+                RtcpPacket::SenderReport {
+                    packet_count,
+                    octet_count,
+                    mut reports,
+                } => {
+                    rtcp.sender_reports += 1;
+                    rtcp.sender_packets = Some(packet_count);
+                    rtcp.sender_octets = Some(octet_count);
+                    if let Some(report) = reports.pop() {
+                        rtcp.last_report = Some(report);
+                    }
+                }
+                RtcpPacket::ReceiverReport { mut reports } => {
+                    rtcp.receiver_reports += 1;
+                    if let Some(report) = reports.pop() {
+                        rtcp.last_report = Some(report);
+                    }
+                }
+                RtcpPacket::SourceDescription { cname } => {
+                    if rtcp.cname.is_none() {
+                        rtcp.cname = cname;
+                    }
+                }
+                RtcpPacket::Goodbye => rtcp.bye = true,
+                RtcpPacket::Other => (),
+            }
+        }
+        ParseResult::Continue(0)
+    }
+}
+
+impl ConnParsable for RtpParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 || self.sessions.is_empty() {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if is_rtcp(data) {
+                self.process_rtcp(data)
+            } else {
+                self.process_rtp(data, pdu.ts)
+            }
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.proto != UDP_PROTOCOL {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            // Compound RTCP packets start with a sender or receiver report, and are padded to
+            // exactly fill the datagram.
+            let valid = is_rtcp(data)
+                && matches!(data[1], RTCP_SR | RTCP_RR)
+                && data[0] >> 6 == RTP_VERSION
+                && compound_len(data) == Some(data.len());
+            if valid {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|rtp| Session {
+            data: SessionData::Rtp(Box::new(rtp)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|rtp| Session {
+                data: SessionData::Rtp(Box::new(rtp)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Stop
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+/// Returns the total length of the RTCP packets at the start of `data`.
+fn compound_len(data: &[u8]) -> Option<usize> {
+    let mut len = 0;
+    while len + 4 <= data.len() {
+        if data[len] >> 6 != RTP_VERSION {
+            return None;
+        }
+        len += (u16::from_be_bytes([data[len + 2], data[len + 3]]) as usize + 1) * 4;
+    }
+    Some(len)
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{tcp, udp};
+
+    use std::time::Duration;
+
+    const SSRC: u32 = 0x1a2b_3c4d;
+
+    /// Compound RTCP packet with a sender report and the CNAME `alice@10.0.0.1`.
+    const SENDER_REPORT: &[u8] = b"\
+        \x81\xc8\x00\x0c\x5d\x3c\x2a\x17\xea\x8f\x2a\x4c\x80\x00\x00\x00\
+        \x00\xb4\xa6\x60\x00\x00\x01\x9c\x00\x01\x01\x80\x1a\x2b\x3c\x4d\
+        \x0d\xff\xff\xfe\x00\x01\x03\xe8\x00\x00\x00\x25\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x81\xca\x00\x06\x5d\x3c\x2a\x17\x01\x0e\x61\x6c\
+        \x69\x63\x65\x40\x31\x30\x2e\x30\x2e\x30\x2e\x31\x00\x00\x00\x00";
+
+    /// Compound RTCP packet with a receiver report and a BYE.
+    const RECEIVER_REPORT: &[u8] = b"\
+        \x81\xc9\x00\x07\x1a\x2b\x3c\x4d\x5d\x3c\x2a\x17\x00\x00\x00\x03\
+        \x00\x00\xc3\xa1\x00\x00\x00\x78\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x81\xcb\x00\x01\x1a\x2b\x3c\x4d";
+
+    /// Returns an RTP packet with a 160-byte payload.
+    fn rtp(payload_type: u8, seq: u16, timestamp: u32, ssrc: u32) -> Vec<u8> {
+        let mut packet = vec![0x80, payload_type];
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&timestamp.to_be_bytes());
+        packet.extend_from_slice(&ssrc.to_be_bytes());
+        packet.resize(12 + 160, 0xd5);
+        packet
+    }
+
+    /// Parses `packet` as arriving `ms` milliseconds after `start`.
+    fn parse(parser: &mut RtpParser, packet: &[u8], start: Instant, ms: u64) -> ParseResult {
+        let mut pdu = udp(packet, 49170, false);
+        pdu.ts = start + Duration::from_millis(ms);
+        parser.parse(&pdu)
+    }
+
+    fn expected() -> ExpectedConn {
+        ExpectedConn {
+            endpoint: "10.0.0.1:49170".parse().unwrap(),
+            call_id: "a84b4c76e66710@pc33.example.com".to_owned(),
+            media: "audio".to_owned(),
+            rtpmap: vec![RtpMap {
+                payload_type: 111,
+                encoding: "opus".to_owned(),
+                clock_rate: 48000,
+            }],
+        }
+    }
+
+    #[test]
+    fn core_rtp_probe() {
+        let parser = RtpParser::default();
+        assert_eq!(
+            parser.probe(&udp(SENDER_REPORT, 49171, true)),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            parser.probe(&udp(RECEIVER_REPORT, 49171, false)),
+            ProbeResult::Certain
+        );
+        // RTP has no reliable signature
+        assert_eq!(
+            parser.probe(&udp(&rtp(0, 1, 160, SSRC), 49170, true)),
+            ProbeResult::NotForUs
+        );
+        // Compound packets fill the datagram
+        assert_eq!(
+            parser.probe(&udp(&SENDER_REPORT[..60], 49171, true)),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            parser.probe(&tcp(SENDER_REPORT, 49171, true)),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_rtp_stream() {
+        let mut parser = RtpParser::from_expected(&expected());
+        let start = Instant::now();
+        // 20 ms of opus per packet. 1003 is lost, 1006 is late, and 1007 is duplicated.
+        for (i, seq) in [1000, 1001, 1002, 1004, 1005, 1007, 1006, 1007, 1008]
+            .into_iter()
+            .enumerate()
+        {
+            let timestamp = 48000 + 960 * (seq as u32 - 1000);
+            let packet = rtp(111, seq, timestamp, SSRC);
+            assert_eq!(
+                parse(&mut parser, &packet, start, 20 * i as u64),
+                ParseResult::Continue(0)
+            );
+        }
+        // Comfort noise from the same source does not affect jitter
+        let packet = rtp(13, 1009, 48000 + 960 * 9, SSRC);
+        parse(&mut parser, &packet, start, 180);
+
+        let rtp = &parser.sessions[0];
+        assert_eq!(rtp.call_id(), "a84b4c76e66710@pc33.example.com");
+        assert_eq!(rtp.media(), "audio");
+        assert_eq!(rtp.ssrc(), SSRC);
+        assert_eq!(rtp.payload_type(), 111);
+        assert_eq!(rtp.codec(), "opus");
+        assert_eq!(rtp.num_sources(), 1);
+        assert_eq!(rtp.packets(), 10);
+        assert_eq!(rtp.sources[0].bytes, 1600);
+        // The lost packet is offset by the duplicate
+        assert_eq!(rtp.lost(), 0);
+        assert_eq!(rtp.gaps(), 2);
+        assert_eq!(rtp.sources[0].reordered, 2);
+        assert!(rtp.sources[0].jitter > 0.0);
+    }
+
+    #[test]
+    fn core_rtp_jitter() {
+        let mut parser = RtpParser::default();
+        let start = Instant::now();
+        // PCMU at 8 kHz, with the sequence number wrapping around
+        for (i, seq) in [65534, 65535, 0, 1].into_iter().enumerate() {
+            let packet = rtp(0, seq, 160 * i as u32, SSRC);
+            parse(&mut parser, &packet, start, 20 * i as u64);
+        }
+        assert_eq!(parser.sessions[0].jitter_ms(), 0);
+        // 20 ms late: |D| = 160 timestamp units, and the estimate moves 1/16 of the way to it
+        let packet = rtp(0, 2, 160 * 4, SSRC);
+        parse(&mut parser, &packet, start, 100);
+        let source = &parser.sessions[0].sources[0];
+        assert!((source.jitter - 10.0).abs() < 1e-6);
+        assert!((source.jitter_ms().unwrap() - 1.25).abs() < 1e-6);
+        assert_eq!(source.encoding.as_deref(), Some("PCMU"));
+        assert_eq!(source.lost(), 0);
+        assert_eq!(source.gaps, 0);
+        assert_eq!(source.restarts, 0);
+        assert_eq!(parser.sessions[0].call_id(), "");
+
+        // A second source in the same flow
+        parse(&mut parser, &rtp(8, 7, 0, 0x0bad_cafe), start, 100);
+        assert_eq!(parser.sessions[0].num_sources(), 2);
+        assert_eq!(parser.sessions[0].codec(), "PCMU");
+    }
+
+    #[test]
+    fn core_rtp_rtcp() {
+        let mut parser = RtpParser::default();
+        let start = Instant::now();
+        assert_eq!(
+            parse(&mut parser, SENDER_REPORT, start, 0),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parse(&mut parser, RECEIVER_REPORT, start, 5000),
+            ParseResult::Continue(0)
+        );
+        let rtp = &parser.sessions[0];
+        assert_eq!(rtp.packets(), 0);
+        assert_eq!(rtp.rtcp_packets(), 2);
+        assert_eq!(rtp.cname(), "alice@10.0.0.1");
+        assert_eq!(rtp.rtcp.sender_reports, 1);
+        assert_eq!(rtp.rtcp.receiver_reports, 1);
+        assert_eq!(rtp.rtcp.sender_packets, Some(412));
+        assert_eq!(rtp.rtcp.sender_octets, Some(65920));
+        assert!(rtp.rtcp.bye);
+        let report = rtp.rtcp.last_report.as_ref().unwrap();
+        assert_eq!(report.ssrc, 0x5d3c_2a17);
+        assert_eq!(report.cumulative_lost, 3);
+        assert_eq!(report.highest_seq, 0xc3a1);
+        assert_eq!(report.jitter, 120);
+    }
+
+    #[test]
+    fn core_rtp_truncated() {
+        let mut parser = RtpParser::default();
+        let start = Instant::now();
+        let packet = rtp(0, 1, 160, SSRC);
+        assert_eq!(
+            parse(&mut parser, &packet[..8], start, 0),
+            ParseResult::Skipped
+        );
+        // Padding longer than the packet
+        let mut padded = packet.clone();
+        padded[0] |= 0x20;
+        *padded.last_mut().unwrap() = 0xff;
+        assert_eq!(parse(&mut parser, &padded, start, 0), ParseResult::Skipped);
+        // Header extension beyond the packet
+        let mut extended = packet[..16].to_vec();
+        extended[0] |= 0x10;
+        extended[14..16].copy_from_slice(&4u16.to_be_bytes());
+        assert_eq!(
+            parse(&mut parser, &extended, start, 0),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions[0].sources.is_empty());
+
+        // Sender report cut off within its report block
+        assert_eq!(
+            parse(&mut parser, &SENDER_REPORT[..40], start, 0),
+            ParseResult::Skipped
+        );
+        // Sender report whose length leaves no room for its report block
+        let mut report = SENDER_REPORT[..40].to_vec();
+        report[3] = 9;
+        assert_eq!(parse(&mut parser, &report, start, 0), ParseResult::Skipped);
+        assert_eq!(parser.sessions[0].rtcp_packets(), 0);
+    }
+}
@@ -0,0 +1,212 @@
+//! SIP message components.
+//!
+//! SIP messages are HTTP-like requests and responses, as specified in
+//! [RFC 3261](https://datatracker.ietf.org/doc/html/rfc3261). Compact header names are expanded,
+//! and `application/sdp` bodies are parsed as session descriptions.
+
+use super::sdp::Sdp;
+
+use anyhow::{bail, Result};
+use httparse::{Status, EMPTY_HEADER};
+use serde::Serialize;
+
+/// Maximum number of headers parsed per message.
+const NUM_OF_HEADERS: usize = 64;
+/// Upper bound on the size of a SIP message.
+pub(super) const MAX_MESSAGE_LEN: usize = 1 << 16;
+
+/// A SIP request or response.
+#[derive(Clone, Debug, Serialize)]
+pub struct SipMessage {
+    /// Request method (e.g., `INVITE`), if this is a request.
+    pub method: Option<String>,
+    /// Request-URI, if this is a request.
+    pub uri: Option<String>,
+    /// Status code, if this is a response.
+    pub status_code: Option<u16>,
+    /// Reason phrase, if this is a response.
+    pub reason: Option<String>,
+    pub call_id: String,
+    /// `From` header, including any display name and parameters.
+    pub from: String,
+    /// `To` header, including any display name and parameters.
+    pub to: String,
+    /// Sequence number of the `CSeq` header.
+    pub cseq: u32,
+    /// Method of the `CSeq` header.
+    pub cseq_method: String,
+    /// Branch parameter of the topmost `Via` header.
+    pub branch: Option<String>,
+    pub contact: Option<String>,
+    pub user_agent: Option<String>,
+    pub server: Option<String>,
+    pub content_type: Option<String>,
+    /// Session description, if the body is `application/sdp`.
+    pub sdp: Option<Sdp>,
+    /// Length of the message in bytes.
+    pub length: usize,
+}
+
+impl SipMessage {
+    /// Parses a message at the start of `data`. Over a stream transport, returns `Ok(None)` if the
+    /// message is incomplete, and the message length is determined by its `Content-Length`.
+    /// Otherwise, the message is the entire datagram.
+    pub(super) fn parse_from(data: &[u8], stream: bool) -> Result<Option<Self>> {
+        let (line, rest) = match memchr::memmem::find(data, b"\r\n") {
+            Some(pos) => (&data[..pos], &data[pos + 2..]),
+            None if stream && data.len() < MAX_MESSAGE_LEN => return Ok(None),
+            None => bail!("truncated start line"),
+        };
+        let line = std::str::from_utf8(line)?;
+        let mut msg = SipMessage::from_start_line(line)?;
+
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let (headers_len, headers) = match httparse::parse_headers(rest, &mut headers)? {
+            Status::Complete(headers) => headers,
+            Status::Partial if stream => return Ok(None),
+            Status::Partial => bail!("truncated headers"),
+        };
+        let mut content_length = None;
+        for hdr in headers.iter() {
+            let value = String::from_utf8_lossy(hdr.value).trim().to_owned();
+            match expand_name(hdr.name).to_ascii_lowercase().as_ref() {
+                "call-id" => msg.call_id = value,
+                "from" => msg.from = value,
+                "to" => msg.to = value,
+                "cseq" => {
+                    let (seq, method) = value.split_once(' ').unwrap_or((value.as_str(), ""));
+                    msg.cseq = seq.parse().unwrap_or_default();
+                    msg.cseq_method = method.trim().to_owned();
+                }
+                "via" if msg.branch.is_none() => {
+                    // Multiple Via values may be combined in one header
+                    let top = value.split(',').next().unwrap_or_default();
+                    msg.branch = parameter(top, "branch");
+                }
+                "contact" => msg.contact = Some(value),
+                "user-agent" => msg.user_agent = Some(value),
+                "server" => msg.server = Some(value),
+                "content-type" => msg.content_type = Some(value),
+                "content-length" => content_length = value.parse::<usize>().ok(),
+                _ => (),
+            }
+        }
+        if msg.call_id.is_empty() || msg.cseq_method.is_empty() {
+            bail!("missing Call-ID or CSeq");
+        }
+
+        let body_start = data.len() - rest.len() + headers_len;
+        let body_len = match content_length {
+            Some(len) => len,
+            None if stream => 0,
+            None => data.len() - body_start,
+        };
+        msg.length = body_start + body_len;
+        if data.len() < msg.length {
+            if stream && msg.length <= MAX_MESSAGE_LEN {
+                return Ok(None);
+            }
+            bail!("truncated body");
+        }
+        let is_sdp = msg
+            .content_type
+            .as_deref()
+            .is_some_and(|c| c.to_ascii_lowercase().starts_with("application/sdp"));
+        if is_sdp {
+            msg.sdp = Sdp::parse_from(&data[body_start..msg.length]).ok();
+        }
+        Ok(Some(msg))
+    }
+
+    /// Parses a request line (`<method> <uri> SIP/2.0`) or status line
+    /// (`SIP/2.0 <code> <reason>`).
+    fn from_start_line(line: &str) -> Result<Self> {
+        let mut fields = line.splitn(3, ' ');
+        let (first, second, third) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(first), Some(second), Some(third)) => (first, second, third),
+            _ => bail!("invalid start line"),
+        };
+        let mut msg = SipMessage {
+            method: None,
+            uri: None,
+            status_code: None,
+            reason: None,
+            call_id: String::new(),
+            from: String::new(),
+            to: String::new(),
+            cseq: 0,
+            cseq_method: String::new(),
+            branch: None,
+            contact: None,
+            user_agent: None,
+            server: None,
+            content_type: None,
+            sdp: None,
+            length: 0,
+        };
+        if first == "SIP/2.0" {
+            msg.status_code = match second.parse() {
+                Ok(code @ 100..=699) => Some(code),
+                _ => bail!("invalid status code {}", second),
+            };
+            msg.reason = Some(third.to_owned());
+        } else if third == "SIP/2.0" && is_token(first) {
+            msg.method = Some(first.to_owned());
+            msg.uri = Some(second.to_owned());
+        } else {
+            bail!("invalid start line");
+        }
+        Ok(msg)
+    }
+
+    /// Returns `true` if this is a request.
+    pub fn is_request(&self) -> bool {
+        self.method.is_some()
+    }
+
+    /// Returns `true` if this is a provisional (1xx) response.
+    pub fn is_provisional(&self) -> bool {
+        self.status_code.is_some_and(|code| code < 200)
+    }
+}
+
+/// Returns the URI of a name-addr or addr-spec header value (e.g., the `From` header
+/// `"Alice" <sip:alice@example.com>;tag=1928301774` has URI `sip:alice@example.com`).
+pub fn header_uri(value: &str) -> &str {
+    match value.split_once('<') {
+        Some((_, rest)) => rest.split('>').next().unwrap_or(rest),
+        None => value.split(';').next().unwrap_or(value).trim(),
+    }
+}
+
+/// Returns the value of the parameter `name` of a header value, if present.
+pub(super) fn parameter(value: &str, name: &str) -> Option<String> {
+    let params = match value.rsplit_once('>') {
+        Some((_, params)) => params,
+        None => value,
+    };
+    params.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().to_owned())
+    })
+}
+
+/// Expands a compact header name (e.g., `i` for `Call-ID`).
+fn expand_name(name: &str) -> &str {
+    match name {
+        "i" | "I" => "Call-ID",
+        "f" | "F" => "From",
+        "t" | "T" => "To",
+        "v" | "V" => "Via",
+        "m" | "M" => "Contact",
+        "c" | "C" => "Content-Type",
+        "l" | "L" => "Content-Length",
+        _ => name,
+    }
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_uppercase() || b == b'-')
+}
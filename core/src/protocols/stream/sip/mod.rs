@@ -0,0 +1,160 @@
+//! SIP transaction parsing.
+//!
+//! ## Remarks
+//! A transaction is a request paired with its final response, identified by the `Call-ID` and
+//! `CSeq` headers. Provisional (1xx) response codes are recorded with the transaction. `ACK`
+//! requests have no response and are recorded alone. Retransmitted requests are ignored.
+//!
+//! Session descriptions (SDP) carried in offers and answers are parsed, and the endpoints of
+//! their RTP and RTCP streams are registered with the connection tracker as expected connections.
+//! The media flows of the call are then parsed as [RTP](crate::protocols::stream::rtp) and tagged
+//! with the call's `Call-ID`, if RTP parsing is required by the subscription. Endpoints are
+//! matched as announced, so media flows that traverse a NAT that does not rewrite SDP are not
+//! associated with the call.
+//!
+//! Signalling connections between proxies are often long-lived, so completed transactions are
+//! retained and delivered when the connection terminates, or once a bounded number of them have
+//! been observed. Media announced after parsing stops is not associated with its call.
+
+mod message;
+pub mod parser;
+mod sdp;
+
+pub use self::message::*;
+pub use self::sdp::*;
+
+use serde::Serialize;
+
+/// Parsed SIP transaction contents.
+#[derive(Debug, Serialize)]
+pub struct Sip {
+    /// Request, if observed.
+    pub request: Option<SipMessage>,
+    /// Status codes of provisional responses, in order.
+    pub provisional: Vec<u16>,
+    /// Final response, if observed.
+    pub response: Option<SipMessage>,
+}
+
+impl Sip {
+    /// Returns the request method (e.g., `"INVITE"`), or the `CSeq` method of the response if no
+    /// request was observed.
+    pub fn method(&self) -> &str {
+        match &self.request {
+            Some(request) => request.method.as_deref().unwrap_or(""),
+            None => self.message().map_or("", |m| &m.cseq_method),
+        }
+    }
+
+    /// Returns the Request-URI, or `""` if no request was observed.
+    pub fn request_uri(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|m| m.uri.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the `Call-ID` of the transaction.
+    pub fn call_id(&self) -> &str {
+        self.message().map_or("", |m| &m.call_id)
+    }
+
+    /// Returns the URI of the `From` header (e.g., `"sip:alice@example.com"`).
+    pub fn from(&self) -> &str {
+        self.message().map_or("", |m| header_uri(&m.from))
+    }
+
+    /// Returns the URI of the `To` header (e.g., `"sip:bob@example.com"`).
+    pub fn to(&self) -> &str {
+        self.message().map_or("", |m| header_uri(&m.to))
+    }
+
+    /// Returns the sequence number of the `CSeq` header.
+    pub fn cseq(&self) -> u32 {
+        self.message().map_or(0, |m| m.cseq)
+    }
+
+    /// Returns the `User-Agent` header of the request, or `""` if there is none.
+    pub fn user_agent(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|m| m.user_agent.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the `Server` header of the response, or `""` if there is none.
+    pub fn server(&self) -> &str {
+        self.response
+            .as_ref()
+            .and_then(|m| m.server.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the final response status code, or 0 if no final response was observed.
+    pub fn status_code(&self) -> u16 {
+        self.response
+            .as_ref()
+            .and_then(|m| m.status_code)
+            .unwrap_or(0)
+    }
+
+    /// Returns the final response reason phrase, or `""` if no final response was observed.
+    pub fn reason(&self) -> &str {
+        self.response
+            .as_ref()
+            .and_then(|m| m.reason.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns `true` if the callee was alerted (180 Ringing) or early media was offered (183
+    /// Session Progress).
+    pub fn ringing(&self) -> bool {
+        self.provisional
+            .iter()
+            .any(|code| matches!(code, 180 | 183))
+    }
+
+    /// Returns `true` if the request or response carried a session description.
+    pub fn has_sdp(&self) -> bool {
+        self.sdps().next().is_some()
+    }
+
+    /// Returns the media types negotiated in the transaction (e.g., `"audio video"`).
+    pub fn media_types(&self) -> String {
+        let mut media: Vec<&str> = vec![];
+        for m in self.sdps().flat_map(|sdp| sdp.media.iter()) {
+            if !media.contains(&m.media.as_str()) {
+                media.push(&m.media);
+            }
+        }
+        media.join(" ")
+    }
+
+    /// Returns the encodings offered or answered in the transaction (e.g., `"opus PCMU"`).
+    pub fn codecs(&self) -> String {
+        let mut codecs: Vec<&str> = vec![];
+        for rtpmap in self
+            .sdps()
+            .flat_map(|sdp| sdp.media.iter())
+            .flat_map(|m| m.rtpmap.iter())
+        {
+            if !codecs.contains(&rtpmap.encoding.as_str()) {
+                codecs.push(&rtpmap.encoding);
+            }
+        }
+        codecs.join(" ")
+    }
+
+    /// Returns the request, or the response if no request was observed.
+    fn message(&self) -> Option<&SipMessage> {
+        self.request.as_ref().or(self.response.as_ref())
+    }
+
+    /// Returns the session descriptions of the request and response.
+    fn sdps(&self) -> impl Iterator<Item = &Sdp> {
+        self.request
+            .iter()
+            .chain(self.response.iter())
+            .filter_map(|m| m.sdp.as_ref())
+    }
+}
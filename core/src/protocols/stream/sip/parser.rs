@@ -0,0 +1,550 @@
+//! SIP transaction parser.
+//!
+//! Parses SIP messages over UDP or TCP, pairs responses with outstanding requests, and collects
+//! the media endpoints announced in session descriptions.
+
+use super::message::*;
+use super::sdp::Sdp;
+use super::Sip;
+use crate::conntrack::expected::ExpectedConn;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::packet::tcp::TCP_PROTOCOL;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+/// Maximum number of transactions recorded before parsing stops.
+const MAX_TRANSACTIONS: usize = 64;
+
+/// Identifies a transaction by its `Call-ID`, `CSeq` number, and `CSeq` method.
+type TransactionKey = (String, u32, String);
+
+#[derive(Default, Debug)]
+pub struct SipParser {
+    /// Maps session ID to SIP transaction
+    sessions: HashMap<usize, Sip>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Maps each transaction observed to its session ID
+    transactions: HashMap<TransactionKey, usize>,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// Media endpoints announced since they were last collected
+    expected: Vec<ExpectedConn>,
+}
+
+impl ConnParsable for SipParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.ctxt.proto == TCP_PROTOCOL {
+                self.process_stream(data, pdu.dir)
+            } else {
+                self.process_datagram(data)
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            let data = skip_keepalive(data);
+            let line = match memchr::memmem::find(data, b"\r\n") {
+                Some(pos) => &data[..pos],
+                None if data.is_empty() => return ProbeResult::Unsure,
+                None => return ProbeResult::NotForUs,
+            };
+            if line.starts_with(b"SIP/2.0 ") || line.ends_with(b" SIP/2.0") {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|sip| Session {
+            data: SessionData::Sip(Box::new(sip)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, sip)| Session {
+                data: SessionData::Sip(Box::new(sip)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider SIP to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl SipParser {
+    /// Removes and returns the media endpoints announced since the last call.
+    pub(crate) fn drain_expected(&mut self) -> Vec<ExpectedConn> {
+        std::mem::take(&mut self.expected)
+    }
+
+    /// Process a message carried in a datagram
+    fn process_datagram(&mut self, data: &[u8]) -> ParseResult {
+        let data = skip_keepalive(data);
+        if data.is_empty() {
+            return ParseResult::Skipped;
+        }
+        match SipMessage::parse_from(data, false) {
+            Ok(Some(msg)) => self.process_message(msg),
+            Ok(None) => ParseResult::Skipped,
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+
+    /// Process data segments of a stream in direction `dir`
+    fn process_stream(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        loop {
+            cur_data = skip_keepalive(cur_data);
+            if cur_data.is_empty() {
+                break;
+            }
+            match SipMessage::parse_from(cur_data, true) {
+                Ok(Some(msg)) => {
+                    cur_data = &cur_data[msg.length..];
+                    result = self.process_message(msg);
+                    if matches!(result, ParseResult::HeadersDone(_)) {
+                        return result;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return result;
+                }
+            }
+        }
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        buffer.extend_from_slice(cur_data);
+        result
+    }
+
+    fn process_message(&mut self, msg: SipMessage) -> ParseResult {
+        log::debug!(
+            "SIP {} {}",
+            msg.method.as_deref().unwrap_or_default(),
+            msg.status_code.unwrap_or_default()
+        );
+        if let Some(sdp) = &msg.sdp {
+            self.expect_media(&msg.call_id, sdp);
+        }
+
+        let key = (msg.call_id.clone(), msg.cseq, msg.cseq_method.clone());
+        let session_id = match self.transactions.get(&key) {
+            Some(session_id) => *session_id,
+            None => {
+                let session_id = self.insert(Sip {
+                    request: None,
+                    provisional: vec![],
+                    response: None,
+                });
+                self.transactions.insert(key, session_id);
+                session_id
+            }
+        };
+        if let Some(sip) = self.sessions.get_mut(&session_id) {
+            if msg.is_request() {
+                // Ignore retransmissions
+                if sip.request.is_none() {
+                    sip.request = Some(msg);
+                }
+            } else if msg.is_provisional() {
+                // Ignore retransmissions
+                if let Some(code) = msg
+                    .status_code
+                    .filter(|c| sip.provisional.last() != Some(c))
+                {
+                    sip.provisional.push(code);
+                }
+            } else if sip.response.is_none() {
+                sip.response = Some(msg);
+            }
+        }
+
+        if self.cnt >= MAX_TRANSACTIONS {
+            self.client_buffer.clear();
+            self.server_buffer.clear();
+            return ParseResult::HeadersDone(session_id);
+        }
+        ParseResult::Continue(session_id)
+    }
+
+    /// Records the RTP and RTCP endpoints announced by `sdp`.
+    fn expect_media(&mut self, call_id: &str, sdp: &Sdp) {
+        for media in sdp.media.iter() {
+            let endpoints = media
+                .rtp_endpoint()
+                .into_iter()
+                .chain(media.rtcp_endpoint());
+            for endpoint in endpoints {
+                self.expected.push(ExpectedConn {
+                    endpoint,
+                    call_id: call_id.to_owned(),
+                    media: media.media.clone(),
+                    rtpmap: media.rtpmap.clone(),
+                });
+            }
+        }
+    }
+
+    fn insert(&mut self, sip: Sip) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, sip);
+        session_id
+    }
+}
+
+/// Skips CRLF keep-alives (RFC 5626) preceding a message.
+fn skip_keepalive(data: &[u8]) -> &[u8] {
+    let start = data
+        .iter()
+        .position(|b| *b != b'\r' && *b != b'\n')
+        .unwrap_or(data.len());
+    &data[start..]
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{tcp, udp};
+
+    use std::net::SocketAddr;
+
+    /// INVITE from Alice offering opus and PCMU audio, and declining video.
+    const INVITE: &[u8] = b"INVITE sip:bob@example.com SIP/2.0\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds\r\n\
+        Max-Forwards: 70\r\n\
+        From: \"Alice\" <sip:alice@example.com>;tag=1928301774\r\n\
+        To: <sip:bob@example.com>\r\n\
+        Call-ID: a84b4c76e66710@pc33.example.com\r\n\
+        CSeq: 314159 INVITE\r\n\
+        Contact: <sip:alice@10.0.0.1:5060>\r\n\
+        User-Agent: Linphone/5.2.0\r\n\
+        Content-Type: application/sdp\r\n\
+        Content-Length: 195\r\n\
+        \r\n\
+        v=0\r\n\
+        o=alice 2890844526 2890844526 IN IP4 10.0.0.1\r\n\
+        s=-\r\n\
+        c=IN IP4 10.0.0.1\r\n\
+        t=0 0\r\n\
+        m=audio 49170 RTP/AVP 111 0\r\n\
+        a=rtpmap:111 opus/48000/2\r\n\
+        a=rtpmap:0 PCMU/8000\r\n\
+        a=sendrecv\r\n\
+        m=video 0 RTP/AVP 96\r\n";
+
+    const TRYING: &[u8] = b"SIP/2.0 100 Trying\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds\r\n\
+        From: \"Alice\" <sip:alice@example.com>;tag=1928301774\r\n\
+        To: <sip:bob@example.com>\r\n\
+        Call-ID: a84b4c76e66710@pc33.example.com\r\n\
+        CSeq: 314159 INVITE\r\n\
+        Content-Length: 0\r\n\
+        \r\n";
+
+    const RINGING: &[u8] = b"SIP/2.0 180 Ringing\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds\r\n\
+        From: \"Alice\" <sip:alice@example.com>;tag=1928301774\r\n\
+        To: <sip:bob@example.com>;tag=a6c85cf\r\n\
+        Call-ID: a84b4c76e66710@pc33.example.com\r\n\
+        CSeq: 314159 INVITE\r\n\
+        Contact: <sip:bob@10.0.0.2:5060>\r\n\
+        Content-Length: 0\r\n\
+        \r\n";
+
+    /// 200 OK answering with opus audio, with RTCP multiplexed.
+    const OK: &[u8] = b"SIP/2.0 200 OK\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds\r\n\
+        From: \"Alice\" <sip:alice@example.com>;tag=1928301774\r\n\
+        To: <sip:bob@example.com>;tag=a6c85cf\r\n\
+        Call-ID: a84b4c76e66710@pc33.example.com\r\n\
+        CSeq: 314159 INVITE\r\n\
+        Contact: <sip:bob@10.0.0.2:5060>\r\n\
+        Server: Asterisk PBX 20.5.0\r\n\
+        Content-Type: application/sdp\r\n\
+        Content-Length: 168\r\n\
+        \r\n\
+        v=0\r\n\
+        o=bob 2808844564 2808844564 IN IP4 10.0.0.2\r\n\
+        s=-\r\n\
+        c=IN IP4 10.0.0.2\r\n\
+        t=0 0\r\n\
+        m=audio 3456 RTP/AVP 111\r\n\
+        a=rtpmap:111 opus/48000/2\r\n\
+        a=rtcp-mux\r\n\
+        m=video 0 RTP/AVP 96\r\n";
+
+    const ACK: &[u8] = b"ACK sip:bob@10.0.0.2:5060 SIP/2.0\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bKnashds8\r\n\
+        Max-Forwards: 70\r\n\
+        From: \"Alice\" <sip:alice@example.com>;tag=1928301774\r\n\
+        To: <sip:bob@example.com>;tag=a6c85cf\r\n\
+        Call-ID: a84b4c76e66710@pc33.example.com\r\n\
+        CSeq: 314159 ACK\r\n\
+        Content-Length: 0\r\n\
+        \r\n";
+
+    /// REGISTER with compact header names.
+    const REGISTER: &[u8] = b"REGISTER sip:example.com SIP/2.0\r\n\
+        v: SIP/2.0/TCP 10.0.0.1:50000;branch=z9hG4bKnashds7\r\n\
+        f: <sip:alice@example.com>;tag=456248\r\n\
+        t: <sip:alice@example.com>\r\n\
+        i: 843817637684230@998sdasdh09\r\n\
+        CSeq: 1826 REGISTER\r\n\
+        m: <sip:alice@10.0.0.1:50000;transport=tcp>\r\n\
+        Expires: 7200\r\n\
+        Content-Length: 0\r\n\
+        \r\n";
+
+    const UNAUTHORIZED: &[u8] = b"SIP/2.0 401 Unauthorized\r\n\
+        v: SIP/2.0/TCP 10.0.0.1:50000;branch=z9hG4bKnashds7\r\n\
+        f: <sip:alice@example.com>;tag=456248\r\n\
+        t: <sip:alice@example.com>;tag=2493k59kd\r\n\
+        i: 843817637684230@998sdasdh09\r\n\
+        CSeq: 1826 REGISTER\r\n\
+        WWW-Authenticate: Digest realm=\"example.com\", nonce=\"ea9c8e88df84f1cec4341ae6cbe5a359\"\r\n\
+        Content-Length: 0\r\n\
+        \r\n";
+
+    #[test]
+    fn core_sip_probe() {
+        let parser = SipParser::default();
+        assert_eq!(parser.probe(&udp(INVITE, 5060, true)), ProbeResult::Certain);
+        assert_eq!(parser.probe(&udp(OK, 5060, false)), ProbeResult::Certain);
+        assert_eq!(
+            parser.probe(&tcp(REGISTER, 5060, true)),
+            ProbeResult::Certain
+        );
+        // Keep-alive before the first message
+        assert_eq!(
+            parser.probe(&tcp(b"\r\n\r\n", 5060, true)),
+            ProbeResult::Unsure
+        );
+        assert_eq!(
+            parser.probe(&udp(b"GET / HTTP/1.1\r\n", 5060, true)),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            parser.probe(&udp(b"SIP/2.0", 5060, false)),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_sip_call() {
+        let mut parser = SipParser::default();
+        assert_eq!(
+            parser.parse(&udp(INVITE, 5060, true)),
+            ParseResult::Continue(0)
+        );
+        // Retransmitted request
+        assert_eq!(
+            parser.parse(&udp(INVITE, 5060, true)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(TRYING, 5060, false)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(RINGING, 5060, false)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(RINGING, 5060, false)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(OK, 5060, false)),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            parser.parse(&udp(ACK, 5060, true)),
+            ParseResult::Continue(1)
+        );
+
+        let invite = &parser.sessions[&0];
+        assert_eq!(invite.method(), "INVITE");
+        assert_eq!(invite.request_uri(), "sip:bob@example.com");
+        assert_eq!(invite.call_id(), "a84b4c76e66710@pc33.example.com");
+        assert_eq!(invite.from(), "sip:alice@example.com");
+        assert_eq!(invite.to(), "sip:bob@example.com");
+        assert_eq!(invite.cseq(), 314159);
+        assert_eq!(invite.user_agent(), "Linphone/5.2.0");
+        assert_eq!(invite.server(), "Asterisk PBX 20.5.0");
+        assert_eq!(invite.provisional, [100, 180]);
+        assert!(invite.ringing());
+        assert_eq!(invite.status_code(), 200);
+        assert_eq!(invite.reason(), "OK");
+        assert!(invite.has_sdp());
+        assert_eq!(invite.media_types(), "audio video");
+        assert_eq!(invite.codecs(), "opus PCMU");
+        let request = invite.request.as_ref().unwrap();
+        assert_eq!(request.branch.as_deref(), Some("z9hG4bK776asdhds"));
+        let sdp = request.sdp.as_ref().unwrap();
+        assert_eq!(sdp.media[0].direction.as_deref(), Some("sendrecv"));
+        assert_eq!(sdp.media[1].rtp_endpoint(), None);
+
+        let ack = &parser.sessions[&1];
+        assert_eq!(ack.method(), "ACK");
+        assert_eq!(ack.status_code(), 0);
+
+        // RTP and RTCP of the offer (announced again by the retransmission), and multiplexed RTP
+        // and RTCP of the answer
+        let expected = parser.drain_expected();
+        let endpoints: Vec<SocketAddr> = expected.iter().map(|e| e.endpoint).collect();
+        assert_eq!(
+            endpoints,
+            [
+                "10.0.0.1:49170".parse().unwrap(),
+                "10.0.0.1:49171".parse().unwrap(),
+                "10.0.0.1:49170".parse().unwrap(),
+                "10.0.0.1:49171".parse().unwrap(),
+                "10.0.0.2:3456".parse().unwrap(),
+            ]
+        );
+        assert!(expected
+            .iter()
+            .all(|e| e.call_id == "a84b4c76e66710@pc33.example.com" && e.media == "audio"));
+        assert_eq!(expected[4].rtpmap.len(), 1);
+        assert!(parser.drain_expected().is_empty());
+    }
+
+    #[test]
+    fn core_sip_tcp_split() {
+        let mut parser = SipParser::default();
+        // Keep-alive, then a message split within a header and within the body
+        assert_eq!(
+            parser.parse(&tcp(b"\r\n\r\n", 5060, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&tcp(&INVITE[..100], 5060, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&tcp(&INVITE[100..450], 5060, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&tcp(&INVITE[450..], 5060, true)),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.client_buffer.is_empty());
+        assert_eq!(parser.drain_expected().len(), 2);
+
+        // Compact header names
+        assert_eq!(
+            parser.parse(&tcp(&REGISTER[..150], 5060, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&tcp(&REGISTER[150..], 5060, true)),
+            ParseResult::Continue(1)
+        );
+        assert_eq!(
+            parser.parse(&tcp(UNAUTHORIZED, 5060, false)),
+            ParseResult::Continue(1)
+        );
+        let register = &parser.sessions[&1];
+        assert_eq!(register.method(), "REGISTER");
+        assert_eq!(register.call_id(), "843817637684230@998sdasdh09");
+        assert_eq!(register.from(), "sip:alice@example.com");
+        assert_eq!(register.status_code(), 401);
+        let request = register.request.as_ref().unwrap();
+        assert_eq!(
+            request.contact.as_deref(),
+            Some("<sip:alice@10.0.0.1:50000;transport=tcp>")
+        );
+    }
+
+    #[test]
+    fn core_sip_truncated() {
+        let mut parser = SipParser::default();
+        // Datagrams hold entire messages
+        assert_eq!(
+            parser.parse(&udp(&INVITE[..450], 5060, true)),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            parser.parse(&udp(&INVITE[..100], 5060, true)),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+        assert!(parser.drain_expected().is_empty());
+
+        // Incomplete message at the end of a stream
+        assert_eq!(
+            parser.parse(&tcp(&OK[..200], 5060, false)),
+            ParseResult::Skipped
+        );
+        assert_eq!(parser.server_buffer.len(), 200);
+        assert!(parser.drain_sessions().is_empty());
+
+        // Missing Call-ID
+        let mut parser = SipParser::default();
+        let options = b"OPTIONS sip:bob@example.com SIP/2.0\r\nCSeq: 1 OPTIONS\r\n\r\n";
+        assert_eq!(
+            parser.parse(&udp(options, 5060, true)),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+    }
+}
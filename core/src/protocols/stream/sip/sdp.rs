@@ -0,0 +1,193 @@
+//! Session Description Protocol (SDP) components.
+//!
+//! See [RFC 8866](https://datatracker.ietf.org/doc/html/rfc8866). Only the fields needed to
+//! identify the media streams of a session are parsed: connection addresses, media descriptions,
+//! and the `rtpmap`, `rtcp`, `rtcp-mux`, and direction attributes.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use std::net::{IpAddr, SocketAddr};
+
+/// Maximum number of media descriptions parsed per session description.
+const MAX_MEDIA: usize = 16;
+
+/// A parsed session description.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Sdp {
+    /// Origin (`o=`) username, session ID, and version.
+    pub origin: Option<String>,
+    /// Session name (`s=`).
+    pub session_name: Option<String>,
+    /// Media descriptions (`m=`), in order.
+    pub media: Vec<SdpMedia>,
+}
+
+/// A media description.
+#[derive(Clone, Debug, Serialize)]
+pub struct SdpMedia {
+    /// Media type (e.g., `audio`).
+    pub media: String,
+    /// Connection address of the media, or of the session if the media does not have its own.
+    pub addr: Option<IpAddr>,
+    /// Transport port. 0 if the stream is disabled.
+    pub port: u16,
+    /// Transport protocol (e.g., `RTP/AVP`).
+    pub protocol: String,
+    /// Media formats. RTP payload types for RTP-based transports.
+    pub formats: Vec<String>,
+    /// Payload type mappings (`a=rtpmap`).
+    pub rtpmap: Vec<RtpMap>,
+    /// RTCP port (`a=rtcp`), if it is not the RTP port plus one.
+    pub rtcp_port: Option<u16>,
+    /// `true` if RTP and RTCP are multiplexed on the same port (`a=rtcp-mux`).
+    pub rtcp_mux: bool,
+    /// Direction attribute (e.g., `sendrecv`), if present.
+    pub direction: Option<String>,
+}
+
+/// A mapping from an RTP payload type to an encoding.
+#[derive(Clone, Debug, Serialize)]
+pub struct RtpMap {
+    pub payload_type: u8,
+    /// Encoding name (e.g., `opus`).
+    pub encoding: String,
+    /// RTP timestamp clock rate in Hz.
+    pub clock_rate: u32,
+}
+
+impl Sdp {
+    /// Parses a session description from a message body.
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        let text = String::from_utf8_lossy(data);
+        let mut lines = text.lines().map(|line| line.trim_end());
+        if lines.next() != Some("v=0") {
+            bail!("not a session description");
+        }
+
+        let mut sdp = Sdp::default();
+        let mut session_addr = None;
+        for line in lines {
+            let (field, value) = match line.split_once('=') {
+                Some((field, value)) if field.len() == 1 => (field, value),
+                _ => continue,
+            };
+            if field == "m" {
+                if sdp.media.len() >= MAX_MEDIA {
+                    break;
+                }
+                sdp.media.push(SdpMedia::parse_from(value)?);
+                continue;
+            }
+            match (field, sdp.media.last_mut()) {
+                ("o", None) => sdp.origin = Some(value.to_owned()),
+                ("s", None) => sdp.session_name = Some(value.to_owned()),
+                ("c", None) => session_addr = parse_connection(value),
+                ("c", Some(media)) => media.addr = parse_connection(value),
+                ("a", Some(media)) => media.parse_attribute(value),
+                _ => (),
+            }
+        }
+        for media in sdp.media.iter_mut() {
+            media.addr = media.addr.or(session_addr);
+        }
+        Ok(sdp)
+    }
+}
+
+impl SdpMedia {
+    /// Parses an `m=` line: `<media> <port>[/<number of ports>] <proto> <fmt> ...`.
+    fn parse_from(value: &str) -> Result<Self> {
+        let mut fields = value.split_ascii_whitespace();
+        let (media, port, protocol) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(media), Some(port), Some(protocol)) => (media, port, protocol),
+            _ => bail!("truncated media description"),
+        };
+        let port = match port.split('/').next().and_then(|p| p.parse().ok()) {
+            Some(port) => port,
+            None => bail!("invalid media port {}", port),
+        };
+        Ok(SdpMedia {
+            media: media.to_owned(),
+            addr: None,
+            port,
+            protocol: protocol.to_owned(),
+            formats: fields.map(|f| f.to_owned()).collect(),
+            rtpmap: vec![],
+            rtcp_port: None,
+            rtcp_mux: false,
+            direction: None,
+        })
+    }
+
+    fn parse_attribute(&mut self, value: &str) {
+        let (name, value) = value.split_once(':').unwrap_or((value, ""));
+        match name {
+            // a=rtpmap:<payload type> <encoding name>/<clock rate>[/<parameters>]
+            "rtpmap" => {
+                if let Some((pt, encoding)) = value.split_once(' ') {
+                    let mut encoding = encoding.trim().split('/');
+                    if let (Ok(payload_type), Some(name), Some(Ok(clock_rate))) = (
+                        pt.parse(),
+                        encoding.next(),
+                        encoding.next().map(|rate| rate.parse()),
+                    ) {
+                        self.rtpmap.push(RtpMap {
+                            payload_type,
+                            encoding: name.to_owned(),
+                            clock_rate,
+                        });
+                    }
+                }
+            }
+            // a=rtcp:<port> [<nettype> <addrtype> <connection-address>]
+            "rtcp" => {
+                self.rtcp_port = value
+                    .split_ascii_whitespace()
+                    .next()
+                    .and_then(|port| port.parse().ok());
+            }
+            "rtcp-mux" => self.rtcp_mux = true,
+            "sendrecv" | "sendonly" | "recvonly" | "inactive" => {
+                self.direction = Some(name.to_owned())
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns `true` if the media is transported over RTP.
+    pub fn is_rtp(&self) -> bool {
+        self.protocol.contains("RTP/")
+    }
+
+    /// Returns the endpoint that receives the RTP stream, or `None` if the stream is disabled or
+    /// not transported over RTP.
+    pub fn rtp_endpoint(&self) -> Option<SocketAddr> {
+        match self.addr {
+            Some(addr) if self.port != 0 && !addr.is_unspecified() && self.is_rtp() => {
+                Some(SocketAddr::new(addr, self.port))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the endpoint that receives the RTCP stream, or `None` if RTCP is multiplexed with
+    /// RTP or there is no RTP stream.
+    pub fn rtcp_endpoint(&self) -> Option<SocketAddr> {
+        if self.rtcp_mux {
+            return None;
+        }
+        let rtp = self.rtp_endpoint()?;
+        let port = self.rtcp_port.or_else(|| rtp.port().checked_add(1))?;
+        Some(SocketAddr::new(rtp.ip(), port))
+    }
+}
+
+/// Parses a `c=` line: `IN IP4|IP6 <address>[/<ttl>][/<number of addresses>]`.
+fn parse_connection(value: &str) -> Option<IpAddr> {
+    let mut fields = value.split_ascii_whitespace();
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some("IN"), Some("IP4" | "IP6"), Some(addr)) => addr.split('/').next()?.parse().ok(),
+        _ => None,
+    }
+}
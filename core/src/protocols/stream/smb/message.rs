@@ -0,0 +1,396 @@
+//! SMB2/3 message components.
+//!
+//! See [\[MS-SMB2\]](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-smb2/) for
+//! the message formats. All integers are little-endian, and buffer offsets are relative to the
+//! start of the SMB2 header.
+
+use anyhow::{bail, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Serialize;
+
+/// Length of the SMB2 header.
+pub(super) const HDR_LEN: usize = 64;
+/// Protocol identifier of an SMB2 message.
+pub(super) const SMB2_MAGIC: &[u8] = b"\xfeSMB";
+/// Protocol identifier of an SMB2 transform (encrypted) message.
+pub(super) const SMB2_TRANSFORM_MAGIC: &[u8] = b"\xfdSMB";
+/// Protocol identifier of an SMB2 compression transform message.
+pub(super) const SMB2_COMPRESSION_MAGIC: &[u8] = b"\xfcSMB";
+/// Protocol identifier of an SMB1 message, used by clients to start a multi-protocol negotiation.
+pub(super) const SMB1_MAGIC: &[u8] = b"\xffSMB";
+
+pub const SMB2_NEGOTIATE: u16 = 0x0000;
+pub const SMB2_SESSION_SETUP: u16 = 0x0001;
+pub const SMB2_LOGOFF: u16 = 0x0002;
+pub const SMB2_TREE_CONNECT: u16 = 0x0003;
+pub const SMB2_TREE_DISCONNECT: u16 = 0x0004;
+pub const SMB2_CREATE: u16 = 0x0005;
+
+/// Response to a request that will complete asynchronously.
+pub const STATUS_PENDING: u32 = 0x0000_0103;
+/// Response to a session setup request that requires another authentication round trip.
+pub const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+
+const SMB2_FLAGS_SERVER_TO_REDIR: u32 = 0x0000_0001;
+const SMB2_FLAGS_SIGNED: u32 = 0x0000_0008;
+
+const SMB2_ENCRYPTION_CAPABILITIES: u16 = 0x0002;
+const SMB2_SIGNING_CAPABILITIES: u16 = 0x0008;
+
+/// Kerberos 5 mechanism OID (1.2.840.113554.1.2.2) in DER encoding.
+const KRB5_OID: &[u8] = b"\x2a\x86\x48\x86\xf7\x12\x01\x02\x02";
+/// Microsoft Kerberos 5 mechanism OID (1.2.840.48018.1.2.2) in DER encoding.
+const MS_KRB5_OID: &[u8] = b"\x2a\x86\x48\x82\xf7\x12\x01\x02\x02";
+const NTLMSSP_SIGNATURE: &[u8] = b"NTLMSSP\0";
+const NTLMSSP_AUTH: u32 = 3;
+
+/// Returns the name of an SMB2 command (e.g., `"tree_connect"`).
+pub fn command_name(command: u16) -> &'static str {
+    match command {
+        0x0000 => "negotiate",
+        0x0001 => "session_setup",
+        0x0002 => "logoff",
+        0x0003 => "tree_connect",
+        0x0004 => "tree_disconnect",
+        0x0005 => "create",
+        0x0006 => "close",
+        0x0007 => "flush",
+        0x0008 => "read",
+        0x0009 => "write",
+        0x000a => "lock",
+        0x000b => "ioctl",
+        0x000c => "cancel",
+        0x000d => "echo",
+        0x000e => "query_directory",
+        0x000f => "change_notify",
+        0x0010 => "query_info",
+        0x0011 => "set_info",
+        0x0012 => "oplock_break",
+        _ => "unknown",
+    }
+}
+
+/// Returns the name of an SMB2 dialect revision (e.g., `"3.1.1"`).
+pub fn dialect_name(dialect: u16) -> &'static str {
+    match dialect {
+        0x0202 => "2.0.2",
+        0x0210 => "2.1",
+        0x02ff => "2.???",
+        0x0300 => "3.0",
+        0x0302 => "3.0.2",
+        0x0311 => "3.1.1",
+        _ => "unknown",
+    }
+}
+
+/// Returns the name of an SMB 3.x encryption cipher (e.g., `"AES-128-GCM"`).
+pub fn cipher_name(cipher: u16) -> &'static str {
+    match cipher {
+        0x0001 => "AES-128-CCM",
+        0x0002 => "AES-128-GCM",
+        0x0003 => "AES-256-CCM",
+        0x0004 => "AES-256-GCM",
+        _ => "unknown",
+    }
+}
+
+/// An SMB2 message header.
+#[derive(Clone, Debug, Serialize)]
+pub struct Smb2Header {
+    pub command: u16,
+    /// NT status code (responses only).
+    pub status: u32,
+    pub flags: u32,
+    /// Offset of the next message in a compounded chain, or 0 if this is the last.
+    pub next_command: u32,
+    pub message_id: u64,
+    /// Tree ID (synchronous messages only).
+    pub tree_id: u32,
+    pub session_id: u64,
+}
+
+impl Smb2Header {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.len() < HDR_LEN || !data.starts_with(SMB2_MAGIC) {
+            bail!("not an SMB2 message");
+        }
+        Ok(Smb2Header {
+            status: LittleEndian::read_u32(&data[8..12]),
+            command: LittleEndian::read_u16(&data[12..14]),
+            flags: LittleEndian::read_u32(&data[16..20]),
+            next_command: LittleEndian::read_u32(&data[20..24]),
+            message_id: LittleEndian::read_u64(&data[24..32]),
+            tree_id: LittleEndian::read_u32(&data[36..40]),
+            session_id: LittleEndian::read_u64(&data[40..48]),
+        })
+    }
+
+    /// Returns `true` if the message is a response.
+    pub fn is_response(&self) -> bool {
+        self.flags & SMB2_FLAGS_SERVER_TO_REDIR != 0
+    }
+
+    /// Returns `true` if the message is signed.
+    pub fn is_signed(&self) -> bool {
+        self.flags & SMB2_FLAGS_SIGNED != 0
+    }
+}
+
+/// Authentication information from a session setup request.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SmbAuth {
+    /// Authentication mechanism (`"ntlmssp"` or `"kerberos"`), or `""` if unknown.
+    pub mechanism: &'static str,
+    /// User name from an NTLMSSP AUTHENTICATE message.
+    pub user: Option<String>,
+    /// Domain name from an NTLMSSP AUTHENTICATE message.
+    pub domain: Option<String>,
+    /// Workstation name from an NTLMSSP AUTHENTICATE message.
+    pub workstation: Option<String>,
+}
+
+/// Command-specific SMB2 message contents.
+#[derive(Clone, Debug, Serialize)]
+pub enum SmbBody {
+    NegotiateRequest {
+        dialects: Vec<u16>,
+        security_mode: u16,
+        client_guid: String,
+        /// Ciphers offered in the encryption capabilities negotiate context (3.1.1 only).
+        ciphers: Vec<u16>,
+    },
+    NegotiateResponse {
+        dialect: u16,
+        security_mode: u16,
+        server_guid: String,
+        /// Cipher selected in the encryption capabilities negotiate context (3.1.1 only).
+        cipher: Option<u16>,
+        /// Signing algorithm selected in the signing capabilities negotiate context.
+        signing_algorithm: Option<u16>,
+    },
+    SessionSetupRequest {
+        security_mode: u8,
+        auth: SmbAuth,
+    },
+    SessionSetupResponse {
+        session_flags: u16,
+    },
+    TreeConnectRequest {
+        /// Share path (e.g., `\\server\share`).
+        path: String,
+    },
+    TreeConnectResponse {
+        /// Share type (1 for disk, 2 for named pipe, 3 for printer).
+        share_type: u8,
+        share_flags: u32,
+    },
+    CreateRequest {
+        /// File name, relative to the share.
+        name: String,
+        desired_access: u32,
+        create_disposition: u32,
+        create_options: u32,
+    },
+    CreateResponse {
+        create_action: u32,
+    },
+    /// Other commands, or an error response.
+    Other,
+}
+
+/// An SMB2 message.
+#[derive(Clone, Debug, Serialize)]
+pub struct SmbMessage {
+    pub header: Smb2Header,
+    pub body: SmbBody,
+}
+
+impl SmbMessage {
+    /// Parses a single SMB2 message starting at the beginning of `data`. `data` may extend past
+    /// the message (e.g., into the next message of a compounded chain).
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        let header = Smb2Header::parse_from(data)?;
+        let body = if header.is_response()
+            && header.status != 0
+            && !(header.command == SMB2_SESSION_SETUP
+                && header.status == STATUS_MORE_PROCESSING_REQUIRED)
+        {
+            // Error response, other than an intermediate session setup response
+            SmbBody::Other
+        } else {
+            Self::parse_body(&header, data).unwrap_or(SmbBody::Other)
+        };
+        Ok(SmbMessage { header, body })
+    }
+
+    fn parse_body(header: &Smb2Header, data: &[u8]) -> Option<SmbBody> {
+        let body = data.get(HDR_LEN..)?;
+        let body = match (header.command, header.is_response()) {
+            (SMB2_NEGOTIATE, false) => {
+                let count = LittleEndian::read_u16(body.get(2..4)?) as usize;
+                let dialects: Vec<u16> = body
+                    .get(36..36 + 2 * count)?
+                    .chunks_exact(2)
+                    .map(LittleEndian::read_u16)
+                    .collect();
+                let mut ciphers = vec![];
+                if dialects.contains(&0x0311) {
+                    let offset = LittleEndian::read_u32(body.get(28..32)?) as usize;
+                    let num = LittleEndian::read_u16(body.get(32..34)?) as usize;
+                    for (ctx_type, ctx) in negotiate_contexts(data, offset, num) {
+                        if ctx_type == SMB2_ENCRYPTION_CAPABILITIES {
+                            let n = LittleEndian::read_u16(ctx.get(..2)?) as usize;
+                            ciphers = ctx
+                                .get(2..2 + 2 * n)?
+                                .chunks_exact(2)
+                                .map(LittleEndian::read_u16)
+                                .collect();
+                        }
+                    }
+                }
+                SmbBody::NegotiateRequest {
+                    dialects,
+                    security_mode: LittleEndian::read_u16(body.get(4..6)?),
+                    client_guid: guid(body.get(12..28)?),
+                    ciphers,
+                }
+            }
+            (SMB2_NEGOTIATE, true) => {
+                let dialect = LittleEndian::read_u16(body.get(4..6)?);
+                let mut cipher = None;
+                let mut signing_algorithm = None;
+                if dialect == 0x0311 {
+                    let num = LittleEndian::read_u16(body.get(6..8)?) as usize;
+                    let offset = LittleEndian::read_u32(body.get(60..64)?) as usize;
+                    for (ctx_type, ctx) in negotiate_contexts(data, offset, num) {
+                        match ctx_type {
+                            SMB2_ENCRYPTION_CAPABILITIES => {
+                                cipher = ctx.get(2..4).map(LittleEndian::read_u16)
+                            }
+                            SMB2_SIGNING_CAPABILITIES => {
+                                signing_algorithm = ctx.get(2..4).map(LittleEndian::read_u16)
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                SmbBody::NegotiateResponse {
+                    dialect,
+                    security_mode: LittleEndian::read_u16(body.get(2..4)?),
+                    server_guid: guid(body.get(8..24)?),
+                    cipher,
+                    signing_algorithm,
+                }
+            }
+            (SMB2_SESSION_SETUP, false) => {
+                let offset = LittleEndian::read_u16(body.get(12..14)?) as usize;
+                let len = LittleEndian::read_u16(body.get(14..16)?) as usize;
+                let auth = data
+                    .get(offset..offset + len)
+                    .map(parse_security_blob)
+                    .unwrap_or_default();
+                SmbBody::SessionSetupRequest {
+                    security_mode: *body.get(3)?,
+                    auth,
+                }
+            }
+            (SMB2_SESSION_SETUP, true) => SmbBody::SessionSetupResponse {
+                session_flags: LittleEndian::read_u16(body.get(2..4)?),
+            },
+            (SMB2_TREE_CONNECT, false) => {
+                let offset = LittleEndian::read_u16(body.get(4..6)?) as usize;
+                let len = LittleEndian::read_u16(body.get(6..8)?) as usize;
+                SmbBody::TreeConnectRequest {
+                    path: utf16le(data.get(offset..offset + len)?),
+                }
+            }
+            (SMB2_TREE_CONNECT, true) => SmbBody::TreeConnectResponse {
+                share_type: *body.get(2)?,
+                share_flags: LittleEndian::read_u32(body.get(4..8)?),
+            },
+            (SMB2_CREATE, false) => {
+                let offset = LittleEndian::read_u16(body.get(44..46)?) as usize;
+                let len = LittleEndian::read_u16(body.get(46..48)?) as usize;
+                SmbBody::CreateRequest {
+                    name: utf16le(data.get(offset..offset + len)?),
+                    desired_access: LittleEndian::read_u32(body.get(24..28)?),
+                    create_disposition: LittleEndian::read_u32(body.get(36..40)?),
+                    create_options: LittleEndian::read_u32(body.get(40..44)?),
+                }
+            }
+            (SMB2_CREATE, true) => SmbBody::CreateResponse {
+                create_action: LittleEndian::read_u32(body.get(4..8)?),
+            },
+            _ => SmbBody::Other,
+        };
+        Some(body)
+    }
+}
+
+/// Returns the (type, data) of each negotiate context in an SMB 3.1.1 negotiate message.
+fn negotiate_contexts(data: &[u8], offset: usize, num: usize) -> Vec<(u16, &[u8])> {
+    let mut contexts = vec![];
+    let mut offset = offset;
+    for _ in 0..num {
+        let hdr = match data.get(offset..offset + 8) {
+            Some(hdr) => hdr,
+            None => break,
+        };
+        let ctx_type = LittleEndian::read_u16(&hdr[0..2]);
+        let len = LittleEndian::read_u16(&hdr[2..4]) as usize;
+        match data.get(offset + 8..offset + 8 + len) {
+            Some(ctx) => contexts.push((ctx_type, ctx)),
+            None => break,
+        }
+        // Contexts are 8-byte aligned
+        offset = (offset + 8 + len + 7) & !7;
+    }
+    contexts
+}
+
+/// Extracts the authentication mechanism and NTLMSSP identity from a session setup security
+/// buffer (a GSS-API/SPNEGO token).
+fn parse_security_blob(blob: &[u8]) -> SmbAuth {
+    let mut auth = SmbAuth::default();
+    if let Some(pos) = memchr::memmem::find(blob, NTLMSSP_SIGNATURE) {
+        auth.mechanism = "ntlmssp";
+        let ntlm = &blob[pos..];
+        if ntlm.len() >= 52 && LittleEndian::read_u32(&ntlm[8..12]) == NTLMSSP_AUTH {
+            auth.domain = ntlm_field(ntlm, 28);
+            auth.user = ntlm_field(ntlm, 36);
+            auth.workstation = ntlm_field(ntlm, 44);
+        }
+    } else if memchr::memmem::find(blob, KRB5_OID).is_some()
+        || memchr::memmem::find(blob, MS_KRB5_OID).is_some()
+    {
+        auth.mechanism = "kerberos";
+    }
+    auth
+}
+
+/// Reads a UTF-16LE string field (length, maximum length, offset) of an NTLMSSP message.
+fn ntlm_field(ntlm: &[u8], field_offset: usize) -> Option<String> {
+    let len = LittleEndian::read_u16(ntlm.get(field_offset..field_offset + 2)?) as usize;
+    let offset = LittleEndian::read_u32(ntlm.get(field_offset + 4..field_offset + 8)?) as usize;
+    if len == 0 {
+        return None;
+    }
+    Some(utf16le(ntlm.get(offset..offset + len)?))
+}
+
+fn utf16le(data: &[u8]) -> String {
+    let units: Vec<u16> = data.chunks_exact(2).map(LittleEndian::read_u16).collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Formats a GUID in its canonical mixed-endian string form.
+fn guid(data: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{}-{}",
+        LittleEndian::read_u32(&data[0..4]),
+        LittleEndian::read_u16(&data[4..6]),
+        LittleEndian::read_u16(&data[6..8]),
+        hex::encode(&data[8..10]),
+        hex::encode(&data[10..16]),
+    )
+}
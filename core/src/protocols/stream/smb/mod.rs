@@ -0,0 +1,162 @@
+//! SMB2/3 transaction parsing.
+//!
+//! ## Remarks
+//! The parser records negotiate, session setup, tree connect, and create transactions, pairing
+//! each request with its response by message ID. Other commands (e.g., reads and writes) are
+//! skipped without buffering their contents. Each transaction carries a snapshot of the
+//! connection state at the time it was observed (negotiated dialect, authenticated user, share
+//! name), so that, e.g., a file open can be filtered on the share it was opened on.
+//!
+//! SMB connections are long-lived and carry many transactions, so completed transactions are
+//! retained and delivered when the connection terminates, or once a bounded number of them have
+//! been observed. SMB1 messages, which are only used by modern clients to start a multi-protocol
+//! negotiation, are not recorded. Once SMB 3.x encryption is in use, message contents are opaque
+//! and only the fact that encryption was observed is recorded.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Connection state at the time of an SMB transaction.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SmbContext {
+    /// Negotiated dialect revision, or 0 if not yet negotiated.
+    pub dialect: u16,
+    /// Dialect revisions offered by the client.
+    pub client_dialects: Vec<u16>,
+    /// Negotiated encryption cipher (SMB 3.1.1 only).
+    pub cipher: Option<u16>,
+    /// `true` if either endpoint requires message signing.
+    pub signing_required: bool,
+    /// `true` if the session or share requires encryption, or an encrypted message was observed.
+    pub encryption: bool,
+    /// Most recent session setup authentication information.
+    pub auth: SmbAuth,
+    /// Share path of the tree the transaction was sent on.
+    pub share: Option<String>,
+}
+
+/// Parsed SMB2/3 request/response transaction.
+#[derive(Debug, Serialize)]
+pub struct Smb {
+    /// Message ID, unique per request on a connection.
+    pub message_id: u64,
+    /// SMB2 command.
+    pub command: u16,
+    /// Request message.
+    pub request: Option<SmbMessage>,
+    /// Response message.
+    pub response: Option<SmbMessage>,
+    /// Connection state.
+    pub context: SmbContext,
+}
+
+impl Smb {
+    /// Returns the name of the SMB2 command (e.g., `"tree_connect"`).
+    pub fn command(&self) -> &str {
+        command_name(self.command)
+    }
+
+    /// Returns the NT status code of the response, or 0 if there is no response.
+    pub fn status(&self) -> u32 {
+        self.response.as_ref().map_or(0, |r| r.header.status)
+    }
+
+    /// Returns the session ID.
+    pub fn session_id(&self) -> u64 {
+        self.response
+            .as_ref()
+            .or(self.request.as_ref())
+            .map_or(0, |m| m.header.session_id)
+    }
+
+    /// Returns the negotiated dialect (e.g., `"3.1.1"`), or `""` if not yet negotiated.
+    pub fn dialect(&self) -> &str {
+        match self.context.dialect {
+            0 => "",
+            dialect => dialect_name(dialect),
+        }
+    }
+
+    /// Returns a comma-separated list of the dialects offered by the client.
+    pub fn client_dialects(&self) -> String {
+        self.context
+            .client_dialects
+            .iter()
+            .map(|d| dialect_name(*d))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Returns the negotiated encryption cipher (e.g., `"AES-128-GCM"`), or `""` if none.
+    pub fn cipher(&self) -> &str {
+        self.context.cipher.map_or("", cipher_name)
+    }
+
+    /// Returns `true` if either endpoint requires message signing.
+    pub fn signing_required(&self) -> bool {
+        self.context.signing_required
+    }
+
+    /// Returns `true` if the request or response was signed.
+    pub fn signed(&self) -> bool {
+        self.request
+            .iter()
+            .chain(self.response.iter())
+            .any(|m| m.header.is_signed())
+    }
+
+    /// Returns `true` if the session or share requires encryption.
+    pub fn encrypted(&self) -> bool {
+        self.context.encryption
+    }
+
+    /// Returns the authentication mechanism (`"ntlmssp"` or `"kerberos"`), or `""` if unknown.
+    pub fn auth_mechanism(&self) -> &str {
+        self.context.auth.mechanism
+    }
+
+    /// Returns the NTLMSSP user name, or `""` if unknown.
+    pub fn user(&self) -> &str {
+        self.context.auth.user.as_deref().unwrap_or("")
+    }
+
+    /// Returns the NTLMSSP domain name, or `""` if unknown.
+    pub fn domain(&self) -> &str {
+        self.context.auth.domain.as_deref().unwrap_or("")
+    }
+
+    /// Returns the NTLMSSP workstation name, or `""` if unknown.
+    pub fn workstation(&self) -> &str {
+        self.context.auth.workstation.as_deref().unwrap_or("")
+    }
+
+    /// Returns the share path (e.g., `\\server\IPC$`) being connected to or accessed, or `""` if
+    /// unknown.
+    pub fn share_name(&self) -> &str {
+        match self.request.as_ref().map(|r| &r.body) {
+            Some(SmbBody::TreeConnectRequest { path }) => path,
+            _ => self.context.share.as_deref().unwrap_or(""),
+        }
+    }
+
+    /// Returns the share type of a tree connect (1 for disk, 2 for named pipe, 3 for printer), or
+    /// 0 if this is not a successful tree connect.
+    pub fn share_type(&self) -> u8 {
+        match self.response.as_ref().map(|r| &r.body) {
+            Some(SmbBody::TreeConnectResponse { share_type, .. }) => *share_type,
+            _ => 0,
+        }
+    }
+
+    /// Returns the name of the file being opened, or `""` if this is not a create transaction.
+    pub fn file_name(&self) -> &str {
+        match self.request.as_ref().map(|r| &r.body) {
+            Some(SmbBody::CreateRequest { name, .. }) => name,
+            _ => "",
+        }
+    }
+}
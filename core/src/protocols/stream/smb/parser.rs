@@ -0,0 +1,777 @@
+//! SMB2/3 transaction parser.
+//!
+//! Parses SMB2 messages over NetBIOS session service framing, pairs each request with its
+//! response by message ID, and tracks the connection state shared by later transactions.
+
+use super::message::*;
+use super::{Smb, SmbContext};
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::packet::tcp::TCP_PROTOCOL;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::collections::HashMap;
+
+/// Maximum number of completed transactions recorded before parsing stops.
+const MAX_TRANSACTIONS: usize = 64;
+/// Upper bound on the size of a buffered SMB message.
+const MAX_BUFFER_LEN: usize = 1 << 16;
+/// NetBIOS session service session message.
+const NBSS_SESSION_MESSAGE: u8 = 0x00;
+/// NetBIOS session service session request, sent before SMB on port 139.
+const NBSS_SESSION_REQUEST: u8 = 0x81;
+/// Session flag indicating that the session requires encryption.
+const SMB2_SESSION_FLAG_ENCRYPT_DATA: u16 = 0x0004;
+/// Share flag indicating that the share requires encryption.
+const SMB2_SHAREFLAG_ENCRYPT_DATA: u32 = 0x0000_8000;
+
+#[derive(Default, Debug)]
+pub struct SmbParser {
+    /// Maps session ID to SMB transaction
+    sessions: HashMap<usize, Smb>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+    /// Number of transactions with both a request and a response
+    completed: usize,
+    /// Current connection state
+    context: SmbContext,
+    /// Maps tree ID to share path and whether the share requires encryption
+    trees: HashMap<u32, (String, bool)>,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+    /// Remaining bytes of a large or untracked client message that are not buffered
+    client_skip: usize,
+    /// Remaining bytes of a large or untracked server message that are not buffered
+    server_skip: usize,
+}
+
+impl ConnParsable for SmbParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.proto != TCP_PROTOCOL {
+            return ProbeResult::NotForUs;
+        }
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            if data[0] == NBSS_SESSION_REQUEST
+                && (pdu.ctxt.src.port() == 139 || pdu.ctxt.dst.port() == 139)
+            {
+                // NetBIOS session establishment precedes SMB
+                return ProbeResult::Unsure;
+            }
+            if data.len() >= 8
+                && data[0] == NBSS_SESSION_MESSAGE
+                && (&data[4..8] == SMB2_MAGIC || &data[4..8] == SMB1_MAGIC)
+            {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|smb| Session {
+            data: SessionData::Smb(Box::new(smb)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, smb)| Session {
+                data: SessionData::Smb(Box::new(smb)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider SMB to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl SmbParser {
+    pub(crate) fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let skip = match dir {
+            true => &mut self.client_skip,
+            false => &mut self.server_skip,
+        };
+        let skipped = (*skip).min(data.len());
+        *skip -= skipped;
+        let data = &data[skipped..];
+
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
+
+        let mut result = ParseResult::Skipped;
+        while cur_data.len() >= 4 {
+            let len = 4 + BigEndian::read_u24(&cur_data[1..4]) as usize;
+            if cur_data.len() >= len {
+                if cur_data[0] == NBSS_SESSION_MESSAGE {
+                    result = match self.process_record(&cur_data[4..len]) {
+                        ParseResult::Skipped => result,
+                        ParseResult::HeadersDone(id) => return ParseResult::HeadersDone(id),
+                        record_result => record_result,
+                    };
+                }
+                cur_data = &cur_data[len..];
+                continue;
+            }
+            // Incomplete message. Only buffer tracked commands that fit in the buffer.
+            if cur_data.len() < 4 + HDR_LEN {
+                break;
+            }
+            let record = &cur_data[4..];
+            let tracked = record.starts_with(SMB2_MAGIC)
+                && is_tracked(LittleEndian::read_u16(&record[12..14]));
+            if record.starts_with(SMB2_TRANSFORM_MAGIC) {
+                self.context.encryption = true;
+            }
+            if tracked && len <= MAX_BUFFER_LEN {
+                break;
+            }
+            let skip = match dir {
+                true => &mut self.client_skip,
+                false => &mut self.server_skip,
+            };
+            *skip = len - cur_data.len();
+            cur_data = &[];
+        }
+
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        buffer.extend_from_slice(cur_data);
+        result
+    }
+
+    /// Processes a NetBIOS session message, which may hold a chain of compounded SMB2 messages.
+    fn process_record(&mut self, record: &[u8]) -> ParseResult {
+        if record.starts_with(SMB2_TRANSFORM_MAGIC) {
+            self.context.encryption = true;
+            return ParseResult::Skipped;
+        }
+        if record.starts_with(SMB1_MAGIC) || record.starts_with(SMB2_COMPRESSION_MAGIC) {
+            return ParseResult::Skipped;
+        }
+
+        let mut result = ParseResult::Skipped;
+        let mut offset = 0;
+        while offset < record.len() {
+            match SmbMessage::parse_from(&record[offset..]) {
+                Ok(msg) => {
+                    let next = msg.header.next_command as usize;
+                    if is_tracked(msg.header.command) {
+                        result = self.push(msg);
+                        if matches!(result, ParseResult::HeadersDone(_)) {
+                            return result;
+                        }
+                    }
+                    if next == 0 {
+                        break;
+                    }
+                    offset += next;
+                }
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Records a tracked SMB2 message, pairing responses with outstanding requests.
+    fn push(&mut self, msg: SmbMessage) -> ParseResult {
+        let header = &msg.header;
+        if !header.is_response() {
+            log::debug!("SMB2 {} request", command_name(header.command));
+            match &msg.body {
+                SmbBody::NegotiateRequest {
+                    dialects,
+                    security_mode,
+                    ..
+                } => {
+                    self.context.client_dialects = dialects.clone();
+                    self.context.signing_required |= security_mode & 0x02 != 0;
+                }
+                SmbBody::SessionSetupRequest { auth, .. } => {
+                    if !auth.mechanism.is_empty() {
+                        self.context.auth.mechanism = auth.mechanism;
+                    }
+                    if auth.user.is_some() {
+                        self.context.auth.user = auth.user.clone();
+                        self.context.auth.domain = auth.domain.clone();
+                        self.context.auth.workstation = auth.workstation.clone();
+                    }
+                }
+                _ => (),
+            }
+            let smb = Smb {
+                message_id: header.message_id,
+                command: header.command,
+                context: self.snapshot(header.tree_id),
+                request: Some(msg),
+                response: None,
+            };
+            return ParseResult::Continue(self.insert(smb));
+        }
+
+        if header.status == STATUS_PENDING {
+            // Interim response, the final response follows
+            return ParseResult::Skipped;
+        }
+        log::debug!("SMB2 {} response", command_name(header.command));
+        match &msg.body {
+            SmbBody::NegotiateResponse {
+                dialect,
+                security_mode,
+                cipher,
+                ..
+            } => {
+                self.context.dialect = *dialect;
+                self.context.cipher = *cipher;
+                self.context.signing_required |= security_mode & 0x02 != 0;
+            }
+            SmbBody::SessionSetupResponse { session_flags } => {
+                self.context.encryption |= session_flags & SMB2_SESSION_FLAG_ENCRYPT_DATA != 0;
+            }
+            _ => (),
+        }
+
+        let session_id = self.sessions.iter().find_map(|(session_id, smb)| {
+            (smb.message_id == header.message_id
+                && smb.command == header.command
+                && smb.response.is_none())
+            .then_some(*session_id)
+        });
+        if let SmbBody::TreeConnectResponse { share_flags, .. } = &msg.body {
+            let path = session_id
+                .and_then(|id| self.sessions.get(&id))
+                .and_then(|smb| match smb.request.as_ref().map(|r| &r.body) {
+                    Some(SmbBody::TreeConnectRequest { path }) => Some(path.clone()),
+                    _ => None,
+                });
+            if let Some(path) = path {
+                let encrypted = share_flags & SMB2_SHAREFLAG_ENCRYPT_DATA != 0;
+                self.trees.insert(header.tree_id, (path, encrypted));
+            }
+        }
+        let context = self.snapshot(header.tree_id);
+
+        let session_id = match session_id.and_then(|id| self.sessions.get_mut(&id).map(|s| (id, s)))
+        {
+            Some((session_id, smb)) => {
+                smb.context = context;
+                smb.response = Some(msg);
+                session_id
+            }
+            None => {
+                let smb = Smb {
+                    message_id: header.message_id,
+                    command: header.command,
+                    request: None,
+                    response: Some(msg),
+                    context,
+                };
+                self.insert(smb)
+            }
+        };
+        self.completed += 1;
+        if self.completed >= MAX_TRANSACTIONS {
+            return ParseResult::HeadersDone(session_id);
+        }
+        ParseResult::Continue(session_id)
+    }
+
+    /// Returns the current connection state as seen by a message on tree `tree_id`.
+    fn snapshot(&self, tree_id: u32) -> SmbContext {
+        let mut context = self.context.clone();
+        if let Some((path, encrypted)) = self.trees.get(&tree_id) {
+            context.share = Some(path.clone());
+            context.encryption |= encrypted;
+        }
+        context
+    }
+
+    fn insert(&mut self, smb: Smb) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, smb);
+        session_id
+    }
+}
+
+/// Returns `true` if transactions of `command` are recorded.
+fn is_tracked(command: u16) -> bool {
+    matches!(
+        command,
+        SMB2_NEGOTIATE | SMB2_SESSION_SETUP | SMB2_TREE_CONNECT | SMB2_CREATE
+    )
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{tcp, udp, Flow};
+
+    const SMB2_CLOSE: u16 = 0x0006;
+    const SMB2_READ: u16 = 0x0008;
+    const SESSION_ID: u64 = 0x0000_4400_0000_0021;
+    const TREE_ID: u32 = 5;
+
+    /// Body of a Windows 11 NEGOTIATE request offering SMB 2.0.2 to 3.1.1, with preauthentication
+    /// integrity and encryption capabilities (AES-128-GCM, AES-128-CCM) negotiate contexts.
+    const NEGOTIATE_REQUEST: &[u8] = b"\
+        \x24\x00\x05\x00\x01\x00\x00\x00\x7f\x00\x00\x00\x3f\x25\x04\xe0\
+        \x4f\x89\x41\xd3\x9a\x0c\x03\x05\xe8\x2c\x33\x01\x70\x00\x00\x00\
+        \x02\x00\x00\x00\x02\x02\x10\x02\x00\x03\x02\x03\x11\x03\x00\x00\
+        \x01\x00\x26\x00\x00\x00\x00\x00\x01\x00\x20\x00\x01\x00\x40\x41\
+        \x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x4f\x50\x51\
+        \x52\x53\x54\x55\x56\x57\x58\x59\x5a\x5b\x5c\x5d\x5e\x5f\x00\x00\
+        \x02\x00\x06\x00\x00\x00\x00\x00\x02\x00\x02\x00\x01\x00";
+
+    /// Body of a NEGOTIATE response selecting SMB 3.1.1, AES-128-GCM, and AES-GMAC signing.
+    const NEGOTIATE_RESPONSE: &[u8] = b"\
+        \x41\x00\x03\x00\x11\x03\x03\x00\xa1\xb2\xc3\xd4\xe5\xf6\x07\x18\
+        \x29\x3a\x4b\x5c\x6d\x7e\x8f\x90\x2f\x00\x00\x00\x00\x00\x80\x00\
+        \x00\x00\x80\x00\x00\x00\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x80\x00\x08\x00\x88\x00\x00\x00\
+        \x60\x06\x06\x04\x2b\x06\x01\x05\x01\x00\x26\x00\x00\x00\x00\x00\
+        \x01\x00\x20\x00\x01\x00\x80\x81\x82\x83\x84\x85\x86\x87\x88\x89\
+        \x8a\x8b\x8c\x8d\x8e\x8f\x90\x91\x92\x93\x94\x95\x96\x97\x98\x99\
+        \x9a\x9b\x9c\x9d\x9e\x9f\x00\x00\x02\x00\x04\x00\x00\x00\x00\x00\
+        \x01\x00\x02\x00\x00\x00\x00\x00\x08\x00\x04\x00\x00\x00\x00\x00\
+        \x01\x00\x02\x00";
+
+    /// Body of a session setup request with an NTLMSSP NEGOTIATE token.
+    const SESSION_SETUP_NTLM_NEGOTIATE: &[u8] = b"\
+        \x19\x00\x00\x01\x01\x00\x00\x00\x00\x00\x00\x00\x58\x00\x42\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x60\x40\x06\x06\x2b\x06\x01\x05\
+        \x05\x02\xa0\x36\x30\x34\xa0\x0e\x30\x0c\x06\x0a\x2b\x06\x01\x04\
+        \x01\x82\x37\x02\x02\x0a\xa2\x22\x04\x20\x4e\x54\x4c\x4d\x53\x53\
+        \x50\x00\x01\x00\x00\x00\x97\x82\x08\xe2\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+    /// Body of a session setup request with an NTLMSSP AUTHENTICATE token from `CORP\\alice`
+    /// on `WS01`.
+    const SESSION_SETUP_NTLM_AUTHENTICATE: &[u8] = b"\
+        \x19\x00\x00\x01\x01\x00\x00\x00\x00\x00\x00\x00\x58\x00\xae\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\xa1\x81\xab\x30\x81\xa8\xa2\x81\
+        \xa5\x04\x81\xa2\x4e\x54\x4c\x4d\x53\x53\x50\x00\x03\x00\x00\x00\
+        \x18\x00\x18\x00\x5a\x00\x00\x00\x30\x00\x30\x00\x72\x00\x00\x00\
+        \x08\x00\x08\x00\x40\x00\x00\x00\x0a\x00\x0a\x00\x48\x00\x00\x00\
+        \x08\x00\x08\x00\x52\x00\x00\x00\x00\x00\x00\x00\xa2\x00\x00\x00\
+        \x15\x82\x88\xe2\x43\x00\x4f\x00\x52\x00\x50\x00\x61\x00\x6c\x00\
+        \x69\x00\x63\x00\x65\x00\x57\x00\x53\x00\x30\x00\x31\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\
+        \x1a\x1b\x1c\x1d\x1e\x1f\x20\x21\x22\x23\x24\x25\x26\x27\x28\x29\
+        \x2a\x2b\x2c\x2d\x2e\x2f\x30\x31\x32\x33\x34\x35\x36\x37\x38\x39\
+        \x3a\x3b\x3c\x3d\x3e\x3f";
+
+    /// Body of a session setup request with a SPNEGO token offering Kerberos (AP-REQ elided).
+    const SESSION_SETUP_KERBEROS: &[u8] = b"\
+        \x19\x00\x00\x01\x01\x00\x00\x00\x00\x00\x00\x00\x58\x00\x30\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x60\x2e\x06\x06\x2b\x06\x01\x05\
+        \x05\x02\xa0\x24\x30\x22\xa0\x18\x30\x16\x06\x09\x2a\x86\x48\x82\
+        \xf7\x12\x01\x02\x02\x06\x09\x2a\x86\x48\x86\xf7\x12\x01\x02\x02\
+        \xa2\x06\x04\x04\x60\x82\x00\x00";
+
+    /// Body of a tree connect request to `\\\\fs01\\projects`.
+    const TREE_CONNECT_REQUEST: &[u8] = b"\
+        \x09\x00\x00\x00\x48\x00\x1e\x00\x5c\x00\x5c\x00\x66\x00\x73\x00\
+        \x30\x00\x31\x00\x5c\x00\x70\x00\x72\x00\x6f\x00\x6a\x00\x65\x00\
+        \x63\x00\x74\x00\x73\x00";
+
+    /// Body of a tree connect response for a disk share.
+    const TREE_CONNECT_RESPONSE: &[u8] = b"\
+        \x10\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\xff\x01\x1f\x00";
+
+    /// Body of a create request opening `reports\\q3.xlsx`.
+    const CREATE_REQUEST: &[u8] = b"\
+        \x39\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x89\x00\x12\x00\x80\x00\x00\x00\
+        \x07\x00\x00\x00\x01\x00\x00\x00\x40\x00\x00\x00\x78\x00\x1e\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x72\x00\x65\x00\x70\x00\x6f\x00\
+        \x72\x00\x74\x00\x73\x00\x5c\x00\x71\x00\x33\x00\x2e\x00\x78\x00\
+        \x6c\x00\x73\x00\x78\x00";
+
+    /// Body of a create response for an opened file.
+    const CREATE_RESPONSE: &[u8] = b"\
+        \x59\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\
+        \x01\x00\x00\x00\x00\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00";
+
+    /// Returns an SMB2 message with a synchronous header.
+    fn message(command: u16, response: bool, status: u32, message_id: u64, body: &[u8]) -> Vec<u8> {
+        let mut msg = SMB2_MAGIC.to_vec();
+        msg.extend_from_slice(&64u16.to_le_bytes());
+        msg.extend_from_slice(&1u16.to_le_bytes());
+        msg.extend_from_slice(&status.to_le_bytes());
+        msg.extend_from_slice(&command.to_le_bytes());
+        msg.extend_from_slice(&1u16.to_le_bytes());
+        msg.extend_from_slice(&(response as u32).to_le_bytes());
+        msg.extend_from_slice(&0u32.to_le_bytes());
+        msg.extend_from_slice(&message_id.to_le_bytes());
+        msg.extend_from_slice(&0xfeffu32.to_le_bytes());
+        let tree_id = match command {
+            SMB2_NEGOTIATE | SMB2_SESSION_SETUP => 0,
+            _ => TREE_ID,
+        };
+        msg.extend_from_slice(&tree_id.to_le_bytes());
+        let session_id = match command {
+            SMB2_NEGOTIATE => 0,
+            _ => SESSION_ID,
+        };
+        msg.extend_from_slice(&session_id.to_le_bytes());
+        msg.extend_from_slice(&[0; 16]);
+        msg.extend_from_slice(body);
+        msg
+    }
+
+    /// Returns a NetBIOS session message with `msgs` compounded.
+    fn nbss(msgs: &[Vec<u8>]) -> Vec<u8> {
+        let mut chain = vec![];
+        for (i, msg) in msgs.iter().enumerate() {
+            let mut msg = msg.clone();
+            if i + 1 < msgs.len() {
+                msg.resize(msg.len().div_ceil(8) * 8, 0);
+                let next = msg.len() as u32;
+                msg[20..24].copy_from_slice(&next.to_le_bytes());
+            }
+            chain.extend_from_slice(&msg);
+        }
+        let mut record = (chain.len() as u32).to_be_bytes().to_vec();
+        record[0] = NBSS_SESSION_MESSAGE;
+        record.extend_from_slice(&chain);
+        record
+    }
+
+    fn request(command: u16, message_id: u64, body: &[u8]) -> Vec<u8> {
+        nbss(&[message(command, false, 0, message_id, body)])
+    }
+
+    fn response(command: u16, status: u32, message_id: u64, body: &[u8]) -> Vec<u8> {
+        nbss(&[message(command, true, status, message_id, body)])
+    }
+
+    const FLOW: Flow = Flow::tcp(445);
+
+    /// Runs negotiate and NTLMSSP session setup on `parser`.
+    fn login(parser: &mut SmbParser) {
+        let negotiate = request(SMB2_NEGOTIATE, 0, NEGOTIATE_REQUEST);
+        assert_eq!(
+            FLOW.parse(parser, &negotiate, true),
+            ParseResult::Continue(0)
+        );
+        let negotiate = response(SMB2_NEGOTIATE, 0, 0, NEGOTIATE_RESPONSE);
+        assert_eq!(
+            FLOW.parse(parser, &negotiate, false),
+            ParseResult::Continue(0)
+        );
+        let setup = request(SMB2_SESSION_SETUP, 1, SESSION_SETUP_NTLM_NEGOTIATE);
+        assert_eq!(FLOW.parse(parser, &setup, true), ParseResult::Continue(1));
+        let challenge = response(
+            SMB2_SESSION_SETUP,
+            STATUS_MORE_PROCESSING_REQUIRED,
+            1,
+            b"\x09\x00\x00\x00\x48\x00\x00\x00",
+        );
+        assert_eq!(
+            FLOW.parse(parser, &challenge, false),
+            ParseResult::Continue(1)
+        );
+        let setup = request(SMB2_SESSION_SETUP, 2, SESSION_SETUP_NTLM_AUTHENTICATE);
+        assert_eq!(FLOW.parse(parser, &setup, true), ParseResult::Continue(2));
+        let setup = response(
+            SMB2_SESSION_SETUP,
+            0,
+            2,
+            b"\x09\x00\x00\x00\x48\x00\x00\x00",
+        );
+        assert_eq!(FLOW.parse(parser, &setup, false), ParseResult::Continue(2));
+    }
+
+    #[test]
+    fn core_smb_probe() {
+        let parser = SmbParser::default();
+        let negotiate = request(SMB2_NEGOTIATE, 0, NEGOTIATE_REQUEST);
+        assert_eq!(FLOW.probe(&parser, &negotiate, true), ProbeResult::Certain);
+        // SMB1 multi-protocol negotiate
+        let smb1 = b"\x00\x00\x00\x2f\xffSMB\x72\x00\x00\x00\x00\x18\x43\xc8";
+        assert_eq!(FLOW.probe(&parser, smb1, true), ProbeResult::Certain);
+        let session_request = b"\x81\x00\x00\x44\x20\x45\x4e\x45\x42\x46\x44\x46\x45";
+        assert_eq!(
+            parser.probe(&tcp(session_request, 139, true)),
+            ProbeResult::Unsure
+        );
+        assert_eq!(
+            FLOW.probe(&parser, session_request, true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            parser.probe(&udp(&negotiate, 445, true)),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_smb_transactions() {
+        let mut parser = SmbParser::default();
+        login(&mut parser);
+        let tree = request(SMB2_TREE_CONNECT, 3, TREE_CONNECT_REQUEST);
+        assert_eq!(
+            FLOW.parse(&mut parser, &tree, true),
+            ParseResult::Continue(3)
+        );
+        let tree = response(SMB2_TREE_CONNECT, 0, 3, TREE_CONNECT_RESPONSE);
+        assert_eq!(
+            FLOW.parse(&mut parser, &tree, false),
+            ParseResult::Continue(3)
+        );
+        let create = request(SMB2_CREATE, 4, CREATE_REQUEST);
+        assert_eq!(
+            FLOW.parse(&mut parser, &create, true),
+            ParseResult::Continue(4)
+        );
+        // Interim response of an asynchronous create
+        let pending = response(SMB2_CREATE, STATUS_PENDING, 4, b"\x09\x00\x00\x00");
+        assert_eq!(
+            FLOW.parse(&mut parser, &pending, false),
+            ParseResult::Skipped
+        );
+        let create = response(SMB2_CREATE, 0, 4, CREATE_RESPONSE);
+        assert_eq!(
+            FLOW.parse(&mut parser, &create, false),
+            ParseResult::Continue(4)
+        );
+
+        let negotiate = &parser.sessions[&0];
+        assert_eq!(negotiate.command(), "negotiate");
+        assert_eq!(negotiate.dialect(), "3.1.1");
+        assert_eq!(negotiate.client_dialects(), "2.0.2,2.1,3.0,3.0.2,3.1.1");
+        assert_eq!(negotiate.cipher(), "AES-128-GCM");
+        assert!(negotiate.signing_required());
+        match &negotiate.request.as_ref().unwrap().body {
+            SmbBody::NegotiateRequest {
+                client_guid,
+                ciphers,
+                ..
+            } => {
+                assert_eq!(client_guid, "e004253f-894f-d341-9a0c-0305e82c3301");
+                assert_eq!(ciphers, &[0x0002, 0x0001]);
+            }
+            _ => panic!("Expected negotiate request"),
+        }
+        match &negotiate.response.as_ref().unwrap().body {
+            SmbBody::NegotiateResponse {
+                signing_algorithm, ..
+            } => assert_eq!(*signing_algorithm, Some(0x0002)),
+            _ => panic!("Expected negotiate response"),
+        }
+
+        let challenge = &parser.sessions[&1];
+        assert_eq!(challenge.status(), STATUS_MORE_PROCESSING_REQUIRED);
+        assert_eq!(challenge.auth_mechanism(), "ntlmssp");
+        assert_eq!(challenge.user(), "");
+
+        let setup = &parser.sessions[&2];
+        assert_eq!(setup.status(), 0);
+        assert_eq!(setup.session_id(), SESSION_ID);
+        assert_eq!(setup.user(), "alice");
+        assert_eq!(setup.domain(), "CORP");
+        assert_eq!(setup.workstation(), "WS01");
+
+        let tree = &parser.sessions[&3];
+        assert_eq!(tree.command(), "tree_connect");
+        assert_eq!(tree.share_name(), r"\\fs01\projects");
+        assert_eq!(tree.share_type(), 1);
+        assert!(!tree.encrypted());
+
+        let create = &parser.sessions[&4];
+        assert_eq!(create.command(), "create");
+        assert_eq!(create.file_name(), r"reports\q3.xlsx");
+        assert_eq!(create.share_name(), r"\\fs01\projects");
+        assert_eq!(create.user(), "alice");
+        assert_eq!(create.dialect(), "3.1.1");
+        match &create.response.as_ref().unwrap().body {
+            SmbBody::CreateResponse { create_action } => assert_eq!(*create_action, 1),
+            _ => panic!("Expected create response"),
+        }
+        assert_eq!(parser.sessions.len(), 5);
+    }
+
+    #[test]
+    fn core_smb_kerberos() {
+        let mut parser = SmbParser::default();
+        let setup = request(SMB2_SESSION_SETUP, 1, SESSION_SETUP_KERBEROS);
+        assert_eq!(
+            FLOW.parse(&mut parser, &setup, true),
+            ParseResult::Continue(0)
+        );
+        let smb = &parser.sessions[&0];
+        assert_eq!(smb.auth_mechanism(), "kerberos");
+        assert_eq!(smb.user(), "");
+    }
+
+    #[test]
+    fn core_smb_split() {
+        let mut parser = SmbParser::default();
+        let negotiate = request(SMB2_NEGOTIATE, 0, NEGOTIATE_REQUEST);
+        // Split within the NetBIOS header, then within the negotiate contexts
+        assert_eq!(
+            FLOW.parse(&mut parser, &negotiate[..2], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &negotiate[2..150], true),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &negotiate[150..], true),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.client_buffer.is_empty());
+        assert_eq!(
+            parser.sessions[&0].client_dialects(),
+            "2.0.2,2.1,3.0,3.0.2,3.1.1"
+        );
+
+        // The end of a message and the start of the next in the same segment
+        let negotiate = response(SMB2_NEGOTIATE, 0, 0, NEGOTIATE_RESPONSE);
+        let setup = response(
+            SMB2_SESSION_SETUP,
+            0,
+            1,
+            b"\x09\x00\x00\x00\x48\x00\x00\x00",
+        );
+        let data = [negotiate, setup].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &data[..100], false),
+            ParseResult::Skipped
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &data[100..250], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.sessions[&0].dialect(), "3.1.1");
+        assert_eq!(
+            FLOW.parse(&mut parser, &data[250..], false),
+            ParseResult::Continue(1)
+        );
+    }
+
+    #[test]
+    fn core_smb_compound() {
+        let mut parser = SmbParser::default();
+        // Create and close of the opened file in one record
+        let chain = nbss(&[
+            message(SMB2_CREATE, false, 0, 4, CREATE_REQUEST),
+            message(SMB2_CLOSE, false, 0, 5, b"\x18\x00\x00\x00\x00\x00\x00\x00"),
+        ]);
+        assert_eq!(
+            FLOW.parse(&mut parser, &chain, true),
+            ParseResult::Continue(0)
+        );
+        let chain = nbss(&[
+            message(SMB2_CREATE, true, 0, 4, CREATE_RESPONSE),
+            message(SMB2_CLOSE, true, 0, 5, b"\x3c\x00\x00\x00"),
+        ]);
+        assert_eq!(
+            FLOW.parse(&mut parser, &chain, false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.sessions.len(), 1);
+        assert_eq!(parser.sessions[&0].file_name(), r"reports\q3.xlsx");
+    }
+
+    #[test]
+    fn core_smb_truncated() {
+        let mut parser = SmbParser::default();
+        // Record too short for an SMB2 header
+        let record = b"\x00\x00\x00\x10\xfeSMB\x40\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        assert_eq!(FLOW.parse(&mut parser, record, true), ParseResult::Skipped);
+        assert!(parser.sessions.is_empty());
+        assert!(parser.client_buffer.is_empty());
+
+        // Negotiate request cut off in its body is buffered and not recorded
+        let negotiate = request(SMB2_NEGOTIATE, 0, NEGOTIATE_REQUEST);
+        assert_eq!(
+            FLOW.parse(&mut parser, &negotiate[..90], true),
+            ParseResult::Skipped
+        );
+        assert!(parser.sessions.is_empty());
+        assert_eq!(parser.client_buffer.len(), 90);
+
+        // Read response larger than the segment is skipped without buffering
+        let mut read = message(SMB2_READ, true, 0, 6, b"\x11\x00\x50\x00\x00\x00\x01\x00");
+        read.resize(HDR_LEN + 0x10050, 0xaa);
+        let read = nbss(&[read]);
+        assert_eq!(
+            FLOW.parse(&mut parser, &read[..1448], false),
+            ParseResult::Skipped
+        );
+        assert!(parser.server_buffer.is_empty());
+        assert_eq!(parser.server_skip, read.len() - 1448);
+        // The rest of the read, then a tracked response in the same segment
+        let tree = response(SMB2_TREE_CONNECT, 0, 3, TREE_CONNECT_RESPONSE);
+        let data = [&read[1448..], &tree].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &data, false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.server_skip, 0);
+        assert_eq!(parser.sessions[&0].share_type(), 1);
+    }
+}
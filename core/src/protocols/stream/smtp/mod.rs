@@ -0,0 +1,125 @@
+//! SMTP session parsing.
+//!
+//! ## Remarks
+//! The parser follows the plaintext portion of an SMTP session: the server greeting, the
+//! EHLO/HELO exchange and advertised service extensions, authentication, and the mail envelope
+//! (MAIL FROM and RCPT TO). The session headers are considered done once the envelope is
+//! complete (the server accepts DATA), the client quits, or the server accepts STARTTLS. In the
+//! latter case, the remainder of the stream is handed to the TLS parser and the TLS handshake is
+//! parsed as a second session in the same connection. Message contents are not parsed.
+//!
+//! The connection is identified as SMTP on discovery, so filters on `tls` (e.g., `tls.sni ~
+//! 'example'`) do not match it. The TLS parser is only kept active after the SMTP session
+//! matches for subscriptions that filter on `smtp` and still require parsing of the L7 payload,
+//! i.e., whose datatypes are updated with the reassembled payload (`L7InPayload(true)`). Their
+//! state transition at the end of the SMTP headers keeps `Actions::Parse` set, which is not
+//! cleared while the STARTTLS handover is pending, and the TLS session is then delivered to the
+//! session filter at the end of the TLS handshake. Subscriptions that only require the SMTP
+//! session stop parsing once it is delivered.
+
+pub mod parser;
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Parsed SMTP session contents.
+#[derive(Debug, Default, Serialize)]
+pub struct Smtp {
+    /// Server greeting (text of the initial 220 reply).
+    pub banner: Option<String>,
+    /// Domain or address literal sent in the client's EHLO or HELO command.
+    pub helo_domain: Option<String>,
+    /// `true` if the client greeted with EHLO (Extended SMTP).
+    pub ehlo: bool,
+    /// Service extensions advertised by the server in its EHLO reply (e.g., `STARTTLS`).
+    pub extensions: Vec<String>,
+    /// SASL mechanism requested by the client in an AUTH command.
+    pub auth_mechanism: Option<String>,
+    /// `true` if the server accepted the client's authentication.
+    pub authenticated: bool,
+    /// Reverse-path in the MAIL FROM command.
+    pub mail_from: Option<String>,
+    /// Forward-paths in RCPT TO commands.
+    pub rcpt_to: Vec<String>,
+    /// `true` if the server accepted STARTTLS.
+    pub starttls: bool,
+    /// Commands sent by the client, in order.
+    pub commands: Vec<SmtpCommand>,
+    /// Replies sent by the server, in order (including the greeting).
+    pub replies: Vec<SmtpReply>,
+
+    /// Verbs of commands awaiting a reply (commands may be pipelined).
+    #[serde(skip_serializing)]
+    pub(crate) pending: VecDeque<String>,
+    /// Lines of a multi-line reply received so far.
+    #[serde(skip_serializing)]
+    pub(crate) partial_reply: Option<SmtpReply>,
+    /// `true` while a SASL exchange is in progress (client lines are not commands).
+    #[serde(skip_serializing)]
+    pub(crate) in_auth: bool,
+}
+
+/// An SMTP command.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SmtpCommand {
+    /// Command verb, uppercased (e.g., `MAIL`).
+    pub verb: String,
+    /// Command arguments. Credentials in AUTH commands are not recorded.
+    pub args: String,
+}
+
+/// An SMTP reply, possibly spanning multiple lines.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SmtpReply {
+    /// Three-digit reply code.
+    pub code: u16,
+    /// Text of each reply line, without the reply code.
+    pub lines: Vec<String>,
+}
+
+impl Smtp {
+    /// Returns the server greeting, or `""` if it was not observed.
+    pub fn banner(&self) -> &str {
+        self.banner.as_deref().unwrap_or("")
+    }
+
+    /// Returns the domain sent in the client's EHLO or HELO command.
+    pub fn helo_domain(&self) -> &str {
+        self.helo_domain.as_deref().unwrap_or("")
+    }
+
+    /// Returns the service extensions advertised by the server, separated by spaces.
+    pub fn extensions(&self) -> String {
+        self.extensions
+            .iter()
+            .map(|ext| ext.split(' ').next().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the SASL mechanism requested by the client, or `""` if the client did not
+    /// authenticate.
+    pub fn auth_mechanism(&self) -> &str {
+        self.auth_mechanism.as_deref().unwrap_or("")
+    }
+
+    /// Returns the reverse-path in the MAIL FROM command.
+    pub fn mail_from(&self) -> &str {
+        self.mail_from.as_deref().unwrap_or("")
+    }
+
+    /// Returns the forward-paths in RCPT TO commands, separated by commas.
+    pub fn rcpt_to(&self) -> String {
+        self.rcpt_to.join(",")
+    }
+
+    /// Returns the number of recipients.
+    pub fn rcpt_count(&self) -> usize {
+        self.rcpt_to.len()
+    }
+
+    /// Returns the code of the most recent server reply, or `0` if none was observed.
+    pub fn reply_code(&self) -> u16 {
+        self.replies.last().map_or(0, |reply| reply.code)
+    }
+}
@@ -0,0 +1,377 @@
+//! SMTP session parser.
+//!
+//! Commands and replies are reassembled into lines and matched in order, which accommodates
+//! command pipelining ([RFC 2920](https://datatracker.ietf.org/doc/html/rfc2920)). Only the
+//! plaintext portion of the session is parsed.
+
+use super::{Smtp, SmtpCommand, SmtpReply};
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::line::{split_verb, LineBuffer};
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+/// Maximum number of client commands parsed before the session is considered done.
+const MAX_COMMANDS: usize = 64;
+
+/// Parses a single SMTP session per connection.
+#[derive(Debug)]
+pub struct SmtpParser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<Smtp>,
+    client_lines: LineBuffer,
+    server_lines: LineBuffer,
+    /// `true` if the server accepted STARTTLS and the stream continues as TLS.
+    starttls: bool,
+}
+
+impl Default for SmtpParser {
+    fn default() -> Self {
+        SmtpParser {
+            sessions: vec![Smtp::default()],
+            client_lines: LineBuffer::default(),
+            server_lines: LineBuffer::default(),
+            starttls: false,
+        }
+    }
+}
+
+impl SmtpParser {
+    /// Returns `true` if the remainder of the stream should be parsed as TLS.
+    pub(crate) fn starttls(&self) -> bool {
+        self.starttls
+    }
+
+    /// Process data segments from client to server
+    fn process_ctos(&mut self, data: &[u8]) -> ParseResult {
+        let smtp = &mut self.sessions[0];
+        let lines = self.client_lines.lines(data);
+        if lines.is_empty() {
+            return ParseResult::Skipped;
+        }
+        for line in lines {
+            if smtp.in_auth {
+                // SASL response (credentials), not a command
+                continue;
+            }
+            let (verb, args) = split_verb(&line);
+            let verb = verb.to_ascii_uppercase();
+            let mut args = args.to_string();
+            match verb.as_str() {
+                "EHLO" | "HELO" => {
+                    smtp.helo_domain = Some(args.clone());
+                    smtp.ehlo = verb == "EHLO";
+                }
+                "MAIL" => {
+                    if let Some(path) = strip_param(&args, "FROM:") {
+                        smtp.mail_from = Some(path);
+                    }
+                }
+                "RCPT" => {
+                    if let Some(path) = strip_param(&args, "TO:") {
+                        smtp.rcpt_to.push(path);
+                    }
+                }
+                "AUTH" => {
+                    let mechanism = split_verb(&args).0.to_ascii_uppercase();
+                    smtp.auth_mechanism = Some(mechanism.clone());
+                    smtp.in_auth = true;
+                    args = mechanism;
+                }
+                _ => (),
+            }
+            smtp.pending.push_back(verb.clone());
+            smtp.commands.push(SmtpCommand { verb, args });
+        }
+        if smtp.commands.len() >= MAX_COMMANDS {
+            return ParseResult::HeadersDone(0);
+        }
+        ParseResult::Continue(0)
+    }
+
+    /// Process data segments from server to client
+    fn process_stoc(&mut self, data: &[u8]) -> ParseResult {
+        let smtp = &mut self.sessions[0];
+        let lines = self.server_lines.lines(data);
+        if lines.is_empty() {
+            return ParseResult::Skipped;
+        }
+        for line in lines {
+            let code = match line.get(..3).and_then(|c| c.parse::<u16>().ok()) {
+                Some(code) => code,
+                None => {
+                    log::debug!("Invalid SMTP reply line: {}", line);
+                    continue;
+                }
+            };
+            let is_last = line.as_bytes().get(3) != Some(&b'-');
+            let text = line.get(4..).unwrap_or("").to_string();
+            let reply = smtp.partial_reply.get_or_insert_with(|| SmtpReply {
+                code,
+                lines: vec![],
+            });
+            reply.lines.push(text);
+            if !is_last {
+                continue;
+            }
+            let reply = smtp.partial_reply.take().unwrap();
+
+            if smtp.banner.is_none() && smtp.pending.is_empty() && smtp.replies.is_empty() {
+                smtp.banner = Some(reply.lines.join(" "));
+                smtp.replies.push(reply);
+                continue;
+            }
+            // 334 is an intermediate reply in a SASL exchange
+            let verb = if reply.code == 334 {
+                None
+            } else {
+                smtp.pending.pop_front()
+            };
+            let code = reply.code;
+            match (verb.as_deref(), code) {
+                (Some("EHLO"), 250) => {
+                    // First line is the server's domain and greeting
+                    smtp.extensions = reply.lines.iter().skip(1).cloned().collect();
+                }
+                (Some("AUTH"), _) => {
+                    smtp.in_auth = false;
+                    smtp.authenticated = code == 235;
+                }
+                _ => (),
+            }
+            smtp.replies.push(reply);
+            match (verb.as_deref(), code) {
+                (Some("STARTTLS"), 220) => {
+                    smtp.starttls = true;
+                    self.starttls = true;
+                    return ParseResult::HeadersDone(0);
+                }
+                (Some("DATA"), 354) | (Some("QUIT"), _) => {
+                    return ParseResult::HeadersDone(0);
+                }
+                _ => (),
+            }
+        }
+        ParseResult::Continue(0)
+    }
+}
+
+impl ConnParsable for SmtpParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 || self.sessions.is_empty() {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.dir {
+                self.process_ctos(data)
+            } else {
+                self.process_stoc(data)
+            }
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() < 4 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if pdu.dir {
+                // Capture may have started after the server greeting
+                match &data[..4].to_ascii_uppercase()[..] {
+                    b"EHLO" | b"HELO" => ProbeResult::Certain,
+                    _ => ProbeResult::NotForUs,
+                }
+            } else if data.starts_with(b"220 ") || data.starts_with(b"220-") {
+                // FTP servers also greet with 220
+                if memchr::memmem::find(&data.to_ascii_uppercase(), b"SMTP").is_some() {
+                    ProbeResult::Certain
+                } else {
+                    ProbeResult::Unsure
+                }
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|smtp| Session {
+            data: SessionData::Smtp(Box::new(smtp)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|smtp| Session {
+                data: SessionData::Smtp(Box::new(smtp)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        if self.starttls {
+            // TLS session expected next
+            ParsingState::Probing
+        } else {
+            ParsingState::Stop
+        }
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+/// Extracts the path following `prefix` (e.g., `FROM:`) in MAIL or RCPT arguments, without angle
+/// brackets or ESMTP parameters.
+fn strip_param(args: &str, prefix: &str) -> Option<String> {
+    match args.get(..prefix.len()) {
+        Some(verb) if verb.eq_ignore_ascii_case(prefix) => (),
+        _ => return None,
+    }
+    let path = args[prefix.len()..].trim_start();
+    let path = match path.strip_prefix('<') {
+        Some(path) => path.split('>').next().unwrap_or_default(),
+        None => path.split(' ').next().unwrap_or_default(),
+    };
+    Some(path.to_string())
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    const BANNER: &[u8] = b"220 mx.example.com ESMTP Postfix (Debian/GNU)\r\n";
+    const EHLO_REPLY: &[u8] = b"250-mx.example.com\r\n250-PIPELINING\r\n250-SIZE 10240000\r\n\
+        250-STARTTLS\r\n250-AUTH PLAIN LOGIN\r\n250 8BITMIME\r\n";
+
+    const FLOW: Flow = Flow::tcp(25);
+
+    #[test]
+    fn core_smtp_probe() {
+        let parser = SmtpParser::default();
+        assert_eq!(FLOW.probe(&parser, BANNER, false), ProbeResult::Certain);
+        assert_eq!(
+            FLOW.probe(&parser, b"ehlo client.example.org\r\n", true),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"220 (vsFTPd 3.0.3)\r\n", false),
+            ProbeResult::Unsure
+        );
+        assert_eq!(FLOW.probe(&parser, b"22", false), ProbeResult::Unsure);
+        assert_eq!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"+OK POP3 ready\r\n", false),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_smtp_parse_split() {
+        let mut parser = SmtpParser::default();
+        FLOW.parse(&mut parser, BANNER, false);
+        assert_eq!(
+            FLOW.parse(&mut parser, b"EHLO cli", true),
+            ParseResult::Skipped
+        );
+        FLOW.parse(&mut parser, b"ent.example.org\r\n", true);
+        FLOW.parse(&mut parser, &EHLO_REPLY[..40], false);
+        FLOW.parse(&mut parser, &EHLO_REPLY[40..], false);
+        // Pipelined commands
+        FLOW.parse(
+            &mut parser,
+            b"MAIL FROM:<alice@example.org> SIZE=1024\r\nRCPT TO:<bob@example.com>\r\n\
+            RCPT TO:carol@example.com\r\nDATA\r\n",
+            true,
+        );
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                b"250 2.1.0 Ok\r\n250 2.1.5 Ok\r\n250 2.1.5 Ok\r\n",
+                false
+            ),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(
+                &mut parser,
+                b"354 End data with <CR><LF>.<CR><LF>\r\n",
+                false
+            ),
+            ParseResult::HeadersDone(0)
+        );
+
+        let smtp = &parser.sessions[0];
+        assert_eq!(smtp.banner(), "mx.example.com ESMTP Postfix (Debian/GNU)");
+        assert_eq!(smtp.helo_domain(), "client.example.org");
+        assert_eq!(
+            smtp.extensions,
+            [
+                "PIPELINING",
+                "SIZE 10240000",
+                "STARTTLS",
+                "AUTH PLAIN LOGIN",
+                "8BITMIME"
+            ]
+        );
+        assert_eq!(smtp.mail_from(), "alice@example.org");
+        assert_eq!(smtp.rcpt_to, ["bob@example.com", "carol@example.com"]);
+        assert_eq!(smtp.reply_code(), 354);
+    }
+
+    #[test]
+    fn core_smtp_starttls() {
+        let mut parser = SmtpParser::default();
+        FLOW.parse(&mut parser, BANNER, false);
+        FLOW.parse(&mut parser, b"EHLO client.example.org\r\n", true);
+        FLOW.parse(&mut parser, EHLO_REPLY, false);
+        FLOW.parse(&mut parser, b"STARTTLS\r\n", true);
+        assert_eq!(
+            FLOW.parse(&mut parser, b"220 2.0.0 Ready to start TLS\r\n", false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.starttls());
+        assert!(matches!(
+            parser.session_parsed_state(),
+            ParsingState::Probing
+        ));
+    }
+
+    #[test]
+    fn core_smtp_truncated() {
+        let mut parser = SmtpParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &BANNER[..20], false),
+            ParseResult::Skipped
+        );
+        FLOW.parse(&mut parser, b"250-mx.exa", false);
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Smtp(smtp) => {
+                assert_eq!(smtp.banner(), "");
+                assert!(smtp.replies.is_empty());
+            }
+            _ => panic!("Expected SMTP session"),
+        }
+    }
+}
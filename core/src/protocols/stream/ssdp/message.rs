@@ -0,0 +1,117 @@
+//! SSDP message components.
+//!
+//! SSDP messages are HTTP-formatted requests and responses carried over UDP (HTTPU), as
+//! specified in the [UPnP Device Architecture](https://openconnectivity.org/upnp-specs/UPnP-arch-DeviceArchitecture-v2.0-20200417.pdf).
+
+use anyhow::{bail, Result};
+use httparse::{Request, Response, Status, EMPTY_HEADER};
+use serde::Serialize;
+
+/// Maximum number of headers parsed per message.
+const NUM_OF_HEADERS: usize = 32;
+
+/// SSDP message kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum SsdpKind {
+    /// Search request (`M-SEARCH`).
+    Search,
+    /// Advertisement (`NOTIFY`).
+    Notify,
+    /// Search response.
+    Response,
+}
+
+/// An SSDP message.
+#[derive(Clone, Debug, Serialize)]
+pub struct SsdpMessage {
+    pub kind: SsdpKind,
+    /// Response status code, if this is a response.
+    pub status_code: Option<u16>,
+    pub host: Option<String>,
+    /// `MAN` header of a search request (e.g., `"ssdp:discover"`).
+    pub man: Option<String>,
+    /// Maximum response delay in seconds (`MX`) of a search request.
+    pub mx: Option<u32>,
+    /// Search target (`ST`) of a search request or response.
+    pub st: Option<String>,
+    /// Notification type (`NT`) of an advertisement.
+    pub nt: Option<String>,
+    /// Notification subtype (`NTS`) of an advertisement (e.g., `"ssdp:alive"`).
+    pub nts: Option<String>,
+    /// Unique service name (`USN`).
+    pub usn: Option<String>,
+    /// URL of the device description (`LOCATION`).
+    pub location: Option<String>,
+    /// `SERVER` header of an advertisement or response.
+    pub server: Option<String>,
+    /// `USER-AGENT` header of a search request.
+    pub user_agent: Option<String>,
+    pub cache_control: Option<String>,
+    /// Length of the UDP payload in bytes.
+    pub length: usize,
+}
+
+impl SsdpMessage {
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let (kind, status_code) = if data.starts_with(b"HTTP/") {
+            let mut resp = Response::new(&mut headers[..]);
+            match resp.parse(data) {
+                Ok(Status::Complete(_)) => (),
+                // Messages are not split across datagrams
+                Ok(Status::Partial) => bail!("truncated message"),
+                Err(_) => bail!("error"),
+            }
+            (SsdpKind::Response, resp.code)
+        } else {
+            let mut req = Request::new(&mut headers[..]);
+            match req.parse(data) {
+                Ok(Status::Complete(_)) => (),
+                // Messages are not split across datagrams
+                Ok(Status::Partial) => bail!("truncated message"),
+                Err(_) => bail!("error"),
+            }
+            let kind = match req.method {
+                Some("M-SEARCH") => SsdpKind::Search,
+                Some("NOTIFY") => SsdpKind::Notify,
+                _ => bail!("not an SSDP method"),
+            };
+            (kind, None)
+        };
+
+        let mut msg = SsdpMessage {
+            kind,
+            status_code,
+            host: None,
+            man: None,
+            mx: None,
+            st: None,
+            nt: None,
+            nts: None,
+            usn: None,
+            location: None,
+            server: None,
+            user_agent: None,
+            cache_control: None,
+            length: data.len(),
+        };
+        for hdr in headers.iter().take_while(|hdr| !hdr.name.is_empty()) {
+            let value = String::from_utf8_lossy(hdr.value).trim().to_owned();
+            match hdr.name.to_lowercase().as_ref() {
+                "host" => msg.host = Some(value),
+                "man" => msg.man = Some(value.trim_matches('"').to_owned()),
+                "mx" => msg.mx = value.parse().ok(),
+                "st" => msg.st = Some(value),
+                "nt" => msg.nt = Some(value),
+                "nts" => msg.nts = Some(value),
+                "usn" => msg.usn = Some(value),
+                "location" => msg.location = Some(value),
+                "server" => msg.server = Some(value),
+                "user-agent" => msg.user_agent = Some(value),
+                "cache-control" => msg.cache_control = Some(value),
+                _ => (),
+            }
+        }
+        Ok(msg)
+    }
+}
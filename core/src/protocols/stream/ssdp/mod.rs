@@ -0,0 +1,133 @@
+//! SSDP transaction parsing.
+//!
+//! ## Remarks
+//! A transaction is either an `M-SEARCH` request paired with a search response, or a single
+//! `NOTIFY` advertisement. SSDP has no transaction ID, so a response is paired with an outstanding
+//! search whose search target (`ST`) matches the response, or is `ssdp:all`.
+//!
+//! Searches are usually sent to a multicast group and answered by unicast from each device, so a
+//! search and its responses are typically tracked as separate connections. Only the first
+//! response to a search is paired with it. Other responses, including those to unicast searches
+//! used in amplification attacks, are reported as response-only transactions, and searches
+//! without a response are reported when the connection terminates.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed SSDP transaction contents.
+#[derive(Clone, Debug, Serialize)]
+pub struct Ssdp {
+    /// `M-SEARCH` or `NOTIFY` request, if observed.
+    pub request: Option<SsdpMessage>,
+    /// Search response, if observed.
+    pub response: Option<SsdpMessage>,
+}
+
+impl Ssdp {
+    /// Returns the request method (`"M-SEARCH"` or `"NOTIFY"`), or `""` if no request was
+    /// observed.
+    pub fn method(&self) -> &str {
+        match self.request.as_ref().map(|m| m.kind) {
+            Some(SsdpKind::Search) => "M-SEARCH",
+            Some(SsdpKind::Notify) => "NOTIFY",
+            _ => "",
+        }
+    }
+
+    /// Returns the search target of the request, or of the response if no request was observed.
+    pub fn search_target(&self) -> &str {
+        self.request
+            .iter()
+            .chain(self.response.iter())
+            .find_map(|m| m.st.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the notification type of an advertisement, or `""` if there is none.
+    pub fn notification_type(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|m| m.nt.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the notification subtype of an advertisement (e.g., `"ssdp:alive"`), or `""` if
+    /// there is none.
+    pub fn notification_subtype(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|m| m.nts.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the device's unique service name, or `""` if there is none.
+    pub fn usn(&self) -> &str {
+        self.device_message()
+            .and_then(|m| m.usn.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the URL of the device description, or `""` if there is none.
+    pub fn location(&self) -> &str {
+        self.device_message()
+            .and_then(|m| m.location.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the device's `SERVER` header (OS, UPnP version, and product), or `""` if there is
+    /// none.
+    pub fn server(&self) -> &str {
+        self.device_message()
+            .and_then(|m| m.server.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the `USER-AGENT` header of a search request, or `""` if there is none.
+    pub fn user_agent(&self) -> &str {
+        self.request
+            .as_ref()
+            .and_then(|m| m.user_agent.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Returns the response status code, or 0 if no response was observed.
+    pub fn status_code(&self) -> u16 {
+        self.response
+            .as_ref()
+            .and_then(|m| m.status_code)
+            .unwrap_or(0)
+    }
+
+    /// Returns the search request size in bytes, or 0 if no search was observed.
+    pub fn request_bytes(&self) -> usize {
+        self.request.as_ref().map_or(0, |m| m.length)
+    }
+
+    /// Returns the response size in bytes, or 0 if no response was observed.
+    pub fn response_bytes(&self) -> usize {
+        self.response.as_ref().map_or(0, |m| m.length)
+    }
+
+    /// Returns the message sent by the device: the response to a search, or an advertisement.
+    fn device_message(&self) -> Option<&SsdpMessage> {
+        self.response
+            .as_ref()
+            .or_else(|| self.request.as_ref().filter(|m| m.kind == SsdpKind::Notify))
+    }
+
+    /// Returns `true` if `response` answers the search in this transaction.
+    fn answered_by(&self, response: &SsdpMessage) -> bool {
+        match (&self.request, &self.response) {
+            (Some(req), None) if req.kind == SsdpKind::Search => match req.st.as_deref() {
+                Some("ssdp:all") => true,
+                Some(st) => response.st.as_deref() == Some(st),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! SSDP transaction parser.
+//!
+//! Parses SSDP messages and pairs search responses with outstanding searches.
+
+use super::message::{SsdpKind, SsdpMessage};
+use super::Ssdp;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use std::collections::HashMap;
+
+#[derive(Default, Debug)]
+pub struct SsdpParser {
+    /// Maps session ID to SSDP transaction
+    sessions: HashMap<usize, Ssdp>,
+    /// Total sessions ever seen (Running session ID)
+    cnt: usize,
+}
+
+impl ConnParsable for SsdpParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.ctxt.src.port() != 1900 && pdu.ctxt.dst.port() != 1900 {
+            return ProbeResult::NotForUs;
+        }
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            if data.starts_with(b"M-SEARCH * ")
+                || data.starts_with(b"NOTIFY * ")
+                || data.starts_with(b"HTTP/1.")
+            {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, session_id: usize) -> Option<Session> {
+        self.sessions.remove(&session_id).map(|ssdp| Session {
+            data: SessionData::Ssdp(Box::new(ssdp)),
+            id: session_id,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain()
+            .map(|(session_id, ssdp)| Session {
+                data: SessionData::Ssdp(Box::new(ssdp)),
+                id: session_id,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Parsing
+    }
+
+    /// We consider SSDP to not have a "body"
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl SsdpParser {
+    pub(crate) fn process(&mut self, data: &[u8]) -> ParseResult {
+        match SsdpMessage::parse_from(data) {
+            Ok(msg) => {
+                log::debug!("SSDP {:?}", msg.kind);
+                match msg.kind {
+                    SsdpKind::Search => {
+                        let ssdp = Ssdp {
+                            request: Some(msg),
+                            response: None,
+                        };
+                        ParseResult::Continue(self.insert(ssdp))
+                    }
+                    SsdpKind::Notify => {
+                        let ssdp = Ssdp {
+                            request: Some(msg),
+                            response: None,
+                        };
+                        ParseResult::Done(self.insert(ssdp))
+                    }
+                    SsdpKind::Response => {
+                        for (session_id, ssdp) in self.sessions.iter_mut() {
+                            if ssdp.answered_by(&msg) {
+                                ssdp.response = Some(msg);
+                                return ParseResult::Done(*session_id);
+                            }
+                        }
+                        let ssdp = Ssdp {
+                            request: None,
+                            response: Some(msg),
+                        };
+                        ParseResult::Done(self.insert(ssdp))
+                    }
+                }
+            }
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+
+    fn insert(&mut self, ssdp: Ssdp) -> usize {
+        let session_id = self.cnt;
+        self.cnt += 1;
+        self.sessions.insert(session_id, ssdp);
+        session_id
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::{udp, Flow};
+
+    const SEARCH: &[u8] = b"M-SEARCH * HTTP/1.1\r\n\
+        HOST: 239.255.255.250:1900\r\n\
+        MAN: \"ssdp:discover\"\r\n\
+        MX: 1\r\n\
+        ST: urn:dial-multiscreen-org:service:dial:1\r\n\
+        USER-AGENT: Google Chrome/120.0.6099.109 Linux\r\n\r\n";
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\n\
+        CACHE-CONTROL: max-age=1800\r\n\
+        DATE: Thu, 14 Dec 2023 10:12:31 GMT\r\n\
+        EXT:\r\n\
+        LOCATION: http://192.168.1.50:8008/ssdp/device-desc.xml\r\n\
+        SERVER: Linux/3.8.13+, UPnP/1.0, Portable SDK for UPnP devices/1.6.18\r\n\
+        ST: urn:dial-multiscreen-org:service:dial:1\r\n\
+        USN: uuid:3e1cc7c3-f0e5-4d23-a1c7-5a6a3b9f2c10::urn:dial-multiscreen-org:service:dial:1\r\n\r\n";
+
+    const NOTIFY: &[u8] = b"NOTIFY * HTTP/1.1\r\n\
+        HOST: 239.255.255.250:1900\r\n\
+        CACHE-CONTROL: max-age=100\r\n\
+        LOCATION: http://192.168.1.1:49152/description.xml\r\n\
+        NT: upnp:rootdevice\r\n\
+        NTS: ssdp:alive\r\n\
+        SERVER: Linux/4.4.60, UPnP/1.0, miniupnpd/2.1\r\n\
+        USN: uuid:9f0865b3-f5da-4ad5-85b7-7404637fdf37::upnp:rootdevice\r\n\r\n";
+
+    const FLOW: Flow = Flow::udp(1900);
+
+    #[test]
+    fn core_ssdp_probe() {
+        let parser = SsdpParser::default();
+        for data in [SEARCH, RESPONSE, NOTIFY] {
+            assert!(matches!(
+                FLOW.probe(&parser, data, true),
+                ProbeResult::Certain
+            ));
+        }
+        // Not the SSDP port
+        assert!(matches!(
+            parser.probe(&udp(SEARCH, 80, true)),
+            ProbeResult::NotForUs
+        ));
+        assert!(matches!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n\r\n", true),
+            ProbeResult::NotForUs
+        ));
+    }
+
+    #[test]
+    fn core_ssdp_search() {
+        let mut parser = SsdpParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, SEARCH, true),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, NOTIFY, false),
+            ParseResult::Done(1)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, RESPONSE, false),
+            ParseResult::Done(0)
+        ));
+
+        let session = parser.remove_session(0).unwrap();
+        match &session.data {
+            SessionData::Ssdp(ssdp) => {
+                assert_eq!(ssdp.method(), "M-SEARCH");
+                assert_eq!(
+                    ssdp.search_target(),
+                    "urn:dial-multiscreen-org:service:dial:1"
+                );
+                assert_eq!(ssdp.user_agent(), "Google Chrome/120.0.6099.109 Linux");
+                assert_eq!(ssdp.status_code(), 200);
+                assert_eq!(
+                    ssdp.location(),
+                    "http://192.168.1.50:8008/ssdp/device-desc.xml"
+                );
+                assert_eq!(
+                    ssdp.request.as_ref().unwrap().man.as_deref(),
+                    Some("ssdp:discover")
+                );
+                assert_eq!(ssdp.response_bytes(), RESPONSE.len());
+            }
+            _ => panic!("Expected SSDP session"),
+        }
+        match &parser.remove_session(1).unwrap().data {
+            SessionData::Ssdp(ssdp) => {
+                assert_eq!(ssdp.method(), "NOTIFY");
+                assert_eq!(ssdp.notification_type(), "upnp:rootdevice");
+                assert_eq!(ssdp.notification_subtype(), "ssdp:alive");
+                assert_eq!(ssdp.server(), "Linux/4.4.60, UPnP/1.0, miniupnpd/2.1");
+            }
+            _ => panic!("Expected SSDP session"),
+        }
+    }
+
+    #[test]
+    fn core_ssdp_truncated() {
+        let mut parser = SsdpParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, &SEARCH[..60], true),
+            ParseResult::Skipped
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, &RESPONSE[..RESPONSE.len() - 2], false),
+            ParseResult::Skipped
+        ));
+        assert!(parser.drain_sessions().is_empty());
+    }
+}
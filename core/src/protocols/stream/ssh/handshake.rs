@@ -14,7 +14,7 @@ pub struct SshVersionExchange {
 }
 
 /// A parsed SSH Key Exchange message.
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct SshKeyExchange {
     #[serde(with = "base64")]
     pub cookie: Vec<u8>,
@@ -47,3 +47,68 @@ pub struct SshDhResponse {
 
 #[derive(Debug, Default, Serialize)]
 pub struct SshNewKeys;
+
+/// The server host key (`K_S`) sent in the key exchange reply.
+#[derive(Debug, Default, Serialize)]
+pub struct SshHostKey {
+    /// Public key algorithm name encoded in the key blob (e.g., `ssh-ed25519`).
+    pub key_type: String,
+    /// OpenSSH-style SHA256 fingerprint of the key blob (e.g., `SHA256:...`).
+    pub fingerprint: String,
+    /// Raw public key blob.
+    #[serde(with = "base64")]
+    pub raw: Vec<u8>,
+}
+
+impl SshHostKey {
+    /// Parses the server host key blob sent in a KEXDH_REPLY (or equivalent) message.
+    pub(crate) fn from_blob(blob: &[u8]) -> SshHostKey {
+        let key_type = match read_string(blob) {
+            Some((key_type, _)) => String::from_utf8_lossy(key_type).to_string(),
+            None => String::new(),
+        };
+        let digest = ring::digest::digest(&ring::digest::SHA256, blob);
+        SshHostKey {
+            key_type,
+            fingerprint: format!(
+                "SHA256:{}",
+                ::base64::encode_config(digest.as_ref(), ::base64::STANDARD_NO_PAD)
+            ),
+            raw: blob.to_vec(),
+        }
+    }
+}
+
+/// Length and direction of an encrypted packet observed after NEWKEYS.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SshEncryptedPacket {
+    /// Direction of the packet (`true` if sent by the client).
+    pub dir: bool,
+    /// Length of the encrypted segment in bytes.
+    pub len: usize,
+}
+
+/// Heuristic outcome of the user authentication phase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum AuthResult {
+    /// Not enough encrypted packets were observed to infer an outcome.
+    #[default]
+    Unknown,
+    /// The server appears to have accepted a user authentication request.
+    Success,
+    /// The server rejected all user authentication requests observed.
+    Failure,
+}
+
+/// Reads an SSH `string` (uint32 length followed by data) from the start of `data`.
+/// Returns the string contents and the remaining bytes.
+pub(crate) fn read_string(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if data.len() < 4 {
+        return None;
+    }
+    let len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    if data.len() < 4 + len {
+        return None;
+    }
+    Some((&data[4..4 + len], &data[4 + len..]))
+}
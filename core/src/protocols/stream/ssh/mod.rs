@@ -1,9 +1,17 @@
 //! SSH handshake parsing.
+//!
+//! ## Remarks
+//! The parser follows the handshake through the version exchange, algorithm negotiation, and key
+//! exchange. It then records the sizes of a bounded number of encrypted packets in the user
+//! authentication phase, which are used to infer (heuristically) whether authentication
+//! succeeded. The session is considered done once success is inferred or the authentication
+//! window is exhausted.
 
 mod handshake;
 pub mod parser;
 
 pub use self::handshake::*;
+use itertools::Itertools;
 use serde::Serialize;
 
 /// Parsed SSH handshake contents.
@@ -14,8 +22,12 @@ pub struct Ssh {
     /// Server protocol version exchange message.
     pub server_version_exchange: Option<SshVersionExchange>,
 
-    /// Key Exchange message.
+    /// Key Exchange message (the most recently observed in either direction).
     pub key_exchange: Option<SshKeyExchange>,
+    /// Key Exchange message sent by the client.
+    pub client_key_exchange: Option<SshKeyExchange>,
+    /// Key Exchange message sent by the server.
+    pub server_key_exchange: Option<SshKeyExchange>,
 
     /// Client Diffie-Hellman Key Exchange message.
     pub client_dh_key_exchange: Option<SshDhInit>,
@@ -27,10 +39,34 @@ pub struct Ssh {
     /// Server New Keys message.
     pub server_new_keys: Option<SshNewKeys>,
 
+    /// Server host key sent in the key exchange reply.
+    pub server_host_key: Option<SshHostKey>,
+    /// Encrypted packets observed in the user authentication phase.
+    pub auth_packets: Vec<SshEncryptedPacket>,
+
+    /// Client buffer for SSH packets that span multiple segments.
+    #[serde(skip_serializing)]
+    pub(crate) client_buffer: Vec<u8>,
+    /// Server buffer for SSH packets that span multiple segments.
+    #[serde(skip_serializing)]
+    pub(crate) server_buffer: Vec<u8>,
+    /// Offset of the encrypted data following NEWKEYS in the segment that completed the key
+    /// exchange.
     #[serde(skip_serializing)]
     pub(crate) last_body_offset: Option<usize>,
 }
 
+/// Returns the first algorithm in `client` that is also in `server`, or `""` if there is none.
+///
+/// See [RFC 4253 Section 7.1](https://datatracker.ietf.org/doc/html/rfc4253#section-7.1).
+fn negotiate<'a>(client: &'a [String], server: &[String]) -> &'a str {
+    client
+        .iter()
+        .find(|alg| server.contains(alg))
+        .map(|alg| alg.as_str())
+        .unwrap_or("")
+}
+
 impl Ssh {
     /// Returns the SSH protocol version used by the client (e.g. 2.0).
     pub fn protocol_version_ctos(&self) -> &str {
@@ -225,4 +261,209 @@ impl Ssh {
             None => vec![],
         }
     }
+
+    /// Returns the negotiated key exchange algorithm, or `""` if it cannot be determined.
+    pub fn negotiated_kex_alg(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => negotiate(&client.kex_algs, &server.kex_algs),
+            _ => "",
+        }
+    }
+
+    /// Returns the negotiated server host key algorithm, or `""` if it cannot be determined.
+    pub fn negotiated_host_key_alg(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => {
+                negotiate(&client.server_host_key_algs, &server.server_host_key_algs)
+            }
+            _ => "",
+        }
+    }
+
+    /// Returns the negotiated client-to-server encryption algorithm, or `""` if it cannot be
+    /// determined.
+    pub fn negotiated_encryption_alg_ctos(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => negotiate(
+                &client.encryption_algs_client_to_server,
+                &server.encryption_algs_client_to_server,
+            ),
+            _ => "",
+        }
+    }
+
+    /// Returns the negotiated server-to-client encryption algorithm, or `""` if it cannot be
+    /// determined.
+    pub fn negotiated_encryption_alg_stoc(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => negotiate(
+                &client.encryption_algs_server_to_client,
+                &server.encryption_algs_server_to_client,
+            ),
+            _ => "",
+        }
+    }
+
+    /// Returns the negotiated client-to-server MAC algorithm, or `""` if it cannot be determined.
+    pub fn negotiated_mac_alg_ctos(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => negotiate(
+                &client.mac_algs_client_to_server,
+                &server.mac_algs_client_to_server,
+            ),
+            _ => "",
+        }
+    }
+
+    /// Returns the negotiated server-to-client MAC algorithm, or `""` if it cannot be determined.
+    pub fn negotiated_mac_alg_stoc(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => negotiate(
+                &client.mac_algs_server_to_client,
+                &server.mac_algs_server_to_client,
+            ),
+            _ => "",
+        }
+    }
+
+    /// Returns the negotiated client-to-server compression algorithm, or `""` if it cannot be
+    /// determined.
+    pub fn negotiated_compression_alg_ctos(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => negotiate(
+                &client.compression_algs_client_to_server,
+                &server.compression_algs_client_to_server,
+            ),
+            _ => "",
+        }
+    }
+
+    /// Returns the negotiated server-to-client compression algorithm, or `""` if it cannot be
+    /// determined.
+    pub fn negotiated_compression_alg_stoc(&self) -> &str {
+        match (&self.client_key_exchange, &self.server_key_exchange) {
+            (Some(client), Some(server)) => negotiate(
+                &client.compression_algs_server_to_client,
+                &server.compression_algs_server_to_client,
+            ),
+            _ => "",
+        }
+    }
+
+    /// Returns the server host key algorithm name, or `""` if no key exchange reply was observed.
+    pub fn host_key_type(&self) -> &str {
+        match &self.server_host_key {
+            Some(host_key) => host_key.key_type.as_str(),
+            None => "",
+        }
+    }
+
+    /// Returns the SHA256 fingerprint of the server host key (e.g., `SHA256:...`), or `""` if no
+    /// key exchange reply was observed.
+    pub fn host_key_fingerprint(&self) -> &str {
+        match &self.server_host_key {
+            Some(host_key) => host_key.fingerprint.as_str(),
+            None => "",
+        }
+    }
+
+    /// Returns the client HASSH string, or `""` if no client Key Exchange message was observed.
+    ///
+    /// ## Remarks
+    /// The HASSH string is defined as the concatenation of:
+    /// `KexAlgorithms;EncryptionAlgorithms;MacAlgorithms;CompressionAlgorithms`, using the
+    /// client-to-server algorithm lists. See [salesforce/hassh](https://github.com/salesforce/hassh)
+    /// for more details.
+    pub fn hassh_str(&self) -> String {
+        match &self.client_key_exchange {
+            Some(kex) => format!(
+                "{};{};{};{}",
+                kex.kex_algs.iter().join(","),
+                kex.encryption_algs_client_to_server.iter().join(","),
+                kex.mac_algs_client_to_server.iter().join(","),
+                kex.compression_algs_client_to_server.iter().join(","),
+            ),
+            None => "".to_string(),
+        }
+    }
+
+    /// Returns the server HASSHServer string, or `""` if no server Key Exchange message was
+    /// observed.
+    ///
+    /// ## Remarks
+    /// The HASSHServer string is defined as the concatenation of:
+    /// `KexAlgorithms;EncryptionAlgorithms;MacAlgorithms;CompressionAlgorithms`, using the
+    /// server-to-client algorithm lists. See [salesforce/hassh](https://github.com/salesforce/hassh)
+    /// for more details.
+    pub fn hassh_server_str(&self) -> String {
+        match &self.server_key_exchange {
+            Some(kex) => format!(
+                "{};{};{};{}",
+                kex.kex_algs.iter().join(","),
+                kex.encryption_algs_server_to_client.iter().join(","),
+                kex.mac_algs_server_to_client.iter().join(","),
+                kex.compression_algs_server_to_client.iter().join(","),
+            ),
+            None => "".to_string(),
+        }
+    }
+
+    /// Returns the HASSH fingerprint.
+    pub fn hassh(&self) -> String {
+        format!("{:x}", md5::compute(self.hassh_str()))
+    }
+
+    /// Returns the HASSHServer fingerprint.
+    pub fn hassh_server(&self) -> String {
+        format!("{:x}", md5::compute(self.hassh_server_str()))
+    }
+
+    /// Returns the heuristic outcome of the user authentication phase.
+    ///
+    /// ## Remarks
+    /// The first encrypted client packet is assumed to be the SSH_MSG_SERVICE_REQUEST for
+    /// `ssh-userauth`, whose size is used as a reference. Once the client has sent a user
+    /// authentication request, a server packet smaller than the reference is assumed to be an
+    /// SSH_MSG_USERAUTH_SUCCESS (empty payload), and a larger one an SSH_MSG_USERAUTH_FAILURE
+    /// (which carries a list of methods). This may be inaccurate for, e.g., keyboard-interactive
+    /// authentication or when several SSH packets are coalesced into one segment.
+    pub fn auth_result(&self) -> AuthResult {
+        if self.auth_success_idx().is_some() {
+            return AuthResult::Success;
+        }
+        match self.auth_attempts() {
+            0 => AuthResult::Unknown,
+            _ => AuthResult::Failure,
+        }
+    }
+
+    /// Returns the number of (heuristically) rejected user authentication requests.
+    pub fn auth_attempts(&self) -> usize {
+        let end = self.auth_success_idx().unwrap_or(self.auth_packets.len());
+        match self.auth_reference() {
+            Some((reference, start)) => self.auth_packets[start..end]
+                .iter()
+                .filter(|p| !p.dir && p.len >= reference)
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Returns the size of the first encrypted client packet and the index of the first packet
+    /// that may be a response to a user authentication request.
+    fn auth_reference(&self) -> Option<(usize, usize)> {
+        let mut client = self.auth_packets.iter().enumerate().filter(|(_, p)| p.dir);
+        let (_, service_request) = client.next()?;
+        let (first_request, _) = client.next()?;
+        Some((service_request.len, first_request + 1))
+    }
+
+    /// Returns the index of the server packet inferred to be SSH_MSG_USERAUTH_SUCCESS.
+    fn auth_success_idx(&self) -> Option<usize> {
+        let (reference, start) = self.auth_reference()?;
+        self.auth_packets[start..]
+            .iter()
+            .position(|p| !p.dir && p.len < reference)
+            .map(|idx| idx + start)
+    }
 }
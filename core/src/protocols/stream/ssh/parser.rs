@@ -12,6 +12,17 @@ use crate::protocols::stream::{
 
 use ssh_parser::*;
 
+/// Maximum number of encrypted packets recorded in the user authentication phase.
+const MAX_AUTH_PACKETS: usize = 32;
+
+// SSH message numbers. See [RFC 4250 Section 4.1](https://datatracker.ietf.org/doc/html/rfc4250#section-4.1).
+const SSH_MSG_KEXINIT: u8 = 20;
+const SSH_MSG_NEWKEYS: u8 = 21;
+const SSH_MSG_KEXDH_INIT: u8 = 30;
+const SSH_MSG_KEXDH_REPLY: u8 = 31;
+const SSH_MSG_KEX_DH_GEX_INIT: u8 = 32;
+const SSH_MSG_KEX_DH_GEX_REPLY: u8 = 33;
+
 #[derive(Debug)]
 pub struct SshParser {
     sessions: Vec<Ssh>,
@@ -101,27 +112,41 @@ impl Ssh {
             client_version_exchange: None,
             server_version_exchange: None,
             key_exchange: None,
+            client_key_exchange: None,
+            server_key_exchange: None,
             client_dh_key_exchange: None,
             server_dh_key_exchange: None,
             client_new_keys: None,
             server_new_keys: None,
+            server_host_key: None,
+            auth_packets: vec![],
+            client_buffer: vec![],
+            server_buffer: vec![],
             last_body_offset: None,
         }
     }
 
     fn byte_to_string(&mut self, b: &[u8]) -> String {
-        String::from_utf8(b.to_vec()).unwrap()
+        String::from_utf8_lossy(b).into_owned()
     }
 
-    pub(crate) fn parse_version_exchange(&mut self, data: &[u8], dir: bool) {
+    /// Parse a protocol version exchange message. Return the data following the message, or
+    /// `None` if the message is not terminated yet.
+    pub(crate) fn parse_version_exchange<'a>(
+        &mut self,
+        data: &'a [u8],
+        dir: bool,
+    ) -> Option<&'a [u8]> {
         let ssh_identifier = b"SSH-";
         if let Some(contains_ssh_identifier) = data
             .windows(ssh_identifier.len())
             .position(|window| window == ssh_identifier)
             .map(|p| &data[p..])
         {
+            // The identification string ends with CR LF
+            let line_end = contains_ssh_identifier.iter().position(|&b| b == b'\n')? + 1;
             match ssh_parser::parse_ssh_identification(contains_ssh_identifier) {
-                Ok((_, (_, ssh_id_string))) => {
+                Ok((rem, (_, ssh_id_string))) => {
                     let version_exchange = SshVersionExchange {
                         protoversion: Some(self.byte_to_string(ssh_id_string.proto)),
                         softwareversion: Some(self.byte_to_string(ssh_id_string.software)),
@@ -137,19 +162,24 @@ impl Ssh {
                     } else {
                         self.server_version_exchange = Some(version_exchange);
                     }
+                    return Some(rem);
+                }
+                e => {
+                    log::debug!("Not a valid SSH version exchange message: {:?}", e);
+                    return Some(&contains_ssh_identifier[line_end..]);
                 }
-                e => log::debug!("Not a valid SSH version exchange message: {:?}", e),
             }
         }
+        Some(data)
     }
 
     fn bytes_to_string_vec(&mut self, data: &[u8]) -> Vec<String> {
         data.split(|&b| b == b',')
-            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
             .collect()
     }
 
-    pub(crate) fn parse_key_exchange(&mut self, data: &[u8]) {
+    pub(crate) fn parse_key_exchange(&mut self, data: &[u8], dir: bool) {
         match ssh_parser::parse_ssh_packet(data) {
             Ok((_, (pkt, _))) => match pkt {
                 SshPacket::KeyExchange(pkt) => {
@@ -176,6 +206,11 @@ impl Ssh {
                         first_kex_packet_follows: pkt.first_kex_packet_follows,
                     };
 
+                    if dir {
+                        self.client_key_exchange = Some(key_exchange.clone());
+                    } else {
+                        self.server_key_exchange = Some(key_exchange.clone());
+                    }
                     self.key_exchange = Some(key_exchange);
                 }
                 e => log::debug!("Could not parse data as a SSH KeyExchange packet: {:?}", e),
@@ -211,6 +246,7 @@ impl Ssh {
                         signature: pkt.signature.to_vec(),
                     };
 
+                    self.server_host_key = Some(SshHostKey::from_blob(pkt.pubkey_and_cert));
                     self.server_dh_key_exchange = Some(dh_response);
                 }
                 e => log::debug!(
@@ -222,77 +258,464 @@ impl Ssh {
         }
     }
 
-    /// Parse a new keys packet. Return length of remaining data.
-    pub(crate) fn parse_new_keys(&mut self, data: &[u8], dir: bool) -> usize {
-        let mut remaining = 0;
-        match ssh_parser::parse_ssh_packet(data) {
-            Ok((rem, (pkt, _))) => match pkt {
-                SshPacket::NewKeys => {
-                    let new_keys = SshNewKeys;
-                    remaining = rem.len();
-                    if dir {
-                        self.client_new_keys = Some(new_keys);
-                    } else {
-                        self.server_new_keys = Some(new_keys);
-                    }
-                }
-                e => log::debug!("Could not parse data as a SSH NewKeys packet: {:?}", e),
-            },
-            e => log::debug!("Could not parse data as a SSH packet: {:?}", e),
+    /// Parse a Diffie-Hellman Group Exchange init message payload (excluding the message number).
+    pub(crate) fn parse_dh_gex_client_init(&mut self, payload: &[u8]) {
+        match read_string(payload) {
+            Some((e, _)) => self.client_dh_key_exchange = Some(SshDhInit { e: e.to_vec() }),
+            None => log::debug!("Could not parse data as a SSH KEX_DH_GEX_INIT packet"),
+        }
+    }
+
+    /// Parse a Diffie-Hellman Group Exchange reply message payload (excluding the message number).
+    pub(crate) fn parse_dh_gex_server_response(&mut self, payload: &[u8]) {
+        let parsed = read_string(payload).and_then(|(pubkey_and_certs, rem)| {
+            let (f, rem) = read_string(rem)?;
+            let (signature, _) = read_string(rem)?;
+            Some((pubkey_and_certs, f, signature))
+        });
+        match parsed {
+            Some((pubkey_and_certs, f, signature)) => {
+                self.server_host_key = Some(SshHostKey::from_blob(pubkey_and_certs));
+                self.server_dh_key_exchange = Some(SshDhResponse {
+                    pubkey_and_certs: pubkey_and_certs.to_vec(),
+                    f: f.to_vec(),
+                    signature: signature.to_vec(),
+                });
+            }
+            None => log::debug!("Could not parse data as a SSH KEX_DH_GEX_REPLY packet"),
+        }
+    }
+
+    /// Parse a new keys packet.
+    pub(crate) fn parse_new_keys(&mut self, dir: bool) {
+        let new_keys = SshNewKeys;
+        if dir {
+            self.client_new_keys = Some(new_keys);
+        } else {
+            self.server_new_keys = Some(new_keys);
+        }
+    }
+
+    /// Returns `true` if the key exchange negotiated Diffie-Hellman Group Exchange, in which
+    /// message numbers 30-34 take on different meanings.
+    fn is_group_exchange(&self) -> bool {
+        self.negotiated_kex_alg().contains("group-exchange")
+    }
+
+    /// Returns `true` if NEWKEYS has been sent in direction `dir`.
+    fn encrypted(&self, dir: bool) -> bool {
+        match dir {
+            true => self.client_new_keys.is_some(),
+            false => self.server_new_keys.is_some(),
+        }
+    }
+
+    /// Returns `true` if the handshake and authentication phase are done.
+    fn auth_done(&self) -> bool {
+        self.client_new_keys.is_some()
+            && self.server_new_keys.is_some()
+            && (self.auth_result() == AuthResult::Success
+                || self.auth_packets.len() >= MAX_AUTH_PACKETS)
+    }
+
+    /// Parse a single (unencrypted) SSH binary packet.
+    fn parse_message(&mut self, packet: &[u8], msg: u8, payload: &[u8], dir: bool) {
+        match msg {
+            SSH_MSG_KEXINIT => self.parse_key_exchange(packet, dir),
+            SSH_MSG_NEWKEYS => self.parse_new_keys(dir),
+            SSH_MSG_KEXDH_INIT if !self.is_group_exchange() => self.parse_dh_client_init(packet),
+            SSH_MSG_KEXDH_REPLY if !self.is_group_exchange() => {
+                self.parse_dh_server_response(packet)
+            }
+            SSH_MSG_KEX_DH_GEX_INIT if self.is_group_exchange() => {
+                self.parse_dh_gex_client_init(payload)
+            }
+            SSH_MSG_KEX_DH_GEX_REPLY if self.is_group_exchange() => {
+                self.parse_dh_gex_server_response(payload)
+            }
+            _ => log::trace!("Skipping SSH message {}", msg),
         }
-        remaining
     }
 
     pub(crate) fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
-        let mut status = ParseResult::Continue(0);
         log::trace!("process ({} bytes)", data.len());
+        self.last_body_offset = None;
+        if self.encrypted(dir) {
+            self.auth_packets.push(SshEncryptedPacket {
+                dir,
+                len: data.len(),
+            });
+            return match self.auth_done() {
+                true => ParseResult::HeadersDone(0),
+                false => ParseResult::Continue(0),
+            };
+        }
+
+        let mut v: Vec<u8>;
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        let mut cur_data = match buffer.len() {
+            0 => data,
+            _ => {
+                v = std::mem::take(buffer);
+                v.extend_from_slice(data);
+                v.as_slice()
+            }
+        };
 
         let ssh_identifier = b"SSH-";
-        if data
-            .windows(ssh_identifier.len())
-            .position(|window| window == ssh_identifier)
-            .map(|p| &data[p..])
-            .is_some()
+        let has_version_exchange = match dir {
+            true => self.client_version_exchange.is_some(),
+            false => self.server_version_exchange.is_some(),
+        };
+        if !has_version_exchange
+            && cur_data
+                .windows(ssh_identifier.len())
+                .any(|window| window == ssh_identifier)
         {
-            self.parse_version_exchange(data, dir);
-            status = ParseResult::Continue(0);
-        } else {
-            match ssh_parser::parse_ssh_packet(data) {
-                Ok((_, (pkt, _))) => {
-                    match pkt {
-                        SshPacket::KeyExchange(_) => {
-                            self.parse_key_exchange(data);
-                            status = ParseResult::Continue(0);
-                        }
-                        SshPacket::DiffieHellmanInit(_) => {
-                            self.parse_dh_client_init(data);
-                            status = ParseResult::Continue(0);
-                        }
-                        SshPacket::DiffieHellmanReply(_) => {
-                            self.parse_dh_server_response(data);
-                            status = ParseResult::Continue(0);
-                        }
-                        SshPacket::NewKeys => {
-                            let remaining = self.parse_new_keys(data, dir);
-
-                            // finish parsing when client and server have both sent a NewKeys packet
-                            if self.client_new_keys.is_some() && self.server_new_keys.is_some() {
-                                if remaining > 0 && remaining < data.len() {
-                                    self.last_body_offset = Some(data.len() - remaining - 1);
-                                }
-                                return ParseResult::HeadersDone(0);
-                            }
-                            status = ParseResult::Continue(0);
-                        }
-                        _ => (),
-                    }
+            cur_data = match self.parse_version_exchange(cur_data, dir) {
+                Some(rem) => rem,
+                None => return self.buffer(cur_data, dir),
+            };
+        }
+
+        while !cur_data.is_empty() {
+            if self.encrypted(dir) {
+                // Remainder of segment follows NEWKEYS
+                if self.client_new_keys.is_some() && self.server_new_keys.is_some() {
+                    self.last_body_offset =
+                        data.len().checked_sub(cur_data.len()).filter(|&o| o > 0);
                 }
-                e => {
-                    log::debug!("parse error: {:?}", e);
-                    status = ParseResult::Skipped;
+                self.auth_packets.push(SshEncryptedPacket {
+                    dir,
+                    len: cur_data.len(),
+                });
+                break;
+            }
+            match split_packet(cur_data) {
+                Ok((packet, msg, payload, rem)) => {
+                    self.parse_message(packet, msg, payload, dir);
+                    cur_data = rem;
+                }
+                Err(SplitError::Incomplete) => return self.buffer(cur_data, dir),
+                Err(SplitError::Invalid) => {
+                    log::debug!("parse error: invalid SSH packet");
+                    return ParseResult::Skipped;
                 }
             }
         }
-        status
+
+        if self.auth_done() {
+            return ParseResult::HeadersDone(0);
+        }
+        ParseResult::Continue(0)
+    }
+
+    /// Buffer the incomplete message at the end of a segment until more data arrives.
+    fn buffer(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        if data.len() > MAX_BUFFER_LEN {
+            log::debug!("SSH packet exceeds maximum buffer size");
+            return ParseResult::Skipped;
+        }
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        buffer.extend_from_slice(data);
+        ParseResult::Continue(0)
+    }
+}
+
+/// Upper bound on the size of a buffered (unencrypted) SSH packet.
+const MAX_BUFFER_LEN: usize = 262_144;
+
+/// Errors returned when splitting an SSH binary packet off a segment.
+#[derive(Debug)]
+enum SplitError {
+    /// More data is required.
+    Incomplete,
+    /// The data does not look like an SSH binary packet.
+    Invalid,
+}
+
+/// Splits the next SSH binary packet off `data`. Returns the packet, its message number, the
+/// message payload (excluding the message number), and the remaining data.
+///
+/// See [RFC 4253 Section 6](https://datatracker.ietf.org/doc/html/rfc4253#section-6).
+#[allow(clippy::type_complexity)]
+fn split_packet(data: &[u8]) -> Result<(&[u8], u8, &[u8], &[u8]), SplitError> {
+    if data.len() < 6 {
+        return Err(SplitError::Incomplete);
+    }
+    let packet_len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let padding_len = data[4] as usize;
+    if packet_len < padding_len + 2 || packet_len > MAX_BUFFER_LEN {
+        return Err(SplitError::Invalid);
+    }
+    if data.len() < 4 + packet_len {
+        return Err(SplitError::Incomplete);
+    }
+    let payload = &data[5..4 + packet_len - padding_len];
+    Ok((
+        &data[..4 + packet_len],
+        payload[0],
+        &payload[1..],
+        &data[4 + packet_len..],
+    ))
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    /// Identification string and KEXINIT sent by an OpenSSH 9.2 client.
+    const CLIENT_FLIGHT: &[u8] =
+        b"SSH-2.0-OpenSSH_9.2p1 Debian-2+deb12u6\r\n\x00\x00\x06\x14\x08\x14'\xfd\xbc\x06`+\xe9&\
+        \xb5\xca\xfc\x0f\xb6\xfd\xd3\x8e\x00\x00\x01Hsntrup761x25519-sha512,sntrup761x25519-sh\
+        a512@openssh.com,curve25519-sha256,curve25519-sha256@libssh.org,ecdh-sha2-nistp256,ecd\
+        h-sha2-nistp384,ecdh-sha2-nistp521,diffie-hellman-group-exchange-sha256,diffie-hellman\
+        -group16-sha512,diffie-hellman-group18-sha512,diffie-hellman-group14-sha256,ext-info-c\
+        ,kex-strict-c-v00@openssh.com\x00\x00\x01\xcfssh-ed25519-cert-v01@openssh.com,ecdsa-sh\
+        a2-nistp256-cert-v01@openssh.com,ecdsa-sha2-nistp384-cert-v01@openssh.com,ecdsa-sha2-n\
+        istp521-cert-v01@openssh.com,sk-ssh-ed25519-cert-v01@openssh.com,sk-ecdsa-sha2-nistp25\
+        6-cert-v01@openssh.com,rsa-sha2-512-cert-v01@openssh.com,rsa-sha2-256-cert-v01@openssh\
+        .com,ssh-ed25519,ecdsa-sha2-nistp256,ecdsa-sha2-nistp384,ecdsa-sha2-nistp521,sk-ssh-ed\
+        25519@openssh.com,sk-ecdsa-sha2-nistp256@openssh.com,rsa-sha2-512,rsa-sha2-256\x00\x00\
+        \x00lchacha20-poly1305@openssh.com,aes128-ctr,aes192-ctr,aes256-ctr,aes128-gcm@openssh\
+        .com,aes256-gcm@openssh.com\x00\x00\x00lchacha20-poly1305@openssh.com,aes128-ctr,aes19\
+        2-ctr,aes256-ctr,aes128-gcm@openssh.com,aes256-gcm@openssh.com\x00\x00\x00\xd5umac-64-\
+        etm@openssh.com,umac-128-etm@openssh.com,hmac-sha2-256-etm@openssh.com,hmac-sha2-512-e\
+        tm@openssh.com,hmac-sha1-etm@openssh.com,umac-64@openssh.com,umac-128@openssh.com,hmac\
+        -sha2-256,hmac-sha2-512,hmac-sha1\x00\x00\x00\xd5umac-64-etm@openssh.com,umac-128-etm@\
+        openssh.com,hmac-sha2-256-etm@openssh.com,hmac-sha2-512-etm@openssh.com,hmac-sha1-etm@\
+        openssh.com,umac-64@openssh.com,umac-128@openssh.com,hmac-sha2-256,hmac-sha2-512,hmac-\
+        sha1\x00\x00\x00\x1anone,zlib@openssh.com,zlib\x00\x00\x00\x1anone,zlib@openssh.com,zl\
+        ib\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+    /// OpenSSH ed25519 host key blob.
+    const HOST_KEY: &[u8] = b"\x00\x00\x00\x0bssh-ed25519\x00\x00\x00\x20\
+        \x58\x4a\x48\xa9\x4c\x4d\x02\x0c\x94\xf8\xea\x87\xe2\x0e\xd5\x45\
+        \xf2\xcd\xf7\x12\x40\xfb\xa8\x91\x5f\xdb\xd6\xbf\x68\xf8\x65\x4b";
+    const HOST_KEY_FINGERPRINT: &str = "SHA256:b28XYAO7y710QO+YnRwFc+aMZghxAvcKLmv/cEGDeb4";
+
+    const SERVER_ID: &[u8] = b"SSH-2.0-OpenSSH_9.6\r\n";
+
+    /// Frames `payload` as an unencrypted binary packet with 8-byte alignment.
+    fn packet(payload: &[u8]) -> Vec<u8> {
+        let mut padding_len = 8 - (5 + payload.len()) % 8;
+        if padding_len < 4 {
+            padding_len += 8;
+        }
+        let mut pkt = ((1 + payload.len() + padding_len) as u32)
+            .to_be_bytes()
+            .to_vec();
+        pkt.push(padding_len as u8);
+        pkt.extend_from_slice(payload);
+        pkt.resize(pkt.len() + padding_len, 0);
+        pkt
+    }
+
+    fn string(data: &[u8]) -> Vec<u8> {
+        let mut s = (data.len() as u32).to_be_bytes().to_vec();
+        s.extend_from_slice(data);
+        s
+    }
+
+    /// Returns a server KEXINIT with the same algorithms in both directions.
+    fn server_kexinit(kex: &[u8], enc: &[u8], mac: &[u8], comp: &[u8]) -> Vec<u8> {
+        let mut payload = vec![SSH_MSG_KEXINIT];
+        payload.extend_from_slice(&[0xab; 16]);
+        for list in [
+            kex,
+            b"ssh-ed25519",
+            enc,
+            enc,
+            mac,
+            mac,
+            comp,
+            comp,
+            b"",
+            b"",
+        ] {
+            payload.extend(string(list));
+        }
+        payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+        packet(&payload)
+    }
+
+    /// Returns a key exchange reply with message number `msg`.
+    fn kex_reply(msg: u8) -> Vec<u8> {
+        let mut payload = vec![msg];
+        payload.extend(string(HOST_KEY));
+        payload.extend(string(&[0x42; 32]));
+        payload.extend(string(&[0x5a; 83]));
+        packet(&payload)
+    }
+
+    const FLOW: Flow = Flow::tcp(22);
+
+    #[test]
+    fn core_ssh_probe() {
+        let parser = SshParser::default();
+        assert_eq!(
+            FLOW.probe(&parser, CLIENT_FLIGHT, true),
+            ProbeResult::Certain
+        );
+        assert_eq!(FLOW.probe(&parser, SERVER_ID, false), ProbeResult::Certain);
+        assert_eq!(FLOW.probe(&parser, b"SS", true), ProbeResult::Unsure);
+        assert_eq!(
+            FLOW.probe(&parser, b"GET / HTTP/1.1\r\n", true),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_ssh_banner_split() {
+        let mut parser = SshParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, &CLIENT_FLIGHT[..12], true),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.sessions[0].client_version_exchange.is_none());
+        assert_eq!(
+            FLOW.parse(&mut parser, &CLIENT_FLIGHT[12..], true),
+            ParseResult::Continue(0)
+        );
+        let ssh = &parser.sessions[0];
+        assert_eq!(ssh.protocol_version_ctos(), "2.0");
+        assert_eq!(ssh.software_version_ctos(), "OpenSSH_9.2p1");
+        assert_eq!(ssh.comments_ctos(), "Debian-2+deb12u6");
+        // KEXINIT in the same segment as the end of the identification string
+        assert!(ssh.client_key_exchange.is_some());
+    }
+
+    #[test]
+    fn core_ssh_kexinit_split() {
+        let mut parser = SshParser::default();
+        FLOW.parse(&mut parser, &CLIENT_FLIGHT[..700], true);
+        assert!(parser.sessions[0].client_version_exchange.is_some());
+        assert!(parser.sessions[0].client_key_exchange.is_none());
+        FLOW.parse(&mut parser, &CLIENT_FLIGHT[700..], true);
+        let ssh = &parser.sessions[0];
+        assert_eq!(ssh.client_key_exchange.as_ref().unwrap().kex_algs.len(), 13);
+        assert_eq!(ssh.hassh(), "472b5de333ad665af5cbf10ff892c4df");
+    }
+
+    #[test]
+    fn core_ssh_truncated() {
+        let mut parser = SshParser::default();
+        FLOW.parse(&mut parser, &CLIENT_FLIGHT[..700], true);
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Ssh(ssh) => {
+                assert_eq!(ssh.software_version_ctos(), "OpenSSH_9.2p1");
+                assert!(ssh.client_key_exchange.is_none());
+                assert_eq!(ssh.hassh_str(), "");
+            }
+            _ => panic!("Expected SSH session"),
+        }
+    }
+
+    #[test]
+    fn core_ssh_kex_reply() {
+        let mut parser = SshParser::default();
+        FLOW.parse(&mut parser, CLIENT_FLIGHT, true);
+        let mut server = SERVER_ID.to_vec();
+        server.extend(server_kexinit(
+            b"curve25519-sha256,curve25519-sha256@libssh.org,diffie-hellman-group14-sha256",
+            b"aes128-gcm@openssh.com,aes256-ctr",
+            b"hmac-sha2-256-etm@openssh.com,hmac-sha2-256",
+            b"none,zlib@openssh.com",
+        ));
+        FLOW.parse(&mut parser, &server, false);
+
+        let mut init = vec![SSH_MSG_KEXDH_INIT];
+        init.extend(string(&[0x24; 32]));
+        FLOW.parse(&mut parser, &packet(&init), true);
+        let mut reply = kex_reply(SSH_MSG_KEXDH_REPLY);
+        reply.extend(packet(&[SSH_MSG_NEWKEYS]));
+        FLOW.parse(&mut parser, &reply, false);
+
+        let ssh = &parser.sessions[0];
+        assert_eq!(ssh.negotiated_kex_alg(), "curve25519-sha256");
+        assert_eq!(ssh.negotiated_encryption_alg_ctos(), "aes256-ctr");
+        assert_eq!(ssh.hassh_server(), "f6b0081db4b16008ceeea3c6d4786fa2");
+        assert_eq!(ssh.host_key_type(), "ssh-ed25519");
+        assert_eq!(ssh.host_key_fingerprint(), HOST_KEY_FINGERPRINT);
+        assert_eq!(ssh.client_dh_key_exchange.as_ref().unwrap().e, [0x24; 32]);
+
+        // Client NEWKEYS followed by the first encrypted packet
+        let newkeys = packet(&[SSH_MSG_NEWKEYS]);
+        let mut data = newkeys.clone();
+        data.extend_from_slice(&[0x77; 52]);
+        assert_eq!(
+            FLOW.parse(&mut parser, &data, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.body_offset(), Some(newkeys.len()));
+        assert_eq!(parser.body_offset(), None);
+        assert_eq!(parser.sessions[0].auth_packets.len(), 1);
+    }
+
+    #[test]
+    fn core_ssh_gex() {
+        let mut parser = SshParser::default();
+        FLOW.parse(&mut parser, CLIENT_FLIGHT, true);
+        let mut server = SERVER_ID.to_vec();
+        server.extend(server_kexinit(
+            b"diffie-hellman-group-exchange-sha256",
+            b"aes256-ctr",
+            b"hmac-sha2-256",
+            b"none",
+        ));
+        FLOW.parse(&mut parser, &server, false);
+        assert_eq!(
+            parser.sessions[0].hassh_server(),
+            "d926179b9f35d25a315454d1cc0f86f3"
+        );
+
+        // KEX_DH_GEX_REQUEST and KEX_DH_GEX_GROUP share message numbers with KEXDH_INIT/REPLY
+        let mut request = vec![34];
+        request.extend_from_slice(&[0, 0, 8, 0, 0, 0, 32, 0, 0, 0, 32, 0]);
+        FLOW.parse(&mut parser, &packet(&request), true);
+        let mut group = vec![SSH_MSG_KEXDH_REPLY];
+        group.extend(string(&[0x7f; 256]));
+        group.extend(string(&[2]));
+        FLOW.parse(&mut parser, &packet(&group), false);
+        assert!(parser.sessions[0].server_host_key.is_none());
+
+        let mut init = vec![SSH_MSG_KEX_DH_GEX_INIT];
+        init.extend(string(&[0x31; 256]));
+        FLOW.parse(&mut parser, &packet(&init), true);
+        FLOW.parse(&mut parser, &kex_reply(SSH_MSG_KEX_DH_GEX_REPLY), false);
+
+        let ssh = &parser.sessions[0];
+        assert_eq!(
+            ssh.negotiated_kex_alg(),
+            "diffie-hellman-group-exchange-sha256"
+        );
+        assert_eq!(ssh.client_dh_key_exchange.as_ref().unwrap().e, [0x31; 256]);
+        assert_eq!(ssh.server_dh_key_exchange.as_ref().unwrap().f, [0x42; 32]);
+        assert_eq!(ssh.host_key_type(), "ssh-ed25519");
+        assert_eq!(ssh.host_key_fingerprint(), HOST_KEY_FINGERPRINT);
+    }
+
+    #[test]
+    fn core_ssh_non_utf8() {
+        let mut parser = SshParser::default();
+        let mut server = b"SSH-2.0-OpenSSH_9.6 caf\xe9\r\n".to_vec();
+        server.extend(server_kexinit(
+            b"curve25519-sha256",
+            b"aes\xff",
+            b"hmac-sha2-256",
+            b"none",
+        ));
+        FLOW.parse(&mut parser, &server, false);
+        let ssh = &parser.sessions[0];
+        assert_eq!(ssh.comments_stoc(), "caf\u{fffd}");
+        assert_eq!(ssh.encryption_algs_stoc(), ["aes\u{fffd}"]);
     }
 }
@@ -0,0 +1,106 @@
+//! Helpers for parser unit tests.
+//!
+//! PDUs are backed by heap memory, so parser tests are only built with the `heap_mbuf` feature:
+//! `cargo test -p iris-core --features heap_mbuf`.
+
+use crate::conntrack::pdu::{L4Context, L4Pdu};
+use crate::memory::mbuf::Mbuf;
+use crate::protocols::packet::tcp::TCP_PROTOCOL;
+use crate::protocols::packet::udp::UDP_PROTOCOL;
+use crate::protocols::stream::{ConnParsable, ParseResult, ProbeResult};
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Instant;
+
+/// Port of the client in PDUs built by [tcp] and [udp].
+pub(crate) const CLIENT_PORT: u16 = 50000;
+
+/// Returns a PDU with `payload` sent from `src_port` on the client (`dir` is `true`) or server
+/// to `dst_port` on the other host.
+pub(crate) fn pdu(payload: &[u8], proto: usize, src_port: u16, dst_port: u16, dir: bool) -> L4Pdu {
+    let client = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let server = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+    let (src, dst) = match dir {
+        true => (client, server),
+        false => (server, client),
+    };
+    let ctxt = L4Context {
+        src: SocketAddr::new(src, src_port),
+        dst: SocketAddr::new(dst, dst_port),
+        proto,
+        offset: 0,
+        length: payload.len(),
+        seq_no: 0,
+        ack_no: 0,
+        flags: 0,
+        reassembled: false,
+        app_offset: None,
+    };
+    let mbuf = Mbuf::from_bytes(payload).unwrap();
+    L4Pdu::new(mbuf, ctxt, dir, Instant::now(), None, None)
+}
+
+/// A TCP connection or UDP flow between the client and `port` on the server.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Flow {
+    proto: usize,
+    port: u16,
+}
+
+impl Flow {
+    /// A TCP connection to `port` on the server.
+    pub(crate) const fn tcp(port: u16) -> Self {
+        Flow {
+            proto: TCP_PROTOCOL,
+            port,
+        }
+    }
+
+    /// A UDP flow to `port` on the server.
+    pub(crate) const fn udp(port: u16) -> Self {
+        Flow {
+            proto: UDP_PROTOCOL,
+            port,
+        }
+    }
+
+    /// Returns a PDU with `payload` in this flow, sent by the client if `dir` is `true`.
+    pub(crate) fn pdu(&self, payload: &[u8], dir: bool) -> L4Pdu {
+        match dir {
+            true => pdu(payload, self.proto, CLIENT_PORT, self.port, dir),
+            false => pdu(payload, self.proto, self.port, CLIENT_PORT, dir),
+        }
+    }
+
+    /// Probes `payload` in this flow, sent by the client if `dir` is `true`, with `parser`.
+    pub(crate) fn probe<P: ConnParsable>(
+        &self,
+        parser: &P,
+        payload: &[u8],
+        dir: bool,
+    ) -> ProbeResult {
+        parser.probe(&self.pdu(payload, dir))
+    }
+
+    /// Parses `payload` in this flow, sent by the client if `dir` is `true`, with `parser`.
+    pub(crate) fn parse<P: ConnParsable>(
+        &self,
+        parser: &mut P,
+        payload: &[u8],
+        dir: bool,
+    ) -> ParseResult {
+        parser.parse(&self.pdu(payload, dir))
+    }
+}
+
+/// Returns a TCP segment with `payload` on a connection to `port` on the server, sent by the
+/// client if `dir` is `true`.
+pub(crate) fn tcp(payload: &[u8], port: u16, dir: bool) -> L4Pdu {
+    Flow::tcp(port).pdu(payload, dir)
+}
+
+/// Returns a UDP datagram with `payload` on a flow to `port` on the server, sent by the client
+/// if `dir` is `true`.
+pub(crate) fn udp(payload: &[u8], port: u16, dir: bool) -> L4Pdu {
+    Flow::udp(port).pdu(payload, dir)
+}
@@ -54,6 +54,13 @@ pub struct Certificate {
     // TODO: parsed certificate
 }
 
+impl Certificate {
+    /// Returns the SHA-256 fingerprint of the DER-encoded certificate, as lowercase hex.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(ring::digest::digest(&ring::digest::SHA256, &self.raw))
+    }
+}
+
 /// Key data sent by the server in a ServerKeyExchange message.
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "snake_case")]
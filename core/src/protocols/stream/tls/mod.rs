@@ -15,6 +15,11 @@ const GREASE_TABLE: &[u16] = &[
     0xcaca, 0xdada, 0xeaea, 0xfafa,
 ];
 
+/// Server Name Indication extension type.
+const EXT_SERVER_NAME: u16 = 0x0000;
+/// Application-Layer Protocol Negotiation extension type.
+const EXT_ALPN: u16 = 0x0010;
+
 /// Parsed TLS handshake contents.
 #[derive(Debug, Default, Serialize, Clone)]
 pub struct Tls {
@@ -302,4 +307,306 @@ impl Tls {
     pub fn ja3s_hash(&self) -> String {
         format!("{:x}", md5::compute(self.ja3s_str()))
     }
+
+    /// Returns the client JA4 fingerprint, or `""` if no ClientHello was observed.
+    ///
+    /// ## Remarks
+    /// The JA4 fingerprint is defined as `a_b_c`, where `a` summarizes the protocol, TLS version,
+    /// SNI, cipher and extension counts, and first ALPN value, `b` is the truncated SHA-256 hash of
+    /// the sorted cipher suites, and `c` is the truncated SHA-256 hash of the sorted extensions
+    /// (excluding SNI and ALPN) followed by the signature algorithms. GREASE values are ignored. See
+    /// [FoxIO-LLC/ja4](https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4.md) for
+    /// more details.
+    pub fn ja4(&self) -> String {
+        self.ja4_fingerprint('t', false)
+    }
+
+    /// Returns the client JA4 fingerprint with unhashed cipher suites and extensions (JA4_r), or
+    /// `""` if no ClientHello was observed.
+    pub fn ja4_r(&self) -> String {
+        self.ja4_fingerprint('t', true)
+    }
+
+    /// Returns the server JA4S fingerprint, or `""` if no ServerHello was observed.
+    ///
+    /// ## Remarks
+    /// The JA4S fingerprint is defined as `a_b_c`, where `a` summarizes the protocol, TLS version,
+    /// extension count, and chosen ALPN value, `b` is the chosen cipher suite, and `c` is the
+    /// truncated SHA-256 hash of the extensions in the order they were sent. See
+    /// [FoxIO-LLC/ja4](https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4S.md) for
+    /// more details.
+    pub fn ja4s(&self) -> String {
+        self.ja4s_fingerprint('t', false)
+    }
+
+    /// Returns the server JA4S fingerprint with unhashed extensions (JA4S_r), or `""` if no
+    /// ServerHello was observed.
+    pub fn ja4s_r(&self) -> String {
+        self.ja4s_fingerprint('t', true)
+    }
+
+    /// Computes the JA4 fingerprint of the ClientHello sent over `transport` (`t` for TCP, `q` for
+    /// QUIC).
+    pub(crate) fn ja4_fingerprint(&self, transport: char, raw: bool) -> String {
+        let ch = match &self.client_hello {
+            Some(ch) => ch,
+            None => return "".to_string(),
+        };
+        let version = ch
+            .supported_versions
+            .iter()
+            .map(|x| x.0)
+            .filter(|x| !GREASE_TABLE.contains(x))
+            .max()
+            .unwrap_or(ch.version.0);
+        let ciphers = ch
+            .cipher_suites
+            .iter()
+            .map(|x| x.0)
+            .filter(|x| !GREASE_TABLE.contains(x))
+            .collect::<Vec<_>>();
+        let extensions = ch
+            .extension_list
+            .iter()
+            .map(|x| x.0)
+            .filter(|x| !GREASE_TABLE.contains(x))
+            .collect::<Vec<_>>();
+
+        let a = format!(
+            "{}{}{}{:02}{:02}{}",
+            transport,
+            ja4_version(version),
+            if extensions.contains(&EXT_SERVER_NAME) {
+                'd'
+            } else {
+                'i'
+            },
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            ja4_alpn(ch.alpn_protocols.first().map(String::as_str)),
+        );
+        let b = ciphers
+            .iter()
+            .sorted()
+            .map(|x| format!("{:04x}", x))
+            .join(",");
+        let mut c = extensions
+            .iter()
+            .filter(|x| **x != EXT_SERVER_NAME && **x != EXT_ALPN)
+            .sorted()
+            .map(|x| format!("{:04x}", x))
+            .join(",");
+        if !ch.signature_algs.is_empty() {
+            c.push('_');
+            c.push_str(
+                &ch.signature_algs
+                    .iter()
+                    .map(|x| format!("{:04x}", x.0))
+                    .join(","),
+            );
+        }
+        match raw {
+            true => format!("{}_{}_{}", a, b, c),
+            false => format!("{}_{}_{}", a, ja4_hash(&b), ja4_hash(&c)),
+        }
+    }
+
+    /// Computes the JA4S fingerprint of the ServerHello sent over `transport` (`t` for TCP, `q`
+    /// for QUIC).
+    pub(crate) fn ja4s_fingerprint(&self, transport: char, raw: bool) -> String {
+        let sh = match &self.server_hello {
+            Some(sh) => sh,
+            None => return "".to_string(),
+        };
+        let version = match sh.selected_version {
+            Some(version) => version.0,
+            None => sh.version.0,
+        };
+        let extensions = sh
+            .extension_list
+            .iter()
+            .map(|x| x.0)
+            .filter(|x| !GREASE_TABLE.contains(x))
+            .collect::<Vec<_>>();
+
+        let a = format!(
+            "{}{}{:02}{}",
+            transport,
+            ja4_version(version),
+            extensions.len().min(99),
+            ja4_alpn(sh.alpn_protocol.as_deref()),
+        );
+        let c = extensions.iter().map(|x| format!("{:04x}", x)).join(",");
+        match raw {
+            true => format!("{}_{:04x}_{}", a, sh.cipher_suite.0, c),
+            false => format!("{}_{:04x}_{}", a, sh.cipher_suite.0, ja4_hash(&c)),
+        }
+    }
+}
+
+/// Returns the two-character JA4 code for a TLS version identifier.
+fn ja4_version(version: u16) -> &'static str {
+    match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        0x0002 => "s2",
+        0xfeff => "d1",
+        0xfefd => "d2",
+        0xfefc => "d3",
+        _ => "00",
+    }
+}
+
+/// Returns the first and last characters of an ALPN value, or `"00"` if there is none.
+///
+/// Falls back to the first and last characters of its hex representation if either is not
+/// alphanumeric.
+fn ja4_alpn(alpn: Option<&str>) -> String {
+    let alpn = match alpn {
+        Some(alpn) if !alpn.is_empty() => alpn.as_bytes(),
+        _ => return "00".to_string(),
+    };
+    let (first, last) = (alpn[0], alpn[alpn.len() - 1]);
+    if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+        format!("{}{}", first as char, last as char)
+    } else {
+        let hex = hex::encode(alpn);
+        format!("{}{}", &hex[..1], &hex[hex.len() - 1..])
+    }
+}
+
+/// Returns the first 12 characters of the hex-encoded SHA-256 hash of `s`, or `"000000000000"` if
+/// `s` is empty, as used in the JA4+ fingerprints.
+pub(crate) fn ja4_hash(s: &str) -> String {
+    if s.is_empty() {
+        return "000000000000".to_string();
+    }
+    let digest = ring::digest::digest(&ring::digest::SHA256, s.as_bytes());
+    hex::encode(&digest.as_ref()[..6])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tls_parser::{SignatureScheme, TlsCipherSuiteID, TlsExtensionType, TlsVersion};
+
+    /// Cipher suites, extensions, and signature algorithms of the Chrome ClientHello used as the
+    /// JA4 example in the FoxIO specification, with GREASE values added.
+    const CHROME_CIPHERS: &[u16] = &[
+        0x2a2a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013,
+        0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+    ];
+    const CHROME_EXTENSIONS: &[u16] = &[
+        0x8a8a, 0x0000, 0x0017, 0xff01, 0x000a, 0x000b, 0x0023, 0x0010, 0x0005, 0x000d, 0x0012,
+        0x0033, 0x002d, 0x002b, 0x001b, 0x4469, 0x0015, 0x3a3a,
+    ];
+    const CHROME_SIGNATURE_ALGS: &[u16] = &[
+        0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601,
+    ];
+
+    fn client_hello(ciphers: &[u16], extensions: &[u16], signature_algs: &[u16]) -> ClientHello {
+        ClientHello {
+            version: TlsVersion(0x0303),
+            cipher_suites: ciphers.iter().map(|c| TlsCipherSuiteID(*c)).collect(),
+            extension_list: extensions.iter().map(|e| TlsExtensionType(*e)).collect(),
+            signature_algs: signature_algs.iter().map(|s| SignatureScheme(*s)).collect(),
+            ..ClientHello::default()
+        }
+    }
+
+    fn chrome() -> Tls {
+        let mut ch = client_hello(CHROME_CIPHERS, CHROME_EXTENSIONS, CHROME_SIGNATURE_ALGS);
+        ch.server_name = Some("www.example.com".to_string());
+        ch.alpn_protocols = vec!["h2".to_string(), "http/1.1".to_string()];
+        ch.supported_versions = vec![TlsVersion(0x6a6a), TlsVersion(0x0304), TlsVersion(0x0303)];
+        Tls {
+            client_hello: Some(ch),
+            ..Tls::default()
+        }
+    }
+
+    #[test]
+    fn core_tls_ja4() {
+        let tls = chrome();
+        assert_eq!(tls.ja4(), "t13d1516h2_8daaf6152771_e5627efa2ab1");
+        assert_eq!(
+            tls.ja4_r(),
+            "t13d1516h2_002f,0035,009c,009d,1301,1302,1303,c013,c014,c02b,c02c,c02f,c030,cca8,\
+             cca9_0005,000a,000b,000d,0012,0015,0017,001b,0023,002b,002d,0033,4469,ff01_0403,\
+             0804,0401,0503,0805,0501,0806,0601"
+        );
+        assert_eq!(
+            tls.ja4_fingerprint('q', false),
+            "q13d1516h2_8daaf6152771_e5627efa2ab1"
+        );
+
+        // TLS 1.2 without SNI, ALPN, or supported versions
+        let ch = client_hello(
+            &[0xc02f, 0xc030, 0x009e, 0x00ff],
+            &[0x000b, 0x000a, 0x0023, 0x000d, 0x0016, 0x0017],
+            &[0x0601, 0x0603, 0x0501, 0x0503, 0x0401, 0x0403],
+        );
+        let tls = Tls {
+            client_hello: Some(ch),
+            ..Tls::default()
+        };
+        assert_eq!(tls.ja4(), "t12i040600_9c0314f64b5a_f2c24448866c");
+
+        // No cipher suites or extensions
+        let tls = Tls {
+            client_hello: Some(client_hello(&[], &[], &[])),
+            ..Tls::default()
+        };
+        assert_eq!(tls.ja4(), "t12i000000_000000000000_000000000000");
+        assert_eq!(Tls::default().ja4(), "");
+    }
+
+    #[test]
+    fn core_tls_ja4s() {
+        let sh = ServerHello {
+            version: TlsVersion(0x0303),
+            cipher_suite: TlsCipherSuiteID(0x1301),
+            extension_list: vec![TlsExtensionType(0x002b), TlsExtensionType(0x0033)],
+            selected_version: Some(TlsVersion(0x0304)),
+            ..ServerHello::default()
+        };
+        let tls = Tls {
+            server_hello: Some(sh),
+            ..Tls::default()
+        };
+        assert_eq!(tls.ja4s(), "t130200_1301_a56c5b993250");
+        assert_eq!(tls.ja4s_r(), "t130200_1301_002b,0033");
+
+        // TLS 1.2 with ALPN; extensions are hashed in the order they were sent
+        let sh = ServerHello {
+            version: TlsVersion(0x0303),
+            cipher_suite: TlsCipherSuiteID(0xc02f),
+            extension_list: [0xff01, 0x0000, 0x0010, 0x000b, 0x0023]
+                .iter()
+                .map(|e| TlsExtensionType(*e))
+                .collect(),
+            alpn_protocol: Some("h2".to_string()),
+            ..ServerHello::default()
+        };
+        let tls = Tls {
+            server_hello: Some(sh),
+            ..Tls::default()
+        };
+        assert_eq!(tls.ja4s(), "t1205h2_c02f_de3f2fc78737");
+        assert_eq!(Tls::default().ja4s(), "");
+    }
+
+    #[test]
+    fn core_tls_ja4_alpn() {
+        assert_eq!(ja4_alpn(Some("http/1.1")), "h1");
+        assert_eq!(ja4_alpn(Some("h3")), "h3");
+        assert_eq!(ja4_alpn(Some("x")), "xx");
+        assert_eq!(ja4_alpn(Some("")), "00");
+        assert_eq!(ja4_alpn(None), "00");
+        // Non-alphanumeric first or last character
+        assert_eq!(ja4_alpn(Some("x-")), "7d");
+    }
 }
@@ -0,0 +1,79 @@
+//! RFB (VNC) handshake components.
+//!
+//! See [RFC 6143](https://datatracker.ietf.org/doc/html/rfc6143) sections 7.1 and 7.3. Versions
+//! 3.3, 3.7, and 3.8 of the handshake are supported; other minor versions are treated as 3.3, as
+//! the RFC recommends, except that vendor versions above 3.8 are treated as 3.8.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// Length of a ProtocolVersion message.
+pub(super) const VERSION_LEN: usize = 12;
+/// Length of the VNC Authentication challenge and response.
+pub(super) const CHALLENGE_LEN: usize = 16;
+/// Length of the fixed part of a ServerInit message.
+pub(super) const SERVER_INIT_LEN: usize = 24;
+
+pub const SECURITY_INVALID: u8 = 0;
+pub const SECURITY_NONE: u8 = 1;
+pub const SECURITY_VNC_AUTH: u8 = 2;
+
+/// Returns the name of a security type (e.g., `"vnc_auth"`).
+pub fn security_type_name(security_type: u8) -> &'static str {
+    match security_type {
+        SECURITY_INVALID => "invalid",
+        SECURITY_NONE => "none",
+        SECURITY_VNC_AUTH => "vnc_auth",
+        5 => "ra2",
+        6 => "ra2ne",
+        16 => "tight",
+        17 => "ultra",
+        18 => "tls",
+        19 => "vencrypt",
+        20 => "sasl",
+        21 => "md5",
+        22 => "xvp",
+        30 => "ard",
+        _ => "unknown",
+    }
+}
+
+/// An RFB protocol version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct RfbVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl RfbVersion {
+    /// Parses a ProtocolVersion message (e.g., `RFB 003.008\n`).
+    pub(super) fn parse_from(data: &[u8]) -> Result<Self> {
+        if data.len() < VERSION_LEN {
+            bail!("truncated version");
+        }
+        let msg = &data[..VERSION_LEN];
+        if !msg.starts_with(b"RFB ") || msg[7] != b'.' || msg[11] != b'\n' {
+            bail!("invalid version");
+        }
+        let number = |digits: &[u8]| -> Result<u16> { Ok(std::str::from_utf8(digits)?.parse()?) };
+        Ok(RfbVersion {
+            major: number(&msg[4..7])?,
+            minor: number(&msg[8..11])?,
+        })
+    }
+
+    /// Returns the version of the handshake that is used by peers of this version.
+    pub(super) fn handshake(&self) -> u16 {
+        match self.minor {
+            7 => 7,
+            8.. => 8,
+            _ => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for RfbVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
@@ -0,0 +1,98 @@
+//! VNC (RFB) handshake parsing.
+//!
+//! ## Remarks
+//! The parser follows the RFB handshake: the protocol versions of the server and client, the
+//! security types offered by the server and selected by the client, the VNC Authentication
+//! challenge-response (which is not recorded), the security result, and the initialization
+//! messages, which carry the framebuffer size and desktop name. Security types other than None
+//! and VNC Authentication (e.g., VeNCrypt or Apple Remote Desktop) run their own subprotocols,
+//! so the handshake is only followed up to the selection of the security type.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed VNC handshake contents.
+#[derive(Debug, Default, Serialize)]
+pub struct Vnc {
+    /// Protocol version sent by the server.
+    pub server_version: Option<RfbVersion>,
+    /// Protocol version sent by the client.
+    pub client_version: Option<RfbVersion>,
+    /// Security types offered by the server (or the single type chosen by a version 3.3 server).
+    pub security_types: Vec<u8>,
+    /// Security type used, if known.
+    pub security_type: Option<u8>,
+    /// Security result (0 for OK, 1 for failed), if observed.
+    pub security_result: Option<u32>,
+    /// Reason sent by the server for a connection or authentication failure.
+    pub failure_reason: Option<String>,
+    /// `true` if the client requested a shared session.
+    pub shared: Option<bool>,
+    /// Framebuffer width and height in pixels, if the ServerInit message was observed.
+    pub framebuffer: Option<(u16, u16)>,
+    /// Desktop name, if the ServerInit message was observed.
+    pub desktop_name: Option<String>,
+}
+
+impl Vnc {
+    /// Returns the protocol version in use (e.g., `"3.8"`): the client's version, or the
+    /// server's version if the client's was not observed.
+    pub fn version(&self) -> String {
+        self.client_version
+            .or(self.server_version)
+            .map_or(String::new(), |v| v.to_string())
+    }
+
+    /// Returns the protocol version sent by the server (e.g., `"3.889"` for Apple Screen
+    /// Sharing), or `""` if it was not observed.
+    pub fn server_version(&self) -> String {
+        self.server_version.map_or(String::new(), |v| v.to_string())
+    }
+
+    /// Returns the security types offered by the server (e.g., `"vnc_auth tight"`).
+    pub fn security_types(&self) -> String {
+        self.security_types
+            .iter()
+            .map(|t| security_type_name(*t))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the security type used (e.g., `"none"`), or `""` if it was not observed.
+    pub fn security_type(&self) -> &str {
+        self.security_type.map_or("", security_type_name)
+    }
+
+    /// Returns `true` if no authentication was required.
+    pub fn no_auth(&self) -> bool {
+        self.security_type == Some(SECURITY_NONE)
+    }
+
+    /// Returns `true` if the security handshake succeeded.
+    pub fn auth_success(&self) -> bool {
+        match self.security_result {
+            Some(result) => result == 0,
+            // Version 3.3 and 3.7 servers do not send a result for security type None
+            None => self.no_auth() && self.desktop_name.is_some(),
+        }
+    }
+
+    /// Returns the desktop name, or `""` if it was not observed.
+    pub fn desktop_name(&self) -> &str {
+        self.desktop_name.as_deref().unwrap_or("")
+    }
+
+    /// Returns the framebuffer width in pixels, or 0 if it was not observed.
+    pub fn width(&self) -> u16 {
+        self.framebuffer.map_or(0, |(width, _)| width)
+    }
+
+    /// Returns the framebuffer height in pixels, or 0 if it was not observed.
+    pub fn height(&self) -> u16 {
+        self.framebuffer.map_or(0, |(_, height)| height)
+    }
+}
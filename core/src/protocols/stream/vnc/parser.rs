@@ -0,0 +1,538 @@
+//! VNC (RFB) handshake parser.
+//!
+//! Follows the handshake as a state machine, buffering data sent by each peer until the message
+//! expected in the current state is complete.
+
+use super::message::*;
+use super::Vnc;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+
+/// Upper bound on the size of buffered handshake data.
+const MAX_BUFFER_LEN: usize = 4096;
+
+/// Handshake message expected next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    ServerVersion,
+    ClientVersion,
+    SecurityTypes,
+    SecuritySelect,
+    Challenge,
+    ChallengeResponse,
+    SecurityResult,
+    ClientInit,
+    ServerInit,
+    Done,
+}
+
+impl State {
+    /// Returns `true` if the message is sent by the client.
+    fn sent_by_client(&self) -> bool {
+        matches!(
+            self,
+            State::ClientVersion
+                | State::SecuritySelect
+                | State::ChallengeResponse
+                | State::ClientInit
+        )
+    }
+}
+
+/// Parses a single VNC handshake per connection.
+#[derive(Debug)]
+pub struct VncParser {
+    /// Sessions seen. We expect there to only be one.
+    sessions: Vec<Vnc>,
+    state: State,
+    client_buffer: Vec<u8>,
+    server_buffer: Vec<u8>,
+}
+
+impl Default for VncParser {
+    fn default() -> Self {
+        VncParser {
+            sessions: vec![Vnc::default()],
+            state: State::ServerVersion,
+            client_buffer: vec![],
+            server_buffer: vec![],
+        }
+    }
+}
+
+impl VncParser {
+    /// Process data segments in direction `dir`
+    fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        let buffer = match dir {
+            true => &mut self.client_buffer,
+            false => &mut self.server_buffer,
+        };
+        if buffer.len() + data.len() > MAX_BUFFER_LEN {
+            log::debug!("VNC handshake exceeds maximum buffer size");
+            return ParseResult::HeadersDone(0);
+        }
+        buffer.extend_from_slice(data);
+
+        loop {
+            match self.step() {
+                Ok(true) if self.state == State::Done => return ParseResult::HeadersDone(0),
+                Ok(true) => (),
+                Ok(false) => return ParseResult::Continue(0),
+                Err(e) => {
+                    log::debug!("parse error: {:?}", e);
+                    return ParseResult::HeadersDone(0);
+                }
+            }
+        }
+    }
+
+    /// Parses the message expected in the current state, if it is complete. Returns `false` if
+    /// more data is needed.
+    fn step(&mut self) -> Result<bool> {
+        let state = self.state;
+        let handshake = self.handshake();
+        let data = match state.sent_by_client() {
+            true => &self.client_buffer,
+            false => &self.server_buffer,
+        };
+        let vnc = match self.sessions.first_mut() {
+            Some(vnc) => vnc,
+            None => bail!("session removed"),
+        };
+        let (len, next) = match state {
+            State::ServerVersion | State::ClientVersion => {
+                if data.len() < VERSION_LEN {
+                    return Ok(false);
+                }
+                let version = RfbVersion::parse_from(data)?;
+                match state {
+                    State::ServerVersion => {
+                        vnc.server_version = Some(version);
+                        (VERSION_LEN, State::ClientVersion)
+                    }
+                    _ => {
+                        vnc.client_version = Some(version);
+                        (VERSION_LEN, State::SecurityTypes)
+                    }
+                }
+            }
+            State::SecurityTypes if handshake == 3 => {
+                // The server decides the security type
+                if data.len() < 4 {
+                    return Ok(false);
+                }
+                let security_type = BigEndian::read_u32(data);
+                if security_type == SECURITY_INVALID as u32 {
+                    match parse_reason(&data[4..]) {
+                        Some((reason, len)) => {
+                            vnc.failure_reason = Some(reason);
+                            (4 + len, State::Done)
+                        }
+                        None => return Ok(false),
+                    }
+                } else {
+                    let security_type = u8::try_from(security_type)?;
+                    vnc.security_types = vec![security_type];
+                    vnc.security_type = Some(security_type);
+                    (4, after_security_type(security_type, handshake))
+                }
+            }
+            State::SecurityTypes => {
+                let count = match data.first() {
+                    Some(count) => *count as usize,
+                    None => return Ok(false),
+                };
+                if count == 0 {
+                    match parse_reason(&data[1..]) {
+                        Some((reason, len)) => {
+                            vnc.failure_reason = Some(reason);
+                            (1 + len, State::Done)
+                        }
+                        None => return Ok(false),
+                    }
+                } else if data.len() < 1 + count {
+                    return Ok(false);
+                } else {
+                    vnc.security_types = data[1..1 + count].to_vec();
+                    (1 + count, State::SecuritySelect)
+                }
+            }
+            State::SecuritySelect => {
+                let security_type = match data.first() {
+                    Some(security_type) => *security_type,
+                    None => return Ok(false),
+                };
+                vnc.security_type = Some(security_type);
+                (1, after_security_type(security_type, handshake))
+            }
+            State::Challenge | State::ChallengeResponse => {
+                if data.len() < CHALLENGE_LEN {
+                    return Ok(false);
+                }
+                match state {
+                    State::Challenge => (CHALLENGE_LEN, State::ChallengeResponse),
+                    _ => (CHALLENGE_LEN, State::SecurityResult),
+                }
+            }
+            State::SecurityResult => {
+                if data.len() < 4 {
+                    return Ok(false);
+                }
+                let result = BigEndian::read_u32(data);
+                vnc.security_result = Some(result);
+                if result == 0 {
+                    (4, State::ClientInit)
+                } else if handshake == 8 {
+                    match parse_reason(&data[4..]) {
+                        Some((reason, len)) => {
+                            vnc.failure_reason = Some(reason);
+                            (4 + len, State::Done)
+                        }
+                        None => return Ok(false),
+                    }
+                } else {
+                    (4, State::Done)
+                }
+            }
+            State::ClientInit => {
+                let shared = match data.first() {
+                    Some(shared) => *shared,
+                    None => return Ok(false),
+                };
+                vnc.shared = Some(shared != 0);
+                (1, State::ServerInit)
+            }
+            State::ServerInit => {
+                if data.len() < SERVER_INIT_LEN {
+                    return Ok(false);
+                }
+                let name_len = BigEndian::read_u32(&data[20..24]) as usize;
+                if name_len > MAX_BUFFER_LEN - SERVER_INIT_LEN {
+                    bail!("desktop name too long");
+                }
+                if data.len() < SERVER_INIT_LEN + name_len {
+                    return Ok(false);
+                }
+                vnc.framebuffer = Some((
+                    BigEndian::read_u16(&data[0..2]),
+                    BigEndian::read_u16(&data[2..4]),
+                ));
+                let name = &data[SERVER_INIT_LEN..SERVER_INIT_LEN + name_len];
+                vnc.desktop_name = Some(String::from_utf8_lossy(name).into_owned());
+                (SERVER_INIT_LEN + name_len, State::Done)
+            }
+            State::Done => return Ok(false),
+        };
+        log::debug!("VNC {:?}", state);
+        match state.sent_by_client() {
+            true => self.client_buffer.drain(..len),
+            false => self.server_buffer.drain(..len),
+        };
+        self.state = next;
+        Ok(true)
+    }
+
+    /// Returns the version of the handshake in use: 3, 7, or 8.
+    fn handshake(&self) -> u16 {
+        self.sessions
+            .first()
+            .and_then(|vnc| vnc.client_version.or(vnc.server_version))
+            .map_or(3, |version| version.handshake())
+    }
+}
+
+/// Returns the state after the security type is decided.
+fn after_security_type(security_type: u8, handshake: u16) -> State {
+    match security_type {
+        SECURITY_VNC_AUTH => State::Challenge,
+        SECURITY_NONE if handshake == 8 => State::SecurityResult,
+        SECURITY_NONE => State::ClientInit,
+        // Other security types run their own subprotocol
+        _ => State::Done,
+    }
+}
+
+/// Parses a length-prefixed failure reason, returning it and its encoded length. Returns `None`
+/// if the reason is incomplete.
+fn parse_reason(data: &[u8]) -> Option<(String, usize)> {
+    let len = BigEndian::read_u32(data.get(..4)?) as usize;
+    let reason = data.get(4..4 + len)?;
+    Some((String::from_utf8_lossy(reason).into_owned(), 4 + len))
+}
+
+impl ConnParsable for VncParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            self.process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        if pdu.length() == 0 {
+            return ProbeResult::Unsure;
+        }
+        if pdu.dir {
+            // The server speaks first
+            return ProbeResult::NotForUs;
+        }
+
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            if data.len() < VERSION_LEN {
+                if b"RFB ".starts_with(&data[..data.len().min(4)]) {
+                    ProbeResult::Unsure
+                } else {
+                    ProbeResult::NotForUs
+                }
+            } else if RfbVersion::parse_from(data).is_ok() {
+                ProbeResult::Certain
+            } else {
+                ProbeResult::NotForUs
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|vnc| Session {
+            data: SessionData::Vnc(Box::new(vnc)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|vnc| Session {
+                data: SessionData::Vnc(Box::new(vnc)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Stop
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    const VERSION_3_8: &[u8] = b"RFB 003.008\n";
+    const VERSION_3_3: &[u8] = b"RFB 003.003\n";
+    /// Security types offered by a version 3.8 server: VNC Authentication and Tight.
+    const SECURITY_TYPES: &[u8] = b"\x02\x02\x10";
+    /// VNC Authentication challenge or response.
+    const CHALLENGE: &[u8] = b"\
+        \x9a\x4f\x1e\x07\xc3\x58\x22\xb0\x6d\x41\x8e\x53\xf2\x0c\x97\x3b";
+    /// ServerInit for a 1920x1080 desktop named `alice's desktop`.
+    const SERVER_INIT: &[u8] = b"\
+        \x07\x80\x04\x38\x20\x18\x00\x01\x00\xff\x00\xff\x00\xff\x10\x08\
+        \x00\x00\x00\x00\x00\x00\x00\x0f\x61\x6c\x69\x63\x65\x27\x73\x20\
+        \x64\x65\x73\x6b\x74\x6f\x70";
+
+    const FLOW: Flow = Flow::tcp(5900);
+
+    #[test]
+    fn core_vnc_probe() {
+        let parser = VncParser::default();
+        assert_eq!(
+            FLOW.probe(&parser, VERSION_3_8, false),
+            ProbeResult::Certain
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"RFB 003.889\n", false),
+            ProbeResult::Certain
+        );
+        assert_eq!(FLOW.probe(&parser, b"RFB 00", false), ProbeResult::Unsure);
+        assert_eq!(
+            FLOW.probe(&parser, VERSION_3_8, true),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"RFB 003.00x\n", false),
+            ProbeResult::NotForUs
+        );
+        assert_eq!(
+            FLOW.probe(&parser, b"SSH-2.0-OpenSSH_9.6\r\n", false),
+            ProbeResult::NotForUs
+        );
+    }
+
+    #[test]
+    fn core_vnc_auth() {
+        let mut parser = VncParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, VERSION_3_8, false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, VERSION_3_8, true),
+            ParseResult::Continue(0)
+        );
+        // Security types and challenge in one segment
+        let data = [SECURITY_TYPES, CHALLENGE].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &data, false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.state, State::SecuritySelect);
+        let data = [b"\x02", CHALLENGE].concat();
+        assert_eq!(
+            FLOW.parse(&mut parser, &data, true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x00\x00\x00\x00", false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x01", true),
+            ParseResult::Continue(0)
+        );
+        // ServerInit split within the pixel format, then within the desktop name
+        assert_eq!(
+            FLOW.parse(&mut parser, &SERVER_INIT[..10], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &SERVER_INIT[10..30], false),
+            ParseResult::Continue(0)
+        );
+        assert!(parser.sessions[0].desktop_name.is_none());
+        assert_eq!(
+            FLOW.parse(&mut parser, &SERVER_INIT[30..], false),
+            ParseResult::HeadersDone(0)
+        );
+
+        let vnc = &parser.sessions[0];
+        assert_eq!(vnc.version(), "3.8");
+        assert_eq!(vnc.server_version(), "3.8");
+        assert_eq!(vnc.security_types(), "vnc_auth tight");
+        assert_eq!(vnc.security_type(), "vnc_auth");
+        assert!(!vnc.no_auth());
+        assert!(vnc.auth_success());
+        assert_eq!(vnc.shared, Some(true));
+        assert_eq!(vnc.width(), 1920);
+        assert_eq!(vnc.height(), 1080);
+        assert_eq!(vnc.desktop_name(), "alice's desktop");
+    }
+
+    #[test]
+    fn core_vnc_auth_failed() {
+        let mut parser = VncParser::default();
+        FLOW.parse(&mut parser, VERSION_3_8, false);
+        FLOW.parse(&mut parser, VERSION_3_8, true);
+        FLOW.parse(&mut parser, &[SECURITY_TYPES, CHALLENGE].concat(), false);
+        FLOW.parse(&mut parser, &[b"\x02", CHALLENGE].concat(), true);
+        // Result split within the failure reason
+        let result = b"\x00\x00\x00\x01\x00\x00\x00\x15Authentication failed";
+        assert_eq!(
+            FLOW.parse(&mut parser, &result[..12], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, &result[12..], false),
+            ParseResult::HeadersDone(0)
+        );
+
+        let vnc = &parser.sessions[0];
+        assert_eq!(vnc.security_result, Some(1));
+        assert!(!vnc.auth_success());
+        assert_eq!(vnc.failure_reason.as_deref(), Some("Authentication failed"));
+        assert_eq!(vnc.desktop_name(), "");
+    }
+
+    #[test]
+    fn core_vnc_no_auth_3_3() {
+        let mut parser = VncParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, VERSION_3_3, false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, VERSION_3_3, true),
+            ParseResult::Continue(0)
+        );
+        // The server decides the security type, and sends no security result
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x00\x00\x00\x01", false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, b"\x00", true),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(
+            FLOW.parse(&mut parser, SERVER_INIT, false),
+            ParseResult::HeadersDone(0)
+        );
+
+        let vnc = &parser.sessions[0];
+        assert_eq!(vnc.version(), "3.3");
+        assert_eq!(vnc.security_types(), "none");
+        assert!(vnc.no_auth());
+        assert!(vnc.security_result.is_none());
+        assert!(vnc.auth_success());
+        assert_eq!(vnc.shared, Some(false));
+    }
+
+    #[test]
+    fn core_vnc_truncated() {
+        // Handshake cut short within the security types
+        let mut parser = VncParser::default();
+        FLOW.parse(&mut parser, VERSION_3_8, false);
+        FLOW.parse(&mut parser, VERSION_3_8, true);
+        assert_eq!(
+            FLOW.parse(&mut parser, &SECURITY_TYPES[..2], false),
+            ParseResult::Continue(0)
+        );
+        assert_eq!(parser.state, State::SecurityTypes);
+        assert!(parser.sessions[0].security_types.is_empty());
+
+        // Invalid version
+        let mut parser = VncParser::default();
+        assert_eq!(
+            FLOW.parse(&mut parser, b"RFB 003.00x\n", false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.sessions[0].server_version.is_none());
+
+        // Desktop name longer than the buffer limit
+        let mut parser = VncParser::default();
+        FLOW.parse(&mut parser, VERSION_3_3, false);
+        FLOW.parse(&mut parser, VERSION_3_3, true);
+        FLOW.parse(&mut parser, b"\x00\x00\x00\x01", false);
+        FLOW.parse(&mut parser, b"\x01", true);
+        let mut server_init = SERVER_INIT[..SERVER_INIT_LEN].to_vec();
+        server_init[20..24].copy_from_slice(&(MAX_BUFFER_LEN as u32).to_be_bytes());
+        assert_eq!(
+            FLOW.parse(&mut parser, &server_init, false),
+            ParseResult::HeadersDone(0)
+        );
+        assert!(parser.sessions[0].desktop_name.is_none());
+    }
+}
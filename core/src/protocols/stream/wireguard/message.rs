@@ -0,0 +1,83 @@
+//! WireGuard message components.
+//!
+//! See the [WireGuard whitepaper](https://www.wireguard.com/papers/wireguard.pdf), Section 5.4,
+//! for the message formats. All messages begin with a 1-byte type and 3 reserved zero bytes, and
+//! integers are little-endian.
+
+use anyhow::{bail, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Serialize;
+
+pub const MSG_HANDSHAKE_INITIATION: u8 = 1;
+pub const MSG_HANDSHAKE_RESPONSE: u8 = 2;
+pub const MSG_COOKIE_REPLY: u8 = 3;
+pub const MSG_TRANSPORT_DATA: u8 = 4;
+
+pub(super) const HANDSHAKE_INITIATION_LEN: usize = 148;
+pub(super) const HANDSHAKE_RESPONSE_LEN: usize = 92;
+pub(super) const COOKIE_REPLY_LEN: usize = 64;
+/// Length of a transport data message with an empty (keepalive) payload.
+pub(super) const MIN_TRANSPORT_DATA_LEN: usize = 32;
+
+/// A WireGuard message header.
+#[derive(Clone, Debug, Serialize)]
+pub struct WireGuardMessage {
+    /// Direction (`true` if sent by the connection originator).
+    pub dir: bool,
+    /// Message type.
+    pub msg_type: u8,
+    /// Sender index, present in handshake initiation and response messages.
+    pub sender_index: Option<u32>,
+    /// Receiver index, present in handshake response, cookie reply, and transport data messages.
+    pub receiver_index: Option<u32>,
+    /// `true` if the `mac2` field of a handshake message is set, indicating the sender holds a
+    /// cookie from a responder under load.
+    pub mac2: bool,
+    /// Message length in bytes.
+    pub length: usize,
+}
+
+impl WireGuardMessage {
+    pub(super) fn parse_from(data: &[u8], dir: bool) -> Result<Self> {
+        if data.len() < 4 || data[1..4] != [0, 0, 0] {
+            bail!("invalid message header");
+        }
+        let msg_type = data[0];
+        let valid_len = match msg_type {
+            MSG_HANDSHAKE_INITIATION => data.len() == HANDSHAKE_INITIATION_LEN,
+            MSG_HANDSHAKE_RESPONSE => data.len() == HANDSHAKE_RESPONSE_LEN,
+            MSG_COOKIE_REPLY => data.len() == COOKIE_REPLY_LEN,
+            MSG_TRANSPORT_DATA => {
+                data.len() >= MIN_TRANSPORT_DATA_LEN
+                    && (data.len() - MIN_TRANSPORT_DATA_LEN).is_multiple_of(16)
+            }
+            _ => bail!("invalid message type {}", msg_type),
+        };
+        if !valid_len {
+            bail!(
+                "invalid length {} for message type {}",
+                data.len(),
+                msg_type
+            );
+        }
+        let (sender_index, receiver_index) = match msg_type {
+            MSG_HANDSHAKE_INITIATION => (Some(LittleEndian::read_u32(&data[4..8])), None),
+            MSG_HANDSHAKE_RESPONSE => (
+                Some(LittleEndian::read_u32(&data[4..8])),
+                Some(LittleEndian::read_u32(&data[8..12])),
+            ),
+            _ => (None, Some(LittleEndian::read_u32(&data[4..8]))),
+        };
+        // mac2 is the last 16 bytes of handshake messages
+        let mac2 = matches!(msg_type, MSG_HANDSHAKE_INITIATION | MSG_HANDSHAKE_RESPONSE)
+            && data[data.len() - 16..].iter().any(|b| *b != 0);
+        Ok(WireGuardMessage {
+            dir,
+            msg_type,
+            sender_index,
+            receiver_index,
+            mac2,
+            length: data.len(),
+        })
+    }
+}
@@ -0,0 +1,73 @@
+//! WireGuard handshake parsing.
+//!
+//! ## Remarks
+//! The parser identifies WireGuard by a handshake initiation or response message, which have
+//! fixed lengths and reserved zero bytes. Connections first observed mid-tunnel are identified at
+//! the next handshake, which WireGuard performs at least every two minutes. The handshake is
+//! considered done once a handshake response is observed. Later handshakes that rotate keys are
+//! not recorded.
+
+mod message;
+pub mod parser;
+
+pub use self::message::*;
+
+use serde::Serialize;
+
+/// Parsed WireGuard handshake contents.
+#[derive(Debug, Default, Serialize)]
+pub struct WireGuard {
+    /// Handshake messages (initiation, response, and cookie reply), in order of arrival.
+    pub messages: Vec<WireGuardMessage>,
+}
+
+impl WireGuard {
+    fn message(&self, msg_type: u8) -> Option<&WireGuardMessage> {
+        self.messages.iter().find(|m| m.msg_type == msg_type)
+    }
+
+    /// Returns the type of the most recent handshake message (1 for initiation, 2 for response,
+    /// 3 for cookie reply), or 0 if there is none.
+    pub fn message_type(&self) -> u8 {
+        self.messages.last().map_or(0, |m| m.msg_type)
+    }
+
+    /// Returns the initiator's sender index, or 0 if no handshake initiation was observed.
+    pub fn initiator_index(&self) -> u32 {
+        self.message(MSG_HANDSHAKE_INITIATION)
+            .or_else(|| self.message(MSG_HANDSHAKE_RESPONSE))
+            .and_then(|m| match m.msg_type {
+                MSG_HANDSHAKE_INITIATION => m.sender_index,
+                _ => m.receiver_index,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns the responder's sender index, or 0 if no handshake response was observed.
+    pub fn responder_index(&self) -> u32 {
+        self.message(MSG_HANDSHAKE_RESPONSE)
+            .and_then(|m| m.sender_index)
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if the initiator was issued a cookie because the responder was under load.
+    pub fn cookie_reply(&self) -> bool {
+        self.message(MSG_COOKIE_REPLY).is_some()
+    }
+
+    /// Returns `true` if any handshake message carried a `mac2`.
+    pub fn mac2(&self) -> bool {
+        self.messages.iter().any(|m| m.mac2)
+    }
+
+    /// Returns `true` if the connection originator initiated the handshake.
+    pub fn originator_initiated(&self) -> bool {
+        self.message(MSG_HANDSHAKE_INITIATION)
+            .is_some_and(|m| m.dir)
+    }
+
+    /// Returns `true` if a handshake response was observed.
+    pub fn handshake_done(&self) -> bool {
+        self.message(MSG_HANDSHAKE_RESPONSE).is_some()
+    }
+}
@@ -0,0 +1,259 @@
+//! WireGuard parser.
+//!
+//! Parses WireGuard handshake message headers.
+
+use super::message::*;
+use super::WireGuard;
+use crate::conntrack::pdu::L4Pdu;
+use crate::protocols::stream::{
+    ConnParsable, ParseResult, ParsingState, ProbeResult, Session, SessionData,
+};
+
+/// Maximum number of handshake messages recorded before the handshake is considered done.
+const MAX_HANDSHAKE_MESSAGES: usize = 16;
+
+#[derive(Debug, Default)]
+pub struct WireGuardParser {
+    sessions: Vec<WireGuard>,
+}
+
+impl ConnParsable for WireGuardParser {
+    fn parse(&mut self, pdu: &L4Pdu) -> ParseResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length == 0 {
+            return ParseResult::Skipped;
+        }
+
+        if let Ok(data) = (pdu.mbuf_ref()).get_data_slice(offset, length) {
+            if self.sessions.is_empty() {
+                self.sessions.push(WireGuard::default());
+            }
+            self.sessions[0].process(data, pdu.dir)
+        } else {
+            log::warn!("Malformed packet on parse");
+            ParseResult::Skipped
+        }
+    }
+
+    fn probe(&self, pdu: &L4Pdu) -> ProbeResult {
+        let offset = pdu.offset();
+        let length = pdu.length();
+        if length < 4 {
+            return ProbeResult::NotForUs;
+        }
+
+        if let Ok(data) = (pdu.mbuf).get_data_slice(offset, length) {
+            match WireGuardMessage::parse_from(data, pdu.dir) {
+                Ok(msg) => match msg.msg_type {
+                    MSG_HANDSHAKE_INITIATION | MSG_HANDSHAKE_RESPONSE => ProbeResult::Certain,
+                    // Mid-tunnel; wait for the next handshake
+                    _ => ProbeResult::Unsure,
+                },
+                Err(_) => ProbeResult::NotForUs,
+            }
+        } else {
+            log::warn!("Malformed packet");
+            ProbeResult::Error
+        }
+    }
+
+    fn remove_session(&mut self, _session_id: usize) -> Option<Session> {
+        self.sessions.pop().map(|wireguard| Session {
+            data: SessionData::Wireguard(Box::new(wireguard)),
+            id: 0,
+        })
+    }
+
+    fn drain_sessions(&mut self) -> Vec<Session> {
+        self.sessions
+            .drain(..)
+            .map(|wireguard| Session {
+                data: SessionData::Wireguard(Box::new(wireguard)),
+                id: 0,
+            })
+            .collect()
+    }
+
+    fn session_parsed_state(&self) -> ParsingState {
+        ParsingState::Stop
+    }
+
+    fn body_offset(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+impl WireGuard {
+    pub(crate) fn process(&mut self, data: &[u8], dir: bool) -> ParseResult {
+        match WireGuardMessage::parse_from(data, dir) {
+            Ok(msg) if msg.msg_type == MSG_TRANSPORT_DATA => ParseResult::Continue(0),
+            Ok(msg) => {
+                let done = msg.msg_type == MSG_HANDSHAKE_RESPONSE;
+                self.messages.push(msg);
+                if done || self.messages.len() >= MAX_HANDSHAKE_MESSAGES {
+                    return ParseResult::HeadersDone(0);
+                }
+                ParseResult::Continue(0)
+            }
+            Err(e) => {
+                log::debug!("parse error: {:?}", e);
+                ParseResult::Skipped
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "heap_mbuf"))]
+mod tests {
+    use super::*;
+    use crate::protocols::stream::testing::Flow;
+
+    /// Handshake initiation with sender index `0x5d3c1a07` and no `mac2`.
+    const INITIATION: &[u8] = b"\
+        \x01\x00\x00\x00\x07\x1a\x3c\x5d\x52\xf2\x26\x65\xa6\x0c\x12\xd2\
+        \x89\x18\x5d\x95\x0e\xe8\x81\x36\x09\x16\x6f\x6b\x11\x3d\x17\x8d\
+        \x6c\x0f\xd3\x90\x1f\xf2\x39\xa1\xa0\x95\xf2\x0f\x93\x95\x65\x0c\
+        \xf9\x38\x0b\x8e\xdb\x22\x4a\x6b\x24\x8a\x1e\x92\x4e\x8f\xd0\xae\
+        \x2e\x1a\x94\x92\xa3\x30\x5f\x18\x8c\xb6\x10\x90\x0f\x9e\x34\x7f\
+        \xae\x88\x6d\xc6\x50\x77\x95\xec\x74\x5c\x4c\x3f\xcb\x2e\xb2\xc7\
+        \x3e\x14\x93\x4c\x86\x7e\xe0\x57\xba\x72\x49\x9b\xfa\x12\x1e\x83\
+        \x6b\x2a\xc1\x57\x26\xee\x7d\x6b\x0a\xf6\xab\x13\xc3\x8e\x92\xca\
+        \xe0\xd1\x50\x57\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00";
+
+    /// Handshake response with sender index `0xa4b19e62` and no `mac2`.
+    const RESPONSE: &[u8] = b"\
+        \x02\x00\x00\x00\x62\x9e\xb1\xa4\x07\x1a\x3c\x5d\xb1\x59\x98\x7f\
+        \x94\xcc\x74\x11\xd7\x17\xf1\x45\x79\xb2\xaa\x10\x0f\xbb\xb3\x4f\
+        \xa5\x93\xfe\xae\xd2\x72\x48\xb7\x62\xe3\xab\x58\x05\xf0\x76\x5a\
+        \x2b\x9c\x1d\x7e\x0f\x37\xc4\x49\x21\xbd\x3f\x65\x64\xea\xdf\x7f\
+        \x14\x2a\x72\x66\x8c\x47\xe2\x23\xd1\x6e\xdd\x8c\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+    /// Returns a transport data message to `receiver` with `payload_len` bytes of ciphertext.
+    fn transport(receiver: u32, payload_len: usize) -> Vec<u8> {
+        let mut msg = vec![MSG_TRANSPORT_DATA, 0, 0, 0];
+        msg.extend_from_slice(&receiver.to_le_bytes());
+        msg.extend_from_slice(&1u64.to_le_bytes());
+        msg.resize(MIN_TRANSPORT_DATA_LEN + payload_len, 0x3c);
+        msg
+    }
+
+    const FLOW: Flow = Flow::udp(51820);
+
+    fn probe(data: &[u8]) -> ProbeResult {
+        FLOW.probe(&WireGuardParser::default(), data, true)
+    }
+
+    #[test]
+    fn core_wireguard_probe() {
+        assert!(matches!(probe(INITIATION), ProbeResult::Certain));
+        assert!(matches!(probe(RESPONSE), ProbeResult::Certain));
+        // Mid-tunnel
+        assert!(matches!(
+            probe(&transport(0x5d3c1a07, 64)),
+            ProbeResult::Unsure
+        ));
+        // Nonzero reserved bytes
+        let mut invalid = INITIATION.to_vec();
+        invalid[2] = 0x01;
+        assert!(matches!(probe(&invalid), ProbeResult::NotForUs));
+        // Unknown message type
+        invalid = INITIATION.to_vec();
+        invalid[0] = 0x05;
+        assert!(matches!(probe(&invalid), ProbeResult::NotForUs));
+        assert!(matches!(probe(b"\x01\x00\x00"), ProbeResult::NotForUs));
+    }
+
+    #[test]
+    fn core_wireguard_handshake() {
+        let mut parser = WireGuardParser::default();
+        assert!(matches!(
+            FLOW.parse(&mut parser, INITIATION, true),
+            ParseResult::Continue(0)
+        ));
+        // The responder is under load and replies with a cookie, so the initiator retries with
+        // a mac2.
+        let mut cookie = vec![MSG_COOKIE_REPLY, 0, 0, 0];
+        cookie.extend_from_slice(&0x5d3c1a07u32.to_le_bytes());
+        cookie.resize(COOKIE_REPLY_LEN, 0x9b);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &cookie, false),
+            ParseResult::Continue(0)
+        ));
+        let mut retry = INITIATION.to_vec();
+        retry[HANDSHAKE_INITIATION_LEN - 16..].copy_from_slice(&[0x6e; 16]);
+        assert!(matches!(
+            FLOW.parse(&mut parser, &retry, true),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, RESPONSE, false),
+            ParseResult::HeadersDone(0)
+        ));
+
+        let sessions = parser.drain_sessions();
+        match &sessions[0].data {
+            SessionData::Wireguard(wg) => {
+                assert_eq!(wg.messages.len(), 4);
+                assert_eq!(wg.message_type(), MSG_HANDSHAKE_RESPONSE);
+                assert_eq!(wg.initiator_index(), 0x5d3c1a07);
+                assert_eq!(wg.responder_index(), 0xa4b19e62);
+                assert!(wg.cookie_reply());
+                assert!(wg.mac2());
+                assert!(wg.originator_initiated());
+                assert!(wg.handshake_done());
+            }
+            _ => panic!("Expected WireGuard session"),
+        }
+    }
+
+    #[test]
+    fn core_wireguard_mid_tunnel() {
+        let mut parser = WireGuardParser::default();
+        // Transport data is not recorded.
+        assert!(matches!(
+            FLOW.parse(&mut parser, &transport(0xa4b19e62, 0), true),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, &transport(0x5d3c1a07, 1408), false),
+            ParseResult::Continue(0)
+        ));
+        // Rekey initiated by the responder
+        assert!(matches!(
+            FLOW.parse(&mut parser, INITIATION, false),
+            ParseResult::Continue(0)
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, RESPONSE, true),
+            ParseResult::HeadersDone(0)
+        ));
+        assert!(!parser.sessions[0].originator_initiated());
+        assert_eq!(parser.sessions[0].messages.len(), 2);
+    }
+
+    #[test]
+    fn core_wireguard_truncated() {
+        let mut parser = WireGuardParser::default();
+        assert!(matches!(
+            FLOW.parse(
+                &mut parser,
+                &INITIATION[..HANDSHAKE_INITIATION_LEN - 1],
+                true
+            ),
+            ParseResult::Skipped
+        ));
+        assert!(matches!(
+            FLOW.parse(&mut parser, &RESPONSE[..64], false),
+            ParseResult::Skipped
+        ));
+        // Ciphertext not a multiple of the block size
+        assert!(matches!(
+            FLOW.parse(&mut parser, &transport(0x5d3c1a07, 64)[..90], true),
+            ParseResult::Skipped
+        ));
+        assert!(parser.sessions[0].messages.is_empty());
+    }
+}
@@ -1,21 +1,31 @@
 //! Iris runtime.
 //!
 //! The runtime initializes the DPDK environment abstraction layer, creates memory pools, launches
-//! the packet processing cores, and manages logging and display output.
+//! the packet processing cores, and manages logging and display output. With the `heap_mbuf`
+//! feature, the EAL is not initialized and only offline analysis is available.
 
 mod offline;
+#[cfg(not(feature = "heap_mbuf"))]
 mod online;
 use self::offline::*;
+#[cfg(not(feature = "heap_mbuf"))]
 use self::online::*;
 
 use crate::config::*;
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::dpdk;
 use crate::filter::FilterFactory;
+#[cfg(not(feature = "heap_mbuf"))]
+use crate::filter::HardwareReport;
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::lcore::SocketId;
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::memory::mempool::Mempool;
 use crate::subscription::*;
 
+#[cfg(not(feature = "heap_mbuf"))]
 use std::collections::BTreeMap;
+#[cfg(not(feature = "heap_mbuf"))]
 use std::ffi::CString;
 use std::sync::Arc;
 
@@ -29,8 +39,10 @@ pub struct Runtime<S>
 where
     S: Subscribable,
 {
+    #[cfg(not(feature = "heap_mbuf"))]
     #[allow(dead_code)]
     mempools: BTreeMap<SocketId, Mempool>,
+    #[cfg(not(feature = "heap_mbuf"))]
     online: Option<OnlineRuntime<S>>,
     pub(crate) offline: Option<OfflineRuntime<S>>, // Public for testing only
     #[cfg(feature = "timing")]
@@ -52,10 +64,15 @@ where
     /// # Example
     ///
     /// let mut runtime = Runtime::new(config, filter, callback)?;
+    #[cfg(not(feature = "heap_mbuf"))]
     pub fn new(config: RuntimeConfig, factory: fn() -> FilterFactory<S::Tracked>) -> Result<Self> {
         let factory = factory();
         let filter_str = factory.hw_filter_str.clone();
+        let filter_marks = factory.marks.clone();
         let subscription = Arc::new(Subscription::new(factory));
+        if let Some(output) = &config.output {
+            crate::output::init(output)?;
+        }
 
         println!("Initializing Iris runtime...");
         log::info!("Initializing EAL...");
@@ -105,6 +122,7 @@ where
                 online_opts,
                 &mut mempools,
                 filter_str.clone(),
+                &filter_marks,
                 Arc::clone(&subscription),
             )
         });
@@ -128,24 +146,103 @@ where
         })
     }
 
+    /// Creates a new offline runtime from the `config` settings, filter, and callback, without
+    /// initializing DPDK.
+    ///
+    /// # Remarks
+    ///
+    /// Packets are read from the configured pcap into heap-allocated Mbufs. Errors if an online
+    /// configuration is provided.
+    #[cfg(feature = "heap_mbuf")]
+    pub fn new(config: RuntimeConfig, factory: fn() -> FilterFactory<S::Tracked>) -> Result<Self> {
+        if config.online.is_some() {
+            bail!("Online mode requires DPDK; build without the `heap_mbuf` feature");
+        }
+        let subscription = Arc::new(Subscription::new(factory()));
+        if let Some(output) = &config.output {
+            crate::output::init(output)?;
+        }
+
+        println!("Initializing Iris runtime...");
+        let offline = config.offline.as_ref().map(|cfg| {
+            log::info!("Initializing Offline Analysis...");
+            let offline_opts = OfflineOptions {
+                offline: cfg.clone(),
+                conntrack: config.conntrack.clone(),
+            };
+            OfflineRuntime::new(offline_opts, Arc::clone(&subscription))
+        });
+
+        log::info!("Runtime ready.");
+        Ok(Runtime {
+            offline,
+            #[cfg(feature = "timing")]
+            subscription,
+        })
+    }
+
     /// Run Iris for the duration specified in the configuration or until `ctrl-c` to terminate.
     ///
     /// # Example
     ///
     /// runtime.run();
     pub fn run(&mut self) {
+        #[cfg(not(feature = "heap_mbuf"))]
         if let Some(online) = &mut self.online {
             online.run();
-        } else if let Some(offline) = &self.offline {
-            offline.run();
         } else {
-            log::error!("No runtime");
+            self.run_offline();
         }
+        #[cfg(feature = "heap_mbuf")]
+        self.run_offline();
         #[cfg(feature = "timing")]
         {
             self.subscription.timers.display_stats();
             self.subscription.timers.dump_stats();
         }
+        for shed in budget::shed_counts() {
+            log::info!(
+                "Subscription {} shed {} callbacks, {} connections",
+                shed.subscription,
+                shed.callbacks,
+                shed.connections
+            );
+        }
+        if let Err(error) = crate::output::finish() {
+            log::error!("Failed to finish output: {}", error);
+        }
         log::info!("Done.");
     }
+
+    /// Returns the outcome of offloading the filter to each port, once the runtime has run with
+    /// `hardware_assist` enabled.
+    #[cfg(not(feature = "heap_mbuf"))]
+    pub fn hardware_reports(&self) -> Vec<HardwareReport> {
+        match &self.online {
+            Some(online) => online.hardware_reports().to_vec(),
+            None => vec![],
+        }
+    }
+
+    /// Validates the filter against each port's hardware without installing any rules.
+    ///
+    /// # Remarks
+    ///
+    /// Use this before [run](Runtime::run) to check how much of a filter the NICs can apply. Offline
+    /// runtimes have no ports and return an empty list.
+    #[cfg(not(feature = "heap_mbuf"))]
+    pub fn validate_hardware_filter(&self) -> Vec<HardwareReport> {
+        match &self.online {
+            Some(online) => online.validate_hardware_filter(),
+            None => vec![],
+        }
+    }
+
+    fn run_offline(&self) {
+        if let Some(offline) = &self.offline {
+            offline.run();
+        } else {
+            log::error!("No runtime");
+        }
+    }
 }
@@ -1,12 +1,17 @@
 use crate::config::{ConnTrackConfig, OfflineConfig};
 use crate::conntrack::{ConnTracker, TrackerConfig};
 use crate::dpdk;
-use crate::lcore::{CoreId, SocketId};
+use crate::lcore::CoreId;
+#[cfg(not(feature = "heap_mbuf"))]
+use crate::lcore::SocketId;
 use crate::memory::mbuf::Mbuf;
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::memory::mempool::Mempool;
 use crate::subscription::*;
 
+#[cfg(not(feature = "heap_mbuf"))]
 use std::collections::BTreeMap;
+#[cfg(not(feature = "heap_mbuf"))]
 use std::ffi::CString;
 use std::sync::Arc;
 
@@ -17,6 +22,7 @@ pub(crate) struct OfflineRuntime<S>
 where
     S: Subscribable,
 {
+    #[cfg(not(feature = "heap_mbuf"))]
     pub(crate) mempool_name: String,
     pub(crate) subscription: Arc<Subscription<S>>,
     pub(crate) options: OfflineOptions,
@@ -27,6 +33,7 @@ impl<S> OfflineRuntime<S>
 where
     S: Subscribable,
 {
+    #[cfg(not(feature = "heap_mbuf"))]
     pub(crate) fn new(
         options: OfflineOptions,
         mempools: &BTreeMap<SocketId, Mempool>,
@@ -46,6 +53,16 @@ where
         }
     }
 
+    #[cfg(feature = "heap_mbuf")]
+    pub(crate) fn new(options: OfflineOptions, subscription: Arc<Subscription<S>>) -> Self {
+        let core_id = CoreId(unsafe { dpdk::rte_lcore_id() } as u32);
+        OfflineRuntime {
+            subscription,
+            options,
+            id: core_id,
+        }
+    }
+
     pub(crate) fn run(&self) {
         log::info!(
             "Launched offline analysis. Processing pcap: {}",
@@ -55,11 +72,13 @@ where
         let mut nb_pkts = 0;
         let mut nb_bytes = 0;
 
-        let config = TrackerConfig::from(&self.options.conntrack);
+        let mut config = TrackerConfig::from(&self.options.conntrack);
+        config.emulate_shunting();
         let registry = S::Tracked::parsers();
         log::debug!("{:#?}", registry);
         let mut stream_table = ConnTracker::<S::Tracked>::new(config, registry, self.id);
 
+        #[cfg(not(feature = "heap_mbuf"))]
         let mempool_raw = self.get_mempool_raw();
         let pcap = self.options.offline.pcap.as_str();
         let mut cap = Capture::from_file(pcap).expect("Error opening pcap. Aborting.");
@@ -68,8 +87,11 @@ where
             if frame.header.len as usize > self.options.offline.mtu {
                 continue;
             }
+            #[cfg(not(feature = "heap_mbuf"))]
             let mbuf = Mbuf::from_bytes(frame.data, mempool_raw)
                 .expect("Unable to allocate mbuf. Try increasing mempool size.");
+            #[cfg(feature = "heap_mbuf")]
+            let mbuf = Mbuf::from_bytes(frame.data).expect("Unable to allocate mbuf.");
             nb_pkts += 1;
             nb_bytes += mbuf.data_len() as u64;
 
@@ -87,6 +109,7 @@ where
         println!("CPU time: {:?}ms", cpu_time.as_millis());
     }
 
+    #[cfg(not(feature = "heap_mbuf"))]
     pub(crate) fn get_mempool_raw(&self) -> *mut dpdk::rte_mempool {
         let cname = CString::new(self.mempool_name.clone()).expect("Invalid CString conversion");
         unsafe { dpdk::rte_mempool_lookup(cname.as_ptr()) }
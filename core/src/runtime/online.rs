@@ -1,6 +1,6 @@
-use crate::config::{ConnTrackConfig, OnlineConfig, RuntimeConfig};
+use crate::config::{CaptureBackend, ConnTrackConfig, OnlineConfig, RuntimeConfig};
 use crate::dpdk;
-use crate::filter::Filter;
+use crate::filter::{Filter, HardwareReport};
 use crate::lcore::monitor::Monitor;
 use crate::lcore::rx_core::RxCore;
 use crate::lcore::{CoreId, SocketId};
@@ -22,6 +22,7 @@ where
     rx_cores: BTreeMap<CoreId, RxCore<S>>,
     monitor: Option<Monitor>,
     filter: Filter,
+    reports: Vec<HardwareReport>,
     options: OnlineOptions,
 }
 
@@ -34,10 +35,12 @@ where
         options: OnlineOptions,
         mempools: &mut BTreeMap<SocketId, Mempool>,
         hw_filter_str: String,
+        hw_filter_marks: &[(u32, String)],
         subscription: Arc<Subscription<S>>,
     ) -> Self {
-        let hw_filter =
-            Filter::new(&hw_filter_str, &vec![]).expect("Failed to parse collapsed filter");
+        let hw_filter = Filter::new(&hw_filter_str, &vec![])
+            .and_then(|filter| filter.with_marks(hw_filter_marks))
+            .expect("Failed to parse collapsed filter");
         // Set up signal handler
         let is_running = Arc::new(AtomicBool::new(true));
         let r = Arc::clone(&is_running);
@@ -48,8 +51,10 @@ where
 
         log::info!("Initializing Ports...");
         let mut ports: BTreeMap<PortId, Port> = BTreeMap::new();
-        for port_map in options.online.ports.iter() {
-            let port = Port::new(port_map);
+        let backend = options.online.backend;
+        options.online.check_sinks();
+        for (index, port_map) in options.online.ports.iter().enumerate() {
+            let port = Port::new(port_map, backend.device_name(index, port_map));
             let socket_id = port.id.socket_id();
             mempools.entry(socket_id).or_insert_with(|| {
                 // Create a local mempool if user is not polling the port
@@ -100,6 +105,7 @@ where
             rx_cores,
             monitor,
             filter: hw_filter,
+            reports: vec![],
             options,
         }
     }
@@ -177,20 +183,45 @@ where
         println!("Main done. Ran for {:?}", start.elapsed());
     }
 
-    fn start_ports(&self) {
+    // Validates the hardware filter against each port without installing it.
+    pub(crate) fn validate_hardware_filter(&self) -> Vec<HardwareReport> {
+        self.ports
+            .values()
+            .map(|port| self.filter.set_hardware_filter(port, true))
+            .collect()
+    }
+
+    // Returns the hardware filter reports of the ports started.
+    pub(crate) fn hardware_reports(&self) -> &[HardwareReport] {
+        &self.reports
+    }
+
+    fn start_ports(&mut self) {
         log::info!("Starting ports...");
         for port in self.ports.values() {
             port.start();
 
-            if self.options.online.hardware_assist {
-                log::info!("Applying hardware filters...");
-                let res = self.filter.set_hardware_filter(port);
-                match res {
-                    Ok(_) => (),
-                    Err(error) => {
-                        log::warn!("Failed to apply some patterns, passing all traffic through Port {}. Reason: {}", port.id, error);
-                    }
+            if self.options.online.backend != CaptureBackend::Dpdk {
+                log::info!(
+                    "Hardware assist not available with the {:?} backend, passing all traffic through port {}.",
+                    self.options.online.backend,
+                    port.id
+                );
+            } else if self.options.online.hardware_assist {
+                let dry_run = self.options.online.hardware_dry_run;
+                match dry_run {
+                    true => log::info!("Validating hardware filters..."),
+                    false => log::info!("Applying hardware filters..."),
+                }
+                let report = self.filter.set_hardware_filter(port, dry_run);
+                log::info!("{}", report);
+                if !report.is_ok() && !dry_run {
+                    log::warn!(
+                        "Failed to apply some patterns, passing all traffic through Port {}.",
+                        port.id
+                    );
                 }
+                self.reports.push(report);
             } else {
                 log::info!("No hardware assist configured for port {}, passing all traffic through device.", port.id);
             }
@@ -11,6 +11,7 @@ thread_local! {
     pub(crate) static IGNORED_BY_PACKET_FILTER_BYTE: Cell<u64> = const { Cell::new(0) };
     pub(crate) static DROPPED_MIDDLE_OF_CONNECTION_TCP_PKT: Cell<u64> = const { Cell::new(0) };
     pub(crate) static DROPPED_MIDDLE_OF_CONNECTION_TCP_BYTE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static NOT_SAMPLED_PKT: Cell<u64> = const { Cell::new(0) };
     pub(crate) static TOTAL_PKT: Cell<u64> = const { Cell::new(0) };
     pub(crate) static TOTAL_BYTE: Cell<u64> = const { Cell::new(0) };
     pub(crate) static TCP_PKT: Cell<u64> = const { Cell::new(0) };
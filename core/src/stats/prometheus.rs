@@ -41,7 +41,7 @@ use hyper::{
     Request, Response,
 };
 use prometheus_client::{
-    encoding::EncodeLabelSet,
+    encoding::{EncodeLabelSet, LabelSetEncoder},
     metrics::{counter::Counter, family::Family},
     registry::{Registry, Unit},
 };
@@ -51,19 +51,39 @@ use std::{
 };
 
 use super::*;
+use crate::subscription::budget;
 use crate::CoreId;
 
+fn encode_label(
+    encoder: &mut LabelSetEncoder,
+    key: &str,
+    value: impl std::fmt::Display,
+) -> Result<(), std::fmt::Error> {
+    let mut label = encoder.encode_label();
+    let mut label_key = label.encode_label_key()?;
+    label_key.write_str(key)?;
+    let mut label_value = label_key.encode_label_value()?;
+    write!(label_value, "{}", value)?;
+    label_value.finish()
+}
+
 impl EncodeLabelSet for CoreId {
-    fn encode(
-        &self,
-        mut encoder: prometheus_client::encoding::LabelSetEncoder,
-    ) -> Result<(), std::fmt::Error> {
-        let mut label = encoder.encode_label();
-        let mut key = label.encode_label_key()?;
-        key.write_str("core")?;
-        let mut value = key.encode_label_value()?;
-        write!(value, "{}", self.0)?;
-        value.finish()
+    fn encode(&self, mut encoder: LabelSetEncoder) -> Result<(), std::fmt::Error> {
+        encode_label(&mut encoder, "core", self.0)
+    }
+}
+
+/// Labels of statistics kept per core for each budgeted subscription.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct SubscriptionLabels {
+    core: CoreId,
+    subscription: &'static str,
+}
+
+impl EncodeLabelSet for SubscriptionLabels {
+    fn encode(&self, mut encoder: LabelSetEncoder) -> Result<(), std::fmt::Error> {
+        encode_label(&mut encoder, "core", self.core.0)?;
+        encode_label(&mut encoder, "subscription", self.subscription)
     }
 }
 
@@ -73,6 +93,9 @@ struct Families {
     ignored_by_packet_filter_byte: Family<CoreId, Counter>,
     dropped_middle_of_connection_tcp_pkt: Family<CoreId, Counter>,
     dropped_middle_of_connection_tcp_byte: Family<CoreId, Counter>,
+    not_sampled_pkt: Family<CoreId, Counter>,
+    shed_callbacks: Family<SubscriptionLabels, Counter>,
+    shed_connections: Family<SubscriptionLabels, Counter>,
     total_pkt: Family<CoreId, Counter>,
     total_byte: Family<CoreId, Counter>,
     tcp_pkt: Family<CoreId, Counter>,
@@ -191,6 +214,22 @@ pub(crate) static STAT_REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
         Unit::Bytes,
         FAMILIES.dropped_middle_of_connection_tcp_byte.clone(),
     );
+    r.register_with_unit(
+        "iris_not_sampled",
+        "Number of packets dropped due to connection sampling.",
+        Unit::Other("pkts".to_string()),
+        FAMILIES.not_sampled_pkt.clone(),
+    );
+    r.register(
+        "iris_shed_callbacks",
+        "Number of callbacks skipped due to subscription budgets.",
+        FAMILIES.shed_callbacks.clone(),
+    );
+    r.register(
+        "iris_shed_connections",
+        "Number of connections not admitted due to subscription budgets or load shedding.",
+        FAMILIES.shed_connections.clone(),
+    );
     r.register_with_unit(
         "iris_worker_received",
         "Number of total packets received from dpdk.",
@@ -267,6 +306,7 @@ pub(crate) struct PerCorePrometheusStats {
     ignored_by_packet_filter_byte: Counter,
     dropped_middle_of_connection_tcp_pkt: Counter,
     dropped_middle_of_connection_tcp_byte: Counter,
+    not_sampled_pkt: Counter,
     total_pkt: Counter,
     total_byte: Counter,
     tcp_pkt: Counter,
@@ -298,6 +338,7 @@ pub(crate) fn update_thread_local_stats(core: CoreId) {
                 .dropped_middle_of_connection_tcp_byte
                 .get_or_create(&core)
                 .clone(),
+            not_sampled_pkt: FAMILIES.not_sampled_pkt.get_or_create(&core).clone(),
             total_pkt: FAMILIES.total_pkt.get_or_create(&core).clone(),
             total_byte: FAMILIES.total_byte.get_or_create(&core).clone(),
             tcp_pkt: FAMILIES.tcp_pkt.get_or_create(&core).clone(),
@@ -321,6 +362,8 @@ pub(crate) fn update_thread_local_stats(core: CoreId) {
         pr.dropped_middle_of_connection_tcp_byte
             .inc_by(DROPPED_MIDDLE_OF_CONNECTION_TCP_BYTE.get());
         DROPPED_MIDDLE_OF_CONNECTION_TCP_BYTE.set(0);
+        pr.not_sampled_pkt.inc_by(NOT_SAMPLED_PKT.get());
+        NOT_SAMPLED_PKT.set(0);
         pr.total_pkt.inc_by(TOTAL_PKT.get());
         TOTAL_PKT.set(0);
         pr.total_byte.inc_by(TOTAL_BYTE.get());
@@ -342,6 +385,24 @@ pub(crate) fn update_thread_local_stats(core: CoreId) {
         pr.total_cycles.inc_by(TOTAL_CYCLES.get());
         TOTAL_CYCLES.set(0);
     });
+    for shed in budget::take_core_shed_counts() {
+        let labels = SubscriptionLabels {
+            core,
+            subscription: shed.subscription,
+        };
+        if shed.callbacks > 0 {
+            FAMILIES
+                .shed_callbacks
+                .get_or_create(&labels)
+                .inc_by(shed.callbacks);
+        }
+        if shed.connections > 0 {
+            FAMILIES
+                .shed_connections
+                .get_or_create(&labels)
+                .inc_by(shed.connections);
+        }
+    }
 }
 
 pub(crate) async fn serve_req(
@@ -0,0 +1,332 @@
+//! Per-subscription budgets and load shedding.
+//!
+//! Subscriptions can bound the number of callbacks they receive per second and the number of
+//! connections they track at once on each core, and can be assigned a priority class. Connections
+//! are admitted to a budgeted subscription when they first match its filter; connections that are
+//! not admitted are shed for the rest of their lifetime. When the connection table or the RX
+//! queues of a core fill up, the `ConnTracker` raises the shedding level so that low-priority
+//! subscriptions stop admitting new connections first.
+//!
+//! Budgets are declared with callback attributes (e.g., `#[callback("tls,max_rate=1000,priority=low")]`)
+//! and enforced by the generated filter and update code.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+/// Maximum number of subscriptions that can declare a budget.
+pub const MAX_BUDGETS: usize = 64;
+
+/// Priority class of a subscription under overload.
+///
+/// Classes are ordered from the first to be shed (`Low`) to the last (`High`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl FromStr for Priority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            _ => bail!("Unknown priority class: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::Low => write!(f, "low"),
+            Priority::Normal => write!(f, "normal"),
+            Priority::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Budget declared by a subscription.
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    /// Name of the callback (or callback group).
+    pub subscription: &'static str,
+    /// Maximum number of callbacks invoked per second on each core.
+    pub max_rate: Option<u32>,
+    /// Maximum number of connections tracked at once on each core.
+    pub max_conns: Option<usize>,
+    /// Priority class under overload.
+    pub priority: Priority,
+}
+
+/// Number of callbacks and connections shed by a subscription across all cores.
+#[derive(Debug, Clone)]
+pub struct ShedCount {
+    pub subscription: &'static str,
+    pub callbacks: u64,
+    pub connections: u64,
+}
+
+/// Budgets of all subscriptions, indexed by the ID assigned by the compiler.
+static BUDGETS: OnceLock<Vec<(Budget, AtomicU64, AtomicU64)>> = OnceLock::new();
+
+thread_local! {
+    /// Per-core usage of each budget.
+    static USAGE: RefCell<Vec<Usage>> = const { RefCell::new(Vec::new()) };
+    /// Subscriptions with a priority at or below this class do not admit new connections.
+    static SHED_LEVEL: Cell<Option<Priority>> = const { Cell::new(None) };
+}
+
+/// Registers the budgets of the application's subscriptions. Only the first call has an effect.
+pub(crate) fn init(budgets: &[Budget]) {
+    assert!(
+        budgets.len() <= MAX_BUDGETS,
+        "At most {} subscriptions can declare a budget",
+        MAX_BUDGETS
+    );
+    let _ = BUDGETS.set(
+        budgets
+            .iter()
+            .map(|b| (*b, AtomicU64::new(0), AtomicU64::new(0)))
+            .collect(),
+    );
+}
+
+/// Sets the shedding level of the current core.
+pub(crate) fn set_shed_level(level: Option<Priority>) {
+    SHED_LEVEL.set(level);
+}
+
+/// Returns the shedding level of the current core.
+#[cfg(test)]
+pub(crate) fn shed_level() -> Option<Priority> {
+    SHED_LEVEL.get()
+}
+
+/// Returns the number of callbacks and connections shed by each budgeted subscription.
+pub fn shed_counts() -> Vec<ShedCount> {
+    match BUDGETS.get() {
+        Some(budgets) => budgets
+            .iter()
+            .map(|(budget, callbacks, connections)| ShedCount {
+                subscription: budget.subscription,
+                callbacks: callbacks.load(Ordering::Relaxed),
+                connections: connections.load(Ordering::Relaxed),
+            })
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Returns the number of callbacks and connections shed by each budgeted subscription on the
+/// current core since the last call.
+#[cfg(feature = "prometheus")]
+pub(crate) fn take_core_shed_counts() -> Vec<ShedCount> {
+    let budgets = match BUDGETS.get() {
+        Some(budgets) => budgets,
+        None => return vec![],
+    };
+    USAGE.with(|usage| {
+        usage
+            .borrow_mut()
+            .iter_mut()
+            .zip(budgets.iter())
+            .map(|(usage, (budget, _, _))| ShedCount {
+                subscription: budget.subscription,
+                callbacks: std::mem::take(&mut usage.shed_callbacks),
+                connections: std::mem::take(&mut usage.shed_connections),
+            })
+            .collect()
+    })
+}
+
+/// Budgeted subscriptions that a connection has been admitted to or shed from.
+///
+/// Admitted connections are released from their budgets when this is dropped.
+#[derive(Debug, Default)]
+pub struct Admitted {
+    admitted: u64,
+    shed: u64,
+}
+
+impl Drop for Admitted {
+    fn drop(&mut self) {
+        if self.admitted == 0 {
+            return;
+        }
+        USAGE.with(|usage| {
+            let mut usage = usage.borrow_mut();
+            for (idx, core) in usage.iter_mut().enumerate() {
+                if self.admitted & (1 << idx) != 0 {
+                    core.conns = core.conns.saturating_sub(1);
+                }
+            }
+        });
+    }
+}
+
+/// Usage of a budget on a single core.
+#[derive(Debug, Clone)]
+pub(crate) struct Usage {
+    /// Number of admitted connections.
+    pub(crate) conns: usize,
+    /// Start of the current one-second rate limiting window.
+    window_start: Instant,
+    /// Number of callbacks invoked in the current window.
+    window_calls: u32,
+    /// Number of callbacks shed since the statistics were last exported.
+    pub(crate) shed_callbacks: u64,
+    /// Number of connections shed since the statistics were last exported.
+    pub(crate) shed_connections: u64,
+}
+
+impl Usage {
+    pub(crate) fn new(now: Instant) -> Self {
+        Usage {
+            conns: 0,
+            window_start: now,
+            window_calls: 0,
+            shed_callbacks: 0,
+            shed_connections: 0,
+        }
+    }
+
+    /// Returns `true` if a new connection can be admitted.
+    pub(crate) fn admit_conn(&mut self, budget: &Budget, shed_level: Option<Priority>) -> bool {
+        if shed_level.is_some_and(|level| budget.priority <= level)
+            || budget.max_conns.is_some_and(|max| self.conns >= max)
+        {
+            self.shed_connections += 1;
+            return false;
+        }
+        self.conns += 1;
+        true
+    }
+
+    /// Returns `true` if a callback can be invoked at `now`.
+    pub(crate) fn admit_callback(&mut self, budget: &Budget, now: Instant) -> bool {
+        let max_rate = match budget.max_rate {
+            Some(max_rate) => max_rate,
+            None => return true,
+        };
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.window_calls = 0;
+        }
+        if self.window_calls >= max_rate {
+            self.shed_callbacks += 1;
+            return false;
+        }
+        self.window_calls += 1;
+        true
+    }
+}
+
+/// Applies `f` to the budget `idx` and its usage on the current core.
+fn with_usage<R>(idx: usize, f: impl FnOnce(&Budget, &mut Usage) -> R) -> Option<R> {
+    let budgets = BUDGETS.get()?;
+    let (budget, _, _) = budgets.get(idx)?;
+    USAGE.with(|usage| {
+        let mut usage = usage.borrow_mut();
+        if usage.len() < budgets.len() {
+            usage.resize(budgets.len(), Usage::new(Instant::now()));
+        }
+        Some(f(budget, &mut usage[idx]))
+    })
+}
+
+/// Admits the connection to subscription `idx`, if it has not already been admitted or shed.
+/// Returns `true` if the connection is admitted.
+///
+/// Invoked by generated code when a connection matches the subscription's filter.
+#[doc(hidden)]
+pub fn admit_conn(idx: usize, admitted: &mut Admitted) -> bool {
+    let bit = 1 << idx;
+    if admitted.admitted & bit != 0 {
+        return true;
+    }
+    if admitted.shed & bit != 0 {
+        return false;
+    }
+    let shed_level = SHED_LEVEL.get();
+    match with_usage(idx, |budget, usage| usage.admit_conn(budget, shed_level)) {
+        Some(true) | None => {
+            admitted.admitted |= bit;
+            true
+        }
+        Some(false) => {
+            admitted.shed |= bit;
+            if let Some((_, _, connections)) = BUDGETS.get().and_then(|b| b.get(idx)) {
+                connections.fetch_add(1, Ordering::Relaxed);
+            }
+            false
+        }
+    }
+}
+
+/// Returns `true` if a callback of subscription `idx` can be invoked on the connection.
+///
+/// Invoked by generated code before each callback of a budgeted subscription.
+#[doc(hidden)]
+pub fn admit_callback(idx: usize, admitted: &mut Admitted) -> bool {
+    if !admit_conn(idx, admitted) {
+        return false;
+    }
+    match with_usage(idx, |budget, usage| {
+        usage.admit_callback(budget, Instant::now())
+    }) {
+        Some(false) => {
+            if let Some((_, callbacks, _)) = BUDGETS.get().and_then(|b| b.get(idx)) {
+                callbacks.fetch_add(1, Ordering::Relaxed);
+            }
+            false
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_budget_usage() {
+        let budget = Budget {
+            subscription: "test",
+            max_rate: Some(2),
+            max_conns: Some(2),
+            priority: Priority::Normal,
+        };
+        let start = Instant::now();
+        let mut usage = Usage::new(start);
+
+        // Connections are admitted up to the limit
+        assert!(usage.admit_conn(&budget, None));
+        assert!(usage.admit_conn(&budget, Some(Priority::Low)));
+        assert!(!usage.admit_conn(&budget, None));
+        usage.conns -= 1;
+        assert!(
+            !usage.admit_conn(&budget, Some(Priority::Normal)),
+            "Normal priority should be shed at the normal shedding level."
+        );
+        assert!(usage.admit_conn(&budget, None));
+        assert_eq!(usage.shed_connections, 2);
+
+        // Callbacks are limited per one-second window
+        assert!(usage.admit_callback(&budget, start));
+        assert!(usage.admit_callback(&budget, start + Duration::from_millis(10)));
+        assert!(!usage.admit_callback(&budget, start + Duration::from_millis(20)));
+        assert!(usage.admit_callback(&budget, start + Duration::from_millis(1000)));
+        assert_eq!(usage.shed_callbacks, 1);
+    }
+}
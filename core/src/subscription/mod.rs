@@ -8,6 +8,7 @@ use crate::protocols::packet::udp::UDP_PROTOCOL;
 use crate::protocols::stream::ParserRegistry;
 use crate::stats::{StatExt, TCP_BYTE, TCP_PKT, UDP_BYTE, UDP_PKT};
 
+pub mod budget;
 pub mod data;
 #[doc(hidden)]
 pub mod filter;
@@ -71,6 +72,7 @@ where
     S: Subscribable,
 {
     pub fn new(factory: FilterFactory<S::Tracked>) -> Self {
+        budget::init(&factory.budgets);
         Subscription {
             packet_filter: factory.packet_filter,
             state_tx_filter: factory.state_tx,
@@ -97,10 +99,8 @@ where
         }
     }
 
-    // TODO: packet continue filter should ideally be built at
-    // compile-time based on what the NIC supports (what has
-    // already been filtered out in HW).
-    // Ideally, NIC would `mark` mbufs as `deliver` and/or `continue`.
+    // Packets MARKed by a hardware rule resume the filter from the
+    // node the rule matched; the full filter only runs on unmarked traffic.
     /// Invokes the software packet filter.
     /// Used for each packet to determine
     /// forwarding to conn. tracker. /// TMP - todo return bool
@@ -0,0 +1,24 @@
+//! A BitTorrent peer wire handshake or DHT transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::bittorrent::Bittorrent`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::bittorrent::Bittorrent;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=bittorrent"))]
+pub type BittorrentSession = Box<Bittorrent>;
+
+impl FromSession for BittorrentSession {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("BittorrentSession,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Bittorrent(bittorrent) = &session.data {
+            return Some(bittorrent);
+        }
+        None
+    }
+}
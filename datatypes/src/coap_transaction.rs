@@ -0,0 +1,24 @@
+//! A CoAP request and its response.
+//! Subscribable alias for [`iris_core::protocols::stream::coap::Coap`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::coap::Coap;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=coap"))]
+pub type CoapTransaction = Box<Coap>;
+
+impl FromSession for CoapTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("CoapTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Coap(coap) = &session.data {
+            return Some(coap);
+        }
+        None
+    }
+}
@@ -301,3 +301,62 @@ impl Tracked for ConnHistory {
     #[inline]
     fn phase_tx(&mut self, _: &StateTxData) {}
 }
+
+use iris_core::protocols::packet::{ethernet::Ethernet, ipv4::Ipv4, ipv6::Ipv6, tcp::Tcp};
+use iris_core::protocols::packet::{tcp::SYN, Packet};
+
+/// TCP fingerprint of the client SYN.
+///
+/// See [`Tcp::ja4t`] for the format of the JA4T fingerprint. Empty if no client SYN was observed
+/// (e.g., UDP or connections picked up mid-stream).
+#[derive(Default, Debug, serde::Serialize, Clone)]
+#[cfg_attr(not(feature = "skip_expand"), datatype("L4Terminated"))]
+pub struct TcpFingerprint {
+    pub ja4t: String,
+}
+
+impl TcpFingerprint {
+    /// The JA4T fingerprint of the client SYN, or `""` if it was not observed.
+    pub fn ja4t(&self) -> &str {
+        &self.ja4t
+    }
+
+    fn syn_ja4t(pdu: &L4Pdu) -> Option<String> {
+        if !pdu.dir || pdu.flags() != SYN {
+            return None;
+        }
+        let eth = pdu.mbuf_ref().parse_to::<Ethernet>().ok()?;
+        if let Ok(ipv4) = eth.parse_to::<Ipv4>() {
+            return ipv4.parse_to::<Tcp>().ok().map(|tcp| tcp.ja4t());
+        }
+        let ipv6 = eth.parse_to::<Ipv6>().ok()?;
+        ipv6.parse_to::<Tcp>().ok().map(|tcp| tcp.ja4t())
+    }
+}
+
+impl Tracked for TcpFingerprint {
+    fn new(first_pkt: &L4Pdu) -> Self {
+        Self {
+            ja4t: Self::syn_ja4t(first_pkt).unwrap_or_default(),
+        }
+    }
+
+    #[inline]
+    fn clear(&mut self) {}
+
+    #[inline]
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("TcpFingerprint,level=L4InPayload")
+    )]
+    fn update(&mut self, pdu: &L4Pdu) {
+        if self.ja4t.is_empty() {
+            if let Some(ja4t) = Self::syn_ja4t(pdu) {
+                self.ja4t = ja4t;
+            }
+        }
+    }
+
+    #[inline]
+    fn phase_tx(&mut self, _: &StateTxData) {}
+}
@@ -0,0 +1,24 @@
+//! A DHCP transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::dhcp::Dhcp`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::dhcp::Dhcp;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=dhcp"))]
+pub type DhcpTransaction = Box<Dhcp>;
+
+impl FromSession for DhcpTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("DhcpTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Dhcp(dhcp) = &session.data {
+            return Some(dhcp);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! A DHCPv6 transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::dhcpv6::Dhcpv6`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::dhcpv6::Dhcpv6;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=dhcpv6"))]
+pub type Dhcpv6Transaction = Box<Dhcpv6>;
+
+impl FromSession for Dhcpv6Transaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("Dhcpv6Transaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Dhcpv6(dhcpv6) = &session.data {
+            return Some(dhcpv6);
+        }
+        None
+    }
+}
@@ -0,0 +1,319 @@
+//! IPFIX and NetFlow v9 message encoding.
+
+use super::{FlowExportProtocol, FlowRecord};
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Template of IPv4 records.
+pub(super) const TEMPLATE_V4: u16 = 256;
+/// Template of IPv6 records.
+pub(super) const TEMPLATE_V6: u16 = 257;
+
+/// Maximum length of strings in IPFIX records, which fits a one-byte length prefix.
+const IPFIX_MAX_STRING: usize = 254;
+/// Lengths of the fixed-length SNI, HTTP host, and JA3 fields in NetFlow v9 records.
+const NETFLOW9_SNI_LEN: u16 = 128;
+const NETFLOW9_HOST_LEN: u16 = 128;
+const NETFLOW9_JA3_LEN: u16 = 32;
+/// Length of IPFIX variable-length fields in templates.
+const VARIABLE_LEN: u16 = u16::MAX;
+
+/// A field of exported records.
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    SrcAddr,
+    DstAddr,
+    SrcPort,
+    DstPort,
+    Protocol,
+    TcpFlags,
+    Octets,
+    Packets,
+    Start,
+    End,
+    EndReason,
+    Sni,
+    HttpHost,
+    Ja3,
+}
+
+const FIELDS: [Field; 11] = [
+    Field::SrcAddr,
+    Field::DstAddr,
+    Field::SrcPort,
+    Field::DstPort,
+    Field::Protocol,
+    Field::TcpFlags,
+    Field::Octets,
+    Field::Packets,
+    Field::Start,
+    Field::End,
+    Field::EndReason,
+];
+
+const L7_FIELDS: [Field; 3] = [Field::Sni, Field::HttpHost, Field::Ja3];
+
+/// Encodes templates, records, and messages of a single protocol.
+#[derive(Debug)]
+pub(super) struct Encoder {
+    protocol: FlowExportProtocol,
+    /// Private Enterprise Number of the L7 fields, which are only exported if set.
+    enterprise: Option<u32>,
+    /// Start of the NetFlow v9 system uptime.
+    boot: SystemTime,
+}
+
+impl Encoder {
+    pub(super) fn new(protocol: FlowExportProtocol, enterprise: Option<u32>) -> Self {
+        Encoder {
+            protocol,
+            enterprise,
+            boot: SystemTime::now(),
+        }
+    }
+
+    pub(super) fn protocol(&self) -> FlowExportProtocol {
+        self.protocol
+    }
+
+    fn fields(&self) -> impl Iterator<Item = Field> + '_ {
+        let l7: &[Field] = match self.enterprise {
+            Some(_) => &L7_FIELDS,
+            None => &[],
+        };
+        FIELDS.iter().chain(l7).copied()
+    }
+
+    /// Returns the information element ID, length, and enterprise number of `field`.
+    fn spec(&self, field: Field, v6: bool) -> (u16, u16, Option<u32>) {
+        let ipfix = self.protocol == FlowExportProtocol::Ipfix;
+        match field {
+            Field::SrcAddr if v6 => (27, 16, None),
+            Field::SrcAddr => (8, 4, None),
+            Field::DstAddr if v6 => (28, 16, None),
+            Field::DstAddr => (12, 4, None),
+            Field::SrcPort => (7, 2, None),
+            Field::DstPort => (11, 2, None),
+            Field::Protocol => (4, 1, None),
+            Field::TcpFlags if ipfix => (6, 2, None),
+            Field::TcpFlags => (6, 1, None),
+            Field::Octets => (1, 8, None),
+            Field::Packets => (2, 8, None),
+            // flowStartMilliseconds and flowEndMilliseconds
+            Field::Start if ipfix => (152, 8, None),
+            Field::End if ipfix => (153, 8, None),
+            // FIRST_SWITCHED and LAST_SWITCHED, in milliseconds of system uptime
+            Field::Start => (22, 4, None),
+            Field::End => (21, 4, None),
+            Field::EndReason => (136, 1, None),
+            Field::Sni if ipfix => (0x8001, VARIABLE_LEN, self.enterprise),
+            Field::HttpHost if ipfix => (0x8002, VARIABLE_LEN, self.enterprise),
+            Field::Ja3 if ipfix => (0x8003, VARIABLE_LEN, self.enterprise),
+            Field::Sni => (0x8001, NETFLOW9_SNI_LEN, None),
+            Field::HttpHost => (0x8002, NETFLOW9_HOST_LEN, None),
+            Field::Ja3 => (0x8003, NETFLOW9_JA3_LEN, None),
+        }
+    }
+
+    /// Length of message headers.
+    pub(super) fn header_len(&self) -> usize {
+        match self.protocol {
+            FlowExportProtocol::Ipfix => 16,
+            FlowExportProtocol::Netflow9 => 20,
+        }
+    }
+
+    /// Encodes the set of all templates. Returns its ID and body.
+    pub(super) fn templates(&self) -> (u16, Vec<u8>) {
+        let mut body = vec![];
+        for (id, v6) in [(TEMPLATE_V4, false), (TEMPLATE_V6, true)] {
+            body.extend_from_slice(&id.to_be_bytes());
+            body.extend_from_slice(&(self.fields().count() as u16).to_be_bytes());
+            for field in self.fields() {
+                let (ie, len, enterprise) = self.spec(field, v6);
+                body.extend_from_slice(&ie.to_be_bytes());
+                body.extend_from_slice(&len.to_be_bytes());
+                if let Some(enterprise) = enterprise {
+                    body.extend_from_slice(&enterprise.to_be_bytes());
+                }
+            }
+        }
+        let id = match self.protocol {
+            FlowExportProtocol::Ipfix => 2,
+            FlowExportProtocol::Netflow9 => 0,
+        };
+        (id, body)
+    }
+
+    /// Encodes the data record of the originator (`orig`) or responder direction of `record`.
+    /// Returns its template ID and encoding.
+    pub(super) fn record(&self, record: &FlowRecord, orig: bool) -> (u16, Vec<u8>) {
+        let (src, dst, counters) = match orig {
+            true => (record.five_tuple.orig, record.five_tuple.resp, record.orig),
+            false => (record.five_tuple.resp, record.five_tuple.orig, record.resp),
+        };
+        let ipfix = self.protocol == FlowExportProtocol::Ipfix;
+        let v6 = src.is_ipv6();
+        let mut buf = vec![];
+        for field in self.fields() {
+            match field {
+                Field::SrcAddr => put_addr(&mut buf, src),
+                Field::DstAddr => put_addr(&mut buf, dst),
+                Field::SrcPort => buf.extend_from_slice(&src.port().to_be_bytes()),
+                Field::DstPort => buf.extend_from_slice(&dst.port().to_be_bytes()),
+                Field::Protocol => buf.push(record.five_tuple.proto as u8),
+                Field::TcpFlags if ipfix => {
+                    buf.extend_from_slice(&(counters.tcp_flags as u16).to_be_bytes())
+                }
+                Field::TcpFlags => buf.push(counters.tcp_flags),
+                Field::Octets => buf.extend_from_slice(&counters.bytes.to_be_bytes()),
+                Field::Packets => buf.extend_from_slice(&counters.pkts.to_be_bytes()),
+                Field::Start => self.put_time(&mut buf, record.start),
+                Field::End => self.put_time(&mut buf, record.end),
+                Field::EndReason => buf.push(record.end_reason as u8),
+                Field::Sni => self.put_string(&mut buf, &record.sni, self.spec(field, v6).1),
+                Field::HttpHost => {
+                    self.put_string(&mut buf, &record.http_host, self.spec(field, v6).1)
+                }
+                Field::Ja3 => self.put_string(&mut buf, &record.ja3, self.spec(field, v6).1),
+            }
+        }
+        let id = match v6 {
+            true => TEMPLATE_V6,
+            false => TEMPLATE_V4,
+        };
+        (id, buf)
+    }
+
+    /// Maximum length of an encoded data record.
+    fn max_record_len(&self) -> usize {
+        self.fields()
+            .map(|field| match self.spec(field, true).1 {
+                VARIABLE_LEN => 1 + IPFIX_MAX_STRING,
+                len => len as usize,
+            })
+            .sum()
+    }
+
+    /// Smallest message that fits the templates and a data record.
+    pub(super) fn min_message_size(&self) -> usize {
+        let (_, templates) = self.templates();
+        self.header_len() + 4 + templates.len() + 4 + self.max_record_len() + 3
+    }
+
+    /// Length of a set with `body`, including padding.
+    pub(super) fn set_len(&self, body: usize) -> usize {
+        match self.protocol {
+            // NetFlow v9 flowsets are padded to 32 bits.
+            FlowExportProtocol::Netflow9 => (4 + body).div_ceil(4) * 4,
+            FlowExportProtocol::Ipfix => 4 + body,
+        }
+    }
+
+    /// Encodes a message with `sets` (ID and body).
+    ///
+    /// `sequence` is the number of data records (IPFIX) or messages (NetFlow v9) previously
+    /// exported, and `count` the number of template and data records in the message.
+    pub(super) fn message(
+        &self,
+        sets: &[(u16, Vec<u8>)],
+        count: u16,
+        sequence: u32,
+        domain: u32,
+    ) -> Vec<u8> {
+        let now = SystemTime::now();
+        let export_secs = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or_default();
+        let mut msg = vec![];
+        match self.protocol {
+            FlowExportProtocol::Ipfix => {
+                msg.extend_from_slice(&10u16.to_be_bytes());
+                // Length, filled in below.
+                msg.extend_from_slice(&0u16.to_be_bytes());
+                msg.extend_from_slice(&export_secs.to_be_bytes());
+                msg.extend_from_slice(&sequence.to_be_bytes());
+                msg.extend_from_slice(&domain.to_be_bytes());
+            }
+            FlowExportProtocol::Netflow9 => {
+                msg.extend_from_slice(&9u16.to_be_bytes());
+                msg.extend_from_slice(&count.to_be_bytes());
+                msg.extend_from_slice(&self.uptime_ms(now).to_be_bytes());
+                msg.extend_from_slice(&export_secs.to_be_bytes());
+                msg.extend_from_slice(&sequence.to_be_bytes());
+                msg.extend_from_slice(&domain.to_be_bytes());
+            }
+        }
+        for (id, body) in sets {
+            let len = self.set_len(body.len());
+            msg.extend_from_slice(&id.to_be_bytes());
+            msg.extend_from_slice(&(len as u16).to_be_bytes());
+            msg.extend_from_slice(body);
+            msg.resize(msg.len() + len - 4 - body.len(), 0);
+        }
+        if self.protocol == FlowExportProtocol::Ipfix {
+            let len = (msg.len() as u16).to_be_bytes();
+            msg[2..4].copy_from_slice(&len);
+        }
+        msg
+    }
+
+    fn uptime_ms(&self, time: SystemTime) -> u32 {
+        time.duration_since(self.boot)
+            .map(|d| d.as_millis() as u32)
+            .unwrap_or_default()
+    }
+
+    fn put_time(&self, buf: &mut Vec<u8>, time: SystemTime) {
+        match self.protocol {
+            FlowExportProtocol::Ipfix => {
+                let ms = time
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or_default();
+                buf.extend_from_slice(&ms.to_be_bytes());
+            }
+            FlowExportProtocol::Netflow9 => {
+                buf.extend_from_slice(&self.uptime_ms(time).to_be_bytes())
+            }
+        }
+    }
+
+    /// Writes a variable-length (IPFIX) or zero-padded fixed-length (NetFlow v9) string.
+    fn put_string(&self, buf: &mut Vec<u8>, value: &Option<String>, len: u16) {
+        let value = value.as_deref().unwrap_or_default();
+        match len {
+            VARIABLE_LEN => {
+                let value = truncate(value, IPFIX_MAX_STRING);
+                buf.push(value.len() as u8);
+                buf.extend_from_slice(value.as_bytes());
+            }
+            len => {
+                let value = truncate(value, len as usize);
+                buf.extend_from_slice(value.as_bytes());
+                buf.resize(buf.len() + len as usize - value.len(), 0);
+            }
+        }
+    }
+}
+
+fn put_addr(buf: &mut Vec<u8>, addr: SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => buf.extend_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => buf.extend_from_slice(&ip.octets()),
+    }
+}
+
+/// Truncates `s` to at most `len` bytes, on a character boundary.
+fn truncate(s: &str, len: usize) -> &str {
+    if s.len() <= len {
+        return s;
+    }
+    let mut end = len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
@@ -0,0 +1,490 @@
+//! Per-core flow exporter.
+
+use super::{Exporter, FlowCounters, FlowEndReason, FlowExportProtocol, FlowRecord};
+use iris_core::conntrack::conn_id::FiveTuple;
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Maximum time records are buffered while the core exports records, and interval between checks
+/// of the inactive timeout.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A connection passed to `update`.
+struct TrackedFlow {
+    /// The most recent record of the connection.
+    last: FlowRecord,
+    /// Originator and responder counters at the last export.
+    exported: (FlowCounters, FlowCounters),
+    /// Start of the packets not yet exported.
+    segment_start: SystemTime,
+}
+
+impl TrackedFlow {
+    fn pending(&self) -> bool {
+        self.last.orig.pkts + self.last.resp.pkts > self.exported.0.pkts + self.exported.1.pkts
+    }
+
+    /// Returns the packets and bytes of `record` not yet exported, and marks them as exported.
+    fn export(&mut self, record: &FlowRecord, reason: FlowEndReason) -> FlowRecord {
+        let mut delta = record.clone();
+        delta.start = self.segment_start;
+        delta.orig = record.orig.since(&self.exported.0);
+        delta.resp = record.resp.since(&self.exported.1);
+        delta.end_reason = reason;
+        self.exported = (record.orig, record.resp);
+        self.segment_start = record.end;
+        delta
+    }
+}
+
+/// Exports the records of a single core.
+pub(super) struct CoreExporter {
+    exporter: &'static Exporter,
+    /// `None` if the socket could not be created, in which case messages are dropped.
+    socket: Option<UdpSocket>,
+    /// Sets (ID and body) of the pending message.
+    sets: Vec<(u16, Vec<u8>)>,
+    /// Number of template and data records in the pending message.
+    count: u16,
+    /// Number of data records in the pending message.
+    data_count: u32,
+    /// Time the first record of the pending message was added.
+    pending_since: Option<Instant>,
+    /// Time the templates were last sent.
+    templates_sent: Option<Instant>,
+    /// Number of data records (IPFIX) or messages (NetFlow v9) sent.
+    sequence: u32,
+    /// Connections passed to `update`, until they are exported or evicted.
+    flows: HashMap<FiveTuple, TrackedFlow>,
+    last_sweep: Instant,
+    /// Number of messages that could not be sent.
+    dropped: u64,
+}
+
+impl CoreExporter {
+    pub(super) fn new(exporter: &'static Exporter) -> Self {
+        let bind: SocketAddr = match exporter.collector {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind)
+            .and_then(|socket| socket.connect(exporter.collector).map(|_| socket))
+            .ok();
+        CoreExporter {
+            exporter,
+            socket,
+            sets: vec![],
+            count: 0,
+            data_count: 0,
+            pending_since: None,
+            templates_sent: None,
+            sequence: 0,
+            flows: HashMap::new(),
+            last_sweep: Instant::now(),
+            dropped: 0,
+        }
+    }
+
+    pub(super) fn export(&mut self, record: &FlowRecord) {
+        match self.flows.remove(&record.five_tuple) {
+            Some(mut flow) => {
+                let delta = flow.export(record, record.end_reason);
+                self.push(&delta);
+            }
+            None => self.push(record),
+        }
+        self.poll();
+    }
+
+    pub(super) fn update(&mut self, record: &FlowRecord) {
+        let exporter = self.exporter;
+        let mut expired = vec![];
+        let flow = self
+            .flows
+            .entry(record.five_tuple)
+            .or_insert_with(|| TrackedFlow {
+                last: record.clone(),
+                exported: Default::default(),
+                segment_start: record.start,
+            });
+        let idle = record.end.duration_since(flow.last.end).unwrap_or_default();
+        if idle > exporter.inactive_timeout {
+            if flow.pending() {
+                let last = flow.last.clone();
+                expired.push(flow.export(&last, FlowEndReason::IdleTimeout));
+            }
+            flow.segment_start = record.end;
+        }
+        flow.last = record.clone();
+        let active = record
+            .end
+            .duration_since(flow.segment_start)
+            .unwrap_or_default();
+        if active >= exporter.active_timeout {
+            expired.push(flow.export(record, FlowEndReason::ActiveTimeout));
+        }
+        for record in expired {
+            self.push(&record);
+        }
+        self.poll();
+    }
+
+    /// Adds both directions of `record` to the pending message.
+    fn push(&mut self, record: &FlowRecord) {
+        for (orig, counters) in [(true, record.orig), (false, record.resp)] {
+            if counters.pkts == 0 {
+                continue;
+            }
+            let (id, body) = self.exporter.encoder.record(record, orig);
+            if !self.sets.is_empty()
+                && self.len_with(id, body.len()) > self.exporter.max_message_size
+            {
+                self.flush();
+            }
+            if self.sets.is_empty() {
+                self.start_message();
+            }
+            match self.sets.last_mut() {
+                Some((last, set)) if *last == id => set.extend_from_slice(&body),
+                _ => self.sets.push((id, body)),
+            }
+            self.count = self.count.saturating_add(1);
+            self.data_count += 1;
+        }
+    }
+
+    /// Length of the pending message after adding a record of `len` bytes to a set of `id`.
+    fn len_with(&self, id: u16, len: usize) -> usize {
+        let encoder = &self.exporter.encoder;
+        let mut total = encoder.header_len();
+        for (i, (set, body)) in self.sets.iter().enumerate() {
+            match i == self.sets.len() - 1 && *set == id {
+                true => total += encoder.set_len(body.len() + len),
+                false => total += encoder.set_len(body.len()),
+            }
+        }
+        if self.sets.last().map(|(set, _)| *set) != Some(id) {
+            total += encoder.set_len(len);
+        }
+        total
+    }
+
+    /// Starts a message, with the templates if they are due.
+    fn start_message(&mut self) {
+        let due = match self.templates_sent {
+            Some(sent) => sent.elapsed() >= self.exporter.template_refresh,
+            None => true,
+        };
+        if due {
+            self.sets.push(self.exporter.encoder.templates());
+            self.count += 2;
+            self.templates_sent = Some(Instant::now());
+        }
+        self.pending_since = Some(Instant::now());
+    }
+
+    /// Sends the pending message.
+    fn flush(&mut self) {
+        if self.sets.is_empty() {
+            return;
+        }
+        let msg = self.exporter.encoder.message(
+            &self.sets,
+            self.count,
+            self.sequence,
+            self.exporter.observation_domain,
+        );
+        let sent = match &self.socket {
+            Some(socket) => socket.send(&msg).is_ok(),
+            None => false,
+        };
+        if !sent {
+            self.dropped += 1;
+            // The templates may have been lost.
+            self.templates_sent = None;
+        }
+        self.sequence = match self.exporter.encoder.protocol() {
+            FlowExportProtocol::Ipfix => self.sequence.wrapping_add(self.data_count),
+            FlowExportProtocol::Netflow9 => self.sequence.wrapping_add(1),
+        };
+        self.sets.clear();
+        self.count = 0;
+        self.data_count = 0;
+        self.pending_since = None;
+    }
+
+    /// Sends the pending message if it is due, exports connections that exceeded the inactive
+    /// timeout, and evicts connections that exceeded the eviction timeout.
+    fn poll(&mut self) {
+        if self.last_sweep.elapsed() >= FLUSH_INTERVAL {
+            self.last_sweep = Instant::now();
+            let now = SystemTime::now();
+            let inactive_timeout = self.exporter.inactive_timeout;
+            let evict_timeout = self.exporter.evict_timeout;
+            let mut expired = vec![];
+            self.flows.retain(|_, flow| {
+                let idle = now.duration_since(flow.last.end).unwrap_or_default();
+                if flow.pending() && idle > inactive_timeout {
+                    let last = flow.last.clone();
+                    expired.push(flow.export(&last, FlowEndReason::IdleTimeout));
+                }
+                idle <= evict_timeout
+            });
+            for record in expired {
+                self.push(&record);
+            }
+        }
+        if let Some(since) = self.pending_since {
+            if since.elapsed() >= FLUSH_INTERVAL {
+                self.flush();
+            }
+        }
+    }
+
+    /// Exports the remaining packets and bytes of tracked connections and sends the pending
+    /// message. Returns the number of messages that could not be sent.
+    pub(super) fn finish(&mut self) -> u64 {
+        let mut flows: Vec<_> = self.flows.drain().map(|(_, flow)| flow).collect();
+        for flow in flows.iter_mut().filter(|flow| flow.pending()) {
+            let last = flow.last.clone();
+            let record = flow.export(&last, FlowEndReason::ForcedEnd);
+            self.push(&record);
+        }
+        self.flush();
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_export::FlowExportConfig;
+
+    /// Stub collector, and an exporter that sends to it.
+    fn collector(config: FlowExportConfig) -> (UdpSocket, CoreExporter) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let config = FlowExportConfig {
+            collector: socket.local_addr().unwrap().to_string(),
+            ..config
+        };
+        let exporter = Box::leak(Box::new(Exporter::new(&config).unwrap()));
+        (socket, CoreExporter::new(exporter))
+    }
+
+    fn record(pkts: u64, end: u64) -> FlowRecord {
+        let start = SystemTime::now();
+        FlowRecord {
+            five_tuple: FiveTuple {
+                orig: "10.0.0.1:51000".parse().unwrap(),
+                resp: "10.0.0.2:443".parse().unwrap(),
+                proto: 6,
+            },
+            start,
+            end: start + Duration::from_secs(end),
+            orig: FlowCounters {
+                pkts,
+                bytes: 100 * pkts,
+                tcp_flags: 0x12,
+            },
+            resp: FlowCounters::default(),
+            end_reason: FlowEndReason::EndOfFlow,
+            sni: None,
+            http_host: None,
+            ja3: None,
+        }
+    }
+
+    fn u16_at(buf: &[u8], i: usize) -> u16 {
+        u16::from_be_bytes(buf[i..i + 2].try_into().unwrap())
+    }
+
+    fn u32_at(buf: &[u8], i: usize) -> u32 {
+        u32::from_be_bytes(buf[i..i + 4].try_into().unwrap())
+    }
+
+    fn u64_at(buf: &[u8], i: usize) -> u64 {
+        u64::from_be_bytes(buf[i..i + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn core_flow_export_ipfix() {
+        let (socket, mut core) = collector(FlowExportConfig {
+            observation_domain: 7,
+            enterprise_number: Some(32473),
+            ..Default::default()
+        });
+        let mut conn = record(3, 2).with_sni("example.com").with_ja3("abc");
+        conn.resp.pkts = 2;
+        core.export(&conn);
+        assert_eq!(core.finish(), 0);
+
+        let mut msg = [0; 2048];
+        let len = socket.recv(&mut msg).unwrap();
+        let msg = &msg[..len];
+        assert_eq!(u16_at(msg, 0), 10);
+        assert_eq!(u16_at(msg, 2) as usize, len);
+        assert_eq!(u32_at(msg, 8), 0); // sequence
+        assert_eq!(u32_at(msg, 12), 7);
+
+        // Template set: two templates of 11 fields and 3 enterprise fields.
+        let templates = &msg[16..];
+        assert_eq!(u16_at(templates, 0), 2);
+        assert_eq!(u16_at(templates, 2), 4 + 2 * (4 + 11 * 4 + 3 * 8));
+        assert_eq!(u16_at(templates, 4), 256);
+        assert_eq!(u16_at(templates, 6), 14);
+        assert_eq!(u16_at(templates, 8), 8); // sourceIPv4Address
+        let sni = 4 + 4 + 11 * 4;
+        assert_eq!(u16_at(templates, sni), 0x8001);
+        assert_eq!(u16_at(templates, sni + 2), u16::MAX);
+        assert_eq!(u32_at(templates, sni + 4), 32473);
+
+        // Data set: originator, then responder record.
+        let data = &templates[u16_at(templates, 2) as usize..];
+        assert_eq!(u16_at(data, 0), 256);
+        assert_eq!(u16_at(data, 2) as usize, data.len());
+        let orig = &data[4..];
+        assert_eq!(&orig[0..4], &[10, 0, 0, 1]);
+        assert_eq!(&orig[4..8], &[10, 0, 0, 2]);
+        assert_eq!(u16_at(orig, 8), 51000);
+        assert_eq!(u16_at(orig, 10), 443);
+        assert_eq!(orig[12], 6);
+        assert_eq!(u16_at(orig, 13), 0x12);
+        assert_eq!(u64_at(orig, 15), 300);
+        assert_eq!(u64_at(orig, 23), 3);
+        assert_eq!(u64_at(orig, 39) - u64_at(orig, 31), 2000);
+        assert_eq!(orig[47], 3); // endOfFlowDetected
+        assert_eq!(&orig[48..60], b"\x0bexample.com");
+        assert_eq!(&orig[60..65], b"\x00\x03abc");
+        let resp = &orig[65..];
+        assert_eq!(&resp[0..4], &[10, 0, 0, 2]);
+        assert_eq!(u16_at(resp, 8), 443);
+        assert_eq!(u64_at(resp, 23), 2);
+        assert_eq!(resp.len(), 65);
+    }
+
+    #[test]
+    fn core_flow_export_netflow9_timeouts() {
+        let (socket, mut core) = collector(FlowExportConfig {
+            protocol: FlowExportProtocol::Netflow9,
+            active_timeout: 60,
+            inactive_timeout: 15,
+            ..Default::default()
+        });
+        let start = record(1, 0).start;
+        let at = |pkts, end| FlowRecord {
+            start,
+            end: start + Duration::from_secs(end),
+            ..record(pkts, end)
+        };
+        // Exported after 60s (active timeout), then after 20s without packets (inactive timeout),
+        // then when the connection terminates.
+        for (pkts, end) in [(1, 0), (2, 10), (3, 20), (4, 30), (5, 40), (6, 50), (7, 60)] {
+            core.update(&at(pkts, end));
+        }
+        core.update(&at(8, 70));
+        core.update(&at(9, 90));
+        core.export(&at(10, 95));
+        assert!(core.flows.is_empty());
+        assert_eq!(core.finish(), 0);
+
+        let mut msg = [0; 2048];
+        let len = socket.recv(&mut msg).unwrap();
+        let msg = &msg[..len];
+        assert_eq!(u16_at(msg, 0), 9);
+        assert_eq!(u16_at(msg, 2), 2 + 3); // templates and records
+        assert_eq!(u32_at(msg, 12), 0); // sequence
+
+        let templates = &msg[20..];
+        assert_eq!(u16_at(templates, 0), 0);
+        let data = &templates[u16_at(templates, 2) as usize..];
+        assert_eq!(u16_at(data, 0), 256);
+        // Records of 39 bytes, padded to 32 bits.
+        assert_eq!(u16_at(data, 2), 4 + 3 * 39 + 3);
+        assert_eq!(data.len(), 124);
+        let records: Vec<_> = data[4..4 + 3 * 39].chunks(39).collect();
+        let fields = |r: &[u8]| (u64_at(r, 22), u32_at(r, 34) - u32_at(r, 30), r[38]);
+        assert_eq!(fields(records[0]), (7, 60_000, 2));
+        assert_eq!(fields(records[1]), (1, 10_000, 1));
+        assert_eq!(fields(records[2]), (2, 5_000, 3));
+    }
+
+    #[test]
+    fn core_flow_export_evict() {
+        let (socket, mut core) = collector(FlowExportConfig {
+            protocol: FlowExportProtocol::Netflow9,
+            inactive_timeout: 5,
+            evict_timeout: 10,
+            ..Default::default()
+        });
+        // Connections are updated but never exported, and stop receiving packets
+        let now = SystemTime::now();
+        let at = |pkts, idle| FlowRecord {
+            start: now - Duration::from_secs(60),
+            end: now - Duration::from_secs(idle),
+            ..record(pkts, 0)
+        };
+        let mut idle = at(2, 8);
+        idle.five_tuple.orig.set_port(51001);
+        core.update(&at(3, 20));
+        core.update(&idle);
+        assert_eq!(core.flows.len(), 2);
+
+        // Both are exported once idle, and the first is evicted
+        core.last_sweep -= FLUSH_INTERVAL;
+        core.poll();
+        assert_eq!(core.flows.len(), 1);
+        assert!(core.flows.contains_key(&idle.five_tuple));
+        assert_eq!(core.finish(), 0);
+
+        let mut msg = [0; 2048];
+        let len = socket.recv(&mut msg).unwrap();
+        let msg = &msg[..len];
+        assert_eq!(u16_at(msg, 2), 2 + 2); // templates and records
+        let templates = &msg[20..];
+        let data = &templates[u16_at(templates, 2) as usize..];
+        let records: Vec<_> = data[4..4 + 2 * 39].chunks(39).collect();
+        let mut fields: Vec<_> = records.iter().map(|r| (u64_at(r, 22), r[38])).collect();
+        fields.sort();
+        assert_eq!(fields, vec![(2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn core_flow_export_finish() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        crate::flow_export::register(FlowExportConfig {
+            collector: socket.local_addr().unwrap().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Each core buffers its records until `finish`
+        let cores: Vec<_> = (1..=3)
+            .map(|pkts| std::thread::spawn(move || crate::flow_export::export(&record(pkts, 1))))
+            .collect();
+        for core in cores {
+            core.join().unwrap();
+        }
+        crate::flow_export::finish().unwrap();
+
+        let mut pkts = vec![];
+        let mut msg = [0; 2048];
+        for _ in 0..3 {
+            let len = socket.recv(&mut msg).unwrap();
+            let msg = &msg[..len];
+            assert_eq!(u32_at(msg, 8), 0); // sequence of each core
+            let templates = &msg[16..];
+            let data = &templates[u16_at(templates, 2) as usize..];
+            pkts.push(u64_at(&data[4..], 23));
+        }
+        pkts.sort();
+        assert_eq!(pkts, vec![1, 2, 3]);
+    }
+}
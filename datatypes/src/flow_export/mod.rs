@@ -0,0 +1,297 @@
+//! IPFIX and NetFlow v9 flow export.
+//!
+//! Converts connections into unidirectional flow records and exports them over UDP to an IPFIX
+//! ([RFC 7011](https://www.rfc-editor.org/rfc/rfc7011)) or NetFlow v9
+//! ([RFC 3954](https://www.rfc-editor.org/rfc/rfc3954)) collector. Each connection is exported as
+//! one record per direction with at least one packet. The TLS SNI, HTTP host, and JA3 hash of a
+//! connection can be attached to its records as enterprise-specific information elements.
+//!
+//! Each core (thread) has its own socket, sequence numbers, and templates, and batches its records
+//! into messages of up to `max_message_size` bytes. Templates are sent with the first message and
+//! again every `template_refresh` seconds.
+//!
+//! Records of terminated connections are exported with [export]. To also apply active and
+//! inactive timeouts to long-lived connections, as a NetFlow exporter would, connections must be
+//! passed to [update] as they progress, e.g. from a streaming callback. Each call to [export] or
+//! [update] then reports only the packets and bytes not yet exported for that connection.
+//!
+//! ## Example
+//! ```rust,ignore
+//! #[callback("tcp or udp,level=L4InPayload")]
+//! fn flow_update(conn: &ConnRecord) -> bool {
+//!     flow_export::update(&FlowRecord::from(conn));
+//!     true
+//! }
+//!
+//! #[callback("tls,level=L4Terminated")]
+//! fn flow_tls(conn: &ConnRecord, tls: &TlsHandshake) {
+//!     let record = FlowRecord::from(conn)
+//!         .with_sni(tls.sni())
+//!         .with_ja3(&tls.ja3_hash());
+//!     flow_export::export(&record);
+//! }
+//!
+//! #[iris_main]
+//! fn main() {
+//!     let config = FlowExportConfig {
+//!         collector: "10.0.0.2:4739".to_string(),
+//!         enterprise_number: Some(32473),
+//!         ..Default::default()
+//!     };
+//!     flow_export::register(config).unwrap();
+//!     // ... create and run the runtime
+//!     flow_export::finish().unwrap();
+//! }
+//! ```
+//!
+//! ## Remarks
+//! Flow start and end times are taken from the system clock when packets are processed, and do
+//! not reflect packet capture timestamps in offline analysis.
+
+mod encode;
+mod exporter;
+mod record;
+
+pub use self::record::*;
+
+use self::encode::Encoder;
+use self::exporter::CoreExporter;
+
+use std::cell::{OnceCell, UnsafeCell};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+/// Flow export protocol.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowExportProtocol {
+    /// IPFIX (version 10).
+    #[default]
+    Ipfix,
+    /// NetFlow version 9.
+    Netflow9,
+}
+
+/// Flow export options.
+///
+/// ## Remarks
+/// Can be deserialized from the application's own configuration, e.g.:
+/// ```toml
+/// [flow_export]
+///     collector = "10.0.0.2:4739"
+///     protocol = "ipfix"
+///     enterprise_number = 32473
+///     active_timeout = 1800
+///     inactive_timeout = 15
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+pub struct FlowExportConfig {
+    /// Address (`host:port`) of the collector.
+    pub collector: String,
+
+    /// Export protocol. Defaults to `ipfix`.
+    #[serde(default)]
+    pub protocol: FlowExportProtocol,
+
+    /// Observation domain ID (IPFIX) or source ID (NetFlow v9) of exported messages. Defaults to
+    /// `0`.
+    #[serde(default)]
+    pub observation_domain: u32,
+
+    /// IANA Private Enterprise Number of the SNI, HTTP host, and JA3 information elements. These
+    /// are not exported if unset. Defaults to `None`.
+    ///
+    /// ## Remarks
+    /// In IPFIX, the elements are numbered `1` (SNI), `2` (HTTP host), and `3` (JA3) within this
+    /// enterprise. NetFlow v9 has no enterprise numbers, so they are sent as field types `32769`,
+    /// `32770`, and `32771`, with fixed lengths of 128, 128, and 32 bytes.
+    #[serde(default)]
+    pub enterprise_number: Option<u32>,
+
+    /// Seconds after which a connection passed to [update] is exported, even if it has not
+    /// terminated. Defaults to `1800`.
+    #[serde(default = "default_active_timeout")]
+    pub active_timeout: u64,
+
+    /// Seconds without packets after which a connection passed to [update] is exported, even if
+    /// it has not terminated. Defaults to `15`.
+    #[serde(default = "default_inactive_timeout")]
+    pub inactive_timeout: u64,
+
+    /// Seconds without packets after which a connection passed to [update] is no longer tracked,
+    /// if it was never passed to [export]. Its remaining packets and bytes are exported first.
+    /// Must be at least `inactive_timeout`, and should exceed the connection tracker's inactivity
+    /// timeouts, so that connections are not forgotten while they can still resume. Defaults to
+    /// `600`.
+    #[serde(default = "default_evict_timeout")]
+    pub evict_timeout: u64,
+
+    /// Seconds between retransmissions of templates. Defaults to `600`.
+    #[serde(default = "default_template_refresh")]
+    pub template_refresh: u64,
+
+    /// Maximum size of an exported message, in bytes. Defaults to `1400`.
+    #[serde(default = "default_max_message_size")]
+    pub max_message_size: usize,
+}
+
+fn default_active_timeout() -> u64 {
+    1800
+}
+
+fn default_inactive_timeout() -> u64 {
+    15
+}
+
+fn default_evict_timeout() -> u64 {
+    600
+}
+
+fn default_template_refresh() -> u64 {
+    600
+}
+
+fn default_max_message_size() -> usize {
+    1400
+}
+
+impl Default for FlowExportConfig {
+    fn default() -> Self {
+        FlowExportConfig {
+            collector: String::new(),
+            protocol: FlowExportProtocol::default(),
+            observation_domain: 0,
+            enterprise_number: None,
+            active_timeout: default_active_timeout(),
+            inactive_timeout: default_inactive_timeout(),
+            evict_timeout: default_evict_timeout(),
+            template_refresh: default_template_refresh(),
+            max_message_size: default_max_message_size(),
+        }
+    }
+}
+
+/// Export settings shared by all cores.
+struct Exporter {
+    collector: SocketAddr,
+    encoder: Encoder,
+    observation_domain: u32,
+    active_timeout: Duration,
+    inactive_timeout: Duration,
+    evict_timeout: Duration,
+    template_refresh: Duration,
+    max_message_size: usize,
+}
+
+impl Exporter {
+    fn new(config: &FlowExportConfig) -> Result<Self> {
+        let collector = config
+            .collector
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("Cannot resolve collector {}", config.collector))?;
+        let encoder = Encoder::new(config.protocol, config.enterprise_number);
+        if config.max_message_size < encoder.min_message_size() {
+            bail!(
+                "max_message_size must be at least {} bytes",
+                encoder.min_message_size()
+            );
+        }
+        if config.evict_timeout < config.inactive_timeout {
+            bail!("evict_timeout must be at least inactive_timeout");
+        }
+        Ok(Exporter {
+            collector,
+            encoder,
+            observation_domain: config.observation_domain,
+            active_timeout: Duration::from_secs(config.active_timeout),
+            inactive_timeout: Duration::from_secs(config.inactive_timeout),
+            evict_timeout: Duration::from_secs(config.evict_timeout),
+            template_refresh: Duration::from_secs(config.template_refresh),
+            max_message_size: config.max_message_size.min(u16::MAX as usize),
+        })
+    }
+}
+
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+/// Handles to the export state of every core that has exported records, flushed by [finish].
+/// Only locked when a core exports its first record.
+static CORES: Mutex<Vec<Arc<CoreCell>>> = Mutex::new(vec![]);
+
+thread_local! {
+    /// Export state of the current core.
+    static CORE: OnceCell<Arc<CoreCell>> = const { OnceCell::new() };
+}
+
+/// Export state of a core, accessed without locking.
+struct CoreCell(UnsafeCell<CoreExporter>);
+
+// SAFETY: the state is only accessed by the thread that created it, and by `finish` once no core
+// exports records.
+unsafe impl Sync for CoreCell {}
+
+/// Exports flow records to the collector in `config`.
+///
+/// Must be called before the runtime is created.
+pub fn register(config: FlowExportConfig) -> Result<()> {
+    if EXPORTER.set(Exporter::new(&config)?).is_err() {
+        bail!("Flow export already registered");
+    }
+    Ok(())
+}
+
+/// Exports the record of a terminated connection from the current core.
+///
+/// If the connection was passed to [update] on the same core, only the packets and bytes that
+/// were not yet exported are reported. Records are discarded if no exporter is registered.
+pub fn export(record: &FlowRecord) {
+    with_core_exporter(|core| core.export(record));
+}
+
+/// Updates the record of an ongoing connection from the current core, and exports it if it
+/// exceeded the active or inactive timeout.
+///
+/// Connections passed to `update` are tracked until they are passed to [export], or until they
+/// have been idle for `evict_timeout`.
+pub fn update(record: &FlowRecord) {
+    with_core_exporter(|core| core.update(record));
+}
+
+fn with_core_exporter(f: impl FnOnce(&mut CoreExporter)) {
+    let exporter = match EXPORTER.get() {
+        Some(exporter) => exporter,
+        None => return,
+    };
+    CORE.with(|core| {
+        let core = core.get_or_init(|| {
+            let core = Arc::new(CoreCell(UnsafeCell::new(CoreExporter::new(exporter))));
+            CORES.lock().unwrap().push(Arc::clone(&core));
+            core
+        });
+        // SAFETY: no other thread accesses the state until `finish`.
+        f(unsafe { &mut *core.0.get() });
+    });
+}
+
+/// Exports the remaining packets and bytes of tracked connections, and sends all buffered
+/// records.
+///
+/// Must be called after the runtime has stopped. Returns an error if any message could not be
+/// sent.
+pub fn finish() -> Result<()> {
+    let mut dropped = 0;
+    for core in CORES.lock().unwrap().iter() {
+        // SAFETY: cores have stopped exporting records, so their state is no longer accessed.
+        let core = unsafe { &mut *core.0.get() };
+        dropped += core.finish();
+    }
+    if dropped > 0 {
+        bail!("Failed to send {} flow export messages", dropped);
+    }
+    Ok(())
+}
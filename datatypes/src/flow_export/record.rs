@@ -0,0 +1,174 @@
+//! Flow records built from Iris datatypes.
+
+use crate::connection::{Flow, HIST_ACK, HIST_FIN, HIST_RST, HIST_SYN, HIST_SYNACK};
+use crate::{ByteCount, ConnDuration, ConnRecord, PktCount};
+use iris_core::conntrack::conn_id::FiveTuple;
+use iris_core::protocols::packet::tcp::{ACK, FIN, RST, SYN};
+
+use std::time::{Instant, SystemTime};
+
+/// Reason a flow record was exported, as in IPFIX's `flowEndReason`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowEndReason {
+    /// The connection exceeded the inactive timeout.
+    IdleTimeout = 1,
+    /// The connection exceeded the active timeout.
+    ActiveTimeout = 2,
+    /// The connection terminated.
+    EndOfFlow = 3,
+    /// The exporter shut down.
+    ForcedEnd = 4,
+}
+
+/// Packets, bytes, and TCP flags observed in one direction of a connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlowCounters {
+    /// Number of packets.
+    pub pkts: u64,
+    /// Number of bytes.
+    pub bytes: u64,
+    /// Union of the TCP flags observed.
+    pub tcp_flags: u8,
+}
+
+impl FlowCounters {
+    /// Returns the packets and bytes observed since `earlier`.
+    pub(super) fn since(&self, earlier: &FlowCounters) -> FlowCounters {
+        FlowCounters {
+            pkts: self.pkts.saturating_sub(earlier.pkts),
+            bytes: self.bytes.saturating_sub(earlier.bytes),
+            tcp_flags: self.tcp_flags,
+        }
+    }
+}
+
+/// A bidirectional flow record, exported as one record per direction.
+#[derive(Debug, Clone)]
+pub struct FlowRecord {
+    /// The connection 5-tuple.
+    pub five_tuple: FiveTuple,
+    /// Time the first packet was processed.
+    pub start: SystemTime,
+    /// Time the last packet was processed.
+    pub end: SystemTime,
+    /// Originator to responder direction.
+    pub orig: FlowCounters,
+    /// Responder to originator direction.
+    pub resp: FlowCounters,
+    /// Reason the record is exported.
+    pub end_reason: FlowEndReason,
+    /// TLS server name.
+    pub sni: Option<String>,
+    /// HTTP `Host` header.
+    pub http_host: Option<String>,
+    /// JA3 hash of the TLS client.
+    pub ja3: Option<String>,
+}
+
+impl FlowRecord {
+    /// Creates a record from individual connection features.
+    ///
+    /// ## Remarks
+    /// [ByteCount] does not include packet headers, so exported byte counts are payload bytes.
+    pub fn from_counts(
+        five_tuple: &FiveTuple,
+        duration: &ConnDuration,
+        pkts: &PktCount,
+        bytes: &ByteCount,
+    ) -> Self {
+        FlowRecord {
+            five_tuple: *five_tuple,
+            start: system_time(duration.start_ts),
+            end: system_time(duration.last_ts),
+            orig: FlowCounters {
+                pkts: pkts.orig as u64,
+                bytes: bytes.orig as u64,
+                tcp_flags: 0,
+            },
+            resp: FlowCounters {
+                pkts: pkts.resp as u64,
+                bytes: bytes.resp as u64,
+                tcp_flags: 0,
+            },
+            end_reason: FlowEndReason::EndOfFlow,
+            sni: None,
+            http_host: None,
+            ja3: None,
+        }
+    }
+
+    /// Sets the TLS server name.
+    pub fn with_sni(mut self, sni: &str) -> Self {
+        self.sni = Some(sni.to_string()).filter(|s| !s.is_empty());
+        self
+    }
+
+    /// Sets the HTTP `Host` header.
+    pub fn with_http_host(mut self, host: &str) -> Self {
+        self.http_host = Some(host.to_string()).filter(|s| !s.is_empty());
+        self
+    }
+
+    /// Sets the JA3 hash of the TLS client.
+    pub fn with_ja3(mut self, ja3: &str) -> Self {
+        self.ja3 = Some(ja3.to_string()).filter(|s| !s.is_empty());
+        self
+    }
+}
+
+/// Exports packet bytes, including headers, and the TCP flags recorded in the connection history.
+impl From<&ConnRecord> for FlowRecord {
+    fn from(conn: &ConnRecord) -> Self {
+        let counters = |flow: &Flow, mask| FlowCounters {
+            pkts: flow.nb_pkts,
+            bytes: flow.nb_pkt_bytes,
+            tcp_flags: tcp_flags(&conn.history, mask),
+        };
+        FlowRecord {
+            five_tuple: conn.five_tuple,
+            start: system_time(conn.first_seen_ts),
+            end: system_time(conn.last_seen_ts),
+            orig: counters(&conn.orig, 0x0),
+            resp: counters(&conn.resp, 0x20),
+            end_reason: FlowEndReason::EndOfFlow,
+            sni: None,
+            http_host: None,
+            ja3: None,
+        }
+    }
+}
+
+/// Union of the TCP flags in the connection history of one direction. The history of the
+/// responder is lowercase (`mask` is `0x20`).
+fn tcp_flags(history: &[u8], mask: u8) -> u8 {
+    history
+        .iter()
+        .map(|&event| match event ^ mask {
+            HIST_SYN => SYN,
+            HIST_SYNACK => SYN | ACK,
+            HIST_ACK => ACK,
+            HIST_FIN => FIN,
+            HIST_RST => RST,
+            _ => 0,
+        })
+        .fold(0, |flags, flag| flags | flag)
+}
+
+/// Approximates the system time of `ts`.
+fn system_time(ts: Instant) -> SystemTime {
+    SystemTime::now() - ts.elapsed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_flow_tcp_flags() {
+        assert_eq!(tcp_flags(b"ShADadFf", 0x0), SYN | ACK | FIN);
+        assert_eq!(tcp_flags(b"ShADadFf", 0x20), SYN | ACK | FIN);
+        assert_eq!(tcp_flags(b"Sr", 0x0), SYN);
+        assert_eq!(tcp_flags(b"Sr", 0x20), RST);
+        assert_eq!(tcp_flags(b"Dd", 0x0), 0);
+    }
+}
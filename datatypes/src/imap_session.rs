@@ -0,0 +1,24 @@
+//! An IMAP session.
+//! Subscribable alias for [`iris_core::protocols::stream::imap::Imap`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::imap::Imap;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=imap"))]
+pub type ImapSession = Box<Imap>;
+
+impl FromSession for ImapSession {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("ImapSession,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Imap(imap) = &session.data {
+            return Some(imap);
+        }
+        None
+    }
+}
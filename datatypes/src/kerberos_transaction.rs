@@ -0,0 +1,24 @@
+//! A Kerberos KDC transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::kerberos::Kerberos`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::kerberos::Kerberos;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=kerberos"))]
+pub type KerberosTransaction = Box<Kerberos>;
+
+impl FromSession for KerberosTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("KerberosTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Kerberos(kerberos) = &session.data {
+            return Some(kerberos);
+        }
+        None
+    }
+}
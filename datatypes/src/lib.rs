@@ -2,27 +2,95 @@
 use iris_compiler::cache_file;
 use iris_core::{protocols::Session, L4Pdu, Mbuf};
 
+pub mod bittorrent_session;
+pub use bittorrent_session::BittorrentSession;
+
+pub mod coap_transaction;
+pub use coap_transaction::CoapTransaction;
+
 pub mod conn_fts;
 pub use conn_fts::*;
 
 pub mod connection;
 pub use connection::ConnRecord;
 
+pub mod dhcp_transaction;
+pub use dhcp_transaction::DhcpTransaction;
+
+pub mod dhcpv6_transaction;
+pub use dhcpv6_transaction::Dhcpv6Transaction;
+
 pub mod dns_transaction;
 pub use dns_transaction::DnsTransaction;
 
+pub mod flow_export;
+
 pub mod http_transaction;
 pub use http_transaction::HttpTransaction;
 
+pub mod imap_session;
+pub use imap_session::ImapSession;
+
+pub mod kerberos_transaction;
+pub use kerberos_transaction::KerberosTransaction;
+
+pub mod llmnr_transaction;
+pub use llmnr_transaction::LlmnrTransaction;
+
+pub mod mdns_transaction;
+pub use mdns_transaction::MdnsTransaction;
+
+pub mod modbus_transaction;
+pub use modbus_transaction::ModbusTransaction;
+
+pub mod mqtt_message;
+pub use mqtt_message::MqttMessage;
+
+pub mod mysql_handshake;
+pub use mysql_handshake::MySqlHandshake;
+
+pub mod ntp_transaction;
+pub use ntp_transaction::NtpTransaction;
+
+pub mod openvpn_handshake;
+pub use openvpn_handshake::OpenVpnHandshake;
+
 pub mod packet_list;
 pub use packet_list::*;
 
 pub mod packet;
 pub use packet::{Payload, ZcFrame};
 
+pub mod pop3_session;
+pub use pop3_session::Pop3Session;
+
+pub mod postgres_startup;
+pub use postgres_startup::PostgresStartup;
+
 pub mod quic_stream;
 pub use quic_stream::QuicStream;
 
+pub mod rdp_handshake;
+pub use rdp_handshake::RdpHandshake;
+
+pub mod redis_transaction;
+pub use redis_transaction::RedisTransaction;
+
+pub mod rtp_stream;
+pub use rtp_stream::RtpStream;
+
+pub mod sip_transaction;
+pub use sip_transaction::SipTransaction;
+
+pub mod smb_transaction;
+pub use smb_transaction::SmbTransaction;
+
+pub mod smtp_session;
+pub use smtp_session::SmtpSession;
+
+pub mod ssdp_transaction;
+pub use ssdp_transaction::SsdpTransaction;
+
 pub mod ssh_handshake;
 pub use ssh_handshake::SshHandshake;
 
@@ -32,6 +100,15 @@ pub use static_type::*;
 pub mod tls_handshake;
 pub use tls_handshake::TlsHandshake;
 
+pub mod vnc_handshake;
+pub use vnc_handshake::VncHandshake;
+
+pub mod wireguard_handshake;
+pub use wireguard_handshake::WireGuardHandshake;
+
+pub mod zeek;
+pub use zeek::ZeekUid;
+
 /// No-op function to invoke macro
 /// TODO can we do this more cleanly?
 #[cfg_attr(
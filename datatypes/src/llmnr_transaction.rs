@@ -0,0 +1,24 @@
+//! An LLMNR transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::llmnr::Llmnr`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::llmnr::Llmnr;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=llmnr"))]
+pub type LlmnrTransaction = Box<Llmnr>;
+
+impl FromSession for LlmnrTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("LlmnrTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Llmnr(llmnr) = &session.data {
+            return Some(llmnr);
+        }
+        None
+    }
+}
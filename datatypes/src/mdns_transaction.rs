@@ -0,0 +1,24 @@
+//! An mDNS transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::mdns::Mdns`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::mdns::Mdns;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=mdns"))]
+pub type MdnsTransaction = Box<Mdns>;
+
+impl FromSession for MdnsTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("MdnsTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Mdns(mdns) = &session.data {
+            return Some(mdns);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! A Modbus/TCP request and its response.
+//! Subscribable alias for [`iris_core::protocols::stream::modbus::Modbus`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::modbus::Modbus;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=modbus"))]
+pub type ModbusTransaction = Box<Modbus>;
+
+impl FromSession for ModbusTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("ModbusTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Modbus(modbus) = &session.data {
+            return Some(modbus);
+        }
+        None
+    }
+}
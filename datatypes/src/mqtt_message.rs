@@ -0,0 +1,24 @@
+//! An MQTT control packet and its acknowledgement.
+//! Subscribable alias for [`iris_core::protocols::stream::mqtt::Mqtt`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::mqtt::Mqtt;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=mqtt"))]
+pub type MqttMessage = Box<Mqtt>;
+
+impl FromSession for MqttMessage {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("MqttMessage,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Mqtt(mqtt) = &session.data {
+            return Some(mqtt);
+        }
+        None
+    }
+}
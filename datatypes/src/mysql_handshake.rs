@@ -0,0 +1,24 @@
+//! A MySQL connection phase handshake.
+//! Subscribable alias for [`iris_core::protocols::stream::mysql::MySql`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::mysql::MySql;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=mysql"))]
+pub type MySqlHandshake = Box<MySql>;
+
+impl FromSession for MySqlHandshake {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("MySqlHandshake,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Mysql(mysql) = &session.data {
+            return Some(mysql);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! An NTP transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::ntp::Ntp`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::ntp::Ntp;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=ntp"))]
+pub type NtpTransaction = Box<Ntp>;
+
+impl FromSession for NtpTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("NtpTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Ntp(ntp) = &session.data {
+            return Some(ntp);
+        }
+        None
+    }
+}
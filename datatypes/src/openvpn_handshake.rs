@@ -0,0 +1,24 @@
+//! An OpenVPN handshake.
+//! Subscribable alias for [`iris_core::protocols::stream::openvpn::OpenVpn`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::openvpn::OpenVpn;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=openvpn"))]
+pub type OpenVpnHandshake = Box<OpenVpn>;
+
+impl FromSession for OpenVpnHandshake {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("OpenVpnHandshake,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Openvpn(openvpn) = &session.data {
+            return Some(openvpn);
+        }
+        None
+    }
+}
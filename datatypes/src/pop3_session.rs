@@ -0,0 +1,24 @@
+//! A POP3 session.
+//! Subscribable alias for [`iris_core::protocols::stream::pop3::Pop3`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::pop3::Pop3;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=pop3"))]
+pub type Pop3Session = Box<Pop3>;
+
+impl FromSession for Pop3Session {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("Pop3Session,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Pop3(pop3) = &session.data {
+            return Some(pop3);
+        }
+        None
+    }
+}
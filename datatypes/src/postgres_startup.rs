@@ -0,0 +1,24 @@
+//! A PostgreSQL connection startup.
+//! Subscribable alias for [`iris_core::protocols::stream::postgres::Postgres`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::postgres::Postgres;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=postgres"))]
+pub type PostgresStartup = Box<Postgres>;
+
+impl FromSession for PostgresStartup {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("PostgresStartup,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Postgres(postgres) = &session.data {
+            return Some(postgres);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! An RDP connection request and confirm.
+//! Subscribable alias for [`iris_core::protocols::stream::rdp::Rdp`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::rdp::Rdp;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=rdp"))]
+pub type RdpHandshake = Box<Rdp>;
+
+impl FromSession for RdpHandshake {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("RdpHandshake,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Rdp(rdp) = &session.data {
+            return Some(rdp);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! A Redis command and its reply.
+//! Subscribable alias for [`iris_core::protocols::stream::redis::Redis`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::redis::Redis;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=redis"))]
+pub type RedisTransaction = Box<Redis>;
+
+impl FromSession for RedisTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("RedisTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Redis(redis) = &session.data {
+            return Some(redis);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! Statistics of an RTP media flow, tagged with the SIP call that announced it.
+//! Subscribable alias for [`iris_core::protocols::stream::rtp::Rtp`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::rtp::Rtp;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=rtp"))]
+pub type RtpStream = Box<Rtp>;
+
+impl FromSession for RtpStream {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("RtpStream,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Rtp(rtp) = &session.data {
+            return Some(rtp);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! A SIP request and its responses.
+//! Subscribable alias for [`iris_core::protocols::stream::sip::Sip`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::sip::Sip;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=sip"))]
+pub type SipTransaction = Box<Sip>;
+
+impl FromSession for SipTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("SipTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Sip(sip) = &session.data {
+            return Some(sip);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! An SMB2/3 transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::smb::Smb`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::smb::Smb;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=smb"))]
+pub type SmbTransaction = Box<Smb>;
+
+impl FromSession for SmbTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("SmbTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Smb(smb) = &session.data {
+            return Some(smb);
+        }
+        None
+    }
+}
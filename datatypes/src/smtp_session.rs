@@ -0,0 +1,24 @@
+//! An SMTP session.
+//! Subscribable alias for [`iris_core::protocols::stream::smtp::Smtp`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::smtp::Smtp;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=smtp"))]
+pub type SmtpSession = Box<Smtp>;
+
+impl FromSession for SmtpSession {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("SmtpSession,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Smtp(smtp) = &session.data {
+            return Some(smtp);
+        }
+        None
+    }
+}
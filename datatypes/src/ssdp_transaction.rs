@@ -0,0 +1,24 @@
+//! An SSDP transaction.
+//! Subscribable alias for [`iris_core::protocols::stream::ssdp::Ssdp`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::ssdp::Ssdp;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=ssdp"))]
+pub type SsdpTransaction = Box<Ssdp>;
+
+impl FromSession for SsdpTransaction {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("SsdpTransaction,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Ssdp(ssdp) = &session.data {
+            return Some(ssdp);
+        }
+        None
+    }
+}
@@ -0,0 +1,24 @@
+//! A VNC (RFB) handshake.
+//! Subscribable alias for [`iris_core::protocols::stream::vnc::Vnc`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::vnc::Vnc;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=vnc"))]
+pub type VncHandshake = Box<Vnc>;
+
+impl FromSession for VncHandshake {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("VncHandshake,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Vnc(vnc) = &session.data {
+            return Some(vnc);
+        }
+        None
+    }
+}
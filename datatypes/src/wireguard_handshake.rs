@@ -0,0 +1,24 @@
+//! A WireGuard handshake.
+//! Subscribable alias for [`iris_core::protocols::stream::wireguard::WireGuard`]
+
+use crate::FromSession;
+#[allow(unused_imports)]
+use iris_compiler::{datatype, datatype_group};
+use iris_core::protocols::stream::wireguard::WireGuard;
+use iris_core::protocols::stream::{Session, SessionData};
+
+#[cfg_attr(not(feature = "skip_expand"), datatype("L7EndHdrs,parsers=wireguard"))]
+pub type WireGuardHandshake = Box<WireGuard>;
+
+impl FromSession for WireGuardHandshake {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype_group("WireGuardHandshake,level=L7EndHdrs")
+    )]
+    fn from_session(session: &Session) -> Option<&Self> {
+        if let SessionData::Wireguard(wireguard) = &session.data {
+            return Some(wireguard);
+        }
+        None
+    }
+}
@@ -0,0 +1,841 @@
+//! Zeek log entries built from Iris datatypes.
+
+use super::{ZeekLog, ZeekUid};
+use crate::{ConnRecord, DnsTransaction, HttpTransaction, SshHandshake, TlsHandshake};
+use iris_core::conntrack::conn_id::FiveTuple;
+use iris_core::protocols::packet::tcp::TCP_PROTOCOL;
+use iris_core::protocols::packet::udp::UDP_PROTOCOL;
+use iris_core::protocols::stream::dns::Data;
+use iris_core::protocols::stream::ssh::AuthResult;
+use iris_core::protocols::stream::tls::{Certificate, ServerKeyExchange};
+
+use std::net::IpAddr;
+
+use serde::Serialize;
+use x509_parser::der_parser::oid::Oid;
+use x509_parser::objects::{oid2sn, oid_registry};
+use x509_parser::prelude::{GeneralName, PublicKey};
+
+/// The `id` record shared by connection logs.
+#[derive(Debug, Clone, Serialize)]
+pub struct ZeekConnId {
+    #[serde(rename = "id.orig_h")]
+    pub orig_h: IpAddr,
+    #[serde(rename = "id.orig_p")]
+    pub orig_p: u16,
+    #[serde(rename = "id.resp_h")]
+    pub resp_h: IpAddr,
+    #[serde(rename = "id.resp_p")]
+    pub resp_p: u16,
+}
+
+impl From<&FiveTuple> for ZeekConnId {
+    fn from(five_tuple: &FiveTuple) -> Self {
+        ZeekConnId {
+            orig_h: five_tuple.orig.ip(),
+            orig_p: five_tuple.orig.port(),
+            resp_h: five_tuple.resp.ip(),
+            resp_p: five_tuple.resp.port(),
+        }
+    }
+}
+
+const ID_FIELDS: [(&str, &str); 4] = [
+    ("id.orig_h", "addr"),
+    ("id.orig_p", "port"),
+    ("id.resp_h", "addr"),
+    ("id.resp_p", "port"),
+];
+
+/// Concatenates field lists at compile time.
+macro_rules! fields {
+    ($($field:expr),* $(,)?) => {{
+        const FIELDS: &[&[(&str, &str)]] = &[$($field),*];
+        const LEN: usize = {
+            let mut len = 0;
+            let mut i = 0;
+            while i < FIELDS.len() {
+                len += FIELDS[i].len();
+                i += 1;
+            }
+            len
+        };
+        const ALL: [(&str, &str); LEN] = {
+            let mut all = [("", ""); LEN];
+            let mut n = 0;
+            let mut i = 0;
+            while i < FIELDS.len() {
+                let mut j = 0;
+                while j < FIELDS[i].len() {
+                    all[n] = FIELDS[i][j];
+                    n += 1;
+                    j += 1;
+                }
+                i += 1;
+            }
+            all
+        };
+        &ALL
+    }};
+}
+
+/// Returns `None` for empty strings, which Iris uses for missing values.
+fn opt(s: &str) -> Option<String> {
+    match s.is_empty() {
+        true => None,
+        false => Some(s.to_string()),
+    }
+}
+
+fn proto_name(proto: usize) -> &'static str {
+    match proto {
+        TCP_PROTOCOL => "tcp",
+        UDP_PROTOCOL => "udp",
+        _ => "unknown_transport",
+    }
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// A `conn.log` entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnLog {
+    pub ts: f64,
+    pub uid: String,
+    #[serde(flatten)]
+    pub id: ZeekConnId,
+    pub proto: &'static str,
+    pub service: Option<String>,
+    pub duration: f64,
+    pub orig_bytes: u64,
+    pub resp_bytes: u64,
+    pub conn_state: &'static str,
+    pub missed_bytes: u64,
+    pub history: String,
+    pub orig_pkts: u64,
+    pub orig_ip_bytes: u64,
+    pub resp_pkts: u64,
+    pub resp_ip_bytes: u64,
+}
+
+impl ConnLog {
+    /// Builds the entry of a terminated connection.
+    ///
+    /// ## Remarks
+    /// `orig_ip_bytes` and `resp_ip_bytes` include link-layer headers. The service is unset; see
+    /// [with_service](ConnLog::with_service).
+    pub fn new(uid: &ZeekUid, conn: &ConnRecord) -> Self {
+        ConnLog {
+            ts: uid.ts(),
+            uid: uid.to_string(),
+            id: ZeekConnId::from(&conn.five_tuple),
+            proto: proto_name(conn.five_tuple.proto),
+            service: None,
+            duration: conn.duration().as_secs_f64(),
+            orig_bytes: conn.orig.nb_payload_bytes,
+            resp_bytes: conn.resp.nb_payload_bytes,
+            conn_state: conn_state(&conn.history, conn.five_tuple.proto),
+            missed_bytes: conn.orig.missed_bytes() + conn.resp.missed_bytes(),
+            history: conn.history(),
+            orig_pkts: conn.orig.nb_pkts,
+            orig_ip_bytes: conn.orig.nb_pkt_bytes,
+            resp_pkts: conn.resp.nb_pkts,
+            resp_ip_bytes: conn.resp.nb_pkt_bytes,
+        }
+    }
+
+    /// Sets the application protocol (e.g., `ssl`, `http`).
+    pub fn with_service(mut self, service: &str) -> Self {
+        self.service = opt(service);
+        self
+    }
+}
+
+impl ZeekLog for ConnLog {
+    const PATH: &'static str = "conn";
+    const FIELDS: &'static [(&'static str, &'static str)] = fields!(
+        &[("ts", "time"), ("uid", "string")],
+        &ID_FIELDS,
+        &[
+            ("proto", "enum"),
+            ("service", "string"),
+            ("duration", "interval"),
+            ("orig_bytes", "count"),
+            ("resp_bytes", "count"),
+            ("conn_state", "string"),
+            ("missed_bytes", "count"),
+            ("history", "string"),
+            ("orig_pkts", "count"),
+            ("orig_ip_bytes", "count"),
+            ("resp_pkts", "count"),
+            ("resp_ip_bytes", "count"),
+        ],
+    );
+}
+
+/// Summarizes a connection history as a Zeek connection state.
+///
+/// See [Zeek's conn.log](https://docs.zeek.org/en/master/logs/conn.html#conn-state) for the
+/// meaning of each state.
+pub(crate) fn conn_state(history: &[u8], proto: usize) -> &'static str {
+    let has = |event: u8| history.contains(&event);
+    if proto != TCP_PROTOCOL {
+        return match (has(b'D'), has(b'd')) {
+            (_, true) => "SF",
+            (true, false) => "S0",
+            (false, false) => "OTH",
+        };
+    }
+    let (syn, synack) = (has(b'S'), has(b'h'));
+    let (orig_fin, resp_fin) = (has(b'F'), has(b'f'));
+    let (orig_rst, resp_rst) = (has(b'R'), has(b'r'));
+    match (syn, synack) {
+        (true, true) => {
+            if orig_rst {
+                "RSTO"
+            } else if resp_rst {
+                "RSTR"
+            } else if orig_fin && resp_fin {
+                "SF"
+            } else if orig_fin {
+                "S2"
+            } else if resp_fin {
+                "S3"
+            } else {
+                "S1"
+            }
+        }
+        (true, false) => {
+            if resp_rst {
+                "REJ"
+            } else if orig_rst {
+                "RSTOS0"
+            } else if orig_fin {
+                "SH"
+            } else {
+                "S0"
+            }
+        }
+        (false, true) => {
+            if resp_rst {
+                "RSTRH"
+            } else if resp_fin {
+                "SHR"
+            } else {
+                "OTH"
+            }
+        }
+        (false, false) => "OTH",
+    }
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// An `ssl.log` entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct SslLog {
+    pub ts: f64,
+    pub uid: String,
+    #[serde(flatten)]
+    pub id: ZeekConnId,
+    pub version: Option<String>,
+    pub cipher: Option<String>,
+    pub curve: Option<String>,
+    pub server_name: Option<String>,
+    pub resumed: bool,
+    pub next_protocol: Option<String>,
+    pub established: bool,
+    pub cert_chain_fps: Vec<String>,
+    pub client_cert_chain_fps: Vec<String>,
+    pub ja3: Option<String>,
+    pub ja3s: Option<String>,
+}
+
+impl SslLog {
+    /// Builds the entry of a TLS handshake.
+    ///
+    /// ## Remarks
+    /// The handshake is considered established once a ServerHello is observed. Resumption is only
+    /// detected for TLS 1.2 and earlier session IDs.
+    pub fn new(uid: &ZeekUid, tls: &TlsHandshake) -> Self {
+        let resumed = match (&tls.client_hello, &tls.server_hello) {
+            (Some(ch), Some(sh)) => !ch.session_id.is_empty() && ch.session_id == sh.session_id,
+            _ => false,
+        };
+        let curve = match (&tls.server_key_exchange, &tls.server_hello) {
+            (Some(ServerKeyExchange::Ecdh(params)), _) => Some(curve_name(params.curve.0)),
+            (_, Some(sh)) => sh.key_share.as_ref().map(|share| curve_name(share.group.0)),
+            _ => None,
+        };
+        let cipher = match tls.cipher_suite() {
+            Some(suite) => Some(suite.name.to_string()),
+            None => opt(&tls.cipher()),
+        };
+        SslLog {
+            ts: uid.ts(),
+            uid: uid.to_string(),
+            id: ZeekConnId::from(&uid.five_tuple),
+            version: version_name(tls.version()),
+            cipher,
+            curve,
+            server_name: opt(tls.sni()),
+            resumed,
+            next_protocol: tls
+                .server_hello
+                .as_ref()
+                .and_then(|sh| sh.alpn_protocol.clone()),
+            established: tls.server_hello.is_some(),
+            cert_chain_fps: tls
+                .server_certificates
+                .iter()
+                .map(Certificate::fingerprint)
+                .collect(),
+            client_cert_chain_fps: tls
+                .client_certificates
+                .iter()
+                .map(Certificate::fingerprint)
+                .collect(),
+            ja3: tls.client_hello.as_ref().map(|_| tls.ja3_hash()),
+            ja3s: tls.server_hello.as_ref().map(|_| tls.ja3s_hash()),
+        }
+    }
+}
+
+impl ZeekLog for SslLog {
+    const PATH: &'static str = "ssl";
+    const FIELDS: &'static [(&'static str, &'static str)] = fields!(
+        &[("ts", "time"), ("uid", "string")],
+        &ID_FIELDS,
+        &[
+            ("version", "string"),
+            ("cipher", "string"),
+            ("curve", "string"),
+            ("server_name", "string"),
+            ("resumed", "bool"),
+            ("next_protocol", "string"),
+            ("established", "bool"),
+            ("cert_chain_fps", "vector[string]"),
+            ("client_cert_chain_fps", "vector[string]"),
+            ("ja3", "string"),
+            ("ja3s", "string"),
+        ],
+    );
+}
+
+/// Zeek's name of a TLS version identifier.
+fn version_name(version: u16) -> Option<String> {
+    match version {
+        0 => None,
+        0x0300 => Some("SSLv3".to_string()),
+        0x0301 => Some("TLSv10".to_string()),
+        0x0302 => Some("TLSv11".to_string()),
+        0x0303 => Some("TLSv12".to_string()),
+        0x0304 => Some("TLSv13".to_string()),
+        version => Some(format!("unknown-{}", version)),
+    }
+}
+
+/// Zeek's name of a TLS named group.
+fn curve_name(group: u16) -> String {
+    match group {
+        23 => "secp256r1".to_string(),
+        24 => "secp384r1".to_string(),
+        25 => "secp521r1".to_string(),
+        29 => "x25519".to_string(),
+        30 => "x448".to_string(),
+        256 => "ffdhe2048".to_string(),
+        257 => "ffdhe3072".to_string(),
+        0x11ec => "X25519MLKEM768".to_string(),
+        group => format!("unknown-{}", group),
+    }
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// An `x509.log` entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct X509Log {
+    pub ts: f64,
+    pub fingerprint: String,
+    #[serde(rename = "certificate.version")]
+    pub version: u32,
+    #[serde(rename = "certificate.serial")]
+    pub serial: String,
+    #[serde(rename = "certificate.subject")]
+    pub subject: String,
+    #[serde(rename = "certificate.issuer")]
+    pub issuer: String,
+    #[serde(rename = "certificate.not_valid_before")]
+    pub not_valid_before: i64,
+    #[serde(rename = "certificate.not_valid_after")]
+    pub not_valid_after: i64,
+    #[serde(rename = "certificate.key_alg")]
+    pub key_alg: String,
+    #[serde(rename = "certificate.sig_alg")]
+    pub sig_alg: String,
+    #[serde(rename = "certificate.key_type")]
+    pub key_type: Option<String>,
+    #[serde(rename = "certificate.key_length")]
+    pub key_length: Option<usize>,
+    #[serde(rename = "certificate.exponent")]
+    pub exponent: Option<String>,
+    #[serde(rename = "certificate.curve")]
+    pub curve: Option<String>,
+    #[serde(rename = "san.dns")]
+    pub san_dns: Option<Vec<String>>,
+    #[serde(rename = "san.uri")]
+    pub san_uri: Option<Vec<String>>,
+    #[serde(rename = "san.email")]
+    pub san_email: Option<Vec<String>>,
+    #[serde(rename = "san.ip")]
+    pub san_ip: Option<Vec<IpAddr>>,
+    #[serde(rename = "basic_constraints.ca")]
+    pub ca: Option<bool>,
+    #[serde(rename = "basic_constraints.path_len")]
+    pub path_len: Option<u32>,
+    pub host_cert: bool,
+    pub client_cert: bool,
+}
+
+impl X509Log {
+    /// Builds the entries of all certificates in a TLS handshake. Certificates that cannot be
+    /// parsed are skipped.
+    pub fn from_tls(uid: &ZeekUid, tls: &TlsHandshake) -> Vec<Self> {
+        let server = tls
+            .server_certificates
+            .iter()
+            .filter_map(|cert| Self::new(uid, cert, false));
+        let client = tls
+            .client_certificates
+            .iter()
+            .filter_map(|cert| Self::new(uid, cert, true));
+        server.chain(client).collect()
+    }
+
+    /// Builds the entry of a certificate sent by the server, or by the client if `client_cert`.
+    /// Returns `None` if the certificate cannot be parsed.
+    pub fn new(uid: &ZeekUid, cert: &Certificate, client_cert: bool) -> Option<Self> {
+        let (_, x509) = x509_parser::parse_x509_certificate(&cert.raw).ok()?;
+        let spki = x509.public_key();
+        let (key_type, key_length, exponent) = match spki.parsed() {
+            Ok(PublicKey::RSA(rsa)) => (
+                Some("rsa"),
+                Some(rsa.key_size()),
+                rsa.try_exponent().ok().map(|e| e.to_string()),
+            ),
+            Ok(key @ PublicKey::EC(_)) => (Some("ecdsa"), Some(key.key_size()), None),
+            Ok(key @ PublicKey::DSA(_)) => (Some("dsa"), Some(key.key_size()), None),
+            _ => (None, None, None),
+        };
+        let curve = match key_type {
+            Some("ecdsa") => spki
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|params| params.as_oid().ok())
+                .map(oid_name),
+            _ => None,
+        };
+
+        let (mut dns, mut uri, mut email, mut ip) = (vec![], vec![], vec![], vec![]);
+        if let Ok(Some(san)) = x509.subject_alternative_name() {
+            for name in san.value.general_names.iter() {
+                match name {
+                    GeneralName::DNSName(name) => dns.push(name.to_string()),
+                    GeneralName::URI(name) => uri.push(name.to_string()),
+                    GeneralName::RFC822Name(name) => email.push(name.to_string()),
+                    GeneralName::IPAddress(bytes) => {
+                        if let Ok(octets) = <[u8; 4]>::try_from(*bytes) {
+                            ip.push(IpAddr::from(octets));
+                        } else if let Ok(octets) = <[u8; 16]>::try_from(*bytes) {
+                            ip.push(IpAddr::from(octets));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let some = |names: Vec<_>| (!names.is_empty()).then_some(names);
+        let basic_constraints = x509.basic_constraints().ok().flatten();
+
+        Some(X509Log {
+            ts: uid.ts(),
+            fingerprint: cert.fingerprint(),
+            version: x509.version().0 + 1,
+            serial: x509
+                .raw_serial()
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect(),
+            subject: x509.subject().to_string(),
+            issuer: x509.issuer().to_string(),
+            not_valid_before: x509.validity().not_before.timestamp(),
+            not_valid_after: x509.validity().not_after.timestamp(),
+            key_alg: oid_name(&spki.algorithm.algorithm),
+            sig_alg: oid_name(&x509.signature_algorithm.algorithm),
+            key_type: key_type.map(str::to_string),
+            key_length,
+            exponent,
+            curve,
+            san_dns: some(dns),
+            san_uri: some(uri),
+            san_email: some(email),
+            san_ip: (!ip.is_empty()).then_some(ip),
+            ca: basic_constraints.as_ref().map(|bc| bc.value.ca),
+            path_len: basic_constraints.and_then(|bc| bc.value.path_len_constraint),
+            host_cert: !client_cert,
+            client_cert,
+        })
+    }
+}
+
+impl ZeekLog for X509Log {
+    const PATH: &'static str = "x509";
+    const FIELDS: &'static [(&'static str, &'static str)] = &[
+        ("ts", "time"),
+        ("fingerprint", "string"),
+        ("certificate.version", "count"),
+        ("certificate.serial", "string"),
+        ("certificate.subject", "string"),
+        ("certificate.issuer", "string"),
+        ("certificate.not_valid_before", "time"),
+        ("certificate.not_valid_after", "time"),
+        ("certificate.key_alg", "string"),
+        ("certificate.sig_alg", "string"),
+        ("certificate.key_type", "string"),
+        ("certificate.key_length", "count"),
+        ("certificate.exponent", "string"),
+        ("certificate.curve", "string"),
+        ("san.dns", "vector[string]"),
+        ("san.uri", "vector[string]"),
+        ("san.email", "vector[string]"),
+        ("san.ip", "vector[addr]"),
+        ("basic_constraints.ca", "bool"),
+        ("basic_constraints.path_len", "count"),
+        ("host_cert", "bool"),
+        ("client_cert", "bool"),
+    ];
+}
+
+/// Short name of an OID (e.g., `sha256WithRSAEncryption`), or its dotted form if unknown.
+fn oid_name(oid: &Oid) -> String {
+    match oid2sn(oid, oid_registry()) {
+        Ok(name) => name.to_string(),
+        Err(_) => oid.to_id_string(),
+    }
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// An `http.log` entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct HttpLog {
+    pub ts: f64,
+    pub uid: String,
+    #[serde(flatten)]
+    pub id: ZeekConnId,
+    pub trans_depth: usize,
+    pub method: Option<String>,
+    pub host: Option<String>,
+    pub uri: Option<String>,
+    pub referrer: Option<String>,
+    pub version: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_body_len: usize,
+    pub response_body_len: usize,
+    pub status_code: Option<u16>,
+    pub status_msg: Option<String>,
+}
+
+impl HttpLog {
+    /// Builds the entry of an HTTP transaction.
+    ///
+    /// ## Remarks
+    /// Body lengths are taken from the `Content-Length` headers.
+    pub fn new(uid: &ZeekUid, http: &HttpTransaction) -> Self {
+        HttpLog {
+            ts: uid.ts(),
+            uid: uid.to_string(),
+            id: ZeekConnId::from(&uid.five_tuple),
+            trans_depth: http.trans_depth,
+            method: opt(http.method()),
+            host: opt(http.host()),
+            uri: opt(http.uri()),
+            referrer: http.request.referer.clone(),
+            version: opt(http.request_version().trim_start_matches("HTTP/")),
+            user_agent: opt(http.user_agent()),
+            request_body_len: http.request_content_length(),
+            response_body_len: http.response_content_length(),
+            status_code: match http.status_code() {
+                0 => None,
+                code => Some(code),
+            },
+            status_msg: opt(http.status_msg()),
+        }
+    }
+}
+
+impl ZeekLog for HttpLog {
+    const PATH: &'static str = "http";
+    const FIELDS: &'static [(&'static str, &'static str)] = fields!(
+        &[("ts", "time"), ("uid", "string")],
+        &ID_FIELDS,
+        &[
+            ("trans_depth", "count"),
+            ("method", "string"),
+            ("host", "string"),
+            ("uri", "string"),
+            ("referrer", "string"),
+            ("version", "string"),
+            ("user_agent", "string"),
+            ("request_body_len", "count"),
+            ("response_body_len", "count"),
+            ("status_code", "count"),
+            ("status_msg", "string"),
+        ],
+    );
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// A `dns.log` entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsLog {
+    pub ts: f64,
+    pub uid: String,
+    #[serde(flatten)]
+    pub id: ZeekConnId,
+    pub proto: &'static str,
+    pub trans_id: u16,
+    pub query: Option<String>,
+    pub rcode: Option<u8>,
+    pub rcode_name: Option<&'static str>,
+    #[serde(rename = "AA")]
+    pub aa: bool,
+    #[serde(rename = "RD")]
+    pub rd: bool,
+    #[serde(rename = "RA")]
+    pub ra: bool,
+    pub answers: Option<Vec<String>>,
+    #[serde(rename = "TTLs")]
+    pub ttls: Option<Vec<f64>>,
+    pub rejected: bool,
+}
+
+impl DnsLog {
+    /// Builds the entry of a DNS transaction.
+    ///
+    /// ## Remarks
+    /// Query types and classes are not parsed, and are omitted.
+    pub fn new(uid: &ZeekUid, dns: &DnsTransaction) -> Self {
+        let rcode = dns.response_code();
+        let (mut answers, mut ttls) = (vec![], vec![]);
+        if let Some(response) = &dns.response {
+            for answer in response.answers.iter() {
+                let data = match &answer.data {
+                    Data::A(a) => a.0.to_string(),
+                    Data::Aaaa(aaaa) => aaaa.0.to_string(),
+                    Data::Cname(name) | Data::Ns(name) | Data::Ptr(name) => name.clone(),
+                    Data::Mx(mx) => mx.exchange.clone(),
+                    Data::Soa(soa) => soa.primary_ns.clone(),
+                    Data::Srv(srv) => srv.target.clone(),
+                    Data::Txt(txt) => format!("TXT {} {}", txt.len(), txt),
+                    Data::Unknown => continue,
+                };
+                answers.push(data);
+                ttls.push(answer.ttl as f64);
+            }
+        }
+        DnsLog {
+            ts: uid.ts(),
+            uid: uid.to_string(),
+            id: ZeekConnId::from(&uid.five_tuple),
+            proto: proto_name(uid.five_tuple.proto),
+            trans_id: dns.transaction_id,
+            query: opt(dns.query_domain()),
+            rcode,
+            rcode_name: rcode.map(rcode_name),
+            aa: dns.response.as_ref().is_some_and(|r| r.authoritative),
+            rd: dns.query.as_ref().is_some_and(|q| q.recursion_desired),
+            ra: dns.response.as_ref().is_some_and(|r| r.recursion_available),
+            rejected: rcode.is_some_and(|rcode| rcode != 0) && answers.is_empty(),
+            answers: (!answers.is_empty()).then_some(answers),
+            ttls: (!ttls.is_empty()).then_some(ttls),
+        }
+    }
+}
+
+impl ZeekLog for DnsLog {
+    const PATH: &'static str = "dns";
+    const FIELDS: &'static [(&'static str, &'static str)] = fields!(
+        &[("ts", "time"), ("uid", "string")],
+        &ID_FIELDS,
+        &[
+            ("proto", "enum"),
+            ("trans_id", "count"),
+            ("query", "string"),
+            ("rcode", "count"),
+            ("rcode_name", "string"),
+            ("AA", "bool"),
+            ("RD", "bool"),
+            ("RA", "bool"),
+            ("answers", "vector[string]"),
+            ("TTLs", "vector[interval]"),
+            ("rejected", "bool"),
+        ],
+    );
+}
+
+/// Zeek's name of a DNS response code.
+fn rcode_name(rcode: u8) -> &'static str {
+    match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        6 => "YXDOMAIN",
+        7 => "YXRRSET",
+        8 => "NXRRSET",
+        9 => "NOTAUTH",
+        10 => "NOTZONE",
+        _ => "unknown",
+    }
+}
+
+/* --------------------------------------------------------------------------------- */
+
+/// An `ssh.log` entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct SshLog {
+    pub ts: f64,
+    pub uid: String,
+    #[serde(flatten)]
+    pub id: ZeekConnId,
+    pub version: Option<u32>,
+    pub auth_success: Option<bool>,
+    pub auth_attempts: usize,
+    pub client: Option<String>,
+    pub server: Option<String>,
+    pub cipher_alg: Option<String>,
+    pub mac_alg: Option<String>,
+    pub compression_alg: Option<String>,
+    pub kex_alg: Option<String>,
+    pub host_key_alg: Option<String>,
+    pub host_key: Option<String>,
+    pub hassh: Option<String>,
+    #[serde(rename = "hasshServer")]
+    pub hassh_server: Option<String>,
+}
+
+impl SshLog {
+    /// Builds the entry of an SSH handshake.
+    ///
+    /// ## Remarks
+    /// The host key is identified by its OpenSSH SHA-256 fingerprint, and the outcome of user
+    /// authentication is inferred from the sizes of encrypted packets.
+    pub fn new(uid: &ZeekUid, ssh: &SshHandshake) -> Self {
+        let banner = |proto: &str, software: &str, comments: &str| match (proto, comments) {
+            ("", _) => None,
+            (_, "") => Some(format!("SSH-{}-{}", proto, software)),
+            _ => Some(format!("SSH-{}-{} {}", proto, software, comments)),
+        };
+        SshLog {
+            ts: uid.ts(),
+            uid: uid.to_string(),
+            id: ZeekConnId::from(&uid.five_tuple),
+            version: ssh
+                .protocol_version_stoc()
+                .split('.')
+                .next()
+                .and_then(|major| major.parse().ok()),
+            auth_success: match ssh.auth_result() {
+                AuthResult::Success => Some(true),
+                AuthResult::Failure => Some(false),
+                AuthResult::Unknown => None,
+            },
+            auth_attempts: ssh.auth_attempts(),
+            client: banner(
+                ssh.protocol_version_ctos(),
+                ssh.software_version_ctos(),
+                ssh.comments_ctos(),
+            ),
+            server: banner(
+                ssh.protocol_version_stoc(),
+                ssh.software_version_stoc(),
+                ssh.comments_stoc(),
+            ),
+            cipher_alg: opt(ssh.negotiated_encryption_alg_ctos()),
+            mac_alg: opt(ssh.negotiated_mac_alg_ctos()),
+            compression_alg: opt(ssh.negotiated_compression_alg_ctos()),
+            kex_alg: opt(ssh.negotiated_kex_alg()),
+            host_key_alg: opt(ssh.negotiated_host_key_alg()),
+            host_key: opt(ssh.host_key_fingerprint()),
+            hassh: ssh.client_key_exchange.as_ref().map(|_| ssh.hassh()),
+            hassh_server: ssh.server_key_exchange.as_ref().map(|_| ssh.hassh_server()),
+        }
+    }
+}
+
+impl ZeekLog for SshLog {
+    const PATH: &'static str = "ssh";
+    const FIELDS: &'static [(&'static str, &'static str)] = fields!(
+        &[("ts", "time"), ("uid", "string")],
+        &ID_FIELDS,
+        &[
+            ("version", "count"),
+            ("auth_success", "bool"),
+            ("auth_attempts", "count"),
+            ("client", "string"),
+            ("server", "string"),
+            ("cipher_alg", "string"),
+            ("mac_alg", "string"),
+            ("compression_alg", "string"),
+            ("kex_alg", "string"),
+            ("host_key_alg", "string"),
+            ("host_key", "string"),
+            ("hassh", "string"),
+            ("hasshServer", "string"),
+        ],
+    );
+}
+
+/// Fields of each built-in log, by name.
+pub(crate) const LOGS: [(&str, &[(&str, &str)]); 6] = [
+    (ConnLog::PATH, ConnLog::FIELDS),
+    (SslLog::PATH, SslLog::FIELDS),
+    (X509Log::PATH, X509Log::FIELDS),
+    (HttpLog::PATH, HttpLog::FIELDS),
+    (DnsLog::PATH, DnsLog::FIELDS),
+    (SshLog::PATH, SshLog::FIELDS),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_zeek_conn_state() {
+        assert_eq!(conn_state(b"ShADadFf", TCP_PROTOCOL), "SF");
+        assert_eq!(conn_state(b"ShADad", TCP_PROTOCOL), "S1");
+        assert_eq!(conn_state(b"ShADadR", TCP_PROTOCOL), "RSTO");
+        assert_eq!(conn_state(b"ShAF", TCP_PROTOCOL), "S2");
+        assert_eq!(conn_state(b"Sr", TCP_PROTOCOL), "REJ");
+        assert_eq!(conn_state(b"S", TCP_PROTOCOL), "S0");
+        assert_eq!(conn_state(b"hf", TCP_PROTOCOL), "SHR");
+        assert_eq!(conn_state(b"Dd", UDP_PROTOCOL), "SF");
+        assert_eq!(conn_state(b"D", UDP_PROTOCOL), "S0");
+    }
+
+    #[test]
+    fn core_zeek_fields() {
+        for (path, fields) in LOGS.iter() {
+            assert!(!fields.is_empty(), "{}", path);
+            assert_eq!(fields[0], ("ts", "time"));
+        }
+        assert_eq!(ConnLog::FIELDS.len(), 18);
+        assert_eq!(ConnLog::FIELDS[2], ("id.orig_h", "addr"));
+        assert_eq!(ConnLog::FIELDS[17], ("resp_ip_bytes", "count"));
+    }
+}
@@ -0,0 +1,183 @@
+//! Zeek-compatible logs.
+//!
+//! Builds `conn.log`, `ssl.log`, `x509.log`, `http.log`, `dns.log`, and `ssh.log` entries from the
+//! built-in datatypes, and writes them as Zeek TSV or JSON logs through the per-core output sinks
+//! in [iris_core::output]. Each core writes its own `{path}_{core}.log` files, which are merged
+//! into `{path}.log` on shutdown.
+//!
+//! Entries of the same connection share the `uid` of its [ZeekUid]. X509 entries are linked to
+//! `ssl.log` by the certificate fingerprints in `cert_chain_fps`, as in Zeek.
+//!
+//! ## Example
+//! ```rust,ignore
+//! #[callback("tls,level=L4Terminated")]
+//! fn tls_logs(uid: &ZeekUid, conn: &ConnRecord, tls: &TlsHandshake, core_id: &CoreId) {
+//!     zeek::emit(core_id, &ConnLog::new(uid, conn).with_service("ssl"));
+//!     zeek::emit(core_id, &SslLog::new(uid, tls));
+//!     for x509 in X509Log::from_tls(uid, tls) {
+//!         zeek::emit(core_id, &x509);
+//!     }
+//! }
+//!
+//! #[iris_main]
+//! fn main() {
+//!     zeek::register("./logs", ZeekFormat::Tsv).unwrap();
+//!     // ...
+//! }
+//! ```
+//!
+//! ## Remarks
+//! Timestamps are taken from the system clock when the first packet of a connection is processed,
+//! and do not reflect packet capture timestamps in offline analysis.
+
+mod logs;
+mod writer;
+
+pub use self::logs::*;
+
+use crate::StaticData;
+#[allow(unused_imports)]
+use iris_compiler::datatype;
+use iris_core::conntrack::conn_id::FiveTuple;
+use iris_core::conntrack::pdu::L4Pdu;
+use iris_core::lcore::CoreId;
+use iris_core::output;
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// Number of entries buffered per core before they are written.
+const BATCH_SIZE: usize = 1024;
+
+/// Zeek log format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZeekFormat {
+    /// Tab-separated values, with Zeek's `#`-prefixed headers.
+    Tsv,
+    /// One JSON object per line.
+    Json,
+}
+
+/// A Zeek log entry.
+///
+/// ## Remarks
+/// Only implemented by the built-in logs in this module, which are the only logs the Zeek sink
+/// knows the fields of.
+pub trait ZeekLog: Serialize {
+    /// Name of the log (e.g., `conn`).
+    const PATH: &'static str;
+    /// Zeek names and types of the log's fields, in order.
+    const FIELDS: &'static [(&'static str, &'static str)];
+}
+
+/// Writes Zeek logs in `format` to the `dir` directory.
+///
+/// Must be called before the runtime is created, instead of configuring `[output]`.
+pub fn register(dir: &str, format: ZeekFormat) -> Result<()> {
+    let factory_dir = dir.to_string();
+    let merge_dir = dir.to_string();
+    output::register_sink_with_merge(
+        BATCH_SIZE,
+        move |core_id| {
+            let sink = writer::ZeekSink::new(&factory_dir, core_id, format)?;
+            Ok(Box::new(sink) as Box<dyn output::Sink>)
+        },
+        move |files| writer::merge(&merge_dir, format, files),
+    )
+}
+
+/// Emits a log entry from the core `core_id`.
+pub fn emit<L: ZeekLog>(core_id: &CoreId, log: &L) {
+    output::emit(core_id, &Tagged { path: L::PATH, log });
+}
+
+/// A log entry tagged with the name of its log.
+#[derive(Serialize)]
+struct Tagged<'a, L> {
+    #[serde(rename = "_path")]
+    path: &'static str,
+    #[serde(flatten)]
+    log: &'a L,
+}
+
+/// Zeek connection identifier: a unique ID, the connection five-tuple, and the connection start
+/// time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZeekUid {
+    id: u128,
+    /// The connection 5-tuple.
+    pub five_tuple: FiveTuple,
+    /// Time the first packet was processed.
+    pub start: SystemTime,
+}
+
+impl ZeekUid {
+    /// Returns the start time in seconds since the Unix epoch.
+    pub fn ts(&self) -> f64 {
+        epoch_secs(self.start)
+    }
+}
+
+/// Formatted as `C` followed by the base62-encoded ID, as in Zeek.
+impl fmt::Display for ZeekUid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        let mut id = self.id;
+        let mut digits = vec![];
+        while id > 0 {
+            digits.push(BASE62[(id % 62) as usize]);
+            id /= 62;
+        }
+        digits.reverse();
+        write!(f, "C{}", String::from_utf8_lossy(&digits))
+    }
+}
+
+impl Serialize for ZeekUid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl StaticData for ZeekUid {
+    #[cfg_attr(
+        not(feature = "skip_expand"),
+        datatype("name=ZeekUid,level=L4FirstPacket")
+    )]
+    fn new(first_pkt: &L4Pdu) -> Self {
+        // Keyed per process, so that IDs are unique across runs but shared within a connection.
+        static KEY: OnceLock<RandomState> = OnceLock::new();
+        let five_tuple = FiveTuple::from_ctxt(first_pkt.ctxt);
+        let key = KEY.get_or_init(RandomState::new);
+        let hash = |salt: u8| {
+            let mut hasher = key.build_hasher();
+            salt.hash(&mut hasher);
+            five_tuple.hash(&mut hasher);
+            first_pkt.ts.hash(&mut hasher);
+            hasher.finish()
+        };
+        // 96 bits, as in Zeek.
+        let id = ((hash(0) as u128) << 32) | (hash(1) >> 32) as u128;
+        ZeekUid {
+            id,
+            five_tuple,
+            start: SystemTime::now(),
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub(crate) fn epoch_secs(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
@@ -0,0 +1,290 @@
+//! Per-core Zeek log files.
+
+use super::logs::LOGS;
+use super::ZeekFormat;
+use iris_core::lcore::CoreId;
+use iris_core::output::Sink;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+/// Value of unset fields.
+const UNSET: &str = "-";
+/// Value of empty strings and containers.
+const EMPTY: &str = "(empty)";
+
+/// Writes the entries emitted on a single core to `{dir}/{path}_{core}.log`, one file per log.
+pub(super) struct ZeekSink {
+    dir: PathBuf,
+    core_id: CoreId,
+    format: ZeekFormat,
+    /// Open log files, by log name.
+    logs: HashMap<String, LogFile>,
+}
+
+struct LogFile {
+    file: PathBuf,
+    writer: BufWriter<File>,
+    fields: &'static [(&'static str, &'static str)],
+}
+
+impl ZeekSink {
+    pub(super) fn new(dir: &str, core_id: CoreId, format: ZeekFormat) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(ZeekSink {
+            dir: PathBuf::from(dir),
+            core_id,
+            format,
+            logs: HashMap::new(),
+        })
+    }
+
+    fn open(&mut self, path: &str) -> Result<&mut LogFile> {
+        if !self.logs.contains_key(path) {
+            let fields = match LOGS.iter().find(|(name, _)| *name == path) {
+                Some((_, fields)) => *fields,
+                None => bail!("Unknown Zeek log {}", path),
+            };
+            let file = self
+                .dir
+                .join(format!("{}_{}.log", path, self.core_id.raw()));
+            let mut writer = BufWriter::new(File::create(&file)?);
+            if self.format == ZeekFormat::Tsv {
+                writer.write_all(tsv_header(path, fields).as_bytes())?;
+            }
+            let log = LogFile {
+                file,
+                writer,
+                fields,
+            };
+            self.logs.insert(path.to_string(), log);
+        }
+        Ok(self.logs.get_mut(path).unwrap())
+    }
+}
+
+impl Sink for ZeekSink {
+    fn write_batch(&mut self, records: &[Value]) -> Result<()> {
+        for record in records {
+            let mut record = match record {
+                Value::Object(record) => record.clone(),
+                _ => bail!("Zeek log entries must be objects"),
+            };
+            let path = match record.remove("_path") {
+                Some(Value::String(path)) => path,
+                _ => bail!("Zeek log entry without a path"),
+            };
+            let format = self.format;
+            let log = self.open(&path)?;
+            let line = match format {
+                ZeekFormat::Tsv => log
+                    .fields
+                    .iter()
+                    .map(|(name, ty)| tsv_field(record.get(*name), ty))
+                    .collect::<Vec<_>>()
+                    .join("\t"),
+                ZeekFormat::Json => {
+                    record.retain(|_, value| !value.is_null());
+                    serde_json::to_string(&record)?
+                }
+            };
+            log.writer.write_all(line.as_bytes())?;
+            log.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        for (_, mut log) in self.logs.drain() {
+            log.writer.flush()?;
+            files.push(log.file);
+        }
+        Ok(files)
+    }
+}
+
+fn timestamp() -> String {
+    chrono::Local::now().format("%Y-%m-%d-%H-%M-%S").to_string()
+}
+
+fn tsv_header(path: &str, fields: &[(&str, &str)]) -> String {
+    let names: Vec<_> = fields.iter().map(|(name, _)| *name).collect();
+    let types: Vec<_> = fields.iter().map(|(_, ty)| *ty).collect();
+    format!(
+        "#separator \\x09\n#set_separator\t,\n#empty_field\t{}\n#unset_field\t{}\n#path\t{}\n#open\t{}\n#fields\t{}\n#types\t{}\n",
+        EMPTY,
+        UNSET,
+        path,
+        timestamp(),
+        names.join("\t"),
+        types.join("\t"),
+    )
+}
+
+/// Formats a field of Zeek type `ty` as TSV.
+pub(super) fn tsv_field(value: Option<&Value>, ty: &str) -> String {
+    match value {
+        None | Some(Value::Null) => UNSET.to_string(),
+        Some(Value::Array(values)) if values.is_empty() => EMPTY.to_string(),
+        Some(Value::Array(values)) => {
+            // Element type of `set[...]` and `vector[...]`.
+            let ty = match (ty.find('['), ty.strip_suffix(']')) {
+                (Some(start), Some(ty)) => &ty[start + 1..],
+                _ => ty,
+            };
+            values
+                .iter()
+                .map(|value| escape(&tsv_value(value, ty), true))
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+        Some(value) => match tsv_value(value, ty) {
+            s if s.is_empty() => EMPTY.to_string(),
+            s if s == UNSET => "\\x2d".to_string(),
+            s => escape(&s, false),
+        },
+    }
+}
+
+fn tsv_value(value: &Value, ty: &str) -> String {
+    match value {
+        Value::Bool(true) => "T".to_string(),
+        Value::Bool(false) => "F".to_string(),
+        Value::Number(n) if matches!(ty, "time" | "interval" | "double") => {
+            format!("{:.6}", n.as_f64().unwrap_or_default())
+        }
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Escapes separators and non-printable characters as `\xHH`.
+fn escape(s: &str, in_set: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ',' if in_set => escaped.push_str("\\x2c"),
+            c if c.is_control() => {
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    escaped.push_str(&format!("\\x{:02x}", byte));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Concatenates the per-core files of each log into `{dir}/{path}.log` and removes them. TSV
+/// headers are kept from the first file of each log.
+pub(super) fn merge(dir: &str, format: ZeekFormat, files: &[PathBuf]) -> Result<()> {
+    let mut logs: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+    for file in files {
+        let path = file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.rsplit_once('_'))
+            .map(|(path, _core)| path.to_string());
+        match path {
+            Some(path) => logs.entry(path).or_default().push(file),
+            None => bail!("Unexpected Zeek log file {:?}", file),
+        }
+    }
+    for (path, files) in logs {
+        let merged = Path::new(dir).join(format!("{}.log", path));
+        let mut writer = BufWriter::new(File::create(&merged)?);
+        for (i, file) in files.iter().enumerate() {
+            for line in BufReader::new(File::open(file)?).lines() {
+                let line = line?;
+                if line.starts_with('#') && i > 0 {
+                    continue;
+                }
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        if format == ZeekFormat::Tsv {
+            writeln!(writer, "#close\t{}", timestamp())?;
+        }
+        writer.flush()?;
+        for file in files {
+            fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn core_zeek_tsv_field() {
+        assert_eq!(tsv_field(None, "string"), "-");
+        assert_eq!(tsv_field(Some(&json!(null)), "count"), "-");
+        assert_eq!(tsv_field(Some(&json!("")), "string"), "(empty)");
+        assert_eq!(tsv_field(Some(&json!("-")), "string"), "\\x2d");
+        assert_eq!(tsv_field(Some(&json!("a\tb")), "string"), "a\\x09b");
+        assert_eq!(tsv_field(Some(&json!(true)), "bool"), "T");
+        assert_eq!(tsv_field(Some(&json!(443)), "port"), "443");
+        assert_eq!(tsv_field(Some(&json!(1.5)), "time"), "1.500000");
+        assert_eq!(tsv_field(Some(&json!(2)), "interval"), "2.000000");
+        assert_eq!(tsv_field(Some(&json!([])), "vector[string]"), "(empty)");
+        assert_eq!(
+            tsv_field(Some(&json!(["a,b", "c"])), "vector[string]"),
+            "a\\x2cb,c"
+        );
+        assert_eq!(
+            tsv_field(Some(&json!([30, 60])), "vector[interval]"),
+            "30.000000,60.000000"
+        );
+    }
+
+    #[test]
+    fn core_zeek_sink_merge() {
+        let dir = std::env::temp_dir().join(format!("iris_zeek_{}", std::process::id()));
+        let dir_str = dir.to_str().unwrap();
+        let mut files = vec![];
+        for core in 0..2 {
+            let mut sink = ZeekSink::new(dir_str, CoreId(core), ZeekFormat::Tsv).unwrap();
+            let entry = json!({
+                "_path": "dns",
+                "ts": 1.0,
+                "uid": format!("C{}", core),
+                "query": "example.com",
+                "answers": ["1.2.3.4"],
+                "AA": false,
+            });
+            sink.write_batch(&[entry]).unwrap();
+            assert!(sink.write_batch(&[json!({ "_path": "foo" })]).is_err());
+            files.extend(sink.finish().unwrap());
+        }
+        merge(dir_str, ZeekFormat::Tsv, &files).unwrap();
+
+        let merged = fs::read_to_string(dir.join("dns.log")).unwrap();
+        let lines: Vec<&str> = merged.lines().collect();
+        assert_eq!(lines[0], "#separator \\x09");
+        assert_eq!(lines[4], "#path\tdns");
+        assert!(lines[6].starts_with("#fields\tts\tuid\tid.orig_h"));
+        let rows: Vec<&str> = lines
+            .iter()
+            .filter(|l| !l.starts_with('#'))
+            .cloned()
+            .collect();
+        assert_eq!(rows.len(), 2);
+        let row: Vec<&str> = rows[1].split('\t').collect();
+        assert_eq!(row.len(), lines[6].split('\t').count() - 1);
+        assert_eq!(&row[..3], &["1.000000", "C1", "-"]);
+        assert!(lines.last().unwrap().starts_with("#close\t"));
+        assert!(files.iter().all(|file| !file.exists()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -105,7 +105,7 @@ pub fn write_stats() -> std::io::Result<()> {
     Ok(())
 }
 
-use iris_compiler::filter;
+use iris_compiler::{filter, filter_def};
 use iris_core::protocols::stream::{Session, SessionData};
 use iris_core::subscription::FilterResult;
 
@@ -269,7 +269,8 @@ pub fn uris() -> &'static Mutex<IndexSet<String>> {
     URIS.get_or_init(|| Mutex::new(IndexSet::new()))
 }
 
-#[callback("ipv4 and tls and drop_high_vol_sess and drop_high_vol_conn")]
+#[filter_def(name = "low_vol", "drop_high_vol_sess and drop_high_vol_conn")]
+#[callback("ipv4 and tls and low_vol")]
 pub fn get_tls(tls: &TlsHandshake, five_tuple: &FiveTuple, core: &CoreId) {
     // let tls_str = format_tls(&*tls, false);
     if tls.sni().is_empty() {
@@ -279,7 +280,7 @@ pub fn get_tls(tls: &TlsHandshake, five_tuple: &FiveTuple, core: &CoreId) {
     format_record(five_tuple, tls_str, core);
 }
 
-#[callback("ipv4 and quic and drop_high_vol_sess and drop_high_vol_conn")]
+#[callback("ipv4 and quic and low_vol")]
 pub fn get_quic(quic: &QuicStream, five_tuple: &FiveTuple, core: &CoreId) {
     // let quic_str = format_tls(&quic.tls, true);
     if quic.tls.sni().is_empty() {
@@ -289,7 +290,7 @@ pub fn get_quic(quic: &QuicStream, five_tuple: &FiveTuple, core: &CoreId) {
     format_record(five_tuple, quic_str, core);
 }
 
-#[callback("ipv4 and http and drop_high_vol_sess and drop_high_vol_conn")]
+#[callback("ipv4 and http and low_vol")]
 pub fn get_http(http: &HttpTransaction, five_tuple: &FiveTuple, core: &CoreId) {
     if let IpAddr::V4(v4) = five_tuple.orig.ip() {
         let user_agent = http.user_agent();
@@ -334,7 +335,7 @@ pub fn get_http(http: &HttpTransaction, five_tuple: &FiveTuple, core: &CoreId) {
 }
 
 // Note - looks like they didn't really use DNS for fingerprints?
-#[callback("dns and drop_high_vol_sess and drop_high_vol_conn")]
+#[callback("dns and low_vol")]
 pub fn get_dns(dns: &DnsTransaction, five_tuple: &FiveTuple, core: &CoreId) {
     if dns.query.is_none()
         || dns.response.is_none()